@@ -1,3 +1,19 @@
 fn main() {
+    // Record the resolved Tectonic version so diagnostics_info can report it
+    let lock = std::fs::read_to_string("Cargo.lock").unwrap_or_default();
+    let tectonic_version = lock
+        .split("[[package]]")
+        .find_map(|package| {
+            let mut lines = package.lines().map(str::trim).filter(|l| !l.is_empty());
+            if lines.next()? != "name = \"tectonic\"" {
+                return None;
+            }
+            let version = lines.next()?.strip_prefix("version = ")?;
+            Some(version.trim_matches('"').to_string())
+        })
+        .unwrap_or_else(|| "unknown".to_string());
+    println!("cargo:rustc-env=TECTONIC_VERSION={}", tectonic_version);
+    println!("cargo:rerun-if-changed=Cargo.lock");
+
     tauri_build::build()
 }
@@ -0,0 +1,296 @@
+//! A tolerant reader for BibTeX databases.
+//!
+//! Entries are read the way BibTeX itself reads them: text outside an `@type{...}`
+//! block is a comment, `@comment` and `@preamble` blocks are skipped, and field values
+//! may be braced, quoted, bare numbers or `@string` macro names joined with `#`. A
+//! malformed entry is reported and reading resumes at the next `@`, so one typo
+//! doesn't hide the rest of the file.
+
+use std::ops::Range;
+
+use crate::latex::LineIndex;
+
+#[derive(Debug, Clone)]
+pub struct Field {
+    /// Lowercased, as BibTeX field names are case-insensitive
+    pub name: String,
+    pub value: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct Entry {
+    /// Lowercased entry type, e.g. `article`
+    pub kind: String,
+    pub key: String,
+    pub fields: Vec<Field>,
+    pub line: usize,
+    /// Byte range of the whole entry as written, from its `@` to its closing delimiter
+    pub span: Range<usize>,
+}
+
+impl Entry {
+    pub fn field(&self, name: &str) -> Option<&Field> {
+        self.fields.iter().find(|f| f.name == name)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ParseErrorKind {
+    /// A `{` or `"` with no matching close before the end of the file
+    Unbalanced,
+    /// Anything else BibTeX would reject, e.g. a field without `=`
+    Syntax,
+}
+
+#[derive(Debug, Clone)]
+pub struct ParseError {
+    pub kind: ParseErrorKind,
+    pub message: String,
+    pub line: usize,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Bibliography {
+    pub entries: Vec<Entry>,
+    /// Byte ranges of the @string and @preamble blocks, which entries may rely on
+    pub definitions: Vec<Range<usize>>,
+    pub errors: Vec<ParseError>,
+}
+
+struct Parser<'a> {
+    content: &'a str,
+    bytes: &'a [u8],
+    pos: usize,
+    lines: LineIndex,
+}
+
+impl Parser<'_> {
+    fn line(&self, offset: usize) -> usize {
+        self.lines.line(offset)
+    }
+
+    fn skip_whitespace(&mut self) {
+        while self
+            .bytes
+            .get(self.pos)
+            .is_some_and(u8::is_ascii_whitespace)
+        {
+            self.pos += 1;
+        }
+    }
+
+    fn identifier(&mut self) -> &str {
+        let start = self.pos;
+        while self
+            .bytes
+            .get(self.pos)
+            .is_some_and(|b| !b.is_ascii_whitespace() && !b"{}()=,#\"@".contains(b))
+        {
+            self.pos += 1;
+        }
+        &self.content[start..self.pos]
+    }
+
+    /// Skip a group opened at `self.pos`, returning its inner text, or None if it isn't
+    /// closed before the end of the file or the next line starting with `@`, which is
+    /// where a missing `}` shows. Only braces nest; a `(` group ends at the first
+    /// unbraced `)`.
+    fn group(&mut self, close: u8) -> Option<&str> {
+        let start = self.pos + 1;
+        let mut depth = 0usize;
+        let mut i = start;
+        while i < self.bytes.len() {
+            match self.bytes[i] {
+                b'{' => depth += 1,
+                b'}' if depth > 0 => depth -= 1,
+                b if b == close && depth == 0 => {
+                    self.pos = i + 1;
+                    return Some(&self.content[start..i]);
+                }
+                b'@' if self.bytes[i - 1] == b'\n' => return None,
+                _ => {}
+            }
+            i += 1;
+        }
+        None
+    }
+
+    /// One field value: its parts joined by `#`, with delimiters removed
+    fn value(&mut self, entry_close: u8) -> Result<String, ParseError> {
+        let mut value = String::new();
+        loop {
+            self.skip_whitespace();
+            let start = self.pos;
+            match self.bytes.get(self.pos) {
+                Some(b'{') => match self.group(b'}') {
+                    Some(text) => value.push_str(text),
+                    None => {
+                        return Err(self.error(
+                            ParseErrorKind::Unbalanced,
+                            start,
+                            "Unclosed { in field value",
+                        ))
+                    }
+                },
+                Some(b'"') => match self.group(b'"') {
+                    Some(text) => value.push_str(text),
+                    None => {
+                        return Err(self.error(
+                            ParseErrorKind::Unbalanced,
+                            start,
+                            "Unclosed \" in field value",
+                        ))
+                    }
+                },
+                Some(b) if *b != entry_close && *b != b',' => {
+                    let part = self.identifier().to_string();
+                    if part.is_empty() {
+                        return Err(self.error(
+                            ParseErrorKind::Syntax,
+                            start,
+                            "Expected a field value",
+                        ));
+                    }
+                    value.push_str(&part);
+                }
+                _ => {
+                    return Err(self.error(ParseErrorKind::Syntax, start, "Expected a field value"))
+                }
+            }
+
+            self.skip_whitespace();
+            if self.bytes.get(self.pos) != Some(&b'#') {
+                return Ok(value);
+            }
+            self.pos += 1;
+        }
+    }
+
+    fn error(&self, kind: ParseErrorKind, offset: usize, message: &str) -> ParseError {
+        ParseError {
+            kind,
+            message: message.to_string(),
+            line: self.line(offset),
+        }
+    }
+
+    /// The body of a regular entry, after its opening delimiter
+    fn entry(&mut self, kind: String, start: usize, close: u8) -> Result<Entry, ParseError> {
+        self.skip_whitespace();
+        let key = self.identifier().to_string();
+        let mut entry = Entry {
+            kind,
+            key,
+            fields: Vec::new(),
+            line: self.line(start),
+            span: start..start,
+        };
+
+        loop {
+            self.skip_whitespace();
+            match self.bytes.get(self.pos) {
+                Some(b) if *b == close => {
+                    self.pos += 1;
+                    entry.span.end = self.pos;
+                    return Ok(entry);
+                }
+                Some(b',') => {
+                    self.pos += 1;
+                    continue;
+                }
+                None | Some(b'@') => {
+                    return Err(self.error(
+                        ParseErrorKind::Unbalanced,
+                        start,
+                        "Entry is never closed",
+                    ))
+                }
+                _ => {}
+            }
+
+            let field_start = self.pos;
+            let name = self.identifier().to_ascii_lowercase();
+            self.skip_whitespace();
+            if name.is_empty() || self.bytes.get(self.pos) != Some(&b'=') {
+                return Err(self.error(
+                    ParseErrorKind::Syntax,
+                    field_start,
+                    "Expected `name = value`",
+                ));
+            }
+            self.pos += 1;
+            let value = self.value(close)?;
+            entry.fields.push(Field { name, value });
+        }
+    }
+}
+
+/// Where to resume after a malformed entry starting at `start`: the next line that
+/// starts with `@`, rather than anywhere inside the broken one
+fn next_entry(content: &str, start: usize) -> usize {
+    content[start + 1..]
+        .find("\n@")
+        .map_or(content.len(), |i| start + 1 + i + 1)
+}
+
+/// Read every entry of a .bib file, with the problems that stopped any from being read
+pub fn parse(content: &str) -> Bibliography {
+    let mut parser = Parser {
+        content,
+        bytes: content.as_bytes(),
+        pos: 0,
+        lines: LineIndex::new(content),
+    };
+    let mut bibliography = Bibliography::default();
+
+    while let Some(at) = content[parser.pos..].find('@') {
+        let start = parser.pos + at;
+        parser.pos = start + 1;
+        let kind = parser.identifier().to_ascii_lowercase();
+        parser.skip_whitespace();
+
+        let close = match parser.bytes.get(parser.pos) {
+            Some(b'{') => b'}',
+            Some(b'(') => b')',
+            // An @ in comment text, such as an email address
+            _ => continue,
+        };
+        if kind == "comment" || kind == "preamble" || kind == "string" {
+            if parser.group(close).is_none() {
+                let message = format!("Unclosed @{} block", kind);
+                bibliography
+                    .errors
+                    .push(parser.error(ParseErrorKind::Unbalanced, start, &message));
+                parser.pos = next_entry(content, start);
+            } else if kind != "comment" {
+                bibliography.definitions.push(start..parser.pos);
+            }
+            continue;
+        }
+
+        parser.pos += 1;
+        match parser.entry(kind, start, close) {
+            Ok(entry) => {
+                bibliography.entries.push(entry);
+                // BibTeX ignores text between entries, so an extra } closes the entry
+                // early and drops the fields after it without a word
+                let end = parser.pos;
+                let gap =
+                    &content[end..content[end..].find('@').map_or(content.len(), |i| end + i)];
+                if let Some(i) = gap.find('}') {
+                    bibliography.errors.push(parser.error(
+                        ParseErrorKind::Unbalanced,
+                        end + i,
+                        "Unmatched } after the end of an entry",
+                    ));
+                }
+            }
+            Err(error) => {
+                bibliography.errors.push(error);
+                parser.pos = next_entry(content, start);
+            }
+        }
+    }
+
+    bibliography
+}
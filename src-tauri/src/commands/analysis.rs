@@ -0,0 +1,5094 @@
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use super::project::{
+    build_dir, collect_files, read_project_meta, write_project_file, PathPatterns, SymlinkPolicy,
+};
+use super::settings::{get_config_dir, read_user_words};
+use crate::bibtex::{self, ParseErrorKind};
+use crate::diff;
+use crate::latex::{self, ArgKind, LineIndex};
+use crate::prose;
+use crate::spelling::Dictionary;
+use crate::style;
+use crate::tex_log::{Diagnostic, Severity};
+
+/// Commands whose argument is a comma-separated list of label names
+const REFERENCE_COMMANDS: &[&str] = &[
+    "ref",
+    "eqref",
+    "pageref",
+    "autoref",
+    "nameref",
+    "vref",
+    "cref",
+    "Cref",
+    "cpageref",
+    "labelcref",
+];
+
+/// Float environments and whether each holds a figure (otherwise a table)
+const FLOAT_ENVIRONMENTS: &[(&str, bool)] = &[
+    ("figure", true),
+    ("figure*", true),
+    ("wrapfigure", true),
+    ("sidewaysfigure", true),
+    ("table", false),
+    ("table*", false),
+    ("wraptable", false),
+    ("sidewaystable", false),
+];
+
+/// Display math environments, without their starred forms, and whether each numbers
+/// every row (otherwise the environment gets one number)
+const EQUATION_ENVIRONMENTS: &[(&str, bool)] = &[
+    ("equation", false),
+    ("multline", false),
+    ("align", true),
+    ("flalign", true),
+    ("alignat", true),
+    ("xalignat", true),
+    ("gather", true),
+    ("eqnarray", true),
+    ("displaymath", false),
+];
+
+/// Extensions \includegraphics tries, in order, for a name given without one
+pub(crate) const GRAPHICS_EXTENSIONS: &[&str] = &["pdf", "png", "jpg", "jpeg", "eps"];
+
+/// Commands that define an acronym for the glossaries, acronym and acro packages. The
+/// first required argument is the key the document uses it by.
+const ACRONYM_DEFINITIONS: &[&str] = &["newacronym", "acro", "acrodef", "DeclareAcronym"];
+
+/// Commands that typeset or mark as used an acronym defined for a glossary package
+const ACRONYM_USES: &[&str] = &[
+    "gls",
+    "Gls",
+    "GLS",
+    "glspl",
+    "Glspl",
+    "GLSpl",
+    "acrshort",
+    "Acrshort",
+    "acrlong",
+    "Acrlong",
+    "acrfull",
+    "Acrfull",
+    "glsentryshort",
+    "glsentrylong",
+    "ac",
+    "Ac",
+    "acs",
+    "acl",
+    "Acl",
+    "acf",
+    "Acf",
+    "acp",
+    "Acp",
+    "acsp",
+    "aclp",
+    "Aclp",
+    "acfp",
+    "Acfp",
+    "acused",
+    "acuse",
+];
+
+/// Defines a glossary entry other than an acronym, which \gls takes as well
+const GLOSSARY_ENTRY: &str = "newglossaryentry";
+
+/// Commands that read a file of glossary definitions, followed like an include
+const GLOSSARY_INPUTS: &[&str] = &["loadglsentries"];
+
+/// Typical silent reading speed; a talk runs closer to 130
+const WORDS_PER_MINUTE: u32 = 200;
+
+/// Where installed hunspell dictionaries are looked for after the app's own
+/// `dictionaries` directory
+const SYSTEM_DICTIONARY_DIRS: &[&str] = &[
+    "/usr/share/hunspell",
+    "/usr/share/myspell",
+    "/usr/share/myspell/dicts",
+    "/Library/Spelling",
+];
+
+/// Dictionaries loaded so far, by language
+static DICTIONARIES: Mutex<Vec<(String, Arc<Dictionary>)>> = Mutex::new(Vec::new());
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DocumentClass {
+    pub name: String,
+    /// Raw text of the `[...]` option list, exactly as written
+    pub options: Option<String>,
+    pub file: String,
+    pub line: usize,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PackageInfo {
+    pub name: String,
+    /// Raw text of the `[...]` option list, shared by every package in the same \usepackage
+    pub options: Option<String>,
+    pub file: String,
+    pub line: usize,
+}
+
+/// Program that turns a document's .bib files into its bibliography
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BibEngine {
+    Biber,
+    Bibtex,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct PreambleInfo {
+    pub document_class: Option<DocumentClass>,
+    pub packages: Vec<PackageInfo>,
+    /// None when the document has no .bib-based bibliography
+    pub detected_bib_engine: Option<BibEngine>,
+    /// \graphicspath directories, in the order graphicx tries them after the project root
+    pub graphics_paths: Vec<String>,
+}
+
+/// Packages that don't work together, with what to do about it
+const CONFLICTING_PACKAGES: &[(&str, &str, &str)] = &[
+    (
+        "subfigure",
+        "subcaption",
+        "subfigure is obsolete and breaks subcaption; use subcaption alone",
+    ),
+    (
+        "subfig",
+        "subcaption",
+        "both define \\subfloat-style subfigures; use subcaption alone",
+    ),
+    (
+        "subfigure",
+        "subfig",
+        "subfig replaces subfigure; load only subfig",
+    ),
+    (
+        "natbib",
+        "biblatex",
+        "biblatex replaces natbib; its natbib option provides \\citet and \\citep",
+    ),
+    (
+        "cite",
+        "biblatex",
+        "biblatex formats citations itself; drop cite",
+    ),
+    ("cite", "natbib", "both redefine \\cite; load only natbib"),
+    (
+        "algorithmic",
+        "algpseudocode",
+        "both define the algorithmic environment; load only algpseudocode",
+    ),
+    (
+        "algorithm2e",
+        "algorithm",
+        "algorithm2e defines its own algorithm environment; use one",
+    ),
+    (
+        "algorithm2e",
+        "algpseudocode",
+        "both define \\If, \\For and the like; use one",
+    ),
+    (
+        "enumerate",
+        "enumitem",
+        "enumitem replaces enumerate's labels; load only enumitem",
+    ),
+    (
+        "float",
+        "floatrow",
+        "floatrow redefines what float does; load only floatrow",
+    ),
+    (
+        "amsthm",
+        "ntheorem",
+        "both define \\newtheorem and \\proof; use one",
+    ),
+    (
+        "titlesec",
+        "sectsty",
+        "both redefine the sectioning commands; use one",
+    ),
+    (
+        "fontspec",
+        "inputenc",
+        "fontspec reads UTF-8 itself; inputenc does nothing under XeTeX but warn",
+    ),
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PackageIssueKind {
+    /// Loaded again with no options the first load didn't have, which LaTeX ignores
+    Duplicate,
+    /// Loaded again with options the first load didn't have, which LaTeX stops at with
+    /// an "Option clash" error
+    OptionClash,
+    /// Two packages known not to work together
+    Conflict,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PackageIssue {
+    pub kind: PackageIssueKind,
+    /// The package loaded twice, or both packages of a conflict
+    pub packages: Vec<String>,
+    pub message: String,
+    /// Each load involved, in the order LaTeX reads them, with its options as written
+    pub loads: Vec<PackageInfo>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WordCount {
+    pub words: usize,
+    /// Non-whitespace characters of the prose
+    pub characters: usize,
+    /// The same counts for each file of the document, each without its includes
+    pub files: Vec<FileWordCount>,
+}
+
+/// How a file's prose has changed since its writing session started
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SessionDelta {
+    pub words: usize,
+    /// Non-whitespace characters of the prose
+    pub characters: usize,
+    /// Words written and deleted this session, found by comparing the words in order,
+    /// so rewording a sentence counts on both sides
+    pub words_added: usize,
+    pub words_removed: usize,
+    /// Change in `characters`, negative when the prose got shorter
+    pub characters_delta: i64,
+    /// When the session started, RFC 3339
+    pub since: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FileWordCount {
+    pub file: String,
+    pub words: usize,
+    pub characters: usize,
+    /// The file's word_target from the project settings
+    pub target: Option<usize>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SpellIssue {
+    pub word: String,
+    pub line: usize,
+    /// 1-based, in characters
+    pub column: usize,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Readability {
+    pub words: usize,
+    pub sentences: usize,
+    pub reading_time_minutes: f64,
+    /// Words per sentence
+    pub average_sentence_length: f64,
+    pub average_syllables_per_word: f64,
+    /// Flesch reading ease: 60-70 is plain English, academic prose is often below 30
+    pub flesch_reading_ease: Option<f64>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LabelInfo {
+    pub name: String,
+    pub file: String,
+    pub line: usize,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FloatKind {
+    Figure,
+    Table,
+}
+
+/// How a document's display equations are numbered
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct EquationStats {
+    /// Equation numbers the document produces: one per numbered environment, or per
+    /// numbered row of align and the like, counting \tag
+    pub numbered: usize,
+    /// Display equations and rows without a number: starred environments, \[...\],
+    /// $$...$$ and rows marked \nonumber or \notag
+    pub unnumbered: usize,
+    /// How often each display math environment is used, starred forms separately
+    pub environments: BTreeMap<String, usize>,
+    /// \labels inside display math, in the order TeX reads them
+    pub labels: Vec<LabelInfo>,
+}
+
+/// A figure or table environment
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FloatInfo {
+    pub kind: FloatKind,
+    pub environment: String,
+    /// Caption source with line breaks and runs of spaces collapsed
+    pub caption: Option<String>,
+    pub label: Option<String>,
+    /// \includegraphics paths, as written
+    pub graphics: Vec<String>,
+    pub file: String,
+    /// Line of the \begin
+    pub line: usize,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct LabelIssues {
+    /// Labels no \ref-style command points at
+    pub unused: Vec<LabelInfo>,
+    /// Every definition of a label that is defined more than once
+    pub duplicates: Vec<LabelInfo>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "kind")]
+pub enum IncludeIssue {
+    /// An include whose target doesn't exist in the project
+    MissingInclude {
+        file: String,
+        line: usize,
+        target: String,
+    },
+    /// Files that include each other, in include order; the first file is repeated at the end
+    CircularInclude { cycle: Vec<String> },
+    /// An \includegraphics target found under none of the graphics search paths
+    MissingGraphic {
+        file: String,
+        line: usize,
+        target: String,
+    },
+    /// An \includegraphics target that only exists with different letter case. It
+    /// builds on case-insensitive filesystems (macOS, Windows) but not on Linux.
+    GraphicCaseMismatch {
+        file: String,
+        line: usize,
+        target: String,
+        /// Project-relative path of the file on disk
+        actual: String,
+    },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AcronymIssueKind {
+    /// An all-caps word used before its full form is given, as in "graphics processing
+    /// unit (GPU)" or "GPU (graphics processing unit)"
+    Unexpanded,
+    /// Defined for a glossary package but never used
+    Unused,
+    /// Used through a glossary package command but never defined
+    Undefined,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AcronymIssue {
+    /// The acronym, or the key a glossary package knows it by
+    pub acronym: String,
+    pub kind: AcronymIssueKind,
+    pub file: String,
+    pub line: usize,
+}
+
+/// Commands that define the macro named right after them, as `{\name}` or `\name`
+const MACRO_DEFINITIONS: &[&str] = &[
+    "newcommand",
+    "renewcommand",
+    "providecommand",
+    "DeclareRobustCommand",
+    "NewDocumentCommand",
+    "RenewDocumentCommand",
+    "ProvideDocumentCommand",
+    "DeclareDocumentCommand",
+    "NewExpandableDocumentCommand",
+    "DeclareMathOperator",
+    "def",
+    "gdef",
+    "edef",
+    "xdef",
+    "let",
+];
+
+/// Commands that define the environment named in their first argument
+const ENVIRONMENT_DEFINITIONS: &[&str] = &[
+    "newenvironment",
+    "renewenvironment",
+    "provideenvironment",
+    "NewDocumentEnvironment",
+    "RenewDocumentEnvironment",
+    "ProvideDocumentEnvironment",
+    "DeclareDocumentEnvironment",
+    "newtheorem",
+];
+
+/// Where DOIs resolve
+const DOI_RESOLVER: &str = "https://doi.org/";
+
+/// How long a link check waits for a server before counting the link unreachable
+const LINK_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Links checked at the same time
+const MAX_LINK_CHECKS: usize = 8;
+
+/// babel and polyglossia language names with the language tag each stands for
+const LANGUAGE_TAGS: &[(&str, &str)] = &[
+    ("english", "en"),
+    ("american", "en-US"),
+    ("usenglish", "en-US"),
+    ("british", "en-GB"),
+    ("ukenglish", "en-GB"),
+    ("canadian", "en-CA"),
+    ("australian", "en-AU"),
+    ("newzealand", "en-NZ"),
+    ("german", "de"),
+    ("ngerman", "de"),
+    ("austrian", "de-AT"),
+    ("naustrian", "de-AT"),
+    ("swissgerman", "de-CH"),
+    ("nswissgerman", "de-CH"),
+    ("french", "fr"),
+    ("francais", "fr"),
+    ("acadian", "fr-CA"),
+    ("canadien", "fr-CA"),
+    ("spanish", "es"),
+    ("italian", "it"),
+    ("portuguese", "pt"),
+    ("portuges", "pt"),
+    ("brazilian", "pt-BR"),
+    ("brazil", "pt-BR"),
+    ("dutch", "nl"),
+    ("catalan", "ca"),
+    ("galician", "gl"),
+    ("basque", "eu"),
+    ("danish", "da"),
+    ("swedish", "sv"),
+    ("norsk", "nb"),
+    ("norwegian", "nb"),
+    ("nynorsk", "nn"),
+    ("finnish", "fi"),
+    ("icelandic", "is"),
+    ("polish", "pl"),
+    ("czech", "cs"),
+    ("slovak", "sk"),
+    ("slovene", "sl"),
+    ("croatian", "hr"),
+    ("serbian", "sr"),
+    ("bulgarian", "bg"),
+    ("russian", "ru"),
+    ("ukrainian", "uk"),
+    ("hungarian", "hu"),
+    ("magyar", "hu"),
+    ("romanian", "ro"),
+    ("greek", "el"),
+    ("turkish", "tr"),
+    ("estonian", "et"),
+    ("latvian", "lv"),
+    ("lithuanian", "lt"),
+    ("irish", "ga"),
+    ("welsh", "cy"),
+    ("latin", "la"),
+    ("hebrew", "he"),
+    ("arabic", "ar"),
+    ("indonesian", "id"),
+];
+
+/// Very common words of the languages detect_language can recognize from the text alone
+const STOP_WORDS: &[(&str, &[&str])] = &[
+    (
+        "en",
+        &[
+            "the", "and", "of", "to", "is", "that", "with", "for", "this", "are",
+        ],
+    ),
+    (
+        "de",
+        &[
+            "der", "die", "und", "das", "ist", "nicht", "mit", "sich", "auf", "ein",
+        ],
+    ),
+    (
+        "fr",
+        &[
+            "le", "les", "et", "des", "est", "une", "dans", "que", "pour", "du",
+        ],
+    ),
+    (
+        "es",
+        &[
+            "el", "los", "y", "las", "es", "una", "del", "que", "por", "con",
+        ],
+    ),
+    (
+        "it",
+        &[
+            "il", "che", "di", "e", "della", "per", "una", "sono", "gli", "non",
+        ],
+    ),
+    (
+        "pt",
+        &[
+            "o", "os", "e", "das", "uma", "do", "que", "para", "com", "não",
+        ],
+    ),
+    (
+        "nl",
+        &[
+            "de", "het", "een", "en", "van", "is", "dat", "niet", "met", "zijn",
+        ],
+    ),
+];
+
+/// Words of body text detect_language reads at most
+const LANGUAGE_SAMPLE_WORDS: usize = 2000;
+
+/// Stop word hits the most frequent language needs before the text counts as written in it
+const MIN_STOP_WORD_HITS: usize = 10;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SymbolKind {
+    Section,
+    Label,
+    Command,
+    Environment,
+    Caption,
+}
+
+/// Something to jump to: a heading, label, definition or float caption
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Symbol {
+    pub kind: SymbolKind,
+    /// Heading or caption text, label key, or command or environment name
+    pub name: String,
+    /// The command it comes from, e.g. `subsection` or `newcommand`
+    pub command: String,
+    pub file: String,
+    pub line: usize,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SymbolIndex {
+    /// In the order TeX reads them
+    pub symbols: Vec<Symbol>,
+}
+
+/// The symbols symbol_index found in a file, with the content it found them in
+struct ScannedFile {
+    content: String,
+    symbols: Vec<Symbol>,
+}
+
+/// A file's prose when its writing session started
+struct SessionBaseline {
+    /// Local day the session belongs to; the first look at the file on a later day
+    /// starts a new one
+    day: chrono::NaiveDate,
+    started_at: String,
+    words: Vec<String>,
+    characters: usize,
+}
+
+/// Writing sessions by project and file, for as long as the app runs
+static SESSIONS: Mutex<BTreeMap<(String, String), SessionBaseline>> = Mutex::new(BTreeMap::new());
+
+/// Files symbol_index has read, by project and then file. A file whose content is
+/// unchanged isn't scanned again.
+static SYMBOL_CACHE: Mutex<Vec<(String, HashMap<String, ScannedFile>)>> = Mutex::new(Vec::new());
+
+/// Where a macro is defined
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MacroDefinition {
+    pub file: String,
+    pub line: usize,
+    /// The defining command, e.g. `newcommand`
+    pub command: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MacroKind {
+    Command,
+    Environment,
+}
+
+/// A command or environment the project defines itself
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MacroInfo {
+    /// Without the backslash
+    pub name: String,
+    pub kind: MacroKind,
+    /// Arguments it takes, counting optional ones; None for \let, which copies whatever
+    /// the other macro takes
+    pub arguments: Option<usize>,
+    /// How many of `arguments` are optional
+    pub optional_arguments: usize,
+    /// The defining command, e.g. `newcommand`
+    pub command: String,
+    pub file: String,
+    pub line: usize,
+    /// A definition of the same name came earlier, and this one doesn't say it replaces
+    /// it the way \renewcommand does
+    pub duplicate: bool,
+}
+
+/// Where an environment is used
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EnvLocation {
+    pub file: String,
+    /// Line of the \begin
+    pub line: usize,
+}
+
+/// An environment the document uses, starred forms counted separately
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EnvUsage {
+    pub name: String,
+    pub count: usize,
+    /// The first MAX_ENV_LOCATIONS uses, in the order TeX reads them
+    pub locations: Vec<EnvLocation>,
+    /// Defined by the document itself, with \newenvironment, \newtheorem and the like
+    pub custom: bool,
+}
+
+/// How a heading is capitalized
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TitleStyle {
+    /// Every significant word capitalized, e.g. "Results and Future Work"
+    TitleCase,
+    /// Only the first word and names, e.g. "Results and future work"
+    SentenceCase,
+    /// Significant words of both kinds in the same title
+    Mixed,
+    /// Nothing to tell by, e.g. a single word or only acronyms
+    Undetermined,
+}
+
+/// A heading with its capitalization, compared with the others at its level
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TitleIssue {
+    pub file: String,
+    pub line: usize,
+    /// Sectioning command, e.g. `subsection`; starred headings share the level
+    pub level: String,
+    /// The title as typeset, without markup
+    pub title: String,
+    pub style: TitleStyle,
+    /// The style of the other headings at the level, set when this one differs
+    pub expected_style: Option<TitleStyle>,
+    /// The title recased to expected_style
+    pub suggestion: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LinkKind {
+    /// \url{...}
+    Url,
+    /// \href{...}{text}
+    Href,
+    /// \doi{...}, or the doi field of a cited bibliography entry
+    Doi,
+}
+
+/// Outcome of requesting a link's URL
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum LinkStatus {
+    /// The server answered with a success or redirect status
+    Ok { status: u16 },
+    /// The server answered with an error status, e.g. 404
+    Broken { status: u16 },
+    /// No answer: the host doesn't resolve, refused the connection or timed out
+    Unreachable { message: String },
+}
+
+/// A link in the document, for catching dead ones before submission
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LinkInfo {
+    pub kind: LinkKind,
+    pub url: String,
+    /// The link text of an \href
+    pub text: Option<String>,
+    /// Citation key whose bibliography entry gave the DOI
+    pub key: Option<String>,
+    pub file: String,
+    pub line: usize,
+    /// Set when links are validated; None as well for links that aren't http(s)
+    pub status: Option<LinkStatus>,
+}
+
+/// A todonotes macro, by the note it draws
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TodoKind {
+    /// `\todo`, a note in the margin or inline
+    Todo,
+    /// `\missingfigure`, a placeholder box for a figure still to make
+    MissingFigure,
+}
+
+/// A review note left with todonotes, which otherwise only shows in the PDF's margin
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TodoMacro {
+    pub kind: TodoKind,
+    /// The note's text with its whitespace collapsed, markup left in
+    pub text: String,
+    /// The optional argument, e.g. `inline, color=green`
+    pub options: Option<String>,
+    /// Drawn in the text rather than in the margin, through the `inline` option
+    pub inline: bool,
+    pub file: String,
+    pub line: usize,
+}
+
+/// Characters of a note's text kept in a NoteItem; the rest is cut off
+const MAX_NOTE_CHARS: usize = 200;
+
+/// A note macro, by where it puts its text
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum NoteKind {
+    /// `\footnote`, with its mark in the text
+    Footnote,
+    /// `\footnotetext`, the text of a mark placed with \footnotemark
+    FootnoteText,
+    /// `\marginpar`, a note in the margin
+    Marginpar,
+}
+
+/// A footnote or margin note, listed so all of them can be read in one place
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NoteItem {
+    pub kind: NoteKind,
+    /// The note's text with its whitespace collapsed, markup left in, cut to
+    /// MAX_NOTE_CHARS characters
+    pub text: String,
+    /// Whether `text` was cut short
+    pub truncated: bool,
+    /// The optional argument: a footnote's number, or a margin note's text for left
+    /// margins
+    pub option: Option<String>,
+    pub file: String,
+    pub line: usize,
+}
+
+/// A place the document breaks a style rule
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StyleIssue {
+    /// The rule's id, e.g. `figure-caption`
+    pub rule: String,
+    pub message: String,
+    /// What broke the rule, e.g. the matched text
+    pub text: String,
+    pub file: String,
+    pub line: usize,
+}
+
+/// Fields each standard BibTeX entry type needs. A `|` separates fields either of
+/// which will do, including the biblatex names for the same information.
+const REQUIRED_BIB_FIELDS: &[(&str, &[&str])] = &[
+    (
+        "article",
+        &["author", "title", "journal|journaltitle", "year|date"],
+    ),
+    (
+        "book",
+        &["author|editor", "title", "publisher", "year|date"],
+    ),
+    ("booklet", &["title"]),
+    (
+        "inbook",
+        &[
+            "author|editor",
+            "title",
+            "chapter|pages",
+            "publisher",
+            "year|date",
+        ],
+    ),
+    (
+        "incollection",
+        &["author", "title", "booktitle", "publisher", "year|date"],
+    ),
+    (
+        "inproceedings",
+        &["author", "title", "booktitle", "year|date"],
+    ),
+    ("conference", &["author", "title", "booktitle", "year|date"]),
+    ("manual", &["title"]),
+    (
+        "mastersthesis",
+        &["author", "title", "school|institution", "year|date"],
+    ),
+    (
+        "phdthesis",
+        &["author", "title", "school|institution", "year|date"],
+    ),
+    ("proceedings", &["title", "year|date"]),
+    (
+        "techreport",
+        &["author", "title", "institution", "year|date"],
+    ),
+    ("unpublished", &["author", "title", "note"]),
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BibIssueKind {
+    /// A key already used by an earlier entry; BibTeX keys ignore case
+    DuplicateKey,
+    /// A field the entry type needs, without which the style prints a warning or a gap
+    MissingField,
+    /// A brace or quote that is never closed, or a } with nothing to close
+    Unbalanced,
+    /// Text BibTeX can't read as an entry, which drops the entry from the bibliography
+    Syntax,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BibDiagnostic {
+    pub kind: BibIssueKind,
+    /// The entry the problem is in, when it could be read
+    pub key: Option<String>,
+    pub message: String,
+    pub line: usize,
+}
+
+/// A bibliography entry the document cites
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BibEntry {
+    pub key: String,
+    /// Lowercased entry type, e.g. `article`
+    pub kind: String,
+    /// The .bib file it is in
+    pub file: String,
+    pub line: usize,
+    /// Field values without their delimiters, by lowercased name
+    pub fields: BTreeMap<String, String>,
+    /// The entry exactly as written in its .bib file
+    pub source: String,
+}
+
+/// Bytes in a source file that TeX reads differently than the editor shows them
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "kind")]
+pub enum EncodingIssue {
+    /// A UTF-8 byte order mark, which can end up as stray characters before the first line
+    Bom { file: String },
+    /// Bytes that aren't valid UTF-8, usually from a file saved as Latin-1 or Windows-1252
+    InvalidUtf8 {
+        file: String,
+        /// Byte offset of the first invalid sequence
+        offset: usize,
+        line: usize,
+        /// Invalid sequences in the whole file
+        count: usize,
+    },
+}
+
+/// Scan the preamble part of `content` (all of it when `whole_file` is set, as for
+/// files \input before \begin{document}), descending into includes as they appear
+fn scan_preamble(
+    project_dir: &Path,
+    file: &str,
+    content: &str,
+    whole_file: bool,
+    visited: &mut HashSet<String>,
+    info: &mut PreambleInfo,
+) {
+    if !visited.insert(file.to_string()) {
+        return;
+    }
+
+    let masked = latex::mask_comments(content);
+    let preamble_end = if whole_file {
+        masked.len()
+    } else {
+        latex::find_begin_document(&masked).unwrap_or(masked.len())
+    };
+    let preamble = &masked[..preamble_end];
+    let lines = LineIndex::new(preamble);
+
+    for command in latex::commands(preamble) {
+        match command.name {
+            "documentclass" if info.document_class.is_none() => {
+                let args = latex::read_args(preamble, command.end, 2);
+                if let Some(class) = latex::first_required(&args) {
+                    info.document_class = Some(DocumentClass {
+                        name: class.text.trim().to_string(),
+                        options: latex::leading_optional(&args).map(|a| a.text.clone()),
+                        file: file.to_string(),
+                        line: lines.line(command.start),
+                    });
+                }
+            }
+            "usepackage" | "RequirePackage" => {
+                let args = latex::read_args(preamble, command.end, 2);
+                let Some(names) = latex::first_required(&args) else {
+                    continue;
+                };
+                let options = latex::leading_optional(&args).map(|a| a.text.clone());
+                let line = lines.line(command.start);
+
+                for name in names
+                    .text
+                    .split(',')
+                    .map(str::trim)
+                    .filter(|n| !n.is_empty())
+                {
+                    info.packages.push(PackageInfo {
+                        name: name.to_string(),
+                        options: options.clone(),
+                        file: file.to_string(),
+                        line,
+                    });
+                }
+            }
+            "graphicspath" => {
+                let args = latex::read_args(preamble, command.end, 1);
+                if let Some(arg) = latex::first_required(&args) {
+                    info.graphics_paths.extend(graphics_paths(&arg.text));
+                }
+            }
+            name if latex::INCLUDE_COMMANDS.contains(&name) => {
+                let args = latex::read_args(preamble, command.end, 1);
+                let Some(target) = args.first().filter(|a| a.kind == ArgKind::Required) else {
+                    continue;
+                };
+                let Some(child) = latex::resolve_tex_input(project_dir, &target.text) else {
+                    continue;
+                };
+                if let Ok(child_content) = latex::read_source(project_dir, &child) {
+                    scan_preamble(project_dir, &child, &child_content, true, visited, info);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Depth-first walk of the include graph. `stack` holds the chain of files currently
+/// being read, so reaching one of them again closes a cycle.
+fn walk_includes(
+    project_dir: &Path,
+    file: &str,
+    content: &str,
+    stack: &mut Vec<String>,
+    done: &mut HashSet<String>,
+    issues: &mut Vec<IncludeIssue>,
+) {
+    stack.push(file.to_string());
+    let lines = LineIndex::new(content);
+
+    for (target, offset) in latex::include_targets(content) {
+        let Some(child) = latex::resolve_tex_input(project_dir, &target) else {
+            issues.push(IncludeIssue::MissingInclude {
+                file: file.to_string(),
+                line: lines.line(offset),
+                target,
+            });
+            continue;
+        };
+
+        if let Some(start) = stack.iter().position(|f| *f == child) {
+            let mut cycle = stack[start..].to_vec();
+            cycle.push(child);
+            issues.push(IncludeIssue::CircularInclude { cycle });
+        } else if !done.contains(&child) {
+            if let Ok(child_content) = latex::read_source(project_dir, &child) {
+                walk_includes(project_dir, &child, &child_content, stack, done, issues);
+            }
+        }
+    }
+
+    stack.pop();
+    done.insert(file.to_string());
+}
+
+/// Missing and circular includes reachable from `root_file`
+pub(crate) fn check_includes(
+    project_dir: &Path,
+    root_file: &str,
+) -> Result<Vec<IncludeIssue>, String> {
+    let root_file = latex::project_file(root_file)?;
+    let content = latex::read_source(project_dir, &root_file)?;
+
+    let mut issues = Vec::new();
+    walk_includes(
+        project_dir,
+        &root_file,
+        &content,
+        &mut Vec::new(),
+        &mut HashSet::new(),
+        &mut issues,
+    );
+    Ok(issues)
+}
+
+/// A missing \begin{document} or \end{document}, which TeX only reports as confusing
+/// errors such as "\end occurred inside a group". Either may sit in an included file,
+/// so the whole document is scanned, with `root_content` in place of the root file
+/// when given.
+pub(crate) fn check_document_structure(
+    project_dir: &Path,
+    root_file: &str,
+    root_content: Option<&str>,
+) -> Result<Vec<Diagnostic>, String> {
+    let root = latex::project_file(root_file)?;
+    let content = match root_content {
+        Some(content) => content.to_string(),
+        None => latex::read_source(project_dir, &root)?,
+    };
+    let sources = latex::load_sources_from(project_dir, root.clone(), content);
+
+    let error = |message: String, file: &str, line| Diagnostic {
+        severity: Severity::Error,
+        message,
+        file: Some(file.to_string()),
+        line,
+    };
+
+    let mut diagnostics = Vec::new();
+    let mut has_begin = false;
+    let mut has_end = false;
+    let mut unresolved = false;
+    for source in &sources {
+        let masked = latex::mask_comments(&source.content);
+        let lines = LineIndex::new(&masked);
+        let begin = latex::find_begin_document(&masked);
+        let end = latex::find_end_document(&masked);
+        has_begin |= begin.is_some();
+        has_end |= end.is_some();
+
+        if let (Some(begin), Some(end)) = (begin, end) {
+            if end < begin {
+                diagnostics.push(error(
+                    "\\end{document} comes before \\begin{document}".to_string(),
+                    &source.path,
+                    Some(lines.line(end)),
+                ));
+            }
+        }
+
+        unresolved |= latex::include_targets(&source.content)
+            .iter()
+            .any(|(target, _)| latex::resolve_tex_input(project_dir, target).is_none());
+    }
+
+    // Either command could be in a file that isn't found, e.g. one named by a macro
+    if unresolved {
+        return Ok(diagnostics);
+    }
+    if !has_begin {
+        diagnostics.push(error(
+            format!(
+                "\\begin{{document}} is missing from {} and the files it includes",
+                root
+            ),
+            &root,
+            None,
+        ));
+    } else if !has_end {
+        let last_line = LineIndex::new(&sources[0].content).line(sources[0].content.len());
+        diagnostics.push(error(
+            format!(
+                "\\end{{document}} is missing from {} and the files it includes; the file \
+                 may have been cut off",
+                root
+            ),
+            &root,
+            Some(last_line),
+        ));
+    }
+
+    Ok(diagnostics)
+}
+
+/// The document as TeX reads it: the root file with each \input, \include and \subfile
+/// replaced by the file it names, comments masked. `segments` map it back to the files.
+struct ExpandedDocument {
+    text: String,
+    /// Where each piece starts in `text`, the file it comes from and where it starts there
+    segments: Vec<(usize, usize, usize)>,
+    files: Vec<(String, LineIndex)>,
+}
+
+impl ExpandedDocument {
+    fn load(project_dir: &Path, root_file: &str) -> Result<Self, String> {
+        let root = latex::project_file(root_file)?;
+        let content = latex::read_source(project_dir, &root)?;
+        let mut document = ExpandedDocument {
+            text: String::new(),
+            segments: Vec::new(),
+            files: Vec::new(),
+        };
+        document.expand(project_dir, root, &content, &mut Vec::new());
+        Ok(document)
+    }
+
+    /// Append `file` with the files it includes in place, skipping any that would
+    /// include itself again
+    fn expand(&mut self, project_dir: &Path, file: String, content: &str, stack: &mut Vec<String>) {
+        let masked = latex::mask_comments(content);
+        let index = self.files.len();
+        self.files.push((file.clone(), LineIndex::new(&masked)));
+        stack.push(file);
+
+        let mut cursor = 0;
+        for command in latex::commands(&masked) {
+            if command.start < cursor || !latex::INCLUDE_COMMANDS.contains(&command.name) {
+                continue;
+            }
+            let args = latex::read_args(&masked, command.end, 1);
+            let Some(child) = latex::first_required(&args)
+                .and_then(|target| latex::resolve_tex_input(project_dir, &target.text))
+            else {
+                continue;
+            };
+            if stack.contains(&child) {
+                continue;
+            }
+            let Ok(child_content) = latex::read_source(project_dir, &child) else {
+                continue;
+            };
+
+            self.push(index, cursor, &masked[cursor..command.start]);
+            self.expand(project_dir, child, &child_content, stack);
+            cursor = latex::skip_args(&masked, command.end, 1);
+        }
+        self.push(index, cursor, &masked[cursor..]);
+        stack.pop();
+    }
+
+    fn push(&mut self, file: usize, offset: usize, text: &str) {
+        self.segments.push((self.text.len(), file, offset));
+        self.text.push_str(text);
+    }
+
+    /// The file and line of byte `pos` of `text`
+    fn locate(&self, pos: usize) -> (String, usize) {
+        let segment = self.segments.partition_point(|(start, _, _)| *start <= pos);
+        let (start, file, offset) = self.segments[segment.saturating_sub(1)];
+        let (path, lines) = &self.files[file];
+        (path.clone(), lines.line(offset + pos - start))
+    }
+}
+
+/// An open group while checking balance: a brace or an environment, by where it opens
+enum OpenGroup {
+    Brace(usize),
+    Environment(String, usize),
+}
+
+/// Offset just past the body of the definition whose command ends at `pos`, so the
+/// \begin or \end of e.g. \newcommand{\be}{\begin{equation}} isn't taken for one
+fn definition_end(masked: &str, command: &str, pos: usize) -> usize {
+    let name_end = if ENVIRONMENT_DEFINITIONS.contains(&command) {
+        latex::skip_args(masked, pos, 1)
+    } else {
+        defined_macro(masked, pos).map_or(pos, |(_, end)| end)
+    };
+    match command {
+        "let" => name_end,
+        // \def\name#1#2{body}: parameter text up to the body's brace
+        "def" | "gdef" | "edef" | "xdef" => masked[name_end..]
+            .find('{')
+            .and_then(|i| latex::read_group(masked, name_end + i, b'{', b'}'))
+            .map_or(name_end, |(_, end)| end),
+        _ => latex::skip_args(masked, name_end, usize::MAX),
+    }
+}
+
+/// Unbalanced braces and environments across the whole document, with files included
+/// where TeX reads them, so an environment opened in one file and closed in another
+/// counts as balanced. Each problem is reported in the file and line it is in.
+#[tauri::command]
+pub async fn check_balance_project(
+    project_path: String,
+    root_file: String,
+) -> Result<Vec<Diagnostic>, String> {
+    let document = ExpandedDocument::load(&PathBuf::from(&project_path), &root_file)?;
+
+    let text = &document.text;
+    let bytes = text.as_bytes();
+    let mut diagnostics = Vec::new();
+    let mut report = |message: String, pos: usize| {
+        let (file, line) = document.locate(pos);
+        diagnostics.push(Diagnostic {
+            severity: Severity::Error,
+            message,
+            file: Some(file),
+            line: Some(line),
+        });
+    };
+    let opened = |group: &OpenGroup| match group {
+        OpenGroup::Brace(pos) => ("{".to_string(), *pos),
+        OpenGroup::Environment(name, pos) => (format!("\\begin{{{}}}", name), *pos),
+    };
+
+    let mut open: Vec<OpenGroup> = Vec::new();
+    let mut in_definition_until = 0;
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'{' => {
+                open.push(OpenGroup::Brace(i));
+                i += 1;
+                continue;
+            }
+            b'}' => {
+                match open.last() {
+                    Some(OpenGroup::Brace(_)) => {
+                        open.pop();
+                    }
+                    Some(OpenGroup::Environment(name, _)) => {
+                        report(format!("Unmatched }} inside \\begin{{{}}}", name), i)
+                    }
+                    None => report("Unmatched }".to_string(), i),
+                }
+                i += 1;
+                continue;
+            }
+            b'\\' => {}
+            _ => {
+                i += 1;
+                continue;
+            }
+        }
+
+        // A control symbol such as \{ or \%
+        if !bytes.get(i + 1).is_some_and(u8::is_ascii_alphabetic) {
+            i += 2;
+            continue;
+        }
+        let command = latex::commands(&text[i..]).next().unwrap();
+        let end = i + command.end;
+
+        if command.name == "verb" {
+            i = match text[end..].chars().next() {
+                Some(delimiter) => {
+                    let from = end + delimiter.len_utf8();
+                    text[from..]
+                        .find(delimiter)
+                        .map_or(text.len(), |j| from + j + delimiter.len_utf8())
+                }
+                None => end,
+            };
+            continue;
+        }
+        if MACRO_DEFINITIONS.contains(&command.name)
+            || ENVIRONMENT_DEFINITIONS.contains(&command.name)
+        {
+            in_definition_until = in_definition_until.max(definition_end(text, command.name, end));
+            i = end;
+            continue;
+        }
+        if command.name != "begin" && command.name != "end" {
+            i = end;
+            continue;
+        }
+
+        let name_start = latex::skip_arg_whitespace(bytes, end);
+        let Some((name, after)) = latex::read_group(text, name_start, b'{', b'}') else {
+            i = end;
+            continue;
+        };
+        let name = name.trim().to_string();
+        if i < in_definition_until {
+            i = after;
+            continue;
+        }
+
+        if command.name == "begin" {
+            if latex::VERBATIM_ENVIRONMENTS.contains(&name.as_str()) {
+                let closing = format!("\\end{{{}}}", name);
+                match text[after..].find(&closing) {
+                    Some(j) => i = after + j + closing.len(),
+                    None => {
+                        report(format!("\\begin{{{}}} is never closed", name), i);
+                        break;
+                    }
+                }
+                continue;
+            }
+            open.push(OpenGroup::Environment(name, i));
+        } else {
+            let matching = open.iter().rposition(
+                |group| matches!(group, OpenGroup::Environment(open, _) if *open == name),
+            );
+            match matching {
+                Some(index) => {
+                    // Whatever opened after it is closed by this \end, an error in TeX
+                    for group in open.drain(index + 1..).collect::<Vec<_>>() {
+                        let (what, pos) = opened(&group);
+                        report(
+                            format!("{} is not closed before \\end{{{}}}", what, name),
+                            pos,
+                        );
+                    }
+                    open.pop();
+                }
+                None => report(format!("\\end{{{}}} without a matching \\begin", name), i),
+            }
+        }
+        i = after;
+    }
+
+    for group in open {
+        let (what, pos) = opened(&group);
+        report(format!("{} is never closed", what), pos);
+    }
+
+    Ok(diagnostics)
+}
+
+/// Commands whose arguments are set line by line, so \\ is how their lines break
+const LINE_BREAK_COMMANDS: &[&str] = &[
+    "title",
+    "author",
+    "date",
+    "address",
+    "affiliation",
+    "institute",
+    "thanks",
+    "shortstack",
+    "makecell",
+    "parbox",
+    "substack",
+];
+
+/// Spans of `\[ ... \]`, `\( ... \)` and `$$ ... $$` math in `masked`, each to the end of
+/// its closing delimiter, or of the text when it isn't closed. `\\[2pt]` is a row break.
+fn delimited_math(masked: &str) -> Vec<Range<usize>> {
+    let bytes = masked.as_bytes();
+    let mut spans = Vec::new();
+    let mut i = 0;
+    while i + 1 < bytes.len() {
+        let closing = match &bytes[i..i + 2] {
+            b"\\[" if !latex::is_escaped(bytes, i) => "\\]",
+            b"\\(" if !latex::is_escaped(bytes, i) => "\\)",
+            b"$$" if !latex::is_escaped(bytes, i) => "$$",
+            _ => {
+                i += 1;
+                continue;
+            }
+        };
+        let end = masked[i + 2..]
+            .find(closing)
+            .map_or(masked.len(), |end| i + 2 + end + closing.len());
+        spans.push(i..end);
+        i = end;
+    }
+    spans
+}
+
+/// Every \\ in the body of the document outside the places it belongs: aligned and
+/// tabular environments, math, verbatim, title block commands such as \author and
+/// macro definitions. In running text it is usually meant as a paragraph break, which a
+/// blank line gives without the underfull box warnings.
+#[tauri::command]
+pub async fn detect_linebreak_misuse(
+    project_path: String,
+    root_file: String,
+) -> Result<Vec<Diagnostic>, String> {
+    tauri::async_runtime::spawn_blocking(move || {
+        let document = ExpandedDocument::load(&PathBuf::from(&project_path), &root_file)?;
+        let text = &document.text;
+        let bytes = text.as_bytes();
+        let body_start = latex::find_begin_document(text).unwrap_or(0);
+        let body_end = latex::find_end_document(text).unwrap_or(text.len());
+
+        let mut allowed: Vec<Range<usize>> = latex::environments(text)
+            .into_iter()
+            .filter(|env| {
+                latex::ALIGNMENT_ENVIRONMENTS.contains(&env.name.as_str())
+                    || latex::LINE_BREAK_ENVIRONMENTS.contains(&env.name.as_str())
+                    || latex::VERBATIM_ENVIRONMENTS.contains(&env.name.as_str())
+                    || prose::MATH_ENVIRONMENTS.contains(&env.name.as_str())
+            })
+            .map(|env| env.span)
+            .collect();
+        allowed.extend(delimited_math(text));
+        for command in latex::commands(text) {
+            if LINE_BREAK_COMMANDS.contains(&command.name) {
+                allowed.push(command.start..latex::skip_args(text, command.end, usize::MAX));
+            } else if MACRO_DEFINITIONS.contains(&command.name)
+                || ENVIRONMENT_DEFINITIONS.contains(&command.name)
+            {
+                allowed.push(command.start..definition_end(text, command.name, command.end));
+            } else if command.name == "verb" {
+                if let Some(delimiter) = text[command.end..].chars().next() {
+                    let from = command.end + delimiter.len_utf8();
+                    let end = text[from..]
+                        .find(delimiter)
+                        .map_or(text.len(), |j| from + j);
+                    allowed.push(command.start..end);
+                }
+            }
+        }
+
+        let mut diagnostics = Vec::new();
+        let mut i = body_start;
+        while i + 1 < body_end {
+            if bytes[i] != b'\\' {
+                i += 1;
+                continue;
+            }
+            if bytes[i + 1] != b'\\' {
+                // Any other control sequence, including control symbols such as \%
+                i += 2;
+                continue;
+            }
+            let pos = i;
+            i += 2;
+            if allowed.iter().any(|range| range.contains(&pos)) {
+                continue;
+            }
+
+            // Past a star and a [length] argument, to what follows the break
+            let mut after = i + usize::from(bytes.get(i) == Some(&b'*'));
+            let next = latex::skip_arg_whitespace(bytes, after);
+            if bytes.get(next) == Some(&b'[') {
+                if let Some((_, end)) = latex::read_group(text, next, b'[', b']') {
+                    after = end;
+                }
+            }
+            let rest = &text[after..];
+            let line_end = rest.find('\n').map_or(rest.len(), |j| j + 1);
+            let next_line = rest[line_end..].split('\n').next().unwrap_or_default();
+            let ends_paragraph = rest[..line_end].trim().is_empty() && next_line.trim().is_empty();
+            let message = if ends_paragraph {
+                "\\\\ at the end of a paragraph; the blank line already ends it, and the empty \
+                 line left makes TeX warn of an underfull \\hbox"
+            } else if rest.trim_start().starts_with("\\\\") {
+                "Repeated \\\\ for vertical space; use \\medskip, \\bigskip or \\vspace"
+            } else {
+                "\\\\ in running text; leave line breaking to TeX, or start a new paragraph \
+                 with a blank line"
+            };
+
+            let (file, line) = document.locate(pos);
+            diagnostics.push(Diagnostic {
+                severity: Severity::Warning,
+                message: message.to_string(),
+                file: Some(file),
+                line: Some(line),
+            });
+        }
+        Ok(diagnostics)
+    })
+    .await
+    .map_err(|e| format!("Task join error: {}", e))?
+}
+
+/// Commands whose arguments are names, keys or paths rather than text, so _, # and &
+/// in them are meant literally
+const LITERAL_ARGUMENT_COMMANDS: &[&str] = &[
+    "url",
+    "path",
+    "nolinkurl",
+    "label",
+    "input",
+    "include",
+    "includeonly",
+    "subfile",
+    "includegraphics",
+    "graphicspath",
+    "lstinputlisting",
+    "inputminted",
+    "usepackage",
+    "documentclass",
+    "bibliography",
+    "bibliographystyle",
+    "addbibresource",
+    "begin",
+    "end",
+    "hypersetup",
+    "hyperref",
+    "hypertarget",
+    "hyperlink",
+    "definecolor",
+];
+
+/// Environments whose content isn't text at all, left out of the check
+const CODE_ENVIRONMENTS: &[&str] = &["tikzpicture", "tikzcd", "axis"];
+
+/// Where math opened by `opener` is still waiting for `closer`
+struct OpenMath {
+    opener: &'static str,
+    closer: &'static str,
+    pos: usize,
+}
+
+/// Whether inline math between two $ reads as prose, e.g. `$5 and $` from two prices:
+/// it starts with a number and has a word of letters in it
+fn reads_as_prices(content: &str) -> bool {
+    content
+        .trim_start()
+        .starts_with(|c: char| c.is_ascii_digit())
+        && content
+            .split_whitespace()
+            .any(|token| token.len() > 1 && token.chars().all(|c| c.is_ascii_alphabetic()))
+}
+
+/// Special characters in the body of a file that are likely meant literally: _ and # in
+/// text, & outside alignments, a $ whose math never closes or reads as prose, % right
+/// after a number with the line going on, and a \ ending a line. Bare URLs are reported
+/// too, since \url sets their characters and lets them break. Math, verbatim, macro
+/// definitions and arguments that hold names or paths such as \label and \ref are
+/// skipped, as are escaped characters and comments.
+#[tauri::command]
+pub async fn detect_escaping_issues(
+    project_path: String,
+    file_path: String,
+) -> Result<Vec<Diagnostic>, String> {
+    tauri::async_runtime::spawn_blocking(move || {
+        let project_dir = PathBuf::from(&project_path);
+        let file_path = latex::project_file(&file_path)?;
+        let content = latex::read_source(&project_dir, &file_path)?;
+        let masked = latex::mask_comments(&content);
+        let lines = LineIndex::new(&masked);
+        let body = prose::body_range(&content);
+        let environments = latex::environments(&masked);
+
+        let mut excluded: Vec<Range<usize>> = environments
+            .iter()
+            .filter(|env| {
+                latex::VERBATIM_ENVIRONMENTS.contains(&env.name.as_str())
+                    || prose::MATH_ENVIRONMENTS.contains(&env.name.as_str())
+                    || CODE_ENVIRONMENTS.contains(&env.name.as_str())
+            })
+            .map(|env| env.span.clone())
+            .collect();
+        for command in latex::commands(&masked) {
+            let name = command.name;
+            if LITERAL_ARGUMENT_COMMANDS.contains(&name)
+                || REFERENCE_COMMANDS.contains(&name)
+                || name.ends_with("cite")
+                || name.starts_with("cite")
+            {
+                excluded.push(command.start..latex::skip_args(&masked, command.end, usize::MAX));
+            } else if name == "href" {
+                excluded.push(command.start..latex::skip_args(&masked, command.end, 1));
+            } else if MACRO_DEFINITIONS.contains(&name) || ENVIRONMENT_DEFINITIONS.contains(&name) {
+                excluded.push(command.start..definition_end(&masked, name, command.end));
+            } else if name == "verb" || name == "lstinline" {
+                let mut from = command.end;
+                if masked[from..].starts_with('*') {
+                    from += 1;
+                }
+                if masked[from..].starts_with('[') {
+                    from = latex::skip_args(&masked, from, 0);
+                }
+                let end = match masked[from..].chars().next() {
+                    Some('{') => latex::read_group(&masked, from, b'{', b'}').map(|(_, end)| end),
+                    Some(delimiter) => {
+                        let start = from + delimiter.len_utf8();
+                        masked[start..].find(delimiter).map(|j| start + j + 1)
+                    }
+                    None => None,
+                };
+                excluded.push(command.start..end.unwrap_or(masked.len()));
+            }
+        }
+
+        let mut diagnostics = Vec::new();
+        let mut report = |severity: Severity, message: String, pos: usize| {
+            diagnostics.push(Diagnostic {
+                severity,
+                message,
+                file: Some(file_path.clone()),
+                line: Some(lines.line(pos)),
+            });
+        };
+
+        // Blank out what isn't checked, keeping offsets and line breaks
+        let mut text = masked.as_bytes().to_vec();
+        let blank = |text: &mut Vec<u8>, range: &Range<usize>| {
+            for b in &mut text[range.start.min(body.end)..range.end.min(body.end)] {
+                if *b != b'\n' {
+                    *b = b' ';
+                }
+            }
+        };
+        for range in &excluded {
+            blank(&mut text, range);
+        }
+
+        let mut i = body.start;
+        while i < body.end {
+            let rest = &text[i..body.end];
+            let starts_url = [&b"http://"[..], b"https://", b"www."]
+                .iter()
+                .any(|scheme| rest.starts_with(scheme));
+            let after_word = i > 0 && (text[i - 1].is_ascii_alphanumeric() || text[i - 1] == b'/');
+            if !starts_url || after_word {
+                i += 1;
+                continue;
+            }
+            let len = rest
+                .iter()
+                .position(|b| b.is_ascii_whitespace() || matches!(b, b'{' | b'}' | b'\\'))
+                .unwrap_or(rest.len());
+            let url = String::from_utf8_lossy(&rest[..len]);
+            let url = url.trim_end_matches(['.', ',', ';', ':', ')']);
+            report(
+                Severity::Warning,
+                format!("Bare URL {}; write it as \\url{{{}}}", url, url),
+                i,
+            );
+            blank(&mut text, &(i..i + len));
+            i += len;
+        }
+
+        let aligned: Vec<Range<usize>> = environments
+            .iter()
+            .filter(|env| latex::ALIGNMENT_ENVIRONMENTS.contains(&env.name.as_str()))
+            .map(|env| env.span.clone())
+            .collect();
+        let mut math: Option<OpenMath> = None;
+        let mut i = body.start;
+        while i < body.end {
+            match text[i] {
+                b'\\' => {
+                    let closes =
+                        |closer: &str| math.as_ref().is_some_and(|open| open.closer == closer);
+                    match text.get(i + 1) {
+                        Some(b'(') | Some(b'[') if math.is_none() => {
+                            let (opener, closer) = if text[i + 1] == b'(' {
+                                ("\\(", "\\)")
+                            } else {
+                                ("\\[", "\\]")
+                            };
+                            math = Some(OpenMath {
+                                opener,
+                                closer,
+                                pos: i,
+                            });
+                        }
+                        Some(b')') if closes("\\)") => math = None,
+                        Some(b']') if closes("\\]") => math = None,
+                        Some(b'\n') | None if math.is_none() => report(
+                            Severity::Warning,
+                            "\\ at the end of a line sets a space and joins the lines; remove \
+                             it, or write \\\\ to break the line"
+                                .to_string(),
+                            i,
+                        ),
+                        _ => {}
+                    }
+                    // Past the control symbol, or the first letter of a control word
+                    i += 2;
+                    continue;
+                }
+                b'$' => {
+                    let double = text.get(i + 1) == Some(&b'$')
+                        && !matches!(&math, Some(open) if open.closer == "$");
+                    let delimiter = if double { "$$" } else { "$" };
+                    match &math {
+                        None => {
+                            math = Some(OpenMath {
+                                opener: delimiter,
+                                closer: delimiter,
+                                pos: i,
+                            })
+                        }
+                        Some(open) if open.closer == delimiter => {
+                            let inner = &masked[open.pos + 1..i];
+                            if !double && reads_as_prices(inner) {
+                                report(
+                                    Severity::Warning,
+                                    format!(
+                                        "${}$ is set as math; write \\$ for a dollar sign",
+                                        inner
+                                    ),
+                                    open.pos,
+                                );
+                            }
+                            math = None;
+                        }
+                        Some(_) => {}
+                    }
+                    i += delimiter.len();
+                    continue;
+                }
+                b'_' if math.is_none() => report(
+                    Severity::Error,
+                    "_ outside math; write \\_ for an underscore".to_string(),
+                    i,
+                ),
+                b'#' => report(
+                    Severity::Error,
+                    "# outside a macro definition; write \\# for a number sign".to_string(),
+                    i,
+                ),
+                b'&' if !aligned.iter().any(|range| range.contains(&i)) => report(
+                    Severity::Error,
+                    "& outside a table or alignment; write \\& for an ampersand".to_string(),
+                    i,
+                ),
+                b'\n' => {
+                    // A blank line ends the paragraph, and TeX closes the math with an error
+                    let next_line = text[i + 1..body.end].split(|b| *b == b'\n').next();
+                    let blank_line =
+                        next_line.is_some_and(|line| line.iter().all(u8::is_ascii_whitespace));
+                    if blank_line && i + 1 < body.end {
+                        if let Some(open) = math.take() {
+                            report(
+                                Severity::Error,
+                                format!(
+                                    "Math opened by {} is not closed before the paragraph \
+                                     ends; write \\$ for a dollar sign",
+                                    open.opener
+                                ),
+                                open.pos,
+                            );
+                        }
+                    }
+                }
+                _ => {}
+            }
+            i += 1;
+        }
+        if let Some(open) = math {
+            report(
+                Severity::Error,
+                format!("Math opened by {} is never closed", open.opener),
+                open.pos,
+            );
+        }
+
+        // A % right after a number starts a comment, dropping the rest of the line
+        let verbatim = latex::verbatim_lines(&content);
+        let mut line_start = 0;
+        for (line, is_verbatim) in content.split('\n').zip(verbatim) {
+            let code = latex::strip_comment(line);
+            let pos = line_start + code.len();
+            line_start += line.len() + 1;
+            if is_verbatim
+                || code.len() == line.len()
+                || !body.contains(&pos)
+                || excluded.iter().any(|range| range.contains(&pos))
+                || !code.ends_with(|c: char| c.is_ascii_digit())
+            {
+                continue;
+            }
+            let dropped = line[code.len() + 1..].trim_start();
+            if dropped.starts_with(|c: char| c.is_lowercase() || ",.;:)".contains(c)) {
+                report(
+                    Severity::Warning,
+                    format!(
+                        "% after a number starts a comment, dropping \"{}\"; write \\% for \
+                         a percent sign",
+                        dropped.trim_end()
+                    ),
+                    pos,
+                );
+            }
+        }
+
+        diagnostics.sort_by_key(|diagnostic| diagnostic.line);
+        Ok(diagnostics)
+    })
+    .await
+    .map_err(|e| format!("Task join error: {}", e))?
+}
+
+/// Words title case leaves lowercase, which say nothing about a heading's style
+const MINOR_TITLE_WORDS: &[&str] = &[
+    "a", "an", "the", "and", "but", "or", "nor", "for", "so", "yet", "as", "at", "by", "in", "of",
+    "off", "on", "per", "to", "up", "via", "vs", "from", "into", "onto", "over", "with", "than",
+    "upon",
+];
+
+/// The words of a plain title that tell its style, as their index among the title's
+/// whitespace-separated tokens and whether they're capitalized. The first word of the
+/// title and of a phrase after a colon are capitalized in either style, minor words
+/// and acronyms in neither, and a capitalized word the document never writes in
+/// lowercase is likely a name; none of those count.
+fn significant_words(title: &str, lowercase: &HashSet<String>) -> Vec<(usize, bool)> {
+    let mut words = Vec::new();
+    let mut starts_phrase = true;
+    for (i, token) in title.split_whitespace().enumerate() {
+        let ends_phrase = token.ends_with([':', '?', '!', '.']);
+        // The first part of a compound, e.g. Data in Data-Driven
+        let word = token
+            .trim_matches(|c: char| !c.is_alphanumeric())
+            .split('-')
+            .next()
+            .unwrap_or_default();
+        let Some(first) = word.chars().next() else {
+            starts_phrase |= ends_phrase;
+            continue;
+        };
+        let phrase_start = starts_phrase;
+        starts_phrase = ends_phrase;
+        if phrase_start || !first.is_alphabetic() {
+            continue;
+        }
+
+        let acronym = word.chars().skip(1).any(char::is_uppercase)
+            || word.chars().any(|c| c.is_ascii_digit());
+        let lower = word.to_lowercase();
+        if acronym || MINOR_TITLE_WORDS.contains(&lower.as_str()) {
+            continue;
+        }
+        if first.is_lowercase() {
+            words.push((i, false));
+        } else if lowercase.contains(&lower) {
+            words.push((i, true));
+        }
+    }
+    words
+}
+
+fn title_style(words: &[(usize, bool)]) -> TitleStyle {
+    let capitalized = words.iter().filter(|(_, upper)| *upper).count();
+    match (capitalized, words.len() - capitalized) {
+        (0, 0) => TitleStyle::Undetermined,
+        (_, 0) => TitleStyle::TitleCase,
+        (0, _) => TitleStyle::SentenceCase,
+        _ => TitleStyle::Mixed,
+    }
+}
+
+/// `title` with the case of its significant `words` changed to `style`
+fn recase_title(title: &str, words: &[(usize, bool)], style: TitleStyle) -> String {
+    title
+        .split_whitespace()
+        .enumerate()
+        .map(|(i, token)| {
+            let change = match words.iter().find(|(word, _)| *word == i) {
+                Some((_, true)) => style == TitleStyle::SentenceCase,
+                Some((_, false)) => style == TitleStyle::TitleCase,
+                None => false,
+            };
+            let letter = token.char_indices().find(|(_, c)| c.is_alphabetic());
+            let Some((at, first)) = letter.filter(|_| change) else {
+                return token.to_string();
+            };
+            let recased: String = if first.is_uppercase() {
+                first.to_lowercase().collect()
+            } else {
+                first.to_uppercase().collect()
+            };
+            format!(
+                "{}{}{}",
+                &token[..at],
+                recased,
+                &token[at + first.len_utf8()..]
+            )
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Every heading of the document with its capitalization style, compared with the
+/// other headings at its level, as journals want each level to be uniform. A heading
+/// that differs from the style most of its level uses is flagged with that style and
+/// the title recased to it; when a level is split evenly, its first heading's style
+/// counts. Names can't be told from words title case capitalizes, so only capitalized
+/// words the document also writes in lowercase count toward title case.
+#[tauri::command]
+pub async fn section_title_audit(
+    project_path: String,
+    root_file: String,
+) -> Result<Vec<TitleIssue>, String> {
+    tauri::async_runtime::spawn_blocking(move || {
+        let project_dir = PathBuf::from(&project_path);
+        let document = ExpandedDocument::load(&project_dir, &root_file)?;
+        let text = &document.text;
+        let body = latex::find_begin_document(text).unwrap_or(0)
+            ..latex::find_end_document(text).unwrap_or(text.len());
+
+        let mut lowercase = HashSet::new();
+        for token in prose::words(&prose::document_prose(&project_dir, &root_file)?) {
+            for part in token.split('-') {
+                let part = part.trim_matches(|c: char| !c.is_alphanumeric());
+                if part.starts_with(char::is_lowercase) {
+                    lowercase.insert(part.to_string());
+                }
+            }
+        }
+
+        let mut headings = Vec::new();
+        for command in latex::commands(text).filter(|c| body.contains(&c.start)) {
+            if !latex::SECTIONING_COMMANDS.contains(&command.name) {
+                continue;
+            }
+            let args = latex::read_args(text, command.end, 2);
+            let Some(arg) = latex::first_required(&args) else {
+                continue;
+            };
+            let prose = prose::file_prose(&arg.text);
+            let title = prose.split_whitespace().collect::<Vec<_>>().join(" ");
+            if title.is_empty() {
+                continue;
+            }
+            let words = significant_words(&title, &lowercase);
+            let (file, line) = document.locate(command.start);
+            let heading = TitleIssue {
+                file,
+                line,
+                level: command.name.to_string(),
+                style: title_style(&words),
+                title,
+                expected_style: None,
+                suggestion: None,
+            };
+            headings.push((heading, words));
+        }
+
+        for level in latex::SECTIONING_COMMANDS {
+            let decided: Vec<TitleStyle> = headings
+                .iter()
+                .map(|(heading, _)| heading)
+                .filter(|heading| heading.level == *level)
+                .map(|heading| heading.style)
+                .filter(|style| matches!(style, TitleStyle::TitleCase | TitleStyle::SentenceCase))
+                .collect();
+            let Some(first) = decided.first() else {
+                continue;
+            };
+            let title_case = decided
+                .iter()
+                .filter(|style| **style == TitleStyle::TitleCase)
+                .count();
+            let expected = match title_case.cmp(&(decided.len() - title_case)) {
+                std::cmp::Ordering::Greater => TitleStyle::TitleCase,
+                std::cmp::Ordering::Less => TitleStyle::SentenceCase,
+                std::cmp::Ordering::Equal => *first,
+            };
+
+            for (heading, words) in headings.iter_mut() {
+                if heading.level != *level
+                    || heading.style == expected
+                    || heading.style == TitleStyle::Undetermined
+                {
+                    continue;
+                }
+                heading.suggestion = Some(recase_title(&heading.title, words, expected));
+                heading.expected_style = Some(expected);
+            }
+        }
+
+        Ok(headings.into_iter().map(|(heading, _)| heading).collect())
+    })
+    .await
+    .map_err(|e| format!("Task join error: {}", e))?
+}
+
+/// Find `relative` under `project_dir` comparing each path component exactly, and
+/// failing that, ignoring case. Returns the path as it is on disk and whether it
+/// matched exactly.
+fn find_with_case(project_dir: &Path, relative: &Path) -> Option<(PathBuf, bool)> {
+    let mut actual = PathBuf::new();
+    let mut exact = true;
+
+    for component in relative.components() {
+        let wanted = component.as_os_str().to_string_lossy();
+        let names: Vec<String> = std::fs::read_dir(project_dir.join(&actual))
+            .ok()?
+            .filter_map(|entry| entry.ok())
+            .map(|entry| entry.file_name().to_string_lossy().into_owned())
+            .collect();
+
+        let name = match names.iter().find(|name| **name == wanted) {
+            Some(name) => name,
+            None => {
+                exact = false;
+                names
+                    .iter()
+                    .find(|name| name.to_lowercase() == wanted.to_lowercase())?
+            }
+        };
+        actual.push(name);
+    }
+
+    project_dir
+        .join(&actual)
+        .is_file()
+        .then_some((actual, exact))
+}
+
+/// Directories listed in \graphicspath{{dir/}{other/}}
+fn graphics_paths(argument: &str) -> Vec<String> {
+    let mut dirs = Vec::new();
+    let mut pos = 0;
+    while let Some(offset) = argument[pos..].find('{') {
+        let Some((dir, end)) = latex::read_group(argument, pos + offset, b'{', b'}') else {
+            break;
+        };
+        dirs.push(dir.trim().to_string());
+        pos = end;
+    }
+    dirs
+}
+
+/// The project root followed by every \graphicspath entry in `masked` sources
+fn graphics_search_dirs(masked: &[String]) -> Vec<String> {
+    let mut search_dirs = vec![String::new()];
+    for content in masked {
+        for command in latex::commands(content).filter(|c| c.name == "graphicspath") {
+            let args = latex::read_args(content, command.end, 1);
+            if let Some(arg) = latex::first_required(&args) {
+                search_dirs.extend(graphics_paths(&arg.text));
+            }
+        }
+    }
+    search_dirs
+}
+
+/// Where graphicx looks for `name` in a \graphicspath directory: the directory is put
+/// in front of the name as written, so one without its trailing slash finds nothing
+fn graphic_candidate(dir: &str, name: &str) -> Option<PathBuf> {
+    latex::normalize_relative(Path::new(&format!("{}{}", dir, name)))
+}
+
+/// The file an \includegraphics argument refers to, located like graphicx does: names
+/// without a known extension are tried with each of GRAPHICS_EXTENSIONS, relative to
+/// each of `search_dirs`. Returns the path as it is on disk and whether its case
+/// matched exactly.
+fn find_graphic(
+    project_dir: &Path,
+    search_dirs: &[String],
+    target: &str,
+) -> Option<(PathBuf, bool)> {
+    let has_extension = Path::new(target).extension().is_some_and(|ext| {
+        GRAPHICS_EXTENSIONS.contains(&ext.to_string_lossy().to_lowercase().as_str())
+    });
+    let mut names = vec![target.to_string()];
+    if !has_extension {
+        names = GRAPHICS_EXTENSIONS
+            .iter()
+            .map(|ext| format!("{}.{}", target, ext))
+            .chain(names)
+            .collect();
+    }
+
+    let mut found = None;
+    for dir in search_dirs {
+        for name in &names {
+            let Some(relative) = graphic_candidate(dir, name) else {
+                continue;
+            };
+            if let Some(hit) = find_with_case(project_dir, &relative) {
+                if hit.1 {
+                    return Some(hit);
+                }
+                found.get_or_insert(hit);
+            }
+        }
+    }
+    found
+}
+
+/// The \includegraphics targets in `content` with their offsets, leaving out names
+/// built from macros, which can't be resolved without expanding them
+fn graphics_targets(content: &str) -> Vec<(String, usize)> {
+    latex::commands(content)
+        .filter(|c| c.name == "includegraphics")
+        .filter_map(|command| {
+            let args = latex::read_args(content, command.end, 2);
+            let target = latex::first_required(&args)?.text.trim().to_string();
+            (!target.is_empty() && !target.contains(['\\', '#'])).then_some((target, command.start))
+        })
+        .collect()
+}
+
+/// Missing and wrong-cased \includegraphics targets in the document rooted at
+/// `root_file`
+fn check_graphics(project_dir: &Path, root_file: &str) -> Result<Vec<IncludeIssue>, String> {
+    let sources = latex::load_sources(project_dir, root_file)?;
+    let masked: Vec<String> = sources
+        .iter()
+        .map(|source| latex::mask_comments(&source.content))
+        .collect();
+    let search_dirs = graphics_search_dirs(&masked);
+
+    let mut issues = Vec::new();
+    for (source, content) in sources.iter().zip(&masked) {
+        let lines = LineIndex::new(content);
+
+        for (target, offset) in graphics_targets(content) {
+            let found = find_graphic(project_dir, &search_dirs, &target);
+            let file = source.path.clone();
+            let line = lines.line(offset);
+            match found {
+                None => issues.push(IncludeIssue::MissingGraphic { file, line, target }),
+                Some((actual, false)) => issues.push(IncludeIssue::GraphicCaseMismatch {
+                    file,
+                    line,
+                    target,
+                    actual: latex::to_project_path(&actual),
+                }),
+                Some((_, true)) => {}
+            }
+        }
+    }
+
+    Ok(issues)
+}
+
+#[tauri::command]
+pub async fn validate_includes(
+    project_path: String,
+    root_file: String,
+) -> Result<Vec<IncludeIssue>, String> {
+    let project_dir = PathBuf::from(&project_path);
+    let mut issues = check_includes(&project_dir, &root_file)?;
+    issues.extend(check_graphics(&project_dir, &root_file)?);
+    Ok(issues)
+}
+
+/// `name` as a project-relative path, with `extension` appended when it has a different
+/// one, if that file exists
+fn existing_with_extension(project_dir: &Path, name: &str, extension: &str) -> Option<String> {
+    let name = name.trim();
+    let mut file = name.to_string();
+    if Path::new(name)
+        .extension()
+        .is_none_or(|ext| ext != extension)
+    {
+        file = format!("{}.{}", name, extension);
+    }
+    let relative = latex::normalize_relative(Path::new(&file))?;
+    project_dir
+        .join(&relative)
+        .is_file()
+        .then(|| latex::to_project_path(&relative))
+}
+
+/// Every project file the document rooted at `root_file` needs to compile: its sources,
+/// graphics, bibliography databases and styles, listings read from files, and classes
+/// and packages kept in the project. Paths are project-relative: the sources in the
+/// order TeX reads them, then each file's other assets. References that don't resolve
+/// are left out.
+#[tauri::command]
+pub async fn list_asset_dependencies(
+    project_path: String,
+    root_file: String,
+) -> Result<Vec<String>, String> {
+    asset_dependencies(&PathBuf::from(&project_path), &root_file)
+}
+
+/// The files list_asset_dependencies reports, for the build of `root_file`
+pub(crate) fn asset_dependencies(
+    project_dir: &Path,
+    root_file: &str,
+) -> Result<Vec<String>, String> {
+    let sources = latex::load_sources(project_dir, root_file)?;
+    let masked: Vec<String> = sources
+        .iter()
+        .map(|source| latex::mask_comments(&source.content))
+        .collect();
+    let search_dirs = graphics_search_dirs(&masked);
+
+    let mut assets: Vec<String> = sources.iter().map(|source| source.path.clone()).collect();
+    for content in &masked {
+        let graphics = graphics_targets(content)
+            .into_iter()
+            .filter_map(|(target, _)| {
+                find_graphic(project_dir, &search_dirs, &target)
+                    .map(|(actual, _)| latex::to_project_path(&actual))
+            });
+        assets.extend(graphics);
+
+        for command in latex::commands(content) {
+            let args = latex::read_args(content, command.end, 2);
+            let Some(arg) = latex::first_required(&args) else {
+                continue;
+            };
+            let names = || arg.text.split(',').filter(|n| !n.trim().is_empty());
+
+            match command.name {
+                "bibliography" => assets
+                    .extend(names().filter_map(|n| existing_with_extension(project_dir, n, "bib"))),
+                "bibliographystyle" => assets
+                    .extend(names().filter_map(|n| existing_with_extension(project_dir, n, "bst"))),
+                "documentclass" => assets
+                    .extend(names().filter_map(|n| existing_with_extension(project_dir, n, "cls"))),
+                "usepackage" | "RequirePackage" => assets
+                    .extend(names().filter_map(|n| existing_with_extension(project_dir, n, "sty"))),
+                // These read the file exactly as named
+                "addbibresource" | "lstinputlisting" => {
+                    let relative = latex::normalize_relative(Path::new(arg.text.trim()));
+                    if let Some(relative) = relative.filter(|r| project_dir.join(r).is_file()) {
+                        assets.push(latex::to_project_path(&relative));
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    let mut seen = HashSet::new();
+    assets.retain(|asset| seen.insert(asset.clone()));
+    Ok(assets)
+}
+
+/// Commands of the import package, which take a directory and a file in it; the `sub`
+/// ones take the directory relative to the file they're in
+const IMPORT_COMMANDS: &[&str] = &[
+    "import",
+    "subimport",
+    "includefrom",
+    "subincludefrom",
+    "inputfrom",
+    "subinputfrom",
+];
+
+/// A file project_cleanup_report found nothing using
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UnusedFile {
+    pub path: String,
+    pub size: u64,
+}
+
+/// Files none of the project's documents use
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CleanupReport {
+    /// .bib files no \bibliography or \addbibresource names
+    pub bibliographies: Vec<UnusedFile>,
+    /// Images, by GRAPHICS_EXTENSIONS, no \includegraphics finds
+    pub graphics: Vec<UnusedFile>,
+    /// .tex files no document includes
+    pub sources: Vec<UnusedFile>,
+    /// .tex files with a \documentclass of their own that the root file doesn't
+    /// include, e.g. a standalone figure. They count as documents, and what they use as
+    /// used.
+    pub other_documents: Vec<String>,
+    pub total_size: u64,
+}
+
+/// Project files named by an argument of any command in `sources`, as a graphic, a
+/// .bib or .tex file, or exactly, including through the import package. This catches
+/// what asset_dependencies doesn't know to look for, such as a file read by \includepdf
+/// or a macro of the document's own.
+fn mentioned_files(project_dir: &Path, sources: &[latex::SourceFile]) -> HashSet<String> {
+    let masked: Vec<String> = sources
+        .iter()
+        .map(|source| latex::mask_comments(&source.content))
+        .collect();
+    let search_dirs = graphics_search_dirs(&masked);
+
+    let mut tried = HashSet::new();
+    let mut found = HashSet::new();
+    let mut mention = |name: &str| {
+        let name = name.trim().trim_matches('"');
+        if name.is_empty() || name.contains(['\\', '#', '{', '}', '$', '\n']) {
+            return;
+        }
+        if !tried.insert(name.to_string()) {
+            return;
+        }
+        if let Some((actual, _)) = find_graphic(project_dir, &search_dirs, name) {
+            found.insert(latex::to_project_path(&actual));
+        }
+        for extension in ["bib", "tex"] {
+            found.extend(existing_with_extension(project_dir, name, extension));
+        }
+        let relative = latex::normalize_relative(Path::new(name));
+        if let Some(relative) = relative.filter(|r| project_dir.join(r).is_file()) {
+            found.insert(latex::to_project_path(&relative));
+        }
+    };
+
+    for (source, content) in sources.iter().zip(&masked) {
+        for command in latex::commands(content) {
+            let args = latex::read_args(content, command.end, 3);
+            let mut required = args
+                .iter()
+                .filter(|arg| arg.kind == ArgKind::Required)
+                .map(|arg| arg.text.trim());
+            if IMPORT_COMMANDS.contains(&command.name) {
+                let (Some(dir), Some(file)) = (required.next(), required.next()) else {
+                    continue;
+                };
+                let dir = if command.name.starts_with("sub") {
+                    match source.path.rsplit_once('/') {
+                        Some((parent, _)) => format!("{}/{}", parent, dir),
+                        None => dir.to_string(),
+                    }
+                } else {
+                    dir.to_string()
+                };
+                mention(&format!("{}/{}", dir.trim_end_matches('/'), file));
+                continue;
+            }
+            for arg in required {
+                arg.split(',').for_each(&mut mention);
+            }
+        }
+    }
+    found
+}
+
+/// Bibliographies, images and .tex files in the project that no document uses, for
+/// clearing out before archiving it. The documents are `root_file` and every .tex file
+/// with a \documentclass of its own it doesn't include. A file counts as used when its
+/// documents need it to compile, as list_asset_dependencies finds them, or when any
+/// command's argument names it, so a file is only reported when nothing refers to it;
+/// references in comments don't count. PDFs named like a .tex file or a jobname are
+/// taken for build outputs and left out, as is the build directory. Paths are compared
+/// ignoring case.
+#[tauri::command]
+pub async fn project_cleanup_report(
+    project_path: String,
+    root_file: String,
+) -> Result<CleanupReport, String> {
+    tauri::async_runtime::spawn_blocking(move || {
+        let project_dir = PathBuf::from(&project_path);
+        let root_file = latex::project_file(&root_file)?;
+        let meta = read_project_meta(&project_dir)?;
+        let hidden = PathPatterns::new(&meta.tree_ignore, "tree_ignore")?;
+        let mut files = Vec::new();
+        let mut links = SymlinkPolicy::new(&project_dir, meta.allow_symlinks);
+        collect_files(&project_dir, &project_dir, &hidden, &mut links, &mut files);
+        let build = build_dir(&project_dir)?;
+        if let Ok(relative) = build.strip_prefix(&project_dir) {
+            let build = format!("{}/", latex::to_project_path(relative));
+            files.retain(|file| !file.starts_with(&build));
+        }
+
+        let extension = |file: &str| {
+            Path::new(file)
+                .extension()
+                .map(|ext| ext.to_string_lossy().to_lowercase())
+                .unwrap_or_default()
+        };
+        let stem = |file: &str| {
+            Path::new(file)
+                .file_stem()
+                .map(|stem| stem.to_string_lossy().to_lowercase())
+                .unwrap_or_default()
+        };
+
+        let mut used = HashSet::new();
+        let mut documents = vec![root_file.clone()];
+        let mut other_documents = Vec::new();
+        for file in &files {
+            if *file == root_file || extension(file) != "tex" {
+                continue;
+            }
+            let Ok(content) = latex::read_source(&project_dir, file) else {
+                continue;
+            };
+            let masked = latex::mask_comments(&content);
+            if latex::commands(&masked).any(|c| c.name == "documentclass") {
+                documents.push(file.clone());
+            }
+        }
+        for (i, document) in documents.iter().enumerate() {
+            // A document the root file or an earlier one includes is part of that one
+            if i > 0 && used.contains(&document.to_lowercase()) {
+                continue;
+            }
+            let sources = latex::load_sources(&project_dir, document)?;
+            let dependencies = asset_dependencies(&project_dir, document)?;
+            let mentioned = mentioned_files(&project_dir, &sources);
+            used.extend(
+                dependencies
+                    .iter()
+                    .chain(&mentioned)
+                    .map(|file| file.to_lowercase()),
+            );
+            if i > 0 {
+                other_documents.push(document.clone());
+            }
+        }
+
+        let mut outputs: HashSet<String> = files
+            .iter()
+            .filter(|file| extension(file) == "tex")
+            .map(|file| stem(file))
+            .collect();
+        outputs.extend(meta.jobnames.values().map(|jobname| jobname.to_lowercase()));
+
+        let mut report = CleanupReport {
+            bibliographies: Vec::new(),
+            graphics: Vec::new(),
+            sources: Vec::new(),
+            other_documents,
+            total_size: 0,
+        };
+        for file in files {
+            if used.contains(&file.to_lowercase()) {
+                continue;
+            }
+            let extension = extension(&file);
+            let list = match extension.as_str() {
+                "bib" => &mut report.bibliographies,
+                "tex" => &mut report.sources,
+                "pdf" if outputs.contains(&stem(&file)) => continue,
+                ext if GRAPHICS_EXTENSIONS.contains(&ext) => &mut report.graphics,
+                _ => continue,
+            };
+            let size = std::fs::metadata(project_dir.join(&file)).map_or(0, |m| m.len());
+            report.total_size += size;
+            list.push(UnusedFile { path: file, size });
+        }
+        Ok(report)
+    })
+    .await
+    .map_err(|e| format!("Task join error: {}", e))?
+}
+
+const UTF8_BOM: &[u8] = b"\xEF\xBB\xBF";
+
+/// Encoding issues in `file` and, depth-first in reading order, the files it includes
+fn walk_encoding(
+    project_dir: &Path,
+    file: String,
+    visited: &mut HashSet<String>,
+    issues: &mut Vec<EncodingIssue>,
+) {
+    if !visited.insert(file.clone()) {
+        return;
+    }
+    let Some(bytes) = latex::normalize_relative(Path::new(&file))
+        .and_then(|relative| std::fs::read(project_dir.join(relative)).ok())
+    else {
+        return;
+    };
+
+    if bytes.starts_with(UTF8_BOM) {
+        issues.push(EncodingIssue::Bom { file: file.clone() });
+    }
+
+    let mut first_invalid = None;
+    let mut count = 0;
+    let mut pos = 0;
+    while let Err(e) = std::str::from_utf8(&bytes[pos..]) {
+        let offset = pos + e.valid_up_to();
+        first_invalid.get_or_insert(offset);
+        count += 1;
+        match e.error_len() {
+            Some(len) => pos = offset + len,
+            // Cut off at the end of the file
+            None => break,
+        }
+    }
+    if let Some(offset) = first_invalid {
+        let line = bytes[..offset].iter().filter(|b| **b == b'\n').count() + 1;
+        issues.push(EncodingIssue::InvalidUtf8 {
+            file: file.clone(),
+            offset,
+            line,
+            count,
+        });
+    }
+
+    for (target, _) in latex::include_targets(&String::from_utf8_lossy(&bytes)) {
+        if let Some(child) = latex::resolve_tex_input(project_dir, &target) {
+            walk_encoding(project_dir, child, visited, issues);
+        }
+    }
+}
+
+/// UTF-8 byte order marks and invalid UTF-8 in the document rooted at `root_file`
+#[tauri::command]
+pub async fn validate_encoding(
+    project_path: String,
+    root_file: String,
+) -> Result<Vec<EncodingIssue>, String> {
+    let project_dir = PathBuf::from(&project_path);
+    let root_file = latex::project_file(&root_file)?;
+    if !project_dir.join(&root_file).is_file() {
+        return Err(format!("File not found: {}", root_file));
+    }
+
+    let mut issues = Vec::new();
+    walk_encoding(&project_dir, root_file, &mut HashSet::new(), &mut issues);
+    Ok(issues)
+}
+
+/// The `backend=` value in a biblatex option list
+fn biblatex_backend(options: &str) -> Option<&str> {
+    options.split(',').find_map(|option| {
+        let (key, value) = option.split_once('=')?;
+        (key.trim() == "backend").then(|| value.trim())
+    })
+}
+
+/// Which bibliography program the document rooted at `root_file` needs. biblatex
+/// defaults to biber unless its `backend` option names a BibTeX variant; otherwise a
+/// \bibliography or \bibliographystyle command means BibTeX. A hand-written
+/// thebibliography needs neither.
+pub(crate) fn detect_bib_engine(
+    project_dir: &Path,
+    root_file: &str,
+) -> Result<Option<BibEngine>, String> {
+    let sources = latex::load_sources(project_dir, root_file)?;
+    let mut uses_bibtex_commands = false;
+
+    for source in &sources {
+        let masked = latex::mask_comments(&source.content);
+        for command in latex::commands(&masked) {
+            match command.name {
+                "usepackage" | "RequirePackage" => {
+                    let args = latex::read_args(&masked, command.end, 2);
+                    let loads_biblatex = latex::first_required(&args)
+                        .is_some_and(|names| names.text.split(',').any(|n| n.trim() == "biblatex"));
+                    if !loads_biblatex {
+                        continue;
+                    }
+
+                    let backend = latex::leading_optional(&args)
+                        .and_then(|options| biblatex_backend(&options.text))
+                        .unwrap_or("biber");
+                    return Ok(Some(if backend.starts_with("bibtex") {
+                        BibEngine::Bibtex
+                    } else {
+                        BibEngine::Biber
+                    }));
+                }
+                "bibliography" | "bibliographystyle" => uses_bibtex_commands = true,
+                _ => {}
+            }
+        }
+    }
+
+    Ok(uses_bibtex_commands.then_some(BibEngine::Bibtex))
+}
+
+#[tauri::command]
+pub async fn analyze_preamble(
+    project_path: String,
+    root_file: String,
+) -> Result<PreambleInfo, String> {
+    let project_dir = PathBuf::from(&project_path);
+    let root_file = latex::project_file(&root_file)?;
+    let content = latex::read_source(&project_dir, &root_file)?;
+
+    let mut info = PreambleInfo::default();
+    let mut visited = HashSet::new();
+    scan_preamble(
+        &project_dir,
+        &root_file,
+        &content,
+        false,
+        &mut visited,
+        &mut info,
+    );
+    info.detected_bib_engine = detect_bib_engine(&project_dir, &root_file)?;
+
+    Ok(info)
+}
+
+/// The options of a package load, whatever their order and spacing
+fn option_set(options: Option<&str>) -> HashSet<String> {
+    options
+        .unwrap_or("")
+        .split(',')
+        .map(|option| option.split_whitespace().collect::<String>())
+        .filter(|option| !option.is_empty())
+        .collect()
+}
+
+/// The packages the preamble of `content`, the text of `root_file`, loads, including
+/// from the files it includes there
+pub(crate) fn preamble_packages(
+    project_dir: &Path,
+    root_file: &str,
+    content: &str,
+) -> Vec<PackageInfo> {
+    let mut info = PreambleInfo::default();
+    let mut visited = HashSet::new();
+    scan_preamble(
+        project_dir,
+        root_file,
+        content,
+        false,
+        &mut visited,
+        &mut info,
+    );
+    info.packages
+}
+
+#[tauri::command]
+pub async fn package_audit(
+    project_path: String,
+    root_file: String,
+) -> Result<Vec<PackageIssue>, String> {
+    let project_dir = PathBuf::from(&project_path);
+    let root_file = latex::project_file(&root_file)?;
+    let content = latex::read_source(&project_dir, &root_file)?;
+
+    let packages = preamble_packages(&project_dir, &root_file, &content);
+
+    // Where in packages each package is loaded, in the order of their first loads
+    let mut loads: Vec<(&str, Vec<usize>)> = Vec::new();
+    for (i, package) in packages.iter().enumerate() {
+        match loads.iter_mut().find(|(name, _)| *name == package.name) {
+            Some((_, indices)) => indices.push(i),
+            None => loads.push((&package.name, vec![i])),
+        }
+    }
+    let loads_of = |indices: &[usize]| -> Vec<PackageInfo> {
+        indices.iter().map(|&i| packages[i].clone()).collect()
+    };
+
+    let mut issues = Vec::new();
+    for (name, indices) in &loads {
+        if indices.len() < 2 {
+            continue;
+        }
+        let first = &packages[indices[0]];
+        let first_options = option_set(first.options.as_deref());
+        let mut added: Vec<String> = Vec::new();
+        for &i in &indices[1..] {
+            for option in option_set(packages[i].options.as_deref()) {
+                if !first_options.contains(&option) && !added.contains(&option) {
+                    added.push(option);
+                }
+            }
+        }
+
+        let (kind, message) = if added.is_empty() {
+            (
+                PackageIssueKind::Duplicate,
+                format!(
+                    "{} is loaded {} times; only the load at {}:{} does anything",
+                    name,
+                    indices.len(),
+                    first.file,
+                    first.line
+                ),
+            )
+        } else {
+            (
+                PackageIssueKind::OptionClash,
+                format!(
+                    "{} is loaded again with {} after the load at {}:{} without; give all of its options there",
+                    name,
+                    added.join(", "),
+                    first.file,
+                    first.line
+                ),
+            )
+        };
+        issues.push(PackageIssue {
+            kind,
+            packages: vec![name.to_string()],
+            message,
+            loads: loads_of(indices),
+        });
+    }
+
+    for (a, b, advice) in CONFLICTING_PACKAGES {
+        let indices_of = |wanted: &str| {
+            loads
+                .iter()
+                .find(|(name, _)| *name == wanted)
+                .map(|(_, indices)| indices)
+        };
+        let (Some(a_indices), Some(b_indices)) = (indices_of(a), indices_of(b)) else {
+            continue;
+        };
+        let mut indices: Vec<usize> = a_indices.iter().chain(b_indices).copied().collect();
+        indices.sort_unstable();
+        issues.push(PackageIssue {
+            kind: PackageIssueKind::Conflict,
+            packages: vec![a.to_string(), b.to_string()],
+            message: format!("{} and {} don't work together: {}", a, b, advice),
+            loads: loads_of(&indices),
+        });
+    }
+
+    Ok(issues)
+}
+
+#[tauri::command]
+pub async fn label_issues(project_path: String, root_file: String) -> Result<LabelIssues, String> {
+    let sources = latex::load_sources(&PathBuf::from(&project_path), &root_file)?;
+
+    let mut labels = Vec::new();
+    let mut referenced = HashSet::new();
+
+    for source in &sources {
+        let masked = latex::mask_comments(&source.content);
+        let lines = LineIndex::new(&masked);
+
+        for command in latex::commands(&masked) {
+            if command.name == "label" {
+                let args = latex::read_args(&masked, command.end, 1);
+                if let Some(name) = latex::first_required(&args) {
+                    labels.push(LabelInfo {
+                        name: name.text.trim().to_string(),
+                        file: source.path.clone(),
+                        line: lines.line(command.start),
+                    });
+                }
+            } else if REFERENCE_COMMANDS.contains(&command.name) {
+                let args = latex::read_args(&masked, command.end, 1);
+                if let Some(names) = latex::first_required(&args) {
+                    referenced.extend(
+                        names
+                            .text
+                            .split(',')
+                            .map(|n| n.trim().to_string())
+                            .filter(|n| !n.is_empty()),
+                    );
+                }
+            } else if command.name == "hyperref" {
+                // \hyperref[label]{text}
+                let args = latex::read_args(&masked, command.end, 1);
+                if let Some(name) = latex::leading_optional(&args) {
+                    referenced.insert(name.text.trim().to_string());
+                }
+            }
+        }
+    }
+
+    let mut definitions: HashMap<&str, usize> = HashMap::new();
+    for label in &labels {
+        *definitions.entry(label.name.as_str()).or_default() += 1;
+    }
+
+    let mut issues = LabelIssues::default();
+    for label in &labels {
+        if !referenced.contains(&label.name) {
+            issues.unused.push(label.clone());
+        }
+        if definitions[label.name.as_str()] > 1 {
+            issues.duplicates.push(label.clone());
+        }
+    }
+
+    Ok(issues)
+}
+
+/// Byte range of the text inside the `open`...`close` group at or after `pos`
+fn group_range(masked: &str, pos: usize, open: u8, close: u8) -> Option<Range<usize>> {
+    let start = latex::skip_arg_whitespace(masked.as_bytes(), pos);
+    let (_, end) = latex::read_group(masked, start, open, close)?;
+    Some(start + 1..end - 1)
+}
+
+/// Ranges in `content` where `key` is defined by \label or referred to, and whether
+/// any of them is a definition
+fn label_occurrences(content: &str, key: &str) -> (Vec<Range<usize>>, bool) {
+    let masked = latex::mask_comments(content);
+    let mut ranges = Vec::new();
+    let mut defined = false;
+
+    for command in latex::commands(&masked) {
+        let names = if command.name == "label" || REFERENCE_COMMANDS.contains(&command.name) {
+            group_range(&masked, command.end, b'{', b'}')
+        } else if command.name == "hyperref" {
+            group_range(&masked, command.end, b'[', b']')
+        } else {
+            None
+        };
+        let Some(names) = names else {
+            continue;
+        };
+
+        // Reference commands such as \cref take a comma-separated list
+        let mut start = names.start;
+        for name in masked[names.clone()].split(',') {
+            let trimmed = name.trim();
+            if trimmed == key {
+                let offset = start + name.find(trimmed).unwrap_or(0);
+                ranges.push(offset..offset + trimmed.len());
+                defined |= command.name == "label";
+            }
+            start += name.len() + 1;
+        }
+    }
+
+    (ranges, defined)
+}
+
+/// Rename a label in every .tex file of the project, rewriting its \label and each
+/// command that refers to it. Returns the files that were changed.
+#[tauri::command]
+pub async fn rename_label(
+    project_path: String,
+    old_key: String,
+    new_key: String,
+) -> Result<Vec<String>, String> {
+    let project_dir = PathBuf::from(&project_path);
+    let old_key = old_key.trim();
+    let new_key = new_key.trim();
+    if new_key.is_empty() || new_key.contains([',', '{', '}', '\\', '%', '#']) {
+        return Err(format!("Invalid label name: {}", new_key));
+    }
+    if old_key.is_empty() || new_key == old_key {
+        return Ok(Vec::new());
+    }
+
+    let meta = read_project_meta(&project_dir)?;
+    let hidden = PathPatterns::new(&meta.tree_ignore, "tree_ignore")?;
+    let mut files = Vec::new();
+    let mut links = SymlinkPolicy::new(&project_dir, meta.allow_symlinks);
+    collect_files(&project_dir, &project_dir, &hidden, &mut links, &mut files);
+    files.retain(|file| file.ends_with(".tex"));
+
+    // Check every file before writing any, so a refusal leaves the project untouched
+    let mut edits = Vec::new();
+    for file in files {
+        let content = latex::read_source(&project_dir, &file)?;
+        if label_occurrences(&content, new_key).1 {
+            return Err(format!("Label {} already exists in {}", new_key, file));
+        }
+        let (ranges, _) = label_occurrences(&content, old_key);
+        if !ranges.is_empty() {
+            edits.push((file, content, ranges));
+        }
+    }
+    if edits.is_empty() {
+        return Err(format!("Label {} is not used in any .tex file", old_key));
+    }
+
+    let mut changed = Vec::new();
+    for (file, mut content, ranges) in edits {
+        for range in ranges.into_iter().rev() {
+            content.replace_range(range, new_key);
+        }
+        write_project_file(&project_path, &file, &content)?;
+        changed.push(file);
+    }
+
+    Ok(changed)
+}
+
+/// Where `path` is after moving `old` to `new`, if it was `old` or inside it
+fn moved_path(path: &str, old: &str, new: &str) -> Option<String> {
+    if path == old {
+        return Some(new.to_string());
+    }
+    path.strip_prefix(&format!("{}/", old))
+        .map(|rest| format!("{}/{}", new, rest))
+}
+
+/// The argument an \input or \include of a file moved from `old` to `new` needs now, or
+/// None if it named another file. Names without `.tex` keep leaving it out.
+fn moved_input(project_dir: &Path, target: &str, old: &str, new: &str) -> Option<String> {
+    let relative = latex::normalize_relative(Path::new(target))?;
+    let as_given = latex::to_project_path(&relative);
+    let mut candidates = vec![(as_given.clone(), false)];
+    if relative.extension().is_none_or(|ext| ext != "tex") {
+        candidates.insert(0, (format!("{}.tex", as_given), true));
+    }
+
+    // The first candidate still on disk is what TeX reads, moved or not
+    for (candidate, appended) in candidates {
+        if project_dir.join(&candidate).is_file() {
+            return None;
+        }
+        let Some(moved) = moved_path(&candidate, old, new) else {
+            continue;
+        };
+        if project_dir.join(&moved).is_file() {
+            return Some(match moved.strip_suffix(".tex").filter(|_| appended) {
+                Some(stem) => stem.to_string(),
+                None => moved,
+            });
+        }
+    }
+    None
+}
+
+/// The argument an \includegraphics of a graphic moved from `old` to `new` needs now,
+/// relative to the \graphicspath entry it was found through when the new location is
+/// still under it, or else to the project root. Names without an extension keep
+/// leaving it out.
+fn moved_graphic(
+    project_dir: &Path,
+    search_dirs: &[String],
+    target: &str,
+    old: &str,
+    new: &str,
+) -> Option<String> {
+    let has_extension = Path::new(target).extension().is_some_and(|ext| {
+        GRAPHICS_EXTENSIONS.contains(&ext.to_string_lossy().to_lowercase().as_str())
+    });
+    let mut names = vec![(target.to_string(), None)];
+    if !has_extension {
+        names = GRAPHICS_EXTENSIONS
+            .iter()
+            .map(|ext| (format!("{}.{}", target, ext), Some(*ext)))
+            .chain(names)
+            .collect();
+    }
+
+    for dir in search_dirs {
+        for (name, appended) in &names {
+            let Some(relative) = graphic_candidate(dir, name) else {
+                continue;
+            };
+            let candidate = latex::to_project_path(&relative);
+            if project_dir.join(&candidate).is_file() {
+                return None;
+            }
+            let Some(moved) = moved_path(&candidate, old, new) else {
+                continue;
+            };
+            if !project_dir.join(&moved).is_file() {
+                continue;
+            }
+
+            let dir = latex::normalize_relative(Path::new(dir))
+                .map(|dir| latex::to_project_path(&dir))
+                .unwrap_or_default();
+            let mut argument = match moved.strip_prefix(&format!("{}/", dir)) {
+                Some(rest) if !dir.is_empty() => rest.to_string(),
+                _ => moved.clone(),
+            };
+            if let Some(ext) = appended {
+                if let Some(stem) = argument.strip_suffix(&format!(".{}", ext)) {
+                    argument = stem.to_string();
+                }
+            }
+            return Some(argument);
+        }
+    }
+    None
+}
+
+/// After `old_path` was moved to `new_path`, e.g. with rename_path, rewrite every
+/// \input, \include, \subfile and \includegraphics in the project's .tex files that
+/// named it or a file inside it. TeX resolves these from where it runs rather than
+/// from the file they're in, so the new path is the same from every file; graphics
+/// found through \graphicspath stay relative to it while they're still inside it.
+/// Returns the files that were changed.
+#[tauri::command]
+pub async fn update_references_after_move(
+    project_path: String,
+    old_path: String,
+    new_path: String,
+) -> Result<Vec<String>, String> {
+    let project_dir = PathBuf::from(&project_path);
+    let old = latex::project_file(&old_path)?;
+    let new = latex::project_file(&new_path)?;
+    if old == new {
+        return Ok(Vec::new());
+    }
+    if !project_dir.join(&new).exists() {
+        return Err(format!("{} does not exist; move the file first", new));
+    }
+
+    let meta = read_project_meta(&project_dir)?;
+    let hidden = PathPatterns::new(&meta.tree_ignore, "tree_ignore")?;
+    let mut files = Vec::new();
+    let mut links = SymlinkPolicy::new(&project_dir, meta.allow_symlinks);
+    collect_files(&project_dir, &project_dir, &hidden, &mut links, &mut files);
+    files.retain(|file| file.ends_with(".tex"));
+
+    let mut sources = Vec::new();
+    for file in files {
+        let content = latex::read_source(&project_dir, &file)?;
+        let masked = latex::mask_comments(&content);
+        sources.push((file, content, masked));
+    }
+    let masked: Vec<String> = sources
+        .iter()
+        .map(|(_, _, masked)| masked.clone())
+        .collect();
+    let search_dirs = graphics_search_dirs(&masked);
+
+    let mut changed = Vec::new();
+    for (file, mut content, masked) in sources {
+        let mut edits = Vec::new();
+        for command in latex::commands(&masked) {
+            let is_graphic = command.name == "includegraphics";
+            if !is_graphic && !latex::INCLUDE_COMMANDS.contains(&command.name) {
+                continue;
+            }
+            let mut pos = latex::skip_arg_whitespace(masked.as_bytes(), command.end);
+            if is_graphic {
+                if let Some((_, end)) = latex::read_group(&masked, pos, b'[', b']') {
+                    pos = end;
+                }
+            }
+            let Some(range) = group_range(&masked, pos, b'{', b'}') else {
+                continue;
+            };
+
+            let argument = masked[range.clone()].trim();
+            let quoted = argument.len() > 1 && argument.starts_with('"') && argument.ends_with('"');
+            let target = argument.trim_matches('"');
+            if target.is_empty() || target.contains(['\\', '#']) {
+                continue;
+            }
+            let moved = if is_graphic {
+                moved_graphic(&project_dir, &search_dirs, target, &old, &new)
+            } else {
+                moved_input(&project_dir, target, &old, &new)
+            };
+            if let Some(moved) = moved {
+                let moved = if quoted {
+                    format!("\"{}\"", moved)
+                } else {
+                    moved
+                };
+                let start = range.start + masked[range.clone()].find(argument).unwrap_or(0);
+                edits.push((start..start + argument.len(), moved));
+            }
+        }
+        if edits.is_empty() {
+            continue;
+        }
+
+        for (range, moved) in edits.into_iter().rev() {
+            content.replace_range(range, &moved);
+        }
+        write_project_file(&project_path, &file, &content)?;
+        changed.push(file);
+    }
+
+    Ok(changed)
+}
+
+/// Commands that end the document's last section, so split_file leaves what follows them
+/// in the file
+const BACK_MATTER_COMMANDS: &[&str] = &[
+    "appendix",
+    "backmatter",
+    "bibliography",
+    "printbibliography",
+    "printindex",
+];
+
+/// Longest a file name split_file derives from a heading title is, before `.tex`
+const MAX_SPLIT_NAME_CHARS: usize = 40;
+
+/// Start of the line `offset` is on when only whitespace comes before it there, so a
+/// heading moves with its indentation
+fn line_start_before(content: &str, offset: usize) -> usize {
+    let line_start = content[..offset].rfind('\n').map_or(0, |i| i + 1);
+    if content[line_start..offset].trim().is_empty() {
+        line_start
+    } else {
+        offset
+    }
+}
+
+/// A file name for the `index`th part split off, from its heading's title, e.g.
+/// `03-related-work`
+fn split_name(index: usize, title: &str) -> String {
+    let mut slug = String::new();
+    for c in prose::file_prose(title).to_lowercase().chars() {
+        if c.is_ascii_alphanumeric() {
+            slug.push(c);
+        } else if !slug.is_empty() && !slug.ends_with('-') {
+            slug.push('-');
+        }
+    }
+    let slug: String = slug.chars().take(MAX_SPLIT_NAME_CHARS).collect();
+    let slug = slug.trim_matches('-');
+    match slug {
+        "" => format!("{:02}", index),
+        slug => format!("{:02}-{}", index, slug),
+    }
+}
+
+/// Split `file_path` at each heading of sectioning `level`, e.g. `section`, moving each
+/// one and everything up to the next heading at its level or above into a file of its
+/// own under `directory`, and leaving an \input of that file in its place. The files
+/// are numbered in document order and named after the titles. `directory` defaults to
+/// one named after the file, next to it, e.g. `chapters/methods` for
+/// chapters/methods.tex.
+///
+/// Only the body is split, so the preamble and \begin{document} stay where they are,
+/// and headings inside an environment are left alone. The last part ends before
+/// \appendix, the bibliography or the index. \input paths are relative to the project
+/// root, where TeX resolves them. Nothing is written if any of the files exists.
+/// Returns the files created.
+#[tauri::command]
+pub async fn split_file(
+    project_path: String,
+    file_path: String,
+    level: String,
+    directory: Option<String>,
+) -> Result<Vec<String>, String> {
+    let project_dir = PathBuf::from(&project_path);
+    let file_path = latex::project_file(&file_path)?;
+    let level = level.trim().trim_start_matches('\\');
+    let depth = latex::SECTIONING_COMMANDS
+        .iter()
+        .position(|command| *command == level)
+        .ok_or_else(|| format!("Unknown sectioning level: {}", level))?;
+    let directory = match directory {
+        Some(directory) => latex::project_file(&directory)?,
+        None => match file_path.strip_suffix(".tex") {
+            Some(stem) => stem.to_string(),
+            None => format!("{}-parts", file_path),
+        },
+    };
+    if directory.is_empty() {
+        return Err("Invalid directory: the project root".to_string());
+    }
+
+    let content = latex::read_source(&project_dir, &file_path)?;
+    let masked = latex::mask_comments(&content);
+
+    // A file without \begin{document} is included by another and all body
+    let body_start = match latex::find_begin_document(&masked) {
+        Some(begin) => latex::skip_args(&masked, begin + "\\begin".len(), 1),
+        None => 0,
+    };
+    let body_end = line_start_before(
+        &content,
+        latex::find_end_document(&masked).unwrap_or(masked.len()),
+    );
+    let nested: Vec<Range<usize>> = latex::environments(&masked)
+        .into_iter()
+        .filter(|environment| environment.name != "document")
+        .map(|environment| environment.span)
+        .collect();
+
+    // Where each part starts, with its title, and where parts have to end
+    let mut headings = Vec::new();
+    let mut ends = vec![body_end];
+    for command in latex::commands(&masked) {
+        if command.start < body_start || command.start >= body_end {
+            continue;
+        }
+        if nested.iter().any(|span| span.contains(&command.start)) {
+            continue;
+        }
+        let heading_depth = latex::SECTIONING_COMMANDS
+            .iter()
+            .position(|name| *name == command.name);
+        if BACK_MATTER_COMMANDS.contains(&command.name) || heading_depth.is_some_and(|d| d <= depth)
+        {
+            ends.push(line_start_before(&content, command.start));
+        }
+        if heading_depth == Some(depth) {
+            let args = latex::read_args(&masked, command.end, 2);
+            let title = latex::first_required(&args).map(|arg| arg.text.clone());
+            headings.push((
+                line_start_before(&content, command.start),
+                title.unwrap_or_default(),
+            ));
+        }
+    }
+    if headings.is_empty() {
+        return Err(format!("No \\{} to split {} at", level, file_path));
+    }
+
+    let mut parts = Vec::new();
+    for (index, (start, title)) in headings.iter().enumerate() {
+        let end = ends
+            .iter()
+            .copied()
+            .filter(|end| end > start)
+            .min()
+            .unwrap_or(body_end);
+        let file = format!("{}/{}.tex", directory, split_name(index + 1, title));
+        if project_dir.join(&file).exists() {
+            return Err(format!("{} already exists", file));
+        }
+        parts.push((*start..end, file));
+    }
+
+    std::fs::create_dir_all(project_dir.join(&directory))
+        .map_err(|e| format!("Failed to create {}: {}", directory, e))?;
+    let mut created = Vec::new();
+    let mut split = content.clone();
+    for (range, file) in parts.into_iter().rev() {
+        let part = &content[range.clone()];
+        let text = part.trim_end();
+        write_project_file(&project_path, &file, &format!("{}\n", text))?;
+
+        // The blank lines after the part stay, keeping the input apart from what follows
+        let trailing = &part[text.len()..];
+        let trailing = if trailing.contains('\n') {
+            trailing
+        } else {
+            "\n"
+        };
+        let input = file.strip_suffix(".tex").unwrap_or(&file);
+        split.replace_range(range, &format!("\\input{{{}}}{}", input, trailing));
+        created.push(file);
+    }
+    write_project_file(&project_path, &file_path, &split)?;
+
+    created.reverse();
+    Ok(created)
+}
+
+#[tauri::command]
+pub async fn word_count(project_path: String, root_file: String) -> Result<WordCount, String> {
+    let project_dir = PathBuf::from(&project_path);
+    let targets = read_project_meta(&project_dir)?.word_targets;
+
+    let files: Vec<FileWordCount> = prose::prose_by_file(&project_dir, &root_file)?
+        .into_iter()
+        .map(|(file, text)| FileWordCount {
+            words: prose::words(&text).count(),
+            characters: text.chars().filter(|c| !c.is_whitespace()).count(),
+            target: targets.get(&file).copied(),
+            file,
+        })
+        .collect();
+
+    // Files are split where one includes another, which is always between words
+    Ok(WordCount {
+        words: files.iter().map(|f| f.words).sum(),
+        characters: files.iter().map(|f| f.characters).sum(),
+        files,
+    })
+}
+
+/// Words added and removed in `file_path` this session, comparing `content` (the editor
+/// buffer, or the file on disk when it isn't given) with the prose the file had when
+/// the session started. The first call for a file starts its session from the file as
+/// saved, as does the first call after midnight, so the counts are words written today.
+/// Prose is counted as word_count counts it. Sessions aren't kept across restarts.
+#[tauri::command]
+pub async fn session_stats(
+    project_path: String,
+    file_path: String,
+    content: Option<String>,
+) -> Result<SessionDelta, String> {
+    let file_path = latex::project_file(&file_path)?;
+    tauri::async_runtime::spawn_blocking(move || {
+        let project_dir = PathBuf::from(&project_path);
+        let saved = latex::read_source(&project_dir, &file_path)?;
+        let now = chrono::Local::now();
+        let counts = |content: &str| {
+            let prose = prose::file_prose(content);
+            let words: Vec<String> = prose::words(&prose).map(str::to_string).collect();
+            let characters = prose.chars().filter(|c| !c.is_whitespace()).count();
+            (words, characters)
+        };
+
+        let mut sessions = SESSIONS.lock().unwrap();
+        let key = (project_path.clone(), file_path.clone());
+        let today = now.date_naive();
+        if sessions
+            .get(&key)
+            .is_none_or(|baseline| baseline.day != today)
+        {
+            // A session left from an earlier day starts over from the text as it is now
+            let start = if sessions.contains_key(&key) {
+                content.as_deref().unwrap_or(&saved)
+            } else {
+                &saved
+            };
+            let (words, characters) = counts(start);
+            let baseline = SessionBaseline {
+                day: today,
+                started_at: now.to_rfc3339(),
+                words,
+                characters,
+            };
+            sessions.insert(key.clone(), baseline);
+        }
+        let baseline = &sessions[&key];
+
+        let (words, characters) = counts(content.as_deref().unwrap_or(&saved));
+        let (words_removed, words_added) = diff::count_changes(&baseline.words, &words);
+        Ok(SessionDelta {
+            words: words.len(),
+            characters,
+            words_added,
+            words_removed,
+            characters_delta: characters as i64 - baseline.characters as i64,
+            since: baseline.started_at.clone(),
+        })
+    })
+    .await
+    .map_err(|e| format!("Task join error: {}", e))?
+}
+
+#[tauri::command]
+pub async fn readability(
+    project_path: String,
+    root_file: String,
+    words_per_minute: Option<u32>,
+) -> Result<Readability, String> {
+    let words_per_minute = words_per_minute.unwrap_or(WORDS_PER_MINUTE);
+    if words_per_minute == 0 {
+        return Err("Words per minute must be greater than zero".to_string());
+    }
+
+    let text = prose::document_prose(&PathBuf::from(&project_path), &root_file)?;
+    let words: Vec<&str> = prose::words(&text).collect();
+    let sentences = prose::count_sentences(&text);
+    let syllables: usize = words.iter().map(|w| prose::syllables(w)).sum();
+
+    let word_total = words.len() as f64;
+    let average_sentence_length = if sentences > 0 {
+        word_total / sentences as f64
+    } else {
+        0.0
+    };
+    let average_syllables_per_word = if words.is_empty() {
+        0.0
+    } else {
+        syllables as f64 / word_total
+    };
+    let flesch_reading_ease = (sentences > 0 && !words.is_empty())
+        .then_some(206.835 - 1.015 * average_sentence_length - 84.6 * average_syllables_per_word);
+
+    Ok(Readability {
+        words: words.len(),
+        sentences,
+        reading_time_minutes: word_total / words_per_minute as f64,
+        average_sentence_length,
+        average_syllables_per_word,
+        flesch_reading_ease,
+    })
+}
+
+/// The dictionary for `language` (e.g. `en_US`), loaded once from the app's
+/// `dictionaries` directory or a system hunspell directory
+fn dictionary(language: &str) -> Result<Arc<Dictionary>, String> {
+    if language.is_empty()
+        || !language
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+    {
+        return Err(format!("Invalid dictionary language: {}", language));
+    }
+
+    if let Some((_, loaded)) = DICTIONARIES
+        .lock()
+        .unwrap()
+        .iter()
+        .find(|(name, _)| name == language)
+    {
+        return Ok(loaded.clone());
+    }
+
+    let mut dirs = vec![get_config_dir()?.join("dictionaries")];
+    dirs.extend(SYSTEM_DICTIONARY_DIRS.iter().map(PathBuf::from));
+    let dir = dirs
+        .into_iter()
+        .find(|dir| dir.join(format!("{}.dic", language)).is_file())
+        .ok_or_else(|| format!("No dictionary installed for {}", language))?;
+
+    let loaded = Arc::new(Dictionary::load(&dir, language)?);
+    DICTIONARIES
+        .lock()
+        .unwrap()
+        .push((language.to_string(), loaded.clone()));
+    Ok(loaded)
+}
+
+/// Misspelled words in the prose of one file. Commands, math, comments and
+/// single-letter and all-caps words are skipped, as are the project's personal words and
+/// those the user accepts in every project.
+#[tauri::command]
+pub async fn spell_check(
+    project_path: String,
+    file_path: String,
+    language: String,
+) -> Result<Vec<SpellIssue>, String> {
+    tauri::async_runtime::spawn_blocking(move || {
+        let project_dir = PathBuf::from(&project_path);
+        let file_path = latex::project_file(&file_path)?;
+        let content = latex::read_source(&project_dir, &file_path)?;
+        let mut personal: HashSet<String> = read_project_meta(&project_dir)?
+            .personal_words
+            .into_iter()
+            .collect();
+        personal.extend(read_user_words()?);
+        let dictionary = dictionary(&language)?;
+
+        let index = LineIndex::new(&content);
+        let issues = prose::file_words(&content)
+            .into_iter()
+            .filter(|(_, word)| {
+                let letters = word.chars().filter(|c| c.is_alphabetic()).count();
+                letters > 1
+                    && !word.chars().any(|c| c.is_numeric())
+                    && word.chars().any(char::is_lowercase)
+            })
+            .filter(|(_, word)| {
+                !personal.contains(word)
+                    && !personal.contains(&word.to_lowercase())
+                    && !dictionary.check(word)
+            })
+            .map(|(offset, word)| {
+                let line_start = content[..offset].rfind('\n').map_or(0, |i| i + 1);
+                SpellIssue {
+                    word,
+                    line: index.line(offset),
+                    column: content[line_start..offset].chars().count() + 1,
+                }
+            })
+            .collect();
+
+        Ok(issues)
+    })
+    .await
+    .map_err(|e| format!("Task join error: {}", e))?
+}
+
+#[tauri::command]
+pub async fn list_floats(
+    project_path: String,
+    root_file: String,
+) -> Result<Vec<FloatInfo>, String> {
+    let sources = latex::load_sources(&PathBuf::from(&project_path), &root_file)?;
+    let mut floats = Vec::new();
+
+    for source in &sources {
+        let masked = latex::mask_comments(&source.content);
+        let lines = LineIndex::new(&masked);
+
+        for command in latex::commands(&masked).filter(|c| c.name == "begin") {
+            let args = latex::read_args(&masked, command.end, 1);
+            let Some(environment) = latex::first_required(&args).map(|a| a.text.trim()) else {
+                continue;
+            };
+            let Some(&(_, is_figure)) = FLOAT_ENVIRONMENTS.iter().find(|(e, _)| *e == environment)
+            else {
+                continue;
+            };
+
+            let body_start = latex::skip_args(&masked, command.end, 1);
+            let closing = format!("\\end{{{}}}", environment);
+            let body_end = masked[body_start..]
+                .find(&closing)
+                .map_or(masked.len(), |i| body_start + i);
+            let body = &masked[body_start..body_end];
+
+            let mut float = FloatInfo {
+                kind: if is_figure {
+                    FloatKind::Figure
+                } else {
+                    FloatKind::Table
+                },
+                environment: environment.to_string(),
+                caption: None,
+                label: None,
+                graphics: Vec::new(),
+                file: source.path.clone(),
+                line: lines.line(command.start),
+            };
+
+            for inner in latex::commands(body) {
+                match inner.name {
+                    // The required argument follows an optional short caption
+                    "caption" if float.caption.is_none() => {
+                        let args = latex::read_args(body, inner.end, 2);
+                        float.caption = latex::first_required(&args)
+                            .map(|a| a.text.split_whitespace().collect::<Vec<_>>().join(" "));
+                    }
+                    "label" if float.label.is_none() => {
+                        let args = latex::read_args(body, inner.end, 1);
+                        float.label =
+                            latex::first_required(&args).map(|a| a.text.trim().to_string());
+                    }
+                    "includegraphics" => {
+                        let args = latex::read_args(body, inner.end, 2);
+                        if let Some(path) = latex::first_required(&args) {
+                            float.graphics.push(path.text.trim().to_string());
+                        }
+                    }
+                    _ => {}
+                }
+            }
+
+            floats.push(float);
+        }
+    }
+
+    Ok(floats)
+}
+
+/// Acronyms found on a walk through the document in reading order
+#[derive(Default)]
+struct AcronymScan {
+    /// Glossary package definitions: the key and where it is defined
+    definitions: Vec<(String, String, usize)>,
+    /// Short forms the definitions give, which the package expands on its own
+    short_forms: HashSet<String>,
+    /// Keys of glossary entries that aren't acronyms
+    entries: HashSet<String>,
+    /// First use of each glossary key
+    uses: Vec<(String, String, usize)>,
+    /// First occurrence of each all-caps word in the prose, and whether its full form is
+    /// given right there
+    plain: Vec<(String, String, usize, bool)>,
+    visited: HashSet<String>,
+}
+
+/// `word` without a plural "s" if it reads as an acronym: two or more capitals, maybe
+/// with digits, and not a Roman numeral
+fn as_acronym(word: &str) -> Option<&str> {
+    let word = word.strip_suffix('s').unwrap_or(word);
+    let capitals = word.chars().filter(char::is_ascii_uppercase).count();
+    let acronym = capitals >= 2
+        && word.starts_with(|c: char| c.is_ascii_uppercase())
+        && word
+            .chars()
+            .all(|c| c.is_ascii_uppercase() || c.is_ascii_digit());
+    let roman = word.chars().all(|c| "IVXLCDM".contains(c));
+    (acronym && !roman).then_some(word)
+}
+
+/// Whether the occurrence of an acronym at `start..end` of `content` comes with its full
+/// form: wrapped in parentheses after it, or followed by it in parentheses
+fn is_expanded(content: &str, start: usize, end: usize) -> bool {
+    let after = content[end..].strip_prefix('s').unwrap_or(&content[end..]);
+    let wrapped = content[..start].trim_end().ends_with('(') && after.starts_with(')');
+    wrapped || after.trim_start().starts_with('(')
+}
+
+/// Short form an acronym definition gives, when it isn't the key itself
+fn acronym_short_form(name: &str, args: &[latex::Arg]) -> Option<String> {
+    let mut required = args.iter().filter(|a| a.kind == ArgKind::Required);
+    match name {
+        // \newacronym[options]{key}{short}{long}
+        "newacronym" => required.nth(1).map(|a| a.text.trim().to_string()),
+        // \acro{KEY}[short]{long}
+        "acro" | "acrodef" => args
+            .iter()
+            .skip(1)
+            .find(|a| a.kind == ArgKind::Optional)
+            .map(|a| a.text.trim().to_string()),
+        // \DeclareAcronym{key}{short = ..., long = ...}
+        _ => required.nth(1).and_then(|a| {
+            a.text.split(',').find_map(|option| {
+                let (key, value) = option.split_once('=')?;
+                (key.trim() == "short").then(|| value.trim().trim_matches(['{', '}']).to_string())
+            })
+        }),
+    }
+}
+
+/// Something in a file the acronym walk acts on, at its byte offset
+enum AcronymEvent<'a> {
+    Command(latex::Command<'a>),
+    Include(String),
+    Word(String),
+}
+
+/// Read `content` in order, following includes and glossary files where TeX reads them,
+/// so "first use" means first in the typeset document
+fn walk_acronyms(project_dir: &Path, file: &str, content: &str, scan: &mut AcronymScan) {
+    if !scan.visited.insert(file.to_string()) {
+        return;
+    }
+    let masked = latex::mask_comments(content);
+    let lines = LineIndex::new(content);
+
+    let mut events: Vec<(usize, AcronymEvent)> = latex::commands(&masked)
+        .filter(|c| {
+            ACRONYM_DEFINITIONS.contains(&c.name)
+                || ACRONYM_USES.contains(&c.name)
+                || c.name == GLOSSARY_ENTRY
+                || GLOSSARY_INPUTS.contains(&c.name)
+        })
+        .map(|c| (c.start, AcronymEvent::Command(c)))
+        .collect();
+    events.extend(
+        latex::include_targets(content)
+            .into_iter()
+            .map(|(target, offset)| (offset, AcronymEvent::Include(target))),
+    );
+    events.extend(
+        prose::file_words(content)
+            .into_iter()
+            .map(|(offset, word)| (offset, AcronymEvent::Word(word))),
+    );
+    events.sort_by_key(|(offset, _)| *offset);
+
+    for (offset, event) in events {
+        let line = lines.line(offset);
+        let target = match event {
+            AcronymEvent::Include(target) => target,
+            AcronymEvent::Command(command) if GLOSSARY_INPUTS.contains(&command.name) => {
+                let args = latex::read_args(&masked, command.end, 2);
+                match latex::first_required(&args) {
+                    Some(target) => target.text.clone(),
+                    None => continue,
+                }
+            }
+            AcronymEvent::Command(command) => {
+                let args = latex::read_args(&masked, command.end, 4);
+                let Some(key) = latex::first_required(&args).map(|a| a.text.trim().to_string())
+                else {
+                    continue;
+                };
+                if ACRONYM_USES.contains(&command.name) {
+                    if !scan.uses.iter().any(|(used, _, _)| *used == key) {
+                        scan.uses.push((key, file.to_string(), line));
+                    }
+                } else if command.name == GLOSSARY_ENTRY {
+                    scan.entries.insert(key);
+                } else {
+                    scan.short_forms
+                        .extend(acronym_short_form(command.name, &args));
+                    scan.definitions.push((key, file.to_string(), line));
+                }
+                continue;
+            }
+            AcronymEvent::Word(word) => {
+                let Some(acronym) = as_acronym(&word) else {
+                    continue;
+                };
+                if !scan.plain.iter().any(|(seen, _, _, _)| seen == acronym) {
+                    let expanded = is_expanded(content, offset, offset + acronym.len());
+                    scan.plain
+                        .push((acronym.to_string(), file.to_string(), line, expanded));
+                }
+                continue;
+            }
+        };
+
+        let Some(child) = latex::resolve_tex_input(project_dir, &target) else {
+            continue;
+        };
+        if let Ok(child_content) = latex::read_source(project_dir, &child) {
+            walk_acronyms(project_dir, &child, &child_content, scan);
+        }
+    }
+}
+
+/// Acronyms introduced in the prose without their full form, and ones defined for a
+/// glossary package (glossaries, acronym, acro) but never used, or used but never defined
+#[tauri::command]
+pub async fn acronym_check(
+    project_path: String,
+    root_file: String,
+) -> Result<Vec<AcronymIssue>, String> {
+    tauri::async_runtime::spawn_blocking(move || {
+        let project_dir = PathBuf::from(&project_path);
+        let root_file = latex::project_file(&root_file)?;
+        let content = latex::read_source(&project_dir, &root_file)?;
+
+        let mut scan = AcronymScan::default();
+        walk_acronyms(&project_dir, &root_file, &content, &mut scan);
+
+        let defined: HashSet<&str> = scan
+            .definitions
+            .iter()
+            .map(|(k, _, _)| k.as_str())
+            .collect();
+        let used: HashSet<&str> = scan.uses.iter().map(|(k, _, _)| k.as_str()).collect();
+        let issue = |acronym: &str, kind, file: &str, line| AcronymIssue {
+            acronym: acronym.to_string(),
+            kind,
+            file: file.to_string(),
+            line,
+        };
+
+        let mut issues = Vec::new();
+        for (acronym, file, line, expanded) in &scan.plain {
+            // The package spells out the acronyms it manages on first use
+            let managed = defined.contains(acronym.as_str()) || scan.short_forms.contains(acronym);
+            if !expanded && !managed {
+                issues.push(issue(acronym, AcronymIssueKind::Unexpanded, file, *line));
+            }
+        }
+        for (key, file, line) in &scan.definitions {
+            if !used.contains(key.as_str()) {
+                issues.push(issue(key, AcronymIssueKind::Unused, file, *line));
+            }
+        }
+        for (key, file, line) in &scan.uses {
+            if !defined.contains(key.as_str()) && !scan.entries.contains(key) {
+                issues.push(issue(key, AcronymIssueKind::Undefined, file, *line));
+            }
+        }
+
+        Ok(issues)
+    })
+    .await
+    .map_err(|e| format!("Task join error: {}", e))?
+}
+
+/// Duplicate keys, entries missing a field their type requires, and unbalanced braces in
+/// a .bib file, which would otherwise only show as citations quietly left undefined
+#[tauri::command]
+pub async fn validate_bib(
+    project_path: String,
+    bib_path: String,
+) -> Result<Vec<BibDiagnostic>, String> {
+    let bib_path = latex::project_file(&bib_path)?;
+    let content = latex::read_source(Path::new(&project_path), &bib_path)?;
+    let bibliography = bibtex::parse(&content);
+
+    let mut diagnostics: Vec<BibDiagnostic> = bibliography
+        .errors
+        .iter()
+        .map(|error| BibDiagnostic {
+            kind: match error.kind {
+                ParseErrorKind::Unbalanced => BibIssueKind::Unbalanced,
+                ParseErrorKind::Syntax => BibIssueKind::Syntax,
+            },
+            key: None,
+            message: error.message.clone(),
+            line: error.line,
+        })
+        .collect();
+
+    let mut first_lines: HashMap<String, usize> = HashMap::new();
+    for entry in &bibliography.entries {
+        if let Some(first) = first_lines.get(&entry.key.to_lowercase()) {
+            diagnostics.push(BibDiagnostic {
+                kind: BibIssueKind::DuplicateKey,
+                key: Some(entry.key.clone()),
+                message: format!("Key {} is already used on line {}", entry.key, first),
+                line: entry.line,
+            });
+        } else {
+            first_lines.insert(entry.key.to_lowercase(), entry.line);
+        }
+    }
+
+    for entry in &bibliography.entries {
+        let Some((_, required)) = REQUIRED_BIB_FIELDS.iter().find(|(k, _)| *k == entry.kind) else {
+            continue;
+        };
+        // Fields missing here may come from the entry named by crossref
+        let parent = entry.field("crossref").and_then(|crossref| {
+            let key = crossref.value.trim().to_lowercase();
+            bibliography
+                .entries
+                .iter()
+                .find(|e| e.key.to_lowercase() == key)
+        });
+
+        for fields in required.iter() {
+            let present = fields.split('|').any(|name| {
+                entry.field(name).is_some() || parent.is_some_and(|p| p.field(name).is_some())
+            });
+            if !present {
+                diagnostics.push(BibDiagnostic {
+                    kind: BibIssueKind::MissingField,
+                    key: Some(entry.key.clone()),
+                    message: format!(
+                        "@{} {} has no {} field",
+                        entry.kind,
+                        entry.key,
+                        fields.replace('|', " or ")
+                    ),
+                    line: entry.line,
+                });
+            }
+        }
+    }
+
+    diagnostics.sort_by_key(|d| d.line);
+    Ok(diagnostics)
+}
+
+/// The tag for a babel or polyglossia language name, refined by a polyglossia
+/// `variant=` option such as `british`
+fn language_tag(name: &str, variant: Option<&str>) -> Option<String> {
+    let name = name.trim().to_lowercase();
+    let variant = variant.map(|v| v.trim().to_lowercase());
+    let refined = match (name.as_str(), variant.as_deref()) {
+        ("english", Some("british" | "uk")) => Some("british"),
+        ("english", Some("american" | "us" | "usmax")) => Some("american"),
+        ("english", Some("canadian")) => Some("canadian"),
+        ("english", Some("australian")) => Some("australian"),
+        ("german", Some("austrian")) => Some("austrian"),
+        ("german", Some("swiss")) => Some("swissgerman"),
+        ("portuguese", Some("brazilian")) => Some("brazilian"),
+        _ => None,
+    };
+    let name = refined.unwrap_or(&name);
+    LANGUAGE_TAGS
+        .iter()
+        .find(|(n, _)| *n == name)
+        .map(|(_, tag)| tag.to_string())
+}
+
+/// The main language babel gets from `options`: the one `main=` names, otherwise the
+/// last language listed
+fn babel_language(options: &str) -> Option<String> {
+    let options: Vec<&str> = options.split(',').map(str::trim).collect();
+    if let Some(main) = options.iter().find_map(|o| o.strip_prefix("main=")) {
+        return language_tag(main, None);
+    }
+    options.iter().rev().find_map(|o| language_tag(o, None))
+}
+
+/// The language whose stop words are most frequent in `text`, if any clearly is
+fn text_language(text: &str) -> Option<String> {
+    let words: Vec<String> = prose::words(text)
+        .take(LANGUAGE_SAMPLE_WORDS)
+        .map(|w| {
+            w.trim_matches(|c: char| !c.is_alphanumeric())
+                .to_lowercase()
+        })
+        .collect();
+
+    let (tag, hits) = STOP_WORDS
+        .iter()
+        .map(|(tag, stop_words)| {
+            let hits = words
+                .iter()
+                .filter(|w| stop_words.contains(&w.as_str()))
+                .count();
+            (tag, hits)
+        })
+        .max_by_key(|(_, hits)| *hits)?;
+    (hits >= MIN_STOP_WORD_HITS).then(|| tag.to_string())
+}
+
+/// The document's main language as a tag such as `en-GB` or `de`, for choosing the spell
+/// checker's dictionary. It comes from polyglossia's \setdefaultlanguage or
+/// \setmainlanguage, then babel's options (given to the package or the class), and
+/// failing those from the most common words of the body text.
+#[tauri::command]
+pub async fn detect_language(
+    project_path: String,
+    root_file: String,
+) -> Result<Option<String>, String> {
+    tauri::async_runtime::spawn_blocking(move || {
+        let project_dir = PathBuf::from(&project_path);
+        let root_file = latex::project_file(&root_file)?;
+        let content = latex::read_source(&project_dir, &root_file)?;
+
+        for source in latex::load_sources(&project_dir, &root_file)? {
+            let masked = latex::mask_comments(&source.content);
+            for command in latex::commands(&masked) {
+                if command.name != "setdefaultlanguage" && command.name != "setmainlanguage" {
+                    continue;
+                }
+                let args = latex::read_args(&masked, command.end, 2);
+                let variant = latex::leading_optional(&args).and_then(|options| {
+                    options
+                        .text
+                        .split(',')
+                        .find_map(|o| o.trim().strip_prefix("variant=").map(str::to_string))
+                });
+                if let Some(tag) = latex::first_required(&args)
+                    .and_then(|name| language_tag(&name.text, variant.as_deref()))
+                {
+                    return Ok(Some(tag));
+                }
+            }
+        }
+
+        let mut info = PreambleInfo::default();
+        scan_preamble(
+            &project_dir,
+            &root_file,
+            &content,
+            false,
+            &mut HashSet::new(),
+            &mut info,
+        );
+        if let Some(babel) = info.packages.iter().find(|p| p.name == "babel") {
+            // Languages given to the class are passed on to babel too
+            let class_options = info.document_class.and_then(|c| c.options);
+            let options = [class_options, babel.options.clone()]
+                .into_iter()
+                .flatten()
+                .collect::<Vec<_>>()
+                .join(",");
+            if let Some(tag) = babel_language(&options) {
+                return Ok(Some(tag));
+            }
+        }
+
+        Ok(text_language(&prose::document_prose(
+            &project_dir,
+            &root_file,
+        )?))
+    })
+    .await
+    .map_err(|e| format!("Task join error: {}", e))?
+}
+
+/// A URL as written in a \url or \href argument, where `%` and `#` may be escaped
+fn link_url(argument: &str) -> String {
+    argument.trim().replace("\\%", "%").replace("\\#", "#")
+}
+
+/// A DOI as a resolvable URL; DOIs already written as a URL are kept
+fn doi_url(doi: &str) -> String {
+    let doi = doi.trim();
+    if doi.starts_with("http://") || doi.starts_with("https://") {
+        return doi.to_string();
+    }
+    let doi = doi.strip_prefix("doi:").unwrap_or(doi).trim();
+    format!("{}{}", DOI_RESOLVER, doi)
+}
+
+/// Request `url`, with HEAD first. Servers that refuse HEAD get a GET, whose body isn't
+/// read.
+fn check_link(client: &reqwest::blocking::Client, url: &str) -> LinkStatus {
+    let mut response = client.head(url).send();
+    if response
+        .as_ref()
+        .is_ok_and(|r| matches!(r.status().as_u16(), 403 | 405 | 501))
+    {
+        response = client.get(url).send();
+    }
+
+    match response {
+        Ok(response)
+            if response.status().is_client_error() || response.status().is_server_error() =>
+        {
+            LinkStatus::Broken {
+                status: response.status().as_u16(),
+            }
+        }
+        Ok(response) => LinkStatus::Ok {
+            status: response.status().as_u16(),
+        },
+        Err(e) => LinkStatus::Unreachable {
+            message: if e.is_timeout() {
+                format!("No response within {} seconds", LINK_TIMEOUT.as_secs())
+            } else {
+                e.to_string()
+            },
+        },
+    }
+}
+
+/// Check each distinct http(s) URL among `links` once, MAX_LINK_CHECKS at a time, and set
+/// the links' statuses
+fn validate_links(links: &mut [LinkInfo]) -> Result<(), String> {
+    let client = reqwest::blocking::Client::builder()
+        .timeout(LINK_TIMEOUT)
+        .user_agent(concat!("incipit/", env!("CARGO_PKG_VERSION")))
+        .build()
+        .map_err(|e| format!("Failed to create HTTP client: {}", e))?;
+
+    let mut urls: Vec<String> = links
+        .iter()
+        .map(|link| link.url.clone())
+        .filter(|url| url.starts_with("http://") || url.starts_with("https://"))
+        .collect();
+    urls.sort();
+    urls.dedup();
+
+    let queue = Mutex::new(urls.into_iter());
+    let statuses: Mutex<HashMap<String, LinkStatus>> = Mutex::new(HashMap::new());
+    std::thread::scope(|scope| {
+        for _ in 0..MAX_LINK_CHECKS {
+            scope.spawn(|| loop {
+                let Some(url) = queue.lock().unwrap().next() else {
+                    break;
+                };
+                let status = check_link(&client, &url);
+                statuses.lock().unwrap().insert(url, status);
+            });
+        }
+    });
+
+    let statuses = statuses.into_inner().unwrap();
+    for link in links {
+        link.status = statuses.get(&link.url).cloned();
+    }
+    Ok(())
+}
+
+/// The .bib files a document reads and the keys it cites
+struct DocumentCitations {
+    bib_files: Vec<String>,
+    /// Lowercased keys, each with the file and line of its citation, in document order
+    keys: Vec<(String, String, usize)>,
+}
+
+fn document_citations(project_dir: &Path, sources: &[latex::SourceFile]) -> DocumentCitations {
+    let mut citations = DocumentCitations {
+        bib_files: Vec::new(),
+        keys: Vec::new(),
+    };
+    for source in sources {
+        let masked = latex::mask_comments(&source.content);
+        let lines = LineIndex::new(&masked);
+        for command in latex::commands(&masked) {
+            let args = latex::read_args(&masked, command.end, 3);
+            let Some(first) = latex::first_required(&args) else {
+                continue;
+            };
+            match command.name {
+                "bibliography" => citations.bib_files.extend(
+                    first
+                        .text
+                        .split(',')
+                        .filter_map(|n| existing_with_extension(project_dir, n, "bib")),
+                ),
+                "addbibresource" => {
+                    let relative = latex::normalize_relative(Path::new(first.text.trim()));
+                    if let Some(relative) = relative.filter(|r| project_dir.join(r).is_file()) {
+                        citations.bib_files.push(latex::to_project_path(&relative));
+                    }
+                }
+                name if name.ends_with("cite") || name.starts_with("cite") => {
+                    let line = lines.line(command.start);
+                    citations.keys.extend(
+                        first
+                            .text
+                            .split(',')
+                            .map(|key| key.trim().to_lowercase())
+                            .filter(|key| !key.is_empty())
+                            .map(|key| (key, source.path.clone(), line)),
+                    );
+                }
+                _ => {}
+            }
+        }
+    }
+    citations
+}
+
+/// Every \url, \href and \doi in the document, then a DOI link for each cited entry of
+/// its bibliography that has a doi field, at the entry's first citation. With
+/// `validate`, each http(s) link is also requested to see whether it still resolves.
+#[tauri::command]
+pub async fn collect_links(
+    project_path: String,
+    root_file: String,
+    validate: Option<bool>,
+) -> Result<Vec<LinkInfo>, String> {
+    tauri::async_runtime::spawn_blocking(move || {
+        let project_dir = PathBuf::from(&project_path);
+        let sources = latex::load_sources(&project_dir, &root_file)?;
+
+        let mut links = Vec::new();
+        for source in &sources {
+            let masked = latex::mask_comments(&source.content);
+            let lines = LineIndex::new(&masked);
+            for command in latex::commands(&masked) {
+                let args = latex::read_args(&masked, command.end, 3);
+                let mut required = args.iter().filter(|a| a.kind == ArgKind::Required);
+                let Some(first) = required.next() else {
+                    continue;
+                };
+                let mut link = |kind, url, text| {
+                    links.push(LinkInfo {
+                        kind,
+                        url,
+                        text,
+                        key: None,
+                        file: source.path.clone(),
+                        line: lines.line(command.start),
+                        status: None,
+                    })
+                };
+
+                match command.name {
+                    "url" => link(LinkKind::Url, link_url(&first.text), None),
+                    "href" => link(
+                        LinkKind::Href,
+                        link_url(&first.text),
+                        required.next().map(|text| text.text.trim().to_string()),
+                    ),
+                    "doi" => link(LinkKind::Doi, doi_url(&first.text), None),
+                    _ => {}
+                }
+            }
+        }
+
+        let citations = document_citations(&project_dir, &sources);
+        let mut dois: HashMap<String, String> = HashMap::new();
+        for bib_file in &citations.bib_files {
+            let Ok(content) = latex::read_source(&project_dir, bib_file) else {
+                continue;
+            };
+            for entry in bibtex::parse(&content).entries {
+                if let Some(doi) = entry.field("doi") {
+                    dois.entry(entry.key.to_lowercase())
+                        .or_insert_with(|| doi.value.clone());
+                }
+            }
+        }
+        let mut seen = HashSet::new();
+        for (key, file, line) in citations.keys {
+            let Some(doi) = dois.get(&key) else {
+                continue;
+            };
+            if seen.insert(key.clone()) {
+                links.push(LinkInfo {
+                    kind: LinkKind::Doi,
+                    url: doi_url(doi),
+                    text: None,
+                    key: Some(key),
+                    file,
+                    line,
+                    status: None,
+                });
+            }
+        }
+
+        if validate.unwrap_or(false) {
+            validate_links(&mut links)?;
+        }
+        Ok(links)
+    })
+    .await
+    .map_err(|e| format!("Task join error: {}", e))?
+}
+
+/// The entries of the document's .bib files that it cites, in the order of their first
+/// citation, followed by the entries they crossref; \nocite{*} cites them all. With
+/// `output_path`, they are also written to that project file as a trimmed .bib, after
+/// the @string and @preamble blocks of the files they come from.
+#[tauri::command]
+pub async fn used_citations(
+    project_path: String,
+    root_file: String,
+    output_path: Option<String>,
+) -> Result<Vec<BibEntry>, String> {
+    tauri::async_runtime::spawn_blocking(move || {
+        let project_dir = PathBuf::from(&project_path);
+        let sources = latex::load_sources(&project_dir, &root_file)?;
+        let citations = document_citations(&project_dir, &sources);
+
+        let output = output_path
+            .as_deref()
+            .map(latex::project_file)
+            .transpose()?;
+        if let Some(output) = &output {
+            if !output.ends_with(".bib") {
+                return Err(format!("{} is not a .bib file", output));
+            }
+            if citations.bib_files.contains(output) {
+                return Err(format!(
+                    "{} is one of the document's bibliographies; choose another file",
+                    output
+                ));
+            }
+        }
+
+        let mut bibliographies = Vec::new();
+        for bib_file in &citations.bib_files {
+            let Ok(content) = latex::read_source(&project_dir, bib_file) else {
+                continue;
+            };
+            let bibliography = bibtex::parse(&content);
+            bibliographies.push((bib_file.clone(), content, bibliography));
+        }
+        // BibTeX takes the first entry with a key when several files define it
+        let mut by_key: HashMap<String, (usize, usize)> = HashMap::new();
+        for (b, (_, _, bibliography)) in bibliographies.iter().enumerate() {
+            for (e, entry) in bibliography.entries.iter().enumerate() {
+                by_key.entry(entry.key.to_lowercase()).or_insert((b, e));
+            }
+        }
+
+        let mut used: Vec<(usize, usize)> = Vec::new();
+        if citations.keys.iter().any(|(key, _, _)| key == "*") {
+            used = bibliographies
+                .iter()
+                .enumerate()
+                .flat_map(|(b, (_, _, bibliography))| {
+                    (0..bibliography.entries.len()).map(move |e| (b, e))
+                })
+                .filter(|&(b, e)| {
+                    let key = bibliographies[b].2.entries[e].key.to_lowercase();
+                    by_key.get(&key) == Some(&(b, e))
+                })
+                .collect();
+        } else {
+            for (key, _, _) in &citations.keys {
+                if let Some(found) = by_key.get(key).filter(|found| !used.contains(found)) {
+                    used.push(*found);
+                }
+            }
+        }
+        let mut i = 0;
+        while i < used.len() {
+            let (b, e) = used[i];
+            let parent = bibliographies[b].2.entries[e]
+                .field("crossref")
+                .and_then(|crossref| by_key.get(&crossref.value.trim().to_lowercase()));
+            if let Some(parent) = parent.filter(|parent| !used.contains(parent)) {
+                used.push(*parent);
+            }
+            i += 1;
+        }
+
+        let entries: Vec<BibEntry> = used
+            .iter()
+            .map(|&(b, e)| {
+                let (file, content, bibliography) = &bibliographies[b];
+                let entry = &bibliography.entries[e];
+                BibEntry {
+                    key: entry.key.clone(),
+                    kind: entry.kind.clone(),
+                    file: file.clone(),
+                    line: entry.line,
+                    fields: entry
+                        .fields
+                        .iter()
+                        .map(|field| (field.name.clone(), field.value.clone()))
+                        .collect(),
+                    source: content[entry.span.clone()].to_string(),
+                }
+            })
+            .collect();
+
+        if let Some(output) = output {
+            let mut written = String::new();
+            for (b, (_, content, bibliography)) in bibliographies.iter().enumerate() {
+                if used.iter().any(|(used, _)| *used == b) {
+                    for definition in &bibliography.definitions {
+                        written.push_str(&content[definition.clone()]);
+                        written.push_str("\n\n");
+                    }
+                }
+            }
+            for entry in &entries {
+                written.push_str(&entry.source);
+                written.push_str("\n\n");
+            }
+            let written = format!("{}\n", written.trim_end());
+            write_project_file(&project_path, &output, &written)?;
+        }
+
+        Ok(entries)
+    })
+    .await
+    .map_err(|e| format!("Task join error: {}", e))?
+}
+
+/// How long fetch_bib_entry waits for the DOI resolver
+const BIB_FETCH_TIMEOUT: Duration = Duration::from_secs(15);
+
+/// Prefix of the DOIs arXiv registers for its papers, followed by the arXiv ID
+const ARXIV_DOI_PREFIX: &str = "10.48550/arXiv.";
+
+/// `identifier` without the first of `prefixes` it starts with, ignoring case
+fn strip_any_prefix<'a>(identifier: &'a str, prefixes: &[&str]) -> &'a str {
+    prefixes
+        .iter()
+        .find(|prefix| {
+            identifier
+                .get(..prefix.len())
+                .is_some_and(|start| start.eq_ignore_ascii_case(prefix))
+        })
+        .map_or(identifier, |prefix| identifier[prefix.len()..].trim())
+}
+
+/// A DOI as written in a citation or link, without a resolver or `doi:` in front
+fn plain_doi(identifier: &str) -> Option<String> {
+    let doi = strip_any_prefix(
+        identifier,
+        &[
+            "https://doi.org/",
+            "http://doi.org/",
+            "https://dx.doi.org/",
+            "http://dx.doi.org/",
+            "doi:",
+        ],
+    );
+    (doi.starts_with("10.") && doi.contains('/')).then(|| doi.to_string())
+}
+
+/// An arXiv ID, new style as in `2101.00001v2` or old style as in `hep-th/9901001`,
+/// given bare, as `arXiv:...` or as an abs or pdf URL. The version is dropped, as the
+/// DOI arXiv registers names the paper rather than one version of it.
+fn arxiv_id(identifier: &str) -> Option<String> {
+    let id = strip_any_prefix(
+        identifier,
+        &[
+            "https://arxiv.org/abs/",
+            "https://arxiv.org/pdf/",
+            "http://arxiv.org/abs/",
+            "http://arxiv.org/pdf/",
+            "arxiv:",
+        ],
+    );
+    let id = id.strip_suffix(".pdf").unwrap_or(id);
+    let id = match id.rsplit_once('v') {
+        Some((paper, version))
+            if !version.is_empty() && version.bytes().all(|b| b.is_ascii_digit()) =>
+        {
+            paper
+        }
+        _ => id,
+    };
+
+    let digits = |text: &str, counts: &[usize]| {
+        counts.contains(&text.len()) && text.bytes().all(|b| b.is_ascii_digit())
+    };
+    let new_style = id
+        .split_once('.')
+        .is_some_and(|(month, number)| digits(month, &[4]) && digits(number, &[4, 5]));
+    let old_style = id.split_once('/').is_some_and(|(archive, number)| {
+        let subject = archive.split('.').next().unwrap_or_default();
+        !subject.is_empty()
+            && subject.bytes().all(|b| b.is_ascii_lowercase() || b == b'-')
+            && digits(number, &[7])
+    });
+    (new_style || old_style).then(|| id.to_string())
+}
+
+/// The BibTeX record of a DOI or arXiv ID, as the DOI resolver gives it, for appending
+/// to a .bib file. arXiv papers are looked up by the DOI arXiv registers for them.
+#[tauri::command]
+pub async fn fetch_bib_entry(identifier: String) -> Result<String, String> {
+    let identifier = identifier.trim().to_string();
+    let (doi, what) = if let Some(doi) = plain_doi(&identifier) {
+        (doi, "DOI")
+    } else if let Some(id) = arxiv_id(&identifier) {
+        (format!("{}{}", ARXIV_DOI_PREFIX, id), "arXiv ID")
+    } else {
+        return Err(format!("{} is neither a DOI nor an arXiv ID", identifier));
+    };
+
+    tauri::async_runtime::spawn_blocking(move || {
+        let client = reqwest::blocking::Client::builder()
+            .timeout(BIB_FETCH_TIMEOUT)
+            .user_agent(concat!("incipit/", env!("CARGO_PKG_VERSION")))
+            .build()
+            .map_err(|e| format!("Failed to create HTTP client: {}", e))?;
+
+        let response = client
+            .get(doi_url(&doi))
+            .header(reqwest::header::ACCEPT, "application/x-bibtex")
+            .send()
+            .map_err(|e| {
+                if e.is_timeout() {
+                    format!(
+                        "No response from doi.org within {} seconds",
+                        BIB_FETCH_TIMEOUT.as_secs()
+                    )
+                } else {
+                    format!("Failed to reach doi.org: {}", e)
+                }
+            })?;
+        match response.status().as_u16() {
+            404 => return Err(format!("No record found for {} {}", what, identifier)),
+            status if !response.status().is_success() => {
+                return Err(format!(
+                    "doi.org answered {} for {} {}",
+                    status, what, identifier
+                ))
+            }
+            _ => {}
+        }
+        let body = response
+            .text()
+            .map_err(|e| format!("Failed to read the record for {}: {}", identifier, e))?;
+
+        // A resolver without BibTeX for the DOI can answer with an HTML landing page
+        let record = body.trim();
+        let bibliography = bibtex::parse(record);
+        if !record.starts_with('@')
+            || bibliography.entries.is_empty()
+            || !bibliography.errors.is_empty()
+        {
+            return Err(format!(
+                "doi.org has no BibTeX record for {} {}",
+                what, identifier
+            ));
+        }
+        Ok(format!("{}\n", record))
+    })
+    .await
+    .map_err(|e| format!("Task join error: {}", e))?
+}
+
+/// The \todo and \missingfigure notes of todonotes across the document, with the files
+/// it includes, in reading order. Their text may span lines and hold braced markup.
+#[tauri::command]
+pub async fn collect_todo_macros(
+    project_path: String,
+    root_file: String,
+) -> Result<Vec<TodoMacro>, String> {
+    tauri::async_runtime::spawn_blocking(move || {
+        let project_dir = PathBuf::from(&project_path);
+        let sources = latex::load_sources(&project_dir, &root_file)?;
+
+        let mut todos = Vec::new();
+        for source in &sources {
+            let masked = latex::mask_comments(&source.content);
+            let lines = LineIndex::new(&masked);
+            for command in latex::commands(&masked) {
+                let kind = match command.name {
+                    "todo" => TodoKind::Todo,
+                    "missingfigure" => TodoKind::MissingFigure,
+                    _ => continue,
+                };
+                let args = latex::read_args(&masked, command.end, 2);
+                let Some(text) = latex::first_required(&args) else {
+                    continue;
+                };
+                let options = args
+                    .iter()
+                    .find(|a| a.kind == ArgKind::Optional)
+                    .map(|a| a.text.trim().to_string());
+                let inline = options.as_deref().is_some_and(|options| {
+                    options.split(',').any(|option| option.trim() == "inline")
+                });
+
+                todos.push(TodoMacro {
+                    kind,
+                    text: text.text.split_whitespace().collect::<Vec<_>>().join(" "),
+                    options,
+                    inline,
+                    file: source.path.clone(),
+                    line: lines.line(command.start),
+                });
+            }
+        }
+        Ok(todos)
+    })
+    .await
+    .map_err(|e| format!("Task join error: {}", e))?
+}
+
+/// The \footnote, \footnotetext and \marginpar notes across the document, with the
+/// files it includes, in reading order. The text of each is read to its closing brace,
+/// past any braced markup and line breaks in it.
+#[tauri::command]
+pub async fn collect_notes(
+    project_path: String,
+    root_file: String,
+) -> Result<Vec<NoteItem>, String> {
+    tauri::async_runtime::spawn_blocking(move || {
+        let project_dir = PathBuf::from(&project_path);
+        let sources = latex::load_sources(&project_dir, &root_file)?;
+
+        let mut notes = Vec::new();
+        for source in &sources {
+            let masked = latex::mask_comments(&source.content);
+            let lines = LineIndex::new(&masked);
+            for command in latex::commands(&masked) {
+                let kind = match command.name {
+                    "footnote" => NoteKind::Footnote,
+                    "footnotetext" => NoteKind::FootnoteText,
+                    "marginpar" => NoteKind::Marginpar,
+                    _ => continue,
+                };
+                let args = latex::read_args(&masked, command.end, 2);
+                let Some(text) = latex::first_required(&args) else {
+                    continue;
+                };
+                let option = latex::leading_optional(&args).map(|a| a.text.trim().to_string());
+
+                let text = text.text.split_whitespace().collect::<Vec<_>>().join(" ");
+                let truncated = text.chars().count() > MAX_NOTE_CHARS;
+                notes.push(NoteItem {
+                    kind,
+                    text: if truncated {
+                        text.chars().take(MAX_NOTE_CHARS).collect()
+                    } else {
+                        text
+                    },
+                    truncated,
+                    option,
+                    file: source.path.clone(),
+                    line: lines.line(command.start),
+                });
+            }
+        }
+        Ok(notes)
+    })
+    .await
+    .map_err(|e| format!("Task join error: {}", e))?
+}
+
+/// Check the document against the built-in style rule sets named in `rules` (by default
+/// `general`) and the project's own style_rules, for a journal's requirements before
+/// submission. Issues are in reading order, across included files.
+#[tauri::command]
+pub async fn style_check(
+    project_path: String,
+    root_file: String,
+    rules: Option<Vec<String>>,
+) -> Result<Vec<StyleIssue>, String> {
+    tauri::async_runtime::spawn_blocking(move || {
+        let project_dir = PathBuf::from(&project_path);
+        let meta = read_project_meta(&project_dir)?;
+
+        let mut checked = Vec::new();
+        for name in rules.unwrap_or_else(|| vec!["general".to_string()]) {
+            let Some(set) = style::rule_set(&name) else {
+                return Err(format!(
+                    "Unknown style rule set {}; the built-in ones are {}",
+                    name,
+                    style::RULE_SETS.join(", ")
+                ));
+            };
+            checked.extend(set);
+        }
+        checked.extend(meta.style_rules);
+        let compiled = style::compile(&checked)?;
+
+        let mut issues = Vec::new();
+        for source in latex::load_sources(&project_dir, &root_file)? {
+            let masked = latex::mask_comments(&source.content);
+            let lines = LineIndex::new(&masked);
+            for violation in style::check(&masked, &compiled) {
+                issues.push(StyleIssue {
+                    rule: violation.rule.id.clone(),
+                    message: violation.rule.message.clone(),
+                    text: violation.text,
+                    file: source.path.clone(),
+                    line: lines.line(violation.offset),
+                });
+            }
+        }
+        Ok(issues)
+    })
+    .await
+    .map_err(|e| format!("Task join error: {}", e))?
+}
+
+/// The name of the macro a definition command at `pos` defines, without its backslash,
+/// and the offset just past it
+fn defined_macro(masked: &str, pos: usize) -> Option<(String, usize)> {
+    let bytes = masked.as_bytes();
+    let pos = latex::skip_arg_whitespace(bytes, pos);
+    if bytes.get(pos) == Some(&b'{') {
+        let (group, end) = latex::read_group(masked, pos, b'{', b'}')?;
+        let (name, _) = defined_macro(group.trim(), 0)?;
+        return Some((name, end));
+    }
+
+    let name = masked[pos..].strip_prefix('\\')?;
+    let letters = name
+        .find(|c: char| !c.is_ascii_alphabetic())
+        .unwrap_or(name.len());
+    let name = match letters {
+        // A control symbol such as \!
+        0 => name.chars().next()?.to_string(),
+        _ => name[..letters].to_string(),
+    };
+    let end = pos + 1 + name.len();
+    Some((name, end))
+}
+
+/// Where `macro_name` (with or without its backslash) is first defined in the document,
+/// in the order TeX reads the files: by \newcommand and its relatives, their starred
+/// forms, xparse's \NewDocumentCommand family, \DeclareMathOperator, \def and \let.
+/// None when the project doesn't define it, e.g. because a package does.
+#[tauri::command]
+pub async fn find_macro_definition(
+    project_path: String,
+    root_file: String,
+    macro_name: String,
+) -> Result<Option<MacroDefinition>, String> {
+    let macro_name = macro_name.trim();
+    let macro_name = macro_name.strip_prefix('\\').unwrap_or(macro_name);
+    let sources = latex::load_sources(Path::new(&project_path), &root_file)?;
+
+    for source in sources {
+        let masked = latex::mask_comments(&source.content);
+        let definition = latex::commands(&masked).find(|command| {
+            MACRO_DEFINITIONS.contains(&command.name)
+                && defined_macro(&masked, command.end).is_some_and(|(name, _)| name == macro_name)
+        });
+        if let Some(command) = definition {
+            return Ok(Some(MacroDefinition {
+                file: source.path,
+                line: LineIndex::new(&masked).line(command.start),
+                command: command.name.to_string(),
+            }));
+        }
+    }
+
+    Ok(None)
+}
+
+/// Argument counts, all and optional, of an xparse argument specification such as
+/// `s o m O{default}`
+fn xparse_arguments(spec: &str) -> (usize, usize) {
+    let bytes = spec.as_bytes();
+    // Skip a braced group or a single token
+    let skip = |pos: usize| -> usize {
+        let pos = latex::skip_arg_whitespace(bytes, pos);
+        match bytes.get(pos) {
+            Some(b'{') => {
+                latex::read_group(spec, pos, b'{', b'}').map_or(bytes.len(), |(_, end)| end)
+            }
+            Some(_) => pos + 1,
+            None => pos,
+        }
+    };
+    // The tokens of an `e{^_}` group, each an optional embellishment
+    let tokens = |pos: usize| -> usize {
+        latex::read_group(spec, latex::skip_arg_whitespace(bytes, pos), b'{', b'}')
+            .map_or(0, |(group, _)| {
+                group.chars().filter(|c| !c.is_whitespace()).count()
+            })
+    };
+
+    let (mut arguments, mut optional) = (0, 0);
+    let mut pos = 0;
+    while pos < bytes.len() {
+        let token = bytes[pos];
+        pos += 1;
+        match token {
+            b'm' | b'v' | b'b' | b'l' => arguments += 1,
+            b'u' => {
+                arguments += 1;
+                pos = skip(pos);
+            }
+            b'r' => {
+                arguments += 1;
+                pos = skip(skip(pos));
+            }
+            b'R' => {
+                arguments += 1;
+                pos = skip(skip(skip(pos)));
+            }
+            b'o' | b's' => {
+                arguments += 1;
+                optional += 1;
+            }
+            b'O' | b't' => {
+                arguments += 1;
+                optional += 1;
+                pos = skip(pos);
+            }
+            b'd' => {
+                arguments += 1;
+                optional += 1;
+                pos = skip(skip(pos));
+            }
+            b'D' => {
+                arguments += 1;
+                optional += 1;
+                pos = skip(skip(skip(pos)));
+            }
+            b'e' | b'E' => {
+                let count = tokens(pos);
+                arguments += count;
+                optional += count;
+                pos = skip(pos);
+                if token == b'E' {
+                    pos = skip(pos);
+                }
+            }
+            // Argument processors: `>{\SplitList{;}}`
+            b'>' | b'=' => pos = skip(pos),
+            _ => {}
+        }
+    }
+    (arguments, optional)
+}
+
+/// Argument counts given by `[n][default]` after a \newcommand or \newenvironment name
+fn latex_arguments(masked: &str, pos: usize) -> (usize, usize) {
+    let args = latex::read_args(masked, pos, 2);
+    let mut optionals = args.iter().take_while(|a| a.kind == ArgKind::Optional);
+    let arguments = optionals
+        .next()
+        .and_then(|n| n.text.trim().parse().ok())
+        .unwrap_or(0);
+    let optional = usize::from(optionals.next().is_some() && arguments > 0);
+    (arguments, optional)
+}
+
+/// The highest `#n` in a \def parameter text, which runs up to the body's `{`
+fn def_arguments(masked: &str, pos: usize) -> usize {
+    let parameters = &masked[pos..];
+    let parameters = &parameters[..parameters.find('{').unwrap_or(parameters.len())];
+    parameters
+        .as_bytes()
+        .windows(2)
+        .filter(|pair| pair[0] == b'#')
+        .filter_map(|pair| (pair[1] as char).to_digit(10))
+        .max()
+        .unwrap_or(0) as usize
+}
+
+/// Every definition in `source`, in file order
+fn scan_macros(source: &latex::SourceFile, macros: &mut Vec<MacroInfo>) {
+    let masked = latex::mask_comments(&source.content);
+    let lines = LineIndex::new(&masked);
+
+    for command in latex::commands(&masked) {
+        let (name, kind, end) = if MACRO_DEFINITIONS.contains(&command.name) {
+            let Some((name, end)) = defined_macro(&masked, command.end) else {
+                continue;
+            };
+            (name, MacroKind::Command, end)
+        } else if ENVIRONMENT_DEFINITIONS.contains(&command.name) {
+            let pos = latex::skip_arg_whitespace(masked.as_bytes(), command.end);
+            let Some((name, end)) = latex::read_group(&masked, pos, b'{', b'}') else {
+                continue;
+            };
+            (name.trim().to_string(), MacroKind::Environment, end)
+        } else {
+            continue;
+        };
+
+        let (arguments, optional_arguments) = match command.name {
+            "let" => (None, 0),
+            "DeclareMathOperator" => (Some(0), 0),
+            // The optional argument is the theorem's note, e.g. its source
+            "newtheorem" => (Some(1), 1),
+            "def" | "gdef" | "edef" | "xdef" => (Some(def_arguments(&masked, end)), 0),
+            name if name.contains("Document") => {
+                let args = latex::read_args(&masked, end, 1);
+                let (arguments, optional) = latex::first_required(&args)
+                    .map_or((0, 0), |spec| xparse_arguments(&spec.text));
+                (Some(arguments), optional)
+            }
+            _ => {
+                let (arguments, optional) = latex_arguments(&masked, end);
+                (Some(arguments), optional)
+            }
+        };
+
+        let replaces = command.name.starts_with("renew")
+            || command.name.starts_with("Renew")
+            || command.name.starts_with("provide")
+            || command.name.starts_with("Provide")
+            || command.name.starts_with("Declare");
+        let duplicate = !replaces
+            && macros
+                .iter()
+                .any(|earlier| earlier.kind == kind && earlier.name == name);
+
+        macros.push(MacroInfo {
+            name,
+            kind,
+            arguments,
+            optional_arguments,
+            command: command.name.to_string(),
+            file: source.path.clone(),
+            line: lines.line(command.start),
+            duplicate,
+        });
+    }
+}
+
+/// Every command and environment the document defines, in the order TeX reads the
+/// definitions, across the root file and the files it includes. For autocompletion and
+/// spotting definitions that clash.
+#[tauri::command]
+pub async fn collect_macros(
+    project_path: String,
+    root_file: String,
+) -> Result<Vec<MacroInfo>, String> {
+    let sources = latex::load_sources(Path::new(&project_path), &root_file)?;
+    let mut macros = Vec::new();
+    for source in &sources {
+        scan_macros(source, &mut macros);
+    }
+    Ok(macros)
+}
+
+/// Uses of each environment list_environments keeps as examples
+const MAX_ENV_LOCATIONS: usize = 5;
+
+/// Every environment the document opens with \begin, by name, with how often and where,
+/// across the root file and the files it includes. For getting to know a document and
+/// spotting packages nothing uses any more. Environments inside verbatim ones aren't
+/// counted.
+#[tauri::command]
+pub async fn list_environments(
+    project_path: String,
+    root_file: String,
+) -> Result<Vec<EnvUsage>, String> {
+    let sources = latex::load_sources(Path::new(&project_path), &root_file)?;
+    let mut defined = Vec::new();
+    for source in &sources {
+        scan_macros(source, &mut defined);
+    }
+
+    let mut usages: BTreeMap<String, EnvUsage> = BTreeMap::new();
+    for source in &sources {
+        let masked = latex::mask_comments(&source.content);
+        let lines = LineIndex::new(&masked);
+        let environments = latex::environments(&masked);
+        let verbatim: Vec<&Range<usize>> = environments
+            .iter()
+            .filter(|env| latex::VERBATIM_ENVIRONMENTS.contains(&env.name.as_str()))
+            .map(|env| &env.span)
+            .collect();
+
+        let mut opened: Vec<&latex::Environment> = environments
+            .iter()
+            .filter(|env| {
+                !verbatim
+                    .iter()
+                    .any(|span| span.contains(&env.span.start) && span.start != env.span.start)
+            })
+            .collect();
+        opened.sort_by_key(|env| env.span.start);
+
+        for env in opened {
+            let usage = usages.entry(env.name.clone()).or_insert_with(|| EnvUsage {
+                name: env.name.clone(),
+                count: 0,
+                locations: Vec::new(),
+                custom: defined.iter().any(|m| {
+                    m.kind == MacroKind::Environment
+                        && (m.name == env.name
+                            || Some(m.name.as_str()) == env.name.strip_suffix('*'))
+                }),
+            });
+            usage.count += 1;
+            if usage.locations.len() < MAX_ENV_LOCATIONS {
+                usage.locations.push(EnvLocation {
+                    file: source.path.clone(),
+                    line: lines.line(env.span.start),
+                });
+            }
+        }
+    }
+    Ok(usages.into_values().collect())
+}
+
+/// The rows of an align-like environment's body: its text split at each `\\` outside
+/// braces and nested environments such as `cases`
+fn equation_rows(body: &str) -> Vec<&str> {
+    let bytes = body.as_bytes();
+    let mut rows = Vec::new();
+    let (mut depth, mut braces) = (0usize, 0usize);
+    let mut row_start = 0;
+    let mut i = 0;
+
+    while i < bytes.len() {
+        match bytes[i] {
+            b'{' => braces += 1,
+            b'}' => braces = braces.saturating_sub(1),
+            b'\\' if bytes.get(i + 1) == Some(&b'\\') => {
+                if depth == 0 && braces == 0 {
+                    rows.push(&body[row_start..i]);
+                    row_start = i + 2;
+                }
+                i += 2;
+                continue;
+            }
+            b'\\' => {
+                let rest = &body[i + 1..];
+                if rest.starts_with("begin") {
+                    depth += 1;
+                } else if rest.starts_with("end") {
+                    depth = depth.saturating_sub(1);
+                }
+                // Skip the escaped character, so `\{` doesn't open a group
+                i += 2;
+                continue;
+            }
+            _ => {}
+        }
+        i += 1;
+    }
+    rows.push(&body[row_start..]);
+
+    // Text after a final `\\` is no row unless it holds something
+    if rows.len() > 1 && rows.last().is_some_and(|row| row.trim().is_empty()) {
+        rows.pop();
+    }
+    rows
+}
+
+/// Whether a row or single-number equation gets a number
+fn is_numbered(text: &str, starred: bool) -> bool {
+    let mut tagged = false;
+    let mut suppressed = false;
+    for command in latex::commands(text) {
+        match command.name {
+            "tag" => tagged = true,
+            "nonumber" | "notag" => suppressed = true,
+            _ => {}
+        }
+    }
+    tagged || (!starred && !suppressed)
+}
+
+/// Count the numbered and unnumbered display equations across the document, by
+/// environment, with the labels they define
+#[tauri::command]
+pub async fn equation_stats(
+    project_path: String,
+    root_file: String,
+) -> Result<EquationStats, String> {
+    let sources = latex::load_sources(&PathBuf::from(&project_path), &root_file)?;
+    let mut stats = EquationStats::default();
+
+    for source in &sources {
+        let masked = latex::mask_comments(&source.content);
+        let bytes = masked.as_bytes();
+        let lines = LineIndex::new(&masked);
+
+        for command in latex::commands(&masked).filter(|c| c.name == "begin") {
+            let args = latex::read_args(&masked, command.end, 1);
+            let Some(environment) = latex::first_required(&args).map(|a| a.text.trim()) else {
+                continue;
+            };
+            // displaymath is \[ ... \] spelled out, never numbered
+            let starred = environment.ends_with('*') || environment == "displaymath";
+            let base = environment.trim_end_matches('*');
+            let Some(&(_, numbers_rows)) = EQUATION_ENVIRONMENTS.iter().find(|(e, _)| *e == base)
+            else {
+                continue;
+            };
+            *stats
+                .environments
+                .entry(environment.to_string())
+                .or_default() += 1;
+
+            let body_start = latex::skip_args(&masked, command.end, 1);
+            let closing = format!("\\end{{{}}}", environment);
+            let body_end = masked[body_start..]
+                .find(&closing)
+                .map_or(masked.len(), |i| body_start + i);
+            let body = &masked[body_start..body_end];
+
+            let rows = if numbers_rows {
+                equation_rows(body)
+            } else {
+                vec![body]
+            };
+            for row in rows {
+                if is_numbered(row, starred) {
+                    stats.numbered += 1;
+                } else {
+                    stats.unnumbered += 1;
+                }
+            }
+
+            for label in latex::commands(body).filter(|c| c.name == "label") {
+                let args = latex::read_args(body, label.end, 1);
+                if let Some(name) = latex::first_required(&args) {
+                    stats.labels.push(LabelInfo {
+                        name: name.text.trim().to_string(),
+                        file: source.path.clone(),
+                        line: lines.line(body_start + label.start),
+                    });
+                }
+            }
+        }
+
+        // \[ ... \], though not a `\\[2pt]` row break, and $$ ... $$
+        let mut i = 0;
+        while i + 1 < bytes.len() {
+            let closing = match &bytes[i..i + 2] {
+                b"\\[" if !latex::is_escaped(bytes, i) => "\\]",
+                b"$$" if !latex::is_escaped(bytes, i) => "$$",
+                _ => {
+                    i += 1;
+                    continue;
+                }
+            };
+            let body_end = masked[i + 2..]
+                .find(closing)
+                .map_or(masked.len(), |end| i + 2 + end);
+            if is_numbered(&masked[i + 2..body_end], true) {
+                stats.numbered += 1;
+            } else {
+                stats.unnumbered += 1;
+            }
+            *stats
+                .environments
+                .entry("displaymath".to_string())
+                .or_default() += 1;
+            i = body_end + 2;
+        }
+    }
+
+    Ok(stats)
+}
+
+/// Headings, labels, captions and definitions of one file, in one pass over it
+fn scan_symbols(source: &latex::SourceFile) -> Vec<Symbol> {
+    let masked = latex::mask_comments(&source.content);
+    let lines = LineIndex::new(&masked);
+    let mut symbols = Vec::new();
+
+    for command in latex::commands(&masked) {
+        let (kind, args) = if latex::SECTIONING_COMMANDS.contains(&command.name) {
+            (SymbolKind::Section, 2)
+        } else if command.name == "label" {
+            (SymbolKind::Label, 1)
+        } else if command.name == "caption" {
+            (SymbolKind::Caption, 2)
+        } else if command.name == "captionof" {
+            // \captionof{figure}[short]{caption}
+            (SymbolKind::Caption, 3)
+        } else {
+            continue;
+        };
+
+        let args = latex::read_args(&masked, command.end, args);
+        let text = match command.name {
+            "captionof" => args.iter().filter(|a| a.kind == ArgKind::Required).nth(1),
+            _ => latex::first_required(&args),
+        };
+        let Some(text) = text else {
+            continue;
+        };
+        let name = text.text.split_whitespace().collect::<Vec<_>>().join(" ");
+        if name.is_empty() {
+            continue;
+        }
+        symbols.push(Symbol {
+            kind,
+            name,
+            command: command.name.to_string(),
+            file: source.path.clone(),
+            line: lines.line(command.start),
+        });
+    }
+
+    let mut macros = Vec::new();
+    scan_macros(source, &mut macros);
+    symbols.extend(macros.into_iter().map(|definition| Symbol {
+        kind: match definition.kind {
+            MacroKind::Command => SymbolKind::Command,
+            MacroKind::Environment => SymbolKind::Environment,
+        },
+        name: definition.name,
+        command: definition.command,
+        file: definition.file,
+        line: definition.line,
+    }));
+    symbols.sort_by_key(|symbol| symbol.line);
+    symbols
+}
+
+/// Every section, label, float caption and command or environment definition across
+/// the document, for a jump-to-anything palette. Only files whose content changed since
+/// the last call are scanned again; the rest come from SYMBOL_CACHE.
+#[tauri::command]
+pub async fn symbol_index(project_path: String, root_file: String) -> Result<SymbolIndex, String> {
+    tauri::async_runtime::spawn_blocking(move || {
+        let sources = latex::load_sources(Path::new(&project_path), &root_file)?;
+
+        let mut cache = SYMBOL_CACHE.lock().unwrap();
+        let position = match cache.iter().position(|(path, _)| *path == project_path) {
+            Some(position) => position,
+            None => {
+                cache.push((project_path.clone(), HashMap::new()));
+                cache.len() - 1
+            }
+        };
+        let files = &mut cache[position].1;
+
+        let mut index = SymbolIndex::default();
+        for source in &sources {
+            let unchanged = files
+                .get(&source.path)
+                .is_some_and(|scanned| scanned.content == source.content);
+            if !unchanged {
+                let scanned = ScannedFile {
+                    content: source.content.clone(),
+                    symbols: scan_symbols(source),
+                };
+                files.insert(source.path.clone(), scanned);
+            }
+            index
+                .symbols
+                .extend(files[&source.path].symbols.iter().cloned());
+        }
+
+        // Forget files no longer part of the document, e.g. after an \input is removed.
+        // Another root file of the same project loses its entries too, and gets them
+        // back on its next call.
+        files.retain(|path, _| sources.iter().any(|source| source.path == *path));
+        Ok(index)
+    })
+    .await
+    .map_err(|e| format!("Task join error: {}", e))?
+}
@@ -0,0 +1,144 @@
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tauri::{AppHandle, Emitter};
+
+use super::compile_on_save;
+use super::disk_space::check_free_space;
+use super::project::{write_project_file, FileError};
+use super::settings::read_global_settings;
+
+/// How often the autosave thread looks for due saves
+const AUTOSAVE_TICK: Duration = Duration::from_millis(500);
+
+/// Latest unsaved content pushed by the frontend. Newer content for the same file
+/// replaces it, so a burst of edits becomes one write.
+struct PendingSave {
+    project_path: String,
+    file_path: String,
+    content: String,
+    /// When the oldest unsaved edit in this batch was queued
+    queued_at: Instant,
+    interval: Duration,
+}
+
+static PENDING_SAVE: Mutex<Option<PendingSave>> = Mutex::new(None);
+
+/// Set once the autosave thread is running
+static AUTOSAVE_THREAD: Mutex<bool> = Mutex::new(false);
+
+/// Payload of the `file-saved` event
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SavedFile {
+    pub project_path: String,
+    pub file_path: String,
+}
+
+/// Drop a queued autosave of `file_path`, e.g. because the user saved it by hand
+pub(crate) fn discard(project_path: &str, file_path: &str) {
+    let mut pending = PENDING_SAVE.lock().unwrap();
+    if pending
+        .as_ref()
+        .is_some_and(|p| p.project_path == project_path && p.file_path == file_path)
+    {
+        *pending = None;
+    }
+}
+
+/// Take the queued save if its interval has elapsed
+fn take_due() -> Option<PendingSave> {
+    let mut pending = PENDING_SAVE.lock().unwrap();
+    if pending
+        .as_ref()
+        .is_some_and(|p| p.queued_at.elapsed() >= p.interval)
+    {
+        pending.take()
+    } else {
+        None
+    }
+}
+
+fn write_pending(app: &AppHandle, save: PendingSave) {
+    // The disk may have filled up since the save was queued
+    let written = check_free_space(Path::new(&save.project_path))
+        .map_err(|low| FileError::from(low.to_string()))
+        .and_then(|()| write_project_file(&save.project_path, &save.file_path, &save.content));
+    match written {
+        Ok(()) => {
+            compile_on_save::file_saved(app, &save.project_path, &save.file_path);
+            let saved = SavedFile {
+                project_path: save.project_path,
+                file_path: save.file_path,
+            };
+            if let Err(e) = app.emit("file-saved", saved) {
+                eprintln!("Failed to emit file-saved: {}", e);
+            }
+        }
+        Err(e) => eprintln!("Autosave failed: {}", e),
+    }
+}
+
+fn run_autosave(app: AppHandle) {
+    loop {
+        std::thread::sleep(AUTOSAVE_TICK);
+        if let Some(save) = take_due() {
+            write_pending(&app, save);
+        }
+    }
+}
+
+/// Queue the editor's current content for saving after the configured
+/// `auto_save_interval_secs`, emitting `file-saved` once it is written. Returns false
+/// when autosave is turned off. Switching files saves the previous file right away.
+/// Fails without queueing while the project's disk is under the low-disk threshold.
+#[tauri::command]
+pub async fn queue_autosave(
+    app: AppHandle,
+    project_path: String,
+    file_path: String,
+    content: String,
+) -> Result<bool, String> {
+    let interval_secs = read_global_settings()?
+        .editor_settings
+        .auto_save_interval_secs
+        .unwrap_or(0);
+    if interval_secs == 0 {
+        discard(&project_path, &file_path);
+        return Ok(false);
+    }
+    check_free_space(Path::new(&project_path)).map_err(|low| low.to_string())?;
+
+    {
+        let mut running = AUTOSAVE_THREAD.lock().unwrap();
+        if !*running {
+            let app = app.clone();
+            std::thread::spawn(move || run_autosave(app));
+            *running = true;
+        }
+    }
+
+    let mut pending = PENDING_SAVE.lock().unwrap();
+    let same_file = pending
+        .as_ref()
+        .is_some_and(|p| p.project_path == project_path && p.file_path == file_path);
+    let queued_at = match pending.as_ref() {
+        Some(p) if same_file => p.queued_at,
+        _ => Instant::now(),
+    };
+    let previous = if same_file { None } else { pending.take() };
+
+    *pending = Some(PendingSave {
+        project_path,
+        file_path,
+        content,
+        queued_at,
+        interval: Duration::from_secs(interval_secs),
+    });
+    drop(pending);
+
+    if let Some(previous) = previous {
+        write_pending(&app, previous);
+    }
+    Ok(true)
+}
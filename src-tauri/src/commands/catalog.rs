@@ -0,0 +1,334 @@
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::PathBuf;
+
+use super::settings::get_config_dir;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CommandCategory {
+    Structure,
+    Text,
+    References,
+    Layout,
+    Math,
+    Greek,
+    Operators,
+    Relations,
+    Arrows,
+    Delimiters,
+    Accents,
+    /// A `\begin`...`\end` environment; its name is the environment's
+    Environment,
+    #[default]
+    Other,
+}
+
+/// An autocomplete entry for the editor
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CommandInfo {
+    /// Command name without the backslash, or the environment name
+    pub name: String,
+    #[serde(default)]
+    pub category: CommandCategory,
+    /// Text to insert, with tab stops in the snippet syntax (`${1:placeholder}`, `$0`)
+    pub snippet: String,
+    #[serde(default)]
+    pub description: String,
+}
+
+use CommandCategory::*;
+
+/// Built-in entries, grouped by category
+const BUILTIN_COMMANDS: &[(&str, CommandCategory, &str, &str)] = &[
+    // Document structure
+    ("documentclass", Structure, "\\documentclass{${1:article}}", "Set the document class"),
+    ("usepackage", Structure, "\\usepackage{${1:package}}", "Load a package"),
+    ("part", Structure, "\\part{${1:title}}", "Part heading"),
+    ("chapter", Structure, "\\chapter{${1:title}}", "Chapter heading"),
+    ("section", Structure, "\\section{${1:title}}", "Section heading"),
+    ("subsection", Structure, "\\subsection{${1:title}}", "Subsection heading"),
+    ("subsubsection", Structure, "\\subsubsection{${1:title}}", "Subsubsection heading"),
+    ("paragraph", Structure, "\\paragraph{${1:title}}", "Run-in paragraph heading"),
+    ("title", Structure, "\\title{${1:title}}", "Document title for \\maketitle"),
+    ("author", Structure, "\\author{${1:name}}", "Document author for \\maketitle"),
+    ("date", Structure, "\\date{${1:\\today}}", "Document date for \\maketitle"),
+    ("maketitle", Structure, "\\maketitle", "Typeset the title block"),
+    ("tableofcontents", Structure, "\\tableofcontents", "Table of contents"),
+    ("listoffigures", Structure, "\\listoffigures", "List of figures"),
+    ("listoftables", Structure, "\\listoftables", "List of tables"),
+    ("appendix", Structure, "\\appendix", "Start the appendices"),
+    ("input", Structure, "\\input{${1:file}}", "Read another file in place"),
+    ("include", Structure, "\\include{${1:file}}", "Include a chapter file on a new page"),
+    ("includegraphics", Structure, "\\includegraphics[width=${2:\\linewidth}]{${1:file}}", "Insert an image (graphicx)"),
+    ("caption", Structure, "\\caption{${1:text}}", "Caption of a figure or table"),
+    ("item", Structure, "\\item $0", "List item"),
+    ("footnote", Structure, "\\footnote{${1:text}}", "Footnote"),
+    ("bibliography", Structure, "\\bibliography{${1:references}}", "BibTeX bibliography from a .bib file"),
+    ("bibliographystyle", Structure, "\\bibliographystyle{${1:plain}}", "BibTeX bibliography style"),
+    ("addbibresource", Structure, "\\addbibresource{${1:references.bib}}", "Add a .bib file (biblatex)"),
+    ("printbibliography", Structure, "\\printbibliography", "Typeset the bibliography (biblatex)"),
+    // Text formatting
+    ("textbf", Text, "\\textbf{${1:text}}", "Bold text"),
+    ("textit", Text, "\\textit{${1:text}}", "Italic text"),
+    ("emph", Text, "\\emph{${1:text}}", "Emphasized text"),
+    ("underline", Text, "\\underline{${1:text}}", "Underlined text"),
+    ("texttt", Text, "\\texttt{${1:text}}", "Monospaced text"),
+    ("textsc", Text, "\\textsc{${1:text}}", "Small capitals"),
+    ("textsf", Text, "\\textsf{${1:text}}", "Sans-serif text"),
+    ("textrm", Text, "\\textrm{${1:text}}", "Roman text"),
+    ("textsuperscript", Text, "\\textsuperscript{${1:text}}", "Superscript text"),
+    ("textsubscript", Text, "\\textsubscript{${1:text}}", "Subscript text"),
+    ("tiny", Text, "{\\tiny ${1:text}}", "Smallest font size"),
+    ("small", Text, "{\\small ${1:text}}", "Small font size"),
+    ("large", Text, "{\\large ${1:text}}", "Large font size"),
+    ("Large", Text, "{\\Large ${1:text}}", "Larger font size"),
+    ("huge", Text, "{\\huge ${1:text}}", "Huge font size"),
+    ("url", Text, "\\url{${1:address}}", "URL (url or hyperref)"),
+    ("href", Text, "\\href{${1:address}}{${2:text}}", "Hyperlink (hyperref)"),
+    ("today", Text, "\\today", "Today's date"),
+    ("LaTeX", Text, "\\LaTeX{}", "The LaTeX logo"),
+    ("ldots", Text, "\\ldots", "Ellipsis"),
+    // Cross-references and citations
+    ("label", References, "\\label{${1:key}}", "Label for cross-references"),
+    ("ref", References, "\\ref{${1:key}}", "Number of a labelled item"),
+    ("eqref", References, "\\eqref{${1:key}}", "Equation number in parentheses (amsmath)"),
+    ("pageref", References, "\\pageref{${1:key}}", "Page of a labelled item"),
+    ("autoref", References, "\\autoref{${1:key}}", "Reference with its type name (hyperref)"),
+    ("cref", References, "\\cref{${1:key}}", "Reference with its type name (cleveref)"),
+    ("cite", References, "\\cite{${1:key}}", "Citation"),
+    ("citep", References, "\\citep{${1:key}}", "Parenthetical citation (natbib)"),
+    ("citet", References, "\\citet{${1:key}}", "Textual citation (natbib)"),
+    ("parencite", References, "\\parencite{${1:key}}", "Parenthetical citation (biblatex)"),
+    ("textcite", References, "\\textcite{${1:key}}", "Textual citation (biblatex)"),
+    // Spacing and layout
+    ("newpage", Layout, "\\newpage", "Start a new page"),
+    ("clearpage", Layout, "\\clearpage", "Start a new page after placing pending floats"),
+    ("noindent", Layout, "\\noindent", "Don't indent this paragraph"),
+    ("vspace", Layout, "\\vspace{${1:1em}}", "Vertical space"),
+    ("hspace", Layout, "\\hspace{${1:1em}}", "Horizontal space"),
+    ("hfill", Layout, "\\hfill", "Stretchable horizontal space"),
+    ("vfill", Layout, "\\vfill", "Stretchable vertical space"),
+    ("centering", Layout, "\\centering", "Center the rest of the group"),
+    ("linebreak", Layout, "\\linebreak", "Break the line here"),
+    ("hline", Layout, "\\hline", "Horizontal rule in a table"),
+    ("toprule", Layout, "\\toprule", "Top rule of a table (booktabs)"),
+    ("midrule", Layout, "\\midrule", "Middle rule of a table (booktabs)"),
+    ("bottomrule", Layout, "\\bottomrule", "Bottom rule of a table (booktabs)"),
+    ("multicolumn", Layout, "\\multicolumn{${1:2}}{${2:c}}{${3:text}}", "Table cell spanning columns"),
+    ("newcommand", Layout, "\\newcommand{${1:\\name}}[${2:0}]{${3:definition}}", "Define a command"),
+    // Math constructs
+    ("frac", Math, "\\frac{${1:numerator}}{${2:denominator}}", "Fraction"),
+    ("dfrac", Math, "\\dfrac{${1:numerator}}{${2:denominator}}", "Display-style fraction (amsmath)"),
+    ("sqrt", Math, "\\sqrt{${1:x}}", "Square root"),
+    ("sum", Math, "\\sum_{${1:i=1}}^{${2:n}}", "Summation"),
+    ("prod", Math, "\\prod_{${1:i=1}}^{${2:n}}", "Product"),
+    ("int", Math, "\\int_{${1:a}}^{${2:b}}", "Integral"),
+    ("oint", Math, "\\oint", "Contour integral"),
+    ("lim", Math, "\\lim_{${1:x \\to \\infty}}", "Limit"),
+    ("infty", Math, "\\infty", "Infinity"),
+    ("partial", Math, "\\partial", "Partial derivative"),
+    ("nabla", Math, "\\nabla", "Nabla"),
+    ("mathbf", Math, "\\mathbf{${1:x}}", "Bold math letters"),
+    ("mathrm", Math, "\\mathrm{${1:x}}", "Upright math letters"),
+    ("mathit", Math, "\\mathit{${1:x}}", "Italic math letters"),
+    ("mathcal", Math, "\\mathcal{${1:A}}", "Calligraphic letters"),
+    ("mathbb", Math, "\\mathbb{${1:R}}", "Blackboard bold (amssymb)"),
+    ("text", Math, "\\text{${1:text}}", "Text inside math (amsmath)"),
+    ("overline", Math, "\\overline{${1:x}}", "Line over an expression"),
+    ("hat", Accents, "\\hat{${1:x}}", "Hat accent"),
+    ("widehat", Accents, "\\widehat{${1:xy}}", "Wide hat accent"),
+    ("bar", Accents, "\\bar{${1:x}}", "Bar accent"),
+    ("vec", Accents, "\\vec{${1:x}}", "Vector arrow accent"),
+    ("dot", Accents, "\\dot{${1:x}}", "Dot accent"),
+    ("ddot", Accents, "\\ddot{${1:x}}", "Double dot accent"),
+    ("tilde", Accents, "\\tilde{${1:x}}", "Tilde accent"),
+    // Greek letters
+    ("alpha", Greek, "\\alpha", "Greek small letter alpha"),
+    ("beta", Greek, "\\beta", "Greek small letter beta"),
+    ("gamma", Greek, "\\gamma", "Greek small letter gamma"),
+    ("delta", Greek, "\\delta", "Greek small letter delta"),
+    ("epsilon", Greek, "\\epsilon", "Greek small letter epsilon"),
+    ("varepsilon", Greek, "\\varepsilon", "Greek small letter epsilon, variant"),
+    ("zeta", Greek, "\\zeta", "Greek small letter zeta"),
+    ("eta", Greek, "\\eta", "Greek small letter eta"),
+    ("theta", Greek, "\\theta", "Greek small letter theta"),
+    ("vartheta", Greek, "\\vartheta", "Greek small letter theta, variant"),
+    ("iota", Greek, "\\iota", "Greek small letter iota"),
+    ("kappa", Greek, "\\kappa", "Greek small letter kappa"),
+    ("lambda", Greek, "\\lambda", "Greek small letter lambda"),
+    ("mu", Greek, "\\mu", "Greek small letter mu"),
+    ("nu", Greek, "\\nu", "Greek small letter nu"),
+    ("xi", Greek, "\\xi", "Greek small letter xi"),
+    ("pi", Greek, "\\pi", "Greek small letter pi"),
+    ("rho", Greek, "\\rho", "Greek small letter rho"),
+    ("sigma", Greek, "\\sigma", "Greek small letter sigma"),
+    ("tau", Greek, "\\tau", "Greek small letter tau"),
+    ("upsilon", Greek, "\\upsilon", "Greek small letter upsilon"),
+    ("phi", Greek, "\\phi", "Greek small letter phi"),
+    ("varphi", Greek, "\\varphi", "Greek small letter phi, variant"),
+    ("chi", Greek, "\\chi", "Greek small letter chi"),
+    ("psi", Greek, "\\psi", "Greek small letter psi"),
+    ("omega", Greek, "\\omega", "Greek small letter omega"),
+    ("Gamma", Greek, "\\Gamma", "Greek capital letter gamma"),
+    ("Delta", Greek, "\\Delta", "Greek capital letter delta"),
+    ("Theta", Greek, "\\Theta", "Greek capital letter theta"),
+    ("Lambda", Greek, "\\Lambda", "Greek capital letter lambda"),
+    ("Xi", Greek, "\\Xi", "Greek capital letter xi"),
+    ("Pi", Greek, "\\Pi", "Greek capital letter pi"),
+    ("Sigma", Greek, "\\Sigma", "Greek capital letter sigma"),
+    ("Phi", Greek, "\\Phi", "Greek capital letter phi"),
+    ("Psi", Greek, "\\Psi", "Greek capital letter psi"),
+    ("Omega", Greek, "\\Omega", "Greek capital letter omega"),
+    // Binary operators
+    ("times", Operators, "\\times", "Multiplication sign"),
+    ("cdot", Operators, "\\cdot", "Centered dot"),
+    ("div", Operators, "\\div", "Division sign"),
+    ("pm", Operators, "\\pm", "Plus or minus"),
+    ("mp", Operators, "\\mp", "Minus or plus"),
+    ("circ", Operators, "\\circ", "Composition"),
+    ("otimes", Operators, "\\otimes", "Tensor product"),
+    ("oplus", Operators, "\\oplus", "Direct sum"),
+    ("cup", Operators, "\\cup", "Union"),
+    ("cap", Operators, "\\cap", "Intersection"),
+    ("setminus", Operators, "\\setminus", "Set difference"),
+    ("wedge", Operators, "\\wedge", "Logical and"),
+    ("vee", Operators, "\\vee", "Logical or"),
+    ("neg", Operators, "\\neg", "Logical not"),
+    ("forall", Operators, "\\forall", "For all"),
+    ("exists", Operators, "\\exists", "There exists"),
+    ("emptyset", Operators, "\\emptyset", "Empty set"),
+    ("sin", Operators, "\\sin", "Sine"),
+    ("cos", Operators, "\\cos", "Cosine"),
+    ("tan", Operators, "\\tan", "Tangent"),
+    ("log", Operators, "\\log", "Logarithm"),
+    ("ln", Operators, "\\ln", "Natural logarithm"),
+    ("exp", Operators, "\\exp", "Exponential"),
+    ("max", Operators, "\\max", "Maximum"),
+    ("min", Operators, "\\min", "Minimum"),
+    ("det", Operators, "\\det", "Determinant"),
+    ("operatorname", Operators, "\\operatorname{${1:name}}", "Custom operator name (amsmath)"),
+    // Relations
+    ("leq", Relations, "\\leq", "Less than or equal to"),
+    ("geq", Relations, "\\geq", "Greater than or equal to"),
+    ("neq", Relations, "\\neq", "Not equal to"),
+    ("approx", Relations, "\\approx", "Approximately equal to"),
+    ("equiv", Relations, "\\equiv", "Identical to"),
+    ("sim", Relations, "\\sim", "Similar to"),
+    ("simeq", Relations, "\\simeq", "Asymptotically equal to"),
+    ("propto", Relations, "\\propto", "Proportional to"),
+    ("ll", Relations, "\\ll", "Much less than"),
+    ("gg", Relations, "\\gg", "Much greater than"),
+    ("in", Relations, "\\in", "Element of"),
+    ("notin", Relations, "\\notin", "Not an element of"),
+    ("subset", Relations, "\\subset", "Subset of"),
+    ("subseteq", Relations, "\\subseteq", "Subset of or equal to"),
+    ("supset", Relations, "\\supset", "Superset of"),
+    ("supseteq", Relations, "\\supseteq", "Superset of or equal to"),
+    ("perp", Relations, "\\perp", "Perpendicular to"),
+    ("parallel", Relations, "\\parallel", "Parallel to"),
+    ("mid", Relations, "\\mid", "Divides"),
+    // Arrows
+    ("to", Arrows, "\\to", "Right arrow, e.g. for limits and maps"),
+    ("rightarrow", Arrows, "\\rightarrow", "Right arrow"),
+    ("leftarrow", Arrows, "\\leftarrow", "Left arrow"),
+    ("leftrightarrow", Arrows, "\\leftrightarrow", "Left-right arrow"),
+    ("Rightarrow", Arrows, "\\Rightarrow", "Implies"),
+    ("Leftarrow", Arrows, "\\Leftarrow", "Is implied by"),
+    ("Leftrightarrow", Arrows, "\\Leftrightarrow", "If and only if"),
+    ("implies", Arrows, "\\implies", "Implies, with wide spacing (amsmath)"),
+    ("iff", Arrows, "\\iff", "If and only if, with wide spacing"),
+    ("mapsto", Arrows, "\\mapsto", "Maps to"),
+    ("uparrow", Arrows, "\\uparrow", "Up arrow"),
+    ("downarrow", Arrows, "\\downarrow", "Down arrow"),
+    // Delimiters
+    ("left", Delimiters, "\\left( $1 \\right)", "Delimiters sized to their content"),
+    ("langle", Delimiters, "\\langle", "Left angle bracket"),
+    ("rangle", Delimiters, "\\rangle", "Right angle bracket"),
+    ("lfloor", Delimiters, "\\lfloor", "Left floor bracket"),
+    ("rfloor", Delimiters, "\\rfloor", "Right floor bracket"),
+    ("lceil", Delimiters, "\\lceil", "Left ceiling bracket"),
+    ("rceil", Delimiters, "\\rceil", "Right ceiling bracket"),
+    ("lvert", Delimiters, "\\lvert", "Left vertical bar (amsmath)"),
+    ("rvert", Delimiters, "\\rvert", "Right vertical bar (amsmath)"),
+    ("lVert", Delimiters, "\\lVert", "Left double vertical bar (amsmath)"),
+    ("rVert", Delimiters, "\\rVert", "Right double vertical bar (amsmath)"),
+    // Environments
+    ("document", Environment, "\\begin{document}\n$0\n\\end{document}", "Document body"),
+    ("itemize", Environment, "\\begin{itemize}\n\t\\item $0\n\\end{itemize}", "Bulleted list"),
+    ("enumerate", Environment, "\\begin{enumerate}\n\t\\item $0\n\\end{enumerate}", "Numbered list"),
+    ("description", Environment, "\\begin{description}\n\t\\item[${1:term}] $0\n\\end{description}", "Description list"),
+    ("figure", Environment, "\\begin{figure}[${1:htbp}]\n\t\\centering\n\t\\includegraphics[width=\\linewidth]{${2:file}}\n\t\\caption{${3:caption}}\n\t\\label{fig:${4:key}}\n\\end{figure}", "Floating figure"),
+    ("table", Environment, "\\begin{table}[${1:htbp}]\n\t\\centering\n\t\\caption{${2:caption}}\n\t\\label{tab:${3:key}}\n\t$0\n\\end{table}", "Floating table"),
+    ("tabular", Environment, "\\begin{tabular}{${1:ll}}\n\t$0\n\\end{tabular}", "Table of rows and columns"),
+    ("center", Environment, "\\begin{center}\n\t$0\n\\end{center}", "Centered lines"),
+    ("quote", Environment, "\\begin{quote}\n\t$0\n\\end{quote}", "Indented quotation"),
+    ("verbatim", Environment, "\\begin{verbatim}\n$0\n\\end{verbatim}", "Text typeset exactly as written"),
+    ("minipage", Environment, "\\begin{minipage}{${1:0.5\\linewidth}}\n\t$0\n\\end{minipage}", "Box holding paragraphs"),
+    ("abstract", Environment, "\\begin{abstract}\n\t$0\n\\end{abstract}", "Abstract"),
+    ("equation", Environment, "\\begin{equation}\n\t$0\n\\end{equation}", "Numbered equation"),
+    ("equation*", Environment, "\\begin{equation*}\n\t$0\n\\end{equation*}", "Unnumbered equation (amsmath)"),
+    ("align", Environment, "\\begin{align}\n\t$0\n\\end{align}", "Aligned equations (amsmath)"),
+    ("align*", Environment, "\\begin{align*}\n\t$0\n\\end{align*}", "Unnumbered aligned equations (amsmath)"),
+    ("gather", Environment, "\\begin{gather}\n\t$0\n\\end{gather}", "Centered equations (amsmath)"),
+    ("cases", Environment, "\\begin{cases}\n\t${1:value} & ${2:condition} \\\\\n\\end{cases}", "Piecewise definition (amsmath)"),
+    ("matrix", Environment, "\\begin{matrix}\n\t$0\n\\end{matrix}", "Matrix without delimiters (amsmath)"),
+    ("pmatrix", Environment, "\\begin{pmatrix}\n\t$0\n\\end{pmatrix}", "Matrix in parentheses (amsmath)"),
+    ("bmatrix", Environment, "\\begin{bmatrix}\n\t$0\n\\end{bmatrix}", "Matrix in brackets (amsmath)"),
+    ("theorem", Environment, "\\begin{theorem}\n\t$0\n\\end{theorem}", "Theorem, once defined with \\newtheorem"),
+    ("proof", Environment, "\\begin{proof}\n\t$0\n\\end{proof}", "Proof (amsthm)"),
+    ("frame", Environment, "\\begin{frame}{${1:title}}\n\t$0\n\\end{frame}", "Slide (beamer)"),
+];
+
+/// User additions to the catalog, in the same format as what latex_command_catalog returns
+fn get_catalog_path() -> Result<PathBuf, String> {
+    Ok(get_config_dir()?.join("commands.json"))
+}
+
+fn read_user_commands() -> Result<Vec<CommandInfo>, String> {
+    let catalog_path = get_catalog_path()?;
+
+    if !catalog_path.exists() {
+        return Ok(Vec::new());
+    }
+
+    let content = fs::read_to_string(&catalog_path)
+        .map_err(|e| format!("Failed to read command catalog: {}", e))?;
+
+    serde_json::from_str(&content).map_err(|e| format!("Failed to parse command catalog: {}", e))
+}
+
+/// Commands, math symbols and environments for autocomplete. Entries in the user's
+/// commands.json are added, replacing a built-in entry of the same name and category.
+#[tauri::command]
+pub async fn latex_command_catalog() -> Result<Vec<CommandInfo>, String> {
+    let mut catalog: Vec<CommandInfo> = BUILTIN_COMMANDS
+        .iter()
+        .map(|&(name, category, snippet, description)| CommandInfo {
+            name: name.to_string(),
+            category,
+            snippet: snippet.to_string(),
+            description: description.to_string(),
+        })
+        .collect();
+
+    // A broken additions file shouldn't take the built-in entries with it
+    let additions = read_user_commands().unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        Vec::new()
+    });
+    for addition in additions {
+        if addition.name.trim().is_empty() {
+            continue;
+        }
+        match catalog
+            .iter_mut()
+            .find(|c| c.name == addition.name && c.category == addition.category)
+        {
+            Some(existing) => *existing = addition,
+            None => catalog.push(addition),
+        }
+    }
+
+    Ok(catalog)
+}
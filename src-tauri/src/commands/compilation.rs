@@ -1,99 +1,3481 @@
-use std::path::PathBuf;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt::Arguments;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::sync::{Arc, Condvar, Mutex};
+use std::time::{Duration, Instant};
+use tauri::{AppHandle, Emitter};
 use tectonic::config::PersistentConfig;
+use tectonic::docmodel::{DocumentExt, DocumentSetupOptions};
 use tectonic::driver::{OutputFormat, ProcessingSessionBuilder};
-use tectonic_status_base::NoopStatusBackend;
+use tectonic::unstable_opts::UnstableOptions;
+use tectonic_bridge_core::{SecuritySettings, SecurityStance};
+use tectonic_bundles::Bundle;
+use tectonic_docmodel::document::{BuildTargetType, Document};
+use tectonic_errors::Error;
+use tectonic_io_base::{app_dirs, IoProvider, OpenResult};
+use tectonic_status_base::{MessageKind, NoopStatusBackend, StatusBackend};
 
+use super::analysis::{
+    asset_dependencies, check_document_structure, check_includes, detect_bib_engine,
+    preamble_packages, BibEngine, IncludeIssue, PackageInfo, GRAPHICS_EXTENSIONS,
+};
+use super::git::GITIGNORE_PATTERNS;
+use super::project::{
+    build_dir, find_built_pdf, measure_dir, output_name, pdf_is_stale, read_project_meta,
+    record_compile, record_last_compile, set_jobname, write_project_meta, CompileRecord, DiskUsage,
+    LastCompile, OutputPipeline, PathPatterns, ProjectMeta, MAX_TREE_DEPTH,
+};
+use super::settings::{read_global_settings, BundleSource};
+use crate::hooks::{self, HookOutput, HookStage, HookStatus};
+use crate::tex_log::{self, Diagnostic, MissingPackage, Severity};
+use crate::toc::{self, TocEntry};
+use crate::{latex, pdf, storage};
+
+/// Output formats a caller can ask compile_latex_project for
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CompileFormat {
+    Pdf,
+    Xdv,
+    Aux,
+    Html,
+}
+
+impl CompileFormat {
+    fn extension(self) -> &'static str {
+        match self {
+            CompileFormat::Pdf => "pdf",
+            CompileFormat::Xdv => "xdv",
+            CompileFormat::Aux => "aux",
+            CompileFormat::Html => "html",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CompileOptions {
+    /// Formats to return; defaults to just PDF
+    pub output_formats: Vec<CompileFormat>,
+    /// Preamble code to add for this compile only, e.g. `\usepackage{microtype}`
+    pub extra_preamble: Option<String>,
+    /// Override the paper size through geometry, e.g. "a4" or "letter"
+    pub paper_size: Option<String>,
+    pub landscape: bool,
+    /// Output profile to build in a Tectonic.toml project; defaults to "default"
+    pub output_profile: Option<String>,
+    /// Run TeX exactly this many times. Tectonic can pin the number of passes but not
+    /// cap its own rerun detection, so this replaces it rather than limiting it.
+    pub max_passes: Option<u32>,
+    /// Run TeX once, with no reruns, to time a single pass against a full build.
+    /// Tectonic still runs BibTeX or biber when the pass asks for it, as it can't be
+    /// told not to, but TeX doesn't read their output again. References, citations and
+    /// the table of contents come from the .aux files of the last build, or are missing.
+    pub single_pass: bool,
+    /// Pass the `draft` class option: figures become placeholder boxes and overfull
+    /// lines are marked, which keeps the edit loop fast
+    pub draft: bool,
+    /// Stamp this text across every page, e.g. "DRAFT", with the date and time of the
+    /// compile under it, through draftwatermark; an empty text stamps "DRAFT". Only the
+    /// compiled source gets it, not the file.
+    pub watermark: Option<String>,
+    /// Compile `source` from memory and leave the file on disk untouched, so a preview
+    /// of unsaved edits doesn't save them or trip save_file's conflict check. Without
+    /// it, `source` is saved to the file first.
+    pub preview: bool,
+    /// Save `source` to the file before compiling it. Unset saves it; false compiles it
+    /// from memory as `preview` does, its includes still resolving from the project.
+    pub persist_source: Option<bool>,
+    /// Output base name, e.g. "thesis-2024" to build thesis-2024.pdf. It is remembered
+    /// for the file so check_pdf_exists and load_pdf find the output; an empty name goes
+    /// back to the file's stem.
+    pub jobname: Option<String>,
+    /// Typeset only these \include files, e.g. `chapters/intro`, through \includeonly.
+    /// References into the others resolve from their .aux files of the last full build.
+    pub include_only: Vec<String>,
+    /// Let the document run shell commands through \write18, from the project directory.
+    /// Like build hooks, this needs allow_hooks in the global settings.
+    pub shell_escape: bool,
+    /// Compile profile from the project settings to combine with these options (see
+    /// apply_profile); unrelated to Tectonic.toml output profiles
+    pub profile: Option<String>,
+    /// Name chosen by the caller for polling this compile's status lines through
+    /// compile_progress_log while it runs
+    pub progress_token: Option<String>,
+    /// Which of the TeX log's diagnostics to return; warnings the app adds itself, such
+    /// as the page limit's, are always returned
+    pub diagnostics_mode: DiagnosticsMode,
+}
+
+/// How much of the TeX log a compile parses into diagnostics. A large document logs
+/// thousands of warning lines, which a preview compiled on every edit can skip.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DiagnosticsMode {
+    /// Nothing from the log, so the error and warning counts stay 0
+    None,
+    /// Everything, but only when the build failed: TeX stopped, an output is missing or
+    /// the log has an error. A clean build's warnings aren't parsed.
+    ErrorsOnly,
+    #[default]
+    All,
+}
+
+impl DiagnosticsMode {
+    /// The diagnostics in `log` this mode asks for, given whether the build failed
+    /// before looking at the log
+    fn parse(self, log: &str, failed: bool) -> Vec<Diagnostic> {
+        let wanted = match self {
+            DiagnosticsMode::None => false,
+            DiagnosticsMode::ErrorsOnly => failed || tex_log::has_errors(log),
+            DiagnosticsMode::All => true,
+        };
+        if wanted {
+            tex_log::parse_log(log)
+        } else {
+            Vec::new()
+        }
+    }
+}
+
+/// A file's last successful compile, compared with how it would be compiled now
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LastCompileInfo {
+    #[serde(flatten)]
+    pub last: LastCompile,
+    /// The engine or Tectonic version has changed since, so the PDF should be rebuilt
+    pub engine_changed: bool,
+    /// Names of the options given to last_compile_info that differ from that compile
+    pub changed_options: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CompileResult {
+    /// Output bytes keyed by format extension ("pdf", "xdv", ...)
+    pub outputs: BTreeMap<String, Vec<u8>>,
+    /// Errors and warnings from the TeX log
+    pub diagnostics: Vec<Diagnostic>,
+    pub error_count: usize,
+    pub warning_count: usize,
+    /// TeX passes Tectonic ran, including reruns to settle references
+    pub passes: u32,
+    /// Compiled with CompileOptions::single_pass, so references and citations may be
+    /// wrong or unresolved
+    #[serde(default)]
+    pub single_pass: bool,
+    /// Every requested output was produced, even if TeX reported errors along the way
+    pub succeeded: bool,
+    /// The project's pre_build and post_build hooks, in the order they ran
+    pub hooks: Vec<HookOutput>,
+    /// Files TeX read from disk, project-relative (absolute for extra input paths), to
+    /// recompile when one of them changes; bundle files aren't listed. Empty for
+    /// Tectonic.toml projects.
+    pub input_files: Vec<String>,
+    /// Set when the root file's PDF has more pages than the project's max_pages allows
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub page_limit_exceeded: Option<PageLimitExceeded>,
+}
+
+/// A PDF over the project's page limit, e.g. a conference's
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PageLimitExceeded {
+    pub pages: u32,
+    pub max_pages: u32,
+}
+
+/// What a compile started with a progress_token has reported so far
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct CompileProgress {
+    /// The latest status lines, oldest first, e.g. "Running TeX ..." for each pass
+    pub lines: Vec<String>,
+    /// The compile has ended. Its lines are dropped once this has been read.
+    pub finished: bool,
+}
+
+/// A bundle download in progress, as the `bundle-download` event and
+/// bundle_download_status report it. Tectonic fetches bundle files one by one and only
+/// says which file it starts on, so there are no byte counts or totals.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BundleDownload {
+    pub files_downloaded: usize,
+    /// The file being downloaded now
+    pub current_file: Option<String>,
+    /// Since the first file of this download started
+    pub elapsed_secs: u64,
+    /// Only set on the last event prefetch_bundle sends
+    pub finished: bool,
+}
+
+/// Outcome of one file in a compile_all run
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FileCompileResult {
+    pub file_path: String,
+    pub result: Option<CompileResult>,
+    /// Why the file could not be compiled at all
+    pub error: Option<CompileError>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CompileErrorKind {
+    Failed,
+    /// The bundle (or a file from it) couldn't be downloaded, usually because the
+    /// machine is offline and the packages aren't cached yet
+    BundleUnavailable,
+    /// The compile ran longer than the compile_timeout_secs setting allows
+    Timeout,
+    /// Too little free space on the build directory's disk to write the outputs, or on
+    /// the project's under the low-disk threshold for a compile on save
+    InsufficientDiskSpace,
+}
+
+/// Why a compile failed, with whatever TeX logged before it stopped
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CompileError {
+    pub kind: CompileErrorKind,
+    pub message: String,
+    /// Full TeX log, empty if the failure happened before TeX ran
+    pub log: String,
+    pub diagnostics: Vec<Diagnostic>,
+    /// Build hooks that ran before the failure
+    pub hooks: Vec<HookOutput>,
+    /// Free bytes on the disk that was too full, for InsufficientDiskSpace
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub available_bytes: Option<u64>,
+}
+
+impl From<String> for CompileError {
+    fn from(message: String) -> Self {
+        CompileError {
+            kind: CompileErrorKind::Failed,
+            message,
+            log: String::new(),
+            diagnostics: Vec::new(),
+            hooks: Vec::new(),
+            available_bytes: None,
+        }
+    }
+}
+
+impl CompileError {
+    fn bundle_unavailable(message: String) -> Self {
+        CompileError {
+            kind: CompileErrorKind::BundleUnavailable,
+            ..message.into()
+        }
+    }
+
+    /// `message` for a Tectonic failure, classified by whether `error` was a download
+    fn from_tectonic(message: String, error: &dyn std::fmt::Display) -> Self {
+        if is_network_error(&format!("{:#}", error)) {
+            CompileError::bundle_unavailable(message)
+        } else {
+            message.into()
+        }
+    }
+}
+
+impl From<&str> for CompileError {
+    fn from(message: &str) -> Self {
+        message.to_string().into()
+    }
+}
+
+/// The engine Tectonic drives
+const ENGINE: &str = "xetex";
+
+/// Converts XDV to PDF for the xdv output pipeline, from an installed TeX distribution
+const XDV_CONVERTER: &str = "xdvipdfmx";
+
+/// Fragments of the network errors Tectonic passes on when a download fails
+const NETWORK_ERROR_MARKERS: &[&str] = &[
+    "error sending request",
+    "dns error",
+    "failed to lookup address",
+    "could not resolve host",
+    "connection refused",
+    "connection reset",
+    "network is unreachable",
+    "operation timed out",
+];
+
+/// Whether an error (printed with its cause chain) comes from a failed download
+fn is_network_error(error: &str) -> bool {
+    let error = error.to_lowercase();
+    NETWORK_ERROR_MARKERS
+        .iter()
+        .any(|marker| error.contains(marker))
+}
+
+/// Paper sizes accepted by the geometry override, as geometry's `<name>paper` options
+const PAPER_SIZES: &[&str] = &[
+    "a0",
+    "a1",
+    "a2",
+    "a3",
+    "a4",
+    "a5",
+    "a6",
+    "b0",
+    "b1",
+    "b2",
+    "b3",
+    "b4",
+    "b5",
+    "b6",
+    "letter",
+    "legal",
+    "executive",
+];
+
+/// Largest bundle file read_bundle_file returns; fonts and formats can be far bigger
+const MAX_BUNDLE_FILE_BYTES: u64 = 4 * 1024 * 1024;
+
+/// Free space below which a compile isn't started. A large document's PDF,
+/// intermediates and formats easily take this much.
+const MIN_FREE_BYTES: u64 = 50 * 1024 * 1024;
+
+/// Builds shorter than this aren't checked for shrinking; small documents change length
+/// by large fractions all the time
+const MIN_PAGES_FOR_SHRINK_CHECK: u32 = 8;
+
+/// A build at most 1/SHRINK_FACTOR of the previous page count gets a warning
+const SHRINK_FACTOR: u32 = 4;
+
+/// Status lines kept per compile for compile_progress_log
+const MAX_PROGRESS_LINES: usize = 200;
+
+/// Progress of compiles started with a progress_token, by token
+static COMPILE_PROGRESS: Mutex<BTreeMap<String, CompileProgress>> = Mutex::new(BTreeMap::new());
+
+/// How often prefetch_bundle reports on a download, even when no new file has started
+const DOWNLOAD_HEARTBEAT: Duration = Duration::from_secs(2);
+
+/// A small document whose compile fetches the format and the files most documents use
+const PREFETCH_DOCUMENT: &str = r"\documentclass{article}
+\usepackage{amsmath}
+\usepackage{graphicx}
+\begin{document}
+Incipit
+\end{document}
+";
+
+/// Bundle files being downloaded, by any compile
+struct DownloadState {
+    started: Instant,
+    files: usize,
+    current: Option<String>,
+    /// DownloadWatches that have seen a download and are still running
+    watchers: usize,
+}
+
+static BUNDLE_DOWNLOAD: Mutex<Option<DownloadState>> = Mutex::new(None);
+
+/// Package and class names in a bundle
+struct BundlePackages {
+    /// The active bundle when they were read, so switching bundles reads them again
+    bundle: Option<String>,
+    packages: Vec<String>,
+}
+
+/// Packages of the active bundle, filled on first request
+static BUNDLE_PACKAGES: Mutex<Option<BundlePackages>> = Mutex::new(None);
+
+/// Pick the session's output format for the requested set. Tectonic produces one final
+/// format per run; the XDV and .aux files of a PDF build are intermediates that are kept
+/// when asked for, so a single pass can serve every supported combination.
+fn session_format(formats: &[CompileFormat]) -> Result<(OutputFormat, bool), String> {
+    if formats.contains(&CompileFormat::Html) {
+        return Err(
+            "HTML output is not supported: Tectonic's HTML mode requires a dedicated template setup"
+                .to_string(),
+        );
+    }
+
+    let primary = if formats.contains(&CompileFormat::Pdf) {
+        OutputFormat::Pdf
+    } else if formats.contains(&CompileFormat::Xdv) {
+        OutputFormat::Xdv
+    } else {
+        OutputFormat::Aux
+    };
+
+    let needs_intermediates = match primary {
+        OutputFormat::Pdf => formats.iter().any(|f| *f != CompileFormat::Pdf),
+        OutputFormat::Xdv => formats.contains(&CompileFormat::Aux),
+        _ => false,
+    };
+
+    Ok((primary, needs_intermediates))
+}
+
+/// Resolve the project's extra input paths, which must all be existing directories
+fn resolve_search_paths(project_dir: &Path, paths: &[String]) -> Result<Vec<PathBuf>, String> {
+    paths
+        .iter()
+        .map(|path| {
+            let resolved = project_dir.join(path);
+            if resolved.is_dir() {
+                Ok(resolved)
+            } else {
+                Err(format!("Extra input path is not a directory: {}", path))
+            }
+        })
+        .collect()
+}
+
+/// Preamble that sets the page geometry, if the options ask for an override. Loading
+/// geometry again without options is harmless when the document already uses it, and
+/// the later \geometry call wins over the document's own settings.
+fn geometry_preamble(paper_size: Option<&str>, landscape: bool) -> Result<Option<String>, String> {
+    let mut settings = Vec::new();
+
+    if let Some(size) = paper_size {
+        let normalized = size.trim().to_lowercase();
+        let name = normalized.strip_suffix("paper").unwrap_or(&normalized);
+        if !PAPER_SIZES.contains(&name) {
+            return Err(format!(
+                "Unsupported paper size: {} (expected one of {})",
+                size,
+                PAPER_SIZES.join(", ")
+            ));
+        }
+        settings.push(format!("{}paper", name));
+    }
+    if landscape {
+        settings.push("landscape".to_string());
+    }
+
+    if settings.is_empty() {
+        return Ok(None);
+    }
+
+    Ok(Some(format!(
+        "\\usepackage{{geometry}}\\geometry{{{}}}",
+        settings.join(",")
+    )))
+}
+
+/// Package compile-time watermarks are stamped with
+const WATERMARK_PACKAGE: &str = "draftwatermark";
+
+/// Text stamped when a watermark is asked for without any
+const DEFAULT_WATERMARK: &str = "DRAFT";
+
+/// Font of the date and time under a watermark, smaller than the text above it
+const WATERMARK_DATE_FONT: &str = "\\fontsize{14pt}{17pt}\\selectfont";
+
+/// Preamble code stamping `text` across every page, with the date and time of the
+/// compile under it. draftwatermark is only loaded when `loaded` says the document
+/// doesn't already; the options set here come after the document's own and win.
+fn watermark_preamble(text: &str, loaded: bool) -> String {
+    let text = match text.trim() {
+        "" => DEFAULT_WATERMARK,
+        text => text,
+    };
+    let stamped_at = chrono::Local::now().format("%Y-%m-%d %H:%M");
+    let label = format!(
+        "\\shortstack{{{}\\\\{{{} {}}}}}",
+        latex::escape_text(text),
+        WATERMARK_DATE_FONT,
+        stamped_at
+    );
+
+    let mut preamble = String::new();
+    if !loaded {
+        preamble.push_str(&format!("\\usepackage{{{}}}", WATERMARK_PACKAGE));
+    }
+    preamble.push_str(&format!("\\DraftwatermarkOptions{{text={{{}}}}}", label));
+    preamble
+}
+
+/// Add `option` to the \documentclass options, on the same line so reported line
+/// numbers don't shift. Class options are global, so packages such as graphicx see
+/// them too.
+fn add_class_option(source: &str, option: &str) -> Result<String, String> {
+    let masked = latex::mask_comments(source);
+    let class = latex::commands(&masked)
+        .find(|c| c.name == "documentclass")
+        .ok_or("Cannot enable draft mode: no \\documentclass found")?;
+
+    let next = latex::skip_arg_whitespace(masked.as_bytes(), class.end);
+    let (at, insertion) = match latex::read_group(&masked, next, b'[', b']') {
+        Some((options, after)) if options.trim().is_empty() => (after - 1, option.to_string()),
+        Some((_, after)) => (after - 1, format!(",{}", option)),
+        None => (class.end, format!("[{}]", option)),
+    };
+
+    Ok(format!("{}{}{}", &source[..at], insertion, &source[at..]))
+}
+
+/// Whether an executable called `name` is on PATH
+pub(crate) fn on_path(name: &str) -> bool {
+    let Some(path) = std::env::var_os("PATH") else {
+        return false;
+    };
+    std::env::split_paths(&path).any(|dir| {
+        dir.join(name).is_file() || (cfg!(windows) && dir.join(format!("{}.exe", name)).is_file())
+    })
+}
+
+/// Tectonic runs BibTeX itself whenever the .aux file asks for it, but biber is an
+/// external program. Fail with a clear message up front rather than an obscure one
+/// after the first pass when a biblatex document needs biber and it isn't installed.
+fn check_bib_engine(project_dir: &Path, file_path: &str) -> Result<(), CompileError> {
+    // Detection problems (e.g. an unreadable include) surface in the compile itself
+    if detect_bib_engine(project_dir, file_path).ok().flatten() == Some(BibEngine::Biber)
+        && !on_path("biber")
+    {
+        return Err(
+            "This document uses biblatex with biber, which is not installed. Install biber, \
+             or load biblatex with backend=bibtex to use the built-in BibTeX."
+                .into(),
+        );
+    }
+    Ok(())
+}
+
+/// The settings of a compile with `options` that change its output
+fn last_compile(options: &CompileOptions) -> LastCompile {
+    LastCompile {
+        engine: ENGINE.to_string(),
+        tectonic_version: env!("TECTONIC_VERSION").to_string(),
+        draft: options.draft,
+        paper_size: options.paper_size.clone(),
+        landscape: options.landscape,
+        extra_preamble: options.extra_preamble.clone(),
+        include_only: options.include_only.clone(),
+        watermark: options.watermark.clone(),
+        profile: options.profile.clone(),
+        compiled_at: chrono::Utc::now().to_rfc3339(),
+        pdf_pages: None,
+    }
+}
+
+/// A warning when a PDF came out at a small fraction of the pages of the last successful
+/// build with the same \includeonly selection. TeX can stop early, e.g. at a stray
+/// \end{document} in an included file, and still write a PDF without reporting an error.
+fn shrink_warning(previous: Option<&LastCompile>, current: &LastCompile) -> Option<Diagnostic> {
+    let previous = previous.filter(|p| p.include_only == current.include_only)?;
+    let before = previous.pdf_pages?;
+    let after = current.pdf_pages?;
+    if before < MIN_PAGES_FOR_SHRINK_CHECK || after * SHRINK_FACTOR > before {
+        return None;
+    }
+
+    Some(Diagnostic {
+        severity: Severity::Warning,
+        message: format!(
+            "The PDF has {} page{}, down from {} in the last successful build; an error may \
+             have cut the document short",
+            after,
+            if after == 1 { "" } else { "s" },
+            before
+        ),
+        file: None,
+        line: None,
+    })
+}
+
+/// How far the PDF of `file_path` goes over the project's max_pages, which only the root
+/// file's is held to; supplementary material and the like built alongside aren't
+fn page_limit_exceeded(
+    meta: &ProjectMeta,
+    file_path: &str,
+    pages: Option<u32>,
+) -> Option<PageLimitExceeded> {
+    let max_pages = meta.max_pages?;
+    let pages = pages?;
+    let is_root =
+        latex::project_file(file_path).ok()? == latex::project_file(&meta.root_file).ok()?;
+    (is_root && pages > max_pages).then_some(PageLimitExceeded { pages, max_pages })
+}
+
+/// Refuse to build into `dir` when its disk is nearly full: TeX would stop partway with
+/// an unhelpful write error, possibly leaving a truncated PDF. Disks whose free space
+/// can't be read aren't checked.
+fn check_disk_space(dir: &Path) -> Result<(), CompileError> {
+    let Ok(available) = fs2::available_space(dir) else {
+        return Ok(());
+    };
+    if available >= MIN_FREE_BYTES {
+        return Ok(());
+    }
+
+    Err(CompileError {
+        kind: CompileErrorKind::InsufficientDiskSpace,
+        available_bytes: Some(available),
+        ..format!(
+            "Only {:.1} MB free on the disk the build is written to; free up some space and \
+             compile again",
+            available as f64 / (1024.0 * 1024.0)
+        )
+        .into()
+    })
+}
+
+/// Combine `options` with the compile profile they name, returning the profile's output
+/// directory. Flags are on when either turns them on, a paper size or watermark in
+/// `options` wins over the profile's, and both preambles are used, the profile's first.
+fn apply_profile(
+    project_dir: &Path,
+    mut options: CompileOptions,
+) -> Result<(CompileOptions, Option<String>), String> {
+    let Some(name) = options.profile.clone() else {
+        return Ok((options, None));
+    };
+    let meta = read_project_meta(project_dir)?;
+    let profile = meta
+        .profiles
+        .get(&name)
+        .cloned()
+        .ok_or_else(|| format!("No compile profile named {}", name))?;
+
+    if let Some(engine) = &profile.engine {
+        if engine != ENGINE {
+            return Err(format!(
+                "Profile {} uses the {} engine, but only {} is available",
+                name, engine, ENGINE
+            ));
+        }
+    }
+    if let Some(dir) = &profile.output_dir {
+        latex::normalize_relative(Path::new(dir))
+            .filter(|dir| !dir.as_os_str().is_empty())
+            .ok_or_else(|| format!("Invalid output_dir in profile {}: {}", name, dir))?;
+    }
+
+    options.draft |= profile.draft.unwrap_or(false);
+    options.landscape |= profile.landscape.unwrap_or(false);
+    options.shell_escape |= profile.shell_escape.unwrap_or(false);
+    options.paper_size = options.paper_size.or(profile.paper_size);
+    options.watermark = options.watermark.or(profile.watermark);
+    options.extra_preamble = match (profile.extra_preamble, options.extra_preamble) {
+        (Some(first), Some(second)) => Some(format!("{}\n{}", first, second)),
+        (first, second) => first.or(second),
+    };
+
+    Ok((options, profile.output_dir))
+}
+
+/// Preamble code a compile with `options`, already combined with their profile, adds to
+/// the document: their own, then \includeonly, then the page geometry, then the
+/// watermark. `loaded` is the packages the document loads itself, so a package it
+/// already has isn't loaded twice.
+fn options_preamble(options: &CompileOptions, loaded: &[PackageInfo]) -> Result<String, String> {
+    let mut preamble = options.extra_preamble.clone().unwrap_or_default();
+    if !options.include_only.is_empty() {
+        let mut names = Vec::new();
+        for name in &options.include_only {
+            let name = name.trim();
+            let name = name.strip_suffix(".tex").unwrap_or(name);
+            if name.is_empty() || name.contains([',', '{', '}', '\\']) {
+                return Err(format!("Invalid include_only entry: {}", name));
+            }
+            names.push(name);
+        }
+        preamble.push_str(&format!("\n\\includeonly{{{}}}", names.join(",")));
+    }
+    if let Some(geometry) = geometry_preamble(options.paper_size.as_deref(), options.landscape)? {
+        preamble.push('\n');
+        preamble.push_str(&geometry);
+    }
+    if let Some(text) = &options.watermark {
+        let own = preamble_packages(Path::new(""), "", &preamble);
+        let watermark_loaded = loaded
+            .iter()
+            .chain(&own)
+            .any(|package| package.name == WATERMARK_PACKAGE);
+        preamble.push('\n');
+        preamble.push_str(&watermark_preamble(text, watermark_loaded));
+    }
+    Ok(preamble)
+}
+
+/// Copy a build's outputs, named after `stem`, into the project's `output_dir`
+fn copy_outputs(
+    project_dir: &Path,
+    output_dir: &str,
+    stem: &str,
+    result: &CompileResult,
+) -> Result<(), String> {
+    let dir = project_dir.join(output_dir);
+    std::fs::create_dir_all(&dir).map_err(|e| format!("Failed to create {}: {}", output_dir, e))?;
+    for (extension, bytes) in &result.outputs {
+        let name = format!("{}.{}", stem, extension);
+        storage::write_atomic(&dir.join(&name), bytes)
+            .map_err(|e| format!("Failed to copy {} to {}: {}", name, output_dir, e))?;
+    }
+    Ok(())
+}
+
+/// Run one of the project's build hooks, or record that it was skipped
+fn build_hook(project_dir: &Path, stage: HookStage, command: &str, allowed: bool) -> HookOutput {
+    if !allowed {
+        return HookOutput::disabled(stage, command);
+    }
+    eprintln!("Running {:?} build hook: {}", stage, command);
+    hooks::run_hook(project_dir, stage, command)
+}
+
+/// Replace each `{{name}}` in `template` with the value of variable `name`, escaped
+/// for LaTeX. Double braces around anything else, such as `{{a}}` in math, are TeX's
+/// and stay as they are.
+fn fill_template(template: &str, variables: &BTreeMap<String, String>) -> String {
+    let mut filled = String::with_capacity(template.len());
+    let mut rest = template;
+
+    while let Some(start) = rest.find("{{") {
+        let after = &rest[start + 2..];
+        let placeholder = after
+            .find("}}")
+            .and_then(|end| Some((end, variables.get(after[..end].trim())?)));
+        let Some((end, value)) = placeholder else {
+            filled.push_str(&rest[..start + 2]);
+            rest = after;
+            continue;
+        };
+
+        filled.push_str(&rest[..start]);
+        filled.push_str(&latex::escape_text(value));
+        rest = &after[end + 2..];
+    }
+
+    filled.push_str(rest);
+    filled
+}
+
+/// Insert `preamble` just before \begin{document}. It is folded onto that line so the
+/// line numbers TeX reports still match the user's file.
+fn inject_preamble(source: &str, preamble: &str) -> Result<String, String> {
+    let masked = latex::mask_comments(source);
+    let begin = latex::find_begin_document(&masked)
+        .ok_or("Cannot add preamble: no \\begin{document} found")?;
+
+    let folded = preamble
+        .lines()
+        .map(|line| latex::strip_comment(line).trim())
+        .filter(|line| !line.is_empty())
+        .collect::<Vec<_>>()
+        .join(" ");
+
+    let (before, rest) = source.split_at(begin);
+    Ok(format!("{}{} {}", before, folded, rest))
+}
+
+/// The Tectonic.toml document at the project root, if this is a Tectonic V2 project
+fn open_workspace_document(project_dir: &Path) -> Result<Option<Document>, String> {
+    let toml_path = project_dir.join("Tectonic.toml");
+    if !toml_path.is_file() {
+        return Ok(None);
+    }
+
+    let mut file = std::fs::File::open(&toml_path)
+        .map_err(|e| format!("Failed to open Tectonic.toml: {}", e))?;
+
+    Document::new_from_toml(project_dir, build_dir(project_dir)?, &mut file)
+        .map(Some)
+        .map_err(|e| format!("Failed to parse Tectonic.toml: {}", e))
+}
+
+/// Choose which of the document's output profiles to build
+fn pick_output_profile(document: &Document, requested: Option<&str>) -> Result<String, String> {
+    let name = match requested {
+        Some(name) => name.to_string(),
+        None => {
+            let mut names: Vec<&str> = document.output_names().collect();
+            names.sort();
+            names
+                .iter()
+                .find(|n| **n == "default")
+                .or(names.first())
+                .ok_or("Tectonic.toml defines no outputs")?
+                .to_string()
+        }
+    };
+
+    let profile = document
+        .outputs
+        .get(&name)
+        .ok_or_else(|| format!("Unknown output profile: {}", name))?;
+
+    if profile.target_type != BuildTargetType::Pdf {
+        return Err(format!("Output profile {} does not produce a PDF", name));
+    }
+
+    Ok(name)
+}
+
+/// Set up a session for one output profile of a Tectonic.toml document. Its sources,
+/// bundle and TeX format all come from the document; outputs go to build/<profile>/.
+fn workspace_session(
+    document: &Document,
+    profile: &str,
+    format_cache: &Path,
+    status: &mut dyn StatusBackend,
+) -> Result<ProcessingSessionBuilder, CompileError> {
+    // This opens the document's bundle, which may need downloading
+    let mut builder = document
+        .setup_session(profile, &DocumentSetupOptions::default(), status)
+        .map_err(|e| {
+            CompileError::from_tectonic(format!("Failed to set up Tectonic.toml build: {}", e), &e)
+        })?;
+
+    builder
+        .format_cache_path(format_cache)
+        .keep_logs(true)
+        .print_stdout(false)
+        .unstables(UnstableOptions {
+            continue_on_errors: true,
+            ..Default::default()
+        });
+
+    Ok(builder)
+}
+
+/// Run a prepared session and collect `<stem>.<ext>` for each format from `build_dir`,
+/// along with the diagnostics in its log
+fn run_session(
+    mut builder: ProcessingSessionBuilder,
+    build_dir: &Path,
+    stem: &str,
+    formats: &[CompileFormat],
+    max_passes: Option<u32>,
+    diagnostics: DiagnosticsMode,
+    status: &mut dyn StatusBackend,
+) -> Result<CompileResult, CompileError> {
+    // Outputs left over from an earlier build would hide that this one produced none,
+    // and its log would be read for this one's diagnostics
+    let log_path = build_dir.join(format!("{}.log", stem));
+    for format in formats {
+        let stale = build_dir.join(format!("{}.{}", stem, format.extension()));
+        let _ = std::fs::remove_file(stale);
+    }
+    let _ = std::fs::remove_file(&log_path);
+
+    if let Some(passes) = max_passes {
+        builder.reruns(passes.saturating_sub(1) as usize);
+    }
+
+    // Create and run the session. Either step may need files from the bundle.
+    let mut session = builder
+        .create(status)
+        .map_err(|e| CompileError::from_tectonic(format!("Failed to create session: {}", e), &e))?;
+
+    let mut observer = SessionObserver {
+        inner: status,
+        passes: 0,
+        error_log: Vec::new(),
+    };
+    if let Err(e) = session.run(&mut observer) {
+        // A failed TeX pass still writes its log, as keep_logs is set. A session that
+        // fails before TeX runs, e.g. building the format, writes none, and what it
+        // dumped is all there is.
+        let log = match std::fs::read(&log_path) {
+            Ok(bytes) => String::from_utf8_lossy(&bytes).into_owned(),
+            Err(_) => String::from_utf8_lossy(&observer.error_log).into_owned(),
+        };
+        return Err(CompileError {
+            diagnostics: diagnostics.parse(&log, true),
+            log,
+            ..CompileError::from_tectonic(format!("LaTeX compilation failed: {}", e), &e)
+        });
+    }
+    let passes = observer.passes;
+
+    eprintln!("Tectonic compilation completed successfully");
+
+    // Read the generated outputs from build/ directory
+    let mut outputs = BTreeMap::new();
+
+    for format in formats {
+        let output_path = build_dir.join(format!("{}.{}", stem, format.extension()));
+
+        let bytes = match std::fs::read(&output_path) {
+            Ok(bytes) if !bytes.is_empty() => bytes,
+            Ok(_) => continue,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => continue,
+            Err(e) => {
+                return Err(format!("Failed to read {}: {}", output_path.display(), e).into());
+            }
+        };
+
+        eprintln!("Found output at: {}", output_path.display());
+
+        outputs.insert(format.extension().to_string(), bytes);
+    }
+
+    let diagnostics = if diagnostics == DiagnosticsMode::None {
+        Vec::new()
+    } else {
+        let log = std::fs::read(&log_path)
+            .map(|bytes| String::from_utf8_lossy(&bytes).into_owned())
+            .unwrap_or_default();
+        diagnostics.parse(&log, outputs.len() != formats.len())
+    };
+
+    let count = |severity| {
+        diagnostics
+            .iter()
+            .filter(|d| d.severity == severity)
+            .count()
+    };
+
+    Ok(CompileResult {
+        succeeded: outputs.len() == formats.len(),
+        error_count: count(Severity::Error),
+        warning_count: count(Severity::Warning),
+        passes,
+        single_pass: false,
+        outputs,
+        diagnostics,
+        hooks: Vec::new(),
+        input_files: Vec::new(),
+        page_limit_exceeded: None,
+    })
+}
+
+/// Turn the XDV file a build wrote into its PDF with the installed xdvipdfmx, for the
+/// xdv output pipeline. It runs where TeX was rooted, so the figures the XDV file names
+/// resolve as they did for TeX. The XDV stays among the outputs only if it was asked for.
+fn convert_xdv_output(
+    tex_root: &Path,
+    build_dir: &Path,
+    stem: &str,
+    formats: &[CompileFormat],
+    result: &mut CompileResult,
+) -> Result<(), CompileError> {
+    let xdv_path = build_dir.join(format!("{}.xdv", stem));
+    let pdf_path = build_dir.join(format!("{}.pdf", stem));
+    let _ = std::fs::remove_file(&pdf_path);
+
+    // TeX wrote no XDV; the result already says the build didn't succeed
+    if result.outputs.contains_key("xdv") {
+        let output = std::process::Command::new(XDV_CONVERTER)
+            .arg("-q")
+            .arg("-o")
+            .arg(&pdf_path)
+            .arg(&xdv_path)
+            .current_dir(tex_root)
+            .stdin(std::process::Stdio::null())
+            .output()
+            .map_err(|e| format!("Failed to run {}: {}", XDV_CONVERTER, e))?;
+        if !output.status.success() {
+            return Err(CompileError {
+                log: String::from_utf8_lossy(&output.stderr).into_owned(),
+                ..format!("{} failed to convert the XDV output to PDF", XDV_CONVERTER).into()
+            });
+        }
+
+        let pdf = std::fs::read(&pdf_path)
+            .map_err(|e| format!("Failed to read {}: {}", pdf_path.display(), e))?;
+        result.outputs.insert("pdf".to_string(), pdf);
+        if !formats.contains(&CompileFormat::Xdv) {
+            result.outputs.remove("xdv");
+        }
+    }
+
+    result.succeeded = formats
+        .iter()
+        .all(|format| result.outputs.contains_key(format.extension()));
+    Ok(())
+}
+
+/// Inputs listed in the Makefile rules Tectonic writes for a build: the outputs as
+/// targets, then one file per continuation line. Tectonic has no .fls recorder file;
+/// these rules list the same files, minus bundle files and ones the build wrote itself.
+fn read_dependencies(rules_path: &Path) -> Vec<String> {
+    let Ok(rules) = std::fs::read_to_string(rules_path) else {
+        return Vec::new();
+    };
+
+    let mut lines = rules.lines();
+    let first = lines
+        .next()
+        .and_then(|line| line.rsplit_once(':'))
+        .map_or("", |(_, inputs)| inputs);
+    std::iter::once(first)
+        .chain(lines)
+        .map(|line| line.trim().trim_end_matches('\\').trim())
+        .filter(|name| !name.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+/// Forwards to another status backend, counting the TeX passes Tectonic announces and
+/// keeping the log it dumps when a run fails
+struct SessionObserver<'a> {
+    inner: &'a mut dyn StatusBackend,
+    passes: u32,
+    error_log: Vec<u8>,
+}
+
+impl StatusBackend for SessionObserver<'_> {
+    fn report(&mut self, kind: MessageKind, args: Arguments, err: Option<&Error>) {
+        self.inner.report(kind, args, err);
+    }
+
+    fn report_error(&mut self, err: &Error) {
+        self.inner.report_error(err);
+    }
+
+    fn note_highlighted(&mut self, before: &str, highlighted: &str, after: &str) {
+        // Each pass starts with "Running TeX ..." or "Rerunning TeX because ..."
+        if highlighted == "TeX" && (before == "Running " || before == "Rerunning ") {
+            self.passes += 1;
+        }
+        self.inner.note_highlighted(before, highlighted, after);
+    }
+
+    fn dump_error_logs(&mut self, output: &[u8]) {
+        self.error_log.extend_from_slice(output);
+        self.inner.dump_error_logs(output);
+    }
+}
+
+/// Forwards to another status backend, noting the bundle files Tectonic downloads in
+/// BUNDLE_DOWNLOAD. The download counts as over once every watch that saw part of it is
+/// dropped.
+struct DownloadWatch<'a> {
+    inner: &'a mut dyn StatusBackend,
+    downloaded: bool,
+}
+
+impl<'a> DownloadWatch<'a> {
+    fn new(inner: &'a mut dyn StatusBackend) -> Self {
+        DownloadWatch {
+            inner,
+            downloaded: false,
+        }
+    }
+}
+
+impl StatusBackend for DownloadWatch<'_> {
+    fn report(&mut self, kind: MessageKind, args: Arguments, err: Option<&Error>) {
+        let message = args.to_string();
+        if let Some(file) = message.strip_prefix("downloading ") {
+            let mut download = BUNDLE_DOWNLOAD.lock().unwrap();
+            let download = download.get_or_insert_with(|| DownloadState {
+                started: Instant::now(),
+                files: 0,
+                current: None,
+                watchers: 0,
+            });
+            if !self.downloaded {
+                self.downloaded = true;
+                download.watchers += 1;
+            }
+            download.files += 1;
+            download.current = Some(file.to_string());
+        }
+        self.inner.report(kind, args, err);
+    }
+
+    fn report_error(&mut self, err: &Error) {
+        self.inner.report_error(err);
+    }
+
+    fn note_highlighted(&mut self, before: &str, highlighted: &str, after: &str) {
+        self.inner.note_highlighted(before, highlighted, after);
+    }
+
+    fn dump_error_logs(&mut self, output: &[u8]) {
+        self.inner.dump_error_logs(output);
+    }
+}
+
+impl Drop for DownloadWatch<'_> {
+    fn drop(&mut self) {
+        if !self.downloaded {
+            return;
+        }
+        let mut download = BUNDLE_DOWNLOAD.lock().unwrap();
+        if let Some(state) = download.as_mut() {
+            state.watchers -= 1;
+            if state.watchers == 0 {
+                *download = None;
+            }
+        }
+    }
+}
+
+/// The bundle download running now, if any
+fn current_download() -> Option<BundleDownload> {
+    BUNDLE_DOWNLOAD
+        .lock()
+        .unwrap()
+        .as_ref()
+        .map(|state| BundleDownload {
+            files_downloaded: state.files,
+            current_file: state.current.clone(),
+            elapsed_secs: state.started.elapsed().as_secs(),
+            finished: false,
+        })
+}
+
+/// Start collecting the progress of a compile under `token`, replacing an earlier compile
+/// with the same token. Finished compiles nobody read are dropped here.
+fn register_progress(token: &str) {
+    let mut progress = COMPILE_PROGRESS.lock().unwrap();
+    progress.retain(|_, p| !p.finished);
+    progress.insert(token.to_string(), CompileProgress::default());
+}
+
+/// Status backend recording what Tectonic reports for compile_progress_log. The
+/// compile counts as finished once this is dropped, however it ended.
+struct ProgressStatus {
+    token: Option<String>,
+}
+
+impl ProgressStatus {
+    fn push(&self, line: String) {
+        let Some(token) = &self.token else {
+            return;
+        };
+        let mut progress = COMPILE_PROGRESS.lock().unwrap();
+        if let Some(progress) = progress.get_mut(token) {
+            progress.lines.push(line);
+            let excess = progress.lines.len().saturating_sub(MAX_PROGRESS_LINES);
+            progress.lines.drain(..excess);
+        }
+    }
+}
+
+impl Drop for ProgressStatus {
+    fn drop(&mut self) {
+        let Some(token) = &self.token else {
+            return;
+        };
+        if let Some(progress) = COMPILE_PROGRESS.lock().unwrap().get_mut(token) {
+            progress.finished = true;
+        }
+    }
+}
+
+impl StatusBackend for ProgressStatus {
+    fn report(&mut self, kind: MessageKind, args: Arguments, err: Option<&Error>) {
+        let prefix = match kind {
+            MessageKind::Note => "",
+            MessageKind::Warning => "warning: ",
+            MessageKind::Error => "error: ",
+        };
+        self.push(format!("{}{}", prefix, args));
+        if let Some(err) = err {
+            self.push(format!("{}{}", prefix, err));
+        }
+    }
+
+    fn dump_error_logs(&mut self, output: &[u8]) {
+        // The end of the log says where TeX stopped
+        for line in String::from_utf8_lossy(output).lines() {
+            self.push(line.to_string());
+        }
+    }
+}
+
+/// Tectonic configuration shared by every compile in a run
+struct TexEnvironment {
+    config: PersistentConfig,
+    format_cache: PathBuf,
+    /// The bundle chosen in settings; None for the user's default Tectonic bundle
+    bundle: Option<BundleSource>,
+}
+
+impl TexEnvironment {
+    fn open() -> Result<Self, String> {
+        // Get default bundle for LaTeX packages
+        let config = PersistentConfig::open(false)
+            .map_err(|e| format!("Failed to open Tectonic config: {}", e))?;
+
+        let format_cache = config
+            .format_cache_path()
+            .map_err(|e| format!("Failed to get format cache path: {}", e))?;
+
+        let bundle = read_global_settings()?.active_bundle_source()?.cloned();
+
+        Ok(TexEnvironment {
+            config,
+            format_cache,
+            bundle,
+        })
+    }
+
+    /// Open the bundle packages are read from (downloading the index if needed)
+    fn open_bundle(&self, status: &mut dyn StatusBackend) -> Result<Box<dyn Bundle>, String> {
+        let Some(source) = &self.bundle else {
+            return self
+                .config
+                .default_bundle(false, status)
+                .map_err(|e| format!("Failed to get bundle: {}", e));
+        };
+
+        let location = source.location.as_str();
+        let bundle = if location.starts_with("http://") || location.starts_with("https://") {
+            self.config
+                .make_cached_url_provider(location, false, None, status)
+        } else {
+            let path = location.strip_prefix("file://").unwrap_or(location);
+            self.config
+                .make_local_file_provider(PathBuf::from(path), status)
+        };
+        bundle.map_err(|e| format!("Failed to get bundle {}: {}", source.name, e))
+    }
+}
+
+/// How compile_file runs TeX
+#[derive(Clone, Copy)]
+struct TexRun<'a> {
+    formats: &'a [CompileFormat],
+    /// See CompileOptions::max_passes
+    max_passes: Option<u32>,
+    /// See CompileOptions::single_pass
+    single_pass: bool,
+    shell_escape: bool,
+    diagnostics: DiagnosticsMode,
+}
+
+/// The formats TeX writes for `formats`, and whether the xdv pipeline has it write XDV
+/// in place of the PDF, to be converted after the run
+fn pipeline_formats(meta: &ProjectMeta, formats: &[CompileFormat]) -> (Vec<CompileFormat>, bool) {
+    let convert_xdv =
+        meta.output_pipeline == OutputPipeline::Xdv && formats.contains(&CompileFormat::Pdf);
+    let mut session_formats = Vec::new();
+    for &format in formats {
+        let format = match format {
+            CompileFormat::Pdf if convert_xdv => CompileFormat::Xdv,
+            format => format,
+        };
+        if !session_formats.contains(&format) {
+            session_formats.push(format);
+        }
+    }
+    (session_formats, convert_xdv)
+}
+
+/// The files the root file \include-s, from its comment-masked source
+fn included_chapters(masked: &str) -> Vec<String> {
+    latex::commands(masked)
+        .filter(|c| c.name == "include")
+        .filter_map(|c| {
+            let args = latex::read_args(masked, c.end, 1);
+            latex::first_required(&args).map(|arg| arg.text.trim().to_string())
+        })
+        .collect()
+}
+
+/// The name TeX reads `file_path` under when its outputs are named `stem`, and whether
+/// that is a jobname. TeX names its outputs after the input, so a jobname stands in for
+/// the file name.
+fn tex_input_name(file_path: &str, stem: &str) -> (String, bool) {
+    if Path::new(file_path).file_stem().and_then(|s| s.to_str()) == Some(stem) {
+        (file_path.to_string(), false)
+    } else {
+        (format!("{}.tex", stem), true)
+    }
+}
+
+/// Compile `file_path` (relative to the project) as it is on disk, or `compiled_source`
+/// in its place when given, writing outputs to the project's build/ directory
+fn compile_file(
+    env: &TexEnvironment,
+    project_dir: &Path,
+    file_path: &str,
+    compiled_source: Option<&str>,
+    run: TexRun,
+    status: &mut dyn StatusBackend,
+) -> Result<CompileResult, CompileError> {
+    let TexRun {
+        formats,
+        max_passes,
+        single_pass,
+        shell_escape,
+        diagnostics,
+    } = run;
+    let mut status = DownloadWatch::new(status);
+    let status: &mut dyn StatusBackend = &mut status;
+    let full_file_path = project_dir.join(file_path);
+
+    let meta = read_project_meta(project_dir)?;
+    let (session_formats, convert_xdv) = pipeline_formats(&meta, formats);
+    if convert_xdv && !on_path(XDV_CONVERTER) {
+        return Err(format!(
+            "The xdv output pipeline needs {} from a TeX distribution such as TeX Live, \
+             which is not installed",
+            XDV_CONVERTER
+        )
+        .into());
+    }
+    let (output_format, mut keep_intermediates) = session_format(&session_formats)?;
+
+    // TeX would otherwise keep re-reading the files until it runs out of input levels
+    let cycle = check_includes(project_dir, file_path)?
+        .into_iter()
+        .find_map(|issue| match issue {
+            IncludeIssue::CircularInclude { cycle } => Some(cycle),
+            _ => None,
+        });
+    if let Some(cycle) = cycle {
+        return Err(format!("Circular include: {}", cycle.join(" -> ")).into());
+    }
+
+    let structure = check_document_structure(project_dir, file_path, compiled_source)?;
+    if let Some(first) = structure.first() {
+        return Err(CompileError {
+            diagnostics: structure.clone(),
+            ..first.message.clone().into()
+        });
+    }
+
+    // Ensure build directory exists
+    let build_dir = build_dir(project_dir)?;
+    std::fs::create_dir_all(&build_dir)
+        .map_err(|e| format!("Failed to create build directory: {}", e))?;
+    check_disk_space(&build_dir)?;
+
+    eprintln!("Compiling with Tectonic library API");
+    eprintln!("Project dir: {}", project_dir.display());
+    eprintln!("File path: {}", file_path);
+
+    let bundle = env
+        .open_bundle(status)
+        .map_err(CompileError::bundle_unavailable)?;
+
+    let stem = output_name(&meta, file_path)?;
+    let mut extra_search_paths = resolve_search_paths(project_dir, &meta.extra_input_paths)?;
+
+    // \includeonly skips the other \include files but still reads their .aux files, so
+    // references into them keep working. Keep those files in build/ on every build, and
+    // let TeX find them there when a build leaves chapters out.
+    let root_source = match compiled_source {
+        Some(source) => source.to_string(),
+        None => latex::read_source(project_dir, file_path)?,
+    };
+    let masked = latex::mask_comments(&root_source);
+    let chapters = included_chapters(&masked);
+    if !chapters.is_empty() {
+        keep_intermediates = true;
+        // TeX can't create the directory of a chapter's .aux file itself
+        for chapter in &chapters {
+            let Some(parent) = latex::normalize_relative(Path::new(chapter))
+                .and_then(|path| path.parent().map(Path::to_path_buf))
+            else {
+                continue;
+            };
+            std::fs::create_dir_all(build_dir.join(parent))
+                .map_err(|e| format!("Failed to create build directory: {}", e))?;
+        }
+    }
+    if latex::commands(&masked).any(|c| c.name == "includeonly") {
+        extra_search_paths.push(build_dir.clone());
+    }
+    // parse_toc reads the .toc a document with a table of contents writes
+    if latex::commands(&masked).any(|c| c.name == "tableofcontents") {
+        keep_intermediates = true;
+    }
+
+    // Build the processing session. Tectonic ignores extra search paths and shell escape
+    // under its default security stance, so relax it only when they're used.
+    let mut builder = if extra_search_paths.is_empty() && !shell_escape {
+        ProcessingSessionBuilder::default()
+    } else {
+        ProcessingSessionBuilder::new_with_security(SecuritySettings::new(
+            SecurityStance::MaybeAllowInsecures,
+        ))
+    };
+    let rules_path = build_dir.join(format!("{}.d", stem));
+    // Messages about the root file carry the jobname's input name; they're mapped back below
+    let (tex_input_name, is_jobname) = tex_input_name(file_path, &stem);
+    builder
+        .bundle(bundle)
+        .filesystem_root(project_dir) // Critical: allows \input{} to work
+        .tex_input_name(&tex_input_name)
+        .format_name("latex")
+        .format_cache_path(&env.format_cache)
+        .output_dir(&build_dir) // Output to build/ directory
+        .output_format(output_format)
+        .keep_logs(true) // Keep the .log in build/ for diagnostics
+        .keep_intermediates(keep_intermediates)
+        .makefile_output_path(&rules_path)
+        .print_stdout(false)
+        // Recoverable errors still produce output; they're reported as diagnostics
+        .unstables(UnstableOptions {
+            continue_on_errors: true,
+            extra_search_paths,
+            ..Default::default()
+        });
+    if shell_escape {
+        builder.shell_escape_with_work_dir(project_dir);
+    }
+    // A Tex pass would stop before xdvipdfmx and leave no PDF, so pin the default pass
+    // to a single TeX run instead
+    if single_pass {
+        builder.reruns(0);
+    }
+
+    match compiled_source {
+        // TeX reads the modified copy while the file on disk keeps the user's source.
+        // Tectonic roots a path input at its directory, so do the same here.
+        Some(modified) => {
+            builder
+                .primary_input_buffer(modified.as_bytes())
+                .filesystem_root(full_file_path.parent().unwrap_or(project_dir));
+        }
+        None => {
+            builder.primary_input_path(&full_file_path);
+        }
+    }
+
+    let mut result = run_session(
+        builder,
+        &build_dir,
+        &stem,
+        &session_formats,
+        max_passes,
+        diagnostics,
+        status,
+    );
+    if let Ok(result) = &mut result {
+        if convert_xdv {
+            let tex_root = full_file_path.parent().unwrap_or(project_dir);
+            convert_xdv_output(tex_root, &build_dir, &stem, formats, result)?;
+        }
+        // Names are relative to wherever TeX was rooted, which depends on the input kind
+        let roots = [full_file_path.parent().unwrap_or(project_dir), project_dir];
+        // The root file isn't listed when it's read from memory
+        let mut inputs = vec![file_path.to_string()];
+        for name in read_dependencies(&rules_path) {
+            if is_jobname && name == tex_input_name {
+                continue;
+            }
+            let path = roots
+                .iter()
+                .map(|root| root.join(&name))
+                .find(|path| path.is_file())
+                .unwrap_or_else(|| PathBuf::from(&name));
+            let relative = path
+                .strip_prefix(project_dir)
+                .ok()
+                .and_then(latex::normalize_relative);
+            inputs.push(match relative {
+                Some(relative) => latex::to_project_path(&relative),
+                None => path.display().to_string(),
+            });
+        }
+        inputs.sort();
+        inputs.dedup();
+        result.input_files = inputs;
+    }
+    if !is_jobname {
+        return result;
+    }
+
+    let restore_file = |diagnostics: &mut Vec<Diagnostic>| {
+        for diagnostic in diagnostics {
+            if diagnostic.file.as_deref() == Some(tex_input_name.as_str()) {
+                diagnostic.file = Some(file_path.to_string());
+            }
+        }
+    };
+    match result {
+        Ok(mut result) => {
+            restore_file(&mut result.diagnostics);
+            Ok(result)
+        }
+        Err(mut error) => {
+            restore_file(&mut error.diagnostics);
+            Err(error)
+        }
+    }
+}
+
+#[tauri::command]
+pub async fn compile_latex_project(
+    project_path: String,
+    file_path: String,
+    source: String,
+    options: Option<CompileOptions>,
+) -> Result<CompileResult, CompileError> {
+    let options = options.unwrap_or_default();
+    let settings = last_compile(&options);
+    let profile = options.profile.clone();
+    let (options, output_dir) = apply_profile(&PathBuf::from(&project_path), options)?;
+
+    let mut formats = Vec::new();
+    for format in &options.output_formats {
+        if !formats.contains(format) {
+            formats.push(*format);
+        }
+    }
+    if formats.is_empty() {
+        formats.push(CompileFormat::Pdf);
+    }
+    session_format(&formats)?;
+
+    let loaded = match options.watermark {
+        Some(_) => {
+            let root_file = latex::project_file(&file_path)?;
+            preamble_packages(&PathBuf::from(&project_path), &root_file, &source)
+        }
+        None => Vec::new(),
+    };
+    let preamble = options_preamble(&options, &loaded)?;
+
+    let mut compiled_source = None;
+    if options.draft {
+        compiled_source = Some(add_class_option(&source, "draft")?);
+    }
+    if !preamble.trim().is_empty() {
+        let base = compiled_source.as_deref().unwrap_or(&source);
+        compiled_source = Some(inject_preamble(base, &preamble)?);
+    }
+    let output_profile = options.output_profile;
+    let preview = options.preview || options.persist_source == Some(false);
+    let jobname = options.jobname;
+    let shell_escape = options.shell_escape;
+    let progress_token = options.progress_token;
+    let diagnostics_mode = options.diagnostics_mode;
+
+    let max_passes = options.max_passes;
+    if max_passes == Some(0) {
+        return Err("max_passes must be at least 1".into());
+    }
+    let single_pass = options.single_pass;
+    if single_pass && max_passes.is_some_and(|passes| passes > 1) {
+        return Err("single_pass runs TeX once; leave max_passes unset".into());
+    }
+
+    let global = read_global_settings()?;
+    if shell_escape && !global.allow_hooks {
+        return Err(
+            "Shell escape runs commands from the document; allow hooks in the settings to use it"
+                .into(),
+        );
+    }
+    let timeout = global
+        .compile_timeout_secs
+        .filter(|secs| *secs > 0)
+        .map(Duration::from_secs);
+
+    if let Some(token) = &progress_token {
+        register_progress(token);
+    }
+
+    let history_dir = PathBuf::from(&project_path);
+    let history_file = file_path.clone();
+    let started = Instant::now();
+
+    // Use Tectonic library API for in-process compilation with multi-file support
+    let result = run_with_timeout(timeout, move || -> Result<CompileResult, CompileError> {
+        let project_dir = PathBuf::from(&project_path);
+        let full_file_path = project_dir.join(&file_path);
+
+        // Write current editor content to disk (required for \input{} to work). Sibling
+        // files still resolve from disk when the source is compiled from memory.
+        if !preview {
+            std::fs::write(&full_file_path, &source)
+                .map_err(|e| format!("Failed to write file: {}", e))?;
+        }
+
+        let meta = read_project_meta(&project_dir)?;
+        let hooks_allowed = read_global_settings()?.allow_hooks;
+        let configured = |command: &Option<String>| {
+            command.clone().filter(|command| !command.trim().is_empty())
+        };
+
+        let mut hooks = Vec::new();
+        if let Some(command) = configured(&meta.pre_build) {
+            let hook = build_hook(&project_dir, HookStage::Pre, &command, hooks_allowed);
+            let failure = match hook.status {
+                HookStatus::Failed => Some(match hook.exit_code {
+                    Some(code) => format!("Pre-build hook failed with exit code {}", code),
+                    None => "Pre-build hook failed".to_string(),
+                }),
+                HookStatus::TimedOut => Some("Pre-build hook timed out".to_string()),
+                HookStatus::Succeeded | HookStatus::Disabled => None,
+            };
+            hooks.push(hook);
+            if let Some(message) = failure {
+                return Err(CompileError {
+                    hooks,
+                    ..message.into()
+                });
+            }
+        }
+
+        let compile = || -> Result<CompileResult, CompileError> {
+            // Set up status backend (no output, besides progress for the caller to poll)
+            let mut status = ProgressStatus {
+                token: progress_token,
+            };
+
+            let env = TexEnvironment::open()?;
+
+            // A Tectonic.toml project builds its configured output rather than the open file
+            if let Some(document) = open_workspace_document(&project_dir)? {
+                if profile.is_some() {
+                    return Err("Tectonic.toml projects use their own output profiles".into());
+                }
+                if meta.output_pipeline != OutputPipeline::DirectPdf {
+                    return Err("Tectonic.toml projects can only build PDFs directly".into());
+                }
+                if shell_escape {
+                    return Err("Tectonic.toml projects configure shell escape in Tectonic.toml"
+                        .into());
+                }
+                if compiled_source.is_some() {
+                    return Err(
+                        "Preamble, paper size, draft, watermark and include_only overrides are not supported for Tectonic.toml projects"
+                            .into(),
+                    );
+                }
+                if formats != [CompileFormat::Pdf] {
+                    return Err("Tectonic.toml projects can only be compiled to PDF".into());
+                }
+                if preview {
+                    return Err("Tectonic.toml projects build from their saved files; save before \
+                                compiling"
+                        .into());
+                }
+                if jobname.is_some() {
+                    return Err("Tectonic.toml projects name their outputs after the output \
+                                profile"
+                        .into());
+                }
+
+                let profile = pick_output_profile(&document, output_profile.as_deref())?;
+                eprintln!("Building Tectonic.toml output profile: {}", profile);
+
+                let mut status = DownloadWatch::new(&mut status);
+                let mut builder =
+                    workspace_session(&document, &profile, &env.format_cache, &mut status)?;
+                if single_pass {
+                    builder.reruns(0);
+                }
+                let build_dir = document.build_dir().join(&profile);
+                // The build directory doesn't exist before the first build
+                let build_root = build_dir.parent().filter(|dir| dir.is_dir());
+                check_disk_space(build_root.unwrap_or(project_dir.as_path()))?;
+                return run_session(
+                    builder,
+                    &build_dir,
+                    &profile,
+                    &formats,
+                    max_passes,
+                    diagnostics_mode,
+                    &mut status,
+                );
+            }
+
+            check_bib_engine(&project_dir, &file_path)?;
+            if let Some(jobname) = &jobname {
+                set_jobname(&project_dir, &file_path, jobname)?;
+            }
+
+            let compiled_source = compiled_source.or_else(|| preview.then_some(source));
+            let mut result = compile_file(
+                &env,
+                &project_dir,
+                &file_path,
+                compiled_source.as_deref(),
+                TexRun {
+                    formats: &formats,
+                    max_passes,
+                    single_pass,
+                    shell_escape,
+                    diagnostics: diagnostics_mode,
+                },
+                &mut status,
+            )?;
+
+            if let Some(output_dir) = output_dir.as_deref().filter(|_| result.succeeded) {
+                let stem = output_name(&read_project_meta(&project_dir)?, &file_path)?;
+                if let Err(e) = copy_outputs(&project_dir, output_dir, &stem, &result) {
+                    result.diagnostics.push(Diagnostic {
+                        severity: Severity::Warning,
+                        message: e,
+                        file: None,
+                        line: None,
+                    });
+                    result.warning_count += 1;
+                }
+            }
+
+            if result.succeeded {
+                let mut settings = settings;
+                settings.pdf_pages = result.outputs.get("pdf").and_then(|pdf| pdf::page_count(pdf));
+
+                // Not being able to compare must not fail a build that worked
+                let previous = latex::project_file(&file_path).ok().and_then(|file| {
+                    read_project_meta(&project_dir).ok()?.last_compiles.remove(&file)
+                });
+                if let Some(warning) = shrink_warning(previous.as_ref(), &settings) {
+                    result.diagnostics.push(warning);
+                    result.warning_count += 1;
+                }
+                if let Some(exceeded) = page_limit_exceeded(&meta, &file_path, settings.pdf_pages) {
+                    result.diagnostics.push(Diagnostic {
+                        severity: Severity::Warning,
+                        message: format!(
+                            "The PDF has {} pages, over the limit of {} by {}",
+                            exceeded.pages,
+                            exceeded.max_pages,
+                            exceeded.pages - exceeded.max_pages
+                        ),
+                        file: None,
+                        line: None,
+                    });
+                    result.warning_count += 1;
+                    result.page_limit_exceeded = Some(exceeded);
+                }
+
+                if let Err(e) = record_last_compile(&project_dir, &file_path, settings) {
+                    eprintln!("Failed to record compile settings: {}", e);
+                }
+            }
+            Ok(result)
+        };
+
+        let mut result = match compile() {
+            Ok(result) => result,
+            Err(mut error) => {
+                error.hooks = hooks;
+                return Err(error);
+            }
+        };
+        if single_pass {
+            result.single_pass = true;
+            result.diagnostics.push(Diagnostic {
+                severity: Severity::Warning,
+                message: "Compiled in a single pass: references, citations and the table of \
+                          contents may be wrong or missing"
+                    .to_string(),
+                file: None,
+                line: None,
+            });
+            result.warning_count += 1;
+        }
+
+        if let Some(command) = configured(&meta.post_build) {
+            hooks.push(build_hook(&project_dir, HookStage::Post, &command, hooks_allowed));
+        }
+        result.hooks = hooks;
+        Ok(result)
+    })
+    .await;
+
+    let record = compile_record(
+        &history_dir,
+        history_file,
+        started.elapsed(),
+        single_pass,
+        &result,
+    );
+    if let Err(e) = record_compile(&history_dir, record) {
+        eprintln!("Failed to record compile history: {}", e);
+    }
+    result
+}
+
+/// How compile_latex_project would build a project, with its settings, compile profile
+/// and the global settings combined
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EffectiveConfig {
+    pub root_file: String,
+    pub engine: String,
+    pub profile: Option<String>,
+    /// Built from Tectonic.toml, whose output profiles decide everything but the bundle
+    pub tectonic_workspace: bool,
+    /// Base name of the outputs, e.g. `thesis` for thesis.pdf
+    pub output_name: String,
+    pub build_dir: String,
+    /// Where the profile copies the outputs after a successful build
+    pub output_dir: Option<String>,
+    pub output_pipeline: OutputPipeline,
+    pub draft: bool,
+    pub landscape: bool,
+    pub paper_size: Option<String>,
+    pub watermark: Option<String>,
+    /// Only possible with `hooks_allowed`; the compile fails otherwise
+    pub shell_escape: bool,
+    /// Everything added before \begin{document}, in the order it goes in
+    pub preamble: Option<String>,
+    /// Directories searched after the project, resolved
+    pub extra_search_paths: Vec<String>,
+    /// The active bundle, or None for Tectonic's default
+    pub bundle: Option<BundleSource>,
+    pub timeout_secs: Option<u64>,
+    pub pre_build: Option<String>,
+    pub post_build: Option<String>,
+    pub hooks_allowed: bool,
+}
+
+/// The configuration a compile of the project's root file with compile profile
+/// `profile` gets, merged the way compile_latex_project merges it, for finding out
+/// where a setting comes from without compiling
+#[tauri::command]
+pub async fn effective_config(
+    project_path: String,
+    profile: Option<String>,
+) -> Result<EffectiveConfig, String> {
+    let project_dir = PathBuf::from(&project_path);
+    let meta = read_project_meta(&project_dir)?;
+    let global = read_global_settings()?;
+
+    let options = CompileOptions {
+        profile: profile.clone(),
+        ..CompileOptions::default()
+    };
+    let (options, output_dir) = apply_profile(&project_dir, options)?;
+    // A root file that can't be read yet only loses the check for a draftwatermark it loads
+    let loaded = match options.watermark {
+        Some(_) => latex::read_source(&project_dir, &meta.root_file)
+            .map(|source| preamble_packages(&project_dir, &meta.root_file, &source))
+            .unwrap_or_default(),
+        None => Vec::new(),
+    };
+    let preamble = options_preamble(&options, &loaded)?;
+    let search_paths = resolve_search_paths(&project_dir, &meta.extra_input_paths)?;
+    let configured =
+        |command: &Option<String>| command.clone().filter(|command| !command.trim().is_empty());
+
+    Ok(EffectiveConfig {
+        root_file: meta.root_file.clone(),
+        engine: ENGINE.to_string(),
+        profile,
+        tectonic_workspace: project_dir.join("Tectonic.toml").is_file(),
+        output_name: output_name(&meta, &meta.root_file)?,
+        build_dir: build_dir(&project_dir)?.to_string_lossy().into_owned(),
+        output_dir,
+        output_pipeline: meta.output_pipeline,
+        draft: options.draft,
+        landscape: options.landscape,
+        paper_size: options.paper_size,
+        watermark: options.watermark,
+        shell_escape: options.shell_escape,
+        preamble: Some(preamble).filter(|preamble| !preamble.trim().is_empty()),
+        extra_search_paths: search_paths
+            .iter()
+            .map(|path| path.to_string_lossy().into_owned())
+            .collect(),
+        bundle: global.active_bundle_source()?.cloned(),
+        timeout_secs: global.compile_timeout_secs.filter(|secs| *secs > 0),
+        pre_build: configured(&meta.pre_build),
+        post_build: configured(&meta.post_build),
+        hooks_allowed: global.allow_hooks,
+    })
+}
+
+/// What a compile of a file would do, as explain_compile finds it without compiling
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CompilePlan {
+    pub engine: String,
+    /// Set for a Tectonic.toml project, which builds this output profile instead
+    pub output_profile: Option<String>,
+    /// The file TeX starts from; a Tectonic.toml build starts from its own generated
+    /// input instead
+    pub primary_input: Option<String>,
+    /// The name TeX reads the input under, which its outputs are named after
+    pub tex_input_name: String,
+    /// Where TeX resolves \input and \includegraphics paths from
+    pub filesystem_root: String,
+    pub output_dir: String,
+    /// The formats TeX writes, XDV in place of the PDF under the xdv pipeline
+    pub session_formats: Vec<CompileFormat>,
+    pub output_pipeline: OutputPipeline,
+    /// Directories searched after the project, with build/ under \includeonly
+    pub extra_search_paths: Vec<String>,
+    /// Whether .aux and other intermediates stay in the output directory
+    pub keep_intermediates: bool,
+    /// The Tectonic.toml bundle, the active one, or None for Tectonic's default
+    pub bundle: Option<BundleSource>,
+    /// The PDF built last time, if there is one
+    pub built_pdf: Option<String>,
+    /// Whether built_pdf is newer than every file the build reads, so loading, exporting
+    /// or merging it uses it as it is. Always false for Tectonic.toml builds, whose
+    /// inputs aren't tracked.
+    pub pdf_up_to_date: bool,
+}
+
+/// How compile_latex_project would build `file_path` from its saved source with no
+/// options: the input, roots and directories Tectonic would get, resolved the way
+/// compile_file resolves them, without running it
+#[tauri::command]
+pub async fn explain_compile(
+    project_path: String,
+    file_path: String,
+) -> Result<CompilePlan, String> {
+    let file_path = latex::project_file(&file_path)?;
+
+    tauri::async_runtime::spawn_blocking(move || {
+        let project_dir = PathBuf::from(&project_path);
+        let meta = read_project_meta(&project_dir)?;
+        let display = |path: &Path| path.to_string_lossy().into_owned();
+
+        if let Some(document) = open_workspace_document(&project_dir)? {
+            let profile = pick_output_profile(&document, None)?;
+            return Ok(CompilePlan {
+                engine: ENGINE.to_string(),
+                primary_input: None,
+                tex_input_name: profile.clone(),
+                filesystem_root: display(&document.src_dir().join("src")),
+                output_dir: display(&document.build_dir().join(&profile)),
+                session_formats: vec![CompileFormat::Pdf],
+                output_pipeline: OutputPipeline::DirectPdf,
+                extra_search_paths: document.extra_paths.iter().map(|p| display(p)).collect(),
+                keep_intermediates: false,
+                bundle: Some(BundleSource {
+                    name: document.name.clone(),
+                    location: document.bundle_loc.clone(),
+                }),
+                built_pdf: Some(document.output_main_file(&profile))
+                    .filter(|pdf_path| pdf_path.is_file())
+                    .as_deref()
+                    .map(display),
+                output_profile: Some(profile),
+                pdf_up_to_date: false,
+            });
+        }
+
+        let (session_formats, _) = pipeline_formats(&meta, &[CompileFormat::Pdf]);
+        let (_, mut keep_intermediates) = session_format(&session_formats)?;
+
+        let build_dir = build_dir(&project_dir)?;
+        let mut extra_search_paths = resolve_search_paths(&project_dir, &meta.extra_input_paths)?;
+        let masked = latex::mask_comments(&latex::read_source(&project_dir, &file_path)?);
+        if !included_chapters(&masked).is_empty()
+            || latex::commands(&masked).any(|c| c.name == "tableofcontents")
+        {
+            keep_intermediates = true;
+        }
+        if latex::commands(&masked).any(|c| c.name == "includeonly") {
+            extra_search_paths.push(build_dir.clone());
+        }
+
+        let built_pdf = find_built_pdf(&project_path, &file_path).ok();
+        let pdf_up_to_date = match &built_pdf {
+            Some(pdf_path) => !pdf_is_stale(&project_dir, &file_path, pdf_path)?,
+            None => false,
+        };
+
+        let full_file_path = project_dir.join(&file_path);
+        let stem = output_name(&meta, &file_path)?;
+        Ok(CompilePlan {
+            engine: ENGINE.to_string(),
+            output_profile: None,
+            primary_input: Some(display(&full_file_path)),
+            tex_input_name: tex_input_name(&file_path, &stem).0,
+            filesystem_root: display(full_file_path.parent().unwrap_or(&project_dir)),
+            output_dir: display(&build_dir),
+            session_formats,
+            output_pipeline: meta.output_pipeline,
+            extra_search_paths: extra_search_paths.iter().map(|p| display(p)).collect(),
+            keep_intermediates,
+            bundle: read_global_settings()?.active_bundle_source()?.cloned(),
+            built_pdf: built_pdf.as_deref().map(display),
+            pdf_up_to_date,
+        })
+    })
+    .await
+    .map_err(|e| format!("Task join error: {}", e))?
+}
+
+/// Total size of `files`, project-relative or absolute. Files that can't be read count
+/// as empty.
+fn total_size(project_dir: &Path, files: &[String]) -> u64 {
+    files
+        .iter()
+        .filter_map(|file| std::fs::metadata(project_dir.join(file)).ok())
+        .map(|metadata| metadata.len())
+        .sum()
+}
+
+/// Summarize a finished compile for the project's history
+fn compile_record(
+    project_dir: &Path,
+    file_path: String,
+    duration: Duration,
+    single_pass: bool,
+    result: &Result<CompileResult, CompileError>,
+) -> CompileRecord {
+    let first_error = |diagnostics: &[Diagnostic]| {
+        diagnostics
+            .iter()
+            .find(|d| d.severity == Severity::Error)
+            .map(|d| d.message.clone())
+    };
+
+    // Measured now, just after TeX read them
+    let input_bytes = result
+        .as_ref()
+        .ok()
+        .filter(|result| !result.input_files.is_empty())
+        .map(|result| total_size(project_dir, &result.input_files));
+
+    let (succeeded, error_count, warning_count, error_summary) = match result {
+        Ok(result) => (
+            result.succeeded,
+            result.error_count,
+            result.warning_count,
+            first_error(&result.diagnostics),
+        ),
+        Err(error) => (
+            false,
+            error
+                .diagnostics
+                .iter()
+                .filter(|d| d.severity == Severity::Error)
+                .count(),
+            error
+                .diagnostics
+                .iter()
+                .filter(|d| d.severity == Severity::Warning)
+                .count(),
+            first_error(&error.diagnostics).or_else(|| Some(error.message.clone())),
+        ),
+    };
+
+    CompileRecord {
+        file_path,
+        compiled_at: chrono::Utc::now().to_rfc3339(),
+        succeeded,
+        duration_ms: duration.as_millis() as u64,
+        single_pass,
+        input_bytes,
+        error_count,
+        warning_count,
+        error_summary,
+    }
+}
+
+/// Compiles running now, counted against the `compile_thread_limit` setting
+static RUNNING_COMPILES: Mutex<usize> = Mutex::new(0);
+
+/// Signalled whenever a compile finishes and frees its slot
+static COMPILE_SLOT_FREED: Condvar = Condvar::new();
+
+/// Whether a compile counts against the limit, and whether it was given up on. Both
+/// only change with RUNNING_COMPILES locked.
+#[derive(Default)]
+struct SlotTicket {
+    held: AtomicBool,
+    abandoned: AtomicBool,
+}
+
+/// A place among the compiles allowed to run at once, given back when dropped
+struct CompileSlot {
+    ticket: Arc<SlotTicket>,
+}
+
+impl CompileSlot {
+    /// Wait, blocking the thread, until fewer than `compile_thread_limit` compiles are
+    /// running. Each compile is one thread: Tectonic runs TeX and xdvipdfmx one after
+    /// the other and has no threading of its own to configure.
+    fn acquire() -> CompileSlot {
+        CompileSlot::acquire_ticket(Arc::default())
+    }
+
+    /// Wait for a slot as acquire does, giving up once `ticket` is abandoned. A slot
+    /// acquired for an abandoned ticket doesn't count against the limit.
+    fn acquire_ticket(ticket: Arc<SlotTicket>) -> CompileSlot {
+        let limit = read_global_settings()
+            .ok()
+            .and_then(|settings| settings.compile_thread_limit)
+            .filter(|limit| *limit > 0);
+
+        let mut running = RUNNING_COMPILES.lock().unwrap();
+        if let Some(limit) = limit {
+            while *running >= limit && !ticket.abandoned.load(Ordering::Relaxed) {
+                running = COMPILE_SLOT_FREED.wait(running).unwrap();
+            }
+        }
+        if !ticket.abandoned.load(Ordering::Relaxed) {
+            *running += 1;
+            ticket.held.store(true, Ordering::Relaxed);
+        }
+        CompileSlot { ticket }
+    }
+
+    /// Give up on the compile `ticket` is for: a slot it holds is given back while it
+    /// keeps running, and one it is waiting for is never taken
+    fn abandon(ticket: &SlotTicket) {
+        let mut running = RUNNING_COMPILES.lock().unwrap();
+        ticket.abandoned.store(true, Ordering::Relaxed);
+        if ticket.held.swap(false, Ordering::Relaxed) {
+            *running -= 1;
+        }
+        // Wakes the compile itself too, if it is still waiting
+        COMPILE_SLOT_FREED.notify_all();
+    }
+}
+
+impl Drop for CompileSlot {
+    fn drop(&mut self) {
+        let mut running = RUNNING_COMPILES.lock().unwrap();
+        if self.ticket.held.swap(false, Ordering::Relaxed) {
+            *running -= 1;
+        }
+        // Waiters that were abandoned leave without taking the slot, so wake them all
+        COMPILE_SLOT_FREED.notify_all();
+    }
+}
+
+/// Run `task` off the async runtime, giving up on it after `limit`. Tectonic can't be
+/// interrupted, not even between passes, so a compile that times out is abandoned
+/// rather than stopped: it keeps its thread until TeX finishes or fails on its own,
+/// and its outputs, written only at the end, may still appear in build/. Waiting for a
+/// compile slot counts towards `limit`. An abandoned compile gives its slot back, so
+/// one that never finishes doesn't hold up every later compile, and one abandoned while
+/// still waiting for a slot doesn't start.
+async fn run_with_timeout<T: Send + 'static>(
+    limit: Option<Duration>,
+    task: impl FnOnce() -> Result<T, CompileError> + Send + 'static,
+) -> Result<T, CompileError> {
+    let ticket = Arc::new(SlotTicket::default());
+    let task = {
+        let ticket = ticket.clone();
+        move || {
+            let _slot = CompileSlot::acquire_ticket(ticket.clone());
+            if ticket.abandoned.load(Ordering::Relaxed) {
+                return Err("Compilation timed out waiting for a compile slot".into());
+            }
+            task()
+        }
+    };
+    let Some(limit) = limit else {
+        return tauri::async_runtime::spawn_blocking(task)
+            .await
+            .map_err(|e| format!("Task join error: {}", e))?;
+    };
+
+    let (sender, receiver) = mpsc::channel();
+    std::thread::spawn(move || {
+        // The receiver is gone if the compile timed out; nobody wants the result
+        let _ = sender.send(task());
+    });
+
+    let received = tauri::async_runtime::spawn_blocking(move || receiver.recv_timeout(limit))
+        .await
+        .map_err(|e| format!("Task join error: {}", e))?;
+    match received {
+        Ok(result) => result,
+        Err(RecvTimeoutError::Timeout) => {
+            CompileSlot::abandon(&ticket);
+            eprintln!(
+                "Compile timed out after {:?}; leaving it to finish in the background",
+                limit
+            );
+            Err(CompileError {
+                kind: CompileErrorKind::Timeout,
+                ..format!("Compilation timed out after {} seconds", limit.as_secs()).into()
+            })
+        }
+        Err(RecvTimeoutError::Disconnected) => Err("Compilation stopped unexpectedly".into()),
+    }
+}
+
+/// Typeset `source` only as far as TeX itself goes, without building a PDF or writing
+/// the file to disk, and return what it reported. Build hooks don't run.
+#[tauri::command]
+pub async fn check_only(
+    project_path: String,
+    file_path: String,
+    source: String,
+) -> Result<Vec<Diagnostic>, CompileError> {
+    tauri::async_runtime::spawn_blocking(move || {
+        let project_dir = PathBuf::from(&project_path);
+        if project_dir.join("Tectonic.toml").is_file() {
+            return Err("Tectonic.toml projects can only be checked by compiling them".into());
+        }
+
+        check_bib_engine(&project_dir, &file_path)?;
+
+        let _slot = CompileSlot::acquire();
+        let mut status = NoopStatusBackend::default();
+        let env = TexEnvironment::open()?;
+
+        // The .aux output stops the session before xdvipdfmx, which is most of the time
+        // a small document takes. Reruns still happen, so references settle as usual.
+        let result = compile_file(
+            &env,
+            &project_dir,
+            &file_path,
+            Some(&source),
+            TexRun {
+                formats: &[CompileFormat::Aux],
+                max_passes: None,
+                single_pass: false,
+                shell_escape: false,
+                diagnostics: DiagnosticsMode::All,
+            },
+            &mut status,
+        )?;
+        Ok(result.diagnostics)
+    })
+    .await
+    .map_err(|e| format!("Task join error: {}", e))?
+}
+
+/// Most compiles minimize_document runs, after which it returns what it has so far
+const MAX_MINIMIZE_COMPILES: usize = 150;
+
+/// How a compile failed: its first error, or the failure itself when TeX stopped
+/// without reporting one. None when it succeeded without errors.
+fn first_error(result: &Result<CompileResult, CompileError>) -> Option<String> {
+    let (diagnostics, failure) = match result {
+        Ok(result) => (&result.diagnostics, None),
+        Err(error) => (&error.diagnostics, Some(&error.message)),
+    };
+    diagnostics
+        .iter()
+        .find(|d| d.severity == Severity::Error)
+        .map(|d| d.message.clone())
+        .or_else(|| failure.cloned())
+}
+
+/// Drop as many of the `removable` lines from `kept` as possible while `still_fails`,
+/// by delta debugging: try removing each of ever smaller chunks, and start over with
+/// the rest whenever a removal keeps the failure
+fn reduce_lines(
+    kept: &mut [bool],
+    removable: &[usize],
+    still_fails: &mut dyn FnMut(&[bool]) -> bool,
+) {
+    let mut units = removable.to_vec();
+    let mut chunks = 2;
+    while !units.is_empty() {
+        let size = units.len().div_ceil(chunks);
+        let mut reduced = false;
+        for start in (0..units.len()).step_by(size) {
+            let end = (start + size).min(units.len());
+            for &line in &units[start..end] {
+                kept[line] = false;
+            }
+            if still_fails(kept) {
+                units.drain(start..end);
+                chunks = (chunks - 1).max(2);
+                reduced = true;
+                break;
+            }
+            for &line in &units[start..end] {
+                kept[line] = true;
+            }
+        }
+        if !reduced {
+            if size == 1 {
+                break;
+            }
+            chunks = (chunks * 2).min(units.len());
+        }
+    }
+}
+
+/// A smaller version of `root_file` that still fails with the same first error, for
+/// reporting a bug to a package's author. Lines of the body are removed first, then
+/// those of the preamble around \documentclass, compiling after each removal, so this
+/// takes a while. Included files stay as they are, unless their \input goes.
 #[tauri::command]
-pub async fn compile_latex_project(
+pub async fn minimize_document(
     project_path: String,
-    file_path: String,
-    source: String,
-) -> Result<Vec<u8>, String> {
-    // Use Tectonic library API for in-process compilation with multi-file support
+    root_file: String,
+) -> Result<String, CompileError> {
+    let root_file = latex::project_file(&root_file)?;
+
     tauri::async_runtime::spawn_blocking(move || {
         let project_dir = PathBuf::from(&project_path);
-        let full_file_path = project_dir.join(&file_path);
+        if project_dir.join("Tectonic.toml").is_file() {
+            return Err("Tectonic.toml projects can't be minimized".into());
+        }
+
+        let source = latex::read_source(&project_dir, &root_file)?;
+        let masked = latex::mask_comments(&source);
+        let masked_lines: Vec<&str> = masked.split_inclusive('\n').collect();
+        let lines: Vec<&str> = source.split_inclusive('\n').collect();
+        let Some(begin) = masked_lines
+            .iter()
+            .position(|line| line.contains("\\begin{document}"))
+        else {
+            return Err(format!("{} has no \\begin{{document}}", root_file).into());
+        };
+        let end = masked_lines[begin..]
+            .iter()
+            .position(|line| line.contains("\\end{document}"))
+            .map_or(lines.len(), |i| begin + i);
+
+        let env = TexEnvironment::open()?;
+        let mut compiles = 0;
+        let mut compile = |candidate: &str| {
+            if compiles == MAX_MINIMIZE_COMPILES {
+                return None;
+            }
+            compiles += 1;
+            let _slot = CompileSlot::acquire();
+            let mut status = NoopStatusBackend::default();
+            // One pass to the .aux, as most errors show on the first and it's the fastest
+            let result = compile_file(
+                &env,
+                &project_dir,
+                &root_file,
+                Some(candidate),
+                TexRun {
+                    formats: &[CompileFormat::Aux],
+                    max_passes: Some(1),
+                    single_pass: false,
+                    shell_escape: false,
+                    diagnostics: DiagnosticsMode::All,
+                },
+                &mut status,
+            );
+            first_error(&result)
+        };
+
+        let Some(error) = compile(&source) else {
+            return Err(
+                "The document compiles without errors, so there is nothing to reproduce".into(),
+            );
+        };
+        let render = |kept: &[bool]| -> String {
+            lines
+                .iter()
+                .zip(kept)
+                .filter(|(_, kept)| **kept)
+                .map(|(line, _)| *line)
+                .collect()
+        };
+        let mut still_fails = |kept: &[bool]| compile(&render(kept)).as_ref() == Some(&error);
+
+        let mut kept = vec![true; lines.len()];
+        let body: Vec<usize> = (begin + 1..end).chain(end + 1..lines.len()).collect();
+        reduce_lines(&mut kept, &body, &mut still_fails);
+        let preamble: Vec<usize> = (0..begin)
+            .filter(|&i| !masked_lines[i].contains("\\documentclass"))
+            .collect();
+        reduce_lines(&mut kept, &preamble, &mut still_fails);
+
+        Ok(render(&kept))
+    })
+    .await
+    .map_err(|e| format!("Task join error: {}", e))?
+}
+
+/// Numbers the scratch directories of template compiles running at the same time
+static TEMPLATE_BUILDS: AtomicU64 = AtomicU64::new(0);
 
-        // Write current editor content to disk (required for \input{} to work)
-        std::fs::write(&full_file_path, &source)
-            .map_err(|e| format!("Failed to write file: {}", e))?;
+/// Fill in the `{{name}}` placeholders of `template_source` from `variables` and
+/// compile it to a PDF, without a project. Values are escaped, so they can't inject
+/// LaTeX of their own; files the template inputs must come from the bundle.
+#[tauri::command]
+pub async fn compile_template(
+    template_source: String,
+    variables: BTreeMap<String, String>,
+) -> Result<Vec<u8>, CompileError> {
+    let source = fill_template(&template_source, &variables);
+    let timeout = read_global_settings()?
+        .compile_timeout_secs
+        .filter(|secs| *secs > 0)
+        .map(Duration::from_secs);
+
+    run_with_timeout(timeout, move || -> Result<Vec<u8>, CompileError> {
+        let scratch_dir = std::env::temp_dir().join(format!(
+            "incipit-template-{}-{}",
+            std::process::id(),
+            TEMPLATE_BUILDS.fetch_add(1, Ordering::Relaxed)
+        ));
+        std::fs::create_dir_all(&scratch_dir)
+            .map_err(|e| format!("Failed to create build directory: {}", e))?;
+        std::fs::write(scratch_dir.join("template.tex"), &source)
+            .map_err(|e| format!("Failed to write template: {}", e))?;
+        // Resolved now, while the directory it's keyed by still exists
+        let scratch_build = build_dir(&scratch_dir);
+
+        let compile = || -> Result<CompileResult, CompileError> {
+            let mut status = NoopStatusBackend::default();
+            let env = TexEnvironment::open()?;
+            compile_file(
+                &env,
+                &scratch_dir,
+                "template.tex",
+                None,
+                TexRun {
+                    formats: &[CompileFormat::Pdf],
+                    max_passes: None,
+                    single_pass: false,
+                    shell_escape: false,
+                    diagnostics: DiagnosticsMode::All,
+                },
+                &mut status,
+            )
+        };
+        let result = compile();
+
+        if let Ok(scratch_build) = scratch_build {
+            let _ = std::fs::remove_dir_all(scratch_build);
+        }
+        let _ = std::fs::remove_dir_all(&scratch_dir);
+
+        let mut result = result?;
+        match result.outputs.remove("pdf") {
+            Some(pdf) => Ok(pdf),
+            None => Err(CompileError {
+                diagnostics: result.diagnostics,
+                .."Template compiled without producing a PDF".into()
+            }),
+        }
+    })
+    .await
+}
+
+/// Numbers the scratch copies of clean builds running at the same time
+static CLEAN_BUILDS: AtomicU64 = AtomicU64::new(0);
+
+/// Copy the files under `from` to `to` as a fresh checkout would have them: without
+/// git's own directory or anything GITIGNORE_PATTERNS calls generated, such as build/
+/// or a .aux or .bbl another tool left next to the sources. `relative` is `from`'s path
+/// in the project, empty at its root.
+fn copy_clean_tree(
+    from: &Path,
+    to: &Path,
+    relative: &str,
+    generated: &PathPatterns,
+    depth: usize,
+) -> Result<(), String> {
+    if depth >= MAX_TREE_DEPTH {
+        return Ok(());
+    }
+    std::fs::create_dir_all(to).map_err(|e| format!("Failed to create {}: {}", to.display(), e))?;
+    let entries =
+        std::fs::read_dir(from).map_err(|e| format!("Failed to read {}: {}", from.display(), e))?;
+
+    for entry in entries.filter_map(|entry| entry.ok()) {
+        let name = entry.file_name().to_string_lossy().into_owned();
+        let path = entry.path();
+        let is_dir = path.is_dir();
+        let entry_relative = if relative.is_empty() {
+            name.clone()
+        } else {
+            format!("{}/{}", relative, name)
+        };
+        if name == ".git" || generated.matches(&entry_relative, is_dir) {
+            continue;
+        }
+
+        if is_dir {
+            copy_clean_tree(
+                &path,
+                &to.join(&name),
+                &entry_relative,
+                generated,
+                depth + 1,
+            )?;
+        } else if path.is_file() {
+            std::fs::copy(&path, to.join(&name))
+                .map_err(|e| format!("Failed to copy {}: {}", entry_relative, e))?;
+        }
+    }
+    Ok(())
+}
+
+/// Compile `root_file` to a PDF from a copy of the project in a temporary directory,
+/// the way a fresh checkout would build: without the .aux, .bbl and other files an
+/// earlier build or another tool left behind, and with no state kept from a previous
+/// compile. A document that only builds thanks to such leftovers fails here or comes
+/// out different. Shell escape is off, as it is by default. The project and its build
+/// directory aren't touched, and the copy is removed afterwards.
+#[tauri::command]
+pub async fn verify_clean_build(
+    project_path: String,
+    root_file: String,
+) -> Result<CompileResult, CompileError> {
+    let root_file = latex::project_file(&root_file)?;
+    let timeout = read_global_settings()?
+        .compile_timeout_secs
+        .filter(|secs| *secs > 0)
+        .map(Duration::from_secs);
+
+    run_with_timeout(timeout, move || -> Result<CompileResult, CompileError> {
+        let project_dir = PathBuf::from(&project_path);
+        if project_dir.join("Tectonic.toml").is_file() {
+            return Err("Tectonic.toml projects can't be verified with a clean build".into());
+        }
 
-        // Ensure build directory exists
-        let build_dir = project_dir.join("build");
-        std::fs::create_dir_all(&build_dir)
+        let scratch_dir = std::env::temp_dir().join(format!(
+            "incipit-clean-{}-{}",
+            std::process::id(),
+            CLEAN_BUILDS.fetch_add(1, Ordering::Relaxed)
+        ));
+        std::fs::create_dir_all(&scratch_dir)
             .map_err(|e| format!("Failed to create build directory: {}", e))?;
+        // Resolved now, while the directory it's keyed by still exists
+        let scratch_build = build_dir(&scratch_dir);
+
+        let compile = || -> Result<CompileResult, CompileError> {
+            let patterns: Vec<String> = GITIGNORE_PATTERNS.iter().map(|p| p.to_string()).collect();
+            let generated = PathPatterns::new(&patterns, "generated files")?;
+            copy_clean_tree(&project_dir, &scratch_dir, "", &generated, 0)?;
+
+            // Extra input paths outside the project aren't in the copy; use the originals
+            let mut meta = read_project_meta(&scratch_dir)?;
+            let outside = |path: &String| latex::normalize_relative(Path::new(path)).is_none();
+            if meta.extra_input_paths.iter().any(outside) {
+                for path in meta
+                    .extra_input_paths
+                    .iter_mut()
+                    .filter(|path| outside(path))
+                {
+                    *path = project_dir.join(&*path).display().to_string();
+                }
+                write_project_meta(&scratch_dir, &meta)?;
+            }
+
+            // run_with_timeout holds the compile slot
+            let env = TexEnvironment::open()?;
+            let mut status = NoopStatusBackend::default();
+            compile_file(
+                &env,
+                &scratch_dir,
+                &root_file,
+                None,
+                TexRun {
+                    formats: &[CompileFormat::Pdf],
+                    max_passes: None,
+                    single_pass: false,
+                    shell_escape: false,
+                    diagnostics: DiagnosticsMode::All,
+                },
+                &mut status,
+            )
+        };
+        let result = compile();
+
+        if let Ok(scratch_build) = scratch_build {
+            let _ = std::fs::remove_dir_all(scratch_build);
+        }
+        let _ = std::fs::remove_dir_all(&scratch_dir);
+        result
+    })
+    .await
+}
 
-        eprintln!("Compiling with Tectonic library API");
-        eprintln!("Project dir: {}", project_dir.display());
-        eprintln!("File path: {}", file_path);
+/// Status lines a compile started with `token` as its progress_token has reported so
+/// far, at most the last `lines` of them. Tectonic doesn't pass on TeX's own output
+/// until a pass fails, so for a running pass this is the step it is on.
+#[tauri::command]
+pub async fn compile_progress_log(
+    token: String,
+    lines: Option<usize>,
+) -> Result<CompileProgress, String> {
+    let mut progress = COMPILE_PROGRESS.lock().unwrap();
+    let mut current = progress
+        .get(&token)
+        .cloned()
+        .ok_or_else(|| format!("No compile with progress token: {}", token))?;
+    if current.finished {
+        progress.remove(&token);
+    }
+
+    let keep = lines.unwrap_or(current.lines.len());
+    let skip = current.lines.len().saturating_sub(keep);
+    current.lines.drain(..skip);
+    Ok(current)
+}
+
+/// The project's recent compiles, newest first
+#[tauri::command]
+pub async fn compile_history(project_path: String) -> Result<Vec<CompileRecord>, String> {
+    let meta = read_project_meta(&PathBuf::from(&project_path))?;
+    Ok(meta.compile_history.into_iter().rev().collect())
+}
+
+/// Recent builds of a document that estimate_build bases its estimate on
+const ESTIMATE_SAMPLES: usize = 5;
+
+/// Where an estimated build time comes from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EstimateBasis {
+    /// Recent builds of the same document, scaled by how much its input has grown since
+    Document,
+    /// Builds of the project's other documents, at their time per byte of input
+    Project,
+}
+
+/// What building a document will likely take, to decide whether to build it now
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BuildEstimate {
+    /// Sources TeX reads from the project, the root included
+    pub source_files: usize,
+    pub source_bytes: u64,
+    /// Graphics the sources include that are in the project
+    pub graphics: usize,
+    pub graphics_bytes: u64,
+    /// The largest of those graphics, which the PDF driver holds in memory whole while
+    /// embedding it
+    pub largest_graphic: Option<String>,
+    pub largest_graphic_bytes: u64,
+    /// Bibliographies, styles, classes, packages and listings kept in the project
+    pub other_files: usize,
+    pub other_bytes: u64,
+    /// Rough duration of a full build; None when the history has none to go by
+    pub expected_duration_ms: Option<u64>,
+    pub basis: Option<EstimateBasis>,
+    /// How many recorded builds the expected duration comes from
+    pub samples: usize,
+    /// Size of the PDF the last build wrote, a guide to that of the next one
+    pub last_pdf_bytes: Option<u64>,
+    pub last_pdf_pages: Option<u32>,
+}
+
+fn median(mut values: Vec<f64>) -> Option<f64> {
+    if values.is_empty() {
+        return None;
+    }
+    values.sort_by(f64::total_cmp);
+    let middle = values.len() / 2;
+    Some(if values.len().is_multiple_of(2) {
+        (values[middle - 1] + values[middle]) / 2.0
+    } else {
+        values[middle]
+    })
+}
+
+/// Expected duration of a full build of `file` reading `input_bytes`, how it was worked
+/// out and from how many builds. Build time is taken to grow in proportion to input size,
+/// which overestimates for small documents, where loading the format dominates.
+fn expected_duration(
+    history: &[CompileRecord],
+    file: &str,
+    input_bytes: u64,
+) -> Option<(u64, EstimateBasis, usize)> {
+    // A single pass or a failed build says little about how long a full one takes
+    let full_builds = history.iter().filter(|r| r.succeeded && !r.single_pass);
+    let is_file = |record: &CompileRecord| {
+        latex::project_file(&record.file_path).is_ok_and(|path| path == file)
+    };
+
+    let recent: Vec<&CompileRecord> = full_builds
+        .clone()
+        .filter(|r| is_file(r))
+        .rev()
+        .take(ESTIMATE_SAMPLES)
+        .collect();
+    if !recent.is_empty() {
+        let duration = median(recent.iter().map(|r| r.duration_ms as f64).collect())?;
+        let recorded_bytes = median(
+            recent
+                .iter()
+                .filter_map(|r| r.input_bytes)
+                .map(|bytes| bytes as f64)
+                .collect(),
+        );
+        let growth = recorded_bytes
+            .filter(|bytes| *bytes > 0.0 && input_bytes > 0)
+            .map_or(1.0, |bytes| input_bytes as f64 / bytes);
+        return Some((
+            (duration * growth).round() as u64,
+            EstimateBasis::Document,
+            recent.len(),
+        ));
+    }
+
+    let rates: Vec<f64> = full_builds
+        .filter_map(|r| Some(r.duration_ms as f64 / r.input_bytes.filter(|b| *b > 0)? as f64))
+        .collect();
+    let samples = rates.len();
+    let rate = median(rates)?;
+    Some((
+        (rate * input_bytes as f64).round() as u64,
+        EstimateBasis::Project,
+        samples,
+    ))
+}
+
+/// Estimate what building `root_file` will take before building it: how much it reads
+/// from the project, how large its graphics are, and how long the build should run
+/// judging by the compile history
+#[tauri::command]
+pub async fn estimate_build(
+    project_path: String,
+    root_file: String,
+) -> Result<BuildEstimate, String> {
+    tauri::async_runtime::spawn_blocking(move || {
+        let project_dir = PathBuf::from(&project_path);
+        let file = latex::project_file(&root_file)?;
+        let meta = read_project_meta(&project_dir)?;
+
+        let sources = latex::load_sources(&project_dir, &file)?;
+        // The sources come first, in reading order, then everything else they use
+        let assets = asset_dependencies(&project_dir, &file)?;
+        let (source_paths, others) = assets.split_at(sources.len().min(assets.len()));
+        let size = |file: &String| total_size(&project_dir, std::slice::from_ref(file));
+        let (graphics, others): (Vec<&String>, Vec<&String>) = others.iter().partition(|asset| {
+            Path::new(asset.as_str()).extension().is_some_and(|ext| {
+                GRAPHICS_EXTENSIONS.contains(&ext.to_string_lossy().to_lowercase().as_str())
+            })
+        });
+        let largest = graphics
+            .iter()
+            .map(|graphic| (*graphic, size(graphic)))
+            .max_by_key(|(_, bytes)| *bytes);
+
+        let source_bytes = total_size(&project_dir, source_paths);
+        let graphics_bytes: u64 = graphics.iter().map(|graphic| size(graphic)).sum();
+        let other_bytes: u64 = others.iter().map(|other| size(other)).sum();
+        let expected = expected_duration(
+            &meta.compile_history,
+            &file,
+            source_bytes + graphics_bytes + other_bytes,
+        );
+
+        let last_pdf_bytes = find_built_pdf(&project_path, &file)
+            .ok()
+            .and_then(|pdf| std::fs::metadata(pdf).ok())
+            .map(|metadata| metadata.len());
+
+        Ok(BuildEstimate {
+            source_files: source_paths.len(),
+            source_bytes,
+            graphics: graphics.len(),
+            graphics_bytes,
+            largest_graphic: largest.map(|(graphic, _)| graphic.clone()),
+            largest_graphic_bytes: largest.map_or(0, |(_, bytes)| bytes),
+            other_files: others.len(),
+            other_bytes,
+            expected_duration_ms: expected.map(|(duration, _, _)| duration),
+            basis: expected.map(|(_, basis, _)| basis),
+            samples: expected.map_or(0, |(_, _, samples)| samples),
+            last_pdf_bytes,
+            last_pdf_pages: meta
+                .last_compiles
+                .get(&file)
+                .and_then(|last| last.pdf_pages),
+        })
+    })
+    .await
+    .map_err(|e| format!("Task join error: {}", e))?
+}
+
+/// How `file_path` was last compiled successfully, if the project remembers. Pass the
+/// options the next compile would use to learn which of them changed since.
+#[tauri::command]
+pub async fn last_compile_info(
+    project_path: String,
+    file_path: String,
+    options: Option<CompileOptions>,
+) -> Result<Option<LastCompileInfo>, String> {
+    let meta = read_project_meta(&PathBuf::from(&project_path))?;
+    let file = latex::project_file(&file_path)?;
+    let Some(last) = meta.last_compiles.get(&file).cloned() else {
+        return Ok(None);
+    };
+
+    let current = last_compile(&options.unwrap_or_default());
+    let changed_options = [
+        ("draft", last.draft != current.draft),
+        ("paper_size", last.paper_size != current.paper_size),
+        ("landscape", last.landscape != current.landscape),
+        (
+            "extra_preamble",
+            last.extra_preamble != current.extra_preamble,
+        ),
+        ("include_only", last.include_only != current.include_only),
+        ("watermark", last.watermark != current.watermark),
+        ("profile", last.profile != current.profile),
+    ]
+    .into_iter()
+    .filter(|(_, changed)| *changed)
+    .map(|(name, _)| name.to_string())
+    .collect();
+
+    Ok(Some(LastCompileInfo {
+        engine_changed: last.engine != current.engine
+            || last.tectonic_version != current.tectonic_version,
+        changed_options,
+        last,
+    }))
+}
+
+#[tauri::command]
+pub async fn compile_all(
+    project_path: String,
+    files: Vec<String>,
+) -> Result<Vec<FileCompileResult>, String> {
+    tauri::async_runtime::spawn_blocking(move || {
+        let project_dir = PathBuf::from(&project_path);
+
+        if project_dir.join("Tectonic.toml").is_file() {
+            return Err(
+                "Tectonic.toml projects are built through their output profiles".to_string(),
+            );
+        }
 
-        // Set up status backend (no output)
         let mut status = NoopStatusBackend::default();
 
-        // Get default bundle for LaTeX packages
-        let config = PersistentConfig::open(false)
-            .map_err(|e| format!("Failed to open Tectonic config: {}", e))?;
+        // One after the other, so the format the first run builds is reused by the rest.
+        // That is one compile slot for the whole batch.
+        let _slot = CompileSlot::acquire();
+        let env = TexEnvironment::open()?;
 
-        let bundle = config
-            .default_bundle(false, &mut status)
-            .map_err(|e| format!("Failed to get bundle: {}", e))?;
+        Ok(files
+            .into_iter()
+            .map(|file_path| {
+                let result = compile_file(
+                    &env,
+                    &project_dir,
+                    &file_path,
+                    None,
+                    TexRun {
+                        formats: &[CompileFormat::Pdf],
+                        max_passes: None,
+                        single_pass: false,
+                        shell_escape: false,
+                        diagnostics: DiagnosticsMode::All,
+                    },
+                    &mut status,
+                );
 
-        let format_cache = config
-            .format_cache_path()
-            .map_err(|e| format!("Failed to get format cache path: {}", e))?;
+                match result {
+                    Ok(result) => FileCompileResult {
+                        file_path,
+                        result: Some(result),
+                        error: None,
+                    },
+                    Err(error) => FileCompileResult {
+                        file_path,
+                        result: None,
+                        error: Some(error),
+                    },
+                }
+            })
+            .collect())
+    })
+    .await
+    .map_err(|e| format!("Task join error: {}", e))?
+}
+
+/// What invalidate_compile_cache removed
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ClearedCache {
+    /// Project-relative paths of the build files removed
+    pub build_files: Vec<String>,
+    /// Tectonic format files removed, when that was requested
+    pub format_files: Vec<String>,
+}
+
+/// Remove everything under `dir` except built PDFs, which the viewer still shows
+fn clear_build_dir(dir: &Path, project_dir: &Path, removed: &mut Vec<String>) {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return;
+    };
+    for entry in entries.filter_map(|entry| entry.ok()) {
+        let path = entry.path();
+        let Ok(metadata) = std::fs::symlink_metadata(&path) else {
+            continue;
+        };
+        if metadata.is_dir() {
+            clear_build_dir(&path, project_dir, removed);
+            // Chapter directories made for .aux files go too once empty
+            let _ = std::fs::remove_dir(&path);
+            continue;
+        }
+
+        let name = entry.file_name().to_string_lossy().to_lowercase();
+        if name.ends_with(".pdf") || name.ends_with(".pdf.gz") {
+            continue;
+        }
+        if std::fs::remove_file(&path).is_ok() {
+            removed.push(latex::to_project_path(
+                path.strip_prefix(project_dir).unwrap_or(&path),
+            ));
+        }
+    }
+}
+
+/// Force the next compile to start from scratch: remove the intermediate files kept in
+/// build/ (.aux, .log, ...) and, with `include_formats`, Tectonic's cached formats,
+/// which are shared by every project and rebuilt on the next compile
+#[tauri::command]
+pub async fn invalidate_compile_cache(
+    project_path: String,
+    include_formats: Option<bool>,
+) -> Result<ClearedCache, String> {
+    tauri::async_runtime::spawn_blocking(move || {
+        let project_dir = PathBuf::from(&project_path);
+        let mut cleared = ClearedCache::default();
+
+        let build_dir = build_dir(&project_dir)?;
+        clear_build_dir(&build_dir, &project_dir, &mut cleared.build_files);
+
+        if include_formats.unwrap_or(false) {
+            let format_cache = TexEnvironment::open()?.format_cache;
+            if let Ok(entries) = std::fs::read_dir(&format_cache) {
+                for entry in entries.filter_map(|entry| entry.ok()) {
+                    let path = entry.path();
+                    if path.extension().is_some_and(|ext| ext == "fmt")
+                        && std::fs::remove_file(&path).is_ok()
+                    {
+                        cleared.format_files.push(path.display().to_string());
+                    }
+                }
+            }
+        }
+
+        *BUNDLE_PACKAGES.lock().unwrap() = None;
+        Ok(cleared)
+    })
+    .await
+    .map_err(|e| format!("Task join error: {}", e))?
+}
+
+/// Tectonic's caches on disk, which every project shares. With nothing cached, a
+/// compile downloads each file it needs from the bundle and builds the format first,
+/// which is most of what makes a first compile slow.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CacheHealth {
+    pub format_cache_path: String,
+    pub format_cache_bytes: u64,
+    /// Formats built, one per bundle and engine version
+    pub format_files: usize,
+    /// Some format is built. One for a bundle that has none is built on its next compile.
+    pub format_built: bool,
+    pub bundle_cache_path: String,
+    pub bundle_cache_bytes: u64,
+    /// Packages, classes, fonts and other files downloaded from bundles
+    pub cached_files: usize,
+    /// Bundles whose file index has been downloaded
+    pub cached_bundles: usize,
+}
+
+/// Where Tectonic caches formats, and bundle files
+fn cache_dirs() -> Result<(PathBuf, PathBuf), String> {
+    let config = PersistentConfig::open(false)
+        .map_err(|e| format!("Failed to open Tectonic config: {}", e))?;
+    let format_cache = config
+        .format_cache_path()
+        .map_err(|e| format!("Failed to get format cache path: {}", e))?;
+    let bundle_cache = app_dirs::get_user_cache_dir("bundles")
+        .map_err(|e| format!("Failed to get bundle cache path: {}", e))?;
+    Ok((format_cache, bundle_cache))
+}
+
+/// The formats in the format cache
+fn format_files(format_cache: &Path) -> Vec<PathBuf> {
+    let Ok(entries) = std::fs::read_dir(format_cache) else {
+        return Vec::new();
+    };
+    entries
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "fmt") && path.is_file())
+        .collect()
+}
+
+fn measure_caches() -> Result<CacheHealth, String> {
+    let (format_cache, bundle_cache) = cache_dirs()?;
+    let formats = format_files(&format_cache);
+    let mut format_usage = DiskUsage::default();
+    measure_dir(&format_cache, &format_cache, None, 0, &mut format_usage);
+    let mut bundle_usage = DiskUsage::default();
+    measure_dir(&bundle_cache, &bundle_cache, None, 0, &mut bundle_usage);
+
+    // Each bundle's files are in data/<bundle hash>/, next to its data/<bundle hash>.index
+    let mut cached = DiskUsage::default();
+    let mut cached_bundles = 0;
+    let data = bundle_cache.join("data");
+    for entry in std::fs::read_dir(&data).into_iter().flatten().flatten() {
+        let path = entry.path();
+        let Ok(metadata) = std::fs::symlink_metadata(&path) else {
+            continue;
+        };
+        if metadata.is_dir() {
+            measure_dir(&path, &path, None, 0, &mut cached);
+        } else if path.extension().is_some_and(|ext| ext == "index") {
+            cached_bundles += 1;
+        }
+    }
+
+    Ok(CacheHealth {
+        format_cache_path: format_cache.display().to_string(),
+        format_cache_bytes: format_usage.bytes,
+        format_files: formats.len(),
+        format_built: !formats.is_empty(),
+        bundle_cache_path: bundle_cache.display().to_string(),
+        bundle_cache_bytes: bundle_usage.bytes,
+        cached_files: cached.files,
+        cached_bundles,
+    })
+}
+
+/// How populated Tectonic's format and bundle caches are, for telling whether a slow
+/// compile is one that has to download and build them first
+#[tauri::command]
+pub async fn cache_health() -> Result<CacheHealth, String> {
+    tauri::async_runtime::spawn_blocking(measure_caches)
+        .await
+        .map_err(|e| format!("Task join error: {}", e))?
+}
+
+/// Remove Tectonic's built formats, and with `include_bundles` every file downloaded
+/// from bundles, so the next compile builds (and downloads) them again. Only the cache
+/// directories Tectonic itself uses are touched, and symlinks in them are removed
+/// rather than followed. Fails while a compile runs, as it may be reading them; none
+/// can start until clearing is done. Returns the caches as they are afterwards.
+#[tauri::command]
+pub async fn clear_cache(include_bundles: Option<bool>) -> Result<CacheHealth, String> {
+    tauri::async_runtime::spawn_blocking(move || {
+        let running = RUNNING_COMPILES.lock().unwrap();
+        if *running > 0 || current_download().is_some() {
+            return Err("A compile is running; clear the cache once it has finished".to_string());
+        }
+
+        let (format_cache, bundle_cache) = cache_dirs()?;
+        for path in format_files(&format_cache) {
+            std::fs::remove_file(&path)
+                .map_err(|e| format!("Failed to remove {}: {}", path.display(), e))?;
+        }
 
-        // Build the processing session
-        let mut builder = ProcessingSessionBuilder::default();
-        builder
-            .bundle(bundle)
-            .primary_input_path(&full_file_path)
-            .filesystem_root(&project_dir)  // Critical: allows \input{} to work
-            .tex_input_name(&file_path)
-            .format_name("latex")
-            .format_cache_path(&format_cache)
-            .output_dir(&build_dir)  // Output to build/ directory
-            .output_format(OutputFormat::Pdf)
-            .keep_logs(false)
-            .keep_intermediates(false)
-            .print_stdout(false);
+        if include_bundles.unwrap_or(false) {
+            // Bundle files are in data/ and the bundles' hashes, keyed by URL, in hashes/
+            for name in ["data", "hashes"] {
+                let dir = bundle_cache.join(name);
+                match std::fs::remove_dir_all(&dir) {
+                    Ok(()) => {}
+                    Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+                    Err(e) => return Err(format!("Failed to remove {}: {}", dir.display(), e)),
+                }
+            }
+        }
+        drop(running);
 
-        // Create and run the session
-        let mut session = builder
-            .create(&mut status)
-            .map_err(|e| format!("Failed to create session: {}", e))?;
+        *BUNDLE_PACKAGES.lock().unwrap() = None;
+        measure_caches()
+    })
+    .await
+    .map_err(|e| format!("Task join error: {}", e))?
+}
 
-        session
-            .run(&mut status)
-            .map_err(|e| format!("LaTeX compilation failed: {}", e))?;
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ArtifactKind {
+    /// A built PDF, gzipped or not
+    Pdf,
+    Log,
+    /// An .aux file, which carries references and citations between passes
+    Aux,
+    Other,
+}
 
-        eprintln!("Tectonic compilation completed successfully");
+/// A file in the project's build directory
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BuildArtifact {
+    /// Path relative to the build directory, e.g. `main.pdf` or `chapters/intro.aux`
+    pub path: String,
+    pub size: u64,
+    pub kind: ArtifactKind,
+}
 
-        // Read the generated PDF from build/ directory
-        let pdf_name = PathBuf::from(&file_path)
-            .file_stem()
-            .ok_or("Invalid file path")?
-            .to_str()
-            .ok_or("Invalid file name")?
-            .to_string()
-            + ".pdf";
+fn artifact_kind(name: &str) -> ArtifactKind {
+    let name = name.to_lowercase();
+    if name.ends_with(".pdf") || name.ends_with(".pdf.gz") {
+        ArtifactKind::Pdf
+    } else if name.ends_with(".log") {
+        ArtifactKind::Log
+    } else if name.ends_with(".aux") {
+        ArtifactKind::Aux
+    } else {
+        ArtifactKind::Other
+    }
+}
 
-        let pdf_path = build_dir.join(&pdf_name);
+/// Collect the files under `dir`. Symlinks aren't followed, so nothing outside the
+/// build directory is listed.
+fn collect_artifacts(dir: &Path, build_dir: &Path, artifacts: &mut Vec<BuildArtifact>) {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return;
+    };
+    for entry in entries.filter_map(|entry| entry.ok()) {
+        let path = entry.path();
+        let Ok(metadata) = std::fs::symlink_metadata(&path) else {
+            continue;
+        };
+        if metadata.is_dir() {
+            collect_artifacts(&path, build_dir, artifacts);
+        } else if metadata.is_file() {
+            artifacts.push(BuildArtifact {
+                path: latex::to_project_path(path.strip_prefix(build_dir).unwrap_or(&path)),
+                size: metadata.len(),
+                kind: artifact_kind(&entry.file_name().to_string_lossy()),
+            });
+        }
+    }
+}
 
-        if !pdf_path.exists() {
-            return Err(format!("PDF not found at: {}", pdf_path.display()));
+/// Every file in the project's build directory with its size and kind, to see what a
+/// build left behind before clearing it with invalidate_compile_cache
+#[tauri::command]
+pub async fn list_build_artifacts(project_path: String) -> Result<Vec<BuildArtifact>, String> {
+    tauri::async_runtime::spawn_blocking(move || {
+        let build_dir = build_dir(&PathBuf::from(&project_path))?;
+        if !build_dir.is_dir() {
+            return Ok(Vec::new());
         }
 
-        eprintln!("Found PDF at: {}", pdf_path.display());
+        let mut artifacts = Vec::new();
+        collect_artifacts(&build_dir, &build_dir, &mut artifacts);
+        artifacts.sort_by(|a, b| a.path.cmp(&b.path));
+        Ok(artifacts)
+    })
+    .await
+    .map_err(|e| format!("Task join error: {}", e))?
+}
+
+#[tauri::command]
+pub async fn is_v2_project(project_path: String) -> Result<bool, String> {
+    Ok(PathBuf::from(&project_path).join("Tectonic.toml").is_file())
+}
+
+#[tauri::command]
+pub async fn list_bundle_packages() -> Result<Vec<String>, String> {
+    let active = read_global_settings()?.active_bundle;
+    if let Some(cached) = BUNDLE_PACKAGES
+        .lock()
+        .unwrap()
+        .as_ref()
+        .filter(|cached| cached.bundle == active)
+    {
+        return Ok(cached.packages.clone());
+    }
+
+    // Enumerating the bundle index is slow, so keep it off the main thread
+    let packages = tauri::async_runtime::spawn_blocking(|| {
+        let mut noop = NoopStatusBackend::default();
+        let mut status = DownloadWatch::new(&mut noop);
+        let bundle = TexEnvironment::open()?.open_bundle(&mut status)?;
+
+        let mut packages: Vec<String> = bundle
+            .all_files()
+            .into_iter()
+            .filter(|name| name.ends_with(".sty") || name.ends_with(".cls"))
+            .map(|name| name.rsplit('/').next().unwrap_or(&name).to_string())
+            .collect();
+
+        packages.sort();
+        packages.dedup();
+
+        Ok::<_, String>(packages)
+    })
+    .await
+    .map_err(|e| format!("Task join error: {}", e))??;
+
+    *BUNDLE_PACKAGES.lock().unwrap() = Some(BundlePackages {
+        bundle: active,
+        packages: packages.clone(),
+    });
+
+    Ok(packages)
+}
+
+/// Payload of list_bundles
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BundleList {
+    /// Where Tectonic's own default bundle comes from
+    pub default_location: String,
+    pub bundles: Vec<BundleSource>,
+    /// Name of the bundle compiles use; None for the default
+    pub active: Option<String>,
+}
+
+/// The bundles configured in settings and which one compiles use
+#[tauri::command]
+pub async fn list_bundles() -> Result<BundleList, String> {
+    let settings = read_global_settings()?;
+    let config = PersistentConfig::open(false)
+        .map_err(|e| format!("Failed to open Tectonic config: {}", e))?;
+
+    Ok(BundleList {
+        default_location: config.default_bundle_loc().to_string(),
+        bundles: settings.bundles,
+        active: settings.active_bundle,
+    })
+}
+
+/// Read a file such as `article.cls` out of the active bundle. Returns None when the
+/// bundle has no file by that name.
+#[tauri::command]
+pub async fn read_bundle_file(name: String) -> Result<Option<String>, String> {
+    let name = name.trim().to_string();
+    if name.is_empty() {
+        return Err("Invalid file name".to_string());
+    }
 
-        let pdf_output = std::fs::read(&pdf_path)
-            .map_err(|e| format!("Failed to read PDF: {}", e))?;
+    tauri::async_runtime::spawn_blocking(move || {
+        let mut noop = NoopStatusBackend::default();
+        let mut status = DownloadWatch::new(&mut noop);
+        let mut bundle = TexEnvironment::open()?.open_bundle(&mut status)?;
+
+        let handle = match bundle.input_open_name(&name, &mut status) {
+            OpenResult::Ok(handle) => handle,
+            OpenResult::NotAvailable => return Ok(None),
+            OpenResult::Err(e) => {
+                return Err(format!("Failed to read {} from the bundle: {}", name, e))
+            }
+        };
 
-        if pdf_output.is_empty() {
-            return Err("Compilation produced no output".to_string());
+        let mut bytes = Vec::new();
+        handle
+            .take(MAX_BUNDLE_FILE_BYTES + 1)
+            .read_to_end(&mut bytes)
+            .map_err(|e| format!("Failed to read {} from the bundle: {}", name, e))?;
+        if bytes.len() as u64 > MAX_BUNDLE_FILE_BYTES {
+            return Err(format!("{} is too large to show", name));
         }
 
-        Ok(pdf_output)
+        // Older packages are often Latin-1
+        Ok(Some(String::from_utf8_lossy(&bytes).into_owned()))
     })
     .await
     .map_err(|e| format!("Task join error: {}", e))?
 }
+
+#[tauri::command]
+pub async fn missing_packages(
+    project_path: String,
+    file_path: String,
+) -> Result<Vec<MissingPackage>, String> {
+    let project_dir = PathBuf::from(&project_path);
+    let log_name = output_name(&read_project_meta(&project_dir)?, &file_path)? + ".log";
+
+    let log_path = build_dir(&project_dir)?.join(&log_name);
+
+    if !log_path.exists() {
+        return Ok(Vec::new());
+    }
+
+    // TeX logs aren't guaranteed to be valid UTF-8
+    let log = std::fs::read(&log_path).map_err(|e| format!("Failed to read build log: {}", e))?;
+
+    Ok(tex_log::missing_packages(&String::from_utf8_lossy(&log)))
+}
+
+/// The raw TeX log the last compile of `file_path` left in the build directory
+#[tauri::command]
+pub async fn get_build_log(project_path: String, file_path: String) -> Result<String, String> {
+    let project_dir = PathBuf::from(&project_path);
+    let log_name = output_name(&read_project_meta(&project_dir)?, &file_path)? + ".log";
+
+    let build_dir = build_dir(&project_dir)?;
+    let log_path = build_dir.join(&log_name);
+
+    if !log_path.exists() {
+        return Err(format!(
+            "No build log for {}; compile it first to create one",
+            file_path
+        ));
+    }
+
+    // Security check: ensure the log is within the build directory
+    let canonical_build = build_dir
+        .canonicalize()
+        .map_err(|e| format!("Invalid build path: {}", e))?;
+
+    let canonical_log = log_path
+        .canonicalize()
+        .map_err(|e| format!("Invalid log path: {}", e))?;
+
+    if !canonical_log.starts_with(&canonical_build) {
+        return Err("Access denied: log is outside build directory".to_string());
+    }
+
+    // TeX logs aren't guaranteed to be valid UTF-8
+    let log = std::fs::read(&log_path).map_err(|e| format!("Failed to read build log: {}", e))?;
+    Ok(String::from_utf8_lossy(&log).into_owned())
+}
+
+/// The table of contents of the last build of `file_path`, read from the .toc file it
+/// left in the build directory, with the page each heading ended up on. Builds keep the
+/// .toc when the root file has a \tableofcontents.
+#[tauri::command]
+pub async fn parse_toc(project_path: String, file_path: String) -> Result<Vec<TocEntry>, String> {
+    let project_dir = PathBuf::from(&project_path);
+    let toc_name = output_name(&read_project_meta(&project_dir)?, &file_path)? + ".toc";
+
+    let build_dir = build_dir(&project_dir)?;
+    let toc_path = build_dir.join(&toc_name);
+
+    if !toc_path.exists() {
+        return Err(format!(
+            "No table of contents for {}; compile it with a \\tableofcontents first",
+            file_path
+        ));
+    }
+
+    // Security check: ensure the .toc is within the build directory
+    let canonical_build = build_dir
+        .canonicalize()
+        .map_err(|e| format!("Invalid build path: {}", e))?;
+
+    let canonical_toc = toc_path
+        .canonicalize()
+        .map_err(|e| format!("Invalid table of contents path: {}", e))?;
+
+    if !canonical_toc.starts_with(&canonical_build) {
+        return Err("Access denied: table of contents is outside build directory".to_string());
+    }
+
+    // Titles are written as TeX read them, which needn't be valid UTF-8
+    let toc =
+        std::fs::read(&toc_path).map_err(|e| format!("Failed to read table of contents: {}", e))?;
+    Ok(toc::parse(&String::from_utf8_lossy(&toc)))
+}
+
+/// The bundle download some compile is waiting on, for showing that a slow first build
+/// is still fetching packages. None when nothing is downloading.
+#[tauri::command]
+pub async fn bundle_download_status() -> Result<Option<BundleDownload>, String> {
+    Ok(current_download())
+}
+
+/// Download the bundle files a typical document needs, and build the format, before the
+/// first real compile. While files download, a `bundle-download` event goes out every
+/// DOWNLOAD_HEARTBEAT; one with `finished` set follows when the prefetch ends.
+#[tauri::command]
+pub async fn prefetch_bundle(app: AppHandle) -> Result<(), CompileError> {
+    let done = Arc::new(AtomicBool::new(false));
+    let heartbeat = {
+        let app = app.clone();
+        let done = done.clone();
+        std::thread::spawn(move || {
+            let started = Instant::now();
+            let mut files_downloaded = 0;
+            while !done.load(Ordering::Relaxed) {
+                std::thread::sleep(DOWNLOAD_HEARTBEAT);
+                if let Some(download) = current_download() {
+                    files_downloaded = download.files_downloaded;
+                    if let Err(e) = app.emit("bundle-download", download) {
+                        eprintln!("Failed to emit bundle-download: {}", e);
+                    }
+                }
+            }
+            BundleDownload {
+                files_downloaded,
+                current_file: None,
+                elapsed_secs: started.elapsed().as_secs(),
+                finished: true,
+            }
+        })
+    };
+
+    let result = compile_template(PREFETCH_DOCUMENT.to_string(), BTreeMap::new()).await;
+
+    done.store(true, Ordering::Relaxed);
+    let finished = tauri::async_runtime::spawn_blocking(move || heartbeat.join())
+        .await
+        .map_err(|e| format!("Task join error: {}", e))?;
+    match finished {
+        Ok(finished) => {
+            if let Err(e) = app.emit("bundle-download", finished) {
+                eprintln!("Failed to emit bundle-download: {}", e);
+            }
+        }
+        Err(_) => eprintln!("Bundle download heartbeat stopped unexpectedly"),
+    }
+
+    result.map(|_| ())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    #[ignore = "needs the TeX bundle, which Tectonic downloads on first use"]
+    fn single_pass_still_builds_a_pdf() {
+        let dir = std::env::temp_dir().join(format!("incipit-single-pass-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(
+            dir.join("main.tex"),
+            "\\documentclass{article}\n\\begin{document}\nHello\n\\end{document}\n",
+        )
+        .unwrap();
+
+        let env = TexEnvironment::open().unwrap();
+        let run = TexRun {
+            formats: &[CompileFormat::Pdf],
+            max_passes: None,
+            single_pass: true,
+            shell_escape: false,
+            diagnostics: DiagnosticsMode::All,
+        };
+        let mut status = NoopStatusBackend::default();
+        let result = compile_file(&env, &dir, "main.tex", None, run, &mut status);
+        let _ = std::fs::remove_dir_all(&dir);
+
+        let result = result.unwrap();
+        assert!(result.succeeded);
+        assert_eq!(result.passes, 1);
+        assert!(result.outputs["pdf"].starts_with(b"%PDF"));
+    }
+
+    const WARNINGS_LOG: &str = "(./main.tex
+LaTeX Warning: Citation `knuth' on page 1 undefined on input line 4.
+
+)
+";
+
+    const ERROR_LOG: &str = "(./main.tex
+LaTeX Warning: Citation `knuth' on page 1 undefined on input line 4.
+
+! Undefined control sequence.
+l.5 \\foo
+)
+";
+
+    fn severities(diagnostics: &[Diagnostic]) -> (usize, usize) {
+        let errors = diagnostics
+            .iter()
+            .filter(|d| matches!(d.severity, tex_log::Severity::Error))
+            .count();
+        (errors, diagnostics.len() - errors)
+    }
+
+    #[test]
+    fn no_diagnostics_parses_nothing() {
+        for (log, failed) in [(WARNINGS_LOG, false), (ERROR_LOG, false), (ERROR_LOG, true)] {
+            assert!(DiagnosticsMode::None.parse(log, failed).is_empty());
+        }
+    }
+
+    #[test]
+    fn errors_only_skips_a_clean_build() {
+        assert!(DiagnosticsMode::ErrorsOnly
+            .parse(WARNINGS_LOG, false)
+            .is_empty());
+        // A build that failed without an error in its log, e.g. its PDF missing
+        assert_eq!(
+            severities(&DiagnosticsMode::ErrorsOnly.parse(WARNINGS_LOG, true)),
+            (0, 1)
+        );
+        for failed in [false, true] {
+            assert_eq!(
+                severities(&DiagnosticsMode::ErrorsOnly.parse(ERROR_LOG, failed)),
+                (1, 1)
+            );
+        }
+    }
+
+    #[test]
+    fn all_diagnostics_parses_every_log() {
+        for failed in [false, true] {
+            assert_eq!(
+                severities(&DiagnosticsMode::All.parse(WARNINGS_LOG, failed)),
+                (0, 1)
+            );
+            assert_eq!(
+                severities(&DiagnosticsMode::All.parse(ERROR_LOG, failed)),
+                (1, 1)
+            );
+        }
+    }
+}
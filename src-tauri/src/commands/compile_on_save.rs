@@ -0,0 +1,186 @@
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tauri::{AppHandle, Emitter};
+
+use super::compilation::{
+    compile_latex_project, CompileError, CompileErrorKind, CompileOptions, CompileResult,
+    FileCompileResult,
+};
+use super::disk_space::check_free_space;
+use super::live_preview;
+use super::project::read_project_meta;
+use super::settings::read_global_settings;
+use crate::latex;
+
+/// Quiet time after the last save before compiling, so saving several files in a row
+/// builds once
+const COMPILE_DEBOUNCE: Duration = Duration::from_millis(800);
+
+/// How often the compile thread looks for a due compile
+const COMPILE_TICK: Duration = Duration::from_millis(100);
+
+/// The next compile to run. Another save to the build pushes it back, so a burst of
+/// saves becomes one compile; saves during a compile queue one more after it.
+struct PendingCompile {
+    project_path: String,
+    root_file: String,
+    due: Instant,
+}
+
+static PENDING_COMPILE: Mutex<Option<PendingCompile>> = Mutex::new(None);
+
+/// Set once the compile thread is running
+static COMPILE_THREAD: Mutex<bool> = Mutex::new(false);
+
+/// Payload of the `compile-finished` event
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CompiledOnSave {
+    pub project_path: String,
+    #[serde(flatten)]
+    pub compile: FileCompileResult,
+}
+
+/// The file to compile after `file_path` is saved: the project's root file, if
+/// `file_path` is the root or a file it includes
+fn compiled_root(project_dir: &Path, file_path: &str) -> Result<Option<String>, String> {
+    let file_path = latex::project_file(file_path)?;
+    let root = latex::project_file(&read_project_meta(project_dir)?.root_file)?;
+
+    // A Tectonic.toml project builds its configured output, whatever its entry file
+    if project_dir.join("Tectonic.toml").is_file() {
+        return Ok(file_path.ends_with(".tex").then_some(root));
+    }
+
+    let sources = latex::load_sources(project_dir, &root)?;
+    Ok(sources
+        .iter()
+        .any(|source| source.path == file_path)
+        .then_some(root))
+}
+
+/// Queue a compile of the project when compile_on_save is on and `file_path` is part of
+/// its build. Called after every write of the editor's content, by hand or by autosave.
+pub(crate) fn file_saved(app: &AppHandle, project_path: &str, file_path: &str) {
+    match read_global_settings() {
+        Ok(settings) if settings.compile_on_save => {}
+        Ok(_) => return,
+        Err(e) => {
+            eprintln!("Failed to read settings for compile on save: {}", e);
+            return;
+        }
+    }
+
+    let root_file = match compiled_root(Path::new(project_path), file_path) {
+        Ok(Some(root_file)) => root_file,
+        Ok(None) => return,
+        Err(e) => {
+            eprintln!("Failed to find the build {} belongs to: {}", file_path, e);
+            return;
+        }
+    };
+
+    queue_compile(app, project_path, root_file);
+}
+
+/// Compile `root_file` once no further request for it has come in for
+/// COMPILE_DEBOUNCE, replacing any compile still waiting
+pub(crate) fn queue_compile(app: &AppHandle, project_path: &str, root_file: String) {
+    {
+        let mut running = COMPILE_THREAD.lock().unwrap();
+        if !*running {
+            let app = app.clone();
+            std::thread::spawn(move || run_compiles(app));
+            *running = true;
+        }
+    }
+
+    *PENDING_COMPILE.lock().unwrap() = Some(PendingCompile {
+        project_path: project_path.to_string(),
+        root_file,
+        due: Instant::now() + COMPILE_DEBOUNCE,
+    });
+}
+
+/// Take the queued compile once its debounce has passed
+fn take_due() -> Option<PendingCompile> {
+    let mut pending = PENDING_COMPILE.lock().unwrap();
+    if pending.as_ref().is_some_and(|p| Instant::now() >= p.due) {
+        pending.take()
+    } else {
+        None
+    }
+}
+
+/// Compile the saved root file with the options of its last compile, e.g. still in draft
+/// mode, without writing it back. Refused while the project's disk is under the low-disk
+/// threshold.
+async fn compile_saved(
+    project_path: String,
+    root_file: String,
+) -> Result<CompileResult, CompileError> {
+    let project_dir = PathBuf::from(&project_path);
+    if let Err(low) = check_free_space(&project_dir) {
+        return Err(CompileError {
+            kind: CompileErrorKind::InsufficientDiskSpace,
+            available_bytes: Some(low.available_bytes),
+            ..low.to_string().into()
+        });
+    }
+    let source = latex::read_source(&project_dir, &root_file)?;
+
+    let meta = read_project_meta(&project_dir)?;
+    let mut options = match meta.last_compiles.get(&root_file) {
+        Some(last) => CompileOptions {
+            draft: last.draft,
+            paper_size: last.paper_size.clone(),
+            landscape: last.landscape,
+            extra_preamble: last.extra_preamble.clone(),
+            include_only: last.include_only.clone(),
+            watermark: last.watermark.clone(),
+            profile: last.profile.clone(),
+            ..CompileOptions::default()
+        },
+        None => CompileOptions::default(),
+    };
+    // Tectonic.toml projects can't compile from memory, but build the saved files anyway
+    options.preview = !project_dir.join("Tectonic.toml").is_file();
+
+    compile_latex_project(project_path, root_file, source, Some(options)).await
+}
+
+fn run_compiles(app: AppHandle) {
+    loop {
+        std::thread::sleep(COMPILE_TICK);
+        let Some(pending) = take_due() else {
+            continue;
+        };
+
+        let result = tauri::async_runtime::block_on(compile_saved(
+            pending.project_path.clone(),
+            pending.root_file.clone(),
+        ));
+        live_preview::compiled(
+            &pending.project_path,
+            &pending.root_file,
+            result.as_ref().ok(),
+        );
+        let (result, error) = match result {
+            Ok(result) => (Some(result), None),
+            Err(error) => (None, Some(error)),
+        };
+
+        let compiled = CompiledOnSave {
+            project_path: pending.project_path,
+            compile: FileCompileResult {
+                file_path: pending.root_file,
+                result,
+                error,
+            },
+        };
+        if let Err(e) = app.emit("compile-finished", compiled) {
+            eprintln!("Failed to emit compile-finished: {}", e);
+        }
+    }
+}
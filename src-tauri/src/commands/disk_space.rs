@@ -0,0 +1,145 @@
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::path::Path;
+use std::sync::Mutex;
+use std::time::Duration;
+use tauri::{AppHandle, Emitter};
+
+use super::settings::read_global_settings;
+
+/// How often the monitor reads the free space of the watched project's disk
+const DISK_CHECK_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Low-disk threshold when the `low_disk_threshold_mb` setting is unset
+const DEFAULT_LOW_DISK_MB: u64 = 100;
+
+const BYTES_PER_MB: u64 = 1024 * 1024;
+
+/// The project whose disk watch_disk_space keeps an eye on
+struct DiskWatch {
+    project_path: String,
+    /// Whether the last check found space low, so `low-disk` is emitted once each time
+    /// it drops below the threshold rather than on every check
+    low: bool,
+}
+
+static DISK_WATCH: Mutex<Option<DiskWatch>> = Mutex::new(None);
+
+/// Set once the disk monitor thread is running
+static DISK_WATCH_THREAD: Mutex<bool> = Mutex::new(false);
+
+/// Payload of the `low-disk` event
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LowDisk {
+    pub project_path: String,
+    pub available_bytes: u64,
+    pub threshold_bytes: u64,
+}
+
+/// Why a write in the background was refused: the disk has less free space than the
+/// low-disk threshold
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct LowDiskSpace {
+    pub available_bytes: u64,
+    pub threshold_bytes: u64,
+}
+
+impl fmt::Display for LowDiskSpace {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Only {:.1} MB free on the project's disk, under the low-disk threshold of {} MB; \
+             free up some space to save and compile again",
+            self.available_bytes as f64 / BYTES_PER_MB as f64,
+            self.threshold_bytes / BYTES_PER_MB
+        )
+    }
+}
+
+/// The `low_disk_threshold_mb` setting in bytes
+fn threshold_bytes() -> u64 {
+    let mb = read_global_settings()
+        .ok()
+        .and_then(|settings| settings.low_disk_threshold_mb)
+        .unwrap_or(DEFAULT_LOW_DISK_MB);
+    mb.saturating_mul(BYTES_PER_MB)
+}
+
+/// Refuse a write to the disk holding `path` when it is nearly full, before a partial
+/// write leaves a truncated file. Autosave and compile on save check this, as nobody is
+/// there to notice the failure midway. Disks whose free space can't be read aren't
+/// checked.
+pub(crate) fn check_free_space(path: &Path) -> Result<(), LowDiskSpace> {
+    let Ok(available_bytes) = fs2::available_space(path) else {
+        return Ok(());
+    };
+    let threshold_bytes = threshold_bytes();
+    if available_bytes >= threshold_bytes {
+        return Ok(());
+    }
+    Err(LowDiskSpace {
+        available_bytes,
+        threshold_bytes,
+    })
+}
+
+/// Read the watched disk's free space, emitting `low-disk` if it has just dropped below
+/// the threshold
+fn check_watched(app: &AppHandle) {
+    let mut watch = DISK_WATCH.lock().unwrap();
+    let Some(watch) = watch.as_mut() else {
+        return;
+    };
+
+    let low = check_free_space(Path::new(&watch.project_path)).err();
+    if let Some(low) = low.filter(|_| !watch.low) {
+        let event = LowDisk {
+            project_path: watch.project_path.clone(),
+            available_bytes: low.available_bytes,
+            threshold_bytes: low.threshold_bytes,
+        };
+        if let Err(e) = app.emit("low-disk", event) {
+            eprintln!("Failed to emit low-disk: {}", e);
+        }
+    }
+    watch.low = low.is_some();
+}
+
+fn run_disk_watch(app: AppHandle) {
+    loop {
+        std::thread::sleep(DISK_CHECK_INTERVAL);
+        check_watched(&app);
+    }
+}
+
+/// Check the free space of the disk holding `project_path` every DISK_CHECK_INTERVAL,
+/// emitting `low-disk` whenever it drops below the `low_disk_threshold_mb` setting,
+/// starting with now. Replaces any project already watched. Returns the free bytes.
+#[tauri::command]
+pub async fn watch_disk_space(app: AppHandle, project_path: String) -> Result<u64, String> {
+    let available = fs2::available_space(&project_path)
+        .map_err(|e| format!("Failed to read free disk space: {}", e))?;
+
+    {
+        let mut running = DISK_WATCH_THREAD.lock().unwrap();
+        if !*running {
+            let app = app.clone();
+            std::thread::spawn(move || run_disk_watch(app));
+            *running = true;
+        }
+    }
+
+    *DISK_WATCH.lock().unwrap() = Some(DiskWatch {
+        project_path,
+        low: false,
+    });
+    check_watched(&app);
+    Ok(available)
+}
+
+/// Stop the disk monitor started by watch_disk_space, if any
+#[tauri::command]
+pub async fn stop_watching_disk_space() -> Result<(), String> {
+    DISK_WATCH.lock().unwrap().take();
+    Ok(())
+}
@@ -0,0 +1,540 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use super::compilation::on_path;
+use super::project::{
+    build_dir, find_built_pdf, output_name, pdf_is_stale, read_pdf_bytes, read_project_meta,
+};
+use crate::latex::{self, LineIndex};
+use crate::raster::{self, Raster};
+use crate::{html, pdf, prose, storage};
+
+/// Commands between headings that change how the headings after them are numbered
+const MATTER_COMMANDS: &[&str] = &["appendix", "frontmatter", "mainmatter", "backmatter"];
+
+/// Renders PDF pages to images for diff_pdf, from poppler
+const PDF_RASTERIZER: &str = "pdftoppm";
+
+/// Resolution diff_pdf renders pages at, in pixels per inch
+const DIFF_DPI: u32 = 100;
+
+/// Numbers the scratch directories of PDF diffs running at the same time
+static PDF_DIFFS: AtomicU64 = AtomicU64::new(0);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PageChange {
+    Unchanged,
+    Changed,
+    /// Only the current build has the page
+    Added,
+    /// Only the previous build has the page
+    Removed,
+}
+
+/// A rectangle of a page, in PDF points from its top left
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PageRegion {
+    pub x: f32,
+    pub y: f32,
+    pub width: f32,
+    pub height: f32,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PageDiff {
+    /// 1-based, the same in both builds
+    pub page: u32,
+    pub change: PageChange,
+    /// Share of the page's pixels that differ, from 0 to 1
+    pub changed_fraction: f32,
+    /// Where the page differs, from the top down
+    pub regions: Vec<PageRegion>,
+}
+
+/// Write a copy of the built PDF with every page trimmed to its content, for use as a
+/// standalone figure
+#[tauri::command]
+pub async fn export_cropped_pdf(
+    project_path: String,
+    file_path: String,
+    output_path: String,
+) -> Result<(), String> {
+    let pdf_path = find_built_pdf(&project_path, &file_path)?;
+
+    tauri::async_runtime::spawn_blocking(move || {
+        let bytes = read_pdf_bytes(&pdf_path)?;
+        let mut document =
+            lopdf::Document::load_mem(&bytes).map_err(|e| format!("Failed to parse PDF: {}", e))?;
+
+        if pdf::crop_to_content(&mut document) == 0 {
+            return Err("PDF has no visible content to crop to".to_string());
+        }
+
+        let output_path = PathBuf::from(&output_path);
+        if let Some(parent) = output_path.parent().filter(|p| !p.as_os_str().is_empty()) {
+            if !parent.is_dir() {
+                return Err(format!("Directory does not exist: {}", parent.display()));
+            }
+        }
+
+        document
+            .save(&output_path)
+            .map_err(|e| format!("Failed to write cropped PDF: {}", e))?;
+
+        Ok(())
+    })
+    .await
+    .map_err(|e| format!("Task join error: {}", e))?
+}
+
+/// A file path outside the project to write an export to: absolute, in a directory that
+/// exists, and not itself a directory
+fn check_destination(destination_path: &str) -> Result<PathBuf, String> {
+    let destination = PathBuf::from(destination_path);
+    if !destination.is_absolute() {
+        return Err(format!(
+            "Destination must be an absolute path: {}",
+            destination_path
+        ));
+    }
+    if let Some(parent) = destination.parent() {
+        if !parent.is_dir() {
+            return Err(format!("Directory does not exist: {}", parent.display()));
+        }
+    }
+    if destination.is_dir() {
+        return Err(format!("Destination is a directory: {}", destination_path));
+    }
+    Ok(destination)
+}
+
+/// Save a copy of the built PDF of `file_path` at `destination_path`, e.g. one picked in
+/// a Save As dialog, and return where it was written. The destination may be anywhere;
+/// the source must be the project's real build output. A gzipped build is unpacked.
+#[tauri::command]
+pub async fn export_pdf(
+    project_path: String,
+    file_path: String,
+    destination_path: String,
+) -> Result<String, String> {
+    let pdf_path = find_built_pdf(&project_path, &file_path)?;
+
+    tauri::async_runtime::spawn_blocking(move || {
+        let destination = check_destination(&destination_path)?;
+
+        let bytes = read_pdf_bytes(&pdf_path)?;
+        storage::write_atomic(&destination, bytes)
+            .map_err(|e| format!("Failed to write PDF: {}", e))?;
+
+        Ok(destination_path)
+    })
+    .await
+    .map_err(|e| format!("Task join error: {}", e))?
+}
+
+/// Concatenate the built PDFs of `files`, in order, into one at `output_path`, e.g. for
+/// proceedings. Every file must have been built since its sources last changed. Each
+/// document gets a bookmark named after its file, with its own bookmarks below it.
+#[tauri::command]
+pub async fn merge_pdfs(
+    project_path: String,
+    files: Vec<String>,
+    output_path: String,
+) -> Result<String, String> {
+    if files.is_empty() {
+        return Err("No documents to merge".to_string());
+    }
+
+    tauri::async_runtime::spawn_blocking(move || {
+        let destination = check_destination(&output_path)?;
+        let project_dir = Path::new(&project_path);
+
+        let mut documents = Vec::new();
+        for file in &files {
+            let pdf_path = find_built_pdf(&project_path, file)
+                .map_err(|_| format!("{} has not been built yet", file))?;
+            if pdf_is_stale(project_dir, file, &pdf_path)? {
+                return Err(format!(
+                    "{} changed since it was last built; compile it before merging",
+                    file
+                ));
+            }
+            let bytes = read_pdf_bytes(&pdf_path)?;
+            let document = lopdf::Document::load_mem(&bytes)
+                .map_err(|e| format!("Failed to parse the PDF of {}: {}", file, e))?;
+            let title = Path::new(file)
+                .file_stem()
+                .map_or(file.clone(), |stem| stem.to_string_lossy().into_owned());
+            documents.push((title, document));
+        }
+
+        let mut merged = pdf::merge(documents)?;
+        let mut bytes = Vec::new();
+        merged
+            .save_to(&mut bytes)
+            .map_err(|e| format!("Failed to write merged PDF: {}", e))?;
+        storage::write_atomic(&destination, bytes)
+            .map_err(|e| format!("Failed to write PDF: {}", e))?;
+
+        Ok(output_path)
+    })
+    .await
+    .map_err(|e| format!("Task join error: {}", e))?
+}
+
+/// Page `page` of the PDF at `pdf_path` rendered at DIFF_DPI, by way of an image file
+/// in `scratch_dir`
+fn render_page(pdf_path: &Path, page: u32, scratch_dir: &Path) -> Result<Raster, String> {
+    let stem = pdf_path
+        .file_stem()
+        .map_or("page".into(), |stem| stem.to_string_lossy());
+    let image_root = scratch_dir.join(format!("{}-{}", stem, page));
+    let output = Command::new(PDF_RASTERIZER)
+        .arg("-r")
+        .arg(DIFF_DPI.to_string())
+        .arg("-cropbox")
+        .arg("-f")
+        .arg(page.to_string())
+        .arg("-l")
+        .arg(page.to_string())
+        .arg("-singlefile")
+        .arg(pdf_path)
+        .arg(&image_root)
+        .stdin(Stdio::null())
+        .output()
+        .map_err(|e| format!("Failed to run {}: {}", PDF_RASTERIZER, e))?;
+    if !output.status.success() {
+        return Err(format!(
+            "{} failed to render page {}: {}",
+            PDF_RASTERIZER,
+            page,
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+
+    let image_path = scratch_dir.join(format!("{}-{}.ppm", stem, page));
+    let image = fs::read(&image_path)
+        .map_err(|e| format!("Failed to read rendering of page {}: {}", page, e))?;
+    let _ = fs::remove_file(&image_path);
+    Raster::from_ppm(&image)
+}
+
+/// Render the pages of both PDFs in `scratch_dir` and compare them by number, returning
+/// how each page changed and a document of the overlays
+fn diff_renderings(
+    scratch_dir: &Path,
+    current: &[u8],
+    previous: &[u8],
+) -> Result<(Vec<PageDiff>, lopdf::Document), String> {
+    let current_pages =
+        pdf::page_count(current).ok_or_else(|| "Failed to parse the current PDF".to_string())?;
+    let previous_pages =
+        pdf::page_count(previous).ok_or_else(|| "Failed to parse the previous PDF".to_string())?;
+
+    let current_path = scratch_dir.join("current.pdf");
+    let previous_path = scratch_dir.join("previous.pdf");
+    fs::write(&current_path, current).map_err(|e| format!("Failed to write PDF: {}", e))?;
+    fs::write(&previous_path, previous).map_err(|e| format!("Failed to write PDF: {}", e))?;
+
+    let points_per_pixel = 72.0 / DIFF_DPI as f32;
+    let mut diffs = Vec::new();
+    let mut document = pdf::ImageDocument::new();
+    for page in 1..=current_pages.max(previous_pages) {
+        let new = (page <= current_pages)
+            .then(|| render_page(&current_path, page, scratch_dir))
+            .transpose()?;
+        let old = (page <= previous_pages)
+            .then(|| render_page(&previous_path, page, scratch_dir))
+            .transpose()?;
+        let comparison = raster::compare(
+            old.as_ref().unwrap_or(&Raster::default()),
+            new.as_ref().unwrap_or(&Raster::default()),
+        );
+        let overlay = &comparison.overlay;
+
+        let change = match (&old, &new) {
+            (None, _) => PageChange::Added,
+            (_, None) => PageChange::Removed,
+            _ if comparison.changed_pixels == 0 => PageChange::Unchanged,
+            _ => PageChange::Changed,
+        };
+        let pixels = overlay.width * overlay.height;
+        let (width, height) = (
+            overlay.width as f32 * points_per_pixel,
+            overlay.height as f32 * points_per_pixel,
+        );
+        let regions: Vec<PageRegion> = comparison
+            .regions
+            .iter()
+            .map(|region| PageRegion {
+                x: region.x as f32 * points_per_pixel,
+                y: region.y as f32 * points_per_pixel,
+                width: region.width as f32 * points_per_pixel,
+                height: region.height as f32 * points_per_pixel,
+            })
+            .collect();
+        // PDF coordinates run up from the bottom of the page
+        let outlines: Vec<pdf::BBox> = regions
+            .iter()
+            .map(|region| pdf::BBox {
+                x0: region.x,
+                y0: height - region.y - region.height,
+                x1: region.x + region.width,
+                y1: height - region.y,
+            })
+            .collect();
+        document.add_page(overlay, width, height, &outlines)?;
+
+        diffs.push(PageDiff {
+            page,
+            change,
+            changed_fraction: if pixels == 0 {
+                0.0
+            } else {
+                comparison.changed_pixels as f32 / pixels as f32
+            },
+            regions,
+        });
+    }
+
+    Ok((diffs, document.finish()))
+}
+
+/// Compare the built PDF of `file_path` with an earlier build at `previous_pdf_path`,
+/// page by page as rendered by pdftoppm, and write a PDF of the differences to
+/// `output_path`: a page for each page of either build, showing the current page faded
+/// with ink it added in blue, ink it lost in red, and the changed regions outlined.
+/// Pages are paired by number, so a page inserted early on changes every page after it.
+#[tauri::command]
+pub async fn diff_pdf(
+    project_path: String,
+    file_path: String,
+    previous_pdf_path: String,
+    output_path: String,
+) -> Result<Vec<PageDiff>, String> {
+    let pdf_path = find_built_pdf(&project_path, &file_path)?;
+
+    tauri::async_runtime::spawn_blocking(move || {
+        let destination = check_destination(&output_path)?;
+        if !on_path(PDF_RASTERIZER) {
+            return Err(format!(
+                "Comparing PDFs needs {} from poppler, which is not installed",
+                PDF_RASTERIZER
+            ));
+        }
+
+        let current = read_pdf_bytes(&pdf_path)?;
+        let previous = read_pdf_bytes(Path::new(&previous_pdf_path))?;
+
+        let scratch_dir = std::env::temp_dir().join(format!(
+            "incipit-pdf-diff-{}-{}",
+            std::process::id(),
+            PDF_DIFFS.fetch_add(1, Ordering::Relaxed)
+        ));
+        fs::create_dir_all(&scratch_dir)
+            .map_err(|e| format!("Failed to create scratch directory: {}", e))?;
+        let result = diff_renderings(&scratch_dir, &current, &previous);
+        let _ = fs::remove_dir_all(&scratch_dir);
+        let (diffs, mut document) = result?;
+
+        let mut bytes = Vec::new();
+        document
+            .save_to(&mut bytes)
+            .map_err(|e| format!("Failed to write diff PDF: {}", e))?;
+        storage::write_atomic(&destination, bytes)
+            .map_err(|e| format!("Failed to write PDF: {}", e))?;
+
+        Ok(diffs)
+    })
+    .await
+    .map_err(|e| format!("Task join error: {}", e))?
+}
+
+/// The built PDF's bookmarks with the pages they open, e.g. the ones hyperref writes
+/// for each heading
+#[tauri::command]
+pub async fn pdf_bookmarks(
+    project_path: String,
+    file_path: String,
+) -> Result<Vec<pdf::PdfBookmark>, String> {
+    let pdf_path = find_built_pdf(&project_path, &file_path)?;
+
+    tauri::async_runtime::spawn_blocking(move || {
+        let bytes = read_pdf_bytes(&pdf_path)?;
+        let document =
+            lopdf::Document::load_mem(&bytes).map_err(|e| format!("Failed to parse PDF: {}", e))?;
+        Ok(pdf::outline(&document))
+    })
+    .await
+    .map_err(|e| format!("Task join error: {}", e))?
+}
+
+/// The document rooted at `root_file` as plain text, with markup removed and formulas
+/// replaced by a placeholder
+#[tauri::command]
+pub async fn export_plain_text(project_path: String, root_file: String) -> Result<String, String> {
+    prose::plain_text(&PathBuf::from(&project_path), &root_file)
+}
+
+/// Write the document rooted at `root_file` as a web page in `output_dir`: `<root>.html`
+/// with the graphics it shows copied into `<root>_files`. The bibliography comes from
+/// the last build. Returns the path of the page.
+#[tauri::command]
+pub async fn export_html(
+    project_path: String,
+    root_file: String,
+    output_dir: String,
+) -> Result<String, String> {
+    tauri::async_runtime::spawn_blocking(move || {
+        let output_dir = PathBuf::from(&output_dir);
+        if !output_dir.is_absolute() {
+            return Err(format!(
+                "Output directory must be an absolute path: {}",
+                output_dir.display()
+            ));
+        }
+        if !output_dir.is_dir() {
+            return Err(format!(
+                "Directory does not exist: {}",
+                output_dir.display()
+            ));
+        }
+
+        let project_dir = PathBuf::from(&project_path);
+        let root_file = latex::project_file(&root_file)?;
+        let stem = Path::new(&root_file)
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().into_owned())
+            .unwrap_or_else(|| "document".to_string());
+        let bbl_name = output_name(&read_project_meta(&project_dir)?, &root_file)? + ".bbl";
+        let bibliography = fs::read_to_string(build_dir(&project_dir)?.join(bbl_name)).ok();
+
+        let assets_dir = format!("{}_files", stem);
+        let document = html::render(
+            &project_dir,
+            &root_file,
+            &assets_dir,
+            bibliography.as_deref(),
+        )?;
+
+        for (source, destination) in &document.assets {
+            let destination = output_dir.join(destination);
+            if let Some(parent) = destination.parent() {
+                fs::create_dir_all(parent)
+                    .map_err(|e| format!("Failed to create {}: {}", parent.display(), e))?;
+            }
+            fs::copy(project_dir.join(source), &destination)
+                .map_err(|e| format!("Failed to copy {}: {}", source, e))?;
+        }
+
+        let page = output_dir.join(format!("{}.html", stem));
+        storage::write_atomic(&page, &document.html)
+            .map_err(|e| format!("Failed to write {}: {}", page.display(), e))?;
+        Ok(page.to_string_lossy().into_owned())
+    })
+    .await
+    .map_err(|e| format!("Task join error: {}", e))?
+}
+
+/// Append the headings of `content` from byte `from` on to `headings`, as written, with
+/// those of included files where TeX reads them
+fn collect_headings(
+    project_dir: &Path,
+    content: &str,
+    from: usize,
+    visited: &mut HashSet<String>,
+    headings: &mut Vec<String>,
+) {
+    let masked = latex::mask_comments(content);
+    for command in latex::commands(&masked).filter(|c| c.start >= from) {
+        if latex::SECTIONING_COMMANDS.contains(&command.name) {
+            // Star, short title and title exactly as in the source, commands included
+            let end = latex::skip_args(&masked, command.end, 1);
+            headings.push(content[command.start..end].to_string());
+        } else if MATTER_COMMANDS.contains(&command.name) {
+            headings.push(content[command.start..command.end].to_string());
+        } else if latex::INCLUDE_COMMANDS.contains(&command.name) {
+            let args = latex::read_args(&masked, command.end, 1);
+            let Some(child) = latex::first_required(&args)
+                .and_then(|target| latex::resolve_tex_input(project_dir, &target.text))
+            else {
+                continue;
+            };
+            if !visited.insert(child.clone()) {
+                continue;
+            }
+            if let Ok(child_content) = latex::read_source(project_dir, &child) {
+                collect_headings(project_dir, &child_content, 0, visited, headings);
+            }
+        }
+    }
+}
+
+/// A new document with the root file's preamble and only the part, chapter and section
+/// headings of the document, in order and as written, with empty bodies
+#[tauri::command]
+pub async fn export_outline_skeleton(
+    project_path: String,
+    root_file: String,
+) -> Result<String, String> {
+    let project_dir = PathBuf::from(&project_path);
+    let root_file = latex::project_file(&root_file)?;
+    let content = latex::read_source(&project_dir, &root_file)?;
+
+    let masked = latex::mask_comments(&content);
+    let begin = latex::find_begin_document(&masked)
+        .ok_or_else(|| format!("{} has no \\begin{{document}}", root_file))?;
+    let body = latex::skip_args(&masked, begin + "\\begin".len(), 1);
+
+    let mut visited = HashSet::from([root_file]);
+    let mut headings = Vec::new();
+    let end = latex::find_end_document(&masked)
+        .filter(|end| *end >= body)
+        .unwrap_or(content.len());
+    collect_headings(
+        &project_dir,
+        &content[..end],
+        body,
+        &mut visited,
+        &mut headings,
+    );
+
+    // The preamble defines whatever commands the titles use
+    let mut skeleton = content[..begin].trim_end().to_string();
+    skeleton.push_str("\n\n\\begin{document}\n\n");
+    for heading in headings {
+        skeleton.push_str(&heading);
+        skeleton.push_str("\n\n");
+    }
+    skeleton.push_str("\\end{document}\n");
+    Ok(skeleton)
+}
+
+/// Contents of the file included by the \input, \include or \subfile on `line`
+/// (1-based) of `file_path`, for the editor to inline or preview. The target resolves
+/// as for the compile, with `.tex` tried first.
+#[tauri::command]
+pub async fn expand_include_at(
+    project_path: String,
+    file_path: String,
+    line: usize,
+) -> Result<String, String> {
+    let project_dir = PathBuf::from(&project_path);
+    let file_path = latex::project_file(&file_path)?;
+    let content = latex::read_source(&project_dir, &file_path)?;
+
+    let lines = LineIndex::new(&content);
+    let (target, _) = latex::include_targets(&content)
+        .into_iter()
+        .find(|(_, offset)| lines.line(*offset) == line)
+        .ok_or_else(|| format!("Line {} has no \\input, \\include or \\subfile", line))?;
+
+    let child = latex::resolve_tex_input(&project_dir, &target)
+        .ok_or_else(|| format!("Included file not found: {}", target))?;
+    latex::read_source(&project_dir, &child)
+}
@@ -0,0 +1,967 @@
+use serde::{Deserialize, Serialize};
+use std::ops::Range;
+use std::path::Path;
+
+use super::settings::{read_global_settings, EditorSettings, LineEnding};
+use crate::latex;
+
+/// Payload of the `file-reflowed` event, sent when save_file re-wraps or cleans up what
+/// it was given so the editor can replace its buffer with what went to disk
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReflowedFile {
+    pub project_path: String,
+    pub file_path: String,
+    pub content: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum IndentStyle {
+    Spaces,
+    Tabs,
+}
+
+/// Rewrite the leading whitespace of `line` in `to`, keeping its visual width. The rest
+/// of the line is untouched.
+fn reindent_line(line: &str, to: IndentStyle, width: usize) -> String {
+    let text = line.trim_start_matches([' ', '\t']);
+    let indent = &line[..line.len() - text.len()];
+
+    let column = indent.chars().fold(0, |column, c| match c {
+        '\t' => (column / width + 1) * width,
+        _ => column + 1,
+    });
+
+    let indent = match to {
+        IndentStyle::Spaces => " ".repeat(column),
+        IndentStyle::Tabs => "\t".repeat(column / width) + &" ".repeat(column % width),
+    };
+    indent + text
+}
+
+/// Re-indent a file with tabs or spaces, `width` columns per tab, and return the new
+/// content for the editor to apply. Verbatim environments are left exactly as written.
+#[tauri::command]
+pub async fn convert_indentation(
+    project_path: String,
+    file_path: String,
+    to: IndentStyle,
+    width: u32,
+) -> Result<String, String> {
+    if width == 0 {
+        return Err("Indentation width must be at least 1".to_string());
+    }
+    let file_path = latex::project_file(&file_path)?;
+    let content = latex::read_source(Path::new(&project_path), &file_path)?;
+
+    let verbatim = latex::verbatim_lines(&content);
+    let lines: Vec<String> = content
+        .split('\n')
+        .zip(verbatim)
+        .map(|(line, verbatim)| {
+            if verbatim {
+                line.to_string()
+            } else {
+                reindent_line(line, to, width as usize)
+            }
+        })
+        .collect();
+
+    Ok(lines.join("\n"))
+}
+
+/// Environments whose lines are running text that can be re-wrapped. Anything else,
+/// tables and display math included, is left as written.
+const PROSE_ENVIRONMENTS: &[&str] = &[
+    "document",
+    "abstract",
+    "quote",
+    "quotation",
+    "itemize",
+    "enumerate",
+    "description",
+    "minipage",
+];
+
+/// Commands that can start a line of running text without ending the paragraph
+const INLINE_COMMANDS: &[&str] = &[
+    "emph",
+    "textbf",
+    "textit",
+    "texttt",
+    "textsc",
+    "textsf",
+    "textrm",
+    "underline",
+    "cite",
+    "citep",
+    "citet",
+    "ref",
+    "eqref",
+    "cref",
+    "Cref",
+    "autoref",
+    "footnote",
+    "url",
+    "href",
+    "gls",
+    "ac",
+];
+
+/// Commands whose line breaks or arguments matter, so lines holding them are never joined
+const BREAK_SENSITIVE_COMMANDS: &[&str] = &[
+    "begin",
+    "end",
+    "item",
+    "par",
+    "verb",
+    "lstinline",
+    "mintinline",
+    "newline",
+    "linebreak",
+    "newpage",
+    "clearpage",
+];
+
+/// Byte offsets of the unescaped occurrences of `pattern` in `line`
+fn unescaped<'a>(line: &'a str, pattern: &'a str) -> impl Iterator<Item = usize> + 'a {
+    line.match_indices(pattern)
+        .map(|(i, _)| i)
+        .filter(|&i| !latex::is_escaped(line.as_bytes(), i))
+}
+
+/// Whether a line inside running text can be joined with its neighbours. Forced breaks,
+/// alignment, comments and lines opening with a command all keep their line.
+fn is_prose_line(line: &str) -> bool {
+    let text = line.trim();
+    if text.is_empty() || latex::strip_comment(line).len() != line.len() {
+        return false;
+    }
+    if unescaped(text, "\\\\").next().is_some() || unescaped(text, "&").next().is_some() {
+        return false;
+    }
+    if latex::commands(text).any(|c| BREAK_SENSITIVE_COMMANDS.contains(&c.name)) {
+        return false;
+    }
+    match latex::commands(text).next() {
+        Some(c) if c.start == 0 => INLINE_COMMANDS.contains(&c.name),
+        _ => !text.starts_with('\\'),
+    }
+}
+
+/// Follow display math delimiters over `line`, returning whether it has any
+fn track_display_math(line: &str, in_math: &mut bool) -> bool {
+    let code = latex::strip_comment(line);
+    let mut delimiters: Vec<(usize, &str)> = ["\\[", "\\]", "$$"]
+        .iter()
+        .flat_map(|d| unescaped(code, d).map(move |i| (i, *d)))
+        .collect();
+    delimiters.sort();
+    for (_, delimiter) in &delimiters {
+        *in_math = match *delimiter {
+            "\\[" => true,
+            "\\]" => false,
+            _ => !*in_math,
+        };
+    }
+    !delimiters.is_empty()
+}
+
+/// Update the open environments from the `\begin` and `\end` on `line`
+fn track_environments(line: &str, stack: &mut Vec<String>) {
+    let code = latex::strip_comment(line);
+    for command in latex::commands(code) {
+        if command.name != "begin" && command.name != "end" {
+            continue;
+        }
+        let args = latex::read_args(code, command.end, 1);
+        let Some(name) = latex::first_required(&args).map(|a| a.text.trim().to_string()) else {
+            continue;
+        };
+        if command.name == "begin" {
+            stack.push(name);
+        } else if let Some(open) = stack.iter().rposition(|e| *e == name) {
+            stack.truncate(open);
+        }
+    }
+}
+
+/// Fill `paragraph` greedily to `width` columns, keeping the first line's indent
+fn fill(paragraph: &[&str], width: usize) -> Vec<String> {
+    let first = paragraph[0];
+    let indent = &first[..first.len() - first.trim_start().len()];
+
+    let mut lines = Vec::new();
+    let mut current = String::new();
+    for word in paragraph.iter().flat_map(|line| line.split_whitespace()) {
+        if !current.is_empty() && current.chars().count() + 1 + word.chars().count() > width {
+            lines.push(std::mem::take(&mut current));
+        }
+        if current.is_empty() {
+            current.push_str(indent);
+        } else {
+            current.push(' ');
+        }
+        current.push_str(word);
+    }
+    lines.push(current);
+    lines
+}
+
+/// Re-wrap the prose paragraphs of `content` that have a line longer than `width`.
+/// Verbatim, tables, math, the preamble and any line whose breaks matter keep their
+/// layout; other paragraphs are left alone.
+pub(crate) fn reflow(content: &str, width: usize) -> String {
+    let crlf = content.contains("\r\n");
+    let lines: Vec<&str> = content
+        .split('\n')
+        .map(|line| line.strip_suffix('\r').unwrap_or(line))
+        .collect();
+    let verbatim = latex::verbatim_lines(content);
+
+    // Without \begin{document} the file is a fragment included into the body
+    let mut in_body = latex::find_begin_document(&latex::mask_comments(content)).is_none();
+    let mut stack: Vec<String> = Vec::new();
+    let mut in_math = false;
+
+    let mut output: Vec<String> = Vec::with_capacity(lines.len());
+    let mut paragraph: Vec<&str> = Vec::new();
+    let flush = |paragraph: &mut Vec<&str>, output: &mut Vec<String>| {
+        if paragraph.iter().any(|line| line.chars().count() > width) {
+            output.extend(fill(paragraph, width));
+        } else {
+            output.extend(paragraph.iter().map(|line| line.to_string()));
+        }
+        paragraph.clear();
+    };
+
+    for (line, verbatim) in lines.iter().zip(verbatim) {
+        let mut prose = false;
+        if !verbatim {
+            let was_math = in_math;
+            let math = track_display_math(line, &mut in_math);
+            track_environments(line, &mut stack);
+            if stack.iter().any(|e| e == "document") {
+                in_body = true;
+            }
+            prose = in_body
+                && !was_math
+                && !math
+                && stack
+                    .iter()
+                    .all(|e| PROSE_ENVIRONMENTS.contains(&e.as_str()))
+                && is_prose_line(line);
+        }
+
+        if prose {
+            paragraph.push(line);
+        } else {
+            flush(&mut paragraph, &mut output);
+            output.push(line.to_string());
+        }
+    }
+    flush(&mut paragraph, &mut output);
+
+    output.join(if crlf { "\r\n" } else { "\n" })
+}
+
+/// Remove the spaces and tabs ending `line`, except one after a lone backslash, where
+/// `\ ` is a control space
+fn trim_trailing(line: &str) -> &str {
+    let text = line.trim_end_matches([' ', '\t']);
+    if text.len() < line.len() && latex::is_escaped(line.as_bytes(), text.len()) {
+        &line[..text.len() + 1]
+    } else {
+        text
+    }
+}
+
+/// Apply the cleanups `settings` turns on to a file's content. Line endings are
+/// normalized first so the other steps see plain lines, trailing whitespace is trimmed
+/// before reflowing so it can't make a line look too long, and the chosen line ending
+/// goes back on last. Verbatim lines of .tex files are never trimmed.
+pub(crate) fn format_content(content: &str, file_path: &str, settings: &EditorSettings) -> String {
+    let crlf = match settings.line_ending {
+        Some(ending) => ending == LineEnding::Crlf,
+        None => content.contains("\r\n"),
+    };
+    let mut content = content.replace("\r\n", "\n");
+    let tex = file_path.ends_with(".tex");
+
+    if settings.trim_trailing_whitespace {
+        let verbatim = if tex {
+            latex::verbatim_lines(&content)
+        } else {
+            Vec::new()
+        };
+        let lines: Vec<&str> = content
+            .split('\n')
+            .enumerate()
+            .map(|(i, line)| {
+                if verbatim.get(i).copied().unwrap_or(false) {
+                    line
+                } else {
+                    trim_trailing(line)
+                }
+            })
+            .collect();
+        content = lines.join("\n");
+    }
+
+    let reflow_width = settings.reflow_width.unwrap_or(0);
+    if reflow_width > 0 && tex {
+        content = reflow(&content, reflow_width as usize);
+    }
+
+    if settings.insert_final_newline && !content.is_empty() && !content.ends_with('\n') {
+        content.push('\n');
+    }
+
+    if crlf {
+        content.replace('\n', "\r\n")
+    } else {
+        content
+    }
+}
+
+/// The file as saving it would leave it: the editor settings' enabled cleanups applied
+/// to its content on disk, without writing anything back
+#[tauri::command]
+pub async fn apply_formatting(project_path: String, file_path: String) -> Result<String, String> {
+    let file_path = latex::project_file(&file_path)?;
+    let content = latex::read_source(Path::new(&project_path), &file_path)?;
+    let settings = read_global_settings()?.editor_settings;
+    Ok(format_content(&content, &file_path, &settings))
+}
+
+/// Commands whose arguments are text fix_typography may change. The arguments of any
+/// other command, e.g. labels, keys, URLs and file names, are left as written.
+const TEXT_ARGUMENT_COMMANDS: &[&str] = &[
+    "emph",
+    "textbf",
+    "textit",
+    "textsl",
+    "textsc",
+    "textsf",
+    "textrm",
+    "underline",
+    "footnote",
+    "caption",
+    "title",
+    "part",
+    "chapter",
+    "section",
+    "subsection",
+    "subsubsection",
+    "paragraph",
+    "subparagraph",
+];
+
+/// Environments whose content fix_typography leaves alone besides verbatim ones: math,
+/// and content that is code rather than prose
+const TYPOGRAPHY_SKIPPED_ENVIRONMENTS: &[&str] = &[
+    "equation",
+    "equation*",
+    "align",
+    "align*",
+    "alignat",
+    "alignat*",
+    "gather",
+    "gather*",
+    "multline",
+    "multline*",
+    "flalign",
+    "flalign*",
+    "eqnarray",
+    "eqnarray*",
+    "displaymath",
+    "math",
+    "tikzpicture",
+];
+
+/// Offset just past the first unescaped `delimiter` at or after `from`, or the end
+fn past_closing(masked: &str, from: usize, delimiter: &str) -> usize {
+    let mut search = from;
+    while let Some(i) = masked.get(search..).and_then(|rest| rest.find(delimiter)) {
+        let at = search + i;
+        if delimiter.starts_with('\\') || !latex::is_escaped(masked.as_bytes(), at) {
+            return at + delimiter.len();
+        }
+        search = at + delimiter.len();
+    }
+    masked.len()
+}
+
+/// For each byte of `content`, whether fix_typography must leave it alone: the
+/// preamble, comments, verbatim, math, command names and the arguments of commands
+/// that don't take text
+fn protected_bytes(content: &str) -> Vec<bool> {
+    let masked = latex::mask_comments(content);
+    let bytes = masked.as_bytes();
+    let mut protected: Vec<bool> = content
+        .bytes()
+        .zip(masked.bytes())
+        .map(|(original, masked)| original != masked)
+        .collect();
+    let protect = |range: Range<usize>, protected: &mut Vec<bool>| {
+        for flag in &mut protected[range.start..range.end.min(bytes.len())] {
+            *flag = true;
+        }
+    };
+
+    if let Some(begin) = latex::find_begin_document(&masked) {
+        let start = latex::skip_args(&masked, begin + "\\begin".len(), 1);
+        protect(0..start, &mut protected);
+    }
+    let mut line_start = 0;
+    for (line, verbatim) in content.split('\n').zip(latex::verbatim_lines(content)) {
+        if verbatim {
+            protect(line_start..line_start + line.len(), &mut protected);
+        }
+        line_start += line.len() + 1;
+    }
+
+    let mut i = 0;
+    while i < bytes.len() {
+        if protected[i] {
+            i += 1;
+            continue;
+        }
+        let end = match bytes[i] {
+            b'$' if bytes.get(i + 1) == Some(&b'$') => past_closing(&masked, i + 2, "$$"),
+            b'$' => past_closing(&masked, i + 1, "$"),
+            b'\\' => match bytes.get(i + 1) {
+                Some(b'(') => past_closing(&masked, i + 2, "\\)"),
+                Some(b'[') => past_closing(&masked, i + 2, "\\]"),
+                Some(b) if !b.is_ascii_alphabetic() => {
+                    // A control symbol such as an accent, \" or \'
+                    let next = masked[i + 1..].chars().next().map_or(0, char::len_utf8);
+                    i + 1 + next
+                }
+                // A backslash ends the content, e.g. while it's still being typed
+                None => i + 1,
+                Some(_) => {
+                    let command = latex::commands(&masked[i..]).next().unwrap();
+                    let name = command.name;
+                    let end = i + command.end;
+                    match name {
+                        "verb" => match masked[end..].chars().next() {
+                            Some(delimiter) => {
+                                let from = end + delimiter.len_utf8();
+                                masked[from..]
+                                    .find(delimiter)
+                                    .map_or(masked.len(), |j| from + j + delimiter.len_utf8())
+                            }
+                            None => end,
+                        },
+                        "begin" => {
+                            let args = latex::read_args(&masked, end, 1);
+                            let environment = latex::first_required(&args)
+                                .map(|a| a.text.trim().to_string())
+                                .unwrap_or_default();
+                            let closing = format!("\\end{{{}}}", environment);
+                            if latex::VERBATIM_ENVIRONMENTS.contains(&environment.as_str()) {
+                                content[end..]
+                                    .find(&closing)
+                                    .map_or(content.len(), |j| end + j + closing.len())
+                            } else if TYPOGRAPHY_SKIPPED_ENVIRONMENTS
+                                .contains(&environment.as_str())
+                            {
+                                past_closing(&masked, end, &closing)
+                            } else {
+                                latex::skip_args(&masked, end, usize::MAX)
+                            }
+                        }
+                        _ if TEXT_ARGUMENT_COMMANDS.contains(&name) => end,
+                        _ => latex::skip_args(&masked, end, usize::MAX),
+                    }
+                }
+            },
+            _ => {
+                i += 1;
+                continue;
+            }
+        };
+        protect(i..end, &mut protected);
+        i = end.max(i + 1);
+    }
+
+    protected
+}
+
+/// Whether a quote after `previous` opens rather than closes
+fn opens_quote(previous: Option<char>) -> bool {
+    previous.is_none_or(|c| c.is_whitespace() || "([{-`~".contains(c))
+}
+
+/// Typeset quotes, ellipses and dashes the LaTeX way. Straight and typographic double
+/// quotes become ``...'', an opening single quote becomes `, three dots or … become
+/// \ldots, a hyphen standing alone between spaces becomes an en dash, and – and —
+/// become -- and ---. Anything already written the LaTeX way comes out unchanged, so
+/// running it twice changes nothing more. An existing `--` is left alone, being an en
+/// dash already, as in Navier--Stokes.
+fn fix_typography_content(content: &str) -> String {
+    let protected = protected_bytes(content);
+    let chars: Vec<(usize, char)> = content.char_indices().collect();
+    let editable = |k: usize| chars.get(k).is_some_and(|(i, _)| !protected[*i]);
+    let at = |k: usize| chars.get(k).map(|(_, c)| *c);
+
+    let mut fixed = String::with_capacity(content.len());
+    let mut k = 0;
+    while k < chars.len() {
+        let c = chars[k].1;
+        if !editable(k) {
+            fixed.push(c);
+            k += 1;
+            continue;
+        }
+        let previous = k.checked_sub(1).and_then(at);
+        let next = at(k + 1);
+
+        match c {
+            '"' | '\u{201C}' | '\u{201D}' => {
+                let opening = match c {
+                    '\u{201C}' => true,
+                    '\u{201D}' => false,
+                    _ => opens_quote(previous),
+                };
+                // Between two letters " is a babel shorthand, e.g. M"adchen
+                if c == '"'
+                    && previous.is_some_and(char::is_alphanumeric)
+                    && next.is_some_and(char::is_alphanumeric)
+                {
+                    fixed.push(c);
+                } else {
+                    fixed.push_str(if opening { "``" } else { "''" });
+                }
+            }
+            '\u{2018}' => fixed.push('`'),
+            '\u{2019}' => fixed.push('\''),
+            '\'' if opens_quote(previous) && next.is_some_and(char::is_alphanumeric) => {
+                // Only with a closing quote later on the line, so a leading apostrophe
+                // as in 'tis stays
+                let closes = (k + 1..chars.len())
+                    .take_while(|&j| at(j) != Some('\n'))
+                    .any(|j| {
+                        at(j) == Some('\'')
+                            && editable(j)
+                            && at(j - 1).is_some_and(char::is_alphanumeric)
+                            && !at(j + 1).is_some_and(char::is_alphanumeric)
+                    });
+                fixed.push(if closes { '`' } else { '\'' });
+            }
+            '.' if previous != Some('.')
+                && at(k + 1) == Some('.')
+                && at(k + 2) == Some('.')
+                && at(k + 3) != Some('.')
+                && editable(k + 1)
+                && editable(k + 2) =>
+            {
+                fixed.push_str("\\ldots");
+                // TeX would swallow a following space or run into a following letter
+                if at(k + 3).is_some_and(|c| c.is_alphabetic() || c.is_whitespace()) {
+                    fixed.push_str("{}");
+                }
+                k += 3;
+                continue;
+            }
+            '\u{2026}' => {
+                fixed.push_str("\\ldots");
+                if next.is_some_and(|c| c.is_alphabetic() || c.is_whitespace()) {
+                    fixed.push_str("{}");
+                }
+            }
+            '-' if previous == Some(' ') && next == Some(' ') => fixed.push_str("--"),
+            '\u{2013}' => fixed.push_str("--"),
+            '\u{2014}' => fixed.push_str("---"),
+            _ => fixed.push(c),
+        }
+        k += 1;
+    }
+    fixed
+}
+
+/// A unified diff of the lines fix_typography changes. Its replacements never add or
+/// remove lines, so every hunk is one line replaced by one.
+fn typography_diff(file_path: &str, before: &str, after: &str) -> String {
+    let mut diff = String::new();
+    for (number, (old, new)) in before.split('\n').zip(after.split('\n')).enumerate() {
+        if old == new {
+            continue;
+        }
+        if diff.is_empty() {
+            diff.push_str(&format!("--- a/{}\n+++ b/{}\n", file_path, file_path));
+        }
+        diff.push_str(&format!(
+            "@@ -{} +{} @@\n-{}\n+{}\n",
+            number + 1,
+            number + 1,
+            old,
+            new
+        ));
+    }
+    diff
+}
+
+/// Fix the quotes, ellipses and dashes of a .tex file's prose (see
+/// fix_typography_content), in `content` or the file on disk when it isn't given.
+/// Returns the fixed content for the editor to apply, or with `dry_run` a unified diff
+/// of the lines it would change, empty when there are none. Nothing is saved.
+#[tauri::command]
+pub async fn fix_typography(
+    project_path: String,
+    file_path: String,
+    content: Option<String>,
+    dry_run: Option<bool>,
+) -> Result<String, String> {
+    let file_path = latex::project_file(&file_path)?;
+    let content = match content {
+        Some(content) => content,
+        None => latex::read_source(Path::new(&project_path), &file_path)?,
+    };
+
+    let fixed = fix_typography_content(&content);
+    if dry_run.unwrap_or(false) {
+        Ok(typography_diff(&file_path, &content, &fixed))
+    } else {
+        Ok(fixed)
+    }
+}
+
+/// Environments align_tables lines up, with the arguments \begin takes before the rows
+/// besides the environment's name
+const ALIGNED_TABLES: &[(&str, usize)] = &[
+    ("tabular", 1),
+    ("tabular*", 2),
+    ("tabularx", 2),
+    ("tabulary", 2),
+    ("longtable", 1),
+    ("array", 1),
+];
+
+/// A line of a table holding one whole row
+struct TableRow<'a> {
+    line: usize,
+    /// Trimmed, each with the columns it spans, more than 1 for a \multicolumn
+    cells: Vec<(&'a str, usize)>,
+    /// What follows the row's `\\`, e.g. `[2pt] \hline`, or None when it has none
+    end: Option<&'a str>,
+    /// The line's `%` comment
+    comment: &'a str,
+}
+
+/// Split the code of a table line into its cells at the top-level `&`, up to a
+/// top-level `\\`, and what follows that (None without one). None for a line that
+/// isn't a row on its own: one leaving a brace or environment open, with a second row
+/// after its `\\`, or with \verb, whose `&` can't be told apart.
+fn split_row(code: &str) -> Option<(Vec<&str>, Option<&str>)> {
+    let bytes = code.as_bytes();
+    let mut depth = 0i32;
+    let mut cells = Vec::new();
+    let mut start = 0;
+    let mut end = None;
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'\\' => {
+                let rest = &code[i..];
+                let word = |name: &str| {
+                    rest.strip_prefix(name)
+                        .is_some_and(|after| !after.starts_with(|c: char| c.is_ascii_alphabetic()))
+                };
+                if rest.starts_with("\\\\") && depth == 0 {
+                    if end.is_some() {
+                        return None;
+                    }
+                    cells.push(code[start..i].trim());
+                    end = Some(i + 2);
+                } else if word("\\begin") {
+                    depth += 1;
+                } else if word("\\end") {
+                    depth -= 1;
+                } else if word("\\verb") || word("\\lstinline") {
+                    return None;
+                }
+                // Past the control symbol, or the first letter of a control word
+                i += 2;
+                continue;
+            }
+            b'{' => depth += 1,
+            b'}' => depth -= 1,
+            b'&' if depth == 0 => {
+                if end.is_some() {
+                    return None;
+                }
+                cells.push(code[start..i].trim());
+                start = i + 1;
+            }
+            _ => {}
+        }
+        if depth < 0 {
+            return None;
+        }
+        i += 1;
+    }
+    if depth != 0 {
+        return None;
+    }
+
+    match end {
+        Some(tail) => Some((cells, Some(code[tail..].trim()))),
+        None => {
+            cells.push(code[start..].trim());
+            Some((cells, None))
+        }
+    }
+}
+
+/// Columns a cell spans: its \multicolumn's count, otherwise 1
+fn cell_span(cell: &str) -> usize {
+    match latex::commands(cell).next() {
+        Some(command) if command.start == 0 && command.name == "multicolumn" => {
+            let args = latex::read_args(cell, command.end, 1);
+            latex::first_required(&args)
+                .and_then(|count| count.text.trim().parse().ok())
+                .filter(|count| *count > 0)
+                .unwrap_or(1)
+        }
+        _ => 1,
+    }
+}
+
+/// The rows of a table among `lines`, its lines between the one \begin's arguments
+/// end on and the one with its \end. A row must be its own line, ending in `\\` unless
+/// it is the last; a line with `&` that isn't one starts a row that goes on over the
+/// lines after it, which are all left alone until that row's `\\`.
+fn table_rows<'a>(lines: &[&'a str], first: usize) -> Vec<TableRow<'a>> {
+    let split: Vec<Option<(Vec<&str>, Option<&str>)>> = lines
+        .iter()
+        .map(|line| split_row(latex::strip_comment(line)))
+        .collect();
+    let is_row = |parts: &Option<(Vec<&str>, Option<&str>)>| match parts {
+        Some((cells, end)) => cells.len() > 1 || end.is_some(),
+        None => true,
+    };
+
+    let mut rows = Vec::new();
+    let mut open_row = false;
+    for (i, line) in lines.iter().enumerate() {
+        let code = latex::strip_comment(line);
+        if code.trim().is_empty() {
+            continue;
+        }
+        let ends_row = unescaped(code, "\\\\").next().is_some();
+        if open_row {
+            open_row = !ends_row;
+            continue;
+        }
+        let Some((cells, end)) = &split[i] else {
+            open_row = !ends_row;
+            continue;
+        };
+        if end.is_none() && cells.len() > 1 && split[i + 1..].iter().any(is_row) {
+            open_row = true;
+            continue;
+        }
+        if !is_row(&split[i]) || (cells.len() == 1 && cells[0].is_empty()) {
+            continue;
+        }
+        rows.push(TableRow {
+            line: first + i,
+            cells: cells.iter().map(|cell| (*cell, cell_span(cell))).collect(),
+            end: *end,
+            comment: &line[code.len()..],
+        });
+    }
+    rows
+}
+
+/// A table's rows laid out with each column as wide as its widest cell, indented like
+/// the first. A \multicolumn that doesn't fit the columns it spans widens the last.
+fn layout_rows(rows: &[TableRow], indent: &str) -> Vec<String> {
+    let mut widths: Vec<usize> = Vec::new();
+    for spanning in [false, true] {
+        for row in rows {
+            let mut column = 0;
+            for (cell, span) in &row.cells {
+                let last = column + span - 1;
+                if widths.len() <= last {
+                    widths.resize(last + 1, 0);
+                }
+                let length = cell.chars().count();
+                if !spanning && *span == 1 {
+                    widths[column] = widths[column].max(length);
+                } else if spanning && *span > 1 {
+                    let room = widths[column..=last].iter().sum::<usize>() + 3 * (span - 1);
+                    widths[last] += length.saturating_sub(room);
+                }
+                column += span;
+            }
+        }
+    }
+
+    rows.iter()
+        .map(|row| {
+            let mut text = indent.to_string();
+            let mut column = 0;
+            for (j, (cell, span)) in row.cells.iter().enumerate() {
+                if j > 0 {
+                    text.push_str(" & ");
+                }
+                let width = widths[column..column + span].iter().sum::<usize>() + 3 * (span - 1);
+                text.push_str(cell);
+                text.extend(std::iter::repeat_n(' ', width - cell.chars().count()));
+                column += span;
+            }
+            match row.end {
+                Some(end) => {
+                    text.push_str(" \\\\");
+                    if !end.is_empty() && !end.starts_with(['[', '*']) {
+                        text.push(' ');
+                    }
+                    text.push_str(end);
+                }
+                None => text.truncate(text.trim_end().len()),
+            }
+            if !row.comment.is_empty() {
+                text.push(' ');
+                text.push_str(row.comment);
+            }
+            text
+        })
+        .collect()
+}
+
+/// Line up the `&` and `\\` of every table in `content` (see align_tables)
+fn align_tables_content(content: &str) -> String {
+    let masked = latex::mask_comments(content);
+    let index = latex::LineIndex::new(&masked);
+    let environments = latex::environments(&masked);
+    let mut lines: Vec<String> = content.split('\n').map(str::to_string).collect();
+    // Compared without a CRLF's carriage return, which goes back on each line written
+    let plain: Vec<&str> = content
+        .split('\n')
+        .map(|line| line.strip_suffix('\r').unwrap_or(line))
+        .collect();
+
+    for table in &environments {
+        let Some(&(_, arguments)) = ALIGNED_TABLES.iter().find(|(name, _)| *name == table.name)
+        else {
+            continue;
+        };
+        let skipped = environments.iter().any(|other| {
+            let name = other.name.as_str();
+            (other.span != table.span
+                && table.span.contains(&other.span.start)
+                && ALIGNED_TABLES.iter().any(|(table, _)| *table == name))
+                || (latex::VERBATIM_ENVIRONMENTS.contains(&name)
+                    && (other.span.contains(&table.span.start)
+                        || table.span.contains(&other.span.start)))
+        });
+        let source = &masked[table.span.clone()];
+        let closing = format!("\\end{{{}}}", table.name);
+        if skipped || !source.ends_with(&closing) {
+            continue;
+        }
+
+        let body_start =
+            latex::skip_args(&masked, table.span.start + "\\begin".len(), arguments + 1);
+        let first = index.line(body_start);
+        let end_line = index.line(table.span.end - closing.len()) - 1;
+        if first >= end_line {
+            continue;
+        }
+        let rows = table_rows(&plain[first..end_line], first);
+        let Some(first_row) = rows.first() else {
+            continue;
+        };
+        let line = plain[first_row.line];
+        let indent = &line[..line.len() - line.trim_start().len()];
+        for (row, text) in rows.iter().zip(layout_rows(&rows, indent)) {
+            let ending = if lines[row.line].ends_with('\r') {
+                "\r"
+            } else {
+                ""
+            };
+            lines[row.line] = text + ending;
+        }
+    }
+    lines.join("\n")
+}
+
+/// Line up the cells of the tabular, tabularx, longtable and array environments in
+/// `content`, or the file on disk when it isn't given, so their `&` and `\\` fall in
+/// columns. Only the spaces around those change, so the table typesets the same, and
+/// aligning it again changes nothing. Rows spread over several lines, tables holding
+/// other tables or \verb, and verbatim are left as written. Returns the new content for
+/// the editor to apply; nothing is saved.
+#[tauri::command]
+pub async fn align_tables(
+    project_path: String,
+    file_path: String,
+    content: Option<String>,
+) -> Result<String, String> {
+    let file_path = latex::project_file(&file_path)?;
+    let content = match content {
+        Some(content) => content,
+        None => latex::read_source(Path::new(&project_path), &file_path)?,
+    };
+    Ok(align_tables_content(&content))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn protected_bytes_handles_trailing_backslash() {
+        let content = "Some text \\";
+        assert_eq!(protected_bytes(content).len(), content.len());
+    }
+
+    #[test]
+    fn fix_typography_keeps_trailing_backslash() {
+        assert_eq!(fix_typography_content("Some text \\"), "Some text \\");
+    }
+
+    const TABLE: &str = "\\begin{tabular}{lll}
+  a & bb & c \\\\ \\hline
+  dddd & e & {f & g} \\\\
+  \\multicolumn{2}{c}{h & i} & j \\\\
+  k \\& l & m & n
+\\end{tabular}
+";
+
+    #[test]
+    fn align_tables_lines_up_cells() {
+        // No cell splits at an `&` in braces, in \multicolumn's text, or escaped as `\&`
+        assert_eq!(
+            align_tables_content(TABLE),
+            "\\begin{tabular}{lll}
+  a      & bb               & c       \\\\ \\hline
+  dddd   & e                & {f & g} \\\\
+  \\multicolumn{2}{c}{h & i} & j       \\\\
+  k \\& l & m                & n
+\\end{tabular}
+"
+        );
+    }
+
+    #[test]
+    fn align_tables_is_idempotent() {
+        let aligned = align_tables_content(TABLE);
+        assert_eq!(align_tables_content(&aligned), aligned);
+    }
+
+    #[test]
+    fn align_tables_leaves_verbatim_alone() {
+        let content = "\\begin{verbatim}
+\\begin{tabular}{ll}
+  a & bb \\\\
+  ccc & d \\\\
+\\end{tabular}
+\\end{verbatim}
+";
+        assert_eq!(align_tables_content(content), content);
+    }
+}
@@ -0,0 +1,262 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+use std::process::{Command, Stdio};
+
+use crate::{latex, storage};
+
+/// What a LaTeX project shouldn't commit: the build output, incipit's trash and the
+/// files TeX and its tools write next to the sources
+pub(crate) const GITIGNORE_PATTERNS: &[&str] = &[
+    "build/",
+    ".incipit-trash/",
+    "*.aux",
+    "*.log",
+    "*.out",
+    "*.toc",
+    "*.lof",
+    "*.lot",
+    "*.fls",
+    "*.fdb_latexmk",
+    "*.synctex.gz",
+    "*.synctex(busy)",
+    "*.bbl",
+    "*.blg",
+    "*.bcf",
+    "*.run.xml",
+    "*.idx",
+    "*.ilg",
+    "*.ind",
+    "*.nav",
+    "*.snm",
+    "*.vrb",
+    "*.xdv",
+    "_minted*/",
+];
+
+/// Heading of the block of patterns ensure_gitignore adds
+const GITIGNORE_HEADING: &str = "# LaTeX build files";
+
+/// The hash git blame gives lines that differ from the last commit
+const UNCOMMITTED_HASH: &str = "0000000000000000000000000000000000000000";
+
+/// Who last changed a line of a file, according to git blame
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LineBlame {
+    /// 1-based line in the file as it is on disk
+    pub line: usize,
+    /// None for lines not committed yet
+    pub commit: Option<String>,
+    pub author: Option<String>,
+    /// Author date, RFC 3339 in the author's time zone
+    pub date: Option<String>,
+    /// First line of the commit message
+    pub summary: Option<String>,
+}
+
+#[derive(Default, Clone)]
+struct CommitInfo {
+    author: Option<String>,
+    time: Option<i64>,
+    tz: Option<String>,
+    summary: Option<String>,
+}
+
+impl CommitInfo {
+    fn date(&self) -> Option<String> {
+        let tz = self.tz.as_deref()?;
+        let sign = if tz.starts_with('-') { -1 } else { 1 };
+        let hours: i32 = tz.get(1..3)?.parse().ok()?;
+        let minutes: i32 = tz.get(3..5)?.parse().ok()?;
+        let offset = chrono::FixedOffset::east_opt(sign * (hours * 3600 + minutes * 60))?;
+        let time = chrono::DateTime::from_timestamp(self.time?, 0)?;
+        Some(time.with_timezone(&offset).to_rfc3339())
+    }
+}
+
+/// Parse `git blame --porcelain` output. Each line's entry starts with a header naming
+/// its commit; the commit's details follow only the first time it appears, and the
+/// line's content comes last, prefixed with a tab.
+fn parse_porcelain(output: &str) -> Vec<LineBlame> {
+    let mut commits: HashMap<String, CommitInfo> = HashMap::new();
+    let mut blame = Vec::new();
+    let mut current: Option<(String, usize)> = None;
+
+    for line in output.lines() {
+        if line.starts_with('\t') {
+            let Some((hash, final_line)) = current.take() else {
+                continue;
+            };
+            let info = commits.get(&hash).cloned().unwrap_or_default();
+            let committed = hash != UNCOMMITTED_HASH;
+            blame.push(LineBlame {
+                line: final_line,
+                date: committed.then(|| info.date()).flatten(),
+                author: info.author.filter(|_| committed),
+                summary: info.summary.filter(|_| committed),
+                commit: committed.then_some(hash),
+            });
+            continue;
+        }
+
+        if current.is_none() {
+            let mut fields = line.split(' ');
+            let hash = fields.next().unwrap_or_default();
+            let final_line = fields.nth(1).and_then(|n| n.parse().ok());
+            if let Some(final_line) = final_line.filter(|_| hash.len() == UNCOMMITTED_HASH.len()) {
+                current = Some((hash.to_string(), final_line));
+            }
+            continue;
+        }
+
+        let Some((hash, _)) = &current else {
+            continue;
+        };
+        let info = commits.entry(hash.clone()).or_default();
+        let (key, value) = line.split_once(' ').unwrap_or((line, ""));
+        match key {
+            "author" => info.author = Some(value.to_string()),
+            "author-time" => info.time = value.parse().ok(),
+            "author-tz" => info.tz = Some(value.to_string()),
+            "summary" => info.summary = Some(value.to_string()),
+            _ => {}
+        }
+    }
+
+    blame
+}
+
+/// Whether `dir` is inside a git work tree, false as well when git isn't installed
+fn in_work_tree(dir: &Path) -> bool {
+    Command::new("git")
+        .args(["rev-parse", "--is-inside-work-tree"])
+        .current_dir(dir)
+        .stdin(Stdio::null())
+        .output()
+        .is_ok_and(|output| output.status.success())
+}
+
+/// The commit, author and date that last changed each line of `file_path`, for a blame
+/// gutter. Empty when the project isn't in a git repository or git isn't installed; a
+/// file git doesn't track yet has every line uncommitted.
+#[tauri::command]
+pub async fn git_blame(project_path: String, file_path: String) -> Result<Vec<LineBlame>, String> {
+    let file_path = latex::project_file(&file_path)?;
+
+    tauri::async_runtime::spawn_blocking(move || {
+        let project_dir = Path::new(&project_path);
+        if !in_work_tree(project_dir) {
+            return Ok(Vec::new());
+        }
+
+        let tracked = Command::new("git")
+            .args(["ls-files", "--error-unmatch", "--", &file_path])
+            .current_dir(project_dir)
+            .stdin(Stdio::null())
+            .output()
+            .is_ok_and(|output| output.status.success());
+        if !tracked {
+            let content = latex::read_source(project_dir, &file_path)?;
+            return Ok((1..=content.lines().count())
+                .map(|line| LineBlame {
+                    line,
+                    commit: None,
+                    author: None,
+                    date: None,
+                    summary: None,
+                })
+                .collect());
+        }
+
+        let output = Command::new("git")
+            .args(["blame", "--porcelain", "--", &file_path])
+            .current_dir(project_dir)
+            .stdin(Stdio::null())
+            .output()
+            .map_err(|e| format!("Failed to run git blame: {}", e))?;
+        if !output.status.success() {
+            return Err(format!(
+                "git blame failed: {}",
+                String::from_utf8_lossy(&output.stderr).trim()
+            ));
+        }
+
+        Ok(parse_porcelain(&String::from_utf8_lossy(&output.stdout)))
+    })
+    .await
+    .map_err(|e| format!("Task join error: {}", e))?
+}
+
+/// A .gitignore pattern as written, without the slashes that don't change what it
+/// matches for our purposes: `build`, `/build` and `build/` all keep build/ out
+fn gitignore_key(pattern: &str) -> &str {
+    pattern.trim().trim_start_matches('/').trim_end_matches('/')
+}
+
+/// Add the GITIGNORE_PATTERNS missing from the project's .gitignore, creating it if
+/// there is none, and return the ones added. The user's own rules are kept as they are:
+/// the new patterns go at the end, or before the first `!` pattern so what the user
+/// chose to keep stays unignored.
+#[tauri::command]
+pub async fn ensure_gitignore(project_path: String) -> Result<Vec<String>, String> {
+    let path = Path::new(&project_path).join(".gitignore");
+    let existing = match fs::read_to_string(&path) {
+        Ok(content) => content,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => String::new(),
+        Err(e) => return Err(format!("Failed to read .gitignore: {}", e)),
+    };
+    let newline = if existing.contains("\r\n") {
+        "\r\n"
+    } else {
+        "\n"
+    };
+
+    let present: Vec<&str> = existing
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#') && !line.starts_with('!'))
+        .map(gitignore_key)
+        .collect();
+    let added: Vec<String> = GITIGNORE_PATTERNS
+        .iter()
+        .filter(|pattern| !present.contains(&gitignore_key(pattern)))
+        .map(|pattern| pattern.to_string())
+        .collect();
+    if added.is_empty() {
+        return Ok(added);
+    }
+
+    let mut block = String::new();
+    for line in std::iter::once(GITIGNORE_HEADING).chain(added.iter().map(String::as_str)) {
+        block.push_str(line);
+        block.push_str(newline);
+    }
+
+    let mut lines: Vec<&str> = existing.split_inclusive('\n').collect();
+    let content = match lines
+        .iter()
+        .position(|line| line.trim_start().starts_with('!'))
+    {
+        Some(index) => {
+            block.push_str(newline);
+            lines.insert(index, &block);
+            lines.concat()
+        }
+        None => {
+            let mut content = existing.clone();
+            if !content.is_empty() && !content.ends_with('\n') {
+                content.push_str(newline);
+            }
+            if !content.trim().is_empty() {
+                content.push_str(newline);
+            }
+            content.push_str(&block);
+            content
+        }
+    };
+
+    storage::write_atomic(&path, content)
+        .map_err(|e| format!("Failed to write .gitignore: {}", e))?;
+    Ok(added)
+}
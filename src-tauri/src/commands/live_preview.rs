@@ -0,0 +1,335 @@
+use notify::{RecommendedWatcher, RecursiveMode, Watcher};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tauri::{AppHandle, Emitter};
+
+use super::analysis::asset_dependencies;
+use super::compilation::CompileResult;
+use super::compile_on_save::queue_compile;
+use super::project::{build_dir, content_hash, output_name, read_pdf_bytes, read_project_meta};
+use crate::latex;
+
+/// The build being kept up to date, with the files it was last seen to read
+struct LivePreview {
+    project_path: String,
+    root_file: String,
+    /// Project-relative paths whose changes trigger a compile
+    watched: HashSet<String>,
+    /// Any .tex file counts, as in a Tectonic.toml project
+    all_tex: bool,
+    _watcher: RecommendedWatcher,
+}
+
+static LIVE_PREVIEW: Mutex<Option<LivePreview>> = Mutex::new(None);
+
+/// Files the build of `root_file` depends on: every source it includes, the paths
+/// includes that don't exist yet would resolve to, and `input_files` from its last
+/// compile, which covers images, bibliographies and local packages
+fn watch_set(project_dir: &Path, root_file: &str, input_files: &[String]) -> HashSet<String> {
+    let mut watched: HashSet<String> = input_files
+        .iter()
+        .filter(|path| !path.starts_with("build/"))
+        .cloned()
+        .collect();
+    watched.insert(root_file.to_string());
+
+    let Ok(sources) = latex::load_sources(project_dir, root_file) else {
+        return watched;
+    };
+    for source in sources {
+        for (target, _) in latex::include_targets(&source.content) {
+            if latex::resolve_tex_input(project_dir, &target).is_some() {
+                continue;
+            }
+            // Creating the missing file should rebuild, whichever name TeX would try
+            if let Some(relative) = latex::normalize_relative(Path::new(target.trim_matches('"'))) {
+                let path = latex::to_project_path(&relative);
+                watched.insert(format!("{}.tex", path));
+                watched.insert(path);
+            }
+        }
+        watched.insert(source.path);
+    }
+
+    watched
+}
+
+/// The files a watcher on the build of `root_file` should cover, worked out from the
+/// sources alone so it can be set up before the first compile: the sources in the order
+/// TeX reads them and their assets, as list_asset_dependencies finds them, then the
+/// paths includes that don't exist yet would resolve to. Project-relative, each once.
+#[tauri::command]
+pub async fn resolve_watch_set(
+    project_path: String,
+    root_file: String,
+) -> Result<Vec<String>, String> {
+    let project_dir = PathBuf::from(&project_path);
+    let root_file = latex::project_file(&root_file)?;
+
+    tauri::async_runtime::spawn_blocking(move || {
+        let mut files = asset_dependencies(&project_dir, &root_file)?;
+        let mut missing: Vec<String> = watch_set(&project_dir, &root_file, &files)
+            .into_iter()
+            .filter(|path| !files.contains(path))
+            .collect();
+        missing.sort();
+        files.extend(missing);
+        Ok(files)
+    })
+    .await
+    .map_err(|e| format!("Task join error: {}", e))?
+}
+
+/// Refresh the watch set after a compile of the live build, so includes added or removed
+/// since it started are picked up. A failed compile keeps the files it last read.
+pub(crate) fn compiled(project_path: &str, root_file: &str, result: Option<&CompileResult>) {
+    let mut live = LIVE_PREVIEW.lock().unwrap();
+    let Some(live) = live
+        .as_mut()
+        .filter(|l| l.project_path == project_path && l.root_file == root_file)
+    else {
+        return;
+    };
+
+    let input_files = match result {
+        Some(result) => result.input_files.clone(),
+        None => live.watched.iter().cloned().collect(),
+    };
+    live.watched = watch_set(Path::new(project_path), root_file, &input_files);
+}
+
+/// Whether a change to `path` should rebuild the live build of `project_dir`
+fn is_watched(project_dir: &Path, path: &Path) -> bool {
+    let Some(relative) = path
+        .strip_prefix(project_dir)
+        .ok()
+        .and_then(latex::normalize_relative)
+    else {
+        return false;
+    };
+    let relative = latex::to_project_path(&relative);
+
+    let live = LIVE_PREVIEW.lock().unwrap();
+    live.as_ref().is_some_and(|live| {
+        Path::new(&live.project_path) == project_dir
+            && (live.watched.contains(&relative)
+                || (live.all_tex && relative.ends_with(".tex") && !relative.starts_with("build/")))
+    })
+}
+
+/// Recompile `root_file` whenever it or any file its build reads changes on disk,
+/// emitting each result as a `compile-finished` event. Replaces any live preview
+/// already running; compiles once straight away so the preview starts current.
+#[tauri::command]
+pub async fn watch_and_compile(
+    app: AppHandle,
+    project_path: String,
+    root_file: String,
+) -> Result<(), String> {
+    let project_dir = PathBuf::from(&project_path);
+    let root_file = latex::project_file(&root_file)?;
+    if !project_dir.join(&root_file).is_file() {
+        return Err(format!("File does not exist: {}", root_file));
+    }
+
+    let callback_app = app.clone();
+    let callback_path = project_path.clone();
+    let callback_dir = project_dir.clone();
+    let callback_root = root_file.clone();
+    let mut watcher = notify::recommended_watcher(move |result: notify::Result<notify::Event>| {
+        let Ok(event) = result else {
+            return;
+        };
+        if event.kind.is_access() || !event.paths.iter().any(|p| is_watched(&callback_dir, p)) {
+            return;
+        }
+        queue_compile(&callback_app, &callback_path, callback_root.clone());
+    })
+    .map_err(|e| format!("Failed to watch project: {}", e))?;
+
+    // Watch the whole tree rather than each file: new includes and files saved by
+    // rename are seen without re-registering anything
+    watcher
+        .watch(&project_dir, RecursiveMode::Recursive)
+        .map_err(|e| format!("Failed to watch project: {}", e))?;
+
+    // Until the first compile reports what it read, go by what the sources reference
+    let referenced = asset_dependencies(&project_dir, &root_file).unwrap_or_default();
+    let live = LivePreview {
+        watched: watch_set(&project_dir, &root_file, &referenced),
+        all_tex: project_dir.join("Tectonic.toml").is_file(),
+        project_path: project_path.clone(),
+        root_file: root_file.clone(),
+        _watcher: watcher,
+    };
+    // Drop the previous watcher outside the lock its callback takes
+    let previous = LIVE_PREVIEW.lock().unwrap().replace(live);
+    drop(previous);
+
+    queue_compile(&app, &project_path, root_file);
+    Ok(())
+}
+
+/// Stop the live preview started by watch_and_compile, if any
+#[tauri::command]
+pub async fn stop_live_preview() -> Result<(), String> {
+    let previous = LIVE_PREVIEW.lock().unwrap().take();
+    drop(previous);
+    Ok(())
+}
+
+/// Quiet time after the last write to the watched PDF before it counts as finished, so
+/// a compile writing it in pieces is reported once
+const PDF_DEBOUNCE: Duration = Duration::from_millis(300);
+
+/// How often the PDF watch thread looks for a settled write
+const PDF_TICK: Duration = Duration::from_millis(100);
+
+/// Payload of the `pdf-updated` event
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PdfUpdated {
+    pub project_path: String,
+    pub file_path: String,
+    /// Hash of the PDF's content, as file_hash computes it, so a viewer that already
+    /// shows this version can skip reloading
+    pub checksum: String,
+}
+
+/// The build PDF watched for writes by anything, including other tools
+struct PdfWatch {
+    project_path: String,
+    file_path: String,
+    build_dir: PathBuf,
+    /// The PDF's file name, plain and gzip-compressed
+    names: [String; 2],
+    /// Checksum of the version last seen; None while there is no PDF
+    checksum: Option<String>,
+    /// When the latest write settles, while one is pending
+    due: Option<Instant>,
+    _watcher: RecommendedWatcher,
+}
+
+static PDF_WATCH: Mutex<Option<PdfWatch>> = Mutex::new(None);
+
+/// Set once the PDF watch thread is running
+static PDF_WATCH_THREAD: Mutex<bool> = Mutex::new(false);
+
+/// Checksum of the PDF as it is now, decompressed if gzipped
+fn pdf_checksum(build_dir: &Path, names: &[String]) -> Option<String> {
+    let pdf_path = names
+        .iter()
+        .map(|name| build_dir.join(name))
+        .find(|path| path.is_file())?;
+    read_pdf_bytes(&pdf_path).ok().map(|pdf| content_hash(&pdf))
+}
+
+fn run_pdf_watch(app: AppHandle) {
+    loop {
+        std::thread::sleep(PDF_TICK);
+        let mut watch = PDF_WATCH.lock().unwrap();
+        let Some(watch) = watch
+            .as_mut()
+            .filter(|w| w.due.is_some_and(|due| Instant::now() >= due))
+        else {
+            continue;
+        };
+        watch.due = None;
+
+        let checksum = pdf_checksum(&watch.build_dir, &watch.names);
+        if checksum == watch.checksum {
+            continue;
+        }
+        watch.checksum = checksum.clone();
+        let Some(checksum) = checksum else {
+            continue;
+        };
+        let updated = PdfUpdated {
+            project_path: watch.project_path.clone(),
+            file_path: watch.file_path.clone(),
+            checksum,
+        };
+        if let Err(e) = app.emit("pdf-updated", updated) {
+            eprintln!("Failed to emit pdf-updated: {}", e);
+        }
+    }
+}
+
+/// Emit `pdf-updated` whenever the built PDF of `file_path` changes on disk, whoever
+/// wrote it: a compile here, latexmk or a script. Writes are reported once they settle
+/// and only when the content changed. Replaces any PDF already being watched.
+#[tauri::command]
+pub async fn watch_pdf(
+    app: AppHandle,
+    project_path: String,
+    file_path: String,
+) -> Result<(), String> {
+    let project_dir = PathBuf::from(&project_path);
+    let file_path = latex::project_file(&file_path)?;
+    let stem = output_name(&read_project_meta(&project_dir)?, &file_path)?;
+    let build_dir = build_dir(&project_dir)?;
+    // The directory is watched, so it has to exist before the first build
+    std::fs::create_dir_all(&build_dir)
+        .map_err(|e| format!("Failed to create build directory: {}", e))?;
+    let names = [format!("{}.pdf", stem), format!("{}.pdf.gz", stem)];
+
+    let callback_dir = build_dir.clone();
+    let callback_names = names.clone();
+    let mut watcher = notify::recommended_watcher(move |result: notify::Result<notify::Event>| {
+        let Ok(event) = result else {
+            return;
+        };
+        let is_pdf = |path: &PathBuf| {
+            path.parent() == Some(callback_dir.as_path())
+                && path
+                    .file_name()
+                    .is_some_and(|name| callback_names.iter().any(|n| name == n.as_str()))
+        };
+        if event.kind.is_access() || !event.paths.iter().any(is_pdf) {
+            return;
+        }
+        let mut watch = PDF_WATCH.lock().unwrap();
+        if let Some(watch) = watch.as_mut().filter(|w| w.build_dir == callback_dir) {
+            watch.due = Some(Instant::now() + PDF_DEBOUNCE);
+        }
+    })
+    .map_err(|e| format!("Failed to watch PDF: {}", e))?;
+
+    // Watch the directory rather than the file: TeX replaces the PDF, and it may not
+    // exist yet
+    watcher
+        .watch(&build_dir, RecursiveMode::NonRecursive)
+        .map_err(|e| format!("Failed to watch PDF: {}", e))?;
+
+    {
+        let mut running = PDF_WATCH_THREAD.lock().unwrap();
+        if !*running {
+            std::thread::spawn(move || run_pdf_watch(app));
+            *running = true;
+        }
+    }
+
+    let watch = PdfWatch {
+        checksum: pdf_checksum(&build_dir, &names),
+        project_path,
+        file_path,
+        build_dir,
+        names,
+        due: None,
+        _watcher: watcher,
+    };
+    // Drop the previous watcher outside the lock its callback takes
+    let previous = PDF_WATCH.lock().unwrap().replace(watch);
+    drop(previous);
+    Ok(())
+}
+
+/// Stop the PDF watch started by watch_pdf, if any
+#[tauri::command]
+pub async fn stop_watching_pdf() -> Result<(), String> {
+    let previous = PDF_WATCH.lock().unwrap().take();
+    drop(previous);
+    Ok(())
+}
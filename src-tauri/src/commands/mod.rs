@@ -1,3 +1,15 @@
+pub mod analysis;
+pub mod autosave;
+pub mod catalog;
 pub mod compilation;
+pub mod compile_on_save;
+pub mod disk_space;
+pub mod export;
+pub mod format;
+pub mod git;
+pub mod live_preview;
 pub mod project;
+pub mod search;
 pub mod settings;
+pub mod snippets;
+pub mod support;
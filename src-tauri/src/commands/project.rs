@@ -1,6 +1,21 @@
+use flate2::read::GzDecoder;
+use globset::{GlobBuilder, GlobSet, GlobSetBuilder};
 use serde::{Deserialize, Serialize};
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, BinaryHeap, HashSet};
 use std::fs;
+use std::io::Read;
 use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::SystemTime;
+use tauri::{AppHandle, Emitter};
+
+use super::format::{format_content, ReflowedFile};
+use super::settings::{read_global_settings, read_user_words, write_user_words};
+use super::{autosave, compile_on_save};
+use crate::diff::{self, DiffHunk};
+use crate::style::StyleRule;
+use crate::{latex, storage};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct FileNode {
@@ -9,13 +24,358 @@ pub struct FileNode {
     pub is_dir: bool,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub children: Option<Vec<FileNode>>,
+    /// Why a directory's content couldn't be listed, e.g. permission denied, or why a
+    /// symlink isn't followed. It is shown with no children rather than left out of the
+    /// tree.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FileErrorKind {
+    /// The system doesn't let the app read or write the file
+    PermissionDenied,
+    NotFound,
+    /// save_file's `expected_hash` no longer matches the file on disk
+    ChangedOnDisk,
+    Failed,
+}
+
+/// Why reading or saving a file failed, classified so the frontend can say what to do
+/// about it rather than show the system's message
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FileError {
+    pub kind: FileErrorKind,
+    pub message: String,
+}
+
+impl FileError {
+    /// `Failed to <action> <file_path>: <error>`, classified by the kind of `error`
+    fn io(action: &str, file_path: &str, error: &std::io::Error) -> Self {
+        let kind = match error.kind() {
+            std::io::ErrorKind::PermissionDenied => FileErrorKind::PermissionDenied,
+            std::io::ErrorKind::NotFound => FileErrorKind::NotFound,
+            _ => FileErrorKind::Failed,
+        };
+        let message = match kind {
+            FileErrorKind::PermissionDenied => format!(
+                "Failed to {} {}: permission denied. Check that your user may access it.",
+                action, file_path
+            ),
+            _ => format!("Failed to {} {}: {}", action, file_path, error),
+        };
+        FileError { kind, message }
+    }
+}
+
+impl From<String> for FileError {
+    fn from(message: String) -> Self {
+        FileError {
+            kind: FileErrorKind::Failed,
+            message,
+        }
+    }
+}
+
+impl From<FileError> for String {
+    fn from(error: FileError) -> Self {
+        error.message
+    }
+}
+
+impl std::fmt::Display for FileError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+/// How the file tree changes after a create, delete or rename, so the frontend can patch
+/// the tree open_project returned instead of asking for all of it again. Paths are in
+/// FileNode form, with "" for the project root.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum TreeChange {
+    /// Insert `node` among the children of the directory at `parent`, in the tree's order
+    Added { parent: String, node: FileNode },
+    /// Drop the node at `path` and everything under it
+    Removed { path: String },
+    /// Drop the node at `old_path` and insert `node`, whose paths below are updated too,
+    /// under `parent`
+    Moved {
+        old_path: String,
+        parent: String,
+        node: FileNode,
+    },
+}
+
+/// A file's content with the hash of that version
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FileWithHash {
+    pub content: String,
+    pub hash: String,
+}
+
+/// A project's file tree, possibly cut short on very large directories
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProjectTree {
+    #[serde(flatten)]
+    pub root: FileNode,
+    /// Some entries were left out because the tree hit its size or depth limit
+    pub truncated: bool,
+    pub omitted_entries: usize,
+}
+
+/// Disk usage of a project, in bytes
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProjectSize {
+    /// Files the tree shows, outside build/
+    pub source_bytes: u64,
+    pub build_bytes: u64,
+    pub total_bytes: u64,
+    /// Files counted in either total
+    pub file_count: usize,
+}
+
+/// A built PDF and whether it predates its sources
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PdfWithStatus {
+    pub bytes: Vec<u8>,
+    /// A source file reachable from the compiled file changed after the PDF was built
+    pub is_stale: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Bookmark {
+    pub file_path: String,
+    pub label: String,
+    pub created_at: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RecentFile {
+    pub path: String,
+    #[serde(default)]
+    pub cursor_line: u32,
+    #[serde(default)]
+    pub cursor_column: u32,
+    /// Editor scroll offset in pixels
+    #[serde(default)]
+    pub scroll_top: f64,
+}
+
+/// A single file opened without walking the project tree
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OpenedFile {
+    pub file_path: String,
+    pub content: String,
+    pub meta: ProjectMeta,
+}
+
+/// A file opened on its own, with its directory standing in as the project
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SingleFile {
+    /// The file's directory, to pass as `project_path` to the other commands
+    pub project_path: String,
+    /// The file's name, relative to `project_path`
+    pub file_path: String,
+    pub content: String,
+    pub tree: ProjectTree,
+    /// The directory's .incipit if it has one, otherwise defaults rooted at the file
+    pub meta: ProjectMeta,
+}
+
+/// How a file's last successful compile was run, to tell when its PDF is out of date
+/// with the current engine or options
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LastCompile {
+    /// TeX engine Tectonic ran, e.g. "xetex"
+    pub engine: String,
+    pub tectonic_version: String,
+    pub draft: bool,
+    pub paper_size: Option<String>,
+    pub landscape: bool,
+    pub extra_preamble: Option<String>,
+    pub include_only: Vec<String>,
+    #[serde(default)]
+    pub watermark: Option<String>,
+    /// Compile profile the options were combined with
+    #[serde(default)]
+    pub profile: Option<String>,
+    pub compiled_at: String,
+    /// Pages of the PDF it produced, to notice a build that suddenly comes out far shorter
+    #[serde(default)]
+    pub pdf_pages: Option<u32>,
+}
+
+/// One run of compile_latex_project, kept in the project's compile history
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CompileRecord {
+    /// Project-relative source path
+    pub file_path: String,
+    pub compiled_at: String,
+    pub succeeded: bool,
+    pub duration_ms: u64,
+    /// Compiled with CompileOptions::single_pass, so the duration is for one TeX pass
+    #[serde(default)]
+    pub single_pass: bool,
+    /// Total size of the files TeX read from disk, to scale the duration by when
+    /// estimating a build of the document as it is now; None for Tectonic.toml projects
+    #[serde(default)]
+    pub input_bytes: Option<u64>,
+    pub error_count: usize,
+    pub warning_count: usize,
+    /// The first error TeX reported, or why the compile couldn't run
+    pub error_summary: Option<String>,
+}
+
+/// Modification times of a project's files at one point, to tell later what changed
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TreeSnapshot {
+    pub taken_at: String,
+    /// Milliseconds since the Unix epoch, by project-relative path
+    pub files: BTreeMap<String, u64>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ChangeKind {
+    Added,
+    Modified,
+    Deleted,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FileChange {
+    pub path: String,
+    pub kind: ChangeKind,
+}
+
+/// A named set of compile settings, e.g. "draft" or "print", chosen through the
+/// compile's `profile` option. Unset fields leave the compile's own options alone.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CompileProfile {
+    /// TeX engine to build with; Tectonic only runs "xetex"
+    pub engine: Option<String>,
+    /// Project-relative directory the outputs are copied to after a successful build,
+    /// e.g. "dist"
+    pub output_dir: Option<String>,
+    pub draft: Option<bool>,
+    pub shell_escape: Option<bool>,
+    pub extra_preamble: Option<String>,
+    pub paper_size: Option<String>,
+    pub landscape: Option<bool>,
+    /// Watermark text stamped on every page, as CompileOptions::watermark
+    pub watermark: Option<String>,
 }
 
+/// How a compile turns TeX's output into a PDF
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OutputPipeline {
+    /// Tectonic writes the PDF itself
+    #[default]
+    DirectPdf,
+    /// Tectonic writes an XDV file, which the xdvipdfmx of an installed TeX distribution
+    /// turns into the PDF. Unlike Tectonic's built-in copy, it can hand PostScript
+    /// specials, e.g. from pstricks, and EPS figures to Ghostscript. Packages that need
+    /// dvips itself, such as psfrag, still can't work: XeTeX writes no DVI for it.
+    Xdv,
+}
+
+/// A compile profile along with its name, as list_profiles returns them
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NamedProfile {
+    pub name: String,
+    #[serde(flatten)]
+    pub profile: CompileProfile,
+}
+
+/// How many recently opened files each project remembers
+const MAX_RECENT_FILES: usize = 10;
+
+/// How many file tree snapshots each project keeps, oldest dropped first
+const MAX_TREE_SNAPSHOTS: usize = 5;
+
+/// How many compiles each project's history keeps, oldest dropped first
+const MAX_COMPILE_HISTORY: usize = 50;
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ProjectMeta {
     pub last_opened_file: Option<String>,
     pub root_file: String,
     pub project_settings: serde_json::Value,
+    #[serde(default)]
+    pub bookmarks: Vec<Bookmark>,
+    /// Most recently opened first; `last_opened_file` mirrors the first entry
+    #[serde(default)]
+    pub recent_files: Vec<RecentFile>,
+    /// Directories searched for \input files, classes and packages after the project
+    /// itself; relative paths are resolved against the project root
+    #[serde(default)]
+    pub extra_input_paths: Vec<String>,
+    /// Glob patterns hidden from the file tree, e.g. `*.bak` or `data/`
+    #[serde(default)]
+    pub tree_ignore: Vec<String>,
+    /// Follow symlinks that lead outside the project, e.g. to a shared figures folder or
+    /// bibliography, in the file tree and when reading files. Without it they are shown
+    /// but not followed, and reads through them are refused.
+    #[serde(default)]
+    pub allow_symlinks: bool,
+    /// Words the spell checker accepts in this project, kept sorted
+    #[serde(default)]
+    pub personal_words: Vec<String>,
+    /// Shell command run in the project directory before each compile; runs only when
+    /// hooks are allowed in the global settings
+    #[serde(default)]
+    pub pre_build: Option<String>,
+    /// Shell command run after each successful compile, under the same conditions
+    #[serde(default)]
+    pub post_build: Option<String>,
+    /// Output base names for files that don't build to `<stem>.pdf`, keyed by
+    /// project-relative source path
+    #[serde(default)]
+    pub jobnames: BTreeMap<String, String>,
+    /// Word count goals by project-relative source path, for tracking progress per chapter
+    #[serde(default)]
+    pub word_targets: BTreeMap<String, usize>,
+    /// Settings of each file's last successful compile, by project-relative source path
+    #[serde(default)]
+    pub last_compiles: BTreeMap<String, LastCompile>,
+    /// Taken when the project is opened or on request, oldest first
+    #[serde(default)]
+    pub tree_snapshots: Vec<TreeSnapshot>,
+    /// Recent compiles of any file, oldest first
+    #[serde(default)]
+    pub compile_history: Vec<CompileRecord>,
+    /// Compile profiles by name
+    #[serde(default)]
+    pub profiles: BTreeMap<String, CompileProfile>,
+    /// Identifier written on first use by project_id, so keys derived from it survive
+    /// the folder being moved or renamed
+    #[serde(default)]
+    pub project_id: Option<String>,
+    /// How TeX's output becomes a PDF
+    #[serde(default)]
+    pub output_pipeline: OutputPipeline,
+    /// Style rules of the project's own, checked by style_check along with the built-in
+    /// rule sets it is asked for
+    #[serde(default)]
+    pub style_rules: Vec<StyleRule>,
+    /// Most pages the root file's PDF may have, e.g. a conference's limit. Compiles
+    /// over it still succeed, with a warning.
+    #[serde(default)]
+    pub max_pages: Option<u32>,
+    /// Files open in editor tabs, in tab order, restored when the project is reopened
+    #[serde(default)]
+    pub open_tabs: Vec<String>,
+    /// The focused tab, one of `open_tabs`
+    #[serde(default)]
+    pub active_tab: Option<String>,
+    /// Fields written by newer versions, preserved on save
+    #[serde(flatten)]
+    pub extra: serde_json::Map<String, serde_json::Value>,
 }
 
 impl Default for ProjectMeta {
@@ -26,12 +386,269 @@ impl Default for ProjectMeta {
             project_settings: serde_json::json!({
                 "created_at": chrono::Utc::now().to_rfc3339(),
             }),
+            bookmarks: Vec::new(),
+            recent_files: Vec::new(),
+            extra_input_paths: Vec::new(),
+            tree_ignore: Vec::new(),
+            allow_symlinks: false,
+            personal_words: Vec::new(),
+            pre_build: None,
+            post_build: None,
+            jobnames: BTreeMap::new(),
+            word_targets: BTreeMap::new(),
+            last_compiles: BTreeMap::new(),
+            tree_snapshots: Vec::new(),
+            compile_history: Vec::new(),
+            profiles: BTreeMap::new(),
+            project_id: None,
+            output_pipeline: OutputPipeline::default(),
+            style_rules: Vec::new(),
+            max_pages: None,
+            open_tabs: Vec::new(),
+            active_tab: None,
+            extra: serde_json::Map::new(),
+        }
+    }
+}
+
+/// Entries open_project loads before returning a partial tree
+const DEFAULT_MAX_TREE_ENTRIES: usize = 20_000;
+
+/// Tree entries open_single_file loads; its directory may be something like Downloads
+const SINGLE_FILE_TREE_ENTRIES: usize = 500;
+
+/// Directories nested deeper than this (e.g. through symlink loops) are not read
+pub(crate) const MAX_TREE_DEPTH: usize = 32;
+
+/// Compiled glob patterns over project-relative paths. As in .gitignore, a pattern
+/// without a slash matches a name at any depth, one with a slash matches the whole
+/// path, and a trailing slash restricts it to directories.
+pub(crate) struct PathPatterns {
+    globs: GlobSet,
+    dir_only: Vec<bool>,
+}
+
+impl PathPatterns {
+    /// `setting` names the option the patterns came from, for error messages
+    pub(crate) fn new(patterns: &[String], setting: &str) -> Result<Self, String> {
+        let mut builder = GlobSetBuilder::new();
+        let mut dir_only = Vec::new();
+
+        for pattern in patterns {
+            let trimmed = pattern.trim().trim_end_matches('/');
+            if trimmed.is_empty() {
+                continue;
+            }
+
+            let glob = match trimmed.strip_prefix('/') {
+                Some(anchored) => anchored.to_string(),
+                None if trimmed.contains('/') => trimmed.to_string(),
+                None => format!("**/{}", trimmed),
+            };
+
+            // `*` stays within one path component, as in .gitignore
+            builder.add(
+                GlobBuilder::new(&glob)
+                    .literal_separator(true)
+                    .build()
+                    .map_err(|e| format!("Invalid {} pattern {}: {}", setting, pattern, e))?,
+            );
+            dir_only.push(pattern.trim().ends_with('/'));
+        }
+
+        let globs = builder
+            .build()
+            .map_err(|e| format!("Invalid {} patterns: {}", setting, e))?;
+
+        Ok(PathPatterns { globs, dir_only })
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.globs.is_empty()
+    }
+
+    pub(crate) fn matches(&self, relative_path: &str, is_dir: bool) -> bool {
+        self.globs
+            .matches(relative_path)
+            .into_iter()
+            .any(|i| is_dir || !self.dir_only[i])
+    }
+}
+
+/// How many more entries a tree may hold, and how many were left out
+struct TreeBudget {
+    remaining_entries: usize,
+    omitted_entries: usize,
+}
+
+impl TreeBudget {
+    fn new(max_entries: usize) -> Self {
+        TreeBudget {
+            remaining_entries: max_entries,
+            omitted_entries: 0,
+        }
+    }
+}
+
+/// How a walk of the project treats symlinks. One leading outside the project is only
+/// followed with the project's allow_symlinks, and one leading back into a directory
+/// the walk is already in never is, as the walk would go round it forever.
+pub(crate) struct SymlinkPolicy {
+    allow_outside: bool,
+    /// The project directory, resolved
+    root: PathBuf,
+    /// The directories from the root to the one being read, resolved
+    ancestors: Vec<PathBuf>,
+}
+
+impl SymlinkPolicy {
+    pub(crate) fn new(root: &Path, allow_outside: bool) -> Self {
+        let root = root.canonicalize().unwrap_or_else(|_| root.to_path_buf());
+        SymlinkPolicy {
+            allow_outside,
+            ancestors: vec![root.clone()],
+            root,
+        }
+    }
+
+    /// Why `path` isn't followed, or None when it is. Only symlinks are ever refused.
+    fn refusal(&self, path: &Path) -> Option<&'static str> {
+        let metadata = fs::symlink_metadata(path).ok()?;
+        if !metadata.file_type().is_symlink() {
+            return None;
+        }
+        let Ok(target) = path.canonicalize() else {
+            return Some("Broken symlink: what it points to doesn't exist");
+        };
+        if !self.allow_outside && !target.starts_with(&self.root) {
+            return Some("Symlink leads outside the project; set allow_symlinks to follow it");
+        }
+        if self.ancestors.contains(&target) {
+            return Some("Symlink loops back to a directory it is in");
+        }
+        None
+    }
+
+    /// Note that the walk goes into the directory `dir`, until the matching leave
+    fn enter(&mut self, dir: &Path) {
+        self.ancestors
+            .push(dir.canonicalize().unwrap_or_else(|_| dir.to_path_buf()));
+    }
+
+    fn leave(&mut self) {
+        self.ancestors.pop();
+    }
+
+    /// How many directories below the root the walk is
+    fn depth(&self) -> usize {
+        self.ancestors.len() - 1
+    }
+}
+
+/// Project-relative paths of every file the file tree shows, sorted by path
+pub(crate) fn collect_files(
+    dir: &Path,
+    root: &Path,
+    hidden: &PathPatterns,
+    links: &mut SymlinkPolicy,
+    files: &mut Vec<String>,
+) {
+    if links.depth() >= MAX_TREE_DEPTH {
+        return;
+    }
+    let Ok(entries) = fs::read_dir(dir) else {
+        return;
+    };
+
+    let mut paths: Vec<PathBuf> = entries
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| !entry.file_name().to_string_lossy().starts_with('.'))
+        .map(|entry| entry.path())
+        .collect();
+    paths.sort();
+
+    for path in paths {
+        let relative = latex::to_project_path(path.strip_prefix(root).unwrap_or(&path));
+        let is_dir = path.is_dir();
+        if hidden.matches(&relative, is_dir) || links.refusal(&path).is_some() {
+            continue;
+        }
+
+        if is_dir {
+            links.enter(&path);
+            collect_files(&path, root, hidden, links, files);
+            links.leave();
+        } else {
+            files.push(relative);
+        }
+    }
+}
+
+/// Running byte and file totals for a directory walk
+#[derive(Default)]
+pub(crate) struct DiskUsage {
+    pub(crate) bytes: u64,
+    pub(crate) files: usize,
+}
+
+/// Add up the regular files under `dir`, skipping entries that can't be read and
+/// symlinks. With `hidden`, files the tree hides are left out as well.
+pub(crate) fn measure_dir(
+    dir: &Path,
+    root: &Path,
+    hidden: Option<&PathPatterns>,
+    depth: usize,
+    usage: &mut DiskUsage,
+) {
+    if depth >= MAX_TREE_DEPTH {
+        return;
+    }
+    let Ok(entries) = fs::read_dir(dir) else {
+        return;
+    };
+
+    for entry in entries.filter_map(|entry| entry.ok()) {
+        let path = entry.path();
+        let Ok(metadata) = fs::symlink_metadata(&path) else {
+            continue;
+        };
+
+        if let Some(hidden) = hidden {
+            let relative = latex::to_project_path(path.strip_prefix(root).unwrap_or(&path));
+            if entry.file_name().to_string_lossy().starts_with('.')
+                || hidden.matches(&relative, metadata.is_dir())
+            {
+                continue;
+            }
+        }
+
+        if metadata.is_dir() {
+            measure_dir(&path, root, hidden, depth + 1, usage);
+        } else if metadata.is_file() {
+            usage.bytes += metadata.len();
+            usage.files += 1;
         }
     }
 }
 
+/// Hidden entries the tree leaves out even when showing hidden files: the project
+/// metadata and its backups, temporary files from atomic writes, and version control data
+fn always_hidden(name: &str) -> bool {
+    name.starts_with(".incipit")
+        || (name.starts_with('.') && name.ends_with(".tmp"))
+        || matches!(name, ".git" | ".hg" | ".svn")
+}
+
 /// Recursively build a file tree structure
-fn build_file_tree(path: &Path, root_path: &Path) -> Result<FileNode, String> {
+fn build_file_tree(
+    path: &Path,
+    root_path: &Path,
+    ignore: &PathPatterns,
+    show_hidden: bool,
+    links: &mut SymlinkPolicy,
+    budget: &mut TreeBudget,
+    depth: usize,
+) -> Result<FileNode, String> {
     let name = path
         .file_name()
         .and_then(|n| n.to_str())
@@ -46,18 +663,93 @@ fn build_file_tree(path: &Path, root_path: &Path) -> Result<FileNode, String> {
 
     let is_dir = path.is_dir();
 
+    let mut error = None;
     let children = if is_dir {
-        let mut entries = fs::read_dir(path)
-            .map_err(|e| format!("Failed to read directory {}: {}", path.display(), e))?
+        let listing = match fs::read_dir(path) {
+            Ok(listing) => Some(listing),
+            // The project root itself has to be readable
+            Err(e) if depth > 0 => {
+                error = Some(FileError::io("read directory", &relative_path, &e).message);
+                None
+            }
+            Err(e) => {
+                return Err(format!(
+                    "Failed to read directory {}: {}",
+                    path.display(),
+                    e
+                ))
+            }
+        };
+
+        // The build directory holds outputs, not sources. Only the project's own build/
+        // is inside it, straight under the root.
+        let build = if depth == 0 {
+            build_dir(root_path).ok()
+        } else {
+            None
+        };
+
+        // Collected before recursing so only one directory handle is open at a time
+        let mut dir_entries = listing
+            .into_iter()
+            .flatten()
             .filter_map(|entry| entry.ok())
             .filter(|entry| {
-                // Skip hidden files and .incipit metadata
+                // Skip hidden files, and always the .incipit metadata
                 let file_name = entry.file_name();
                 let name_str = file_name.to_string_lossy();
-                !name_str.starts_with('.') && name_str != ".incipit"
+                !(name_str.starts_with('.') && (!show_hidden || always_hidden(&name_str)))
+            })
+            .filter(|entry| build.as_deref() != Some(entry.path().as_path()))
+            .filter(|entry| {
+                let path = entry.path();
+                let relative = path.strip_prefix(root_path).unwrap_or(&path);
+                !ignore.matches(&latex::to_project_path(relative), path.is_dir())
             })
-            .filter_map(|entry| build_file_tree(&entry.path(), root_path).ok())
             .collect::<Vec<_>>();
+        dir_entries.sort_by_key(|entry| entry.file_name());
+
+        let mut entries = Vec::new();
+        for entry in dir_entries {
+            if budget.remaining_entries == 0 || depth >= MAX_TREE_DEPTH {
+                budget.omitted_entries += 1;
+                continue;
+            }
+            budget.remaining_entries -= 1;
+
+            let entry_path = entry.path();
+            if let Some(reason) = links.refusal(&entry_path) {
+                let relative = entry_path.strip_prefix(root_path).unwrap_or(&entry_path);
+                entries.push(FileNode {
+                    name: entry.file_name().to_string_lossy().into_owned(),
+                    path: relative.to_string_lossy().into_owned(),
+                    is_dir: false,
+                    children: None,
+                    error: Some(reason.to_string()),
+                });
+                continue;
+            }
+
+            let is_dir = entry_path.is_dir();
+            if is_dir {
+                links.enter(&entry_path);
+            }
+            let node = build_file_tree(
+                &entry_path,
+                root_path,
+                ignore,
+                show_hidden,
+                links,
+                budget,
+                depth + 1,
+            );
+            if is_dir {
+                links.leave();
+            }
+            if let Ok(node) = node {
+                entries.push(node);
+            }
+        }
 
         // Sort: directories first, then files, alphabetically
         entries.sort_by(|a, b| {
@@ -78,11 +770,18 @@ fn build_file_tree(path: &Path, root_path: &Path) -> Result<FileNode, String> {
         path: relative_path,
         is_dir,
         children,
+        error,
     })
 }
 
+/// The project's file tree. Hidden files are left out unless `show_hidden` is set; the
+/// .incipit metadata and the project's build directory never show.
 #[tauri::command]
-pub async fn open_project(path: String) -> Result<FileNode, String> {
+pub async fn open_project(
+    path: String,
+    max_entries: Option<usize>,
+    show_hidden: Option<bool>,
+) -> Result<ProjectTree, String> {
     let project_path = PathBuf::from(&path);
 
     if !project_path.exists() {
@@ -93,123 +792,1334 @@ pub async fn open_project(path: String) -> Result<FileNode, String> {
         return Err(format!("Path is not a directory: {}", path));
     }
 
-    build_file_tree(&project_path, &project_path)
+    let meta = read_project_meta(&project_path)?;
+    let ignore = PathPatterns::new(&meta.tree_ignore, "tree_ignore")?;
+
+    let mut budget = TreeBudget::new(max_entries.unwrap_or(DEFAULT_MAX_TREE_ENTRIES));
+    let root = build_file_tree(
+        &project_path,
+        &project_path,
+        &ignore,
+        show_hidden.unwrap_or(false),
+        &mut SymlinkPolicy::new(&project_path, meta.allow_symlinks),
+        &mut budget,
+        0,
+    )?;
+
+    if project_path.join(".incipit").exists() {
+        if let Err(e) = record_tree_snapshot(&project_path) {
+            eprintln!("Failed to record file tree snapshot: {}", e);
+        }
+    }
+
+    Ok(ProjectTree {
+        root,
+        truncated: budget.omitted_entries > 0,
+        omitted_entries: budget.omitted_entries,
+    })
 }
 
-#[tauri::command]
-pub async fn read_file(project_path: String, file_path: String) -> Result<String, String> {
-    let full_path = PathBuf::from(&project_path).join(&file_path);
+/// A path a tree operation may touch: inside the project, not the root itself and not
+/// the .incipit metadata or version control data
+fn tree_path(path: &str) -> Result<String, String> {
+    let path = latex::project_file(path)?;
+    let first = path.split('/').next().unwrap_or_default();
+    if path.is_empty() || always_hidden(first) {
+        return Err(format!("Invalid file path: {}", path));
+    }
+    Ok(path)
+}
 
-    // Security check: ensure the file is within the project directory
-    let canonical_project = PathBuf::from(&project_path)
-        .canonicalize()
-        .map_err(|e| format!("Invalid project path: {}", e))?;
+/// The FileNode path of the directory holding `path`
+fn parent_path(path: &str) -> String {
+    path.rsplit_once('/')
+        .map_or(String::new(), |(parent, _)| parent.to_string())
+}
 
-    let canonical_file = full_path
-        .canonicalize()
-        .map_err(|e| format!("Invalid file path: {}", e))?;
+/// Fail unless the directory `path` would be created in exists
+fn require_parent(project_dir: &Path, path: &str) -> Result<String, String> {
+    let parent = parent_path(path);
+    if !project_dir.join(&parent).is_dir() {
+        return Err(format!("Directory does not exist: {}", parent));
+    }
+    Ok(parent)
+}
 
-    if !canonical_file.starts_with(&canonical_project) {
-        return Err("Access denied: file is outside project directory".to_string());
+/// The tree node for `path` with its subtree, built the way open_project builds it
+fn tree_node(project_dir: &Path, path: &str, show_hidden: bool) -> Result<FileNode, String> {
+    let meta = read_project_meta(project_dir)?;
+    let ignore = PathPatterns::new(&meta.tree_ignore, "tree_ignore")?;
+    let depth = path.split('/').count();
+    let mut links = SymlinkPolicy::new(project_dir, meta.allow_symlinks);
+    let mut dir = project_dir.to_path_buf();
+    for component in path.split('/') {
+        dir.push(component);
+        if dir.is_dir() {
+            links.enter(&dir);
+        }
     }
+    let mut budget = TreeBudget::new(DEFAULT_MAX_TREE_ENTRIES);
+    build_file_tree(
+        &project_dir.join(path),
+        project_dir,
+        &ignore,
+        show_hidden,
+        &mut links,
+        &mut budget,
+        depth,
+    )
+}
+
+/// Create an empty file. Its directory must exist and nothing may be at `file_path` yet.
+#[tauri::command]
+pub async fn create_file(project_path: String, file_path: String) -> Result<TreeChange, String> {
+    let project_dir = PathBuf::from(&project_path);
+    let file_path = tree_path(&file_path)?;
+    let parent = require_parent(&project_dir, &file_path)?;
 
-    fs::read_to_string(&full_path)
-        .map_err(|e| format!("Failed to read file {}: {}", file_path, e))
+    fs::OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(project_dir.join(&file_path))
+        .map_err(|e| format!("Failed to create {}: {}", file_path, e))?;
+
+    Ok(TreeChange::Added {
+        parent,
+        node: tree_node(&project_dir, &file_path, false)?,
+    })
 }
 
+/// Create an empty directory, in a directory that exists
 #[tauri::command]
-pub async fn save_file(
+pub async fn create_directory(
     project_path: String,
-    file_path: String,
-    content: String,
-) -> Result<(), String> {
-    let full_path = PathBuf::from(&project_path).join(&file_path);
+    dir_path: String,
+) -> Result<TreeChange, String> {
+    let project_dir = PathBuf::from(&project_path);
+    let dir_path = tree_path(&dir_path)?;
+    let parent = require_parent(&project_dir, &dir_path)?;
 
-    // Security check: ensure the file is within the project directory
-    let canonical_project = PathBuf::from(&project_path)
-        .canonicalize()
-        .map_err(|e| format!("Invalid project path: {}", e))?;
+    fs::create_dir(project_dir.join(&dir_path))
+        .map_err(|e| format!("Failed to create {}: {}", dir_path, e))?;
 
-    // For new files that don't exist yet, check the parent directory
-    let path_to_check = if full_path.exists() {
-        full_path.clone()
+    Ok(TreeChange::Added {
+        parent,
+        node: tree_node(&project_dir, &dir_path, false)?,
+    })
+}
+
+/// Delete a file, or a directory with everything in it. A symlink is removed, not what
+/// it points at.
+#[tauri::command]
+pub async fn delete_path(project_path: String, path: String) -> Result<TreeChange, String> {
+    let project_dir = PathBuf::from(&project_path);
+    let path = tree_path(&path)?;
+    let full_path = project_dir.join(&path);
+
+    let metadata =
+        fs::symlink_metadata(&full_path).map_err(|e| format!("Failed to read {}: {}", path, e))?;
+    if metadata.is_dir() {
+        fs::remove_dir_all(&full_path)
     } else {
-        full_path
-            .parent()
-            .ok_or("Invalid file path")?
-            .to_path_buf()
+        fs::remove_file(&full_path)
+    }
+    .map_err(|e| format!("Failed to delete {}: {}", path, e))?;
+    autosave::discard(&project_path, &path);
+
+    Ok(TreeChange::Removed { path })
+}
+
+/// Where trash_path moves files, inside the project. Like .incipit, the tree and the
+/// file commands never show it.
+const TRASH_DIR: &str = ".incipit-trash";
+
+/// What is in the trash and where it came from, inside TRASH_DIR
+const TRASH_MANIFEST: &str = "manifest.json";
+
+/// Days trashed files are kept when trash_retention_days isn't set
+const DEFAULT_TRASH_RETENTION_DAYS: u64 = 30;
+
+/// Held while the trash manifest is read and written back
+static TRASH_LOCK: Mutex<()> = Mutex::new(());
+
+/// A file or directory in the project's trash
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TrashEntry {
+    /// Its name in the trash: when it was trashed, then its own name
+    pub id: String,
+    /// Project-relative path it was deleted from, and is restored to
+    pub original_path: String,
+    /// RFC 3339
+    pub trashed_at: String,
+    pub is_dir: bool,
+}
+
+/// The trash's entries, oldest first, leaving out any whose file is gone
+fn read_trash(project_dir: &Path) -> Result<Vec<TrashEntry>, String> {
+    let trash_dir = project_dir.join(TRASH_DIR);
+    let manifest_path = trash_dir.join(TRASH_MANIFEST);
+    if !manifest_path.exists() {
+        return Ok(Vec::new());
+    }
+
+    let content = fs::read_to_string(&manifest_path)
+        .map_err(|e| format!("Failed to read the trash: {}", e))?;
+    let mut entries: Vec<TrashEntry> = match serde_json::from_str(&content) {
+        Ok(entries) => entries,
+        Err(e) => {
+            eprintln!("Failed to parse the trash manifest, starting over: {}", e);
+            storage::set_aside_corrupt(&manifest_path);
+            Vec::new()
+        }
     };
+    entries.retain(|entry| fs::symlink_metadata(trash_dir.join(&entry.id)).is_ok());
+    Ok(entries)
+}
 
-    let canonical_check = path_to_check
-        .canonicalize()
-        .map_err(|e| format!("Invalid file path: {}", e))?;
+fn write_trash(project_dir: &Path, entries: &[TrashEntry]) -> Result<(), String> {
+    let content = serde_json::to_string_pretty(entries)
+        .map_err(|e| format!("Failed to serialize the trash: {}", e))?;
+    storage::write_atomic(&project_dir.join(TRASH_DIR).join(TRASH_MANIFEST), content)
+        .map_err(|e| format!("Failed to write the trash: {}", e))
+}
 
-    if !canonical_check.starts_with(&canonical_project) {
-        return Err("Access denied: file is outside project directory".to_string());
+/// Delete what has been in the trash longer than the trash_retention_days setting
+fn prune_trash(project_dir: &Path, entries: &mut Vec<TrashEntry>) -> Result<(), String> {
+    let days = read_global_settings()?
+        .trash_retention_days
+        .unwrap_or(DEFAULT_TRASH_RETENTION_DAYS);
+    if days == 0 {
+        return Ok(());
     }
+    let cutoff = chrono::Utc::now() - chrono::Duration::days(days as i64);
 
-    fs::write(&full_path, content)
-        .map_err(|e| format!("Failed to write file {}: {}", file_path, e))
+    entries.retain(|entry| {
+        let expired = chrono::DateTime::parse_from_rfc3339(&entry.trashed_at)
+            .is_ok_and(|trashed_at| trashed_at < cutoff);
+        if !expired {
+            return true;
+        }
+        let path = project_dir.join(TRASH_DIR).join(&entry.id);
+        let removed = if entry.is_dir {
+            fs::remove_dir_all(&path)
+        } else {
+            fs::remove_file(&path)
+        };
+        match removed {
+            Ok(()) => false,
+            Err(e) => {
+                eprintln!("Failed to delete {} from the trash: {}", entry.id, e);
+                true
+            }
+        }
+    });
+    Ok(())
 }
 
+/// Move a file or directory into the project's trash instead of deleting it, so
+/// restore_from_trash can bring it back. What has been in the trash longer than the
+/// trash_retention_days setting, 30 days by default, is deleted for good meanwhile.
 #[tauri::command]
-pub async fn load_project_meta(project_path: String) -> Result<ProjectMeta, String> {
-    let meta_path = PathBuf::from(&project_path).join(".incipit");
+pub async fn trash_path(project_path: String, path: String) -> Result<TreeChange, String> {
+    let project_dir = PathBuf::from(&project_path);
+    let path = tree_path(&path)?;
+    let full_path = project_dir.join(&path);
+    let metadata =
+        fs::symlink_metadata(&full_path).map_err(|e| format!("Failed to read {}: {}", path, e))?;
 
-    if !meta_path.exists() {
-        return Ok(ProjectMeta::default());
+    let _lock = TRASH_LOCK.lock().unwrap();
+    let mut entries = read_trash(&project_dir)?;
+    prune_trash(&project_dir, &mut entries)?;
+
+    let trash_dir = project_dir.join(TRASH_DIR);
+    fs::create_dir_all(&trash_dir).map_err(|e| format!("Failed to create the trash: {}", e))?;
+    let now = chrono::Utc::now();
+    let name = path.rsplit('/').next().unwrap_or(&path);
+    let stamp = now.format("%Y%m%d-%H%M%S%.3f");
+    let mut id = format!("{}-{}", stamp, name);
+    let mut copy = 1;
+    while fs::symlink_metadata(trash_dir.join(&id)).is_ok() {
+        copy += 1;
+        id = format!("{}-{}-{}", stamp, copy, name);
     }
 
-    let content = fs::read_to_string(&meta_path)
-        .map_err(|e| format!("Failed to read project metadata: {}", e))?;
+    fs::rename(&full_path, trash_dir.join(&id))
+        .map_err(|e| format!("Failed to move {} to the trash: {}", path, e))?;
+    entries.push(TrashEntry {
+        id,
+        original_path: path.clone(),
+        trashed_at: now.to_rfc3339(),
+        is_dir: metadata.is_dir(),
+    });
+    write_trash(&project_dir, &entries)?;
+    autosave::discard(&project_path, &path);
 
-    serde_json::from_str(&content)
-        .map_err(|e| format!("Failed to parse project metadata: {}", e))
+    Ok(TreeChange::Removed { path })
 }
 
+/// What is in the project's trash, most recently trashed first
 #[tauri::command]
-pub async fn save_project_meta(project_path: String, meta: ProjectMeta) -> Result<(), String> {
-    let meta_path = PathBuf::from(&project_path).join(".incipit");
+pub async fn list_trash(project_path: String) -> Result<Vec<TrashEntry>, String> {
+    let project_dir = PathBuf::from(&project_path);
+    let _lock = TRASH_LOCK.lock().unwrap();
+    let mut entries = read_trash(&project_dir)?;
+    let count = entries.len();
+    prune_trash(&project_dir, &mut entries)?;
+    if entries.len() != count {
+        write_trash(&project_dir, &entries)?;
+    }
 
-    let content =
-        serde_json::to_string_pretty(&meta).map_err(|e| format!("Failed to serialize: {}", e))?;
+    entries.reverse();
+    Ok(entries)
+}
+
+/// Put the trashed entry `id` back where it was deleted from, recreating the
+/// directories it was in if they're gone too. Nothing may be at that path meanwhile.
+#[tauri::command]
+pub async fn restore_from_trash(
+    project_path: String,
+    id: String,
+    show_hidden: Option<bool>,
+) -> Result<TreeChange, String> {
+    let project_dir = PathBuf::from(&project_path);
+    let _lock = TRASH_LOCK.lock().unwrap();
+    let mut entries = read_trash(&project_dir)?;
+    let index = entries
+        .iter()
+        .position(|entry| entry.id == id)
+        .ok_or_else(|| format!("{} is not in the trash", id))?;
+    let original = entries[index].original_path.clone();
+    if fs::symlink_metadata(project_dir.join(&original)).is_ok() {
+        return Err(format!(
+            "{} already exists; move it away before restoring",
+            original
+        ));
+    }
+
+    // The tree gains the outermost directory that has to be recreated, or else the entry
+    let mut added = original.clone();
+    while !project_dir.join(parent_path(&added)).is_dir() {
+        added = parent_path(&added);
+    }
+    fs::create_dir_all(project_dir.join(parent_path(&original)))
+        .map_err(|e| format!("Failed to create the directory of {}: {}", original, e))?;
+    let trashed = project_dir.join(TRASH_DIR).join(&id);
+    fs::rename(trashed, project_dir.join(&original))
+        .map_err(|e| format!("Failed to restore {}: {}", original, e))?;
+    entries.remove(index);
+    write_trash(&project_dir, &entries)?;
 
-    fs::write(&meta_path, content).map_err(|e| format!("Failed to write metadata: {}", e))
+    Ok(TreeChange::Added {
+        parent: parent_path(&added),
+        node: tree_node(&project_dir, &added, show_hidden.unwrap_or(false))?,
+    })
 }
 
+/// Rename or move a file or directory. Nothing may be at `new_path` yet, and its directory
+/// must exist. The project's root file follows the rename.
 #[tauri::command]
-pub async fn create_new_project(project_path: String) -> Result<FileNode, String> {
+pub async fn rename_path(
+    project_path: String,
+    old_path: String,
+    new_path: String,
+    show_hidden: Option<bool>,
+) -> Result<TreeChange, String> {
     let project_dir = PathBuf::from(&project_path);
+    let old_path = tree_path(&old_path)?;
+    let new_path = tree_path(&new_path)?;
+    let parent = require_parent(&project_dir, &new_path)?;
 
-    // Create the project directory if it doesn't exist
-    if !project_dir.exists() {
-        fs::create_dir_all(&project_dir)
-            .map_err(|e| format!("Failed to create project directory: {}", e))?;
+    if new_path.starts_with(&format!("{}/", old_path)) {
+        return Err(format!("Cannot move {} into itself", old_path));
     }
+    if fs::symlink_metadata(project_dir.join(&new_path)).is_ok() {
+        return Err(format!("{} already exists", new_path));
+    }
+    fs::rename(project_dir.join(&old_path), project_dir.join(&new_path))
+        .map_err(|e| format!("Failed to rename {}: {}", old_path, e))?;
+    autosave::discard(&project_path, &old_path);
 
-    // Check if directory is empty (or only has hidden files)
-    let entries: Vec<_> = fs::read_dir(&project_dir)
-        .map_err(|e| format!("Failed to read project directory: {}", e))?
-        .filter_map(|e| e.ok())
-        .filter(|e| {
-            let name = e.file_name();
-            let name_str = name.to_string_lossy();
-            !name_str.starts_with('.')
+    if project_dir.join(".incipit").exists() {
+        let mut meta = read_project_meta(&project_dir)?;
+        let moved_root = if meta.root_file == old_path {
+            Some(new_path.clone())
+        } else {
+            meta.root_file
+                .strip_prefix(&format!("{}/", old_path))
+                .map(|rest| format!("{}/{}", new_path, rest))
+        };
+        if let Some(root_file) = moved_root {
+            meta.root_file = root_file;
+            write_project_meta(&project_dir, &meta)?;
+        }
+    }
+
+    Ok(TreeChange::Moved {
+        node: tree_node(&project_dir, &new_path, show_hidden.unwrap_or(false))?,
+        old_path,
+        parent,
+    })
+}
+
+/// Open a file outside any project. Its directory acts as the project root, e.g. as
+/// the compile's filesystem root, without a .incipit being required or created.
+#[tauri::command]
+pub async fn open_single_file(file_path: String) -> Result<SingleFile, String> {
+    let full_path = PathBuf::from(&file_path);
+    if !full_path.is_file() {
+        return Err(format!("Path is not a file: {}", file_path));
+    }
+
+    let full_path = full_path
+        .canonicalize()
+        .map_err(|e| format!("Invalid file path: {}", e))?;
+    let project_dir = full_path.parent().ok_or("Invalid file path")?.to_path_buf();
+    let name = full_path
+        .file_name()
+        .and_then(|n| n.to_str())
+        .ok_or("Invalid file name")?
+        .to_string();
+
+    let content = fs::read_to_string(&full_path)
+        .map_err(|e| format!("Failed to read file {}: {}", file_path, e))?;
+
+    let meta = if project_dir.join(".incipit").exists() {
+        read_project_meta(&project_dir)?
+    } else {
+        ProjectMeta {
+            last_opened_file: Some(name.clone()),
+            root_file: name.clone(),
+            ..ProjectMeta::default()
+        }
+    };
+
+    let ignore = PathPatterns::new(&meta.tree_ignore, "tree_ignore")?;
+    let mut links = SymlinkPolicy::new(&project_dir, meta.allow_symlinks);
+    let mut budget = TreeBudget::new(SINGLE_FILE_TREE_ENTRIES);
+    let root = build_file_tree(
+        &project_dir,
+        &project_dir,
+        &ignore,
+        false,
+        &mut links,
+        &mut budget,
+        0,
+    )?;
+
+    Ok(SingleFile {
+        project_path: project_dir.to_string_lossy().to_string(),
+        file_path: name,
+        content,
+        tree: ProjectTree {
+            root,
+            truncated: budget.omitted_entries > 0,
+            omitted_entries: budget.omitted_entries,
+        },
+        meta,
+    })
+}
+
+#[tauri::command]
+pub async fn project_size(project_path: String) -> Result<ProjectSize, String> {
+    let project_dir = PathBuf::from(&project_path);
+    if !project_dir.is_dir() {
+        return Err(format!("Path is not a directory: {}", project_path));
+    }
+
+    let meta = read_project_meta(&project_dir)?;
+    // The build directory is measured on its own, so hide it from the source walk
+    let mut hidden_patterns = meta.tree_ignore;
+    hidden_patterns.push("/build/".to_string());
+    let hidden = PathPatterns::new(&hidden_patterns, "tree_ignore")?;
+
+    let build_dir = build_dir(&project_dir)?;
+
+    tauri::async_runtime::spawn_blocking(move || {
+        let mut source = DiskUsage::default();
+        measure_dir(&project_dir, &project_dir, Some(&hidden), 0, &mut source);
+
+        let mut build = DiskUsage::default();
+        measure_dir(&build_dir, &build_dir, None, 0, &mut build);
+
+        Ok(ProjectSize {
+            source_bytes: source.bytes,
+            build_bytes: build.bytes,
+            total_bytes: source.bytes + build.bytes,
+            file_count: source.files + build.files,
         })
+    })
+    .await
+    .map_err(|e| format!("Task join error: {}", e))?
+}
+
+/// Free bytes on the disk holding `path`
+#[tauri::command]
+pub async fn disk_space(path: String) -> Result<u64, String> {
+    fs2::available_space(&path).map_err(|e| format!("Failed to read free disk space: {}", e))
+}
+
+/// Project-relative paths of the files the tree shows with one of `extensions`
+/// (`tex` when not given), as a flat sorted list
+#[tauri::command]
+pub async fn list_tex_files(
+    project_path: String,
+    extensions: Option<Vec<String>>,
+) -> Result<Vec<String>, String> {
+    let project_dir = PathBuf::from(&project_path);
+    if !project_dir.is_dir() {
+        return Err(format!("Path is not a directory: {}", project_path));
+    }
+
+    let extensions: Vec<String> = extensions
+        .unwrap_or_else(|| vec!["tex".to_string()])
+        .iter()
+        .map(|ext| ext.trim().trim_start_matches('.').to_lowercase())
         .collect();
 
-    if !entries.is_empty() {
-        return Err("Directory is not empty. Please choose an empty directory for the new project.".to_string());
+    let meta = read_project_meta(&project_dir)?;
+    let hidden = PathPatterns::new(&meta.tree_ignore, "tree_ignore")?;
+
+    let mut files = Vec::new();
+    let mut links = SymlinkPolicy::new(&project_dir, meta.allow_symlinks);
+    collect_files(&project_dir, &project_dir, &hidden, &mut links, &mut files);
+    files.retain(|file| {
+        Path::new(file)
+            .extension()
+            .is_some_and(|ext| extensions.contains(&ext.to_string_lossy().to_lowercase()))
+    });
+
+    Ok(files)
+}
+
+/// Add the files under `dir` the tree shows to `newest`, keeping only the `limit` most
+/// recently modified. The heap's top is the oldest kept, so it is the one to drop.
+fn collect_recent(
+    dir: &Path,
+    root: &Path,
+    hidden: &PathPatterns,
+    depth: usize,
+    limit: usize,
+    newest: &mut BinaryHeap<Reverse<(SystemTime, String)>>,
+) {
+    if depth >= MAX_TREE_DEPTH {
+        return;
+    }
+    let Ok(entries) = fs::read_dir(dir) else {
+        return;
+    };
+
+    for entry in entries.filter_map(|entry| entry.ok()) {
+        if entry.file_name().to_string_lossy().starts_with('.') {
+            continue;
+        }
+        let path = entry.path();
+        let Ok(metadata) = fs::metadata(&path) else {
+            continue;
+        };
+        let relative = latex::to_project_path(path.strip_prefix(root).unwrap_or(&path));
+        if hidden.matches(&relative, metadata.is_dir()) {
+            continue;
+        }
+
+        if metadata.is_dir() {
+            collect_recent(&path, root, hidden, depth + 1, limit, newest);
+            continue;
+        }
+        let Ok(modified) = metadata.modified() else {
+            continue;
+        };
+        if newest.len() < limit {
+            newest.push(Reverse((modified, relative)));
+        } else if newest
+            .peek()
+            .is_some_and(|Reverse(oldest)| modified > oldest.0)
+        {
+            newest.pop();
+            newest.push(Reverse((modified, relative)));
+        }
+    }
+}
+
+/// The `limit` project files modified most recently, newest first, by their mtime on
+/// disk so files edited outside the app count too. Hidden files, the build directory
+/// and what tree_ignore hides are left out.
+#[tauri::command]
+pub async fn recently_modified(
+    project_path: String,
+    limit: usize,
+) -> Result<Vec<FileNode>, String> {
+    let project_dir = PathBuf::from(&project_path);
+    if !project_dir.is_dir() {
+        return Err(format!("Path is not a directory: {}", project_path));
+    }
+
+    let meta = read_project_meta(&project_dir)?;
+    let mut hidden_patterns = meta.tree_ignore;
+    hidden_patterns.push("/build/".to_string());
+    let hidden = PathPatterns::new(&hidden_patterns, "tree_ignore")?;
+
+    tauri::async_runtime::spawn_blocking(move || {
+        let mut newest = BinaryHeap::with_capacity(limit + 1);
+        if limit > 0 {
+            collect_recent(&project_dir, &project_dir, &hidden, 0, limit, &mut newest);
+        }
+
+        // Ascending Reverse order is newest first
+        Ok(newest
+            .into_sorted_vec()
+            .into_iter()
+            .map(|Reverse((_, path))| FileNode {
+                name: path.rsplit('/').next().unwrap_or(&path).to_string(),
+                path,
+                is_dir: false,
+                children: None,
+                error: None,
+            })
+            .collect())
+    })
+    .await
+    .map_err(|e| format!("Task join error: {}", e))?
+}
+
+#[tauri::command]
+pub async fn read_file(project_path: String, file_path: String) -> Result<String, FileError> {
+    let full_path = readable_path(&project_path, &file_path)?;
+    fs::read_to_string(&full_path).map_err(|e| FileError::io("read file", &file_path, &e))
+}
+
+/// Where `file_path` leads, if the project lets it be read: the file must be inside the
+/// project, or outside it only through a symlink and with the project's allow_symlinks.
+/// A path that climbs out with `..` never may.
+fn readable_path(project_path: &str, file_path: &str) -> Result<PathBuf, FileError> {
+    let full_path = PathBuf::from(project_path).join(file_path);
+
+    // Security check: ensure the file is within the project directory
+    let canonical_project = PathBuf::from(project_path)
+        .canonicalize()
+        .map_err(|e| FileError::io("open project", project_path, &e))?;
+
+    // Resolving fails for a symlink loop as for a missing file
+    let canonical_file = full_path
+        .canonicalize()
+        .map_err(|e| FileError::io("read file", file_path, &e))?;
+
+    if !canonical_file.starts_with(&canonical_project) {
+        // Lexically inside the project, so it's a symlink that leads out
+        let through_symlink = latex::normalize_relative(Path::new(file_path)).is_some();
+        if !through_symlink || !read_project_meta(Path::new(project_path))?.allow_symlinks {
+            return Err("Access denied: file is outside project directory"
+                .to_string()
+                .into());
+        }
+    }
+    Ok(canonical_file)
+}
+
+/// Hash identifying a version of a file's content
+pub(crate) fn content_hash(content: &[u8]) -> String {
+    format!("{:016x}", stable_hash(content))
+}
+
+/// Like read_file, along with the hash to pass back to save_file so it can tell if the
+/// file was changed by something else in the meantime
+#[tauri::command]
+pub async fn read_file_with_hash(
+    project_path: String,
+    file_path: String,
+) -> Result<FileWithHash, FileError> {
+    let content = read_file(project_path, file_path).await?;
+    Ok(FileWithHash {
+        hash: content_hash(content.as_bytes()),
+        content,
+    })
+}
+
+/// Hash of the file's current content on disk, as read_file_with_hash returns it
+#[tauri::command]
+pub async fn file_hash(project_path: String, file_path: String) -> Result<String, FileError> {
+    let content = read_file(project_path, file_path).await?;
+    Ok(content_hash(content.as_bytes()))
+}
+
+/// Line diff from `path_a` to `path_b`, e.g. a chapter and an earlier draft of it, as
+/// hunks to show side by side. Empty when the files have the same lines.
+#[tauri::command]
+pub async fn diff_files(
+    project_path: String,
+    path_a: String,
+    path_b: String,
+) -> Result<Vec<DiffHunk>, String> {
+    let old = read_file(project_path.clone(), path_a).await?;
+    let new = read_file(project_path, path_b).await?;
+
+    tauri::async_runtime::spawn_blocking(move || diff::hunks(&old, &new))
+        .await
+        .map_err(|e| format!("Task join error: {}", e))
+}
+
+/// Remove a UTF-8 byte order mark from the start of `file_path`, leaving the rest of
+/// its bytes untouched. Returns whether there was one.
+#[tauri::command]
+pub async fn strip_bom(project_path: String, file_path: String) -> Result<bool, String> {
+    let full_path = PathBuf::from(&project_path).join(&file_path);
+
+    // Security check: ensure the file is within the project directory
+    let canonical_project = PathBuf::from(&project_path)
+        .canonicalize()
+        .map_err(|e| format!("Invalid project path: {}", e))?;
+
+    let canonical_file = full_path
+        .canonicalize()
+        .map_err(|e| format!("Invalid file path: {}", e))?;
+
+    if !canonical_file.starts_with(&canonical_project) {
+        return Err("Access denied: file is outside project directory".to_string());
+    }
+
+    let bytes =
+        fs::read(&full_path).map_err(|e| format!("Failed to read file {}: {}", file_path, e))?;
+    let Some(rest) = bytes.strip_prefix(b"\xEF\xBB\xBF") else {
+        return Ok(false);
+    };
+
+    storage::write_atomic(&full_path, rest)
+        .map_err(|e| format!("Failed to write file {}: {}", file_path, e))?;
+    Ok(true)
+}
+
+/// Open the system file manager at `file_path` with it selected
+#[tauri::command]
+pub async fn reveal_in_file_manager(project_path: String, file_path: String) -> Result<(), String> {
+    let canonical_file = readable_path(&project_path, &file_path).map_err(|e| e.message)?;
+
+    tauri_plugin_opener::reveal_item_in_dir(&canonical_file)
+        .map_err(|e| format!("Failed to reveal {}: {}", file_path, e))
+}
+
+/// Write a file inside the project atomically, refusing paths that lead outside it
+pub(crate) fn write_project_file(
+    project_path: &str,
+    file_path: &str,
+    content: &str,
+) -> Result<(), FileError> {
+    let full_path = PathBuf::from(project_path).join(file_path);
+
+    // Security check: ensure the file is within the project directory
+    let canonical_project = PathBuf::from(project_path)
+        .canonicalize()
+        .map_err(|e| FileError::io("open project", project_path, &e))?;
+
+    // For new files that don't exist yet, check the parent directory
+    let path_to_check = if full_path.exists() {
+        full_path.clone()
+    } else {
+        full_path
+            .parent()
+            .ok_or_else(|| "Invalid file path".to_string())?
+            .to_path_buf()
+    };
+
+    let canonical_check = path_to_check
+        .canonicalize()
+        .map_err(|e| FileError::io("write file", file_path, &e))?;
+
+    if !canonical_check.starts_with(&canonical_project) {
+        return Err("Access denied: file is outside project directory"
+            .to_string()
+            .into());
+    }
+
+    storage::write_atomic(&full_path, content)
+        .map_err(|e| FileError::io("write file", file_path, &e))
+}
+
+/// Save the editor's content and return its hash. With `expected_hash`, the hash of the
+/// content the editor loaded, refuse to overwrite a file that has changed since.
+#[tauri::command]
+pub async fn save_file(
+    app: AppHandle,
+    project_path: String,
+    file_path: String,
+    content: String,
+    expected_hash: Option<String>,
+) -> Result<String, FileError> {
+    if let Some(expected_hash) = expected_hash {
+        let full_path = PathBuf::from(&project_path).join(&file_path);
+        // A file deleted since it was loaded has changed too
+        let current = match fs::read(&full_path) {
+            Ok(bytes) => Some(content_hash(&bytes)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => None,
+            Err(e) => return Err(FileError::io("read file", &file_path, &e)),
+        };
+        if current.as_deref() != Some(expected_hash.as_str()) {
+            return Err(FileError {
+                kind: FileErrorKind::ChangedOnDisk,
+                message: format!("File changed on disk since it was loaded: {}", file_path),
+            });
+        }
+    }
+
+    let settings = read_global_settings()?.editor_settings;
+    let formatted = format_content(&content, &file_path, &settings);
+    if formatted != content {
+        let payload = ReflowedFile {
+            project_path: project_path.clone(),
+            file_path: file_path.clone(),
+            content: formatted.clone(),
+        };
+        if let Err(e) = app.emit("file-reflowed", payload) {
+            eprintln!("Failed to emit file-reflowed: {}", e);
+        }
+    }
+    let content = formatted;
+
+    write_project_file(&project_path, &file_path, &content)?;
+    // What the user just saved supersedes any autosave still waiting
+    autosave::discard(&project_path, &file_path);
+    compile_on_save::file_saved(&app, &project_path, &file_path);
+    Ok(content_hash(content.as_bytes()))
+}
+
+/// The .tex file most likely to be the project's root: one with a \documentclass and a
+/// \begin{document}, preferring main.tex, then a file named after the folder, then the
+/// one least deeply nested. Symlinks out of the project are followed with
+/// `allow_symlinks`, which callers take from metadata they have already read.
+pub(crate) fn detect_root_file(project_dir: &Path, allow_symlinks: bool) -> Option<String> {
+    let mut files = Vec::new();
+    let no_patterns = PathPatterns::new(&[], "tree_ignore").ok()?;
+    let mut links = SymlinkPolicy::new(project_dir, allow_symlinks);
+    collect_files(
+        project_dir,
+        project_dir,
+        &no_patterns,
+        &mut links,
+        &mut files,
+    );
+
+    let folder = project_dir
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned());
+    let mut roots: Vec<String> = files
+        .into_iter()
+        .filter(|file| file.ends_with(".tex") && !file.starts_with("build/"))
+        .filter(|file| {
+            let Ok(content) = latex::read_source(project_dir, file) else {
+                return false;
+            };
+            let masked = latex::mask_comments(&content);
+            latex::find_begin_document(&masked).is_some()
+                && latex::commands(&masked).any(|c| c.name == "documentclass")
+        })
+        .collect();
+
+    roots.sort_by_key(|file| {
+        let stem = Path::new(file)
+            .file_stem()
+            .map(|s| s.to_string_lossy().into_owned());
+        (
+            file != "main.tex",
+            stem != folder,
+            file.matches('/').count(),
+            file.clone(),
+        )
+    });
+    roots.into_iter().next()
+}
+
+/// Default metadata for a project whose .incipit is lost, with the root file detected
+fn recovered_meta(project_dir: &Path) -> ProjectMeta {
+    let mut meta = ProjectMeta::default();
+    if let Some(root) = detect_root_file(project_dir, meta.allow_symlinks) {
+        meta.last_opened_file = Some(root.clone());
+        meta.root_file = root;
+    }
+    meta
+}
+
+pub(crate) fn read_project_meta(project_dir: &Path) -> Result<ProjectMeta, String> {
+    let meta_path = project_dir.join(".incipit");
+
+    if !meta_path.exists() {
+        return Ok(ProjectMeta::default());
+    }
+
+    let content = fs::read_to_string(&meta_path)
+        .map_err(|e| format!("Failed to read project metadata: {}", e))?;
+
+    // A corrupt file must not lock the user out of the project. Whether the tabs were
+    // ever saved tells an old file apart from one whose tabs were all closed.
+    let parsed = serde_json::from_str::<serde_json::Value>(&content).and_then(|value| {
+        let has_tabs = value.get("open_tabs").is_some();
+        serde_json::from_value::<ProjectMeta>(value).map(|meta| (meta, has_tabs))
+    });
+    let (mut meta, has_tabs) = match parsed {
+        Ok(parsed) => parsed,
+        Err(e) => {
+            eprintln!("Failed to parse project metadata, using defaults: {}", e);
+            // Detecting the root file only once the corrupt file is out of the way keeps
+            // recovery from ever reading it again
+            if storage::set_aside_corrupt(&meta_path).is_none() {
+                return Ok(ProjectMeta::default());
+            }
+            return Ok(recovered_meta(project_dir));
+        }
+    };
+
+    // Metadata written before recent_files existed only knows the last file
+    if meta.recent_files.is_empty() {
+        if let Some(last) = &meta.last_opened_file {
+            meta.recent_files.push(RecentFile {
+                path: last.clone(),
+                cursor_line: 0,
+                cursor_column: 0,
+                scroll_top: 0.0,
+            });
+        }
+    }
+
+    // Metadata written before open_tabs existed reopened just the last file, in a tab
+    // of its own
+    if !has_tabs {
+        if let Some(last) = &meta.last_opened_file {
+            meta.open_tabs = vec![last.clone()];
+            meta.active_tab = Some(last.clone());
+        }
+    }
+
+    Ok(meta)
+}
+
+pub(crate) fn write_project_meta(project_dir: &Path, meta: &ProjectMeta) -> Result<(), String> {
+    let meta_path = project_dir.join(".incipit");
+
+    let content =
+        serde_json::to_string_pretty(meta).map_err(|e| format!("Failed to serialize: {}", e))?;
+
+    storage::write_atomic(&meta_path, &content)
+        .map_err(|e| format!("Failed to write metadata: {}", e))
+}
+
+#[tauri::command]
+pub async fn load_project_meta(project_path: String) -> Result<ProjectMeta, String> {
+    read_project_meta(&PathBuf::from(&project_path))
+}
+
+/// Write a valid .incipit for a project whose metadata is unreadable or points at a root
+/// file that no longer exists. A file that doesn't parse is kept next to it as
+/// `.incipit.corrupt-<time>` and replaced by defaults with a detected root file; a
+/// readable one keeps everything but its root file.
+#[tauri::command]
+pub async fn repair_project_meta(project_path: String) -> Result<ProjectMeta, String> {
+    let project_dir = PathBuf::from(&project_path);
+    let meta_path = project_dir.join(".incipit");
+
+    let mut meta = match fs::read_to_string(&meta_path) {
+        Ok(content) => match serde_json::from_str(&content) {
+            Ok(meta) => meta,
+            Err(e) => {
+                eprintln!("Replacing unreadable project metadata: {}", e);
+                if storage::set_aside_corrupt(&meta_path).is_none() {
+                    return Err("Failed to back up the unreadable project metadata".to_string());
+                }
+                recovered_meta(&project_dir)
+            }
+        },
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => recovered_meta(&project_dir),
+        Err(e) => return Err(format!("Failed to read project metadata: {}", e)),
+    };
+
+    if !project_dir.join(&meta.root_file).is_file() {
+        if let Some(root) = detect_root_file(&project_dir, meta.allow_symlinks) {
+            meta.root_file = root;
+        }
+    }
+
+    write_project_meta(&project_dir, &meta)?;
+    Ok(meta)
+}
+
+#[tauri::command]
+pub async fn save_project_meta(project_path: String, meta: ProjectMeta) -> Result<(), String> {
+    write_project_meta(&PathBuf::from(&project_path), &meta)
+}
+
+/// Fast path for reopening a recent file at startup; the file tree can be loaded with
+/// open_project once it's actually shown
+#[tauri::command]
+pub async fn open_recent_file(
+    project_path: String,
+    file_path: String,
+) -> Result<OpenedFile, String> {
+    let project_dir = PathBuf::from(&project_path);
+    if !project_dir.is_dir() {
+        return Err(format!("Path is not a directory: {}", project_path));
+    }
+
+    let meta = read_project_meta(&project_dir)?;
+    let content = read_file(project_path, file_path.clone()).await?;
+
+    Ok(OpenedFile {
+        file_path,
+        content,
+        meta,
+    })
+}
+
+#[tauri::command]
+pub async fn record_recent_file(
+    project_path: String,
+    file_path: String,
+    cursor_line: Option<u32>,
+    cursor_column: Option<u32>,
+) -> Result<Vec<RecentFile>, String> {
+    let project_dir = PathBuf::from(&project_path);
+    let mut meta = read_project_meta(&project_dir)?;
+
+    // Reopening a file keeps its saved position unless a new one is given
+    let mut entry = take_recent_file(&mut meta, &file_path);
+    if let Some(line) = cursor_line {
+        entry.cursor_line = line;
+    }
+    if let Some(column) = cursor_column {
+        entry.cursor_column = column;
+    }
+    push_recent_file(&project_dir, &mut meta, entry);
+
+    write_project_meta(&project_dir, &meta)?;
+    Ok(meta.recent_files)
+}
+
+/// Remove `file_path`'s entry from the recent files, or make a fresh one
+fn take_recent_file(meta: &mut ProjectMeta, file_path: &str) -> RecentFile {
+    match meta.recent_files.iter().position(|f| f.path == file_path) {
+        Some(i) => meta.recent_files.remove(i),
+        None => RecentFile {
+            path: file_path.to_string(),
+            cursor_line: 0,
+            cursor_column: 0,
+            scroll_top: 0.0,
+        },
+    }
+}
+
+/// Put `entry` first in the recent files. Files that no longer exist are dropped and
+/// only the MAX_RECENT_FILES most recent are kept, so the list can't grow unbounded.
+fn push_recent_file(project_dir: &Path, meta: &mut ProjectMeta, entry: RecentFile) {
+    meta.last_opened_file = Some(entry.path.clone());
+    meta.recent_files.insert(0, entry);
+    meta.recent_files
+        .retain(|f| project_dir.join(&f.path).is_file());
+    meta.recent_files.truncate(MAX_RECENT_FILES);
+}
+
+/// The editor tabs to restore when a project is opened
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TabSession {
+    pub open_tabs: Vec<String>,
+    pub active_tab: Option<String>,
+}
+
+/// Drop tabs whose files no longer exist, and repeats. A closed active tab passes to
+/// the first one left. Returns whether anything changed.
+fn prune_tabs(project_dir: &Path, meta: &mut ProjectMeta) -> bool {
+    let before = (meta.open_tabs.clone(), meta.active_tab.clone());
+    let mut seen = HashSet::new();
+    meta.open_tabs
+        .retain(|tab| seen.insert(tab.clone()) && project_dir.join(tab).is_file());
+    if meta
+        .active_tab
+        .as_ref()
+        .is_none_or(|active| !meta.open_tabs.contains(active))
+    {
+        meta.active_tab = meta.open_tabs.first().cloned();
+    }
+    (meta.open_tabs.clone(), meta.active_tab.clone()) != before
+}
+
+/// Remember the files open in tabs, in tab order, and which one is focused. Paths
+/// are project-relative; files that don't exist are left out.
+#[tauri::command]
+pub async fn save_open_tabs(
+    project_path: String,
+    open_tabs: Vec<String>,
+    active_tab: Option<String>,
+) -> Result<TabSession, String> {
+    let project_dir = PathBuf::from(&project_path);
+    let open_tabs = open_tabs
+        .iter()
+        .map(|tab| latex::project_file(tab))
+        .collect::<Result<Vec<_>, _>>()?;
+    let active_tab = active_tab
+        .map(|tab| latex::project_file(&tab))
+        .transpose()?;
+    if let Some(active) = active_tab.as_ref().filter(|tab| !open_tabs.contains(tab)) {
+        return Err(format!("Active tab is not among the open tabs: {}", active));
+    }
+
+    let mut meta = read_project_meta(&project_dir)?;
+    meta.open_tabs = open_tabs;
+    meta.active_tab = active_tab;
+    prune_tabs(&project_dir, &mut meta);
+    write_project_meta(&project_dir, &meta)?;
+
+    Ok(TabSession {
+        open_tabs: meta.open_tabs,
+        active_tab: meta.active_tab,
+    })
+}
+
+/// The tabs to reopen with the project, without files deleted or moved since they
+/// were saved
+#[tauri::command]
+pub async fn load_open_tabs(project_path: String) -> Result<TabSession, String> {
+    let project_dir = PathBuf::from(&project_path);
+    let mut meta = read_project_meta(&project_dir)?;
+    if prune_tabs(&project_dir, &mut meta) && project_dir.join(".incipit").exists() {
+        write_project_meta(&project_dir, &meta)?;
+    }
+
+    Ok(TabSession {
+        open_tabs: meta.open_tabs,
+        active_tab: meta.active_tab,
+    })
+}
+
+/// Remember where the editor was in `file_path`. This counts as using the file, so it
+/// moves to the front of the recent files.
+#[tauri::command]
+pub async fn save_editor_state(
+    project_path: String,
+    file_path: String,
+    cursor_line: u32,
+    cursor_column: u32,
+    scroll_top: f64,
+) -> Result<(), String> {
+    let project_dir = PathBuf::from(&project_path);
+    let mut meta = read_project_meta(&project_dir)?;
+
+    let mut entry = take_recent_file(&mut meta, &file_path);
+    entry.cursor_line = cursor_line;
+    entry.cursor_column = cursor_column;
+    entry.scroll_top = scroll_top;
+    push_recent_file(&project_dir, &mut meta, entry);
+
+    write_project_meta(&project_dir, &meta)
+}
+
+/// Saved editor position for `file_path`, if it is among the recent files
+#[tauri::command]
+pub async fn load_editor_state(
+    project_path: String,
+    file_path: String,
+) -> Result<Option<RecentFile>, String> {
+    let meta = read_project_meta(&PathBuf::from(&project_path))?;
+    Ok(meta.recent_files.into_iter().find(|f| f.path == file_path))
+}
+
+#[tauri::command]
+pub async fn add_bookmark(
+    project_path: String,
+    file_path: String,
+    label: String,
+) -> Result<Vec<Bookmark>, String> {
+    let project_dir = PathBuf::from(&project_path);
+    let file_path = latex::project_file(&file_path)?;
+
+    if !project_dir.join(&file_path).is_file() {
+        return Err(format!("File not found: {}", file_path));
     }
 
-    // Create build directory
-    let build_dir = project_dir.join("build");
-    fs::create_dir_all(&build_dir)
-        .map_err(|e| format!("Failed to create build directory: {}", e))?;
+    let mut meta = read_project_meta(&project_dir)?;
 
-    // Create default main.tex file
-    let main_tex_path = project_dir.join("main.tex");
-    let default_content = r#"\documentclass{article}
+    // Re-bookmarking a file just updates its label, and the path of one saved before
+    // paths were normalized
+    let same_file = |b: &Bookmark| latex::project_file(&b.file_path).is_ok_and(|p| p == file_path);
+    match meta.bookmarks.iter_mut().find(|b| same_file(b)) {
+        Some(existing) => {
+            existing.file_path = file_path;
+            existing.label = label;
+        }
+        None => meta.bookmarks.push(Bookmark {
+            file_path,
+            label,
+            created_at: chrono::Utc::now().to_rfc3339(),
+        }),
+    }
+
+    write_project_meta(&project_dir, &meta)?;
+    Ok(meta.bookmarks)
+}
+
+#[tauri::command]
+pub async fn list_bookmarks(project_path: String) -> Result<Vec<Bookmark>, String> {
+    Ok(read_project_meta(&PathBuf::from(&project_path))?.bookmarks)
+}
+
+#[tauri::command]
+pub async fn remove_bookmark(
+    project_path: String,
+    file_path: String,
+) -> Result<Vec<Bookmark>, String> {
+    let project_dir = PathBuf::from(&project_path);
+    let file_path = latex::project_file(&file_path)?;
+    let mut meta = read_project_meta(&project_dir)?;
+
+    meta.bookmarks
+        .retain(|b| !latex::project_file(&b.file_path).is_ok_and(|p| p == file_path));
+
+    write_project_meta(&project_dir, &meta)?;
+    Ok(meta.bookmarks)
+}
+
+/// `word` trimmed, or an error unless it is a single word
+fn personal_word(word: &str) -> Result<String, String> {
+    let word = word.trim();
+    if word.is_empty() || word.contains(char::is_whitespace) {
+        return Err("A personal word must be a single word".to_string());
+    }
+    Ok(word.to_string())
+}
+
+#[tauri::command]
+pub async fn add_personal_word(project_path: String, word: String) -> Result<Vec<String>, String> {
+    let word = personal_word(&word)?;
+    let project_dir = PathBuf::from(&project_path);
+    let mut meta = read_project_meta(&project_dir)?;
+
+    if let Err(i) = meta.personal_words.binary_search(&word) {
+        meta.personal_words.insert(i, word);
+        write_project_meta(&project_dir, &meta)?;
+    }
+    Ok(meta.personal_words)
+}
+
+#[tauri::command]
+pub async fn remove_personal_word(
+    project_path: String,
+    word: String,
+) -> Result<Vec<String>, String> {
+    let project_dir = PathBuf::from(&project_path);
+    let mut meta = read_project_meta(&project_dir)?;
+
+    meta.personal_words.retain(|w| *w != word);
+
+    write_project_meta(&project_dir, &meta)?;
+    Ok(meta.personal_words)
+}
+
+/// Which word list add_to_dictionary and remove_from_dictionary change
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DictionaryScope {
+    /// The project's personal words, kept in .incipit
+    Project,
+    /// Words accepted in every project, kept in the config directory
+    User,
+}
+
+/// The words spell_check accepts in a project on top of the language dictionary
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProjectDictionary {
+    pub project: Vec<String>,
+    pub user: Vec<String>,
+}
+
+fn project_dictionary(project_dir: &Path) -> Result<ProjectDictionary, String> {
+    Ok(ProjectDictionary {
+        project: read_project_meta(project_dir)?.personal_words,
+        user: read_user_words()?,
+    })
+}
+
+#[tauri::command]
+pub async fn load_project_dictionary(project_path: String) -> Result<ProjectDictionary, String> {
+    project_dictionary(Path::new(&project_path))
+}
+
+/// Accept `word` from now on, in this project only or, with DictionaryScope::User,
+/// in every project
+#[tauri::command]
+pub async fn add_to_dictionary(
+    project_path: String,
+    word: String,
+    scope: Option<DictionaryScope>,
+) -> Result<ProjectDictionary, String> {
+    let word = personal_word(&word)?;
+    let project_dir = PathBuf::from(&project_path);
+
+    match scope.unwrap_or(DictionaryScope::Project) {
+        DictionaryScope::Project => {
+            let mut meta = read_project_meta(&project_dir)?;
+            if let Err(i) = meta.personal_words.binary_search(&word) {
+                meta.personal_words.insert(i, word);
+                write_project_meta(&project_dir, &meta)?;
+            }
+        }
+        DictionaryScope::User => {
+            let mut words = read_user_words()?;
+            if let Err(i) = words.binary_search(&word) {
+                words.insert(i, word);
+                write_user_words(&words)?;
+            }
+        }
+    }
+    project_dictionary(&project_dir)
+}
+
+#[tauri::command]
+pub async fn remove_from_dictionary(
+    project_path: String,
+    word: String,
+    scope: Option<DictionaryScope>,
+) -> Result<ProjectDictionary, String> {
+    let project_dir = PathBuf::from(&project_path);
+
+    match scope.unwrap_or(DictionaryScope::Project) {
+        DictionaryScope::Project => {
+            let mut meta = read_project_meta(&project_dir)?;
+            meta.personal_words.retain(|w| *w != word);
+            write_project_meta(&project_dir, &meta)?;
+        }
+        DictionaryScope::User => {
+            let mut words = read_user_words()?;
+            words.retain(|w| *w != word);
+            write_user_words(&words)?;
+        }
+    }
+    project_dictionary(&project_dir)
+}
+
+/// Set the word count goal of `file_path`, or clear it with None or 0
+#[tauri::command]
+pub async fn set_word_target(
+    project_path: String,
+    file_path: String,
+    target: Option<usize>,
+) -> Result<BTreeMap<String, usize>, String> {
+    let project_dir = PathBuf::from(&project_path);
+    let file = latex::project_file(&file_path)?;
+    let mut meta = read_project_meta(&project_dir)?;
+
+    match target.filter(|t| *t > 0) {
+        Some(target) => meta.word_targets.insert(file, target),
+        None => meta.word_targets.remove(&file),
+    };
+
+    write_project_meta(&project_dir, &meta)?;
+    Ok(meta.word_targets)
+}
+
+/// Set the most pages the root file's PDF may have, or clear the limit with None or 0
+#[tauri::command]
+pub async fn set_page_limit(project_path: String, max_pages: Option<u32>) -> Result<(), String> {
+    let project_dir = PathBuf::from(&project_path);
+    let mut meta = read_project_meta(&project_dir)?;
+    meta.max_pages = max_pages.filter(|pages| *pages > 0);
+    write_project_meta(&project_dir, &meta)
+}
+
+/// The main.tex a new project starts with
+const DEFAULT_MAIN_TEX: &str = r#"\documentclass{article}
 \usepackage[utf8]{inputenc}
 \usepackage{graphicx}
 \usepackage{amsmath}
@@ -246,53 +2156,542 @@ Here's an example equation:
 
 \end{document}"#;
 
-    fs::write(&main_tex_path, default_content)
+/// What create_new_project has done so far, so a failure can put the directory back
+/// the way it was
+#[derive(Default)]
+struct Scaffold {
+    /// Files and directories that didn't exist before, in creation order
+    created: Vec<PathBuf>,
+    /// Files that were overwritten, with their previous content
+    replaced: Vec<(PathBuf, Vec<u8>)>,
+}
+
+impl Scaffold {
+    fn create_dir(&mut self, dir: &Path, what: &str) -> Result<(), String> {
+        if dir.is_dir() {
+            return Ok(());
+        }
+        fs::create_dir_all(dir).map_err(|e| format!("Failed to create {}: {}", what, e))?;
+        self.created.push(dir.to_path_buf());
+        Ok(())
+    }
+
+    /// Note that `path` is about to be written
+    fn track_file(&mut self, path: &Path) -> Result<(), String> {
+        if !path.exists() {
+            self.created.push(path.to_path_buf());
+            return Ok(());
+        }
+        let previous =
+            fs::read(path).map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+        self.replaced.push((path.to_path_buf(), previous));
+        Ok(())
+    }
+
+    /// Undo everything, newest first. Best effort: the original error is what gets
+    /// reported, so failures here are only logged.
+    fn rollback(self) {
+        for (path, previous) in self.replaced.into_iter().rev() {
+            if let Err(e) = fs::write(&path, previous) {
+                eprintln!("Failed to restore {}: {}", path.display(), e);
+            }
+        }
+        for path in self.created.into_iter().rev() {
+            let removed = if path.is_dir() {
+                fs::remove_dir_all(&path)
+            } else {
+                fs::remove_file(&path)
+            };
+            if let Err(e) = removed {
+                eprintln!("Failed to remove {}: {}", path.display(), e);
+            }
+        }
+    }
+}
+
+/// Whether `entry` is something create_new_project itself puts in a project: the build
+/// directory, or a main.tex nobody has edited yet
+fn is_scaffolding(entry: &fs::DirEntry) -> bool {
+    let path = entry.path();
+    match entry.file_name().to_str() {
+        Some("build") => path.is_dir(),
+        Some("main.tex") => fs::read_to_string(&path).is_ok_and(|c| c == DEFAULT_MAIN_TEX),
+        _ => false,
+    }
+}
+
+/// DEFAULT_MAIN_TEX with the author and document class from the settings, where set
+fn main_tex() -> Result<String, String> {
+    let settings = read_global_settings()?;
+    let mut main_tex = DEFAULT_MAIN_TEX.to_string();
+
+    let setting = |value: Option<String>| {
+        value
+            .map(|value| value.trim().to_string())
+            .filter(|value| !value.is_empty())
+    };
+    if let Some(class) = setting(settings.default_document_class) {
+        main_tex = main_tex.replacen(
+            "\\documentclass{article}",
+            &format!("\\documentclass{{{}}}", class),
+            1,
+        );
+    }
+    if let Some(author) = setting(settings.default_author) {
+        main_tex = main_tex.replacen(
+            "\\author{Your Name}",
+            &format!("\\author{{{}}}", latex::escape_text(&author)),
+            1,
+        );
+    }
+    Ok(main_tex)
+}
+
+fn write_scaffold(
+    project_dir: &Path,
+    force: bool,
+    scaffold: &mut Scaffold,
+) -> Result<FileNode, String> {
+    scaffold.create_dir(project_dir, "project directory")?;
+
+    // Check if directory is empty (or only has hidden files, or with `force`, only what a
+    // previous create left behind)
+    let entries: Vec<_> = fs::read_dir(project_dir)
+        .map_err(|e| format!("Failed to read project directory: {}", e))?
+        .filter_map(|e| e.ok())
+        .filter(|e| {
+            let name = e.file_name();
+            let name_str = name.to_string_lossy();
+            !name_str.starts_with('.')
+        })
+        .filter(|e| !(force && is_scaffolding(e)))
+        .collect();
+
+    if !entries.is_empty() {
+        return Err("Directory is not empty. Please choose an empty directory for the new project.".to_string());
+    }
+
+    scaffold.create_dir(&build_dir(project_dir)?, "build directory")?;
+
+    let main_tex_path = project_dir.join("main.tex");
+    scaffold.track_file(&main_tex_path)?;
+    fs::write(&main_tex_path, main_tex()?)
         .map_err(|e| format!("Failed to create main.tex: {}", e))?;
 
-    // Create .incipit metadata file
-    let meta = ProjectMeta::default();
-    save_project_meta(project_path.clone(), meta).await?;
+    scaffold.track_file(&project_dir.join(".incipit"))?;
+    write_project_meta(project_dir, &ProjectMeta::default())?;
 
-    // Build and return file tree
-    build_file_tree(&project_dir, &project_dir)
+    // Build and return file tree; a new project has no tree_ignore patterns yet
+    let ignore = PathPatterns::new(&[], "tree_ignore")?;
+    let mut links = SymlinkPolicy::new(project_dir, false);
+    let mut budget = TreeBudget::new(DEFAULT_MAX_TREE_ENTRIES);
+    build_file_tree(
+        project_dir,
+        project_dir,
+        &ignore,
+        false,
+        &mut links,
+        &mut budget,
+        0,
+    )
 }
 
+/// Set up a new project in an empty directory, creating it if needed. Either every file
+/// is created or, on failure, everything this call created is removed again. `force`
+/// reinitializes a directory holding nothing but an earlier project's scaffolding.
 #[tauri::command]
-pub async fn check_pdf_exists(project_path: String, file_path: String) -> Result<bool, String> {
+pub async fn create_new_project(
+    project_path: String,
+    force: Option<bool>,
+) -> Result<FileNode, String> {
     let project_dir = PathBuf::from(&project_path);
+    let mut scaffold = Scaffold::default();
 
-    // Get the PDF name from the tex file name
-    let pdf_name = PathBuf::from(&file_path)
-        .file_stem()
-        .ok_or("Invalid file path")?
-        .to_str()
-        .ok_or("Invalid file name")?
-        .to_string()
-        + ".pdf";
+    let result = write_scaffold(&project_dir, force.unwrap_or(false), &mut scaffold);
+    if result.is_err() {
+        scaffold.rollback();
+    }
+    result
+}
+
+/// Remember how `file_path` was last compiled. Only projects that have a .incipit keep
+/// this; opening a lone file must not leave one behind.
+pub(crate) fn record_last_compile(
+    project_dir: &Path,
+    file_path: &str,
+    last: LastCompile,
+) -> Result<(), String> {
+    if !project_dir.join(".incipit").exists() {
+        return Ok(());
+    }
+
+    let file_path = latex::project_file(file_path)?;
+    let mut meta = read_project_meta(project_dir)?;
+    meta.last_compiles.insert(file_path, last);
+    write_project_meta(project_dir, &meta)
+}
+
+/// Add a compile to the project's history, dropping the oldest past
+/// MAX_COMPILE_HISTORY. Like record_last_compile, only for projects with a .incipit.
+pub(crate) fn record_compile(project_dir: &Path, record: CompileRecord) -> Result<(), String> {
+    if !project_dir.join(".incipit").exists() {
+        return Ok(());
+    }
+
+    let mut meta = read_project_meta(project_dir)?;
+    meta.compile_history.push(record);
+    let excess = meta
+        .compile_history
+        .len()
+        .saturating_sub(MAX_COMPILE_HISTORY);
+    meta.compile_history.drain(..excess);
+    write_project_meta(project_dir, &meta)
+}
+
+/// Modification times of the files the tree shows, leaving out build/
+fn scan_tree(project_dir: &Path, meta: &ProjectMeta) -> Result<BTreeMap<String, u64>, String> {
+    let ignore = PathPatterns::new(&meta.tree_ignore, "tree_ignore")?;
+    let mut files = Vec::new();
+    let mut links = SymlinkPolicy::new(project_dir, meta.allow_symlinks);
+    collect_files(project_dir, project_dir, &ignore, &mut links, &mut files);
+
+    Ok(files
+        .into_iter()
+        .filter(|path| !path.starts_with("build/"))
+        .filter_map(|path| {
+            let modified = fs::metadata(project_dir.join(&path))
+                .and_then(|m| m.modified())
+                .ok()?
+                .duration_since(std::time::UNIX_EPOCH)
+                .ok()?;
+            Some((path, modified.as_millis() as u64))
+        })
+        .collect())
+}
+
+/// Store a snapshot of the project's files in its .incipit, returning when it was taken
+fn record_tree_snapshot(project_dir: &Path) -> Result<String, String> {
+    let mut meta = read_project_meta(project_dir)?;
+    let snapshot = TreeSnapshot {
+        taken_at: chrono::Utc::now().to_rfc3339(),
+        files: scan_tree(project_dir, &meta)?,
+    };
+    let taken_at = snapshot.taken_at.clone();
 
-    let pdf_path = project_dir.join("build").join(&pdf_name);
-    Ok(pdf_path.exists())
+    meta.tree_snapshots.push(snapshot);
+    let excess = meta.tree_snapshots.len().saturating_sub(MAX_TREE_SNAPSHOTS);
+    meta.tree_snapshots.drain(..excess);
+    write_project_meta(project_dir, &meta)?;
+    Ok(taken_at)
 }
 
 #[tauri::command]
-pub async fn load_pdf(project_path: String, file_path: String) -> Result<Vec<u8>, String> {
+pub async fn snapshot_tree(project_path: String) -> Result<String, String> {
+    record_tree_snapshot(&PathBuf::from(&project_path))
+}
+
+/// Files added, modified or deleted since the latest snapshot taken at or before
+/// `since_timestamp` (RFC 3339), sorted by path
+#[tauri::command]
+pub async fn tree_diff(
+    project_path: String,
+    since_timestamp: String,
+) -> Result<Vec<FileChange>, String> {
     let project_dir = PathBuf::from(&project_path);
+    let since = chrono::DateTime::parse_from_rfc3339(&since_timestamp)
+        .map_err(|e| format!("Invalid timestamp {}: {}", since_timestamp, e))?;
+
+    let meta = read_project_meta(&project_dir)?;
+    let snapshot = meta
+        .tree_snapshots
+        .iter()
+        .rev()
+        .find(|snapshot| {
+            chrono::DateTime::parse_from_rfc3339(&snapshot.taken_at)
+                .is_ok_and(|taken_at| taken_at <= since)
+        })
+        .ok_or_else(|| format!("No file tree snapshot from before {}", since_timestamp))?;
+
+    let current = scan_tree(&project_dir, &meta)?;
+    let mut changes = Vec::new();
+    for (path, modified) in &current {
+        let kind = match snapshot.files.get(path) {
+            None => ChangeKind::Added,
+            Some(before) if before != modified => ChangeKind::Modified,
+            Some(_) => continue,
+        };
+        changes.push(FileChange {
+            path: path.clone(),
+            kind,
+        });
+    }
+    for path in snapshot.files.keys() {
+        if !current.contains_key(path) {
+            changes.push(FileChange {
+                path: path.clone(),
+                kind: ChangeKind::Deleted,
+            });
+        }
+    }
 
-    // Get the PDF name from the tex file name
-    let pdf_name = PathBuf::from(&file_path)
+    changes.sort_by(|a, b| a.path.cmp(&b.path));
+    Ok(changes)
+}
+
+#[tauri::command]
+pub async fn list_profiles(project_path: String) -> Result<Vec<NamedProfile>, String> {
+    let meta = read_project_meta(&PathBuf::from(&project_path))?;
+    Ok(meta
+        .profiles
+        .into_iter()
+        .map(|(name, profile)| NamedProfile { name, profile })
+        .collect())
+}
+
+/// Base name of the files built from `file_path`: its jobname if one was set, otherwise
+/// the file's stem
+pub(crate) fn output_name(meta: &ProjectMeta, file_path: &str) -> Result<String, String> {
+    if let Some(jobname) = meta.jobnames.get(&latex::project_file(file_path)?) {
+        return Ok(jobname.clone());
+    }
+
+    Ok(PathBuf::from(file_path)
         .file_stem()
         .ok_or("Invalid file path")?
         .to_str()
         .ok_or("Invalid file name")?
-        .to_string()
-        + ".pdf";
+        .to_string())
+}
+
+/// Remember `jobname` as the output base name of `file_path`. An empty jobname, or the
+/// file's own stem, goes back to the default.
+pub(crate) fn set_jobname(
+    project_dir: &Path,
+    file_path: &str,
+    jobname: &str,
+) -> Result<(), String> {
+    let jobname = jobname.trim();
+    if jobname.starts_with('.') || jobname.contains(['/', '\\', ':']) {
+        return Err(format!("Invalid jobname: {}", jobname));
+    }
+
+    let file = latex::project_file(file_path)?;
+    let stem = Path::new(&file).file_stem().and_then(|s| s.to_str());
+
+    let mut meta = read_project_meta(project_dir)?;
+    let jobname = (!jobname.is_empty() && stem != Some(jobname)).then(|| jobname.to_string());
+    if meta.jobnames.get(&file) == jobname.as_ref() {
+        return Ok(());
+    }
+    match jobname {
+        Some(jobname) => meta.jobnames.insert(file, jobname),
+        None => meta.jobnames.remove(&file),
+    };
+    write_project_meta(project_dir, &meta)
+}
 
-    let pdf_path = project_dir.join("build").join(&pdf_name);
+/// FNV-1a, which unlike DefaultHasher stays the same across runs and Rust versions
+fn stable_hash(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf29ce484222325, |hash, byte| {
+        (hash ^ u64::from(*byte)).wrapping_mul(0x100000001b3)
+    })
+}
+
+/// A new random project identifier: 32 hex digits, from the path and time it is created
+/// at mixed with the process's randomly seeded hasher
+fn new_project_id(project_dir: &Path) -> String {
+    use std::hash::BuildHasher;
+
+    let seed = format!(
+        "{}\n{}\n{}",
+        project_dir.display(),
+        chrono::Utc::now().timestamp_nanos_opt().unwrap_or_default(),
+        std::process::id()
+    );
+    let random = std::collections::hash_map::RandomState::new().hash_one(&seed);
+    format!("{:016x}{:016x}", stable_hash(seed.as_bytes()), random)
+}
+
+/// A stable key for the project. The identifier stored in .incipit, or one created and
+/// stored there now, so it follows the project when the folder moves; copies of the
+/// folder share it. Without .incipit, e.g. for a single file, it is a hash of the
+/// canonical path instead, prefixed with `path-`.
+pub(crate) fn project_id_for(project_dir: &Path) -> Result<String, String> {
+    if !project_dir.join(".incipit").exists() {
+        let project_dir = project_dir
+            .canonicalize()
+            .unwrap_or_else(|_| project_dir.to_path_buf());
+        let hash = stable_hash(project_dir.to_string_lossy().as_bytes());
+        return Ok(format!("path-{:016x}", hash));
+    }
+
+    let mut meta = read_project_meta(project_dir)?;
+    if let Some(id) = &meta.project_id {
+        return Ok(id.clone());
+    }
+    let id = new_project_id(project_dir);
+    meta.project_id = Some(id.clone());
+    write_project_meta(project_dir, &meta)?;
+    Ok(id)
+}
+
+#[tauri::command]
+pub async fn project_id(project_path: String) -> Result<String, String> {
+    project_id_for(Path::new(&project_path))
+}
+
+/// Where the project's outputs and intermediate files go: its own build/, or a folder
+/// keyed by the project's path under the `build_root` setting
+pub(crate) fn build_dir(project_dir: &Path) -> Result<PathBuf, String> {
+    let Some(build_root) = read_global_settings()?.build_root else {
+        return Ok(project_dir.join("build"));
+    };
+    let build_root = PathBuf::from(build_root);
+    if !build_root.is_absolute() {
+        return Err(format!(
+            "Build root must be an absolute path: {}",
+            build_root.display()
+        ));
+    }
+
+    // The same project reached through another path, e.g. a symlink, shares its builds
+    let project_dir = project_dir
+        .canonicalize()
+        .unwrap_or_else(|_| project_dir.to_path_buf());
+    let name = project_dir
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_else(|| "project".to_string());
+    let hash = stable_hash(project_dir.to_string_lossy().as_bytes());
+
+    Ok(build_root.join(format!("{}-{:016x}", name, hash)))
+}
+
+#[tauri::command]
+pub async fn check_pdf_exists(project_path: String, file_path: String) -> Result<bool, String> {
+    let project_dir = PathBuf::from(&project_path);
+
+    let meta = read_project_meta(&project_dir)?;
+    let pdf_name = output_name(&meta, &file_path)? + ".pdf";
+
+    let pdf_path = build_dir(&project_dir)?.join(&pdf_name);
+    Ok(pdf_path.exists() || pdf_path.with_extension("pdf.gz").exists())
+}
+
+/// Gzip streams start with these two bytes
+const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+
+/// Locate the built PDF for `file_path`, which may have been stored gzip-compressed as
+/// `<name>.pdf.gz`, and make sure it resolves inside the project's build directory
+pub(crate) fn find_built_pdf(project_path: &str, file_path: &str) -> Result<PathBuf, String> {
+    let project_dir = PathBuf::from(project_path);
+
+    let meta = read_project_meta(&project_dir)?;
+    let pdf_name = output_name(&meta, file_path)? + ".pdf";
+
+    let build_dir = build_dir(&project_dir)?;
+    let pdf_path = [
+        build_dir.join(&pdf_name),
+        build_dir.join(format!("{}.gz", pdf_name)),
+    ]
+    .into_iter()
+    .find(|path| path.exists())
+    .ok_or_else(|| format!("PDF not found at: {}", build_dir.join(&pdf_name).display()))?;
 
-    if !pdf_path.exists() {
-        return Err(format!("PDF not found at: {}", pdf_path.display()));
+    // Security check: ensure the PDF is within the build directory, which may be
+    // outside the project
+    let canonical_build = build_dir
+        .canonicalize()
+        .map_err(|e| format!("Invalid build path: {}", e))?;
+
+    let canonical_pdf = pdf_path
+        .canonicalize()
+        .map_err(|e| format!("Invalid PDF path: {}", e))?;
+
+    if !canonical_pdf.starts_with(&canonical_build) {
+        return Err("Access denied: PDF is outside build directory".to_string());
+    }
+
+    Ok(pdf_path)
+}
+
+/// Read a PDF, decompressing it first if the file is gzipped (whatever its extension)
+pub(crate) fn read_pdf_bytes(pdf_path: &Path) -> Result<Vec<u8>, String> {
+    let bytes = fs::read(pdf_path).map_err(|e| format!("Failed to read PDF: {}", e))?;
+
+    if !bytes.starts_with(&GZIP_MAGIC) {
+        return Ok(bytes);
     }
 
-    fs::read(&pdf_path)
-        .map_err(|e| format!("Failed to read PDF: {}", e))
+    let mut pdf = Vec::new();
+    GzDecoder::new(bytes.as_slice())
+        .read_to_end(&mut pdf)
+        .map_err(|e| format!("Failed to decompress PDF: {}", e))?;
+
+    Ok(pdf)
+}
+
+#[tauri::command]
+pub async fn load_pdf(project_path: String, file_path: String) -> Result<Vec<u8>, String> {
+    let pdf_path = find_built_pdf(&project_path, &file_path)?;
+    read_pdf_bytes(&pdf_path)
+}
+
+/// Open the built PDF of `file_path` in the system's default PDF viewer. A gzipped
+/// build is unpacked to the temp directory first, since viewers can't read it.
+#[tauri::command]
+pub async fn open_pdf_external(project_path: String, file_path: String) -> Result<(), String> {
+    let pdf_path = find_built_pdf(&project_path, &file_path)
+        .map_err(|e| format!("{} has no built PDF, compile it first: {}", file_path, e))?;
+
+    let pdf_path = if pdf_path.extension().is_some_and(|e| e == "gz") {
+        let name = pdf_path.file_stem().unwrap_or_default();
+        let unpacked = std::env::temp_dir().join(name);
+        fs::write(&unpacked, read_pdf_bytes(&pdf_path)?)
+            .map_err(|e| format!("Failed to unpack PDF: {}", e))?;
+        unpacked
+    } else {
+        pdf_path
+    };
+
+    tauri_plugin_opener::open_path(&pdf_path, None::<&str>)
+        .map_err(|e| format!("Failed to open {}: {}", pdf_path.display(), e))
+}
+
+/// Whether `file_path` or any file it includes was modified after `pdf_path`. Files
+/// whose modification time can't be read don't count.
+pub(crate) fn pdf_is_stale(
+    project_dir: &Path,
+    file_path: &str,
+    pdf_path: &Path,
+) -> Result<bool, String> {
+    let built = fs::metadata(pdf_path)
+        .and_then(|m| m.modified())
+        .map_err(|e| format!("Failed to read PDF modification time: {}", e))?;
+
+    let sources = latex::load_sources(project_dir, file_path)?;
+    Ok(sources.iter().any(|source| {
+        fs::metadata(project_dir.join(&source.path))
+            .and_then(|m| m.modified())
+            .is_ok_and(|modified| modified > built)
+    }))
+}
+
+/// Like load_pdf, but also reports whether the sources changed since the build
+#[tauri::command]
+pub async fn load_pdf_with_status(
+    project_path: String,
+    file_path: String,
+) -> Result<PdfWithStatus, String> {
+    let pdf_path = find_built_pdf(&project_path, &file_path)?;
+
+    tauri::async_runtime::spawn_blocking(move || {
+        let is_stale = pdf_is_stale(&PathBuf::from(&project_path), &file_path, &pdf_path)?;
+        Ok(PdfWithStatus {
+            bytes: read_pdf_bytes(&pdf_path)?,
+            is_stale,
+        })
+    })
+    .await
+    .map_err(|e| format!("Task join error: {}", e))?
 }
@@ -0,0 +1,138 @@
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::PathBuf;
+
+use super::project::{collect_files, read_project_meta, PathPatterns, SymlinkPolicy};
+
+/// Stop collecting after this many matches so a common word can't flood the UI
+const MAX_SEARCH_MATCHES: usize = 1000;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SearchMatch {
+    pub file: String,
+    pub line: usize,
+    /// 1-based, in characters
+    pub column: usize,
+    /// The whole matching line
+    pub text: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SearchResults {
+    pub matches: Vec<SearchMatch>,
+    /// Filters the search ran with, for display next to the results
+    pub include_globs: Vec<String>,
+    pub exclude_globs: Vec<String>,
+    pub files_searched: usize,
+    /// More matches exist than were returned
+    pub truncated: bool,
+}
+
+/// Whether `file` or one of the directories containing it matches `patterns`
+fn matches_file(patterns: &PathPatterns, file: &str) -> bool {
+    patterns.matches(file, false)
+        || file
+            .match_indices('/')
+            .any(|(i, _)| patterns.matches(&file[..i], true))
+}
+
+/// Every occurrence of `query` in `content`, as (line, column, line text)
+fn find_in_content(
+    content: &str,
+    query: &str,
+    case_sensitive: bool,
+) -> Vec<(usize, usize, String)> {
+    let query = if case_sensitive {
+        query.to_string()
+    } else {
+        query.to_lowercase()
+    };
+
+    let mut found = Vec::new();
+    for (i, line) in content.lines().enumerate() {
+        let haystack = if case_sensitive {
+            line.to_string()
+        } else {
+            line.to_lowercase()
+        };
+
+        for (offset, _) in haystack.match_indices(&query) {
+            let column = haystack[..offset].chars().count() + 1;
+            found.push((i + 1, column, line.to_string()));
+        }
+    }
+
+    found
+}
+
+/// Search the project's text files for `query`. Globs match project-relative paths, so
+/// `*.tex` keeps to LaTeX sources and `build/` skips the build output. Files hidden
+/// from the file tree are never searched.
+#[tauri::command]
+pub async fn search_project(
+    project_path: String,
+    query: String,
+    include_globs: Option<Vec<String>>,
+    exclude_globs: Option<Vec<String>>,
+    case_sensitive: Option<bool>,
+) -> Result<SearchResults, String> {
+    if query.is_empty() {
+        return Err("Search query cannot be empty".to_string());
+    }
+
+    let project_dir = PathBuf::from(&project_path);
+    let include_globs = include_globs.unwrap_or_default();
+    let exclude_globs = exclude_globs.unwrap_or_default();
+
+    let include = PathPatterns::new(&include_globs, "include_globs")?;
+    let exclude = PathPatterns::new(&exclude_globs, "exclude_globs")?;
+
+    let meta = read_project_meta(&project_dir)?;
+    let hidden = PathPatterns::new(&meta.tree_ignore, "tree_ignore")?;
+
+    let mut files = Vec::new();
+    let mut links = SymlinkPolicy::new(&project_dir, meta.allow_symlinks);
+    collect_files(&project_dir, &project_dir, &hidden, &mut links, &mut files);
+
+    let mut matches = Vec::new();
+    let mut files_searched = 0;
+    let mut truncated = false;
+
+    'files: for file in files {
+        if !include.is_empty() && !matches_file(&include, &file) {
+            continue;
+        }
+        if matches_file(&exclude, &file) {
+            continue;
+        }
+
+        // Binary files and other non-UTF-8 content can't contain the query as text
+        let Ok(content) = fs::read_to_string(project_dir.join(&file)) else {
+            continue;
+        };
+        files_searched += 1;
+
+        for (line, column, text) in
+            find_in_content(&content, &query, case_sensitive.unwrap_or(false))
+        {
+            if matches.len() == MAX_SEARCH_MATCHES {
+                truncated = true;
+                break 'files;
+            }
+            matches.push(SearchMatch {
+                file: file.clone(),
+                line,
+                column,
+                text,
+            });
+        }
+    }
+
+    Ok(SearchResults {
+        matches,
+        include_globs,
+        exclude_globs,
+        files_searched,
+        truncated,
+    })
+}
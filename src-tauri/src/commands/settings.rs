@@ -1,25 +1,178 @@
+use fs2::FileExt;
+use notify::{RecommendedWatcher, RecursiveMode, Watcher};
 use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
 use std::fs;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::Duration;
+use tauri::{AppHandle, Emitter};
+
+use crate::storage;
+
+/// Settings file content the app last wrote or announced, so its own saves and repeated
+/// events for a single external edit don't trigger a reload
+static LAST_SEEN_SETTINGS: Mutex<Option<String>> = Mutex::new(None);
+
+/// Keeps the settings watcher alive once started
+static SETTINGS_WATCHER: Mutex<Option<RecommendedWatcher>> = Mutex::new(None);
+
+/// Editors often write a file in several steps; give them time to finish before reading
+const SETTINGS_DEBOUNCE: Duration = Duration::from_millis(150);
+
+/// Unpinned projects kept on the start screen; pinned ones don't count
+const MAX_RECENT_PROJECTS: usize = 10;
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct EditorSettings {
+    /// Save the open file this often while it has unsaved changes; off when unset or 0
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub auto_save_interval_secs: Option<u64>,
+    /// Re-wrap prose lines longer than this many columns when a .tex file is saved;
+    /// off when unset or 0
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reflow_width: Option<u32>,
+    /// Remove spaces and tabs at the end of lines when a file is saved, outside verbatim
+    #[serde(default)]
+    pub trim_trailing_whitespace: bool,
+    /// Line endings to save files with; each file keeps its own when unset
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub line_ending: Option<LineEnding>,
+    /// End every saved file with a newline
+    #[serde(default)]
+    pub insert_final_newline: bool,
+    /// Settings only the frontend reads, preserved as they are
+    #[serde(flatten)]
+    pub extra: serde_json::Map<String, serde_json::Value>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LineEnding {
+    Lf,
+    Crlf,
+}
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct GlobalSettings {
-    pub recent_projects: Vec<String>,
-    pub editor_settings: serde_json::Value,
+#[serde(from = "StoredRecentProject")]
+pub struct RecentProject {
+    pub path: String,
+    /// Listed first and never pruned
+    pub pinned: bool,
+    /// Shown instead of the path
+    pub display_name: Option<String>,
+}
+
+/// Older settings list recent projects as bare paths
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum StoredRecentProject {
+    Path(String),
+    Entry {
+        path: String,
+        #[serde(default)]
+        pinned: bool,
+        #[serde(default)]
+        display_name: Option<String>,
+    },
 }
 
-impl Default for GlobalSettings {
-    fn default() -> Self {
-        Self {
-            recent_projects: Vec::new(),
-            editor_settings: serde_json::json!({}),
+impl From<StoredRecentProject> for RecentProject {
+    fn from(stored: StoredRecentProject) -> Self {
+        match stored {
+            StoredRecentProject::Path(path) => RecentProject {
+                path,
+                pinned: false,
+                display_name: None,
+            },
+            StoredRecentProject::Entry {
+                path,
+                pinned,
+                display_name,
+            } => RecentProject {
+                path,
+                pinned,
+                display_name,
+            },
         }
     }
 }
 
-fn get_settings_path() -> Result<PathBuf, String> {
-    let config_dir = dirs::config_dir()
-        .ok_or("Failed to determine config directory")?;
+/// A TeX bundle compiles can read packages from instead of Tectonic's default
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BundleSource {
+    pub name: String,
+    /// URL of an indexed tar bundle, or the path of a local zip bundle or directory
+    pub location: String,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct GlobalSettings {
+    /// Pinned projects first, then most recently opened first
+    pub recent_projects: Vec<RecentProject>,
+    pub editor_settings: EditorSettings,
+    /// Run the pre_build and post_build commands projects configure. Off by default so
+    /// opening someone else's project never runs its code.
+    #[serde(default)]
+    pub allow_hooks: bool,
+    /// Stop waiting for a compile after this many seconds; no limit when unset or 0
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub compile_timeout_secs: Option<u64>,
+    /// Most compiles, from any window, that run at the same time; later ones wait their
+    /// turn. Each compile keeps one core busy, as Tectonic is single-threaded. Compiles
+    /// that timed out stop counting, though they may still be running. No limit when
+    /// unset or 0.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub compile_thread_limit: Option<usize>,
+    /// Compile the project's root file whenever it or a file it includes is saved,
+    /// announcing the result with a `compile-finished` event
+    #[serde(default)]
+    pub compile_on_save: bool,
+    /// Absolute directory to build every project under, one folder per project, instead
+    /// of each project's own build/
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub build_root: Option<String>,
+    /// Days trash_path keeps files in a project's trash before deleting them for good;
+    /// 30 when unset, forever when 0
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub trash_retention_days: Option<u64>,
+    /// Bundles to choose from besides Tectonic's default, e.g. an older TeX Live
+    /// snapshot a paper must keep building with
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub bundles: Vec<BundleSource>,
+    /// Name of the entry in `bundles` to compile with; Tectonic's default when unset.
+    /// Tectonic.toml documents always use the bundle they name.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub active_bundle: Option<String>,
+    /// Author new projects' main.tex names, instead of a placeholder
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub default_author: Option<String>,
+    /// Class new projects' main.tex uses instead of article, e.g. scrartcl
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub default_document_class: Option<String>,
+    /// Free space, in MB, on a project's disk below which autosave and compile on save
+    /// stop writing and watch_disk_space emits `low-disk`; 100 when unset
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub low_disk_threshold_mb: Option<u64>,
+}
+
+impl GlobalSettings {
+    /// The bundle selected by `active_bundle`, or None for Tectonic's default
+    pub(crate) fn active_bundle_source(&self) -> Result<Option<&BundleSource>, String> {
+        let Some(name) = &self.active_bundle else {
+            return Ok(None);
+        };
+        self.bundles
+            .iter()
+            .find(|bundle| bundle.name == *name)
+            .map(Some)
+            .ok_or_else(|| format!("No bundle named {}", name))
+    }
+}
+
+/// Application config directory (e.g. ~/.config/incipit), created on demand
+pub(crate) fn get_config_dir() -> Result<PathBuf, String> {
+    let config_dir = dirs::config_dir().ok_or("Failed to determine config directory")?;
 
     let app_config_dir = config_dir.join("incipit");
 
@@ -29,11 +182,14 @@ fn get_settings_path() -> Result<PathBuf, String> {
             .map_err(|e| format!("Failed to create config directory: {}", e))?;
     }
 
-    Ok(app_config_dir.join("settings.json"))
+    Ok(app_config_dir)
 }
 
-#[tauri::command]
-pub async fn load_global_settings() -> Result<GlobalSettings, String> {
+pub(crate) fn get_settings_path() -> Result<PathBuf, String> {
+    Ok(get_config_dir()?.join("settings.json"))
+}
+
+pub(crate) fn read_global_settings() -> Result<GlobalSettings, String> {
     let settings_path = get_settings_path()?;
 
     if !settings_path.exists() {
@@ -43,17 +199,290 @@ pub async fn load_global_settings() -> Result<GlobalSettings, String> {
     let content = fs::read_to_string(&settings_path)
         .map_err(|e| format!("Failed to read settings: {}", e))?;
 
-    serde_json::from_str(&content)
-        .map_err(|e| format!("Failed to parse settings: {}", e))
+    // A corrupt file must not block startup
+    match serde_json::from_str(&content) {
+        Ok(settings) => Ok(settings),
+        Err(e) => {
+            eprintln!("Failed to parse settings, using defaults: {}", e);
+            storage::set_aside_corrupt(&settings_path);
+            Ok(GlobalSettings::default())
+        }
+    }
+}
+
+/// Words the spell checker accepts in every project, one per line and kept sorted. A
+/// file of its own, so saving the settings from a window that loaded them earlier can't
+/// drop words added since.
+fn user_words_path() -> Result<PathBuf, String> {
+    Ok(get_config_dir()?.join("personal_words.txt"))
+}
+
+pub(crate) fn read_user_words() -> Result<Vec<String>, String> {
+    let path = user_words_path()?;
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+    let content =
+        fs::read_to_string(&path).map_err(|e| format!("Failed to read personal words: {}", e))?;
+    let mut words: Vec<String> = content
+        .lines()
+        .map(str::trim)
+        .filter(|word| !word.is_empty())
+        .map(str::to_string)
+        .collect();
+    // Also a file edited by hand
+    words.sort();
+    words.dedup();
+    Ok(words)
+}
+
+pub(crate) fn write_user_words(words: &[String]) -> Result<(), String> {
+    let mut content = words.join("\n");
+    content.push('\n');
+    storage::write_atomic(&user_words_path()?, content)
+        .map_err(|e| format!("Failed to write personal words: {}", e))
 }
 
 #[tauri::command]
-pub async fn save_global_settings(settings: GlobalSettings) -> Result<(), String> {
+pub async fn load_global_settings() -> Result<GlobalSettings, String> {
+    read_global_settings()
+}
+
+/// Read, change and write back the settings while holding a lock on them, so another
+/// window or instance updating them at the same time waits instead of having its
+/// changes overwritten
+fn update_global_settings<T>(
+    change: impl FnOnce(&mut GlobalSettings) -> Result<T, String>,
+) -> Result<T, String> {
+    let lock_path = get_config_dir()?.join("settings.json.lock");
+    let lock = fs::OpenOptions::new()
+        .create(true)
+        .truncate(false)
+        .write(true)
+        .open(&lock_path)
+        .map_err(|e| format!("Failed to open settings lock: {}", e))?;
+    lock.lock_exclusive()
+        .map_err(|e| format!("Failed to lock settings: {}", e))?;
+
+    // Unlocked when `lock` is dropped, also on error
+    let mut settings = read_global_settings()?;
+    let value = change(&mut settings)?;
+    write_global_settings(&settings)?;
+    Ok(value)
+}
+
+fn write_global_settings(settings: &GlobalSettings) -> Result<(), String> {
     let settings_path = get_settings_path()?;
 
-    let content = serde_json::to_string_pretty(&settings)
+    let content = serde_json::to_string_pretty(settings)
         .map_err(|e| format!("Failed to serialize settings: {}", e))?;
 
-    fs::write(&settings_path, content)
+    *LAST_SEEN_SETTINGS.lock().unwrap() = Some(content.clone());
+
+    storage::write_atomic(&settings_path, &content)
         .map_err(|e| format!("Failed to write settings: {}", e))
 }
+
+#[tauri::command]
+pub async fn save_global_settings(settings: GlobalSettings) -> Result<(), String> {
+    if let Some(build_root) = &settings.build_root {
+        if !Path::new(build_root).is_absolute() {
+            return Err(format!(
+                "Build root must be an absolute path: {}",
+                build_root
+            ));
+        }
+    }
+
+    let mut names = HashSet::new();
+    for bundle in &settings.bundles {
+        if bundle.name.trim().is_empty() || bundle.location.trim().is_empty() {
+            return Err("Bundles need a name and a location".to_string());
+        }
+        if !names.insert(bundle.name.as_str()) {
+            return Err(format!("Bundle name used twice: {}", bundle.name));
+        }
+    }
+    settings.active_bundle_source()?;
+
+    if let Some(class) = &settings.default_document_class {
+        let valid = class
+            .trim()
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+        if !valid {
+            return Err(format!("Invalid document class name: {}", class));
+        }
+    }
+
+    // Every other field is taken as sent, but the window sending them may not have
+    // seen projects another window opened since it loaded the settings
+    update_global_settings(|current| {
+        let saved = std::mem::take(&mut current.recent_projects);
+        *current = settings;
+        merge_recent_projects(&mut current.recent_projects, saved);
+        tidy_recent_projects(&mut current.recent_projects);
+        Ok(())
+    })
+}
+
+/// Add the projects in `other` that `projects` doesn't list, after its own. The
+/// entries already in `projects` win, with their pin and name.
+fn merge_recent_projects(projects: &mut Vec<RecentProject>, other: Vec<RecentProject>) {
+    for project in other {
+        if !projects.iter().any(|p| p.path == project.path) {
+            projects.push(project);
+        }
+    }
+}
+
+/// Restore the order of the recent projects and drop the unpinned ones past
+/// MAX_RECENT_PROJECTS
+fn tidy_recent_projects(projects: &mut Vec<RecentProject>) {
+    // Stable, so each group keeps its most-recent-first order
+    projects.sort_by_key(|project| !project.pinned);
+    let mut unpinned = 0;
+    projects.retain(|project| {
+        unpinned += usize::from(!project.pinned);
+        project.pinned || unpinned <= MAX_RECENT_PROJECTS
+    });
+}
+
+/// Apply `change` to the recent projects under the settings lock, then tidy them
+fn change_recent_projects(
+    change: impl FnOnce(&mut Vec<RecentProject>) -> Result<(), String>,
+) -> Result<Vec<RecentProject>, String> {
+    update_global_settings(|settings| {
+        change(&mut settings.recent_projects)?;
+        tidy_recent_projects(&mut settings.recent_projects);
+        Ok(settings.recent_projects.clone())
+    })
+}
+
+/// Store the recent projects a window has, merged with the ones saved since it loaded
+/// them: its own entries come first and win, others are kept after them
+#[tauri::command]
+pub async fn update_recent_projects(
+    recent_projects: Vec<RecentProject>,
+) -> Result<Vec<RecentProject>, String> {
+    change_recent_projects(|projects| {
+        let saved = std::mem::replace(projects, recent_projects);
+        merge_recent_projects(projects, saved);
+        Ok(())
+    })
+}
+
+fn find_recent<'a>(
+    projects: &'a mut [RecentProject],
+    path: &str,
+) -> Result<&'a mut RecentProject, String> {
+    projects
+        .iter_mut()
+        .find(|project| project.path == path)
+        .ok_or_else(|| format!("Not a recent project: {}", path))
+}
+
+/// Move `path` to the front of the recent projects, adding it if needed
+#[tauri::command]
+pub async fn add_recent_project(path: String) -> Result<Vec<RecentProject>, String> {
+    change_recent_projects(|projects| {
+        let project = match projects.iter().position(|project| project.path == path) {
+            Some(index) => projects.remove(index),
+            None => RecentProject {
+                path,
+                pinned: false,
+                display_name: None,
+            },
+        };
+        projects.insert(0, project);
+        Ok(())
+    })
+}
+
+#[tauri::command]
+pub async fn pin_recent_project(path: String, pinned: bool) -> Result<Vec<RecentProject>, String> {
+    change_recent_projects(|projects| {
+        find_recent(projects, &path)?.pinned = pinned;
+        Ok(())
+    })
+}
+
+/// Set the name the start screen shows for `path`; an empty name goes back to the path
+#[tauri::command]
+pub async fn rename_recent_project(
+    path: String,
+    display_name: Option<String>,
+) -> Result<Vec<RecentProject>, String> {
+    let display_name = display_name
+        .map(|name| name.trim().to_string())
+        .filter(|name| !name.is_empty());
+    change_recent_projects(|projects| {
+        find_recent(projects, &path)?.display_name = display_name;
+        Ok(())
+    })
+}
+
+/// Compile with the bundle called `name` from the configured bundles, or with Tectonic's
+/// default when None. Cached formats are keyed by bundle, so each bundle keeps its own
+/// and switching back doesn't rebuild them.
+#[tauri::command]
+pub async fn set_active_bundle(name: Option<String>) -> Result<(), String> {
+    update_global_settings(|settings| {
+        settings.active_bundle = name;
+        settings.active_bundle_source()?;
+        Ok(())
+    })
+}
+
+/// Emit `settings-changed` whenever settings.json is modified outside this app, e.g. by
+/// hand or from another window. Calling it again while already watching is a no-op.
+#[tauri::command]
+pub async fn watch_settings(app: AppHandle) -> Result<(), String> {
+    let mut watcher_slot = SETTINGS_WATCHER.lock().unwrap();
+    if watcher_slot.is_some() {
+        return Ok(());
+    }
+
+    let config_dir = get_config_dir()?;
+    let settings_path = get_settings_path()?;
+    *LAST_SEEN_SETTINGS.lock().unwrap() = fs::read_to_string(&settings_path).ok();
+
+    let mut watcher = notify::recommended_watcher(move |result: notify::Result<notify::Event>| {
+        let Ok(event) = result else {
+            return;
+        };
+        if event.kind.is_access()
+            || !event
+                .paths
+                .iter()
+                .any(|p| p.file_name() == settings_path.file_name())
+        {
+            return;
+        }
+
+        std::thread::sleep(SETTINGS_DEBOUNCE);
+        let Ok(content) = fs::read_to_string(&settings_path) else {
+            return;
+        };
+
+        let mut last_seen = LAST_SEEN_SETTINGS.lock().unwrap();
+        if last_seen.as_deref() == Some(content.as_str()) {
+            return;
+        }
+        *last_seen = Some(content);
+        drop(last_seen);
+
+        if let Err(e) = app.emit("settings-changed", ()) {
+            eprintln!("Failed to emit settings-changed: {}", e);
+        }
+    })
+    .map_err(|e| format!("Failed to watch settings: {}", e))?;
+
+    // Watch the directory rather than the file: saving by rename replaces the file
+    watcher
+        .watch(&config_dir, RecursiveMode::NonRecursive)
+        .map_err(|e| format!("Failed to watch settings: {}", e))?;
+
+    *watcher_slot = Some(watcher);
+    Ok(())
+}
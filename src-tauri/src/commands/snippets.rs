@@ -0,0 +1,354 @@
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::path::PathBuf;
+
+use super::settings::get_config_dir;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Snippet {
+    /// Stable identifier; generated on first save when left empty
+    #[serde(default)]
+    pub id: String,
+    pub name: String,
+    /// Snippet body, may contain `$1`, `${1}` or `${1:placeholder}` tab stops
+    pub body: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    /// Optional trigger word the editor can expand
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub prefix: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TabStopRange {
+    pub start: usize,
+    pub end: usize,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TabStop {
+    pub index: u32,
+    pub placeholder: String,
+    /// Every occurrence of this tab stop (the first is the primary, the rest mirror it)
+    pub ranges: Vec<TabStopRange>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExpandedSnippet {
+    pub text: String,
+    /// Tab stops in navigation order ($1, $2, ... with $0 last). Offsets are in characters.
+    pub tab_stops: Vec<TabStop>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum SnippetNode {
+    Text(String),
+    TabStop {
+        index: u32,
+        children: Vec<SnippetNode>,
+    },
+}
+
+fn get_snippets_path() -> Result<PathBuf, String> {
+    Ok(get_config_dir()?.join("snippets.json"))
+}
+
+fn read_snippets() -> Result<Vec<Snippet>, String> {
+    let snippets_path = get_snippets_path()?;
+
+    if !snippets_path.exists() {
+        return Ok(Vec::new());
+    }
+
+    let content = fs::read_to_string(&snippets_path)
+        .map_err(|e| format!("Failed to read snippets: {}", e))?;
+
+    serde_json::from_str(&content).map_err(|e| format!("Failed to parse snippets: {}", e))
+}
+
+fn write_snippets(snippets: &[Snippet]) -> Result<(), String> {
+    let snippets_path = get_snippets_path()?;
+
+    let content = serde_json::to_string_pretty(snippets)
+        .map_err(|e| format!("Failed to serialize snippets: {}", e))?;
+
+    fs::write(&snippets_path, content).map_err(|e| format!("Failed to write snippets: {}", e))
+}
+
+/// Read the digits of a tab stop index starting at `pos`
+fn parse_index(chars: &[char], pos: &mut usize) -> Option<u32> {
+    let start = *pos;
+    while *pos < chars.len() && chars[*pos].is_ascii_digit() {
+        *pos += 1;
+    }
+
+    if *pos == start {
+        return None;
+    }
+
+    chars[start..*pos].iter().collect::<String>().parse().ok()
+}
+
+/// Parse a snippet body into text and (possibly nested) tab stops.
+///
+/// Only `$N`, `${N}` and `${N:...}` are special; any other `$` is kept literally so
+/// math such as `$x^2$` survives. Inside a placeholder, braces are balanced so LaTeX
+/// groups like `${1:\textbf{x}}` parse as expected.
+fn parse_nodes(chars: &[char], pos: &mut usize, nested: bool) -> Vec<SnippetNode> {
+    let mut nodes = Vec::new();
+    let mut text = String::new();
+    let mut depth = 0usize;
+
+    while *pos < chars.len() {
+        let c = chars[*pos];
+
+        if c == '\\' && matches!(chars.get(*pos + 1), Some('$') | Some('}')) {
+            text.push(chars[*pos + 1]);
+            *pos += 2;
+            continue;
+        }
+
+        if c == '$' {
+            let mut lookahead = *pos + 1;
+
+            if let Some(index) = parse_index(chars, &mut lookahead) {
+                if !text.is_empty() {
+                    nodes.push(SnippetNode::Text(std::mem::take(&mut text)));
+                }
+                nodes.push(SnippetNode::TabStop {
+                    index,
+                    children: Vec::new(),
+                });
+                *pos = lookahead;
+                continue;
+            }
+
+            if chars.get(lookahead) == Some(&'{') {
+                lookahead += 1;
+                if let Some(index) = parse_index(chars, &mut lookahead) {
+                    match chars.get(lookahead) {
+                        Some('}') => {
+                            if !text.is_empty() {
+                                nodes.push(SnippetNode::Text(std::mem::take(&mut text)));
+                            }
+                            nodes.push(SnippetNode::TabStop {
+                                index,
+                                children: Vec::new(),
+                            });
+                            *pos = lookahead + 1;
+                            continue;
+                        }
+                        Some(':') => {
+                            if !text.is_empty() {
+                                nodes.push(SnippetNode::Text(std::mem::take(&mut text)));
+                            }
+                            *pos = lookahead + 1;
+                            let children = parse_nodes(chars, pos, true);
+                            nodes.push(SnippetNode::TabStop { index, children });
+                            continue;
+                        }
+                        _ => {}
+                    }
+                }
+            }
+        }
+
+        if nested {
+            if c == '{' {
+                depth += 1;
+            } else if c == '}' {
+                if depth == 0 {
+                    // Closing brace of the enclosing placeholder
+                    *pos += 1;
+                    break;
+                }
+                depth -= 1;
+            }
+        }
+
+        text.push(c);
+        *pos += 1;
+    }
+
+    if !text.is_empty() {
+        nodes.push(SnippetNode::Text(text));
+    }
+
+    nodes
+}
+
+fn parse_snippet(body: &str) -> Vec<SnippetNode> {
+    let chars: Vec<char> = body.chars().collect();
+    let mut pos = 0;
+    parse_nodes(&chars, &mut pos, false)
+}
+
+/// Plain text of a node list, ignoring tab stop boundaries
+fn flatten_nodes(nodes: &[SnippetNode]) -> String {
+    nodes
+        .iter()
+        .map(|node| match node {
+            SnippetNode::Text(text) => text.clone(),
+            SnippetNode::TabStop { children, .. } => flatten_nodes(children),
+        })
+        .collect()
+}
+
+/// Default text for each tab stop, taken from its first occurrence that has one
+fn collect_defaults(nodes: &[SnippetNode], defaults: &mut HashMap<u32, String>) {
+    for node in nodes {
+        if let SnippetNode::TabStop { index, children } = node {
+            if !children.is_empty() && !defaults.contains_key(index) {
+                defaults.insert(*index, flatten_nodes(children));
+            }
+            collect_defaults(children, defaults);
+        }
+    }
+}
+
+fn render_nodes(
+    nodes: &[SnippetNode],
+    values: &HashMap<u32, String>,
+    defaults: &HashMap<u32, String>,
+    out: &mut String,
+    offset: &mut usize,
+    stops: &mut BTreeMap<u32, TabStop>,
+) {
+    for node in nodes {
+        match node {
+            SnippetNode::Text(text) => {
+                out.push_str(text);
+                *offset += text.chars().count();
+            }
+            SnippetNode::TabStop { index, children } => {
+                let start = *offset;
+
+                if let Some(value) = values.get(index) {
+                    out.push_str(value);
+                    *offset += value.chars().count();
+                } else if !children.is_empty() {
+                    render_nodes(children, values, defaults, out, offset, stops);
+                } else if let Some(default) = defaults.get(index) {
+                    out.push_str(default);
+                    *offset += default.chars().count();
+                }
+
+                let placeholder = values
+                    .get(index)
+                    .or_else(|| defaults.get(index))
+                    .cloned()
+                    .unwrap_or_default();
+
+                stops
+                    .entry(*index)
+                    .or_insert_with(|| TabStop {
+                        index: *index,
+                        placeholder,
+                        ranges: Vec::new(),
+                    })
+                    .ranges
+                    .push(TabStopRange {
+                        start,
+                        end: *offset,
+                    });
+            }
+        }
+    }
+}
+
+/// Expand a snippet body, substituting `values` (keyed by tab stop index) for the
+/// defaults and reporting where each tab stop ended up in the output
+fn expand_body(body: &str, values: &HashMap<u32, String>) -> ExpandedSnippet {
+    let nodes = parse_snippet(body);
+
+    let mut defaults = HashMap::new();
+    collect_defaults(&nodes, &mut defaults);
+
+    let mut text = String::new();
+    let mut offset = 0;
+    let mut stops = BTreeMap::new();
+    render_nodes(
+        &nodes,
+        values,
+        &defaults,
+        &mut text,
+        &mut offset,
+        &mut stops,
+    );
+
+    for stop in stops.values_mut() {
+        stop.ranges.sort_by_key(|range| range.start);
+    }
+
+    // $0 is the final cursor position, so it goes last
+    let final_stop = stops.remove(&0);
+    let mut tab_stops: Vec<TabStop> = stops.into_values().collect();
+    tab_stops.extend(final_stop);
+
+    ExpandedSnippet { text, tab_stops }
+}
+
+#[tauri::command]
+pub async fn list_snippets() -> Result<Vec<Snippet>, String> {
+    let mut snippets = read_snippets()?;
+    snippets.sort_by_key(|s| s.name.to_lowercase());
+    Ok(snippets)
+}
+
+#[tauri::command]
+pub async fn save_snippet(snippet: Snippet) -> Result<Snippet, String> {
+    if snippet.name.trim().is_empty() {
+        return Err("Snippet name cannot be empty".to_string());
+    }
+
+    let mut snippet = snippet;
+    if snippet.id.is_empty() {
+        snippet.id = format!("snippet-{}", chrono::Utc::now().timestamp_millis());
+    }
+
+    let mut snippets = read_snippets()?;
+    match snippets.iter_mut().find(|s| s.id == snippet.id) {
+        Some(existing) => *existing = snippet.clone(),
+        None => snippets.push(snippet.clone()),
+    }
+
+    write_snippets(&snippets)?;
+    Ok(snippet)
+}
+
+#[tauri::command]
+pub async fn delete_snippet(id: String) -> Result<(), String> {
+    let mut snippets = read_snippets()?;
+    let before = snippets.len();
+    snippets.retain(|s| s.id != id);
+
+    if snippets.len() == before {
+        return Err(format!("Snippet not found: {}", id));
+    }
+
+    write_snippets(&snippets)
+}
+
+#[tauri::command]
+pub async fn expand_snippet(
+    id: String,
+    placeholders: Option<HashMap<String, String>>,
+) -> Result<ExpandedSnippet, String> {
+    let snippets = read_snippets()?;
+    let snippet = snippets
+        .iter()
+        .find(|s| s.id == id)
+        .ok_or_else(|| format!("Snippet not found: {}", id))?;
+
+    let mut values = HashMap::new();
+    for (key, value) in placeholders.unwrap_or_default() {
+        let index = key
+            .trim_start_matches('$')
+            .parse::<u32>()
+            .map_err(|_| format!("Invalid tab stop index: {}", key))?;
+        values.insert(index, value);
+    }
+
+    Ok(expand_body(&snippet.body, &values))
+}
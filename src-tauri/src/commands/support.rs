@@ -0,0 +1,63 @@
+use serde::{Deserialize, Serialize};
+use tauri::AppHandle;
+use tectonic::config::PersistentConfig;
+use tectonic_io_base::app_dirs;
+
+use super::settings::{get_config_dir, get_settings_path};
+
+/// Where incipit and Tectonic keep their files, for bug reports
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DiagnosticsInfo {
+    pub app_version: String,
+    pub tectonic_version: String,
+    pub os: String,
+    pub arch: String,
+    pub config_dir: String,
+    pub settings_path: String,
+    pub tectonic_config_path: Option<String>,
+    pub format_cache_path: Option<String>,
+    pub bundle_cache_path: Option<String>,
+    /// URL or path of the bundle Tectonic uses by default
+    pub default_bundle: Option<String>,
+}
+
+#[tauri::command]
+pub async fn diagnostics_info() -> Result<DiagnosticsInfo, String> {
+    // Report what we can: a broken Tectonic setup is exactly when this gets used
+    let config = PersistentConfig::open(false).ok();
+
+    Ok(DiagnosticsInfo {
+        app_version: env!("CARGO_PKG_VERSION").to_string(),
+        tectonic_version: env!("TECTONIC_VERSION").to_string(),
+        os: std::env::consts::OS.to_string(),
+        arch: std::env::consts::ARCH.to_string(),
+        config_dir: get_config_dir()?.display().to_string(),
+        settings_path: get_settings_path()?.display().to_string(),
+        tectonic_config_path: app_dirs::get_user_config()
+            .ok()
+            .map(|dir| dir.join("config.toml").display().to_string()),
+        format_cache_path: config
+            .as_ref()
+            .and_then(|c| c.format_cache_path().ok())
+            .map(|p| p.display().to_string()),
+        bundle_cache_path: app_dirs::get_user_cache_dir("bundles")
+            .ok()
+            .map(|p| p.display().to_string()),
+        default_bundle: config.as_ref().map(|c| c.default_bundle_loc().to_string()),
+    })
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Versions {
+    pub app: String,
+    /// Version of the linked tectonic crate, which determines bundle behavior
+    pub tectonic: String,
+}
+
+#[tauri::command]
+pub async fn versions(app: AppHandle) -> Result<Versions, String> {
+    Ok(Versions {
+        app: app.package_info().version.to_string(),
+        tectonic: env!("TECTONIC_VERSION").to_string(),
+    })
+}
@@ -0,0 +1,354 @@
+//! Diffs between two versions of a text: line diffs grouped into hunks the way unified
+//! diffs are, and counts of what changed, such as the words added and removed.
+//!
+//! The edit script is the shortest one, found with Myers' O(ND) algorithm after the
+//! lines both versions start and end with are set aside. Keeping each round of the
+//! search for the walk back costs memory quadratic in the number of edits, so past
+//! MAX_EDIT_DISTANCE the changed middle is reported as replaced wholesale; versions that
+//! different wouldn't read any better line by line.
+
+use serde::{Deserialize, Serialize};
+
+/// Unchanged lines shown around each change
+const CONTEXT_LINES: usize = 3;
+
+/// Edits searched for before the changed middle is treated as replaced
+const MAX_EDIT_DISTANCE: usize = 2000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Op {
+    Equal,
+    Delete,
+    Insert,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DiffLineKind {
+    Context,
+    Removed,
+    Added,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DiffLine {
+    pub kind: DiffLineKind,
+    /// 1-based line in the old version, None for added lines
+    pub old_line: Option<usize>,
+    /// 1-based line in the new version, None for removed lines
+    pub new_line: Option<usize>,
+    pub text: String,
+}
+
+/// A run of changes with the context around them. A side with no lines in the hunk has
+/// a count of 0 and a start of the line the hunk follows, 0 at the top of the file.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DiffHunk {
+    pub old_start: usize,
+    pub old_count: usize,
+    pub new_start: usize,
+    pub new_count: usize,
+    pub lines: Vec<DiffLine>,
+}
+
+/// Shortest edit script turning `old` into `new`, or None when it has more than `limit`
+/// edits
+fn myers<T: PartialEq>(old: &[T], new: &[T], limit: usize) -> Option<Vec<Op>> {
+    let (n, m) = (old.len() as isize, new.len() as isize);
+    let offset = limit as isize + 1;
+    let index = |k: isize| (k + offset) as usize;
+    // v[k] is the furthest x reached on diagonal k = x - y
+    let mut v = vec![0isize; 2 * limit + 3];
+    let mut trace: Vec<Vec<isize>> = Vec::new();
+
+    for d in 0..=limit as isize {
+        for k in (-d..=d).step_by(2) {
+            let mut x = if k == -d || (k != d && v[index(k - 1)] < v[index(k + 1)]) {
+                v[index(k + 1)]
+            } else {
+                v[index(k - 1)] + 1
+            };
+            let mut y = x - k;
+            while x < n && y < m && old[x as usize] == new[y as usize] {
+                x += 1;
+                y += 1;
+            }
+            v[index(k)] = x;
+
+            if x >= n && y >= m {
+                return Some(backtrack(&trace, n, m));
+            }
+        }
+        trace.push(v[index(-d)..=index(d)].to_vec());
+    }
+    None
+}
+
+/// Walk back from the end through the furthest points each round reached. `trace[d]`
+/// holds round d's points for diagonals -d to d.
+fn backtrack(trace: &[Vec<isize>], n: isize, m: isize) -> Vec<Op> {
+    let mut ops = Vec::new();
+    let (mut x, mut y) = (n, m);
+    for d in (1..=trace.len() as isize).rev() {
+        let previous = &trace[d as usize - 1];
+        let reached = |k: isize| previous[(k + d - 1) as usize];
+        let k = x - y;
+        let previous_k = if k == -d || (k != d && reached(k - 1) < reached(k + 1)) {
+            k + 1
+        } else {
+            k - 1
+        };
+        let previous_x = reached(previous_k);
+        let previous_y = previous_x - previous_k;
+
+        while x > previous_x && y > previous_y {
+            ops.push(Op::Equal);
+            x -= 1;
+            y -= 1;
+        }
+        if previous_k == k + 1 {
+            ops.push(Op::Insert);
+        } else {
+            ops.push(Op::Delete);
+        }
+        x = previous_x;
+        y = previous_y;
+    }
+    ops.extend(std::iter::repeat_n(Op::Equal, x as usize));
+    ops.reverse();
+    ops
+}
+
+fn edit_script<T: PartialEq>(old: &[T], new: &[T]) -> Vec<Op> {
+    let prefix = old.iter().zip(new).take_while(|(a, b)| a == b).count();
+    let suffix = old[prefix..]
+        .iter()
+        .rev()
+        .zip(new[prefix..].iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+    let old_middle = &old[prefix..old.len() - suffix];
+    let new_middle = &new[prefix..new.len() - suffix];
+
+    let mut ops = vec![Op::Equal; prefix];
+    let limit = (old_middle.len() + new_middle.len()).min(MAX_EDIT_DISTANCE);
+    match myers(old_middle, new_middle, limit) {
+        Some(middle) => ops.extend(middle),
+        None => {
+            ops.extend(std::iter::repeat_n(Op::Delete, old_middle.len()));
+            ops.extend(std::iter::repeat_n(Op::Insert, new_middle.len()));
+        }
+    }
+    ops.extend(std::iter::repeat_n(Op::Equal, suffix));
+    ops
+}
+
+/// How many items of `old` a shortest edit to `new` removes, and how many it adds, e.g.
+/// for the words of two versions of a text. Past MAX_EDIT_DISTANCE this overcounts, as
+/// everything between the unchanged start and end counts as replaced.
+pub fn count_changes<T: PartialEq>(old: &[T], new: &[T]) -> (usize, usize) {
+    let ops = edit_script(old, new);
+    let count = |kind| ops.iter().filter(|op| **op == kind).count();
+    (count(Op::Delete), count(Op::Insert))
+}
+
+/// The changes from `old` to `new` line by line, as hunks with CONTEXT_LINES of
+/// unchanged lines around them. Changes closer than twice that share a hunk. Empty
+/// when the texts have the same lines; line endings aren't compared.
+pub fn hunks(old: &str, new: &str) -> Vec<DiffHunk> {
+    let old: Vec<&str> = old.lines().collect();
+    let new: Vec<&str> = new.lines().collect();
+
+    let mut lines = Vec::new();
+    let (mut old_line, mut new_line) = (0, 0);
+    for op in edit_script(&old, &new) {
+        let line = match op {
+            Op::Equal => {
+                old_line += 1;
+                new_line += 1;
+                DiffLine {
+                    kind: DiffLineKind::Context,
+                    old_line: Some(old_line),
+                    new_line: Some(new_line),
+                    text: old[old_line - 1].to_string(),
+                }
+            }
+            Op::Delete => {
+                old_line += 1;
+                DiffLine {
+                    kind: DiffLineKind::Removed,
+                    old_line: Some(old_line),
+                    new_line: None,
+                    text: old[old_line - 1].to_string(),
+                }
+            }
+            Op::Insert => {
+                new_line += 1;
+                DiffLine {
+                    kind: DiffLineKind::Added,
+                    old_line: None,
+                    new_line: Some(new_line),
+                    text: new[new_line - 1].to_string(),
+                }
+            }
+        };
+        lines.push(line);
+    }
+
+    let changed: Vec<usize> = lines
+        .iter()
+        .enumerate()
+        .filter(|(_, line)| line.kind != DiffLineKind::Context)
+        .map(|(i, _)| i)
+        .collect();
+    let mut ranges: Vec<(usize, usize)> = Vec::new();
+    for &i in &changed {
+        match ranges.last_mut() {
+            Some((_, end)) if i <= *end + 2 * CONTEXT_LINES + 1 => *end = i,
+            _ => ranges.push((i, i)),
+        }
+    }
+
+    ranges
+        .into_iter()
+        .map(|(first, last)| {
+            let start = first.saturating_sub(CONTEXT_LINES);
+            let end = (last + CONTEXT_LINES + 1).min(lines.len());
+            let hunk_lines = lines[start..end].to_vec();
+            // The last line numbered on each side before the hunk
+            let before = |side: fn(&DiffLine) -> Option<usize>| {
+                lines[..start].iter().rev().find_map(side).unwrap_or(0)
+            };
+            let old_before = before(|line| line.old_line);
+            let new_before = before(|line| line.new_line);
+            let old_count = hunk_lines.iter().filter(|l| l.old_line.is_some()).count();
+            let new_count = hunk_lines.iter().filter(|l| l.new_line.is_some()).count();
+            DiffHunk {
+                old_start: if old_count == 0 {
+                    old_before
+                } else {
+                    old_before + 1
+                },
+                old_count,
+                new_start: if new_count == 0 {
+                    new_before
+                } else {
+                    new_before + 1
+                },
+                new_count,
+                lines: hunk_lines,
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn numbered(lines: std::ops::RangeInclusive<usize>) -> String {
+        lines.map(|i| format!("l{}\n", i)).collect()
+    }
+
+    fn without(lines: std::ops::RangeInclusive<usize>, removed: &[usize]) -> String {
+        lines
+            .filter(|i| !removed.contains(i))
+            .map(|i| format!("l{}\n", i))
+            .collect()
+    }
+
+    #[test]
+    fn empty_texts_have_no_hunks() {
+        assert!(hunks("", "").is_empty());
+        assert!(hunks("a\r\nb\r\n", "a\nb\n").is_empty());
+        assert_eq!(count_changes::<&str>(&[], &[]), (0, 0));
+    }
+
+    #[test]
+    fn insert_into_empty_text_starts_old_side_at_zero() {
+        let hunks = hunks("", "a\nb\n");
+        assert_eq!(hunks.len(), 1);
+        let hunk = &hunks[0];
+        assert_eq!((hunk.old_start, hunk.old_count), (0, 0));
+        assert_eq!((hunk.new_start, hunk.new_count), (1, 2));
+    }
+
+    #[test]
+    fn delete_everything_starts_new_side_at_zero() {
+        let hunks = hunks("a\nb\n", "");
+        assert_eq!(hunks.len(), 1);
+        let hunk = &hunks[0];
+        assert_eq!((hunk.old_start, hunk.old_count), (1, 2));
+        assert_eq!((hunk.new_start, hunk.new_count), (0, 0));
+    }
+
+    #[test]
+    fn insert_at_top() {
+        let hunks = hunks(&numbered(1..=8), &format!("new\n{}", numbered(1..=8)));
+        assert_eq!(hunks.len(), 1);
+        let hunk = &hunks[0];
+        assert_eq!((hunk.old_start, hunk.old_count), (1, 3));
+        assert_eq!((hunk.new_start, hunk.new_count), (1, 4));
+        assert_eq!(hunk.lines[0].kind, DiffLineKind::Added);
+        assert_eq!(hunk.lines[0].old_line, None);
+        assert_eq!(hunk.lines[0].new_line, Some(1));
+        assert_eq!(hunk.lines[1].old_line, Some(1));
+        assert_eq!(hunk.lines[1].new_line, Some(2));
+    }
+
+    #[test]
+    fn delete_at_end() {
+        let hunks = hunks(&numbered(1..=8), &numbered(1..=7));
+        assert_eq!(hunks.len(), 1);
+        let hunk = &hunks[0];
+        assert_eq!((hunk.old_start, hunk.old_count), (5, 4));
+        assert_eq!((hunk.new_start, hunk.new_count), (5, 3));
+        let last = hunk.lines.last().unwrap();
+        assert_eq!(last.kind, DiffLineKind::Removed);
+        assert_eq!(last.old_line, Some(8));
+        assert_eq!(last.new_line, None);
+    }
+
+    #[test]
+    fn changes_within_twice_the_context_share_a_hunk() {
+        // Six unchanged lines between the deletions: each change's context meets the other's
+        let hunks = hunks(&numbered(1..=20), &without(1..=20, &[2, 9]));
+        assert_eq!(hunks.len(), 1);
+        let hunk = &hunks[0];
+        assert_eq!((hunk.old_start, hunk.old_count), (1, 12));
+        assert_eq!((hunk.new_start, hunk.new_count), (1, 10));
+    }
+
+    #[test]
+    fn changes_further_apart_get_their_own_hunks() {
+        let hunks = hunks(&numbered(1..=20), &without(1..=20, &[2, 10]));
+        assert_eq!(hunks.len(), 2);
+        assert_eq!((hunks[0].old_start, hunks[0].old_count), (1, 5));
+        assert_eq!((hunks[0].new_start, hunks[0].new_count), (1, 4));
+        assert_eq!((hunks[1].old_start, hunks[1].old_count), (7, 7));
+        assert_eq!((hunks[1].new_start, hunks[1].new_count), (6, 6));
+    }
+
+    #[test]
+    fn count_changes_finds_the_shortest_edit() {
+        let old = ["a", "b", "c", "d"];
+        let new = ["a", "x", "c", "d", "e"];
+        assert_eq!(count_changes(&old, &new), (1, 2));
+    }
+
+    #[test]
+    fn past_max_edit_distance_the_middle_counts_as_replaced() {
+        // Lines shared between the two versions, one in every two, keep the shortest edit
+        // to half the lines, but that's still more than MAX_EDIT_DISTANCE edits
+        let pairs = MAX_EDIT_DISTANCE / 2 + 100;
+        let version = |prefix: &str| {
+            let mut lines: Vec<String> = (0..pairs)
+                .flat_map(|i| [format!("{}{}", prefix, i), format!("shared{}", i)])
+                .collect();
+            lines.push(format!("{}end", prefix));
+            lines
+        };
+        let (old, new) = (version("old"), version("new"));
+        assert_eq!(count_changes(&old, &new), (old.len(), new.len()));
+    }
+}
@@ -0,0 +1,194 @@
+//! Project build hooks: shell commands run in the project directory before and after
+//! a compile, e.g. to regenerate a figure from a script.
+//!
+//! A hook runs with no stdin, in its own process group, and is killed along with
+//! anything it started once it exceeds HOOK_TIMEOUT. Its output is captured up to
+//! HOOK_OUTPUT_LIMIT bytes per stream.
+
+use serde::{Deserialize, Serialize};
+use std::io::Read;
+use std::path::Path;
+use std::process::{Child, Command, Stdio};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+const HOOK_TIMEOUT: Duration = Duration::from_secs(120);
+const HOOK_POLL: Duration = Duration::from_millis(50);
+const HOOK_OUTPUT_LIMIT: usize = 64 * 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum HookStage {
+    Pre,
+    Post,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum HookStatus {
+    Succeeded,
+    Failed,
+    TimedOut,
+    /// Configured for the project, but hooks are turned off in the settings
+    Disabled,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HookOutput {
+    pub stage: HookStage,
+    pub command: String,
+    pub status: HookStatus,
+    pub exit_code: Option<i32>,
+    pub stdout: String,
+    pub stderr: String,
+    pub duration_ms: u64,
+}
+
+impl HookOutput {
+    pub fn disabled(stage: HookStage, command: &str) -> Self {
+        HookOutput {
+            stage,
+            command: command.to_string(),
+            status: HookStatus::Disabled,
+            exit_code: None,
+            stdout: String::new(),
+            stderr: String::new(),
+            duration_ms: 0,
+        }
+    }
+}
+
+fn shell_command(command: &str) -> Command {
+    if cfg!(windows) {
+        let mut shell = Command::new("cmd");
+        shell.arg("/C").arg(command);
+        shell
+    } else {
+        let mut shell = Command::new("sh");
+        shell.arg("-c").arg(command);
+        shell
+    }
+}
+
+/// Read a stream to the end on its own thread, keeping the first HOOK_OUTPUT_LIMIT bytes.
+/// Draining past the limit keeps the hook from blocking on a full pipe.
+fn capture(stream: Option<impl Read + Send + 'static>) -> JoinHandle<String> {
+    std::thread::spawn(move || {
+        let Some(mut stream) = stream else {
+            return String::new();
+        };
+        let mut kept = Vec::new();
+        let mut truncated = false;
+        let mut buffer = [0u8; 8192];
+        while let Ok(n) = stream.read(&mut buffer) {
+            if n == 0 {
+                break;
+            }
+            let room = HOOK_OUTPUT_LIMIT.saturating_sub(kept.len());
+            kept.extend_from_slice(&buffer[..n.min(room)]);
+            truncated |= n > room;
+        }
+        let mut text = String::from_utf8_lossy(&kept).into_owned();
+        if truncated {
+            text.push_str("\n[output truncated]");
+        }
+        text
+    })
+}
+
+/// Kill the hook and every process it started
+fn kill_tree(child: &mut Child) {
+    let pid = child.id().to_string();
+    let killed = if cfg!(windows) {
+        Command::new("taskkill")
+            .args(["/T", "/F", "/PID", &pid])
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .status()
+    } else {
+        // The hook leads its own process group, so a negative pid reaches all of it
+        Command::new("kill")
+            .args(["-KILL", "--", &format!("-{}", pid)])
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .status()
+    };
+    if !killed.is_ok_and(|status| status.success()) {
+        let _ = child.kill();
+    }
+}
+
+/// Run `command` through the platform shell in `project_dir`
+pub fn run_hook(project_dir: &Path, stage: HookStage, command: &str) -> HookOutput {
+    let started = Instant::now();
+    let mut shell = shell_command(command);
+    shell
+        .current_dir(project_dir)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped());
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::CommandExt;
+        shell.process_group(0);
+    }
+
+    let finish = |status, exit_code, stdout, stderr| HookOutput {
+        stage,
+        command: command.to_string(),
+        status,
+        exit_code,
+        stdout,
+        stderr,
+        duration_ms: started.elapsed().as_millis() as u64,
+    };
+
+    let mut child = match shell.spawn() {
+        Ok(child) => child,
+        Err(e) => {
+            let message = format!("Failed to start hook: {}", e);
+            return finish(HookStatus::Failed, None, String::new(), message);
+        }
+    };
+
+    let stdout = capture(child.stdout.take());
+    let stderr = capture(child.stderr.take());
+
+    let (status, exit_code) = loop {
+        match child.try_wait() {
+            Ok(Some(exit)) => {
+                let status = if exit.success() {
+                    HookStatus::Succeeded
+                } else {
+                    HookStatus::Failed
+                };
+                break (status, exit.code());
+            }
+            Ok(None) if started.elapsed() >= HOOK_TIMEOUT => {
+                kill_tree(&mut child);
+                let _ = child.wait();
+                break (HookStatus::TimedOut, None);
+            }
+            Ok(None) => std::thread::sleep(HOOK_POLL),
+            Err(e) => {
+                eprintln!("Failed to wait for hook: {}", e);
+                kill_tree(&mut child);
+                let _ = child.wait();
+                break (HookStatus::Failed, None);
+            }
+        }
+    };
+
+    // A background process the hook left running would keep the pipes open; it was
+    // started in the hook's process group, so end it rather than wait on it
+    if status != HookStatus::TimedOut {
+        kill_tree(&mut child);
+    }
+
+    finish(
+        status,
+        exit_code,
+        stdout.join().unwrap_or_default(),
+        stderr.join().unwrap_or_default(),
+    )
+}
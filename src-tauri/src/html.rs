@@ -0,0 +1,3044 @@
+//! An HTML rendering of a LaTeX document, for sharing a draft as a web page.
+//!
+//! This covers the common subset rather than all of LaTeX: headings with their numbers,
+//! paragraphs, lists, text styles, links, footnotes, figures and tables with their
+//! captions, labels and references, theorems, verbatim text, and math, written as
+//! MathML, which browsers render natively. Included files are read in place. Commands
+//! it doesn't know are dropped and the text of their arguments kept, as in plain text
+//! export; macros a document or its packages define aren't expanded.
+
+use std::collections::HashMap;
+use std::path::Path;
+use unicode_normalization::UnicodeNormalization;
+
+use crate::latex;
+use crate::prose;
+
+const STYLESHEET: &str = "body { max-width: 46em; margin: 2em auto; padding: 0 1em; \
+font-family: Georgia, 'Times New Roman', serif; line-height: 1.5; }
+.title-block { text-align: center; margin-bottom: 2em; }
+.author, .date { margin: 0.3em 0; }
+.abstract { margin: 1.5em 3em; font-size: 0.95em; }
+.abstract h2 { font-size: 1em; text-align: center; }
+li > p, dd > p { margin: 0.3em 0; }
+figure { margin: 1.5em 0; text-align: center; }
+figure img { max-width: 100%; }
+figcaption, .caption { font-size: 0.9em; margin-top: 0.5em; }
+.subfigure, .minipage { display: inline-block; vertical-align: top; margin: 0.5em; }
+table { border-collapse: collapse; margin: 0 auto; }
+th, td { padding: 0.2em 0.6em; }
+thead th { border-bottom: 1px solid; }
+.equation { display: flex; align-items: center; margin: 1em 0; }
+.equation math { flex: 1; }
+.eqno { margin-left: 1em; }
+.theorem-head { font-weight: bold; }
+.theorem.plain p { font-style: italic; }
+.theorem.plain .theorem-head { font-style: normal; }
+.proof-head { font-style: italic; }
+.qed { float: right; }
+.center { text-align: center; }
+.flushright { text-align: right; }
+.smallcaps { font-variant: small-caps; }
+.sans { font-family: sans-serif; }
+.toc ul { list-style: none; padding-left: 0; }
+.footnotes { font-size: 0.9em; border-top: 1px solid #ccc; margin-top: 2em; }
+.unsupported { color: #777; font-style: italic; }
+";
+
+/// Text style commands taking their text as an argument, with the markup they become
+const TEXT_STYLES: &[(&str, &str, &str)] = &[
+    ("textbf", "<strong>", "</strong>"),
+    ("textit", "<em>", "</em>"),
+    ("emph", "<em>", "</em>"),
+    ("textsl", "<em>", "</em>"),
+    ("texttt", "<code>", "</code>"),
+    ("textsc", "<span class=\"smallcaps\">", "</span>"),
+    ("textsf", "<span class=\"sans\">", "</span>"),
+    ("underline", "<u>", "</u>"),
+    ("textsuperscript", "<sup>", "</sup>"),
+    ("textsubscript", "<sub>", "</sub>"),
+    ("textrm", "", ""),
+    ("textup", "", ""),
+    ("textmd", "", ""),
+    ("textnormal", "", ""),
+    ("mbox", "", ""),
+    ("text", "", ""),
+];
+
+/// Style declarations, which apply to the rest of their group
+const DECLARATIONS: &[(&str, &str, &str)] = &[
+    ("bfseries", "<strong>", "</strong>"),
+    ("bf", "<strong>", "</strong>"),
+    ("itshape", "<em>", "</em>"),
+    ("it", "<em>", "</em>"),
+    ("em", "<em>", "</em>"),
+    ("slshape", "<em>", "</em>"),
+    ("sl", "<em>", "</em>"),
+    ("ttfamily", "<code>", "</code>"),
+    ("tt", "<code>", "</code>"),
+    ("scshape", "<span class=\"smallcaps\">", "</span>"),
+    ("sc", "<span class=\"smallcaps\">", "</span>"),
+    ("sffamily", "<span class=\"sans\">", "</span>"),
+    ("sf", "<span class=\"sans\">", "</span>"),
+];
+
+/// Reference commands and how each shows its label: `r` the number, `e` the number in
+/// parentheses, `a` the number after the kind of thing labeled, e.g. "Figure 2"
+const REFERENCE_STYLES: &[(&str, char)] = &[
+    ("ref", 'r'),
+    ("pageref", 'r'),
+    ("vref", 'r'),
+    ("eqref", 'e'),
+    ("autoref", 'a'),
+    ("cref", 'a'),
+    ("Cref", 'a'),
+    ("nameref", 'a'),
+];
+
+const CITE_COMMANDS: &[&str] = &[
+    "cite",
+    "citep",
+    "citet",
+    "citealt",
+    "citealp",
+    "parencite",
+    "textcite",
+    "autocite",
+    "footcite",
+    "supercite",
+];
+
+/// Graphics formats a browser shows, tried first for an \includegraphics without one
+const WEB_GRAPHICS: &[&str] = &["png", "jpg", "jpeg", "gif", "svg", "webp"];
+
+/// Formats TeX reads that a browser can't show
+const PRINT_GRAPHICS: &[&str] = &["pdf", "eps", "ps"];
+
+/// Environments set apart from the text around them, and the element each becomes
+const BLOCK_ENVIRONMENTS: &[(&str, &str)] = &[
+    ("quote", "blockquote"),
+    ("quotation", "blockquote"),
+    ("verse", "blockquote"),
+    ("center", "div class=\"center\""),
+    ("flushleft", "div"),
+    ("flushright", "div class=\"flushright\""),
+];
+
+/// Special-purpose environments rendered as a note instead
+const UNSUPPORTED_ENVIRONMENTS: &[&str] = &["tikzpicture", "pgfpicture", "picture", "tikzcd"];
+
+/// Escape `text` for HTML content and attribute values
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// A mark outside any brace group or nested environment that splits the content of a
+/// list, table or alignment
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Split {
+    /// `\\`, with its star and [length], which ends a row
+    Row,
+    /// `&`, between cells
+    Cell,
+    /// `\item`
+    Item,
+}
+
+/// The top-level marks of `text`, by byte range
+fn splits(text: &str) -> Vec<(usize, usize, Split)> {
+    let bytes = text.as_bytes();
+    let (mut depth, mut environments) = (0i32, 0i32);
+    let mut marks = Vec::new();
+    let mut i = 0;
+    while i < bytes.len() {
+        let top = depth == 0 && environments == 0;
+        match bytes[i] {
+            b'{' => depth += 1,
+            b'}' => depth -= 1,
+            b'&' if top => marks.push((i, i + 1, Split::Cell)),
+            b'\\' => {
+                if bytes.get(i + 1) == Some(&b'\\') {
+                    let mut end = i + 2;
+                    if bytes.get(end) == Some(&b'*') {
+                        end += 1;
+                    }
+                    let next = latex::skip_arg_whitespace(bytes, end);
+                    if bytes.get(next) == Some(&b'[') {
+                        if let Some((_, after)) = latex::read_group(text, next, b'[', b']') {
+                            end = after;
+                        }
+                    }
+                    if top {
+                        marks.push((i, end, Split::Row));
+                    }
+                    i = end;
+                    continue;
+                }
+                let name_length = bytes[i + 1..]
+                    .iter()
+                    .take_while(|b| b.is_ascii_alphabetic())
+                    .count();
+                match &text[i + 1..i + 1 + name_length] {
+                    "begin" => environments += 1,
+                    "end" => environments -= 1,
+                    "item" if top => marks.push((i, i + 1 + name_length, Split::Item)),
+                    _ => {}
+                }
+                i += 1 + name_length.max(1);
+                continue;
+            }
+            _ => {}
+        }
+        i += 1;
+    }
+    marks
+}
+
+/// `text` cut at each top-level `kind` mark, e.g. the rows of a table
+fn split_at(text: &str, kind: Split) -> Vec<&str> {
+    let mut parts = Vec::new();
+    let mut from = 0;
+    for (start, end, _) in splits(text).into_iter().filter(|(_, _, k)| *k == kind) {
+        parts.push(&text[from..start]);
+        from = end;
+    }
+    parts.push(&text[from..]);
+    parts
+}
+
+/// Where the body of the environment `name` opened just before `from` ends, and where
+/// its \end does. Verbatim environments end at the first \end for them; others nest.
+fn environment_body(text: &str, name: &str, from: usize) -> (usize, usize) {
+    let closing = format!("\\end{{{}}}", name);
+    if latex::VERBATIM_ENVIRONMENTS.contains(&name) {
+        return match text[from..].find(&closing) {
+            Some(i) => (from + i, from + i + closing.len()),
+            None => (text.len(), text.len()),
+        };
+    }
+
+    let mut depth = 0;
+    for command in latex::commands(&text[from..]) {
+        if command.name != "begin" && command.name != "end" {
+            continue;
+        }
+        let args = latex::read_args(&text[from..], command.end, 1);
+        if latex::first_required(&args).is_none_or(|arg| arg.text.trim() != name) {
+            continue;
+        }
+        if command.name == "begin" {
+            depth += 1;
+        } else if depth == 0 {
+            let end = latex::skip_args(&text[from..], command.end, 1);
+            return (from + command.start, from + end);
+        } else {
+            depth -= 1;
+        }
+    }
+    (text.len(), text.len())
+}
+
+/// Math identifiers written as a single character
+const MATH_IDENTIFIERS: &[(&str, &str)] = &[
+    ("alpha", "α"),
+    ("beta", "β"),
+    ("gamma", "γ"),
+    ("delta", "δ"),
+    ("epsilon", "ϵ"),
+    ("varepsilon", "ε"),
+    ("zeta", "ζ"),
+    ("eta", "η"),
+    ("theta", "θ"),
+    ("vartheta", "ϑ"),
+    ("iota", "ι"),
+    ("kappa", "κ"),
+    ("lambda", "λ"),
+    ("mu", "μ"),
+    ("nu", "ν"),
+    ("xi", "ξ"),
+    ("pi", "π"),
+    ("varpi", "ϖ"),
+    ("rho", "ρ"),
+    ("varrho", "ϱ"),
+    ("sigma", "σ"),
+    ("varsigma", "ς"),
+    ("tau", "τ"),
+    ("upsilon", "υ"),
+    ("phi", "ϕ"),
+    ("varphi", "φ"),
+    ("chi", "χ"),
+    ("psi", "ψ"),
+    ("omega", "ω"),
+    ("infty", "∞"),
+    ("partial", "∂"),
+    ("nabla", "∇"),
+    ("emptyset", "∅"),
+    ("varnothing", "∅"),
+    ("ell", "ℓ"),
+    ("hbar", "ℏ"),
+    ("Re", "ℜ"),
+    ("Im", "ℑ"),
+    ("aleph", "ℵ"),
+    ("wp", "℘"),
+    ("imath", "ı"),
+    ("jmath", "ȷ"),
+];
+
+/// Capital Greek letters, which TeX sets upright
+const UPRIGHT_IDENTIFIERS: &[(&str, &str)] = &[
+    ("Gamma", "Γ"),
+    ("Delta", "Δ"),
+    ("Theta", "Θ"),
+    ("Lambda", "Λ"),
+    ("Xi", "Ξ"),
+    ("Pi", "Π"),
+    ("Sigma", "Σ"),
+    ("Upsilon", "Υ"),
+    ("Phi", "Φ"),
+    ("Psi", "Ψ"),
+    ("Omega", "Ω"),
+];
+
+/// Operators, relations, arrows, delimiters and dots
+const MATH_OPERATORS: &[(&str, &str)] = &[
+    ("pm", "±"),
+    ("mp", "∓"),
+    ("times", "×"),
+    ("div", "÷"),
+    ("cdot", "⋅"),
+    ("ast", "∗"),
+    ("star", "⋆"),
+    ("circ", "∘"),
+    ("bullet", "∙"),
+    ("oplus", "⊕"),
+    ("ominus", "⊖"),
+    ("otimes", "⊗"),
+    ("oslash", "⊘"),
+    ("odot", "⊙"),
+    ("cup", "∪"),
+    ("cap", "∩"),
+    ("setminus", "∖"),
+    ("wedge", "∧"),
+    ("land", "∧"),
+    ("vee", "∨"),
+    ("lor", "∨"),
+    ("le", "≤"),
+    ("leq", "≤"),
+    ("leqslant", "⩽"),
+    ("ge", "≥"),
+    ("geq", "≥"),
+    ("geqslant", "⩾"),
+    ("neq", "≠"),
+    ("ne", "≠"),
+    ("ll", "≪"),
+    ("gg", "≫"),
+    ("lesssim", "≲"),
+    ("gtrsim", "≳"),
+    ("approx", "≈"),
+    ("equiv", "≡"),
+    ("sim", "∼"),
+    ("simeq", "≃"),
+    ("cong", "≅"),
+    ("propto", "∝"),
+    ("doteq", "≐"),
+    ("triangleq", "≜"),
+    ("coloneqq", "≔"),
+    ("prec", "≺"),
+    ("succ", "≻"),
+    ("preceq", "⪯"),
+    ("succeq", "⪰"),
+    ("in", "∈"),
+    ("notin", "∉"),
+    ("ni", "∋"),
+    ("subset", "⊂"),
+    ("supset", "⊃"),
+    ("subseteq", "⊆"),
+    ("supseteq", "⊇"),
+    ("mid", "∣"),
+    ("parallel", "∥"),
+    ("perp", "⊥"),
+    ("models", "⊨"),
+    ("vdash", "⊢"),
+    ("dashv", "⊣"),
+    ("to", "→"),
+    ("rightarrow", "→"),
+    ("leftarrow", "←"),
+    ("gets", "←"),
+    ("leftrightarrow", "↔"),
+    ("Rightarrow", "⇒"),
+    ("Leftarrow", "⇐"),
+    ("Leftrightarrow", "⇔"),
+    ("implies", "⟹"),
+    ("impliedby", "⟸"),
+    ("iff", "⟺"),
+    ("mapsto", "↦"),
+    ("longrightarrow", "⟶"),
+    ("longleftarrow", "⟵"),
+    ("longmapsto", "⟼"),
+    ("uparrow", "↑"),
+    ("downarrow", "↓"),
+    ("hookrightarrow", "↪"),
+    ("forall", "∀"),
+    ("exists", "∃"),
+    ("nexists", "∄"),
+    ("neg", "¬"),
+    ("lnot", "¬"),
+    ("top", "⊤"),
+    ("bot", "⊥"),
+    ("angle", "∠"),
+    ("triangle", "△"),
+    ("prime", "′"),
+    ("ldots", "…"),
+    ("dots", "…"),
+    ("cdots", "⋯"),
+    ("vdots", "⋮"),
+    ("ddots", "⋱"),
+    ("colon", ":"),
+    ("vert", "|"),
+    ("lvert", "|"),
+    ("rvert", "|"),
+    ("Vert", "‖"),
+    ("lVert", "‖"),
+    ("rVert", "‖"),
+    ("langle", "⟨"),
+    ("rangle", "⟩"),
+    ("lfloor", "⌊"),
+    ("rfloor", "⌋"),
+    ("lceil", "⌈"),
+    ("rceil", "⌉"),
+    ("backslash", "\\"),
+    ("lbrace", "{"),
+    ("rbrace", "}"),
+];
+
+/// Operators whose limits go below and above them in display math
+const LARGE_OPERATORS: &[(&str, &str)] = &[
+    ("sum", "∑"),
+    ("prod", "∏"),
+    ("coprod", "∐"),
+    ("bigcup", "⋃"),
+    ("bigcap", "⋂"),
+    ("bigoplus", "⨁"),
+    ("bigotimes", "⨂"),
+    ("bigvee", "⋁"),
+    ("bigwedge", "⋀"),
+    ("bigsqcup", "⨆"),
+];
+
+/// Integrals, whose limits stay at their side
+const INTEGRALS: &[(&str, &str)] = &[("int", "∫"), ("iint", "∬"), ("iiint", "∭"), ("oint", "∮")];
+
+/// Functions set as upright names
+const MATH_FUNCTIONS: &[&str] = &[
+    "sin", "cos", "tan", "cot", "sec", "csc", "arcsin", "arccos", "arctan", "sinh", "cosh", "tanh",
+    "coth", "exp", "log", "ln", "lg", "deg", "dim", "ker", "hom", "arg",
+];
+
+/// Functions whose subscript goes below them in display math, e.g. \lim
+const LIMIT_FUNCTIONS: &[(&str, &str)] = &[
+    ("lim", "lim"),
+    ("liminf", "lim inf"),
+    ("limsup", "lim sup"),
+    ("max", "max"),
+    ("min", "min"),
+    ("sup", "sup"),
+    ("inf", "inf"),
+    ("det", "det"),
+    ("gcd", "gcd"),
+    ("Pr", "Pr"),
+];
+
+/// Accents over (or under) their argument, and the character drawn
+const MATH_ACCENTS: &[(&str, &str)] = &[
+    ("hat", "^"),
+    ("widehat", "^"),
+    ("check", "ˇ"),
+    ("tilde", "~"),
+    ("widetilde", "~"),
+    ("bar", "¯"),
+    ("overline", "¯"),
+    ("vec", "→"),
+    ("overrightarrow", "→"),
+    ("overleftarrow", "←"),
+    ("dot", "˙"),
+    ("ddot", "¨"),
+    ("acute", "´"),
+    ("grave", "`"),
+    ("breve", "˘"),
+    ("overbrace", "⏞"),
+];
+
+/// Math alphabet commands and the Unicode alphabet each maps letters to
+const MATH_ALPHABETS: &[(&str, Alphabet)] = &[
+    ("mathbf", Alphabet::Bold),
+    ("boldsymbol", Alphabet::Bold),
+    ("bm", Alphabet::Bold),
+    ("mathit", Alphabet::Italic),
+    ("mathcal", Alphabet::Script),
+    ("mathscr", Alphabet::Script),
+    ("mathfrak", Alphabet::Fraktur),
+    ("mathbb", Alphabet::DoubleStruck),
+    ("mathsf", Alphabet::Sans),
+    ("mathtt", Alphabet::Monospace),
+];
+
+/// Matrix-like environments inside math, with the delimiters around them
+const MATH_MATRICES: &[(&str, &str, &str)] = &[
+    ("matrix", "", ""),
+    ("smallmatrix", "", ""),
+    ("pmatrix", "(", ")"),
+    ("bmatrix", "[", "]"),
+    ("Bmatrix", "{", "}"),
+    ("vmatrix", "|", "|"),
+    ("Vmatrix", "‖", "‖"),
+    ("cases", "{", ""),
+    ("array", "", ""),
+    ("aligned", "", ""),
+    ("alignedat", "", ""),
+    ("gathered", "", ""),
+    ("split", "", ""),
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Alphabet {
+    Bold,
+    Italic,
+    Script,
+    Fraktur,
+    DoubleStruck,
+    Sans,
+    Monospace,
+}
+
+impl Alphabet {
+    /// `c` in this alphabet, from the Mathematical Alphanumeric Symbols block or the
+    /// Letterlike Symbols it leaves holes for, or `c` itself if the alphabet lacks it
+    fn map(self, c: char) -> char {
+        let holes: &[(char, char)] = match self {
+            Alphabet::Italic => &[('h', 'ℎ')],
+            Alphabet::Script => &[
+                ('B', 'ℬ'),
+                ('E', 'ℰ'),
+                ('F', 'ℱ'),
+                ('H', 'ℋ'),
+                ('I', 'ℐ'),
+                ('L', 'ℒ'),
+                ('M', 'ℳ'),
+                ('R', 'ℛ'),
+                ('e', 'ℯ'),
+                ('g', 'ℊ'),
+                ('o', 'ℴ'),
+            ],
+            Alphabet::Fraktur => &[('C', 'ℭ'), ('H', 'ℌ'), ('I', 'ℑ'), ('R', 'ℜ'), ('Z', 'ℨ')],
+            Alphabet::DoubleStruck => &[
+                ('C', 'ℂ'),
+                ('H', 'ℍ'),
+                ('N', 'ℕ'),
+                ('P', 'ℙ'),
+                ('Q', 'ℚ'),
+                ('R', 'ℝ'),
+                ('Z', 'ℤ'),
+            ],
+            _ => &[],
+        };
+        if let Some((_, mapped)) = holes.iter().find(|(letter, _)| *letter == c) {
+            return *mapped;
+        }
+
+        let (capitals, digits) = match self {
+            Alphabet::Bold => (0x1D400, Some(0x1D7CE)),
+            Alphabet::Italic => (0x1D434, None),
+            Alphabet::Script => (0x1D49C, None),
+            Alphabet::Fraktur => (0x1D504, None),
+            Alphabet::DoubleStruck => (0x1D538, Some(0x1D7D8)),
+            Alphabet::Sans => (0x1D5A0, Some(0x1D7E2)),
+            Alphabet::Monospace => (0x1D670, Some(0x1D7F6)),
+        };
+        let code = match c {
+            'A'..='Z' => Some(capitals + (c as u32 - 'A' as u32)),
+            'a'..='z' => Some(capitals + 26 + (c as u32 - 'a' as u32)),
+            '0'..='9' => digits.map(|start| start + (c as u32 - '0' as u32)),
+            _ => None,
+        };
+        code.and_then(char::from_u32).unwrap_or(c)
+    }
+}
+
+fn lookup<'t>(table: &[(&str, &'t str)], name: &str) -> Option<&'t str> {
+    table
+        .iter()
+        .find(|(entry, _)| *entry == name)
+        .map(|(_, value)| *value)
+}
+
+/// Converts TeX math to MathML, one formula at a time
+struct MathParser<'a> {
+    text: &'a str,
+    pos: usize,
+    display: bool,
+}
+
+/// The MathML for the TeX formula `tex`, without the surrounding <math> element
+fn mathml(tex: &str, display: bool) -> String {
+    let mut parser = MathParser {
+        text: tex,
+        pos: 0,
+        display,
+    };
+    let mut row = parser.row(false);
+    // A stray } ends a row early; carry on after it
+    while parser.pos < tex.len() {
+        parser.pos += 1;
+        row.push_str(&parser.row(false));
+    }
+    row
+}
+
+fn mrow(content: String) -> String {
+    format!("<mrow>{}</mrow>", content)
+}
+
+impl MathParser<'_> {
+    fn rest(&self) -> &str {
+        &self.text[self.pos..]
+    }
+
+    fn skip_whitespace(&mut self) {
+        while self.rest().starts_with(char::is_whitespace) {
+            self.pos += self.rest().chars().next().map_or(1, char::len_utf8);
+        }
+    }
+
+    /// Elements up to the end, a closing brace (left unread) or, with `until_right`,
+    /// a \right
+    fn row(&mut self, until_right: bool) -> String {
+        let mut out = String::new();
+        loop {
+            self.skip_whitespace();
+            let rest = self.rest();
+            if rest.is_empty() || rest.starts_with('}') {
+                break;
+            }
+            if until_right && rest.starts_with("\\right") && !self.word_follows(6) {
+                break;
+            }
+            out.push_str(&self.element());
+        }
+        out
+    }
+
+    /// Whether a letter follows the first `len` bytes of the rest, so they're only the
+    /// start of a longer command name
+    fn word_follows(&self, len: usize) -> bool {
+        self.rest()[len..].starts_with(|c: char| c.is_ascii_alphabetic())
+    }
+
+    /// A command name at the current position, just past its backslash
+    fn command_name(&mut self) -> String {
+        let rest = self.rest();
+        let length = rest.bytes().take_while(u8::is_ascii_alphabetic).count();
+        let length = if length == 0 {
+            rest.chars().next().map_or(0, char::len_utf8)
+        } else {
+            length
+        };
+        let name = rest[..length].to_string();
+        self.pos += length;
+        name
+    }
+
+    /// A required argument: a braced group, or else a single token
+    fn argument(&mut self) -> String {
+        self.skip_whitespace();
+        if self.rest().starts_with('{') {
+            self.pos += 1;
+            let content = self.row(false);
+            if self.rest().starts_with('}') {
+                self.pos += 1;
+            }
+            return mrow(content);
+        }
+        self.atom(true).0
+    }
+
+    /// The source of a braced argument, unparsed, e.g. for \text
+    fn raw_argument(&mut self) -> String {
+        self.skip_whitespace();
+        match latex::read_group(self.text, self.pos, b'{', b'}') {
+            Some((content, end)) => {
+                self.pos = end;
+                content
+            }
+            None => {
+                let c = self.rest().chars().next().unwrap_or(' ');
+                self.pos += c.len_utf8();
+                c.to_string()
+            }
+        }
+    }
+
+    fn optional_argument(&mut self) -> Option<String> {
+        let start = latex::skip_arg_whitespace(self.text.as_bytes(), self.pos);
+        let (content, end) = latex::read_group(self.text, start, b'[', b']')?;
+        self.pos = end;
+        Some(content)
+    }
+
+    /// A delimiter after \left, \right or \big: a character or a delimiter command,
+    /// empty for `.`
+    fn delimiter(&mut self) -> String {
+        self.skip_whitespace();
+        let delimiter = if self.rest().starts_with('\\') {
+            self.pos += 1;
+            let name = self.command_name();
+            match name.as_str() {
+                "{" | "}" => name,
+                "|" => "‖".to_string(),
+                _ => lookup(MATH_OPERATORS, &name)
+                    .unwrap_or_default()
+                    .to_string(),
+            }
+        } else {
+            let c = self.rest().chars().next().unwrap_or('.');
+            self.pos += c.len_utf8();
+            c.to_string()
+        };
+        if delimiter == "." {
+            String::new()
+        } else {
+            escape(&delimiter)
+        }
+    }
+
+    /// Whether a digit, or a decimal point before one, comes next
+    fn continues_number(&self) -> bool {
+        let rest = self.rest();
+        rest.starts_with(|d: char| d.is_ascii_digit())
+            || (rest.starts_with('.') && rest[1..].starts_with(|d: char| d.is_ascii_digit()))
+    }
+
+    /// One element with any sub- and superscripts it takes
+    fn element(&mut self) -> String {
+        let (base, limits) = self.atom(false);
+        let mut subscript = None;
+        let mut superscript: Option<String> = None;
+        loop {
+            self.skip_whitespace();
+            let Some(c) = self.rest().chars().next() else {
+                break;
+            };
+            match c {
+                '_' => {
+                    self.pos += 1;
+                    subscript = Some(self.argument());
+                }
+                '^' => {
+                    self.pos += 1;
+                    let script = self.argument();
+                    superscript = Some(match superscript {
+                        Some(primes) => format!("{}{}", primes, script),
+                        None => script,
+                    });
+                }
+                '\'' => {
+                    self.pos += 1;
+                    let prime = "<mo>′</mo>".to_string();
+                    superscript = Some(superscript.unwrap_or_default() + &prime);
+                }
+                _ => break,
+            }
+        }
+
+        let under = limits && self.display;
+        match (subscript, superscript) {
+            (None, None) => base,
+            (Some(sub), None) if under => format!("<munder>{}{}</munder>", base, sub),
+            (Some(sub), None) => format!("<msub>{}{}</msub>", base, sub),
+            (None, Some(sup)) if under => format!("<mover>{}{}</mover>", base, mrow(sup)),
+            (None, Some(sup)) => format!("<msup>{}{}</msup>", base, mrow(sup)),
+            (Some(sub), Some(sup)) if under => {
+                format!("<munderover>{}{}{}</munderover>", base, sub, mrow(sup))
+            }
+            (Some(sub), Some(sup)) => format!("<msubsup>{}{}{}</msubsup>", base, sub, mrow(sup)),
+        }
+    }
+
+    /// One token or group, and whether its limits go below and above it in display
+    /// math. With `single`, only one digit of a number is read, as for a script.
+    fn atom(&mut self, single: bool) -> (String, bool) {
+        self.skip_whitespace();
+        let Some(c) = self.rest().chars().next() else {
+            return (String::new(), false);
+        };
+
+        if c == '{' {
+            return (self.argument(), false);
+        }
+        if c == '\\' {
+            self.pos += 1;
+            let name = self.command_name();
+            return self.command(&name);
+        }
+
+        self.pos += c.len_utf8();
+        let element = if c.is_ascii_digit() || (c == '.' && self.continues_number()) {
+            let mut number = c.to_string();
+            while !single && self.continues_number() {
+                number.push_str(&self.rest()[..1]);
+                self.pos += 1;
+            }
+            format!("<mn>{}</mn>", number)
+        } else if c.is_alphabetic() {
+            format!("<mi>{}</mi>", escape(&c.to_string()))
+        } else {
+            match c {
+                '~' => "<mspace width=\"0.33em\"/>".to_string(),
+                '&' | '#' => String::new(),
+                '-' => "<mo>−</mo>".to_string(),
+                '*' => "<mo>∗</mo>".to_string(),
+                _ => format!("<mo>{}</mo>", escape(&c.to_string())),
+            }
+        };
+        (element, false)
+    }
+
+    fn command(&mut self, name: &str) -> (String, bool) {
+        if let Some(symbol) = lookup(MATH_IDENTIFIERS, name) {
+            return (format!("<mi>{}</mi>", symbol), false);
+        }
+        if let Some(symbol) = lookup(UPRIGHT_IDENTIFIERS, name) {
+            return (format!("<mi mathvariant=\"normal\">{}</mi>", symbol), false);
+        }
+        if let Some(symbol) = lookup(MATH_OPERATORS, name) {
+            return (format!("<mo>{}</mo>", escape(symbol)), false);
+        }
+        if let Some(symbol) = lookup(LARGE_OPERATORS, name) {
+            return (format!("<mo largeop=\"true\">{}</mo>", symbol), true);
+        }
+        if let Some(symbol) = lookup(INTEGRALS, name) {
+            return (format!("<mo largeop=\"true\">{}</mo>", symbol), false);
+        }
+        if MATH_FUNCTIONS.contains(&name) {
+            return (format!("<mi>{}</mi><mo>\u{2061}</mo>", name), false);
+        }
+        if let Some(text) = lookup(LIMIT_FUNCTIONS, name) {
+            return (format!("<mo movablelimits=\"true\">{}</mo>", text), true);
+        }
+        if let Some(accent) = lookup(MATH_ACCENTS, name) {
+            let base = self.argument();
+            return (
+                format!(
+                    "<mover accent=\"true\">{}<mo stretchy=\"true\">{}</mo></mover>",
+                    base,
+                    escape(accent)
+                ),
+                false,
+            );
+        }
+        if let Some((_, alphabet)) = MATH_ALPHABETS.iter().find(|(entry, _)| *entry == name) {
+            return (self.alphabet(Some(*alphabet)), false);
+        }
+
+        let element = match name {
+            "frac" | "dfrac" | "tfrac" | "cfrac" => {
+                let numerator = self.argument();
+                let denominator = self.argument();
+                format!("<mfrac>{}{}</mfrac>", numerator, denominator)
+            }
+            "binom" | "dbinom" | "tbinom" => {
+                let top = self.argument();
+                let bottom = self.argument();
+                format!(
+                    "<mrow><mo>(</mo><mfrac linethickness=\"0\">{}{}</mfrac><mo>)</mo></mrow>",
+                    top, bottom
+                )
+            }
+            "sqrt" => match self.optional_argument() {
+                Some(index) => {
+                    let radicand = self.argument();
+                    format!("<mroot>{}{}</mroot>", radicand, mrow(mathml(&index, false)))
+                }
+                None => format!("<msqrt>{}</msqrt>", self.argument()),
+            },
+            "mathrm" | "mathup" | "rm" | "operatorname" | "mathnormal" => self.alphabet(None),
+            "text" | "textrm" | "textit" | "textbf" | "mbox" | "hbox" | "textnormal" | "texttt"
+            | "textsf" => {
+                let text = prose::file_prose(&self.raw_argument());
+                format!("<mtext>{}</mtext>", escape(&text.replace('\n', " ")))
+            }
+            "underline" => format!("<munder>{}<mo>_</mo></munder>", self.argument()),
+            "underbrace" => format!("<munder>{}<mo>⏟</mo></munder>", self.argument()),
+            "left" => {
+                let open = self.delimiter();
+                let content = self.row(true);
+                let mut close = String::new();
+                if self.rest().starts_with("\\right") {
+                    self.pos += "\\right".len();
+                    close = self.delimiter();
+                }
+                format!(
+                    "<mrow><mo fence=\"true\">{}</mo>{}<mo fence=\"true\">{}</mo></mrow>",
+                    open, content, close
+                )
+            }
+            // A \right without its \left
+            "right" | "middle" | "big" | "Big" | "bigg" | "Bigg" | "bigl" | "bigr" | "Bigl"
+            | "Bigr" | "biggl" | "biggr" | "Biggl" | "Biggr" | "bigm" | "Bigm" => {
+                format!("<mo>{}</mo>", self.delimiter())
+            }
+            "{" => "<mo>{</mo>".to_string(),
+            "}" => "<mo>}</mo>".to_string(),
+            "|" => "<mo>‖</mo>".to_string(),
+            "%" | "$" | "#" | "&" | "_" => format!("<mo>{}</mo>", escape(name)),
+            "," | ":" | ">" => "<mspace width=\"0.2em\"/>".to_string(),
+            ";" => "<mspace width=\"0.28em\"/>".to_string(),
+            " " => "<mspace width=\"0.33em\"/>".to_string(),
+            "quad" => "<mspace width=\"1em\"/>".to_string(),
+            "qquad" => "<mspace width=\"2em\"/>".to_string(),
+            // Negative space, row breaks and layout switches have no MathML of their own
+            "!" | "\\" | "displaystyle" | "textstyle" | "scriptstyle" | "limits" | "nolimits"
+            | "nonumber" | "notag" | "label" | "tag" | "mathstrut" | "strut" | "ensuremath" => {
+                if matches!(name, "label" | "tag") {
+                    self.raw_argument();
+                }
+                String::new()
+            }
+            "begin" => self.environment(),
+            "end" => {
+                self.raw_argument();
+                String::new()
+            }
+            _ => format!("<mtext>\\{}</mtext>", escape(name)),
+        };
+        (element, false)
+    }
+
+    /// The argument of a math alphabet command, its letters and digits in `alphabet`
+    /// (upright without one). Arguments with more than plain characters are parsed
+    /// as math, keeping their own styles.
+    fn alphabet(&mut self, alphabet: Option<Alphabet>) -> String {
+        let start = self.pos;
+        let raw = self.raw_argument();
+        if !raw.chars().all(|c| c.is_alphanumeric() || c == ' ') {
+            self.pos = start;
+            return self.argument();
+        }
+        let text: String = raw.chars().filter(|c| *c != ' ').collect();
+        match alphabet {
+            None if text.chars().count() == 1 => {
+                format!("<mi mathvariant=\"normal\">{}</mi>", escape(&text))
+            }
+            None => format!("<mi>{}</mi>", escape(&text)),
+            Some(alphabet) => text
+                .chars()
+                .map(|c| format!("<mi>{}</mi>", alphabet.map(c)))
+                .collect(),
+        }
+    }
+
+    /// A matrix-like environment whose \begin was just read, as a table in its
+    /// delimiters
+    fn environment(&mut self) -> String {
+        let name = self.raw_argument();
+        let name = name.trim();
+        let (body_end, end) = environment_body(self.text, name, self.pos);
+        let mut body = &self.text[self.pos..body_end];
+        self.pos = end;
+        if matches!(name, "array" | "alignedat") {
+            // The column spec or count
+            let skip = latex::skip_args(body, 0, 1);
+            body = &body[skip..];
+        }
+
+        let Some((_, open, close)) = MATH_MATRICES.iter().find(|(entry, _, _)| *entry == name)
+        else {
+            return mathml(body, self.display);
+        };
+        let aligned = matches!(name, "aligned" | "alignedat" | "split");
+        let rows = split_at(body, Split::Row);
+        let rows: Vec<&str> = match rows.split_last() {
+            Some((last, rest)) if last.trim().is_empty() => rest.to_vec(),
+            _ => rows,
+        };
+
+        let mut table = String::new();
+        for row in rows {
+            table.push_str("<mtr>");
+            for (i, cell) in split_at(row, Split::Cell).into_iter().enumerate() {
+                let align = if aligned {
+                    if i % 2 == 0 {
+                        "right"
+                    } else {
+                        "left"
+                    }
+                } else if name == "cases" {
+                    "left"
+                } else {
+                    "center"
+                };
+                table.push_str(&format!(
+                    "<mtd columnalign=\"{}\">{}</mtd>",
+                    align,
+                    mathml(cell, self.display)
+                ));
+            }
+            table.push_str("</mtr>");
+        }
+
+        let table = format!("<mtable>{}</mtable>", table);
+        if open.is_empty() && close.is_empty() {
+            return table;
+        }
+        format!(
+            "<mrow><mo fence=\"true\">{}</mo>{}<mo fence=\"true\">{}</mo></mrow>",
+            open, table, close
+        )
+    }
+}
+
+/// Document classes whose top sectioning level is the chapter
+const CHAPTER_CLASSES: &[&str] = &["book", "report", "memoir", "scrbook", "scrreprt"];
+
+/// Sectioning commands by depth, as LaTeX numbers them
+const SECTION_LEVELS: &[(&str, usize)] = &[
+    ("part", 0),
+    ("chapter", 1),
+    ("section", 2),
+    ("subsection", 3),
+    ("subsubsection", 4),
+    ("paragraph", 5),
+    ("subparagraph", 6),
+];
+
+/// Rules between table rows, with the brace groups each takes
+const TABLE_RULES: &[(&str, usize)] = &[
+    ("hline", 0),
+    ("toprule", 0),
+    ("midrule", 0),
+    ("bottomrule", 0),
+    ("cline", 1),
+    ("cmidrule", 1),
+    ("specialrule", 3),
+    ("endhead", 0),
+    ("endfirsthead", 0),
+    ("endfoot", 0),
+    ("endlastfoot", 0),
+];
+
+/// Commands whose arguments were read before rendering, or don't typeset anything
+const DEFINITION_COMMANDS: &[(&str, usize)] = &[
+    ("title", 1),
+    ("author", 1),
+    ("date", 1),
+    ("thanks", 1),
+    ("newtheorem", 2),
+    ("theoremstyle", 1),
+    ("numberwithin", 2),
+    ("tableofcontents", 0),
+];
+
+/// A theorem-like environment declared with \newtheorem
+#[derive(Debug, Clone)]
+struct Theorem {
+    environment: String,
+    title: String,
+    /// The counter it's numbered with, shared with another theorem's environment name
+    /// for `\newtheorem{lemma}[theorem]{Lemma}`; None for \newtheorem*
+    counter: Option<String>,
+    /// The sectioning level its number is within, e.g. 2 for `[section]`
+    within: Option<usize>,
+    /// Its \theoremstyle: plain, definition or remark
+    style: String,
+}
+
+/// What the document declares to be used while rendering it
+#[derive(Debug, Default)]
+struct Definitions {
+    title: Option<String>,
+    author: Option<String>,
+    date: Option<String>,
+    theorems: Vec<Theorem>,
+    graphics_dirs: Vec<String>,
+    chapters: bool,
+}
+
+fn section_level(name: &str) -> Option<usize> {
+    SECTION_LEVELS
+        .iter()
+        .find(|(command, _)| *command == name)
+        .map(|(_, level)| *level)
+}
+
+/// Read the document's title, theorem declarations, graphics path and class from
+/// anywhere in `text`
+fn scan_definitions(text: &str) -> Definitions {
+    let mut definitions = Definitions::default();
+    let mut style = "plain".to_string();
+    for command in latex::commands(text) {
+        let args = latex::read_args(text, command.end, 4);
+        let first = latex::first_required(&args).map(|arg| arg.text.clone());
+        match command.name {
+            "documentclass" => {
+                definitions.chapters =
+                    first.is_some_and(|class| CHAPTER_CLASSES.contains(&class.trim()));
+            }
+            "title" => definitions.title = first,
+            "author" => definitions.author = first,
+            "date" => definitions.date = first,
+            "theoremstyle" => style = first.unwrap_or_default().trim().to_string(),
+            "graphicspath" => {
+                let Some(paths) = first else { continue };
+                let mut dirs = Vec::new();
+                let mut pos = latex::skip_arg_whitespace(paths.as_bytes(), 0);
+                while let Some((dir, after)) = latex::read_group(&paths, pos, b'{', b'}') {
+                    dirs.push(dir);
+                    pos = latex::skip_arg_whitespace(paths.as_bytes(), after);
+                }
+                if dirs.is_empty() {
+                    dirs.push(paths);
+                }
+                definitions.graphics_dirs = dirs
+                    .into_iter()
+                    .map(|dir| dir.trim().trim_end_matches('/').to_string() + "/")
+                    .collect();
+            }
+            "newtheorem" => {
+                // \newtheorem{name}[shared]{Title} or \newtheorem{name}{Title}[within]
+                let mut environment = None;
+                let (mut shared, mut title, mut within) = (None, None, None);
+                for arg in &args {
+                    match (arg.kind, &environment, &title) {
+                        (latex::ArgKind::Required, None, _) => environment = Some(arg.text.trim()),
+                        (latex::ArgKind::Optional, Some(_), None) => shared = Some(arg.text.trim()),
+                        (latex::ArgKind::Required, Some(_), None) => title = Some(arg.text.trim()),
+                        (latex::ArgKind::Optional, Some(_), Some(_)) => {
+                            within = section_level(arg.text.trim());
+                            break;
+                        }
+                        _ => break,
+                    }
+                }
+                let (Some(environment), Some(title)) = (environment, title) else {
+                    continue;
+                };
+
+                let starred = text[..command.end].ends_with('*');
+                let (counter, within) = match shared {
+                    _ if starred => (None, None),
+                    Some(shared) => match definitions
+                        .theorems
+                        .iter()
+                        .find(|theorem| theorem.environment == shared)
+                    {
+                        Some(theorem) => (theorem.counter.clone(), theorem.within),
+                        None => (Some(shared.to_string()), None),
+                    },
+                    None => (Some(environment.to_string()), within),
+                };
+                definitions.theorems.push(Theorem {
+                    environment: environment.to_string(),
+                    title: title.to_string(),
+                    counter,
+                    within,
+                    style: style.clone(),
+                });
+            }
+            _ => {}
+        }
+    }
+    definitions
+}
+
+/// `content` with its comments removed as TeX reads them: a line that is only a
+/// comment goes entirely, and one ending in a comment runs into the next line
+fn strip_comments(content: &str) -> String {
+    let verbatim = latex::verbatim_lines(content);
+    let mut out = String::with_capacity(content.len());
+    let mut joined = false;
+    for (line, inside) in content.split('\n').zip(verbatim) {
+        if inside {
+            out.push_str(line);
+            out.push('\n');
+            joined = false;
+            continue;
+        }
+
+        let code = latex::strip_comment(line);
+        let commented = code.len() < line.len();
+        if commented && code.trim().is_empty() {
+            continue;
+        }
+        let code = if joined { code.trim_start() } else { code };
+        if joined && code.is_empty() {
+            // A blank line still ends the paragraph
+            out.push_str("\n\n");
+            joined = false;
+            continue;
+        }
+        out.push_str(code);
+        joined = commented;
+        if !commented {
+            out.push('\n');
+        }
+    }
+    out
+}
+
+/// `content` without comments and with the files it includes read in place, those of
+/// subfiles without their preamble. Missing includes and include cycles are dropped.
+fn expand(project_dir: &Path, content: &str, stack: &mut Vec<String>) -> String {
+    let text = strip_comments(content);
+    let mut out = String::with_capacity(text.len());
+    let mut last = 0;
+    for command in latex::commands(&text) {
+        if command.start < last || !latex::INCLUDE_COMMANDS.contains(&command.name) {
+            continue;
+        }
+        let args = latex::read_args(&text, command.end, 1);
+        let Some(target) = latex::first_required(&args) else {
+            continue;
+        };
+        out.push_str(&text[last..command.start]);
+        last = latex::skip_args(&text, command.end, 1);
+
+        let Some(child) = latex::resolve_tex_input(project_dir, &target.text) else {
+            continue;
+        };
+        if stack.contains(&child) {
+            continue;
+        }
+        let Ok(source) = latex::read_source(project_dir, &child) else {
+            continue;
+        };
+        stack.push(child);
+        let child_text = expand(project_dir, &source, stack);
+        stack.pop();
+
+        // \include and \subfile start a new page, and so a new paragraph
+        let separate = command.name != "input";
+        if separate {
+            out.push_str("\n\n");
+        }
+        out.push_str(&child_text[prose::body_range(&child_text)]);
+        if separate {
+            out.push_str("\n\n");
+        }
+    }
+    out.push_str(&text[last..]);
+    out
+}
+
+/// A braced group at `pos` or after the whitespace there, with where it ends
+fn required(text: &str, pos: usize) -> Option<(String, usize)> {
+    latex::read_group(
+        text,
+        latex::skip_arg_whitespace(text.as_bytes(), pos),
+        b'{',
+        b'}',
+    )
+}
+
+/// A bracketed group at `pos` or after the whitespace there, with where it ends
+fn optional(text: &str, pos: usize) -> Option<(String, usize)> {
+    latex::read_group(
+        text,
+        latex::skip_arg_whitespace(text.as_bytes(), pos),
+        b'[',
+        b']',
+    )
+}
+
+/// The text between `from` and the next unescaped `delimiter`, and the offset past
+/// the delimiter; everything to the end when it's missing
+fn delimited<'t>(text: &'t str, from: usize, delimiter: &str) -> (&'t str, usize) {
+    let end = prose::find_closing(text, from, delimiter);
+    if text[..end].ends_with(delimiter) && end >= from + delimiter.len() {
+        (&text[from..end - delimiter.len()], end)
+    } else {
+        (&text[from..], end)
+    }
+}
+
+fn roman(mut n: usize) -> String {
+    const NUMERALS: &[(usize, &str)] = &[
+        (1000, "M"),
+        (900, "CM"),
+        (500, "D"),
+        (400, "CD"),
+        (100, "C"),
+        (90, "XC"),
+        (50, "L"),
+        (40, "XL"),
+        (10, "X"),
+        (9, "IX"),
+        (5, "V"),
+        (4, "IV"),
+        (1, "I"),
+    ];
+    let mut numeral = String::new();
+    for (value, letters) in NUMERALS {
+        while n >= *value {
+            numeral.push_str(letters);
+            n -= value;
+        }
+    }
+    numeral
+}
+
+/// The `n`th capital letter, as LaTeX numbers appendices
+fn letter(n: usize) -> String {
+    match n {
+        1..=26 => ((b'A' + (n - 1) as u8) as char).to_string(),
+        _ => n.to_string(),
+    }
+}
+
+/// A LaTeX width, e.g. `0.5\textwidth` or `4cm`, as a CSS width
+fn css_width(value: &str) -> Option<String> {
+    let value = value.trim();
+    for relative in ["\\textwidth", "\\linewidth", "\\columnwidth", "\\hsize"] {
+        if let Some(factor) = value.strip_suffix(relative) {
+            let factor = match factor.trim() {
+                "" => 1.0,
+                factor => factor.parse::<f64>().ok()?,
+            };
+            return Some(format!("{}%", (factor * 100.0).round()));
+        }
+    }
+    ["cm", "mm", "in", "pt", "em", "ex"]
+        .iter()
+        .find_map(|unit| {
+            let number = value.strip_suffix(unit)?.trim();
+            number
+                .parse::<f64>()
+                .ok()
+                .map(|_| format!("{}{}", number, unit))
+        })
+}
+
+/// The column alignments of a tabular column spec, e.g. `|l|c|p{3cm}|`
+fn column_alignments(spec: &str) -> Vec<&'static str> {
+    let bytes = spec.as_bytes();
+    let mut columns = Vec::new();
+    let mut i = 0;
+    while i < bytes.len() {
+        let group = |at: usize| required(spec, at);
+        match bytes[i] {
+            b'l' => columns.push("left"),
+            b'c' | b'S' => columns.push("center"),
+            b'r' => columns.push("right"),
+            b'p' | b'm' | b'b' | b'X' => {
+                columns.push("left");
+                if let Some((_, after)) = group(i + 1).filter(|_| bytes[i] != b'X') {
+                    i = after;
+                    continue;
+                }
+            }
+            b'@' | b'!' | b'>' | b'<' => {
+                if let Some((_, after)) = group(i + 1) {
+                    i = after;
+                    continue;
+                }
+            }
+            b'*' => {
+                if let Some((count, after)) = group(i + 1) {
+                    if let Some((repeated, after)) = group(after) {
+                        let count = count.trim().parse().unwrap_or(0);
+                        for _ in 0..count {
+                            columns.extend(column_alignments(&repeated));
+                        }
+                        i = after;
+                        continue;
+                    }
+                }
+            }
+            _ => {}
+        }
+        i += 1;
+    }
+    columns
+}
+
+/// `row` without the rules before it, and whether it had any
+fn strip_rules(row: &str) -> (bool, &str) {
+    let mut rest = row.trim_start();
+    let mut ruled = false;
+    while let Some(name) = rest.strip_prefix('\\') {
+        let length = name.bytes().take_while(u8::is_ascii_alphabetic).count();
+        let Some((_, groups)) = TABLE_RULES
+            .iter()
+            .find(|(rule, _)| *rule == &name[..length])
+        else {
+            break;
+        };
+        let mut end = 1 + length;
+        // \cmidrule(lr){2-3}
+        if rest[end..].starts_with('(') {
+            end += rest[end..].find(')').map_or(0, |i| i + 1);
+        }
+        end = latex::skip_args(rest, end, *groups);
+        rest = rest[end..].trim_start();
+        ruled = true;
+    }
+    (ruled, rest)
+}
+
+/// The place a \label refers to
+#[derive(Debug, Clone)]
+struct Target {
+    /// What \autoref calls it, e.g. "Figure"
+    kind: String,
+    number: String,
+    id: String,
+}
+
+struct Heading {
+    level: usize,
+    id: String,
+    number: Option<String>,
+    title: String,
+}
+
+struct Caption {
+    number: String,
+    id: String,
+    html: String,
+    /// Whether it came before the rest of the float, as table captions usually do
+    first: bool,
+}
+
+/// A figure or table being rendered, and its subfigures or subtables
+struct Float {
+    kind: &'static str,
+    caption: Option<Caption>,
+    subfloats: usize,
+}
+
+/// Output state set aside while text is rendered on its own, e.g. a caption
+struct Buffer {
+    out: String,
+    paragraph: bool,
+    inline: bool,
+    pending_space: bool,
+    fresh: bool,
+    lead: Option<String>,
+    declarations: Vec<(String, &'static str)>,
+}
+
+struct Converter<'a> {
+    project_dir: &'a Path,
+    assets_dir: &'a str,
+    /// The text of the .bbl BibTeX wrote, shown at \bibliography
+    bibliography: Option<String>,
+    definitions: Definitions,
+    out: String,
+    /// Whether a <p> is open
+    paragraph: bool,
+    /// Whether the text being rendered is inside a line, e.g. a heading, so paragraph
+    /// breaks become spaces
+    inline: bool,
+    /// Whether whitespace came since the last text written
+    pending_space: bool,
+    /// Whether nothing has been written since the paragraph or line started
+    fresh: bool,
+    /// Markup to start the next paragraph with, e.g. a theorem's heading
+    lead: Option<String>,
+    /// Open styles, with the markup that opens and closes them, kept across paragraphs
+    declarations: Vec<(String, &'static str)>,
+    /// Sectioning counters by level
+    counters: [usize; 7],
+    appendix: bool,
+    /// False in \frontmatter and \backmatter, where chapters aren't numbered
+    numbered_chapters: bool,
+    figures: usize,
+    tables: usize,
+    equations: usize,
+    theorem_counters: HashMap<String, usize>,
+    /// For each open enumerate, its item count, the prefix its references take from
+    /// the enclosing item, and the current item's reference
+    enumerations: Vec<(usize, String, String)>,
+    floats: Vec<Float>,
+    target: Option<Target>,
+    labels: HashMap<String, Target>,
+    /// Bibliography keys and their numbers
+    citations: HashMap<String, usize>,
+    ids: usize,
+    headings: Vec<Heading>,
+    footnotes: Vec<String>,
+    /// Graphics to copy, as (project-relative source, page-relative destination)
+    assets: Vec<(String, String)>,
+}
+
+/// A page and the files it shows
+#[derive(Debug, Clone)]
+pub struct HtmlDocument {
+    pub html: String,
+    /// Graphics the page shows, as (project-relative source, page-relative destination)
+    pub assets: Vec<(String, String)>,
+}
+
+/// Render the document rooted at `root_file` as a standalone HTML page. Graphics are
+/// referenced under `assets_dir`, relative to the page, and listed for copying there.
+/// `bibliography` is the .bbl of the last build, shown where the document prints its
+/// bibliography.
+pub fn render(
+    project_dir: &Path,
+    root_file: &str,
+    assets_dir: &str,
+    bibliography: Option<&str>,
+) -> Result<HtmlDocument, String> {
+    let root_file = latex::project_file(root_file)?;
+    let content = latex::read_source(project_dir, &root_file)?;
+    let expanded = expand(project_dir, &content, &mut vec![root_file.clone()]);
+    let definitions = scan_definitions(&expanded);
+
+    let mut converter = Converter {
+        project_dir,
+        assets_dir,
+        bibliography: bibliography
+            .filter(|bbl| bbl.contains("\\begin{thebibliography}"))
+            .map(strip_comments),
+        definitions,
+        out: String::new(),
+        paragraph: false,
+        inline: false,
+        pending_space: false,
+        fresh: true,
+        lead: None,
+        declarations: Vec::new(),
+        counters: [0; 7],
+        appendix: false,
+        numbered_chapters: true,
+        figures: 0,
+        tables: 0,
+        equations: 0,
+        theorem_counters: HashMap::new(),
+        enumerations: Vec::new(),
+        floats: Vec::new(),
+        target: None,
+        labels: HashMap::new(),
+        citations: HashMap::new(),
+        ids: 0,
+        headings: Vec::new(),
+        footnotes: Vec::new(),
+        assets: Vec::new(),
+    };
+    converter.convert(&expanded[prose::body_range(&expanded)]);
+    converter.close_paragraph();
+
+    let title = converter
+        .definitions
+        .title
+        .as_deref()
+        .map(|title| {
+            prose::file_prose(title)
+                .split_whitespace()
+                .collect::<Vec<_>>()
+                .join(" ")
+        })
+        .filter(|title| !title.is_empty())
+        .unwrap_or_else(|| {
+            let stem = Path::new(&root_file).file_stem();
+            stem.map(|stem| stem.to_string_lossy().into_owned())
+                .unwrap_or_default()
+        });
+
+    let mut page = format!(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n\
+         <meta name=\"viewport\" content=\"width=device-width, initial-scale=1\">\n\
+         <title>{}</title>\n<style>\n{}</style>\n</head>\n<body>\n",
+        escape(&title),
+        STYLESHEET
+    );
+    page.push_str(&converter.out);
+    if !converter.footnotes.is_empty() {
+        page.push_str("<section class=\"footnotes\">\n<ol>\n");
+        for (i, footnote) in converter.footnotes.iter().enumerate() {
+            page.push_str(&format!(
+                "<li id=\"footnote-{0}\">{1} <a href=\"#footnote-ref-{0}\">↩</a></li>\n",
+                i + 1,
+                footnote
+            ));
+        }
+        page.push_str("</ol>\n</section>\n");
+    }
+    page.push_str("</body>\n</html>\n");
+
+    let html = converter.resolve(&page).nfc().collect();
+    Ok(HtmlDocument {
+        html,
+        assets: converter.assets,
+    })
+}
+
+/// Marks where a reference, citation or table of contents goes, filled in once the
+/// whole document has been read: `\u{1}` kind `\u{2}` key `\u{3}`
+fn placeholder(kind: char, key: &str) -> String {
+    format!("\u{1}{}\u{2}{}\u{3}", kind, key)
+}
+
+impl Converter<'_> {
+    /// Write inline markup or text, opening a paragraph first if none is
+    fn emit(&mut self, html: &str) {
+        if !self.inline && !self.paragraph {
+            self.out.push_str("<p>");
+            self.paragraph = true;
+            if let Some(lead) = self.lead.take() {
+                self.out.push_str(&lead);
+            }
+            for (open, _) in &self.declarations {
+                self.out.push_str(open);
+            }
+            self.fresh = true;
+        }
+        if self.pending_space && !self.fresh {
+            self.out.push(' ');
+        }
+        self.pending_space = false;
+        self.fresh = false;
+        self.out.push_str(html);
+    }
+
+    fn close_paragraph(&mut self) {
+        if self.paragraph {
+            for (_, close) in self.declarations.iter().rev() {
+                self.out.push_str(close);
+            }
+            self.out.push_str("</p>\n");
+            self.paragraph = false;
+        }
+        self.pending_space = false;
+    }
+
+    fn paragraph_break(&mut self) {
+        if self.inline {
+            self.pending_space = true;
+        } else {
+            self.close_paragraph();
+        }
+    }
+
+    /// Write block markup, between paragraphs
+    fn block(&mut self, html: &str) {
+        self.close_paragraph();
+        if let Some(lead) = self.lead.take() {
+            self.out.push_str(&format!("<p>{}</p>\n", lead.trim_end()));
+        }
+        self.out.push_str(html);
+        self.fresh = true;
+    }
+
+    fn next_id(&mut self, prefix: &str) -> String {
+        self.ids += 1;
+        format!("{}-{}", prefix, self.ids)
+    }
+
+    fn begin_buffer(&mut self, inline: bool) -> Buffer {
+        let buffer = Buffer {
+            out: std::mem::take(&mut self.out),
+            paragraph: self.paragraph,
+            inline: self.inline,
+            pending_space: self.pending_space,
+            fresh: self.fresh,
+            lead: self.lead.take(),
+            declarations: std::mem::take(&mut self.declarations),
+        };
+        self.paragraph = false;
+        self.inline = inline;
+        self.pending_space = false;
+        self.fresh = true;
+        buffer
+    }
+
+    fn end_buffer(&mut self, buffer: Buffer) -> String {
+        self.close_paragraph();
+        if let Some(lead) = self.lead.take() {
+            self.out.push_str(&format!("<p>{}</p>\n", lead.trim_end()));
+        }
+        let rendered = std::mem::replace(&mut self.out, buffer.out);
+        self.paragraph = buffer.paragraph;
+        self.inline = buffer.inline;
+        self.pending_space = buffer.pending_space;
+        self.fresh = buffer.fresh;
+        self.lead = buffer.lead;
+        self.declarations = buffer.declarations;
+        rendered
+    }
+
+    /// The HTML for `text` on its own, inside a line or as blocks
+    fn render(&mut self, text: &str, inline: bool) -> String {
+        let buffer = self.begin_buffer(inline);
+        self.convert(text);
+        self.end_buffer(buffer)
+    }
+
+    /// Write `text` in the style `open` starts and `close` ends
+    fn styled(&mut self, open: String, close: &'static str, text: &str) {
+        if open.is_empty() {
+            self.convert(text);
+            return;
+        }
+        self.emit(&open);
+        self.declarations.push((open, close));
+        self.convert(text);
+        self.declarations.pop();
+        if self.paragraph || self.inline {
+            self.out.push_str(close);
+        }
+    }
+
+    fn convert(&mut self, text: &str) {
+        let bytes = text.as_bytes();
+        let depth = self.declarations.len();
+        let mut i = 0;
+        while i < bytes.len() {
+            match bytes[i] {
+                b'\\' => i = self.control_sequence(text, i),
+                b'$' => {
+                    let display = bytes.get(i + 1) == Some(&b'$');
+                    let delimiter = if display { "$$" } else { "$" };
+                    let (tex, end) = delimited(text, i + delimiter.len(), delimiter);
+                    self.math(tex, display);
+                    i = end;
+                }
+                b'{' => match latex::read_group(text, i, b'{', b'}') {
+                    Some((group, after)) => {
+                        self.convert(&group);
+                        i = after;
+                    }
+                    None => i += 1,
+                },
+                b'}' | b'&' => i += 1,
+                b'~' => {
+                    self.pending_space = false;
+                    self.emit("&nbsp;");
+                    self.fresh = true;
+                    i += 1;
+                }
+                b' ' | b'\t' | b'\r' | b'\n' => {
+                    let length = bytes[i..]
+                        .iter()
+                        .take_while(|b| b.is_ascii_whitespace())
+                        .count();
+                    let newlines = bytes[i..i + length].iter().filter(|b| **b == b'\n');
+                    if newlines.count() >= 2 {
+                        self.paragraph_break();
+                    } else {
+                        self.pending_space = true;
+                    }
+                    i += length;
+                }
+                // TeX ligatures for dashes and curly quotes
+                b'-' if bytes.get(i + 1) == Some(&b'-') => {
+                    if bytes.get(i + 2) == Some(&b'-') {
+                        self.emit("—");
+                        i += 3;
+                    } else {
+                        self.emit("–");
+                        i += 2;
+                    }
+                }
+                b'`' | b'\'' => {
+                    let double = bytes.get(i + 1) == Some(&bytes[i]);
+                    self.emit(match (bytes[i], double) {
+                        (b'`', true) => "“",
+                        (b'`', false) => "‘",
+                        (_, true) => "”",
+                        (_, false) => "’",
+                    });
+                    i += if double { 2 } else { 1 };
+                }
+                _ => {
+                    let c = text[i..].chars().next().unwrap_or(' ');
+                    self.emit(&escape(&c.to_string()));
+                    i += c.len_utf8();
+                }
+            }
+        }
+
+        while self.declarations.len() > depth {
+            let (_, close) = self.declarations.pop().unwrap_or_default();
+            if self.paragraph || self.inline {
+                self.out.push_str(close);
+            }
+        }
+    }
+
+    fn math(&mut self, tex: &str, display: bool) {
+        let attribute = if display { " display=\"block\"" } else { "" };
+        self.emit(&format!(
+            "<math{}>{}</math>",
+            attribute,
+            mathml(tex, display)
+        ));
+    }
+
+    /// Handle the control sequence starting at `start` and return where rendering
+    /// resumes
+    fn control_sequence(&mut self, text: &str, start: usize) -> usize {
+        let bytes = text.as_bytes();
+        let name_start = start + 1;
+        let name_length = bytes[name_start..]
+            .iter()
+            .take_while(|b| b.is_ascii_alphabetic())
+            .count();
+        if name_length == 0 {
+            return self.control_symbol(text, name_start);
+        }
+        let name_end = name_start + name_length;
+        let starred = bytes.get(name_end) == Some(&b'*');
+        let end = name_end + usize::from(starred);
+        let name = &text[name_start..name_end];
+
+        if let Some(level) = section_level(name) {
+            let pos = latex::skip_args(text, end, 0);
+            let Some((title, after)) = required(text, pos) else {
+                return end;
+            };
+            self.heading(level, starred, &title);
+            return after;
+        }
+        if let Some((_, style)) = REFERENCE_STYLES.iter().find(|(r, _)| *r == name) {
+            let Some((key, after)) = required(text, end) else {
+                return end;
+            };
+            let key = key.trim().to_string();
+            self.emit(&placeholder(*style, &key));
+            return after;
+        }
+        if CITE_COMMANDS.contains(&name) {
+            return self.citation(text, end);
+        }
+        if let Some((_, open, close)) = TEXT_STYLES.iter().find(|(s, _, _)| *s == name) {
+            let Some((content, after)) = required(text, end) else {
+                return end;
+            };
+            self.styled(open.to_string(), close, &content);
+            return after;
+        }
+        if let Some((_, open, close)) = DECLARATIONS.iter().find(|(d, _, _)| *d == name) {
+            self.emit(open);
+            self.declarations.push((open.to_string(), close));
+            return prose::skip_symbol_space(text, end);
+        }
+        if let Some((_, symbol)) = prose::TEXT_SYMBOLS.iter().find(|(s, _)| *s == name) {
+            self.emit(&escape(symbol));
+            return prose::skip_symbol_space(text, end);
+        }
+        if let Some((_, mark)) = prose::LETTER_ACCENTS.iter().find(|(a, _)| *a == name) {
+            return self.accent(text, end, *mark);
+        }
+        if let Some((_, groups)) = DEFINITION_COMMANDS.iter().find(|(d, _)| *d == name) {
+            if name == "tableofcontents" {
+                self.block(&placeholder('t', ""));
+            }
+            return latex::skip_args(text, end, *groups);
+        }
+
+        match name {
+            "begin" => match required(text, end) {
+                Some((environment, after)) => self.environment(text, environment.trim(), after),
+                None => end,
+            },
+            "end" => latex::skip_args(text, end, 1),
+            "par" => {
+                self.paragraph_break();
+                end
+            }
+            "newline" | "linebreak" | "break" => {
+                self.line_break();
+                latex::skip_args(text, end, 0)
+            }
+            "maketitle" => {
+                self.title_block();
+                end
+            }
+            "appendix" => {
+                self.appendix = true;
+                let top = if self.definitions.chapters { 1 } else { 2 };
+                self.counters[top..].fill(0);
+                end
+            }
+            "frontmatter" | "backmatter" => {
+                self.numbered_chapters = false;
+                end
+            }
+            "mainmatter" => {
+                self.numbered_chapters = true;
+                self.counters[1..].fill(0);
+                end
+            }
+            "label" => {
+                let Some((key, after)) = required(text, end) else {
+                    return end;
+                };
+                if let Some(target) = self.target.clone() {
+                    self.labels.entry(key.trim().to_string()).or_insert(target);
+                }
+                after
+            }
+            "caption" => {
+                let Some((caption, after)) = required(text, latex::skip_args(text, end, 0)) else {
+                    return end;
+                };
+                self.caption(&caption);
+                after
+            }
+            "captionof" => {
+                let Some((kind, after)) = required(text, end) else {
+                    return end;
+                };
+                let Some((caption, after)) = required(text, latex::skip_args(text, after, 0))
+                else {
+                    return after;
+                };
+                let kind = if kind.trim() == "table" {
+                    "table"
+                } else {
+                    "figure"
+                };
+                self.floats.push(Float {
+                    kind,
+                    caption: None,
+                    subfloats: 0,
+                });
+                self.caption(&caption);
+                if let Some(caption) = self.floats.pop().and_then(|float| float.caption) {
+                    self.block(&format!(
+                        "<p class=\"caption\" id=\"{}\">{}</p>\n",
+                        caption.id, caption.html
+                    ));
+                }
+                after
+            }
+            "footnote" => {
+                let Some((note, after)) = required(text, latex::skip_args(text, end, 0)) else {
+                    return end;
+                };
+                let html = self.render(&note, true);
+                self.footnotes.push(html);
+                let n = self.footnotes.len();
+                self.pending_space = false;
+                self.emit(&format!(
+                    "<sup class=\"footnote-ref\"><a href=\"#footnote-{0}\" \
+                     id=\"footnote-ref-{0}\">{0}</a></sup>",
+                    n
+                ));
+                after
+            }
+            "url" => {
+                let Some((url, after)) = required(text, end) else {
+                    return end;
+                };
+                let url = unescape_url(&url);
+                self.emit(&format!(
+                    "<a href=\"{0}\"><code>{0}</code></a>",
+                    escape(&url)
+                ));
+                after
+            }
+            "href" => {
+                let Some((url, after)) = required(text, end) else {
+                    return end;
+                };
+                let Some((content, after)) = required(text, after) else {
+                    return after;
+                };
+                let open = format!("<a href=\"{}\">", escape(&unescape_url(&url)));
+                self.styled(open, "</a>", &content);
+                after
+            }
+            "textcolor" | "color" => {
+                let Some((color, after)) = required(text, latex::skip_args(text, end, 0)) else {
+                    return end;
+                };
+                let color: String = color
+                    .split('!')
+                    .next()
+                    .unwrap_or_default()
+                    .chars()
+                    .filter(|c| c.is_ascii_alphanumeric() || *c == '#')
+                    .collect();
+                let open = if color.is_empty() {
+                    String::new()
+                } else {
+                    format!("<span style=\"color: {}\">", color)
+                };
+                if name == "color" {
+                    if !open.is_empty() {
+                        self.emit(&open);
+                        self.declarations.push((open, "</span>"));
+                    }
+                    return after;
+                }
+                let Some((content, after)) = required(text, after) else {
+                    return after;
+                };
+                self.styled(open, "</span>", &content);
+                after
+            }
+            "includegraphics" => {
+                let options = optional(text, end);
+                let pos = options.as_ref().map_or(end, |(_, after)| *after);
+                let Some((file, after)) = required(text, pos) else {
+                    return end;
+                };
+                let options = options.map(|(options, _)| options).unwrap_or_default();
+                self.graphic(&options, &file);
+                after
+            }
+            "verb" => {
+                let Some(&delimiter) = bytes.get(end).filter(|b| b.is_ascii()) else {
+                    return end;
+                };
+                let code_end = text[end + 1..]
+                    .find(delimiter as char)
+                    .map_or(text.len(), |i| end + 1 + i);
+                self.emit(&format!(
+                    "<code>{}</code>",
+                    escape(&text[end + 1..code_end])
+                ));
+                (code_end + 1).min(text.len())
+            }
+            "ensuremath" => {
+                let Some((tex, after)) = required(text, end) else {
+                    return end;
+                };
+                self.math(&tex, false);
+                after
+            }
+            "bibliography" | "printbibliography" => {
+                if let Some(bibliography) = self.bibliography.take() {
+                    self.convert(&bibliography);
+                }
+                latex::skip_args(text, end, usize::from(name == "bibliography"))
+            }
+            "today" => {
+                self.emit(&chrono::Local::now().format("%B %-d, %Y").to_string());
+                prose::skip_symbol_space(text, end)
+            }
+            "quad" | "qquad" => {
+                self.pending_space = false;
+                self.emit(if name == "quad" {
+                    "\u{2003}"
+                } else {
+                    "\u{2003}\u{2003}"
+                });
+                self.fresh = true;
+                end
+            }
+            _ => {
+                let groups = prose::DROPPED_COMMANDS
+                    .iter()
+                    .find(|(dropped, _)| *dropped == name)
+                    .map_or(0, |(_, n)| *n);
+                if groups == 0 {
+                    return prose::skip_symbol_space(text, end);
+                }
+                latex::skip_args(text, end, groups)
+            }
+        }
+    }
+
+    /// Handle a control symbol such as `\%` or `\[` whose character is at `pos`
+    fn control_symbol(&mut self, text: &str, pos: usize) -> usize {
+        let Some(c) = text[pos..].chars().next() else {
+            return pos;
+        };
+
+        match c {
+            '[' => {
+                let (tex, end) = delimited(text, pos + 1, "\\]");
+                self.math(tex, true);
+                end
+            }
+            '(' => {
+                let (tex, end) = delimited(text, pos + 1, "\\)");
+                self.math(tex, false);
+                end
+            }
+            '\\' => {
+                self.line_break();
+                let mut end = pos + 1;
+                if text[end..].starts_with('*') {
+                    end += 1;
+                }
+                // The [length] of extra space is only read straight after
+                match text[end..].starts_with('[') {
+                    true => latex::read_group(text, end, b'[', b']').map_or(end, |(_, a)| a),
+                    false => end,
+                }
+            }
+            '%' | '&' | '$' | '#' | '_' | '{' | '}' => {
+                self.emit(&escape(&c.to_string()));
+                pos + 1
+            }
+            ',' => {
+                self.pending_space = false;
+                self.emit("\u{202f}");
+                self.fresh = true;
+                pos + 1
+            }
+            ';' | ':' | ' ' | '\n' | '\t' => {
+                self.pending_space = true;
+                pos + 1
+            }
+            _ => match prose::SYMBOL_ACCENTS
+                .iter()
+                .find(|(accent, _)| *accent == c)
+            {
+                Some((_, mark)) => self.accent(text, pos + 1, *mark),
+                // Discretionary hyphens and the like attach to the surrounding word
+                None => pos + c.len_utf8(),
+            },
+        }
+    }
+
+    fn line_break(&mut self) {
+        if self.paragraph || self.inline {
+            self.out.push_str("<br>");
+            self.pending_space = false;
+            self.fresh = true;
+        }
+    }
+
+    /// Write the letter at `pos` an accent applies to (`e`, `{e}` or `{\i}`) followed by
+    /// its combining `mark`, and return where rendering resumes
+    fn accent(&mut self, text: &str, pos: usize, mark: char) -> usize {
+        let bytes = text.as_bytes();
+        let mut pos = pos;
+        while matches!(bytes.get(pos), Some(b' ' | b'\t')) {
+            pos += 1;
+        }
+
+        let (base, resume) = match latex::read_group(text, pos, b'{', b'}') {
+            Some((group, after)) => (group.trim().to_string(), after),
+            None => {
+                let rest = &text[pos..];
+                match ["\\i", "\\j"].iter().find(|d| {
+                    rest.starts_with(**d)
+                        && !rest[2..].starts_with(|c: char| c.is_ascii_alphabetic())
+                }) {
+                    Some(dotless) => (dotless.to_string(), pos + 2),
+                    None => match rest.chars().next() {
+                        Some(c) => (c.to_string(), pos + c.len_utf8()),
+                        None => return pos,
+                    },
+                }
+            }
+        };
+
+        let base = match base.as_str() {
+            "\\i" => "i",
+            "\\j" => "j",
+            other => other,
+        };
+        let mut chars = base.chars();
+        if let Some(letter) = chars.next() {
+            let accented: String = [letter, mark].into_iter().chain(chars).collect();
+            self.emit(&escape(&accented));
+        }
+        resume
+    }
+
+    /// The number of the `n`th figure, table or equation, within its chapter when the
+    /// document has chapters
+    fn counted(&self, n: usize) -> String {
+        if self.definitions.chapters && self.counters[1] > 0 {
+            format!("{}.{}", self.section_number(1), n)
+        } else {
+            n.to_string()
+        }
+    }
+
+    /// The number of the current heading at `level`, e.g. "2.3" for a subsection
+    fn section_number(&self, level: usize) -> String {
+        if level == 0 {
+            return roman(self.counters[0]);
+        }
+        let top = if self.definitions.chapters { 1 } else { 2 };
+        (top..=level.max(top))
+            .map(|l| match l == top && self.appendix {
+                true => letter(self.counters[l]),
+                false => self.counters[l].to_string(),
+            })
+            .collect::<Vec<_>>()
+            .join(".")
+    }
+
+    fn heading(&mut self, level: usize, starred: bool, title: &str) {
+        let chapters = self.definitions.chapters;
+        let top = if chapters { 1 } else { 2 };
+        let deepest = if chapters { 3 } else { 4 };
+        let numbered = !starred
+            && (level == 0 || (level >= top && level <= deepest))
+            && (level != 1 || self.numbered_chapters);
+
+        let id = self.next_id("section");
+        let number = numbered.then(|| {
+            self.counters[level] += 1;
+            if level > 0 {
+                self.counters[level + 1..].fill(0);
+            }
+            if level == 1 {
+                self.figures = 0;
+                self.tables = 0;
+                self.equations = 0;
+            }
+            for theorem in &self.definitions.theorems {
+                if let (Some(counter), Some(within)) = (&theorem.counter, theorem.within) {
+                    if within >= level {
+                        self.theorem_counters.remove(counter);
+                    }
+                }
+            }
+            self.section_number(level)
+        });
+        if let Some(number) = &number {
+            let kind = match level {
+                0 => "Part",
+                _ if level == top && self.appendix => "Appendix",
+                1 => "Chapter",
+                2..=4 => "Section",
+                _ => "Paragraph",
+            };
+            self.target = Some(Target {
+                kind: kind.to_string(),
+                number: number.clone(),
+                id: id.clone(),
+            });
+        }
+
+        let title = self.render(title, true);
+        let prefix = match &number {
+            Some(number) if level == 0 => format!("Part {}<br>", number),
+            Some(number) if level == 1 && self.appendix => format!("Appendix {}<br>", number),
+            Some(number) if level == 1 => format!("Chapter {}<br>", number),
+            Some(number) => format!("<span class=\"secnum\">{}</span> ", number),
+            None => String::new(),
+        };
+        let h = (level + 2).saturating_sub(top).clamp(2, 6);
+        self.block(&format!(
+            "<h{0} id=\"{1}\">{2}{3}</h{0}>\n",
+            h, id, prefix, title
+        ));
+        self.headings.push(Heading {
+            level,
+            id,
+            number,
+            title,
+        });
+    }
+
+    fn title_block(&mut self) {
+        let mut html = String::from("<header class=\"title-block\">\n");
+        if let Some(title) = self.definitions.title.clone() {
+            html.push_str(&format!("<h1>{}</h1>\n", self.render(&title, true)));
+        }
+        if let Some(author) = self.definitions.author.clone() {
+            for name in split_authors(&author) {
+                let name = self.render(name, true);
+                if !name.trim().is_empty() {
+                    html.push_str(&format!("<p class=\"author\">{}</p>\n", name.trim()));
+                }
+            }
+        }
+        // Without a \date, LaTeX prints today's
+        let date = self.definitions.date.clone();
+        let date = self.render(date.as_deref().unwrap_or("\\today"), true);
+        if !date.trim().is_empty() {
+            html.push_str(&format!("<p class=\"date\">{}</p>\n", date.trim()));
+        }
+        html.push_str("</header>\n");
+        self.block(&html);
+    }
+
+    fn citation(&mut self, text: &str, end: usize) -> usize {
+        let args = latex::read_args(text, end, 3);
+        let Some(keys) = latex::first_required(&args) else {
+            return end;
+        };
+        let notes: Vec<&str> = args
+            .iter()
+            .take_while(|arg| arg.kind == latex::ArgKind::Optional)
+            .map(|arg| arg.text.as_str())
+            .collect();
+        // natbib's \citep[see][p. 2]{key}; a lone note comes after
+        let (before, after) = match notes.as_slice() {
+            [before, after, ..] => (Some(*before), Some(*after)),
+            [after] => (None, Some(*after)),
+            [] => (None, None),
+        };
+
+        self.emit("[");
+        if let Some(before) = before.filter(|note| !note.trim().is_empty()) {
+            let html = self.render(before, true);
+            self.out.push_str(&html);
+            self.out.push(' ');
+        }
+        self.out.push_str(&placeholder('c', keys.text.trim()));
+        if let Some(after) = after.filter(|note| !note.trim().is_empty()) {
+            let html = self.render(after, true);
+            self.out.push_str(", ");
+            self.out.push_str(&html);
+        }
+        self.out.push(']');
+        latex::skip_args(text, end, 1)
+    }
+
+    fn graphic(&mut self, options: &str, file: &str) {
+        let file = file.trim();
+        let width = options.split(',').find_map(|option| {
+            let (key, value) = option.split_once('=')?;
+            (key.trim() == "width").then(|| css_width(value)).flatten()
+        });
+        let style = width.map_or(String::new(), |w| format!(" style=\"width: {}\"", w));
+        let alt = Path::new(file)
+            .file_stem()
+            .map_or(String::new(), |stem| stem.to_string_lossy().into_owned());
+
+        let found = self.find_graphic(file);
+        let web = found.as_deref().is_some_and(|path| {
+            Path::new(path).extension().is_some_and(|ext| {
+                WEB_GRAPHICS.contains(&ext.to_string_lossy().to_lowercase().as_str())
+            })
+        });
+        match found {
+            Some(path) if web => {
+                let destination = format!("{}/{}", self.assets_dir, path);
+                if !self.assets.iter().any(|(source, _)| *source == path) {
+                    self.assets.push((path, destination.clone()));
+                }
+                let src = destination
+                    .replace('%', "%25")
+                    .replace(' ', "%20")
+                    .replace('#', "%23")
+                    .replace('?', "%3F");
+                self.emit(&format!(
+                    "<img src=\"{}\" alt=\"{}\"{}>",
+                    escape(&src),
+                    escape(&alt),
+                    style
+                ));
+            }
+            _ => self.emit(&format!(
+                "<span class=\"unsupported\">[Image: {}]</span>",
+                escape(file)
+            )),
+        }
+    }
+
+    /// The project file an \includegraphics name refers to, looked for the way
+    /// graphicx does: in the project and each \graphicspath directory, with each known
+    /// extension when the name has none
+    fn find_graphic(&self, file: &str) -> Option<String> {
+        let mut dirs = vec![String::new()];
+        dirs.extend(self.definitions.graphics_dirs.iter().cloned());
+        let has_extension = Path::new(file).extension().is_some_and(|ext| {
+            let ext = ext.to_string_lossy().to_lowercase();
+            WEB_GRAPHICS.contains(&ext.as_str()) || PRINT_GRAPHICS.contains(&ext.as_str())
+        });
+
+        let mut candidates = Vec::new();
+        if has_extension {
+            candidates.extend(dirs.iter().map(|dir| format!("{}{}", dir, file)));
+        } else {
+            for ext in WEB_GRAPHICS.iter().chain(PRINT_GRAPHICS) {
+                candidates.extend(dirs.iter().map(|dir| format!("{}{}.{}", dir, file, ext)));
+            }
+        }
+        candidates.into_iter().find_map(|candidate| {
+            let relative = latex::normalize_relative(Path::new(&candidate))?;
+            self.project_dir
+                .join(&relative)
+                .is_file()
+                .then(|| latex::to_project_path(&relative))
+        })
+    }
+
+    /// Number the innermost float's caption and keep it for the float to place
+    fn caption(&mut self, caption: &str) {
+        let Some(index) = self.floats.len().checked_sub(1) else {
+            // A \caption outside a float typesets nothing useful
+            return;
+        };
+        let kind = self.floats[index].kind;
+        let (number, shown) = match kind {
+            "subfigure" | "subtable" => {
+                let parent = index.checked_sub(1).map(|i| &self.floats[i]);
+                let parent_number = match parent {
+                    Some(Float {
+                        caption: Some(caption),
+                        ..
+                    }) => caption.number.clone(),
+                    Some(parent) if parent.kind == "table" => self.counted(self.tables + 1),
+                    _ => self.counted(self.figures + 1),
+                };
+                let sub = match parent.map(|float| float.subfloats) {
+                    Some(n) => n + 1,
+                    None => 1,
+                };
+                if index > 0 {
+                    self.floats[index - 1].subfloats = sub;
+                }
+                let letter = letter(sub).to_lowercase();
+                (
+                    format!("{}{}", parent_number, letter),
+                    format!("({})", letter),
+                )
+            }
+            "table" => {
+                self.tables += 1;
+                let number = self.counted(self.tables);
+                (number.clone(), format!("Table {}:", number))
+            }
+            _ => {
+                self.figures += 1;
+                let number = self.counted(self.figures);
+                (number.clone(), format!("Figure {}:", number))
+            }
+        };
+
+        let id = self.next_id(kind);
+        let target_kind = if kind.ends_with("table") {
+            "Table"
+        } else {
+            "Figure"
+        };
+        self.target = Some(Target {
+            kind: target_kind.to_string(),
+            number: number.clone(),
+            id: id.clone(),
+        });
+        let html = format!("{} {}", shown, self.render(caption, true));
+        let first = self.out.trim().is_empty();
+        self.floats[index].caption = Some(Caption {
+            number,
+            id,
+            html,
+            first,
+        });
+    }
+
+    /// A figure or table, whose content `render` writes, with its caption
+    fn float(&mut self, kind: &'static str, attributes: &str, render: impl FnOnce(&mut Self)) {
+        let target = self.target.clone();
+        self.floats.push(Float {
+            kind,
+            caption: None,
+            subfloats: 0,
+        });
+        let buffer = self.begin_buffer(false);
+        render(self);
+        let body = self.end_buffer(buffer);
+        let float = self.floats.pop();
+        self.target = target;
+
+        let caption = float.and_then(|float| float.caption);
+        let id = caption
+            .as_ref()
+            .map_or(String::new(), |caption| format!(" id=\"{}\"", caption.id));
+        let mut html = format!("<figure{}{}>\n", id, attributes);
+        let figcaption = caption
+            .as_ref()
+            .map(|caption| format!("<figcaption>{}</figcaption>\n", caption.html));
+        match (
+            &figcaption,
+            caption.as_ref().is_some_and(|caption| caption.first),
+        ) {
+            (Some(figcaption), true) => {
+                html.push_str(figcaption);
+                html.push_str(&body);
+            }
+            (Some(figcaption), false) => {
+                html.push_str(&body);
+                html.push_str(figcaption);
+            }
+            (None, _) => html.push_str(&body),
+        }
+        html.push_str("</figure>\n");
+        self.block(&html);
+    }
+
+    /// Handle the environment `name` whose body starts at `after` and return where
+    /// rendering resumes
+    fn environment(&mut self, text: &str, name: &str, after: usize) -> usize {
+        let (body_end, end) = environment_body(text, name, after);
+        let body = &text[after..body_end];
+        let base = name.trim_end_matches('*');
+
+        if prose::MATH_ENVIRONMENTS.contains(&name) {
+            match base {
+                "math" => self.math(body, false),
+                "displaymath" => self.math(body, true),
+                _ => self.display_math(name, body),
+            }
+            return end;
+        }
+        if latex::VERBATIM_ENVIRONMENTS.contains(&name) {
+            if name != "comment" {
+                let skip = latex::skip_args(body, 0, usize::from(name == "minted"));
+                let code = match body[skip..].split_once('\n') {
+                    Some((first, rest)) if first.trim().is_empty() => rest,
+                    _ => &body[skip..],
+                };
+                self.block(&format!(
+                    "<pre><code>{}</code></pre>\n",
+                    escape(code.trim_end())
+                ));
+            }
+            return end;
+        }
+        if let Some(theorem) = self
+            .definitions
+            .theorems
+            .iter()
+            .find(|theorem| theorem.environment == name)
+            .cloned()
+        {
+            self.theorem(&theorem, body);
+            return end;
+        }
+        if let Some((_, tag)) = BLOCK_ENVIRONMENTS.iter().find(|(b, _)| *b == name) {
+            let element = tag.split(' ').next().unwrap_or_default();
+            self.block(&format!("<{}>\n", tag));
+            self.convert(body);
+            self.block(&format!("</{}>\n", element));
+            return end;
+        }
+        if UNSUPPORTED_ENVIRONMENTS.contains(&base) {
+            self.block(&format!(
+                "<p class=\"unsupported\">[{} not shown]</p>\n",
+                escape(base)
+            ));
+            return end;
+        }
+
+        let spec_args = prose::ENVIRONMENT_ARGS
+            .iter()
+            .find(|(environment, _)| *environment == name)
+            .map_or(0, |(_, n)| *n);
+        match base {
+            "itemize" | "enumerate" | "description" => self.list(base, body),
+            "figure" | "table" | "wrapfigure" | "wraptable" => {
+                let kind = if base.ends_with("table") {
+                    "table"
+                } else {
+                    "figure"
+                };
+                let content = &body[latex::skip_args(body, 0, spec_args)..];
+                self.float(kind, "", |converter| converter.convert(content));
+            }
+            "subfigure" | "subtable" => {
+                let width = required(body, latex::skip_args(body, 0, 0));
+                let content = &body[latex::skip_args(body, 0, 1)..];
+                let style = width
+                    .and_then(|(width, _)| css_width(&width))
+                    .map_or(String::new(), |w| format!(" style=\"width: {}\"", w));
+                let attributes = format!(" class=\"subfigure\"{}", style);
+                let kind = if base == "subtable" {
+                    "subtable"
+                } else {
+                    "subfigure"
+                };
+                self.float(kind, &attributes, |converter| converter.convert(content));
+            }
+            "tabular" | "tabularx" | "tabulary" | "longtable" => {
+                let spec_args = spec_args.max(1);
+                let args = latex::read_args(body, 0, spec_args + 2);
+                let spec = args
+                    .iter()
+                    .filter(|arg| arg.kind == latex::ArgKind::Required)
+                    .nth(spec_args - 1)
+                    .map(|arg| arg.text.clone())
+                    .unwrap_or_default();
+                let rows = &body[latex::skip_args(body, 0, spec_args)..];
+                if base == "longtable" {
+                    self.float("table", "", |converter| converter.table(&spec, rows));
+                } else {
+                    self.table(&spec, rows);
+                }
+            }
+            "abstract" => {
+                self.block("<section class=\"abstract\">\n<h2>Abstract</h2>\n");
+                self.convert(body);
+                self.block("</section>\n");
+            }
+            "proof" => {
+                let (title, content) = match optional(body, 0) {
+                    Some((title, after)) => (self.render(&title, true), &body[after..]),
+                    None => ("Proof".to_string(), body),
+                };
+                self.block("<div class=\"proof\">\n");
+                self.lead = Some(format!("<span class=\"proof-head\">{}.</span> ", title));
+                self.convert(content);
+                self.pending_space = true;
+                self.emit("<span class=\"qed\">∎</span>");
+                self.block("</div>\n");
+            }
+            "thebibliography" => self.references(&body[latex::skip_args(body, 0, 1)..]),
+            "minipage" => {
+                let width = required(body, latex::skip_args(body, 0, 0));
+                let style = width
+                    .and_then(|(width, _)| css_width(&width))
+                    .map_or(String::new(), |w| format!(" style=\"width: {}\"", w));
+                self.block(&format!("<div class=\"minipage\"{}>\n", style));
+                self.convert(&body[latex::skip_args(body, 0, 1)..]);
+                self.block("</div>\n");
+            }
+            _ => self.convert(&body[latex::skip_args(body, 0, spec_args)..]),
+        }
+        end
+    }
+
+    fn theorem(&mut self, theorem: &Theorem, body: &str) {
+        let (note, content) = match optional(body, 0) {
+            Some((note, after)) => (Some(note), &body[after..]),
+            None => (None, body),
+        };
+        let number = theorem.counter.as_ref().map(|counter| {
+            let count = self.theorem_counters.entry(counter.clone()).or_insert(0);
+            *count += 1;
+            let count = *count;
+            match theorem.within {
+                Some(level) => format!("{}.{}", self.section_number(level), count),
+                None => count.to_string(),
+            }
+        });
+
+        let id = self.next_id("theorem");
+        let title = self.render(&theorem.title, true);
+        let mut head = format!("<span class=\"theorem-head\">{}", title);
+        if let Some(number) = &number {
+            head.push_str(&format!(" {}", number));
+        }
+        if let Some(note) = note {
+            head.push_str(&format!(" ({})", self.render(&note, true)));
+        }
+        head.push_str(".</span> ");
+
+        let target = self.target.clone();
+        if let Some(number) = number {
+            self.target = Some(Target {
+                kind: title,
+                number,
+                id: id.clone(),
+            });
+        }
+        self.block(&format!(
+            "<div class=\"theorem {}\" id=\"{}\">\n",
+            escape(&theorem.style),
+            id
+        ));
+        self.lead = Some(head);
+        self.convert(content);
+        self.block("</div>\n");
+        self.target = target;
+    }
+
+    fn list(&mut self, kind: &str, body: &str) {
+        let items: Vec<(usize, usize)> = splits(body)
+            .into_iter()
+            .filter(|(_, _, split)| *split == Split::Item)
+            .map(|(start, end, _)| (start, end))
+            .collect();
+        let depth = self.enumerations.len();
+        let (open, close) = match kind {
+            "enumerate" => {
+                let style = ["1", "a", "i", "A"][depth % 4];
+                (format!("<ol type=\"{}\">\n", style), "</ol>\n")
+            }
+            "description" => ("<dl>\n".to_string(), "</dl>\n"),
+            _ => ("<ul>\n".to_string(), "</ul>\n"),
+        };
+
+        let target = self.target.clone();
+        self.block(&open);
+        if kind == "enumerate" {
+            let prefix = self
+                .enumerations
+                .last()
+                .map(|(_, _, current)| current.clone())
+                .unwrap_or_default();
+            self.enumerations.push((0, prefix, String::new()));
+        }
+        for (k, (_, start)) in items.iter().enumerate() {
+            let end = items.get(k + 1).map_or(body.len(), |(next, _)| *next);
+            let mut content = &body[*start..end];
+            let label = optional(content, 0).map(|(label, after)| {
+                content = &content[after..];
+                label
+            });
+
+            if kind == "description" {
+                let label = label.map(|l| self.render(&l, true)).unwrap_or_default();
+                self.block(&format!("<dt>{}</dt>\n<dd>\n", label));
+                self.convert(content);
+                self.block("</dd>\n");
+                continue;
+            }
+
+            let id = self.next_id("item");
+            match &label {
+                Some(label) => {
+                    let label = self.render(label, true);
+                    self.block(&format!(
+                        "<li id=\"{}\" style=\"list-style-type: none\">\n",
+                        id
+                    ));
+                    self.lead = Some(format!("{} ", label));
+                }
+                None => {
+                    if let Some((count, prefix, current)) = self.enumerations.last_mut() {
+                        *count += 1;
+                        let marker = match depth % 4 {
+                            0 => count.to_string(),
+                            1 => letter(*count).to_lowercase(),
+                            2 => roman(*count).to_lowercase(),
+                            _ => letter(*count),
+                        };
+                        *current = format!("{}{}", prefix, marker);
+                        self.target = Some(Target {
+                            kind: "Item".to_string(),
+                            number: current.clone(),
+                            id: id.clone(),
+                        });
+                    }
+                    self.block(&format!("<li id=\"{}\">\n", id));
+                }
+            }
+            self.convert(content);
+            self.block("</li>\n");
+        }
+        if kind == "enumerate" {
+            self.enumerations.pop();
+        }
+        self.block(close);
+        self.target = target;
+    }
+
+    fn references(&mut self, body: &str) {
+        let heading = if self.definitions.chapters {
+            "Bibliography"
+        } else {
+            "References"
+        };
+        self.block(&format!(
+            "<section class=\"references\">\n<h2>{}</h2>\n<ol>\n",
+            heading
+        ));
+        let items: Vec<(usize, usize)> = latex::commands(body)
+            .filter(|command| command.name == "bibitem")
+            .map(|command| (command.start, command.end))
+            .collect();
+        for (k, (_, end)) in items.iter().enumerate() {
+            let next = items.get(k + 1).map_or(body.len(), |(start, _)| *start);
+            let args = latex::read_args(body, *end, 2);
+            let Some(key) = latex::first_required(&args) else {
+                continue;
+            };
+            let key = key.text.trim().to_string();
+            let number = self.citations.len() + 1;
+            self.citations.entry(key.clone()).or_insert(number);
+
+            let content = &body[latex::skip_args(body, *end, 1)..next.max(*end)];
+            let content = content.replace("\\newblock", " ");
+            let html = self.render(&content, true);
+            self.block(&format!(
+                "<li id=\"cite-{}\">{}</li>\n",
+                escape(&key),
+                html.trim()
+            ));
+        }
+        self.block("</ol>\n</section>\n");
+    }
+
+    fn table(&mut self, spec: &str, body: &str) {
+        let alignments = column_alignments(spec);
+        let rows: Vec<(bool, &str)> = split_at(body, Split::Row)
+            .into_iter()
+            .map(strip_rules)
+            .collect();
+        let content_rows: Vec<usize> = (0..rows.len())
+            .filter(|i| !rows[*i].1.trim().is_empty())
+            .collect();
+        // A first row set off by a rule under it is the header
+        let header = match content_rows.as_slice() {
+            [first, _, ..] => rows.get(first + 1).is_some_and(|(ruled, _)| *ruled),
+            _ => false,
+        }
+        .then(|| content_rows[0]);
+
+        let mut html = String::from("<table>\n");
+        for &i in &content_rows {
+            let cell_tag = if Some(i) == header { "th" } else { "td" };
+            let mut cells = Vec::new();
+            let mut column = 0;
+            for cell in split_at(rows[i].1, Split::Cell) {
+                let (span, align, content) = match multicolumn(cell) {
+                    Some((span, align, content)) => (span, align, content),
+                    None => (
+                        1,
+                        alignments.get(column).copied().unwrap_or("left"),
+                        cell.to_string(),
+                    ),
+                };
+                column += span;
+                let rendered = self.render(&content, true);
+                cells.push((span, align, rendered.trim().to_string()));
+            }
+            // A row that only held a \caption, as in a longtable
+            if cells.iter().all(|(_, _, html)| html.is_empty()) {
+                continue;
+            }
+
+            let mut row = String::new();
+            for (span, align, html) in cells {
+                let span = if span > 1 {
+                    format!(" colspan=\"{}\"", span)
+                } else {
+                    String::new()
+                };
+                row.push_str(&format!(
+                    "<{0}{1} style=\"text-align: {2}\">{3}</{0}>",
+                    cell_tag, span, align, html
+                ));
+            }
+            if Some(i) == header {
+                html.push_str(&format!("<thead>\n<tr>{}</tr>\n</thead>\n<tbody>\n", row));
+            } else {
+                html.push_str(&format!("<tr>{}</tr>\n", row));
+            }
+        }
+        if header.is_none() {
+            html = html.replacen("<table>\n", "<table>\n<tbody>\n", 1);
+        }
+        html.push_str("</tbody>\n</table>\n");
+        self.block(&html);
+    }
+
+    /// Fill in the references, citations and table of contents in `html`
+    fn resolve(&self, html: &str) -> String {
+        let mut out = String::with_capacity(html.len());
+        let mut rest = html;
+        while let Some(start) = rest.find('\u{1}') {
+            out.push_str(&rest[..start]);
+            let marked = &rest[start + 1..];
+            let Some(end) = marked.find('\u{3}') else {
+                rest = marked;
+                break;
+            };
+            let (kind, key) = marked[..end].split_once('\u{2}').unwrap_or_default();
+            out.push_str(&self.resolve_one(kind, key));
+            rest = &marked[end + 1..];
+        }
+        out.push_str(rest);
+        out
+    }
+
+    fn resolve_one(&self, kind: &str, key: &str) -> String {
+        match kind {
+            "c" => key
+                .split(',')
+                .map(str::trim)
+                .filter(|key| !key.is_empty())
+                .map(|key| match self.citations.get(key) {
+                    Some(n) => format!("<a href=\"#cite-{}\">{}</a>", escape(key), n),
+                    None => escape(key),
+                })
+                .collect::<Vec<_>>()
+                .join(", "),
+            "t" => {
+                let mut toc = String::from("<nav class=\"toc\">\n<h2>Contents</h2>\n<ul>\n");
+                let top = self.headings.iter().map(|h| h.level).min().unwrap_or(0);
+                for heading in self.headings.iter().filter(|h| h.level <= 4) {
+                    let number = heading
+                        .number
+                        .as_ref()
+                        .map_or(String::new(), |number| format!("{} ", number));
+                    toc.push_str(&format!(
+                        "<li style=\"padding-left: {}em\"><a href=\"#{}\">{}{}</a></li>\n",
+                        (heading.level - top) * 2,
+                        heading.id,
+                        number,
+                        self.resolve(&heading.title)
+                    ));
+                }
+                toc.push_str("</ul>\n</nav>\n");
+                toc
+            }
+            _ => match self.labels.get(key) {
+                Some(target) => {
+                    let link = |text: &str| format!("<a href=\"#{}\">{}</a>", target.id, text);
+                    match kind {
+                        "e" => format!("({})", link(&target.number)),
+                        "a" => link(&format!("{} {}", target.kind, target.number)),
+                        _ => link(&target.number),
+                    }
+                }
+                None if kind == "e" => "(??)".to_string(),
+                None => "??".to_string(),
+            },
+        }
+    }
+
+    /// A display math environment, numbering its rows the way amsmath does
+    fn display_math(&mut self, name: &str, body: &str) {
+        let base = name.trim_end_matches('*');
+        let numbered = !name.ends_with('*');
+        let body = if base == "alignat" {
+            &body[latex::skip_args(body, 0, 1)..]
+        } else {
+            body
+        };
+        let mut rows = match base {
+            "equation" => vec![body],
+            _ => split_at(body, Split::Row),
+        };
+        if rows.len() > 1 && rows.last().is_some_and(|row| row.trim().is_empty()) {
+            rows.pop();
+        }
+
+        // multline numbers the whole formula once, on its last line
+        let mut numbers: Vec<Option<(String, String)>> = if base == "multline" {
+            let mut numbers = vec![None; rows.len()];
+            numbers[rows.len() - 1] = self.equation_number(body, numbered);
+            numbers
+        } else {
+            rows.iter()
+                .map(|row| self.equation_number(row, numbered))
+                .collect()
+        };
+
+        if rows.len() == 1 {
+            let number = numbers.pop().flatten();
+            let id = number
+                .as_ref()
+                .map_or(String::new(), |(_, id)| format!(" id=\"{}\"", id));
+            let eqno = number.map_or(String::new(), |(number, _)| {
+                format!("<span class=\"eqno\">({})</span>", escape(&number))
+            });
+            self.block(&format!(
+                "<div class=\"equation\"{}><math display=\"block\">{}</math>{}</div>\n",
+                id,
+                mathml(rows[0], true),
+                eqno
+            ));
+            return;
+        }
+
+        let aligned = matches!(base, "align" | "alignat" | "flalign" | "eqnarray");
+        let any_number = numbers.iter().any(Option::is_some);
+        let mut table = String::from("<mtable>");
+        for (row, number) in rows.iter().zip(&numbers) {
+            let id = number
+                .as_ref()
+                .map_or(String::new(), |(_, id)| format!(" id=\"{}\"", id));
+            table.push_str(&format!("<mtr{}>", id));
+            let cells = if aligned {
+                split_at(row, Split::Cell)
+            } else {
+                vec![*row]
+            };
+            for (i, cell) in cells.into_iter().enumerate() {
+                let align = match (aligned, i % 2) {
+                    (false, _) => "center",
+                    (true, 0) => "right",
+                    (true, _) => "left",
+                };
+                table.push_str(&format!(
+                    "<mtd columnalign=\"{}\">{}</mtd>",
+                    align,
+                    mathml(cell, true)
+                ));
+            }
+            if any_number {
+                let eqno = number.as_ref().map_or(String::new(), |(number, _)| {
+                    format!("<mtext>({})</mtext>", escape(number))
+                });
+                table.push_str(&format!("<mtd class=\"eqno\">{}</mtd>", eqno));
+            }
+            table.push_str("</mtr>");
+        }
+        table.push_str("</mtable>");
+        self.block(&format!(
+            "<div class=\"equation\"><math display=\"block\">{}</math></div>\n",
+            table
+        ));
+    }
+
+    /// The number and id of a row of display math, from its \tag or the equation
+    /// counter, registering its \label; None when it isn't numbered
+    fn equation_number(&mut self, tex: &str, numbered: bool) -> Option<(String, String)> {
+        let (mut label, mut tag, mut suppressed) = (None, None, false);
+        for command in latex::commands(tex) {
+            match command.name {
+                "label" => label = required(tex, command.end).map(|(key, _)| key),
+                "tag" => tag = required(tex, command.end).map(|(tag, _)| tag),
+                "nonumber" | "notag" => suppressed = true,
+                _ => {}
+            }
+        }
+
+        let number = match tag {
+            Some(tag) => prose::file_prose(&tag).trim().to_string(),
+            None if suppressed || !numbered => return None,
+            None => {
+                self.equations += 1;
+                self.counted(self.equations)
+            }
+        };
+        let id = self.next_id("equation");
+        if let Some(label) = label {
+            self.labels
+                .entry(label.trim().to_string())
+                .or_insert(Target {
+                    kind: "Equation".to_string(),
+                    number: number.clone(),
+                    id: id.clone(),
+                });
+        }
+        Some((number, id))
+    }
+}
+
+/// A table cell's \multicolumn{span}{spec}{content}, if it is one
+fn multicolumn(cell: &str) -> Option<(usize, &'static str, String)> {
+    let rest = cell.trim_start().strip_prefix("\\multicolumn")?;
+    let (span, after) = required(rest, 0)?;
+    let (spec, after) = required(rest, after)?;
+    let (content, _) = required(rest, after)?;
+    let align = column_alignments(&spec).first().copied().unwrap_or("left");
+    Some((span.trim().parse().unwrap_or(1).max(1), align, content))
+}
+
+/// The names in an \author, which separates them with \and
+fn split_authors(author: &str) -> Vec<&str> {
+    let mut names = Vec::new();
+    let mut from = 0;
+    for command in latex::commands(author).filter(|command| command.name == "and") {
+        names.push(&author[from..command.start]);
+        from = command.end;
+    }
+    names.push(&author[from..]);
+    names
+}
+
+/// A URL argument with the characters escaped for TeX restored
+fn unescape_url(url: &str) -> String {
+    let mut unescaped = url.trim().to_string();
+    for escaped in ["\\%", "\\#", "\\_", "\\&", "\\~"] {
+        unescaped = unescaped.replace(escaped, &escaped[1..]);
+    }
+    unescaped
+}
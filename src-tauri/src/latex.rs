@@ -0,0 +1,572 @@
+//! Lightweight LaTeX source scanning shared by the analysis commands.
+//!
+//! This is not a TeX parser: it understands comments, control words and
+//! brace/bracket argument groups well enough to pull structure out of real
+//! documents without running the engine.
+
+use std::collections::HashSet;
+use std::fs;
+use std::ops::Range;
+use std::path::{Component, Path, PathBuf};
+
+/// Commands that pull another .tex file into the document
+pub const INCLUDE_COMMANDS: &[&str] = &["input", "include", "subfile"];
+
+/// Sectioning commands, from the outermost level in
+pub const SECTIONING_COMMANDS: &[&str] = &[
+    "part",
+    "chapter",
+    "section",
+    "subsection",
+    "subsubsection",
+    "paragraph",
+    "subparagraph",
+];
+
+/// Environments whose content TeX reads character for character
+pub const VERBATIM_ENVIRONMENTS: &[&str] = &[
+    "verbatim",
+    "verbatim*",
+    "Verbatim",
+    "lstlisting",
+    "minted",
+    "comment",
+];
+
+/// Environments laid out in rows and columns, with `&` between the columns and `\\`
+/// ending each row
+pub const ALIGNMENT_ENVIRONMENTS: &[&str] = &[
+    "tabular",
+    "tabular*",
+    "tabularx",
+    "tabulary",
+    "longtable",
+    "array",
+    "align",
+    "align*",
+    "alignat",
+    "alignat*",
+    "eqnarray",
+    "eqnarray*",
+    "flalign",
+    "flalign*",
+    "split",
+    "aligned",
+    "alignedat",
+    "cases",
+    "matrix",
+    "pmatrix",
+    "bmatrix",
+    "Bmatrix",
+    "vmatrix",
+    "Vmatrix",
+    "smallmatrix",
+];
+
+/// Environments besides ALIGNMENT_ENVIRONMENTS whose lines are broken with `\\`
+pub const LINE_BREAK_ENVIRONMENTS: &[&str] = &[
+    "gather",
+    "gather*",
+    "gathered",
+    "multline",
+    "multline*",
+    "center",
+    "flushleft",
+    "flushright",
+    "verse",
+    "tikzpicture",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ArgKind {
+    Optional,
+    Required,
+}
+
+#[derive(Debug, Clone)]
+pub struct Arg {
+    pub kind: ArgKind,
+    /// Text between the delimiters
+    pub text: String,
+}
+
+/// A control word found in the source, e.g. `\section*`
+#[derive(Debug, Clone)]
+pub struct Command<'a> {
+    pub name: &'a str,
+    /// Byte offset of the backslash
+    pub start: usize,
+    /// Byte offset just past the name (and star, if any)
+    pub end: usize,
+}
+
+/// A source file reached from the root file, with its project-relative path
+#[derive(Debug, Clone)]
+pub struct SourceFile {
+    pub path: String,
+    pub content: String,
+}
+
+/// Maps byte offsets to 1-based line numbers
+pub struct LineIndex {
+    line_starts: Vec<usize>,
+}
+
+impl LineIndex {
+    pub fn new(content: &str) -> Self {
+        let mut line_starts = vec![0];
+        line_starts.extend(content.match_indices('\n').map(|(i, _)| i + 1));
+        Self { line_starts }
+    }
+
+    /// 1-based line containing `offset`
+    pub fn line(&self, offset: usize) -> usize {
+        match self.line_starts.binary_search(&offset) {
+            Ok(i) => i + 1,
+            Err(i) => i,
+        }
+    }
+}
+
+/// True when the character at `pos` is preceded by an odd number of backslashes
+pub fn is_escaped(bytes: &[u8], pos: usize) -> bool {
+    let mut count = 0;
+    let mut i = pos;
+    while i > 0 && bytes[i - 1] == b'\\' {
+        count += 1;
+        i -= 1;
+    }
+    count % 2 == 1
+}
+
+/// Remove a trailing `%` comment from a single line
+pub fn strip_comment(line: &str) -> &str {
+    let bytes = line.as_bytes();
+    for (i, b) in bytes.iter().enumerate() {
+        if *b == b'%' && !is_escaped(bytes, i) {
+            return &line[..i];
+        }
+    }
+    line
+}
+
+/// Escape the characters LaTeX treats specially, so `text` typesets as it reads
+pub fn escape_text(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '\\' => escaped.push_str("\\textbackslash{}"),
+            '~' => escaped.push_str("\\textasciitilde{}"),
+            '^' => escaped.push_str("\\textasciicircum{}"),
+            '{' | '}' | '$' | '&' | '%' | '#' | '_' => {
+                escaped.push('\\');
+                escaped.push(c);
+            }
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Replace comment text with spaces, keeping every byte offset and line break intact
+pub fn mask_comments(content: &str) -> String {
+    let mut masked = String::with_capacity(content.len());
+    for (i, line) in content.split('\n').enumerate() {
+        if i > 0 {
+            masked.push('\n');
+        }
+        let code = strip_comment(line);
+        masked.push_str(code);
+        masked.push_str(&" ".repeat(line.len() - code.len()));
+    }
+    masked
+}
+
+/// For each line of `content`, whether it starts inside a verbatim environment, so
+/// its text (leading whitespace included) must be kept exactly as it is
+pub fn verbatim_lines(content: &str) -> Vec<bool> {
+    let mut open: Option<&str> = None;
+    let mut flags = Vec::new();
+
+    for line in content.split('\n') {
+        flags.push(open.is_some());
+
+        let mut rest = line;
+        loop {
+            if let Some(environment) = open {
+                // The content ends at the first \end for it, comment characters included
+                let end = format!("\\end{{{}}}", environment);
+                match rest.find(&end) {
+                    Some(i) => {
+                        rest = &rest[i + end.len()..];
+                        open = None;
+                    }
+                    None => break,
+                }
+            }
+
+            let code = strip_comment(rest);
+            let begin = commands(code).filter(|c| c.name == "begin").find_map(|c| {
+                let args = read_args(code, c.end, 1);
+                let name = first_required(&args)?.text.trim().to_string();
+                let environment = VERBATIM_ENVIRONMENTS.iter().find(|v| **v == name)?;
+                Some((environment, skip_args(code, c.end, 1)))
+            });
+            match begin {
+                Some((environment, after)) => {
+                    open = Some(environment);
+                    rest = &rest[after..];
+                }
+                None => break,
+            }
+        }
+    }
+
+    flags
+}
+
+/// Iterate over every control word (`\name`) in `content`. Control symbols such as
+/// `\\` or `\%` are skipped.
+pub fn commands(content: &str) -> impl Iterator<Item = Command<'_>> {
+    let bytes = content.as_bytes();
+    let mut pos = 0;
+
+    std::iter::from_fn(move || {
+        while pos < bytes.len() {
+            if bytes[pos] != b'\\' {
+                pos += 1;
+                continue;
+            }
+
+            let start = pos;
+            let name_start = pos + 1;
+            let mut name_end = name_start;
+            while name_end < bytes.len() && bytes[name_end].is_ascii_alphabetic() {
+                name_end += 1;
+            }
+
+            if name_end == name_start {
+                // Control symbol: skip the backslash and the escaped character
+                pos = name_start + 1;
+                continue;
+            }
+
+            let end = if bytes.get(name_end) == Some(&b'*') {
+                name_end + 1
+            } else {
+                name_end
+            };
+            pos = end;
+
+            return Some(Command {
+                name: &content[name_start..name_end],
+                start,
+                end,
+            });
+        }
+        None
+    })
+}
+
+/// Read a balanced group starting at `pos` (which must hold `open`). Returns the inner
+/// text and the offset just past the closing delimiter.
+pub fn read_group(content: &str, pos: usize, open: u8, close: u8) -> Option<(String, usize)> {
+    let bytes = content.as_bytes();
+    if bytes.get(pos) != Some(&open) {
+        return None;
+    }
+
+    // Brackets don't nest in TeX, but braces inside them do
+    let mut depth = 0usize;
+    let mut brace_depth = 0usize;
+    let mut i = pos + 1;
+
+    while i < bytes.len() {
+        let b = bytes[i];
+        if b == b'\\' {
+            i += 2;
+            continue;
+        }
+
+        if open == b'{' {
+            if b == b'{' {
+                depth += 1;
+            } else if b == b'}' {
+                if depth == 0 {
+                    return Some((content[pos + 1..i].to_string(), i + 1));
+                }
+                depth -= 1;
+            }
+        } else if b == b'{' {
+            brace_depth += 1;
+        } else if b == b'}' {
+            brace_depth = brace_depth.saturating_sub(1);
+        } else if b == close && brace_depth == 0 {
+            return Some((content[pos + 1..i].to_string(), i + 1));
+        }
+
+        i += 1;
+    }
+
+    None
+}
+
+/// Skip spaces and at most one line break (a blank line ends a macro's arguments)
+pub fn skip_arg_whitespace(bytes: &[u8], mut pos: usize) -> usize {
+    let mut newlines = 0;
+    while pos < bytes.len() {
+        match bytes[pos] {
+            b' ' | b'\t' | b'\r' => pos += 1,
+            b'\n' if newlines == 0 => {
+                newlines += 1;
+                pos += 1;
+            }
+            _ => break,
+        }
+    }
+    pos
+}
+
+/// Read up to `max` consecutive `[...]` / `{...}` argument groups starting at `pos`
+pub fn read_args(content: &str, pos: usize, max: usize) -> Vec<Arg> {
+    let bytes = content.as_bytes();
+    let mut args = Vec::new();
+    let mut pos = pos;
+
+    while args.len() < max {
+        let next = skip_arg_whitespace(bytes, pos);
+        let (kind, open, close) = match bytes.get(next) {
+            Some(b'[') => (ArgKind::Optional, b'[', b']'),
+            Some(b'{') => (ArgKind::Required, b'{', b'}'),
+            _ => break,
+        };
+
+        match read_group(content, next, open, close) {
+            Some((text, end)) => {
+                args.push(Arg { kind, text });
+                pos = end;
+            }
+            None => break,
+        }
+    }
+
+    args
+}
+
+/// Offset just past `required` brace groups after `pos`, along with any optional
+/// arguments before them. Whitespace after the last group is left in place.
+pub fn skip_args(content: &str, pos: usize, required: usize) -> usize {
+    let bytes = content.as_bytes();
+    let mut end = pos;
+    let mut seen = 0;
+
+    loop {
+        let next = skip_arg_whitespace(bytes, end);
+        let (open, close) = match bytes.get(next) {
+            Some(b'[') => (b'[', b']'),
+            Some(b'{') if seen < required => (b'{', b'}'),
+            _ => break,
+        };
+
+        match read_group(content, next, open, close) {
+            Some((_, after)) => {
+                end = after;
+                if open == b'{' {
+                    seen += 1;
+                }
+            }
+            None => break,
+        }
+    }
+
+    end
+}
+
+/// First required argument among `args`
+pub fn first_required(args: &[Arg]) -> Option<&Arg> {
+    args.iter().find(|a| a.kind == ArgKind::Required)
+}
+
+/// First optional argument that appears before any required argument
+pub fn leading_optional(args: &[Arg]) -> Option<&Arg> {
+    args.first().filter(|a| a.kind == ArgKind::Optional)
+}
+
+/// An environment in the source: `span` runs from its \begin to the end of its \end,
+/// or to the end of the file when it isn't closed
+pub struct Environment {
+    pub name: String,
+    pub span: Range<usize>,
+}
+
+/// Every environment in `masked`, each \end matched to the innermost \begin of the
+/// same name
+pub fn environments(masked: &str) -> Vec<Environment> {
+    let mut environments = Vec::new();
+    let mut open: Vec<(String, usize)> = Vec::new();
+    for command in commands(masked) {
+        if command.name != "begin" && command.name != "end" {
+            continue;
+        }
+        let start = skip_arg_whitespace(masked.as_bytes(), command.end);
+        let Some((name, end)) = read_group(masked, start, b'{', b'}') else {
+            continue;
+        };
+        let name = name.trim().to_string();
+        if command.name == "begin" {
+            open.push((name, command.start));
+        } else if let Some(index) = open.iter().rposition(|(open, _)| *open == name) {
+            let (name, start) = open.remove(index);
+            environments.push(Environment {
+                name,
+                span: start..end,
+            });
+        }
+    }
+    environments.extend(open.into_iter().map(|(name, start)| Environment {
+        name,
+        span: start..masked.len(),
+    }));
+    environments
+}
+
+/// Byte offset of the first `\begin{document}` outside comments, if any
+pub fn find_begin_document(masked: &str) -> Option<usize> {
+    find_document_command(masked, "begin")
+}
+
+/// Byte offset of the first `\end{document}` outside comments, if any
+pub fn find_end_document(masked: &str) -> Option<usize> {
+    find_document_command(masked, "end")
+}
+
+fn find_document_command(masked: &str, name: &str) -> Option<usize> {
+    commands(masked)
+        .filter(|c| c.name == name)
+        .find(|c| {
+            read_args(masked, c.end, 1)
+                .first()
+                .is_some_and(|a| a.kind == ArgKind::Required && a.text.trim() == "document")
+        })
+        .map(|c| c.start)
+}
+
+/// Lexically normalize a project-relative path, rejecting anything that escapes the root
+pub fn normalize_relative(path: &Path) -> Option<PathBuf> {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::Normal(part) => normalized.push(part),
+            Component::ParentDir => {
+                if !normalized.pop() {
+                    return None;
+                }
+            }
+            Component::RootDir | Component::Prefix(_) => return None,
+        }
+    }
+    Some(normalized)
+}
+
+/// Project-relative path as a forward-slash string, matching the file tree
+pub fn to_project_path(path: &Path) -> String {
+    path.components()
+        .map(|c| c.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+/// Normalize a user-supplied project-relative file path into the tree's form
+pub fn project_file(path: &str) -> Result<String, String> {
+    normalize_relative(Path::new(path))
+        .map(|p| to_project_path(&p))
+        .ok_or_else(|| format!("Invalid file path: {}", path))
+}
+
+/// Resolve an \input/\include argument the way TeX does: relative to the project root,
+/// trying the name with `.tex` appended and then as given
+pub fn resolve_tex_input(project_dir: &Path, name: &str) -> Option<String> {
+    let name = name.trim().trim_matches('"');
+    if name.is_empty() {
+        return None;
+    }
+
+    let relative = normalize_relative(Path::new(name))?;
+    let mut candidates = vec![relative.clone()];
+    if relative.extension().is_none_or(|ext| ext != "tex") {
+        let mut with_ext = relative.clone().into_os_string();
+        with_ext.push(".tex");
+        candidates.insert(0, PathBuf::from(with_ext));
+    }
+
+    candidates
+        .into_iter()
+        .find(|c| project_dir.join(c).is_file())
+        .map(|c| to_project_path(&c))
+}
+
+/// Read a project file by its project-relative path
+pub fn read_source(project_dir: &Path, relative: &str) -> Result<String, String> {
+    let normalized = normalize_relative(Path::new(relative))
+        .ok_or_else(|| format!("Invalid file path: {}", relative))?;
+
+    fs::read_to_string(project_dir.join(&normalized))
+        .map_err(|e| format!("Failed to read file {}: {}", relative, e))
+}
+
+/// Target names of include commands in `content`, in document order
+pub fn include_targets(content: &str) -> Vec<(String, usize)> {
+    let masked = mask_comments(content);
+    commands(&masked)
+        .filter(|c| INCLUDE_COMMANDS.contains(&c.name))
+        .filter_map(|c| {
+            let args = read_args(&masked, c.end, 1);
+            let arg = args.first()?;
+            (arg.kind == ArgKind::Required).then(|| (arg.text.trim().to_string(), c.start))
+        })
+        .collect()
+}
+
+/// Every source file reachable from `root_file`, in the order TeX would first read
+/// them. Each file appears once; missing includes and include cycles are skipped.
+pub fn load_sources(project_dir: &Path, root_file: &str) -> Result<Vec<SourceFile>, String> {
+    let root = project_file(root_file)?;
+    let content = read_source(project_dir, &root)?;
+    Ok(load_sources_from(project_dir, root, content))
+}
+
+/// Like load_sources, with `content` standing in for the root file, e.g. unsaved edits
+pub fn load_sources_from(project_dir: &Path, root: String, content: String) -> Vec<SourceFile> {
+    let mut sources = Vec::new();
+    let mut visited = HashSet::new();
+    collect_sources(project_dir, root, content, &mut visited, &mut sources);
+    sources
+}
+
+fn collect_sources(
+    project_dir: &Path,
+    path: String,
+    content: String,
+    visited: &mut HashSet<String>,
+    sources: &mut Vec<SourceFile>,
+) {
+    if !visited.insert(path.clone()) {
+        return;
+    }
+
+    let targets = include_targets(&content);
+    sources.push(SourceFile { path, content });
+
+    for (target, _) in targets {
+        let Some(child) = resolve_tex_input(project_dir, &target) else {
+            continue;
+        };
+        if visited.contains(&child) {
+            continue;
+        }
+        if let Ok(child_content) = read_source(project_dir, &child) {
+            collect_sources(project_dir, child, child_content, visited, sources);
+        }
+    }
+}
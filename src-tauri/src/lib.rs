@@ -1,11 +1,65 @@
+mod bibtex;
 mod commands;
+mod diff;
+mod hooks;
+mod html;
+mod latex;
+mod pdf;
+mod prose;
+mod raster;
+mod spelling;
+mod storage;
+mod style;
+mod tex_log;
+mod toc;
 
-use commands::compilation::compile_latex_project;
+use commands::analysis::{
+    acronym_check, analyze_preamble, check_balance_project, collect_links, collect_macros,
+    collect_notes, collect_todo_macros, detect_escaping_issues, detect_language,
+    detect_linebreak_misuse, equation_stats, fetch_bib_entry, find_macro_definition, label_issues,
+    list_asset_dependencies, list_environments, list_floats, package_audit, project_cleanup_report,
+    readability, rename_label, section_title_audit, session_stats, spell_check, split_file,
+    style_check, symbol_index, update_references_after_move, used_citations, validate_bib,
+    validate_encoding, validate_includes, word_count,
+};
+use commands::autosave::queue_autosave;
+use commands::catalog::latex_command_catalog;
+use commands::compilation::{
+    bundle_download_status, cache_health, check_only, clear_cache, compile_all, compile_history,
+    compile_latex_project, compile_progress_log, compile_template, effective_config,
+    estimate_build, explain_compile, get_build_log, invalidate_compile_cache, is_v2_project,
+    last_compile_info, list_build_artifacts, list_bundle_packages, list_bundles, minimize_document,
+    missing_packages, parse_toc, prefetch_bundle, read_bundle_file, verify_clean_build,
+};
+use commands::disk_space::{stop_watching_disk_space, watch_disk_space};
+use commands::export::{
+    diff_pdf, expand_include_at, export_cropped_pdf, export_html, export_outline_skeleton,
+    export_pdf, export_plain_text, merge_pdfs, pdf_bookmarks,
+};
+use commands::format::{align_tables, apply_formatting, convert_indentation, fix_typography};
+use commands::git::{ensure_gitignore, git_blame};
+use commands::live_preview::{
+    resolve_watch_set, stop_live_preview, stop_watching_pdf, watch_and_compile, watch_pdf,
+};
 use commands::project::{
-    check_pdf_exists, create_new_project, load_pdf, load_project_meta, open_project, read_file,
-    save_file, save_project_meta,
+    add_bookmark, add_personal_word, add_to_dictionary, check_pdf_exists, create_directory,
+    create_file, create_new_project, delete_path, diff_files, disk_space, file_hash,
+    list_bookmarks, list_profiles, list_tex_files, list_trash, load_editor_state, load_open_tabs,
+    load_pdf, load_pdf_with_status, load_project_dictionary, load_project_meta, open_pdf_external,
+    open_project, open_recent_file, open_single_file, project_id, project_size, read_file,
+    read_file_with_hash, recently_modified, record_recent_file, remove_bookmark,
+    remove_from_dictionary, remove_personal_word, rename_path, repair_project_meta,
+    restore_from_trash, reveal_in_file_manager, save_editor_state, save_file, save_open_tabs,
+    save_project_meta, set_page_limit, set_word_target, snapshot_tree, strip_bom, trash_path,
+    tree_diff,
+};
+use commands::search::search_project;
+use commands::settings::{
+    add_recent_project, load_global_settings, pin_recent_project, rename_recent_project,
+    save_global_settings, set_active_bundle, update_recent_projects, watch_settings,
 };
-use commands::settings::{load_global_settings, save_global_settings};
+use commands::snippets::{delete_snippet, expand_snippet, list_snippets, save_snippet};
+use commands::support::{diagnostics_info, versions};
 
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
@@ -14,16 +68,150 @@ pub fn run() {
         .plugin(tauri_plugin_opener::init())
         .invoke_handler(tauri::generate_handler![
             compile_latex_project,
+            effective_config,
+            compile_all,
+            check_only,
+            compile_template,
+            compile_progress_log,
+            compile_history,
+            estimate_build,
             open_project,
+            create_file,
+            create_directory,
+            delete_path,
+            diff_files,
+            trash_path,
+            list_trash,
+            restore_from_trash,
+            rename_path,
+            list_tex_files,
+            list_profiles,
+            project_id,
+            project_size,
+            disk_space,
             create_new_project,
             check_pdf_exists,
             load_pdf,
+            load_pdf_with_status,
+            open_pdf_external,
             read_file,
+            read_file_with_hash,
+            file_hash,
+            strip_bom,
+            reveal_in_file_manager,
             save_file,
+            queue_autosave,
             load_project_meta,
             save_project_meta,
+            repair_project_meta,
+            open_recent_file,
+            open_single_file,
+            snapshot_tree,
+            tree_diff,
+            recently_modified,
+            record_recent_file,
+            save_editor_state,
+            load_editor_state,
+            save_open_tabs,
+            load_open_tabs,
+            add_bookmark,
+            list_bookmarks,
+            remove_bookmark,
+            add_personal_word,
+            remove_personal_word,
+            load_project_dictionary,
+            add_to_dictionary,
+            remove_from_dictionary,
+            set_word_target,
+            set_page_limit,
+            search_project,
             load_global_settings,
             save_global_settings,
+            set_active_bundle,
+            add_recent_project,
+            pin_recent_project,
+            rename_recent_project,
+            update_recent_projects,
+            watch_settings,
+            list_snippets,
+            save_snippet,
+            delete_snippet,
+            expand_snippet,
+            latex_command_catalog,
+            analyze_preamble,
+            package_audit,
+            detect_language,
+            collect_links,
+            used_citations,
+            fetch_bib_entry,
+            collect_todo_macros,
+            collect_notes,
+            find_macro_definition,
+            collect_macros,
+            list_environments,
+            symbol_index,
+            label_issues,
+            rename_label,
+            update_references_after_move,
+            split_file,
+            list_floats,
+            equation_stats,
+            acronym_check,
+            check_balance_project,
+            detect_escaping_issues,
+            detect_linebreak_misuse,
+            validate_includes,
+            validate_bib,
+            validate_encoding,
+            list_asset_dependencies,
+            project_cleanup_report,
+            word_count,
+            session_stats,
+            section_title_audit,
+            readability,
+            spell_check,
+            style_check,
+            missing_packages,
+            get_build_log,
+            parse_toc,
+            list_bundle_packages,
+            list_bundles,
+            read_bundle_file,
+            prefetch_bundle,
+            bundle_download_status,
+            is_v2_project,
+            invalidate_compile_cache,
+            cache_health,
+            clear_cache,
+            list_build_artifacts,
+            last_compile_info,
+            explain_compile,
+            minimize_document,
+            verify_clean_build,
+            diagnostics_info,
+            versions,
+            expand_include_at,
+            export_cropped_pdf,
+            export_pdf,
+            merge_pdfs,
+            diff_pdf,
+            export_plain_text,
+            export_outline_skeleton,
+            export_html,
+            pdf_bookmarks,
+            convert_indentation,
+            apply_formatting,
+            fix_typography,
+            align_tables,
+            git_blame,
+            ensure_gitignore,
+            watch_and_compile,
+            stop_live_preview,
+            resolve_watch_set,
+            watch_pdf,
+            stop_watching_pdf,
+            watch_disk_space,
+            stop_watching_disk_space,
         ])
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
@@ -0,0 +1,1073 @@
+//! Inspection and editing of built PDFs.
+//!
+//! Page geometry is worked out by walking the content stream operators rather than
+//! rasterising, so text extents come from the font width tables and everything else
+//! from path and image coordinates. That is close enough to trim margins without
+//! clipping ink.
+
+use lopdf::content::Content;
+use lopdf::{Bookmark, Dictionary, Document, Object, ObjectId, Stream};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::rc::Rc;
+
+use crate::raster::Raster;
+
+/// Extra space kept around the inked area when cropping, in PDF points
+const CROP_MARGIN: f32 = 1.0;
+
+/// Form XObjects nested deeper than this are ignored
+const MAX_FORM_DEPTH: usize = 8;
+
+/// Outline items and name tree nodes nested deeper than this are ignored
+const MAX_OUTLINE_DEPTH: usize = 32;
+
+/// Stroke colour of the rectangles drawn over image pages, as RGB
+const OUTLINE_COLOR: [f32; 3] = [0.95, 0.55, 0.0];
+
+/// Stroke width of those rectangles, in PDF points
+const OUTLINE_WIDTH: f32 = 1.5;
+
+/// An entry of the PDF's outline, the bookmarks its viewer shows
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PdfBookmark {
+    pub title: String,
+    /// 1-based page the bookmark jumps to, when it points into the document
+    pub page: Option<u32>,
+    pub children: Vec<PdfBookmark>,
+}
+
+/// PDF transformation matrix `[a b c d e f]`
+#[derive(Debug, Clone, Copy)]
+struct Matrix([f32; 6]);
+
+impl Matrix {
+    const IDENTITY: Matrix = Matrix([1.0, 0.0, 0.0, 1.0, 0.0, 0.0]);
+
+    fn translate(x: f32, y: f32) -> Matrix {
+        Matrix([1.0, 0.0, 0.0, 1.0, x, y])
+    }
+
+    /// `self` applied first, then `other`
+    fn then(self, other: Matrix) -> Matrix {
+        let [a1, b1, c1, d1, e1, f1] = self.0;
+        let [a2, b2, c2, d2, e2, f2] = other.0;
+        Matrix([
+            a1 * a2 + b1 * c2,
+            a1 * b2 + b1 * d2,
+            c1 * a2 + d1 * c2,
+            c1 * b2 + d1 * d2,
+            e1 * a2 + f1 * c2 + e2,
+            e1 * b2 + f1 * d2 + f2,
+        ])
+    }
+
+    fn apply(self, x: f32, y: f32) -> (f32, f32) {
+        let [a, b, c, d, e, f] = self.0;
+        (a * x + c * y + e, b * x + d * y + f)
+    }
+
+    /// Largest factor a length can be stretched by
+    fn scale(self) -> f32 {
+        let [a, b, c, d, _, _] = self.0;
+        (a * a + b * b).sqrt().max((c * c + d * d).sqrt())
+    }
+}
+
+/// Axis-aligned rectangle in default user space
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BBox {
+    pub x0: f32,
+    pub y0: f32,
+    pub x1: f32,
+    pub y1: f32,
+}
+
+impl BBox {
+    fn point(x: f32, y: f32) -> BBox {
+        BBox {
+            x0: x,
+            y0: y,
+            x1: x,
+            y1: y,
+        }
+    }
+
+    fn union(self, other: BBox) -> BBox {
+        BBox {
+            x0: self.x0.min(other.x0),
+            y0: self.y0.min(other.y0),
+            x1: self.x1.max(other.x1),
+            y1: self.y1.max(other.y1),
+        }
+    }
+
+    fn intersect(self, other: BBox) -> Option<BBox> {
+        let clipped = BBox {
+            x0: self.x0.max(other.x0),
+            y0: self.y0.max(other.y0),
+            x1: self.x1.min(other.x1),
+            y1: self.y1.min(other.y1),
+        };
+        (clipped.x0 < clipped.x1 && clipped.y0 < clipped.y1).then_some(clipped)
+    }
+
+    fn grow(self, by: f32) -> BBox {
+        BBox {
+            x0: self.x0 - by,
+            y0: self.y0 - by,
+            x1: self.x1 + by,
+            y1: self.y1 + by,
+        }
+    }
+
+    fn to_object(self) -> Object {
+        Object::Array(vec![
+            Object::Real(self.x0),
+            Object::Real(self.y0),
+            Object::Real(self.x1),
+            Object::Real(self.y1),
+        ])
+    }
+}
+
+/// Glyph widths and vertical extent of a font, in thousandths of the font size
+#[derive(Debug)]
+struct FontMetrics {
+    /// Type0 fonts use two-byte codes
+    two_byte: bool,
+    first_char: u32,
+    widths: Vec<f32>,
+    cid_widths: HashMap<u32, f32>,
+    default_width: f32,
+    ascent: f32,
+    descent: f32,
+}
+
+impl FontMetrics {
+    /// Used when a font has no usable tables
+    fn fallback() -> FontMetrics {
+        FontMetrics {
+            two_byte: false,
+            first_char: 0,
+            widths: Vec::new(),
+            cid_widths: HashMap::new(),
+            default_width: 600.0,
+            ascent: 750.0,
+            descent: -250.0,
+        }
+    }
+
+    fn width(&self, code: u32) -> f32 {
+        if self.two_byte {
+            return self
+                .cid_widths
+                .get(&code)
+                .copied()
+                .unwrap_or(self.default_width);
+        }
+        code.checked_sub(self.first_char)
+            .and_then(|i| self.widths.get(i as usize))
+            .copied()
+            .unwrap_or(self.default_width)
+    }
+
+    fn codes(&self, bytes: &[u8]) -> Vec<u32> {
+        if self.two_byte {
+            bytes
+                .chunks(2)
+                .map(|pair| pair.iter().fold(0, |code, b| (code << 8) | u32::from(*b)))
+                .collect()
+        } else {
+            bytes.iter().map(|b| u32::from(*b)).collect()
+        }
+    }
+}
+
+/// Follow references until reaching a direct object
+fn resolve<'a>(doc: &'a Document, mut object: &'a Object) -> &'a Object {
+    for _ in 0..32 {
+        match object {
+            Object::Reference(id) => match doc.get_object(*id) {
+                Ok(target) => object = target,
+                Err(_) => return &Object::Null,
+            },
+            _ => return object,
+        }
+    }
+    &Object::Null
+}
+
+fn dict_get<'a>(doc: &'a Document, dict: &'a Dictionary, key: &[u8]) -> Option<&'a Object> {
+    dict.get(key).ok().map(|object| resolve(doc, object))
+}
+
+fn number(object: &Object) -> Option<f32> {
+    match object {
+        Object::Integer(i) => Some(*i as f32),
+        Object::Real(r) => Some(*r),
+        _ => None,
+    }
+}
+
+fn numbers(doc: &Document, object: &Object) -> Vec<f32> {
+    match resolve(doc, object) {
+        Object::Array(items) => items
+            .iter()
+            .filter_map(|item| number(resolve(doc, item)))
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+fn rect(doc: &Document, object: &Object) -> Option<BBox> {
+    match numbers(doc, object)[..] {
+        [x0, y0, x1, y1] => Some(BBox {
+            x0: x0.min(x1),
+            y0: y0.min(y1),
+            x1: x0.max(x1),
+            y1: y0.max(y1),
+        }),
+        _ => None,
+    }
+}
+
+/// Parse a CIDFont `W` array: `c [w1 w2 ...]` or `c_first c_last w`
+fn cid_widths(doc: &Document, object: &Object) -> HashMap<u32, f32> {
+    let mut widths = HashMap::new();
+    let Object::Array(items) = resolve(doc, object) else {
+        return widths;
+    };
+
+    let mut i = 0;
+    while i < items.len() {
+        let Some(first) = number(resolve(doc, &items[i])) else {
+            break;
+        };
+        match items.get(i + 1).map(|item| resolve(doc, item)) {
+            Some(Object::Array(run)) => {
+                for (offset, width) in run.iter().enumerate() {
+                    if let Some(width) = number(resolve(doc, width)) {
+                        widths.insert(first as u32 + offset as u32, width);
+                    }
+                }
+                i += 2;
+            }
+            Some(last) => {
+                let (Some(last), Some(width)) = (
+                    number(last),
+                    items.get(i + 2).and_then(|w| number(resolve(doc, w))),
+                ) else {
+                    break;
+                };
+                for code in first as u32..=last as u32 {
+                    widths.insert(code, width);
+                }
+                i += 3;
+            }
+            None => break,
+        }
+    }
+
+    widths
+}
+
+fn font_metrics(doc: &Document, font: &Dictionary) -> FontMetrics {
+    let mut metrics = FontMetrics::fallback();
+    let subtype = dict_get(doc, font, b"Subtype").and_then(|s| s.as_name().ok());
+
+    let descriptor_font = if subtype == Some(b"Type0".as_slice()) {
+        metrics.two_byte = true;
+        let descendant = dict_get(doc, font, b"DescendantFonts")
+            .and_then(|d| d.as_array().ok())
+            .and_then(|fonts| fonts.first())
+            .and_then(|d| resolve(doc, d).as_dict().ok());
+        if let Some(descendant) = descendant {
+            if let Some(dw) = dict_get(doc, descendant, b"DW").and_then(number) {
+                metrics.default_width = dw;
+            } else {
+                metrics.default_width = 1000.0;
+            }
+            if let Ok(w) = descendant.get(b"W") {
+                metrics.cid_widths = cid_widths(doc, w);
+            }
+        }
+        descendant
+    } else {
+        if let Some(first) = dict_get(doc, font, b"FirstChar").and_then(number) {
+            metrics.first_char = first as u32;
+        }
+        if let Ok(widths) = font.get(b"Widths") {
+            metrics.widths = numbers(doc, widths);
+        }
+        Some(font)
+    };
+
+    // Type3 glyph space is set by FontMatrix rather than fixed at 1/1000
+    if subtype == Some(b"Type3".as_slice()) {
+        let scale = font
+            .get(b"FontMatrix")
+            .ok()
+            .and_then(|m| numbers(doc, m).first().copied())
+            .map(|a| a * 1000.0)
+            .unwrap_or(1.0);
+        metrics.widths.iter_mut().for_each(|w| *w *= scale);
+        if let Some(bbox) = font.get(b"FontBBox").ok().and_then(|b| rect(doc, b)) {
+            metrics.ascent = bbox.y1 * scale;
+            metrics.descent = bbox.y0 * scale;
+        }
+        return metrics;
+    }
+
+    let descriptor = descriptor_font
+        .and_then(|f| dict_get(doc, f, b"FontDescriptor"))
+        .and_then(|d| d.as_dict().ok());
+    if let Some(descriptor) = descriptor {
+        if let Some(ascent) = dict_get(doc, descriptor, b"Ascent").and_then(number) {
+            metrics.ascent = ascent;
+        }
+        if let Some(descent) = dict_get(doc, descriptor, b"Descent").and_then(number) {
+            metrics.descent = descent;
+        }
+        // Some producers write Ascent 0; the font box is a safer bound then
+        if metrics.ascent <= 0.0 {
+            if let Some(bbox) = descriptor.get(b"FontBBox").ok().and_then(|b| rect(doc, b)) {
+                metrics.ascent = bbox.y1;
+                metrics.descent = bbox.y0;
+            }
+        }
+    }
+
+    metrics
+}
+
+/// Resource dictionaries in lookup order: the innermost scope first
+type Resources<'a> = Vec<&'a Dictionary>;
+
+fn lookup_resource<'a>(
+    doc: &'a Document,
+    resources: &Resources<'a>,
+    category: &[u8],
+    name: &[u8],
+) -> Option<&'a Object> {
+    resources.iter().find_map(|dict| {
+        dict_get(doc, dict, category)
+            .and_then(|c| c.as_dict().ok())
+            .and_then(|c| dict_get(doc, c, name))
+    })
+}
+
+#[derive(Debug, Clone)]
+struct GraphicsState {
+    ctm: Matrix,
+    line_width: f32,
+    font: Rc<FontMetrics>,
+    font_size: f32,
+    char_spacing: f32,
+    word_spacing: f32,
+    horizontal_scale: f32,
+    leading: f32,
+    rise: f32,
+    render_mode: i64,
+}
+
+/// Walks a content stream and accumulates the area it marks
+struct Tracer<'a> {
+    doc: &'a Document,
+    bounds: Option<BBox>,
+}
+
+impl<'a> Tracer<'a> {
+    fn mark(&mut self, area: BBox) {
+        self.bounds = Some(match self.bounds {
+            Some(bounds) => bounds.union(area),
+            None => area,
+        });
+    }
+
+    /// Mark the image of a rectangle under `matrix`
+    fn mark_rect(&mut self, matrix: Matrix, x0: f32, y0: f32, x1: f32, y1: f32) {
+        let corners = [(x0, y0), (x1, y0), (x0, y1), (x1, y1)];
+        for (x, y) in corners {
+            let (x, y) = matrix.apply(x, y);
+            self.mark(BBox::point(x, y));
+        }
+    }
+
+    fn trace(&mut self, content: &[u8], resources: &Resources<'a>, ctm: Matrix, depth: usize) {
+        let Ok(content) = Content::decode(content) else {
+            return;
+        };
+
+        let mut state = GraphicsState {
+            ctm,
+            line_width: 1.0,
+            font: Rc::new(FontMetrics::fallback()),
+            font_size: 0.0,
+            char_spacing: 0.0,
+            word_spacing: 0.0,
+            horizontal_scale: 1.0,
+            leading: 0.0,
+            rise: 0.0,
+            render_mode: 0,
+        };
+        let mut saved = Vec::new();
+        let mut path: Vec<(f32, f32)> = Vec::new();
+        let mut text_matrix = Matrix::IDENTITY;
+        let mut line_matrix = Matrix::IDENTITY;
+
+        for op in &content.operations {
+            let nums: Vec<f32> = op.operands.iter().filter_map(number).collect();
+
+            match op.operator.as_str() {
+                "q" => saved.push(state.clone()),
+                "Q" => {
+                    if let Some(restored) = saved.pop() {
+                        state = restored;
+                    }
+                }
+                "cm" => {
+                    if let [a, b, c, d, e, f] = nums[..] {
+                        state.ctm = Matrix([a, b, c, d, e, f]).then(state.ctm);
+                    }
+                }
+                "w" => {
+                    if let Some(width) = nums.first() {
+                        state.line_width = *width;
+                    }
+                }
+
+                // Path construction; control points bound the curve, so keep them all
+                "m" | "l" | "c" | "v" | "y" => {
+                    for pair in nums.chunks_exact(2) {
+                        path.push(state.ctm.apply(pair[0], pair[1]));
+                    }
+                }
+                "re" => {
+                    if let [x, y, w, h] = nums[..] {
+                        for (px, py) in [(x, y), (x + w, y), (x, y + h), (x + w, y + h)] {
+                            path.push(state.ctm.apply(px, py));
+                        }
+                    }
+                }
+                "S" | "s" | "f" | "F" | "f*" | "B" | "B*" | "b" | "b*" => {
+                    let stroked =
+                        matches!(op.operator.as_str(), "S" | "s" | "B" | "B*" | "b" | "b*");
+                    let pad = if stroked {
+                        state.line_width.max(1.0) * state.ctm.scale() / 2.0
+                    } else {
+                        0.0
+                    };
+                    for (x, y) in path.drain(..) {
+                        self.mark(BBox::point(x, y).grow(pad));
+                    }
+                }
+                "n" => path.clear(),
+
+                // Images and forms
+                "Do" => {
+                    let Some(name) = op.operands.first().and_then(|n| n.as_name().ok()) else {
+                        continue;
+                    };
+                    let Some(Object::Stream(xobject)) =
+                        lookup_resource(self.doc, resources, b"XObject", name)
+                    else {
+                        continue;
+                    };
+                    let subtype = dict_get(self.doc, &xobject.dict, b"Subtype")
+                        .and_then(|s| s.as_name().ok());
+
+                    if subtype == Some(b"Form".as_slice()) {
+                        if depth >= MAX_FORM_DEPTH {
+                            continue;
+                        }
+                        let matrix = match numbers(
+                            self.doc,
+                            xobject.dict.get(b"Matrix").unwrap_or(&Object::Null),
+                        )[..]
+                        {
+                            [a, b, c, d, e, f] => Matrix([a, b, c, d, e, f]),
+                            _ => Matrix::IDENTITY,
+                        };
+                        let mut inner = resources.clone();
+                        if let Some(own) = dict_get(self.doc, &xobject.dict, b"Resources")
+                            .and_then(|r| r.as_dict().ok())
+                        {
+                            inner.insert(0, own);
+                        }
+                        let data = xobject
+                            .decompressed_content()
+                            .unwrap_or_else(|_| xobject.content.clone());
+                        self.trace(&data, &inner, matrix.then(state.ctm), depth + 1);
+                    } else {
+                        self.mark_rect(state.ctm, 0.0, 0.0, 1.0, 1.0);
+                    }
+                }
+                // Inline images occupy the unit square too
+                "BI" | "EI" => self.mark_rect(state.ctm, 0.0, 0.0, 1.0, 1.0),
+
+                // Text state
+                "BT" => {
+                    text_matrix = Matrix::IDENTITY;
+                    line_matrix = Matrix::IDENTITY;
+                }
+                "Tf" => {
+                    if let Some(size) = nums.first() {
+                        state.font_size = *size;
+                    }
+                    let font = op
+                        .operands
+                        .first()
+                        .and_then(|n| n.as_name().ok())
+                        .and_then(|name| lookup_resource(self.doc, resources, b"Font", name))
+                        .and_then(|f| f.as_dict().ok());
+                    state.font = Rc::new(match font {
+                        Some(font) => font_metrics(self.doc, font),
+                        None => FontMetrics::fallback(),
+                    });
+                }
+                "Tc" => state.char_spacing = nums.first().copied().unwrap_or(0.0),
+                "Tw" => state.word_spacing = nums.first().copied().unwrap_or(0.0),
+                "Tz" => state.horizontal_scale = nums.first().copied().unwrap_or(100.0) / 100.0,
+                "TL" => state.leading = nums.first().copied().unwrap_or(0.0),
+                "Ts" => state.rise = nums.first().copied().unwrap_or(0.0),
+                "Tr" => {
+                    if let Some(Object::Integer(mode)) = op.operands.first() {
+                        state.render_mode = *mode;
+                    }
+                }
+
+                // Text positioning
+                "Td" | "TD" => {
+                    if let [tx, ty] = nums[..] {
+                        if op.operator == "TD" {
+                            state.leading = -ty;
+                        }
+                        line_matrix = Matrix::translate(tx, ty).then(line_matrix);
+                        text_matrix = line_matrix;
+                    }
+                }
+                "Tm" => {
+                    if let [a, b, c, d, e, f] = nums[..] {
+                        line_matrix = Matrix([a, b, c, d, e, f]);
+                        text_matrix = line_matrix;
+                    }
+                }
+                "T*" => {
+                    line_matrix = Matrix::translate(0.0, -state.leading).then(line_matrix);
+                    text_matrix = line_matrix;
+                }
+
+                // Text showing
+                "Tj" | "'" | "\"" | "TJ" => {
+                    if op.operator == "\"" {
+                        if let [aw, ac, ..] = nums[..] {
+                            state.word_spacing = aw;
+                            state.char_spacing = ac;
+                        }
+                    }
+                    if op.operator == "'" || op.operator == "\"" {
+                        line_matrix = Matrix::translate(0.0, -state.leading).then(line_matrix);
+                        text_matrix = line_matrix;
+                    }
+
+                    let mut advance = 0.0;
+                    let pieces: &[Object] = match op.operator.as_str() {
+                        "TJ" => match op.operands.first() {
+                            Some(Object::Array(items)) => items,
+                            _ => &[],
+                        },
+                        _ => op.operands.last().map(std::slice::from_ref).unwrap_or(&[]),
+                    };
+                    for piece in pieces {
+                        match piece {
+                            Object::String(bytes, _) => {
+                                advance += text_advance(&state, bytes);
+                            }
+                            other => {
+                                if let Some(adjust) = number(other) {
+                                    advance -=
+                                        adjust / 1000.0 * state.font_size * state.horizontal_scale;
+                                }
+                            }
+                        }
+                    }
+
+                    // Modes 3 and 7 draw nothing
+                    if state.render_mode != 3 && state.render_mode != 7 {
+                        let size = state.font_size;
+                        let top = state.rise + state.font.ascent / 1000.0 * size;
+                        let bottom = state.rise + state.font.descent / 1000.0 * size;
+                        self.mark_rect(text_matrix.then(state.ctm), 0.0, bottom, advance, top);
+                    }
+                    text_matrix = Matrix::translate(advance, 0.0).then(text_matrix);
+                }
+                _ => {}
+            }
+        }
+    }
+}
+
+/// Horizontal distance, in text space, that showing `bytes` moves the pen
+fn text_advance(state: &GraphicsState, bytes: &[u8]) -> f32 {
+    state
+        .font
+        .codes(bytes)
+        .into_iter()
+        .map(|code| {
+            let glyph = state.font.width(code) / 1000.0 * state.font_size;
+            // Word spacing applies to the single-byte space only
+            let word = if !state.font.two_byte && code == 32 {
+                state.word_spacing
+            } else {
+                0.0
+            };
+            (glyph + state.char_spacing + word) * state.horizontal_scale
+        })
+        .sum()
+}
+
+/// A page attribute that may be inherited from the page tree, such as MediaBox
+fn inherited_box(doc: &Document, page_id: ObjectId, key: &[u8]) -> Option<BBox> {
+    let mut node = doc.get_dictionary(page_id).ok()?;
+    for _ in 0..32 {
+        if let Some(bbox) = node.get(key).ok().and_then(|b| rect(doc, b)) {
+            return Some(bbox);
+        }
+        node = dict_get(doc, node, b"Parent")?.as_dict().ok()?;
+    }
+    None
+}
+
+/// Area of a page that its content actually marks, limited to the page itself
+pub fn page_content_bounds(doc: &Document, page_id: ObjectId) -> Option<BBox> {
+    let content = doc.get_page_content(page_id).ok()?;
+    let (own, inherited) = doc.get_page_resources(page_id);
+
+    let mut resources: Resources = own.into_iter().collect();
+    resources.extend(
+        inherited
+            .into_iter()
+            .filter_map(|id| doc.get_dictionary(id).ok()),
+    );
+
+    let mut tracer = Tracer { doc, bounds: None };
+    tracer.trace(&content, &resources, Matrix::IDENTITY, 0);
+
+    let bounds = tracer.bounds?;
+    match inherited_box(doc, page_id, b"MediaBox") {
+        Some(media) => bounds.intersect(media),
+        None => Some(bounds),
+    }
+}
+
+/// Shrink every page's boxes to its content plus a small margin. Blank pages keep
+/// their size. Returns the number of pages that were cropped.
+pub fn crop_to_content(doc: &mut Document) -> usize {
+    let mut crops = Vec::new();
+    for page_id in doc.get_pages().into_values() {
+        if let Some(bounds) = page_content_bounds(doc, page_id) {
+            let media = inherited_box(doc, page_id, b"MediaBox");
+            let grown = bounds.grow(CROP_MARGIN);
+            let bbox = media.and_then(|m| grown.intersect(m)).unwrap_or(grown);
+            crops.push((page_id, bbox));
+        }
+    }
+
+    for (page_id, bbox) in &crops {
+        if let Ok(page) = doc.get_object_mut(*page_id).and_then(Object::as_dict_mut) {
+            page.set("MediaBox", bbox.to_object());
+            page.set("CropBox", bbox.to_object());
+            // Stale print boxes would extend past the new media box
+            for key in [b"TrimBox".as_slice(), b"BleedBox", b"ArtBox"] {
+                page.remove(key);
+            }
+        }
+    }
+
+    crops.len()
+}
+
+/// Decode a PDF text string: UTF-16BE or UTF-8 after a byte order mark, otherwise
+/// PDFDocEncoding, which matches Latin-1 for the characters titles use
+fn text_string(bytes: &[u8]) -> String {
+    if let Some(utf16) = bytes.strip_prefix(&[0xfe, 0xff]) {
+        let units: Vec<u16> = utf16
+            .chunks_exact(2)
+            .map(|pair| u16::from_be_bytes([pair[0], pair[1]]))
+            .collect();
+        return String::from_utf16_lossy(&units);
+    }
+    if let Some(utf8) = bytes.strip_prefix(&[0xef, 0xbb, 0xbf]) {
+        return String::from_utf8_lossy(utf8).into_owned();
+    }
+    bytes.iter().map(|b| char::from(*b)).collect()
+}
+
+/// Look `name` up in a name tree, e.g. the document's named destinations
+fn name_tree_lookup<'a>(
+    doc: &'a Document,
+    node: &'a Dictionary,
+    name: &[u8],
+    depth: usize,
+) -> Option<&'a Object> {
+    if depth > MAX_OUTLINE_DEPTH {
+        return None;
+    }
+    if let Some(Object::Array(names)) = dict_get(doc, node, b"Names") {
+        for pair in names.chunks_exact(2) {
+            if let Object::String(key, _) = resolve(doc, &pair[0]) {
+                if key.as_slice() == name {
+                    return Some(resolve(doc, &pair[1]));
+                }
+            }
+        }
+    }
+    let Some(Object::Array(kids)) = dict_get(doc, node, b"Kids") else {
+        return None;
+    };
+    kids.iter().find_map(|kid| match resolve(doc, kid) {
+        Object::Dictionary(kid) => name_tree_lookup(doc, kid, name, depth + 1),
+        _ => None,
+    })
+}
+
+/// Destination array a named destination stands for, from the catalog's /Dests
+/// dictionary (PDF 1.1) or the /Dests name tree, which is what hyperref writes
+fn named_destination<'a>(
+    doc: &'a Document,
+    catalog: &'a Dictionary,
+    name: &[u8],
+) -> Option<&'a Object> {
+    let target = match dict_get(doc, catalog, b"Dests") {
+        Some(Object::Dictionary(dests)) => dict_get(doc, dests, name),
+        _ => None,
+    };
+    let target = target.or_else(|| {
+        let Some(Object::Dictionary(names)) = dict_get(doc, catalog, b"Names") else {
+            return None;
+        };
+        let Some(Object::Dictionary(tree)) = dict_get(doc, names, b"Dests") else {
+            return None;
+        };
+        name_tree_lookup(doc, tree, name, 0)
+    })?;
+
+    // Either the array itself or a dictionary holding it as /D
+    match target {
+        Object::Dictionary(dict) => dict_get(doc, dict, b"D"),
+        other => Some(other),
+    }
+}
+
+/// Page a destination points at: an array starting with the page, or the name of one
+fn destination_page(
+    doc: &Document,
+    catalog: &Dictionary,
+    pages: &HashMap<ObjectId, u32>,
+    destination: &Object,
+) -> Option<u32> {
+    let destination = match resolve(doc, destination) {
+        Object::Name(name) | Object::String(name, _) => named_destination(doc, catalog, name)?,
+        other => other,
+    };
+    match destination {
+        Object::Array(items) => match items.first()? {
+            Object::Reference(page_id) => pages.get(page_id).copied(),
+            // Remote destinations number pages from 0
+            Object::Integer(index) => u32::try_from(*index).ok().map(|i| i + 1),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// Read the outline items starting at `first` and their siblings, with their children
+fn outline_items(
+    doc: &Document,
+    catalog: &Dictionary,
+    pages: &HashMap<ObjectId, u32>,
+    first: Option<&Object>,
+    visited: &mut HashSet<ObjectId>,
+    depth: usize,
+) -> Vec<PdfBookmark> {
+    let mut items = Vec::new();
+    if depth > MAX_OUTLINE_DEPTH {
+        return items;
+    }
+
+    let mut next = first;
+    while let Some(Object::Reference(id)) = next {
+        // Broken files can link items into a loop
+        if !visited.insert(*id) {
+            break;
+        }
+        let Ok(item) = doc.get_dictionary(*id) else {
+            break;
+        };
+
+        let title = match dict_get(doc, item, b"Title") {
+            Some(Object::String(bytes, _)) => text_string(bytes),
+            _ => String::new(),
+        };
+        // A /Dest, or a GoTo action's /D
+        let destination = dict_get(doc, item, b"Dest").or_else(|| {
+            let Some(Object::Dictionary(action)) = dict_get(doc, item, b"A") else {
+                return None;
+            };
+            let is_goto =
+                matches!(dict_get(doc, action, b"S"), Some(Object::Name(kind)) if kind == b"GoTo");
+            is_goto.then(|| dict_get(doc, action, b"D")).flatten()
+        });
+
+        items.push(PdfBookmark {
+            title,
+            page: destination.and_then(|d| destination_page(doc, catalog, pages, d)),
+            children: outline_items(
+                doc,
+                catalog,
+                pages,
+                item.get(b"First").ok(),
+                visited,
+                depth + 1,
+            ),
+        });
+        next = item.get(b"Next").ok();
+    }
+
+    items
+}
+
+/// The document's outline tree, empty when it has none
+pub fn outline(doc: &Document) -> Vec<PdfBookmark> {
+    let Some(Object::Dictionary(catalog)) = dict_get(doc, &doc.trailer, b"Root") else {
+        return Vec::new();
+    };
+    let Some(Object::Dictionary(outlines)) = dict_get(doc, catalog, b"Outlines") else {
+        return Vec::new();
+    };
+
+    let pages: HashMap<ObjectId, u32> = doc
+        .get_pages()
+        .into_iter()
+        .map(|(number, id)| (id, number))
+        .collect();
+    let mut visited = HashSet::new();
+    outline_items(
+        doc,
+        catalog,
+        &pages,
+        outlines.get(b"First").ok(),
+        &mut visited,
+        0,
+    )
+}
+
+/// Number of pages in a PDF, or None if it can't be parsed
+pub fn page_count(bytes: &[u8]) -> Option<u32> {
+    let doc = Document::load_mem(bytes).ok()?;
+    u32::try_from(doc.get_pages().len()).ok()
+}
+
+/// Add `items` to `merged`'s outline under `parent`, with `pages` the merged page ids of
+/// the document they came from. Items without a page keep their children, one level up.
+fn add_bookmarks(merged: &mut Document, items: &[PdfBookmark], pages: &[ObjectId], parent: u32) {
+    for item in items {
+        let page = item
+            .page
+            .and_then(|page| pages.get(page.checked_sub(1)? as usize));
+        let parent = match page {
+            Some(page) => merged.add_bookmark(
+                Bookmark::new(item.title.clone(), [0.0, 0.0, 0.0], 0, *page),
+                Some(parent),
+            ),
+            None => parent,
+        };
+        add_bookmarks(merged, &item.children, pages, parent);
+    }
+}
+
+/// Concatenate `documents`, each given with the title of its top-level bookmark, in
+/// order. Each document's page tree is kept whole under the new one, so inherited page
+/// sizes and resources still apply, and its bookmarks are nested under its title.
+/// Named destinations aren't carried over, so links between documents don't resolve.
+pub fn merge(documents: Vec<(String, Document)>) -> Result<Document, String> {
+    let mut merged = Document::with_version("1.5");
+    let mut next_id = 1;
+    let mut parts = Vec::new();
+
+    for (title, mut doc) in documents {
+        let bookmarks = outline(&doc);
+        doc.renumber_objects_with(next_id);
+        next_id = doc.max_id + 1;
+
+        let catalog_id = doc
+            .trailer
+            .get(b"Root")
+            .and_then(Object::as_reference)
+            .map_err(|_| format!("{} has no document catalog", title))?;
+        let pages_id = doc
+            .get_dictionary(catalog_id)
+            .and_then(|catalog| catalog.get(b"Pages"))
+            .and_then(Object::as_reference)
+            .map_err(|_| format!("{} has no page tree", title))?;
+        let pages: Vec<ObjectId> = doc.get_pages().into_values().collect();
+        if pages.is_empty() {
+            return Err(format!("{} has no pages", title));
+        }
+
+        // The catalog and the outline it points to are replaced by the merged ones
+        doc.objects.remove(&catalog_id);
+        merged.objects.extend(doc.objects);
+        parts.push((title, pages_id, pages, bookmarks));
+    }
+    merged.max_id = next_id - 1;
+
+    let root_pages_id = merged.new_object_id();
+    let mut kids = Vec::new();
+    let mut count = 0;
+    for (_, pages_id, pages, _) in &parts {
+        if let Ok(node) = merged.get_dictionary_mut(*pages_id) {
+            node.set("Parent", root_pages_id);
+        }
+        kids.push(Object::Reference(*pages_id));
+        count += pages.len();
+    }
+    let mut root_pages = Dictionary::new();
+    root_pages.set("Type", "Pages");
+    root_pages.set("Kids", kids);
+    root_pages.set("Count", count as i64);
+    merged
+        .objects
+        .insert(root_pages_id, Object::Dictionary(root_pages));
+
+    for (title, _, pages, bookmarks) in &parts {
+        let top = merged.add_bookmark(
+            Bookmark::new(title.clone(), [0.0, 0.0, 0.0], 0, pages[0]),
+            None,
+        );
+        add_bookmarks(&mut merged, bookmarks, pages, top);
+    }
+
+    let mut catalog = Dictionary::new();
+    catalog.set("Type", "Catalog");
+    catalog.set("Pages", root_pages_id);
+    if let Some(outline_id) = merged.build_outline() {
+        catalog.set("Outlines", outline_id);
+        catalog.set("PageMode", "UseOutlines");
+    }
+    let catalog_id = merged.add_object(catalog);
+    merged.trailer.set("Root", catalog_id);
+
+    // Drops the old outlines, which nothing points to any more
+    merged.prune_objects();
+    Ok(merged)
+}
+
+/// A document made of images, a page for each, e.g. renderings of another document's
+/// pages. Each image is compressed as it's added, so they needn't all be kept in memory.
+pub struct ImageDocument {
+    doc: Document,
+    pages_id: ObjectId,
+    kids: Vec<Object>,
+}
+
+impl Default for ImageDocument {
+    fn default() -> ImageDocument {
+        ImageDocument::new()
+    }
+}
+
+impl ImageDocument {
+    pub fn new() -> ImageDocument {
+        let mut doc = Document::with_version("1.5");
+        let pages_id = doc.new_object_id();
+        ImageDocument {
+            doc,
+            pages_id,
+            kids: Vec::new(),
+        }
+    }
+
+    /// Add a page `width` by `height` points with `image` stretched over it and
+    /// `outlines` stroked on top
+    pub fn add_page(
+        &mut self,
+        image: &Raster,
+        width: f32,
+        height: f32,
+        outlines: &[BBox],
+    ) -> Result<(), String> {
+        let mut dict = Dictionary::new();
+        dict.set("Type", "XObject");
+        dict.set("Subtype", "Image");
+        dict.set("Width", image.width as i64);
+        dict.set("Height", image.height as i64);
+        dict.set("ColorSpace", "DeviceRGB");
+        dict.set("BitsPerComponent", 8_i64);
+        let mut stream = Stream::new(dict, image.rgb.clone());
+        stream
+            .compress()
+            .map_err(|e| format!("Failed to compress page image: {}", e))?;
+        let image_id = self.doc.add_object(stream);
+
+        let mut content = format!("q {} 0 0 {} 0 0 cm /Im0 Do Q\n", width, height);
+        if !outlines.is_empty() {
+            let [r, g, b] = OUTLINE_COLOR;
+            content.push_str(&format!("q {} {} {} RG {} w\n", r, g, b, OUTLINE_WIDTH));
+            for outline in outlines {
+                content.push_str(&format!(
+                    "{} {} {} {} re S\n",
+                    outline.x0,
+                    outline.y0,
+                    outline.x1 - outline.x0,
+                    outline.y1 - outline.y0
+                ));
+            }
+            content.push_str("Q\n");
+        }
+        let content_id = self
+            .doc
+            .add_object(Stream::new(Dictionary::new(), content.into_bytes()));
+
+        let mut images = Dictionary::new();
+        images.set("Im0", image_id);
+        let mut resources = Dictionary::new();
+        resources.set("XObject", images);
+        let media_box = BBox {
+            x0: 0.0,
+            y0: 0.0,
+            x1: width,
+            y1: height,
+        };
+        let mut page = Dictionary::new();
+        page.set("Type", "Page");
+        page.set("Parent", self.pages_id);
+        page.set("MediaBox", media_box.to_object());
+        page.set("Resources", resources);
+        page.set("Contents", content_id);
+        self.kids.push(Object::Reference(self.doc.add_object(page)));
+        Ok(())
+    }
+
+    pub fn finish(self) -> Document {
+        let ImageDocument {
+            mut doc,
+            pages_id,
+            kids,
+        } = self;
+
+        let mut page_tree = Dictionary::new();
+        page_tree.set("Type", "Pages");
+        page_tree.set("Count", kids.len() as i64);
+        page_tree.set("Kids", kids);
+        doc.objects.insert(pages_id, Object::Dictionary(page_tree));
+
+        let mut catalog = Dictionary::new();
+        catalog.set("Type", "Catalog");
+        catalog.set("Pages", pages_id);
+        let catalog_id = doc.add_object(catalog);
+        doc.trailer.set("Root", catalog_id);
+        doc
+    }
+}
@@ -0,0 +1,800 @@
+//! Plain prose extracted from LaTeX source, for word counts, readability, spell
+//! checking and plain text export.
+//!
+//! Markup is dropped while the text inside formatting commands is kept, accents and
+//! text symbols become the characters they typeset, math and non-text environments
+//! (code listings, TikZ, ...) are removed entirely, and headings, list items and
+//! environment boundaries are turned into paragraph breaks so they don't run into the
+//! surrounding sentences.
+
+use std::collections::HashSet;
+use std::ops::Range;
+use std::path::Path;
+use unicode_normalization::char::is_combining_mark;
+use unicode_normalization::UnicodeNormalization;
+
+use crate::latex;
+
+/// Stands in for formulas in plain text export
+const MATH_PLACEHOLDER: &str = "[math]";
+
+/// Environments typeset in math mode
+pub const MATH_ENVIRONMENTS: &[&str] = &[
+    "equation",
+    "equation*",
+    "align",
+    "align*",
+    "alignat",
+    "alignat*",
+    "gather",
+    "gather*",
+    "multline",
+    "multline*",
+    "flalign",
+    "flalign*",
+    "eqnarray",
+    "eqnarray*",
+    "displaymath",
+    "math",
+];
+
+/// Other environments whose content is not prose
+const SKIPPED_ENVIRONMENTS: &[&str] = &[
+    "verbatim",
+    "verbatim*",
+    "lstlisting",
+    "minted",
+    "tikzpicture",
+    "comment",
+    "thebibliography",
+];
+
+/// Environments taking required arguments that aren't text, e.g. a column spec
+pub const ENVIRONMENT_ARGS: &[(&str, usize)] = &[
+    ("tabular", 1),
+    ("tabular*", 2),
+    ("tabularx", 2),
+    ("array", 1),
+    ("longtable", 1),
+    ("minipage", 1),
+    ("multicols", 1),
+    ("wrapfigure", 2),
+];
+
+/// Commands dropped together with this many required arguments
+pub const DROPPED_COMMANDS: &[(&str, usize)] = &[
+    ("label", 1),
+    ("ref", 1),
+    ("eqref", 1),
+    ("pageref", 1),
+    ("autoref", 1),
+    ("cref", 1),
+    ("Cref", 1),
+    ("nameref", 1),
+    ("cite", 1),
+    ("citep", 1),
+    ("citet", 1),
+    ("citeauthor", 1),
+    ("citeyear", 1),
+    ("parencite", 1),
+    ("textcite", 1),
+    ("autocite", 1),
+    ("nocite", 1),
+    ("bibitem", 1),
+    ("includegraphics", 1),
+    ("bibliography", 1),
+    ("bibliographystyle", 1),
+    ("addbibresource", 1),
+    ("url", 1),
+    ("vspace", 1),
+    ("hspace", 1),
+    ("pagestyle", 1),
+    ("thispagestyle", 1),
+    ("pagenumbering", 1),
+    ("graphicspath", 1),
+    ("hypersetup", 1),
+    ("usepackage", 1),
+    ("documentclass", 1),
+    ("setlength", 2),
+    ("addtolength", 2),
+    ("setcounter", 2),
+    ("newcommand", 2),
+    ("renewcommand", 2),
+    ("providecommand", 2),
+    ("newenvironment", 3),
+    ("renewenvironment", 3),
+];
+
+/// Commands whose argument is a heading or caption: a sentence of its own
+const HEADING_COMMANDS: &[&str] = &[
+    "part",
+    "chapter",
+    "section",
+    "subsection",
+    "subsubsection",
+    "paragraph",
+    "subparagraph",
+    "caption",
+];
+
+/// Control symbol accents (`\'e`) and the combining marks they put on their letter
+pub const SYMBOL_ACCENTS: &[(char, char)] = &[
+    ('\'', '\u{301}'),
+    ('`', '\u{300}'),
+    ('^', '\u{302}'),
+    ('"', '\u{308}'),
+    ('~', '\u{303}'),
+    ('=', '\u{304}'),
+    ('.', '\u{307}'),
+];
+
+/// Control word accents (`\c{c}`) and their combining marks
+pub const LETTER_ACCENTS: &[(&str, char)] = &[
+    ("c", '\u{327}'),
+    ("v", '\u{30c}'),
+    ("u", '\u{306}'),
+    ("H", '\u{30b}'),
+    ("k", '\u{328}'),
+    ("r", '\u{30a}'),
+    ("d", '\u{323}'),
+    ("b", '\u{331}'),
+];
+
+/// Commands that typeset a fixed piece of text
+pub const TEXT_SYMBOLS: &[(&str, &str)] = &[
+    ("ss", "ß"),
+    ("o", "ø"),
+    ("O", "Ø"),
+    ("ae", "æ"),
+    ("AE", "Æ"),
+    ("oe", "œ"),
+    ("OE", "Œ"),
+    ("aa", "å"),
+    ("AA", "Å"),
+    ("l", "ł"),
+    ("L", "Ł"),
+    // Dotless i and j only appear under accents, where the dot is dropped anyway
+    ("i", "i"),
+    ("j", "j"),
+    ("ldots", "…"),
+    ("dots", "…"),
+    ("textellipsis", "…"),
+    ("textendash", "–"),
+    ("textemdash", "—"),
+    ("textquoteleft", "‘"),
+    ("textquoteright", "’"),
+    ("textquotedblleft", "“"),
+    ("textquotedblright", "”"),
+    ("textbackslash", "\\"),
+    ("textasciitilde", "~"),
+    ("textbar", "|"),
+    ("S", "§"),
+    ("P", "¶"),
+    ("dag", "†"),
+    ("ddag", "‡"),
+    ("copyright", "©"),
+    ("textregistered", "®"),
+    ("texttrademark", "™"),
+    ("pounds", "£"),
+    ("euro", "€"),
+    ("TeX", "TeX"),
+    ("LaTeX", "LaTeX"),
+    ("LaTeXe", "LaTeX2e"),
+    ("BibTeX", "BibTeX"),
+];
+
+/// Abbreviations whose trailing period doesn't end a sentence
+const ABBREVIATIONS: &[&str] = &[
+    "e.g", "i.e", "cf", "al", "fig", "figs", "eq", "eqs", "sec", "ch", "chap", "tab", "ref",
+    "refs", "thm", "def", "lem", "no", "vol", "pp", "vs", "approx", "resp", "dr", "mr", "mrs",
+    "ms", "prof", "st", "jr",
+];
+
+const PARAGRAPH_BREAK: &str = "\n\n";
+
+struct Extractor<'a> {
+    project_dir: &'a Path,
+    visited: HashSet<String>,
+    /// Written in place of each formula, between spaces
+    math: &'static str,
+    /// Offset in the current file of the text being stripped
+    base: usize,
+    /// When set, the source offset of each character copied from a single file, as
+    /// (offset in `out`, offset in the file); includes are not followed
+    origins: Option<Vec<(usize, usize)>>,
+    /// Offsets in `out` where the text starts coming from another file, with that file.
+    /// The last entry is always the file being read.
+    sections: Vec<(usize, String)>,
+    out: String,
+}
+
+impl Extractor<'_> {
+    fn file(&mut self, path: &str, content: &str) {
+        if !self.visited.insert(path.to_string()) {
+            return;
+        }
+        let body = body_range(content);
+        self.base = body.start;
+        self.sections.push((self.out.len(), path.to_string()));
+        self.strip(&content[body]);
+    }
+
+    /// Strip `range` of `text`, keeping source offsets relative to the file
+    fn strip_within(&mut self, text: &str, range: Range<usize>) {
+        let base = self.base;
+        self.base += range.start;
+        self.strip(&text[range]);
+        self.base = base;
+    }
+
+    fn record_origin(&mut self, pos: usize) {
+        let (out_len, offset) = (self.out.len(), self.base + pos);
+        if let Some(origins) = &mut self.origins {
+            origins.push((out_len, offset));
+        }
+    }
+
+    fn strip(&mut self, text: &str) {
+        let bytes = text.as_bytes();
+        let mut i = 0;
+
+        while i < bytes.len() {
+            match bytes[i] {
+                b'%' => i = skip_comment(bytes, i),
+                b'\\' => i = self.control_sequence(text, i),
+                b'$' => {
+                    let delimiter = if bytes.get(i + 1) == Some(&b'$') {
+                        "$$"
+                    } else {
+                        "$"
+                    };
+                    i = find_closing(text, i + delimiter.len(), delimiter);
+                    self.push_math();
+                }
+                b'{' | b'}' => i += 1,
+                b'~' | b'&' => {
+                    self.out.push(' ');
+                    i += 1;
+                }
+                // TeX ligatures for dashes and curly quotes
+                b'-' if bytes.get(i + 1) == Some(&b'-') => {
+                    if bytes.get(i + 2) == Some(&b'-') {
+                        self.out.push('—');
+                        i += 3;
+                    } else {
+                        self.out.push('–');
+                        i += 2;
+                    }
+                }
+                b'`' | b'\'' => {
+                    let double = bytes.get(i + 1) == Some(&bytes[i]);
+                    self.out.push(match (bytes[i], double) {
+                        (b'`', true) => '“',
+                        (b'`', false) => '‘',
+                        (_, true) => '”',
+                        (_, false) => '’',
+                    });
+                    i += if double { 2 } else { 1 };
+                }
+                _ => {
+                    let c = text[i..].chars().next().unwrap_or(' ');
+                    self.record_origin(i);
+                    self.out.push(c);
+                    i += c.len_utf8();
+                }
+            }
+        }
+    }
+
+    /// Handle the control sequence starting at `start` and return where scanning resumes
+    fn control_sequence(&mut self, text: &str, start: usize) -> usize {
+        let bytes = text.as_bytes();
+        let name_start = start + 1;
+        let mut name_end = name_start;
+        while name_end < bytes.len() && bytes[name_end].is_ascii_alphabetic() {
+            name_end += 1;
+        }
+
+        if name_end == name_start {
+            return self.control_symbol(text, name_start);
+        }
+
+        let end = if bytes.get(name_end) == Some(&b'*') {
+            name_end + 1
+        } else {
+            name_end
+        };
+        let name = &text[name_start..name_end];
+
+        match name {
+            "begin" => {
+                let args = latex::read_args(text, end, 1);
+                let environment = latex::first_required(&args)
+                    .map(|a| a.text.trim().to_string())
+                    .unwrap_or_default();
+                let after = latex::skip_args(text, end, 1);
+                self.out.push_str(PARAGRAPH_BREAK);
+
+                let math = MATH_ENVIRONMENTS.contains(&environment.as_str());
+                if math || SKIPPED_ENVIRONMENTS.contains(&environment.as_str()) {
+                    if math {
+                        self.push_math();
+                    }
+                    let closing = format!("\\end{{{}}}", environment);
+                    return text[after..]
+                        .find(&closing)
+                        .map(|i| after + i + closing.len())
+                        .unwrap_or(text.len());
+                }
+
+                let spec_args = ENVIRONMENT_ARGS
+                    .iter()
+                    .find(|(env, _)| *env == environment)
+                    .map(|(_, n)| *n)
+                    .unwrap_or(0);
+                latex::skip_args(text, after, spec_args)
+            }
+            "end" => {
+                self.out.push_str(PARAGRAPH_BREAK);
+                latex::skip_args(text, end, 1)
+            }
+            "verb" => {
+                let Some(&delimiter) = bytes.get(end).filter(|b| b.is_ascii()) else {
+                    return end;
+                };
+                self.out.push(' ');
+                text[end + 1..]
+                    .find(delimiter as char)
+                    .map(|i| end + 1 + i + 1)
+                    .unwrap_or(text.len())
+            }
+            "item" => {
+                self.out.push_str(PARAGRAPH_BREAK);
+                latex::skip_args(text, end, 0)
+            }
+            "par" => {
+                self.out.push_str(PARAGRAPH_BREAK);
+                end
+            }
+            "footnote" => {
+                self.out.push(' ');
+                latex::skip_args(text, end, 0)
+            }
+            // The first argument is the link target, the second the visible text
+            "href" => latex::skip_args(text, end, 1),
+            name if HEADING_COMMANDS.contains(&name) => {
+                self.out.push_str(PARAGRAPH_BREAK);
+                let pos = latex::skip_arg_whitespace(bytes, latex::skip_args(text, end, 0));
+                let resume = match latex::read_group(text, pos, b'{', b'}') {
+                    Some((_, after)) => {
+                        self.strip_within(text, pos + 1..after - 1);
+                        after
+                    }
+                    None => pos,
+                };
+                self.out.push_str(PARAGRAPH_BREAK);
+                resume
+            }
+            name if latex::INCLUDE_COMMANDS.contains(&name) => {
+                let args = latex::read_args(text, end, 1);
+                if let Some(target) = latex::first_required(&args) {
+                    self.include(&target.text);
+                }
+                latex::skip_args(text, end, 1)
+            }
+            name => {
+                if let Some((_, symbol)) = TEXT_SYMBOLS.iter().find(|(s, _)| *s == name) {
+                    self.record_origin(start);
+                    self.out.push_str(symbol);
+                    return skip_symbol_space(text, end);
+                }
+                if let Some((_, mark)) = LETTER_ACCENTS.iter().find(|(a, _)| *a == name) {
+                    return self.accent(text, start, end, *mark);
+                }
+
+                let required = DROPPED_COMMANDS
+                    .iter()
+                    .find(|(dropped, _)| *dropped == name)
+                    .map(|(_, n)| *n)
+                    .unwrap_or(0);
+                latex::skip_args(text, end, required)
+            }
+        }
+    }
+
+    /// Handle a control symbol such as `\%` or `\[` whose character is at `pos`
+    fn control_symbol(&mut self, text: &str, pos: usize) -> usize {
+        let Some(c) = text[pos..].chars().next() else {
+            return pos;
+        };
+
+        match c {
+            '[' => {
+                self.push_math();
+                find_closing(text, pos + 1, "\\]")
+            }
+            '(' => {
+                self.push_math();
+                find_closing(text, pos + 1, "\\)")
+            }
+            '\\' => {
+                self.out.push(' ');
+                latex::skip_args(text, pos + 1, 0)
+            }
+            '%' | '&' | '$' | '#' | '_' | '{' | '}' => {
+                self.out.push(c);
+                pos + 1
+            }
+            ',' | ';' | ':' | '!' | ' ' | '\n' | '\t' => {
+                self.out.push(' ');
+                pos + 1
+            }
+            _ => match SYMBOL_ACCENTS.iter().find(|(accent, _)| *accent == c) {
+                Some((_, mark)) => self.accent(text, pos - 1, pos + 1, *mark),
+                // Discretionary hyphens and the like attach to the surrounding word
+                None => pos + c.len_utf8(),
+            },
+        }
+    }
+
+    fn push_math(&mut self) {
+        self.out.push(' ');
+        self.out.push_str(self.math);
+        self.out.push(' ');
+    }
+
+    /// Write the letter at `pos` that the accent command at `start` applies to (`e`, `{e}`
+    /// or `{\i}`) followed by its combining `mark`, and return where scanning resumes
+    fn accent(&mut self, text: &str, start: usize, pos: usize, mark: char) -> usize {
+        let bytes = text.as_bytes();
+        let mut pos = pos;
+        while matches!(bytes.get(pos), Some(b' ' | b'\t')) {
+            pos += 1;
+        }
+
+        let (base, resume) = match latex::read_group(text, pos, b'{', b'}') {
+            Some((group, after)) => (group.trim().to_string(), after),
+            None => {
+                // A lone dotless i or j, or any single character
+                let rest = &text[pos..];
+                match ["\\i", "\\j"].iter().find(|d| {
+                    rest.starts_with(**d)
+                        && !rest[2..].starts_with(|c: char| c.is_ascii_alphabetic())
+                }) {
+                    Some(dotless) => (dotless.to_string(), pos + 2),
+                    None => match rest.chars().next() {
+                        Some(c) => (c.to_string(), pos + c.len_utf8()),
+                        None => return pos,
+                    },
+                }
+            }
+        };
+
+        let base = match base.as_str() {
+            "\\i" => "i",
+            "\\j" => "j",
+            other => other,
+        };
+        let mut chars = base.chars();
+        if let Some(letter) = chars.next() {
+            self.record_origin(start);
+            self.out.push(letter);
+            self.out.push(mark);
+            self.out.extend(chars);
+        }
+        resume
+    }
+
+    fn include(&mut self, target: &str) {
+        if self.origins.is_some() {
+            return;
+        }
+        let Some(child) = latex::resolve_tex_input(self.project_dir, target) else {
+            return;
+        };
+        let Ok(content) = latex::read_source(self.project_dir, &child) else {
+            return;
+        };
+
+        let parent = self.sections.last().map(|(_, file)| file.clone());
+        self.out.push_str(PARAGRAPH_BREAK);
+        self.file(&child, &content);
+        self.out.push_str(PARAGRAPH_BREAK);
+        if let Some(parent) = parent {
+            self.sections.push((self.out.len(), parent));
+        }
+    }
+}
+
+/// The part of a file that is typeset: between \begin{document} and \end{document}
+/// when it has them (a root or subfile), otherwise all of it
+pub fn body_range(content: &str) -> Range<usize> {
+    let masked = latex::mask_comments(content);
+    let Some(begin) = latex::find_begin_document(&masked) else {
+        return 0..content.len();
+    };
+
+    let start = latex::skip_args(&masked, begin + "\\begin".len(), 1);
+    let end = latex::find_end_document(&masked)
+        .filter(|end| *end >= start)
+        .unwrap_or(content.len());
+    start..end
+}
+
+/// Skip the spaces TeX swallows after a text symbol such as `\ss`, and an empty `{}`
+pub fn skip_symbol_space(text: &str, pos: usize) -> usize {
+    let bytes = text.as_bytes();
+    let mut pos = pos;
+    while matches!(bytes.get(pos), Some(b' ' | b'\t')) {
+        pos += 1;
+    }
+    if text[pos..].starts_with("{}") {
+        pos += 2;
+    }
+    pos
+}
+
+/// Skip a `%` comment, its line break and the next line's indentation, as TeX does
+fn skip_comment(bytes: &[u8], pos: usize) -> usize {
+    let mut i = pos;
+    while i < bytes.len() && bytes[i] != b'\n' {
+        i += 1;
+    }
+    i += 1;
+    while i < bytes.len() && matches!(bytes[i], b' ' | b'\t') {
+        i += 1;
+    }
+    i.min(bytes.len())
+}
+
+/// Offset just past the first unescaped `delimiter` at or after `from`
+pub fn find_closing(text: &str, from: usize, delimiter: &str) -> usize {
+    let bytes = text.as_bytes();
+    let mut search = from;
+
+    while let Some(i) = text.get(search..).and_then(|rest| rest.find(delimiter)) {
+        let at = search + i;
+        if delimiter.starts_with('\\') || !latex::is_escaped(bytes, at) {
+            return at + delimiter.len();
+        }
+        search = at + delimiter.len();
+    }
+
+    text.len()
+}
+
+fn extract<'a>(
+    project_dir: &'a Path,
+    root_file: &str,
+    math: &'static str,
+) -> Result<Extractor<'a>, String> {
+    let root_file = latex::project_file(root_file)?;
+    let content = latex::read_source(project_dir, &root_file)?;
+
+    let mut extractor = Extractor {
+        project_dir,
+        visited: HashSet::new(),
+        math,
+        base: 0,
+        origins: None,
+        sections: Vec::new(),
+        out: String::new(),
+    };
+    extractor.file(&root_file, &content);
+    Ok(extractor)
+}
+
+/// Prose of the document rooted at `root_file`, descending into included files.
+/// Paragraphs, headings and list items are separated by blank lines.
+pub fn document_prose(project_dir: &Path, root_file: &str) -> Result<String, String> {
+    Ok(extract(project_dir, root_file, "")?.out)
+}
+
+/// Like document_prose, split by the file each part of the text comes from: every file
+/// reached from `root_file` with the prose written in it, not counting its includes.
+/// Files are in the order they are first read.
+pub fn prose_by_file(project_dir: &Path, root_file: &str) -> Result<Vec<(String, String)>, String> {
+    let extractor = extract(project_dir, root_file, "")?;
+    let out = &extractor.out;
+
+    let mut files: Vec<(String, String)> = Vec::new();
+    let ends = extractor.sections.iter().skip(1).map(|(start, _)| *start);
+    for ((start, file), end) in extractor.sections.iter().zip(ends.chain([out.len()])) {
+        let text = &out[*start..end];
+        match files.iter_mut().find(|(name, _)| name == file) {
+            Some((_, prose)) => {
+                prose.push_str(PARAGRAPH_BREAK);
+                prose.push_str(text);
+            }
+            None => files.push((file.clone(), text.to_string())),
+        }
+    }
+    Ok(files)
+}
+
+/// Prose of a single file, as prose_by_file gives it for a file of a document.
+/// Included files are not followed.
+pub fn file_prose(content: &str) -> String {
+    let mut extractor = Extractor {
+        project_dir: Path::new(""),
+        visited: HashSet::new(),
+        math: "",
+        base: 0,
+        origins: None,
+        sections: Vec::new(),
+        out: String::new(),
+    };
+    extractor.file("", content);
+    extractor.out
+}
+
+/// Words of a single file's prose with the byte offset in `content` where each starts.
+/// Included files are not followed.
+pub fn file_words(content: &str) -> Vec<(usize, String)> {
+    let mut extractor = Extractor {
+        project_dir: Path::new(""),
+        visited: HashSet::new(),
+        math: "",
+        base: 0,
+        origins: Some(Vec::new()),
+        sections: Vec::new(),
+        out: String::new(),
+    };
+    extractor.file("", content);
+    let origins = extractor.origins.unwrap_or_default();
+    let prose = extractor.out;
+
+    let mut words = Vec::new();
+    let mut start = None;
+    for (i, c) in prose.char_indices().chain([(prose.len(), ' ')]) {
+        let in_word = c.is_alphanumeric()
+            || is_combining_mark(c)
+            || (start.is_some() && matches!(c, '\'' | '’'));
+        match (start, in_word) {
+            (None, true) => start = Some(i),
+            (Some(from), false) => {
+                let word = prose[from..i].trim_end_matches(['\'', '’']);
+                // Words start with a copied character, so the closest origin at or
+                // before them is exact
+                let origin = match origins.binary_search_by_key(&from, |(out, _)| *out) {
+                    Ok(k) => Some(origins[k].1),
+                    Err(0) => None,
+                    Err(k) => Some(origins[k - 1].1),
+                };
+                if let Some(offset) = origin {
+                    words.push((offset, word.replace('’', "'").nfc().collect()));
+                }
+                start = None;
+            }
+            _ => {}
+        }
+    }
+    words
+}
+
+/// Readable plain text of the document: one line per paragraph, heading or list item,
+/// separated by blank lines, with formulas replaced by a placeholder
+pub fn plain_text(project_dir: &Path, root_file: &str) -> Result<String, String> {
+    let prose = extract(project_dir, root_file, MATH_PLACEHOLDER)?.out;
+    let prose: String = prose.nfc().collect();
+
+    let mut text = paragraphs(&prose)
+        .iter()
+        .map(|lines| {
+            lines
+                .iter()
+                .flat_map(|line| line.split_whitespace())
+                .collect::<Vec<_>>()
+                .join(" ")
+        })
+        .collect::<Vec<_>>()
+        .join(PARAGRAPH_BREAK);
+    text.push('\n');
+    Ok(text)
+}
+
+/// Whitespace-separated tokens that contain at least one letter or digit
+pub fn words(text: &str) -> impl Iterator<Item = &str> {
+    text.split_whitespace()
+        .filter(|token| token.chars().any(char::is_alphanumeric))
+}
+
+/// Whether `token` ends a sentence, given the token that follows it
+fn ends_sentence(token: &str, next: Option<&str>) -> bool {
+    let trimmed = token.trim_end_matches([')', ']', '"', '\'', '\u{2019}', '\u{201d}']);
+    let Some(last) = trimmed.chars().last() else {
+        return false;
+    };
+    if !matches!(last, '.' | '!' | '?') {
+        return false;
+    }
+
+    if last == '.' {
+        let stem = trimmed
+            .trim_end_matches('.')
+            .trim_start_matches(|c: char| !c.is_alphanumeric());
+        if ABBREVIATIONS.contains(&stem.to_lowercase().as_str()) {
+            return false;
+        }
+        // Initials such as "J. Smith"
+        let mut chars = stem.chars();
+        if let (Some(c), None) = (chars.next(), chars.next()) {
+            if c.is_uppercase() {
+                return false;
+            }
+        }
+    }
+
+    // A sentence ends before a capital, a digit or the end of the paragraph
+    next.and_then(|n| n.chars().find(|c| c.is_alphanumeric()))
+        .is_none_or(|c| !c.is_lowercase())
+}
+
+/// Group lines into paragraphs separated by blank (or whitespace-only) lines
+fn paragraphs(text: &str) -> Vec<Vec<&str>> {
+    let mut paragraphs = vec![Vec::new()];
+    for line in text.lines() {
+        if line.trim().is_empty() {
+            paragraphs.push(Vec::new());
+        } else if let Some(current) = paragraphs.last_mut() {
+            current.push(line);
+        }
+    }
+    paragraphs.retain(|p| !p.is_empty());
+    paragraphs
+}
+
+/// Number of sentences in extracted prose. Text without terminal punctuation, such as
+/// a heading or a list item, still counts as one sentence.
+pub fn count_sentences(text: &str) -> usize {
+    let mut count = 0;
+
+    for paragraph in paragraphs(text) {
+        let tokens: Vec<&str> = paragraph
+            .iter()
+            .flat_map(|line| line.split_whitespace())
+            .collect();
+        let mut pending = false;
+
+        for (i, token) in tokens.iter().enumerate() {
+            if token.chars().any(char::is_alphanumeric) {
+                pending = true;
+            }
+            if pending && ends_sentence(token, tokens.get(i + 1).copied()) {
+                count += 1;
+                pending = false;
+            }
+        }
+
+        if pending {
+            count += 1;
+        }
+    }
+
+    count
+}
+
+/// Rough English syllable count: vowel groups, less a silent final `e`
+pub fn syllables(word: &str) -> usize {
+    let letters: Vec<char> = word
+        .chars()
+        .filter(|c| c.is_alphabetic())
+        .flat_map(char::to_lowercase)
+        .collect();
+
+    let mut count = 0;
+    let mut previous_vowel = false;
+    for &c in &letters {
+        let vowel = matches!(c, 'a' | 'e' | 'i' | 'o' | 'u' | 'y');
+        if vowel && !previous_vowel {
+            count += 1;
+        }
+        previous_vowel = vowel;
+    }
+
+    if count > 1 && letters.ends_with(&['e']) && !letters.ends_with(&['l', 'e']) {
+        count -= 1;
+    }
+
+    count.max(1)
+}
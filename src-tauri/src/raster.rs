@@ -0,0 +1,241 @@
+//! Page images as a PDF rasterizer renders them, and the pixels that differ between two
+//! renderings of a page.
+//!
+//! Pixels are compared one by one, then the differing ones are grouped on a grid of
+//! CELL_SIZE squares, with squares up to REGION_GAP apart joining the same region, so a
+//! changed word or line comes out as one region rather than a scatter of glyph parts.
+
+/// Channel difference up to which two pixels count as the same, so antialiasing that
+/// shifts with a glyph's subpixel position doesn't show as a change
+const PIXEL_THRESHOLD: u8 = 32;
+
+/// Side of the squares differing pixels are grouped on, in pixels
+const CELL_SIZE: usize = 8;
+
+/// Squares apart, in either direction, that changes can be and still share a region
+const REGION_GAP: usize = 2;
+
+/// Percentage of its ink unchanged content keeps in the overlay, faded so the changes
+/// stand out
+const FADED_INK: u32 = 30;
+
+/// Least strength a changed pixel is drawn at, out of 255, so a change between two
+/// light colours still shows
+const MIN_CHANGE_INK: u32 = 128;
+
+/// Colour of ink in the new rendering only
+const ADDED_COLOR: [u8; 3] = [0, 90, 230];
+
+/// Colour of ink in the old rendering only
+const REMOVED_COLOR: [u8; 3] = [220, 30, 30];
+
+/// An 8-bit RGB image, row by row from the top left. The default has no pixels, and
+/// compares as white all over.
+#[derive(Debug, Clone, Default)]
+pub struct Raster {
+    pub width: usize,
+    pub height: usize,
+    pub rgb: Vec<u8>,
+}
+
+impl Raster {
+    /// An all-white image
+    pub fn blank(width: usize, height: usize) -> Raster {
+        Raster {
+            width,
+            height,
+            rgb: vec![255; width * height * 3],
+        }
+    }
+
+    /// A binary PPM (P6) image with 8-bit channels, as pdftoppm writes them
+    pub fn from_ppm(bytes: &[u8]) -> Result<Raster, String> {
+        let mut fields = Vec::new();
+        let mut i = 0;
+        while fields.len() < 4 {
+            while i < bytes.len() && bytes[i].is_ascii_whitespace() {
+                i += 1;
+            }
+            if bytes.get(i) == Some(&b'#') {
+                while i < bytes.len() && bytes[i] != b'\n' {
+                    i += 1;
+                }
+                continue;
+            }
+            let start = i;
+            while i < bytes.len() && !bytes[i].is_ascii_whitespace() {
+                i += 1;
+            }
+            if start == i {
+                return Err("Truncated PPM header".to_string());
+            }
+            fields.push(&bytes[start..i]);
+        }
+        // A single whitespace byte ends the header
+        i += 1;
+
+        if fields[0] != b"P6" {
+            return Err("Not a binary PPM image".to_string());
+        }
+        let number = |field: &[u8]| {
+            std::str::from_utf8(field)
+                .ok()
+                .and_then(|text| text.parse::<usize>().ok())
+                .ok_or_else(|| "Invalid PPM header".to_string())
+        };
+        let (width, height, max) = (number(fields[1])?, number(fields[2])?, number(fields[3])?);
+        if max != 255 {
+            return Err(format!("Unsupported PPM channel depth: {}", max));
+        }
+
+        let size = width
+            .checked_mul(height)
+            .and_then(|pixels| pixels.checked_mul(3))
+            .ok_or_else(|| "PPM image too large".to_string())?;
+        let data = bytes
+            .get(i..)
+            .filter(|data| data.len() >= size)
+            .ok_or_else(|| "Truncated PPM image".to_string())?;
+        Ok(Raster {
+            width,
+            height,
+            rgb: data[..size].to_vec(),
+        })
+    }
+
+    /// The pixel at `x`, `y`, white outside the image
+    fn pixel(&self, x: usize, y: usize) -> [u8; 3] {
+        if x >= self.width || y >= self.height {
+            return [255; 3];
+        }
+        let i = (y * self.width + x) * 3;
+        [self.rgb[i], self.rgb[i + 1], self.rgb[i + 2]]
+    }
+
+    fn set_pixel(&mut self, x: usize, y: usize, color: [u8; 3]) {
+        let i = (y * self.width + x) * 3;
+        self.rgb[i..i + 3].copy_from_slice(&color);
+    }
+}
+
+/// A rectangle of an image, in pixels from its top left
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PixelRect {
+    pub x: usize,
+    pub y: usize,
+    pub width: usize,
+    pub height: usize,
+}
+
+#[derive(Debug, Clone)]
+pub struct Comparison {
+    pub changed_pixels: usize,
+    /// Where the renderings differ, from the top of the page down
+    pub regions: Vec<PixelRect>,
+    /// The new rendering faded, with ink only it has drawn in ADDED_COLOR and ink only
+    /// the old one has in REMOVED_COLOR. As large as the larger of the two.
+    pub overlay: Raster,
+}
+
+/// How dark a pixel is, from 0 for white to 255 for black
+fn ink([r, g, b]: [u8; 3]) -> u32 {
+    255 - (u32::from(r) * 299 + u32::from(g) * 587 + u32::from(b) * 114) / 1000
+}
+
+/// `color` at the strength of `ink` over white
+fn tint(color: [u8; 3], ink: u32) -> [u8; 3] {
+    color.map(|channel| (255 - (255 - u32::from(channel)) * ink / 255) as u8)
+}
+
+fn fade(pixel: [u8; 3]) -> [u8; 3] {
+    pixel.map(|channel| (255 - (255 - u32::from(channel)) * FADED_INK / 100) as u8)
+}
+
+/// Group the changed cells of a `columns` by `rows` grid into regions of an image
+/// `width` by `height` pixels
+fn group_cells(
+    cells: &[bool],
+    columns: usize,
+    rows: usize,
+    width: usize,
+    height: usize,
+) -> Vec<PixelRect> {
+    let mut seen = vec![false; cells.len()];
+    let mut regions = Vec::new();
+    for start in 0..cells.len() {
+        if !cells[start] || seen[start] {
+            continue;
+        }
+        seen[start] = true;
+        let mut stack = vec![start];
+        let (mut left, mut top, mut right, mut bottom) = (columns, rows, 0, 0);
+        while let Some(cell) = stack.pop() {
+            let (column, row) = (cell % columns, cell / columns);
+            left = left.min(column);
+            top = top.min(row);
+            right = right.max(column);
+            bottom = bottom.max(row);
+            for near_row in row.saturating_sub(REGION_GAP)..=(row + REGION_GAP).min(rows - 1) {
+                let near_columns =
+                    column.saturating_sub(REGION_GAP)..=(column + REGION_GAP).min(columns - 1);
+                for near_column in near_columns {
+                    let near = near_row * columns + near_column;
+                    if cells[near] && !seen[near] {
+                        seen[near] = true;
+                        stack.push(near);
+                    }
+                }
+            }
+        }
+
+        let (x, y) = (left * CELL_SIZE, top * CELL_SIZE);
+        regions.push(PixelRect {
+            x,
+            y,
+            width: ((right + 1) * CELL_SIZE).min(width) - x,
+            height: ((bottom + 1) * CELL_SIZE).min(height) - y,
+        });
+    }
+    regions
+}
+
+/// Compare two renderings of a page, lined up at their top left. Where one is larger,
+/// the other counts as white beyond its edges.
+pub fn compare(old: &Raster, new: &Raster) -> Comparison {
+    let width = old.width.max(new.width);
+    let height = old.height.max(new.height);
+    let columns = width.div_ceil(CELL_SIZE);
+    let rows = height.div_ceil(CELL_SIZE);
+
+    let mut cells = vec![false; columns * rows];
+    let mut overlay = Raster::blank(width, height);
+    let mut changed_pixels = 0;
+    for y in 0..height {
+        for x in 0..width {
+            let (before, after) = (old.pixel(x, y), new.pixel(x, y));
+            let differs = before
+                .iter()
+                .zip(&after)
+                .any(|(a, b)| a.abs_diff(*b) > PIXEL_THRESHOLD);
+            let color = if differs {
+                changed_pixels += 1;
+                cells[(y / CELL_SIZE) * columns + x / CELL_SIZE] = true;
+                let (ink_before, ink_after) = (ink(before), ink(after));
+                if ink_after >= ink_before {
+                    tint(ADDED_COLOR, ink_after.max(MIN_CHANGE_INK))
+                } else {
+                    tint(REMOVED_COLOR, ink_before.max(MIN_CHANGE_INK))
+                }
+            } else {
+                fade(after)
+            };
+            overlay.set_pixel(x, y, color);
+        }
+    }
+
+    Comparison {
+        changed_pixels,
+        regions: group_cells(&cells, columns, rows, width, height),
+        overlay,
+    }
+}
@@ -0,0 +1,307 @@
+//! Hunspell-style dictionaries: a `.dic` word list whose entries carry affix flags, and
+//! an `.aff` file with the prefix and suffix rules those flags stand for.
+//!
+//! Only what word lookup needs is supported: affix rules with their conditions,
+//! cross-product prefix and suffix combinations, and the NEEDAFFIX and FORBIDDENWORD
+//! flags. Compounding and suggestions are left out.
+
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+#[derive(Clone, Copy, PartialEq)]
+enum FlagFormat {
+    /// One character per flag (the default and `FLAG UTF-8`)
+    Char,
+    /// Two characters per flag (`FLAG long`)
+    Long,
+    /// Comma-separated numbers (`FLAG num`)
+    Num,
+}
+
+fn parse_flags(text: &str, format: FlagFormat) -> Vec<u32> {
+    match format {
+        FlagFormat::Char => text.chars().map(u32::from).collect(),
+        FlagFormat::Long => {
+            let chars: Vec<char> = text.chars().collect();
+            chars
+                .chunks(2)
+                .map(|pair| pair.iter().fold(0, |acc, c| (acc << 16) | u32::from(*c)))
+                .collect()
+        }
+        FlagFormat::Num => text
+            .split(',')
+            .filter_map(|n| n.trim().parse().ok())
+            .collect(),
+    }
+}
+
+/// One position of an affix condition such as `[^aeiou]y`
+enum ConditionAtom {
+    Any,
+    Char(char),
+    Set { chars: Vec<char>, negated: bool },
+}
+
+impl ConditionAtom {
+    fn matches(&self, c: char) -> bool {
+        match self {
+            ConditionAtom::Any => true,
+            ConditionAtom::Char(expected) => c == *expected,
+            ConditionAtom::Set { chars, negated } => chars.contains(&c) != *negated,
+        }
+    }
+}
+
+fn parse_condition(condition: &str) -> Vec<ConditionAtom> {
+    let mut atoms = Vec::new();
+    let mut chars = condition.chars();
+
+    while let Some(c) = chars.next() {
+        atoms.push(match c {
+            '.' => ConditionAtom::Any,
+            '[' => {
+                let mut set: Vec<char> = chars.by_ref().take_while(|c| *c != ']').collect();
+                let negated = set.first() == Some(&'^');
+                if negated {
+                    set.remove(0);
+                }
+                ConditionAtom::Set {
+                    chars: set,
+                    negated,
+                }
+            }
+            c => ConditionAtom::Char(c),
+        });
+    }
+
+    atoms
+}
+
+/// An affix's strip or add field, where `0` stands for nothing
+fn affix_text(field: &str) -> String {
+    if field == "0" {
+        String::new()
+    } else {
+        field.to_string()
+    }
+}
+
+struct Affix {
+    flag: u32,
+    cross_product: bool,
+    /// Removed from the stem before `add` is attached
+    strip: String,
+    add: String,
+    /// Must match the start (prefixes) or end (suffixes) of the stem
+    condition: Vec<ConditionAtom>,
+}
+
+impl Affix {
+    /// The stem a prefixed `word` would come from
+    fn prefix_stem(&self, word: &str) -> Option<String> {
+        let rest = word.strip_prefix(self.add.as_str())?;
+        let stem = format!("{}{}", self.strip, rest);
+        let chars: Vec<char> = stem.chars().collect();
+        let fits = chars.len() >= self.condition.len()
+            && self
+                .condition
+                .iter()
+                .zip(&chars)
+                .all(|(atom, c)| atom.matches(*c));
+        (fits && !stem.is_empty()).then_some(stem)
+    }
+
+    /// The stem a suffixed `word` would come from
+    fn suffix_stem(&self, word: &str) -> Option<String> {
+        let rest = word.strip_suffix(self.add.as_str())?;
+        let stem = format!("{}{}", rest, self.strip);
+        let chars: Vec<char> = stem.chars().collect();
+        let fits = chars.len() >= self.condition.len()
+            && self
+                .condition
+                .iter()
+                .rev()
+                .zip(chars.iter().rev())
+                .all(|(atom, c)| atom.matches(*c));
+        (fits && !stem.is_empty()).then_some(stem)
+    }
+}
+
+pub struct Dictionary {
+    words: HashMap<String, Vec<u32>>,
+    prefixes: Vec<Affix>,
+    suffixes: Vec<Affix>,
+    need_affix: Option<u32>,
+    forbidden: Option<u32>,
+}
+
+/// Decode a dictionary file in the encoding named by the `.aff` SET directive
+fn decode(bytes: &[u8], encoding: &str) -> String {
+    match encoding.to_ascii_uppercase().as_str() {
+        "ISO8859-1" | "ISO-8859-1" | "LATIN1" => bytes.iter().map(|b| char::from(*b)).collect(),
+        _ => String::from_utf8_lossy(bytes).into_owned(),
+    }
+}
+
+/// The SET directive of a raw `.aff` file, which is ASCII even in other encodings
+fn declared_encoding(aff: &[u8]) -> String {
+    String::from_utf8_lossy(aff)
+        .lines()
+        .find_map(|line| {
+            line.trim()
+                .strip_prefix("SET ")
+                .map(|e| e.trim().to_string())
+        })
+        .unwrap_or_else(|| "UTF-8".to_string())
+}
+
+impl Dictionary {
+    /// Load `<dir>/<language>.aff` and `<dir>/<language>.dic`
+    pub fn load(dir: &Path, language: &str) -> Result<Self, String> {
+        let aff = fs::read(dir.join(format!("{}.aff", language)))
+            .map_err(|e| format!("Failed to read {}.aff: {}", language, e))?;
+        let dic = fs::read(dir.join(format!("{}.dic", language)))
+            .map_err(|e| format!("Failed to read {}.dic: {}", language, e))?;
+
+        let encoding = declared_encoding(&aff);
+        Ok(Self::parse(
+            &decode(&aff, &encoding),
+            &decode(&dic, &encoding),
+        ))
+    }
+
+    fn parse(aff: &str, dic: &str) -> Self {
+        let mut format = FlagFormat::Char;
+        let mut prefixes = Vec::new();
+        let mut suffixes = Vec::new();
+        let mut need_affix = None;
+        let mut forbidden = None;
+        // Cross-product setting of each affix flag, from its header line
+        let mut cross_products: HashMap<(bool, u32), bool> = HashMap::new();
+
+        for line in aff.lines() {
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            match fields.as_slice() {
+                ["FLAG", "long", ..] => format = FlagFormat::Long,
+                ["FLAG", "num", ..] => format = FlagFormat::Num,
+                ["NEEDAFFIX", flag, ..] => need_affix = parse_flags(flag, format).first().copied(),
+                ["FORBIDDENWORD", flag, ..] => {
+                    forbidden = parse_flags(flag, format).first().copied()
+                }
+                [kind @ ("PFX" | "SFX"), flag, cross, count] if count.parse::<usize>().is_ok() => {
+                    if let Some(flag) = parse_flags(flag, format).first() {
+                        cross_products.insert((*kind == "PFX", *flag), *cross == "Y");
+                    }
+                }
+                [kind @ ("PFX" | "SFX"), flag, strip, add, condition, ..] => {
+                    let Some(&flag) = parse_flags(flag, format).first() else {
+                        continue;
+                    };
+                    let is_prefix = *kind == "PFX";
+                    // Continuation flags after a slash only matter for two-level affixes
+                    let add = add.split('/').next().unwrap_or_default();
+                    let affix = Affix {
+                        flag,
+                        cross_product: cross_products
+                            .get(&(is_prefix, flag))
+                            .copied()
+                            .unwrap_or(false),
+                        strip: affix_text(strip),
+                        add: affix_text(add),
+                        condition: parse_condition(condition),
+                    };
+                    if is_prefix {
+                        prefixes.push(affix);
+                    } else {
+                        suffixes.push(affix);
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        let mut words: HashMap<String, Vec<u32>> = HashMap::new();
+        // The first line is the (approximate) entry count
+        for line in dic.lines().skip(1) {
+            let entry = line.split(['\t', ' ']).next().unwrap_or_default();
+            if entry.is_empty() {
+                continue;
+            }
+            let (word, flags) = match entry.split_once('/') {
+                Some((word, flags)) => (word, parse_flags(flags, format)),
+                None => (entry, Vec::new()),
+            };
+            words.entry(word.to_string()).or_default().extend(flags);
+        }
+
+        Self {
+            words,
+            prefixes,
+            suffixes,
+            need_affix,
+            forbidden,
+        }
+    }
+
+    fn has_flag(&self, stem: &str, flag: u32) -> bool {
+        self.words.get(stem).is_some_and(|flags| {
+            flags.contains(&flag) && !self.forbidden.is_some_and(|f| flags.contains(&f))
+        })
+    }
+
+    /// A word of the list on its own, or derived from one by its affixes
+    fn known(&self, word: &str) -> bool {
+        if let Some(flags) = self.words.get(word) {
+            let excluded = [self.need_affix, self.forbidden]
+                .iter()
+                .flatten()
+                .any(|f| flags.contains(f));
+            if !excluded {
+                return true;
+            }
+        }
+
+        let suffixed = |word: &str, prefix: Option<&Affix>| {
+            self.suffixes.iter().any(|suffix| {
+                suffix.suffix_stem(word).is_some_and(|stem| match prefix {
+                    Some(prefix) => {
+                        suffix.cross_product
+                            && self.has_flag(&stem, suffix.flag)
+                            && self.has_flag(&stem, prefix.flag)
+                    }
+                    None => self.has_flag(&stem, suffix.flag),
+                })
+            })
+        };
+
+        suffixed(word, None)
+            || self.prefixes.iter().any(|prefix| {
+                prefix.prefix_stem(word).is_some_and(|stem| {
+                    self.has_flag(&stem, prefix.flag)
+                        || (prefix.cross_product && suffixed(&stem, Some(prefix)))
+                })
+            })
+    }
+
+    /// Whether `word` is spelled correctly, allowing the capitalized and upper-case
+    /// forms of lower-case entries
+    pub fn check(&self, word: &str) -> bool {
+        if self.known(word) {
+            return true;
+        }
+
+        let lower = word.to_lowercase();
+        if lower != word && self.known(&lower) {
+            return true;
+        }
+
+        // An all-caps word may be a capitalized entry, e.g. PARIS for Paris
+        let mut chars = lower.chars();
+        let capitalized: String = match chars.next() {
+            Some(first) => first.to_uppercase().chain(chars).collect(),
+            None => return false,
+        };
+        capitalized != word && self.known(&capitalized)
+    }
+}
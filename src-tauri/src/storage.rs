@@ -0,0 +1,55 @@
+//! Crash-safe handling of the JSON files the app keeps its state in.
+
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+/// Replace `path` with `content` so that readers see either the old or the new file,
+/// never a partial write: the content goes to a temporary file next to it, which is
+/// then renamed over the original
+pub fn write_atomic(path: &Path, content: impl AsRef<[u8]>) -> std::io::Result<()> {
+    // Replace what a symlink points to rather than the link itself
+    let resolved = fs::canonicalize(path).ok();
+    let path = resolved.as_deref().unwrap_or(path);
+
+    let file_name = path
+        .file_name()
+        .map(|n| n.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let temp_path = path.with_file_name(format!(".{}.{}.tmp", file_name, std::process::id()));
+
+    let result = (|| {
+        let mut file = fs::File::create(&temp_path)?;
+        file.write_all(content.as_ref())?;
+        file.sync_all()?;
+        // The replacement would otherwise get default permissions
+        if let Ok(existing) = fs::metadata(path) {
+            fs::set_permissions(&temp_path, existing.permissions())?;
+        }
+        fs::rename(&temp_path, path)
+    })();
+
+    if result.is_err() {
+        let _ = fs::remove_file(&temp_path);
+    }
+    result
+}
+
+/// Move an unreadable file out of the way so defaults can take its place, keeping it
+/// next to the original for recovery. Returns where it was moved.
+pub fn set_aside_corrupt(path: &Path) -> Option<PathBuf> {
+    let file_name = path.file_name()?.to_string_lossy().into_owned();
+    let backup = path.with_file_name(format!(
+        "{}.corrupt-{}",
+        file_name,
+        chrono::Utc::now().format("%Y%m%dT%H%M%S")
+    ));
+
+    match fs::rename(path, &backup) {
+        Ok(()) => Some(backup),
+        Err(e) => {
+            eprintln!("Failed to back up corrupt {}: {}", path.display(), e);
+            None
+        }
+    }
+}
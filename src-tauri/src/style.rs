@@ -0,0 +1,232 @@
+//! House and journal style rules, checked against the comment-masked source of each
+//! file in a document.
+//!
+//! A rule either matches a regular expression, optionally outside some environments
+//! such as tables where the construct is expected, or requires every instance of an
+//! environment to contain a command, e.g. a figure its \caption. A few rule sets are
+//! built in; projects add their own in .incipit. Verbatim environments are never
+//! checked, as their content isn't LaTeX.
+
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+
+use crate::latex;
+
+/// Names of the built-in rule sets, as rule_set takes them
+pub const RULE_SETS: &[&str] = &["general", "submission"];
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum RuleCheck {
+    /// Every match of a regular expression, except inside the listed environments
+    Pattern {
+        pattern: String,
+        #[serde(default)]
+        outside: Vec<String>,
+    },
+    /// Each `environment`, starred or not, must contain `command`, given without its
+    /// backslash
+    Require {
+        environment: String,
+        command: String,
+    },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StyleRule {
+    /// Short name reported with each violation, e.g. `absolute-vspace`
+    pub id: String,
+    /// What to change, shown to the author
+    pub message: String,
+    #[serde(flatten)]
+    pub check: RuleCheck,
+}
+
+fn pattern(id: &str, message: &str, pattern: &str, outside: &[&str]) -> StyleRule {
+    StyleRule {
+        id: id.to_string(),
+        message: message.to_string(),
+        check: RuleCheck::Pattern {
+            pattern: pattern.to_string(),
+            outside: outside.iter().map(|name| name.to_string()).collect(),
+        },
+    }
+}
+
+fn require(id: &str, message: &str, environment: &str, command: &str) -> StyleRule {
+    StyleRule {
+        id: id.to_string(),
+        message: message.to_string(),
+        check: RuleCheck::Require {
+            environment: environment.to_string(),
+            command: command.to_string(),
+        },
+    }
+}
+
+/// The rules of a built-in rule set. `submission` is `general` plus what journals ask
+/// to be gone from a final manuscript.
+pub fn rule_set(name: &str) -> Option<Vec<StyleRule>> {
+    let mut rules = vec![
+        pattern(
+            "manual-line-break",
+            "Avoid \\\\ in running text; leave line breaking to TeX or start a new paragraph",
+            r"\\\\",
+            &[
+                latex::ALIGNMENT_ENVIRONMENTS,
+                latex::LINE_BREAK_ENVIRONMENTS,
+            ]
+            .concat(),
+        ),
+        pattern(
+            "absolute-vspace",
+            "Avoid absolute \\vspace; spacing is up to the journal's class",
+            r"\\vspace\*?\s*\{\s*-?[0-9.]+\s*(pt|bp|mm|cm|in|ex|em)\s*\}",
+            &[],
+        ),
+        pattern(
+            "display-dollars",
+            "Use \\[ ... \\] or an equation environment instead of $$ ... $$",
+            r"(?s)\$\$.*?\$\$",
+            &[],
+        ),
+        pattern(
+            "eqnarray",
+            "Use align instead of eqnarray, whose spacing is inconsistent",
+            r"\\begin\s*\{eqnarray\*?\}",
+            &[],
+        ),
+        require(
+            "figure-caption",
+            "Every figure needs a \\caption",
+            "figure",
+            "caption",
+        ),
+        require(
+            "table-caption",
+            "Every table needs a \\caption",
+            "table",
+            "caption",
+        ),
+    ];
+    match name {
+        "general" => {}
+        "submission" => rules.extend([
+            pattern(
+                "todo-note",
+                "Resolve review notes before submitting",
+                r"\\(todo|missingfigure)\b",
+                &[],
+            ),
+            pattern(
+                "colored-text",
+                "Remove colored text; journals print in black",
+                r"\\(textcolor|color)\s*\{",
+                &[],
+            ),
+            pattern(
+                "absolute-hspace",
+                "Avoid absolute \\hspace; spacing is up to the journal's class",
+                r"\\hspace\*?\s*\{\s*-?[0-9.]+\s*(pt|bp|mm|cm|in|ex|em)\s*\}",
+                &[],
+            ),
+            pattern(
+                "manual-page-break",
+                "Remove manual page breaks; the journal sets the pages",
+                r"\\(newpage|clearpage|pagebreak)\b",
+                &[],
+            ),
+        ]),
+        _ => return None,
+    }
+    Some(rules)
+}
+
+/// A rule ready to check, with its pattern compiled
+pub struct CompiledRule<'a> {
+    pub rule: &'a StyleRule,
+    regex: Option<Regex>,
+}
+
+/// Compile every rule's pattern, failing on the first that isn't a valid regex
+pub fn compile(rules: &[StyleRule]) -> Result<Vec<CompiledRule<'_>>, String> {
+    rules
+        .iter()
+        .map(|rule| {
+            let regex = match &rule.check {
+                RuleCheck::Pattern { pattern, .. } => Some(
+                    Regex::new(pattern)
+                        .map_err(|e| format!("Invalid pattern in style rule {}: {}", rule.id, e))?,
+                ),
+                RuleCheck::Require { .. } => None,
+            };
+            Ok(CompiledRule { rule, regex })
+        })
+        .collect()
+}
+
+/// A place `masked` breaks a rule
+pub struct Violation<'a> {
+    pub rule: &'a StyleRule,
+    pub offset: usize,
+    /// What matched, or the environment's \begin for a missing command
+    pub text: String,
+}
+
+/// Every violation of `rules` in one comment-masked file, in source order
+pub fn check<'a>(masked: &str, rules: &[CompiledRule<'a>]) -> Vec<Violation<'a>> {
+    let environments = latex::environments(masked);
+    let inside = |offset: usize, names: &[String]| {
+        environments
+            .iter()
+            .any(|env| env.span.contains(&offset) && names.contains(&env.name))
+    };
+    let verbatim: Vec<String> = latex::VERBATIM_ENVIRONMENTS
+        .iter()
+        .map(|name| name.to_string())
+        .collect();
+
+    let mut violations = Vec::new();
+    for compiled in rules {
+        match (&compiled.rule.check, &compiled.regex) {
+            (RuleCheck::Pattern { outside, .. }, Some(regex)) => {
+                for found in regex.find_iter(masked) {
+                    if inside(found.start(), &verbatim) || inside(found.start(), outside) {
+                        continue;
+                    }
+                    violations.push(Violation {
+                        rule: compiled.rule,
+                        offset: found.start(),
+                        text: found.as_str().to_string(),
+                    });
+                }
+            }
+            (
+                RuleCheck::Require {
+                    environment,
+                    command,
+                },
+                _,
+            ) => {
+                let named = |env: &&latex::Environment| {
+                    env.name == *environment
+                        || env.name.strip_suffix('*') == Some(environment.as_str())
+                };
+                for env in environments.iter().filter(named) {
+                    let body = &masked[env.span.clone()];
+                    if latex::commands(body).any(|c| c.name == command) {
+                        continue;
+                    }
+                    violations.push(Violation {
+                        rule: compiled.rule,
+                        offset: env.span.start,
+                        text: format!("\\begin{{{}}}", env.name),
+                    });
+                }
+            }
+            (RuleCheck::Pattern { .. }, None) => {}
+        }
+    }
+    violations.sort_by_key(|violation| violation.offset);
+    violations
+}
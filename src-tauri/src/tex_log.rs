@@ -0,0 +1,427 @@
+//! Parsing of the .log file TeX writes next to the build output.
+
+use serde::{Deserialize, Serialize};
+
+/// TeX wraps log lines at this many characters
+const LOG_LINE_WIDTH: usize = 79;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub message: String,
+    /// Source file that was being read when the message was written
+    pub file: Option<String>,
+    pub line: Option<usize>,
+}
+
+/// A `File `foo.sty' not found` failure, the most common beginner error
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MissingPackage {
+    /// Package (or class) name without extension, e.g. `foo`
+    pub name: String,
+    /// File TeX was looking for, e.g. `foo.sty`
+    pub file_name: String,
+    /// Source file that was being read when the error occurred
+    pub referenced_in: Option<String>,
+    /// Line in `referenced_in` reported by TeX (`l.N`)
+    pub line: Option<usize>,
+}
+
+/// Extract the name between TeX's `` `...' `` quotes
+fn quoted_name(text: &str) -> Option<&str> {
+    let start = text.find('`')? + 1;
+    let end = start + text[start..].find('\'')?;
+    Some(&text[start..end])
+}
+
+/// Parse the `l.N` context line TeX prints after an error
+fn context_line(line: &str) -> Option<usize> {
+    let rest = line.strip_prefix("l.")?;
+    let digits: String = rest.chars().take_while(|c| c.is_ascii_digit()).collect();
+    digits.parse().ok()
+}
+
+/// Parse the number in an `on input line N.` suffix
+fn input_line(message: &str) -> Option<usize> {
+    let (_, rest) = message.rsplit_once("input line ")?;
+    let digits: String = rest.chars().take_while(|c| c.is_ascii_digit()).collect();
+    digits.parse().ok()
+}
+
+/// Lines of quoted text TeX prints after "Runaway argument?" at most, before the error
+const RUNAWAY_EXCERPT_LINES: usize = 4;
+
+/// Characters of a runaway argument quoted in the friendlier message
+const RUNAWAY_EXCERPT_CHARS: usize = 40;
+
+/// Reword the errors TeX gives when an argument never ends, usually because of a missing
+/// `}`: "Paragraph ended before \x was complete", or "File ended" and "Forbidden
+/// control sequence found" while scanning it. `excerpt` is the start of the argument
+/// TeX printed under the `Runaway argument?` line before the error.
+fn runaway_message(error: &str, excerpt: Option<&str>) -> Option<String> {
+    let command = |text: &str| text.trim_end_matches('.').trim().to_string();
+
+    let explanation = if let Some(rest) = error.strip_prefix("Paragraph ended before ") {
+        let name = command(rest.strip_suffix(" was complete.")?);
+        format!(
+            "The argument of {} runs into a blank line or \\par, which it can't contain",
+            name
+        )
+    } else {
+        let (cause, scanned) = error.split_once(" while scanning ")?;
+        let (what, name) = scanned.split_once(" of ")?;
+        let what = match what {
+            "use" => "the argument",
+            "definition" => "the definition",
+            "preamble" => "the table preamble",
+            _ => "the text",
+        };
+        match cause {
+            "File ended" => format!("The file ended inside {} of {}", what, command(name)),
+            "Forbidden control sequence found" => format!(
+                "A command that can't appear in {} of {} was found inside it",
+                what,
+                command(name)
+            ),
+            _ => return None,
+        }
+    };
+
+    let mut message = format!("{}: a }} is probably missing", explanation);
+    if let Some(excerpt) = excerpt {
+        let excerpt = excerpt.trim().trim_end_matches("\\par").trim_end();
+        let mut quoted: String = excerpt.chars().take(RUNAWAY_EXCERPT_CHARS).collect();
+        if quoted.len() < excerpt.len() {
+            quoted.push_str("...");
+        }
+        message.push_str(&format!(" in the text starting \"{}\"", quoted));
+    }
+    message.push_str(&format!(" (TeX: {})", error));
+    Some(message)
+}
+
+/// Characters of the source before a math-mode error quoted in the friendlier message
+const MATH_EXCERPT_CHARS: usize = 40;
+
+/// The source text TeX had read on an `l.N` context line when it stopped, which ends
+/// with the token it stopped at
+fn read_text(line: &str) -> Option<&str> {
+    context_line(line)?;
+    let rest = line["l.".len()..].trim_start_matches(|c: char| c.is_ascii_digit());
+    let rest = rest.strip_prefix(' ').unwrap_or(rest);
+    // A long line is shown from "..." near the error
+    Some(rest.strip_prefix("...").unwrap_or(rest))
+}
+
+/// The token TeX put back to read again before inserting a `$`, shown on the line
+/// under `<to be read again>`; it is the one that needs math mode or can't be in it
+fn backed_up_token<'a>(context: &[&'a str]) -> Option<&'a str> {
+    let i = context
+        .iter()
+        .position(|line| line.starts_with("<to be read again>"))?;
+    let token = context.get(i + 1)?.trim();
+    (!token.is_empty()).then_some(token)
+}
+
+/// Whether a formula is still open at the end of `text`, judging by the unescaped `$`
+/// signs and the `\(` and `\)` it has
+fn ends_in_math(text: &str) -> bool {
+    let bytes = text.as_bytes();
+    let mut open = false;
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'\\' => {
+                match bytes.get(i + 1) {
+                    Some(b'(') => open = true,
+                    Some(b')') => open = false,
+                    _ => {}
+                }
+                i += 1;
+            }
+            b'$' => open = !open,
+            _ => {}
+        }
+        i += 1;
+    }
+    open
+}
+
+/// Reword the errors TeX gives when text and math mode get mixed up: "Missing $
+/// inserted", usually a `_`, `^` or math command outside a formula or a formula left
+/// open, "Display math should end with $$" and "Extra }, or forgotten $". `context` is
+/// the log after the error, from which the token TeX stopped at and the source it had
+/// read on the `l.N` line tell which of the causes it is.
+fn math_mode_message(error: &str, context: &[&str]) -> Option<String> {
+    let end = context
+        .iter()
+        .take(12)
+        .position(|line| context_line(line).is_some())?;
+    let read = read_text(context[end]).unwrap_or("").trim_end();
+    let token = backed_up_token(&context[..end]);
+    // The formula the token is in, if one was opened on this line before it
+    let before_token = token.and_then(|t| read.strip_suffix(t)).unwrap_or(read);
+    let in_math = ends_in_math(before_token);
+
+    let explanation = match error {
+        "Missing $ inserted." => match token {
+            Some("_") if !in_math => "A _ is used outside a formula, and only formulas have \
+                                      subscripts: put it in $...$, as in $x_1$, or write \\_ \
+                                      for an underscore"
+                .to_string(),
+            Some("^") if !in_math => "A ^ is used outside a formula, and only formulas have \
+                                      superscripts: put it in $...$, as in $x^2$, or write \
+                                      \\textasciicircum{} for a caret"
+                .to_string(),
+            Some("\\par") => "A formula runs into a blank line or \\par, which it can't \
+                              contain: a closing $ is probably missing"
+                .to_string(),
+            Some(command) if command.starts_with('\\') && !in_math => format!(
+                "{} only works in a formula: put it in $...$, as in ${}$",
+                command, command
+            ),
+            Some(command) if command.starts_with('\\') => format!(
+                "{} can't be used in a formula, and the one before it isn't closed: a \
+                 closing $ is probably missing",
+                command
+            ),
+            _ if in_math => {
+                "A formula on this line isn't closed: a closing $ is probably missing".to_string()
+            }
+            _ => "Something on this line only works in a formula: put it in $...$".to_string(),
+        },
+        "Display math should end with $$." => "A display formula opened with $$ is closed \
+                                               with a single $: end it with $$, or better \
+                                               write it as \\[...\\]"
+            .to_string(),
+        "Extra }, or forgotten $." => "A } closes a group opened before the formula it is \
+                                       in: a closing $ is probably missing before it, or the \
+                                       } is one too many"
+            .to_string(),
+        _ => return None,
+    };
+
+    let mut message = explanation;
+    if !read.is_empty() {
+        let skip = read.chars().count().saturating_sub(MATH_EXCERPT_CHARS);
+        let quoted: String = read.chars().skip(skip).collect();
+        let ellipsis = if skip > 0 { "..." } else { "" };
+        message.push_str(&format!(", at \"{}{}\"", ellipsis, quoted));
+    }
+    message.push_str(&format!(" (TeX: {})", error));
+    Some(message)
+}
+
+/// Whether `line` starts a LaTeX, package or class warning
+fn is_warning(line: &str) -> bool {
+    line.starts_with("LaTeX Warning: ")
+        || line.starts_with("LaTeX Font Warning: ")
+        || ((line.starts_with("Package ") || line.starts_with("Class "))
+            && line.contains(" Warning: "))
+}
+
+/// Join `lines[start]` with the lines TeX wrapped it onto, plus the `(name)`-prefixed
+/// continuation lines packages use for long warnings. Returns the message and the
+/// index of the first line not consumed.
+fn join_message(lines: &[&str], start: usize) -> (String, usize) {
+    let first = lines[start];
+    let mut message = first.trim_end().to_string();
+    let mut wrapped = first.chars().count() == LOG_LINE_WIDTH;
+
+    // "Package foo Warning: ..." continues on lines starting with "(foo)"
+    let continuation = first
+        .split_whitespace()
+        .nth(1)
+        .filter(|_| first.starts_with("Package ") || first.starts_with("Class "))
+        .map(|name| format!("({})", name));
+
+    let mut i = start + 1;
+    while let Some(line) = lines.get(i) {
+        if wrapped {
+            message.push_str(line.trim_end());
+        } else if let Some(rest) = continuation
+            .as_ref()
+            .and_then(|c| line.strip_prefix(c.as_str()))
+        {
+            message.push(' ');
+            message.push_str(rest.trim());
+        } else {
+            break;
+        }
+        wrapped = line.chars().count() == LOG_LINE_WIDTH;
+        i += 1;
+    }
+
+    (message, i)
+}
+
+/// The .tex file TeX was reading at the start of each of `lines`.
+///
+/// TeX writes `(name` when it opens a file and `)` when it closes it, so a stack of
+/// open parentheses tells which file an error belongs to, even after an \input-ed
+/// child has been closed again. Parentheses that don't open a file are tracked too,
+/// to keep the stack balanced.
+fn current_sources(lines: &[&str]) -> Vec<Option<String>> {
+    let mut stack: Vec<Option<String>> = Vec::new();
+    let mut current = Vec::with_capacity(lines.len());
+    // A line wrapped at LOG_LINE_WIDTH may split a file name; scan it with the next
+    let mut pending = String::new();
+    let mut skip_context = false;
+
+    for line in lines {
+        current.push(
+            stack
+                .iter()
+                .rev()
+                .flatten()
+                .find(|name| name.ends_with(".tex"))
+                .cloned(),
+        );
+
+        // The l.N context quotes the source, whose parentheses mean nothing here
+        if skip_context {
+            skip_context = false;
+            continue;
+        }
+        if context_line(line).is_some() {
+            skip_context = true;
+            continue;
+        }
+
+        pending.push_str(line);
+        if line.chars().count() == LOG_LINE_WIDTH {
+            continue;
+        }
+
+        for (i, c) in pending.char_indices() {
+            match c {
+                '(' => {
+                    let name: String = pending[i + 1..]
+                        .chars()
+                        .take_while(|c| !c.is_whitespace() && *c != '(' && *c != ')')
+                        .collect();
+                    let is_file = name.contains('.') || name.contains('/');
+                    stack.push(is_file.then(|| name.trim_start_matches("./").to_string()));
+                }
+                ')' => {
+                    stack.pop();
+                }
+                _ => {}
+            }
+        }
+        pending.clear();
+    }
+
+    current
+}
+
+/// Whether the log holds an error parse_log would report, found without parsing the
+/// warnings around it
+pub fn has_errors(log: &str) -> bool {
+    log.split('\n').any(|line| line.starts_with("! "))
+}
+
+/// Extract every error (`! ...`) and LaTeX/package/class warning from a TeX log
+pub fn parse_log(log: &str) -> Vec<Diagnostic> {
+    let lines: Vec<&str> = log.split('\n').collect();
+    let sources = current_sources(&lines);
+
+    let mut diagnostics = Vec::new();
+    // Text TeX quoted after "Runaway argument?", for the error that follows it
+    let mut runaway: Option<String> = None;
+    let mut i = 0;
+
+    while i < lines.len() {
+        let line = lines[i];
+        let file = || sources[i].clone();
+
+        if line.starts_with("Runaway ") && line.trim_end().ends_with('?') {
+            // The quoted text may be wrapped over several lines, up to the error itself
+            let mut excerpt = String::new();
+            let end = (i + 1 + RUNAWAY_EXCERPT_LINES).min(lines.len());
+            i += 1;
+            while let Some(next) = lines[..end].get(i).filter(|l| !l.starts_with("! ")) {
+                excerpt.push_str(next.trim_end());
+                i += 1;
+            }
+            runaway = Some(excerpt);
+        } else if line.starts_with("! ") {
+            let (message, _) = join_message(&lines, i);
+            let message = message["! ".len()..].to_string();
+            let excerpt = runaway.take();
+            let message = runaway_message(&message, excerpt.as_deref())
+                .or_else(|| math_mode_message(&message, &lines[i + 1..]))
+                .unwrap_or(message);
+            let line_number = lines[i + 1..].iter().take(12).find_map(|l| context_line(l));
+
+            diagnostics.push(Diagnostic {
+                severity: Severity::Error,
+                message,
+                file: file(),
+                line: line_number,
+            });
+            i += 1;
+        } else if is_warning(line) {
+            let (message, next) = join_message(&lines, i);
+
+            diagnostics.push(Diagnostic {
+                severity: Severity::Warning,
+                line: input_line(&message),
+                message,
+                file: file(),
+            });
+            i = next;
+        } else {
+            i += 1;
+        }
+    }
+
+    diagnostics
+}
+
+/// Find every missing .sty/.cls error in a TeX log
+pub fn missing_packages(log: &str) -> Vec<MissingPackage> {
+    let lines: Vec<&str> = log.split('\n').collect();
+    let sources = current_sources(&lines);
+    let mut missing = Vec::new();
+
+    for (i, line) in lines.iter().enumerate() {
+        let Some(message) = line.strip_prefix("! LaTeX Error: File ") else {
+            continue;
+        };
+        if !message.contains("not found") {
+            continue;
+        }
+        let Some(file_name) = quoted_name(message) else {
+            continue;
+        };
+        if !(file_name.ends_with(".sty") || file_name.ends_with(".cls")) {
+            continue;
+        }
+
+        // TeX prompts for a replacement name, then reports where it stopped
+        let line_number = lines[i + 1..].iter().take(12).find_map(|l| context_line(l));
+
+        let name = file_name
+            .rsplit_once('.')
+            .map(|(stem, _)| stem)
+            .unwrap_or(file_name)
+            .to_string();
+
+        missing.push(MissingPackage {
+            name,
+            file_name: file_name.to_string(),
+            referenced_in: sources[i].clone(),
+            line: line_number,
+        });
+    }
+
+    missing
+}
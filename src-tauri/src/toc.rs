@@ -0,0 +1,116 @@
+//! Parsing of the .toc file LaTeX writes for \tableofcontents, which has every heading
+//! with the page it ended up on.
+//!
+//! Each heading is a `\contentsline {section}{\numberline {1.2}Title}{3}{section.1.2}`,
+//! the last argument only with hyperref. TeX writes them as the headings themselves are
+//! read, so their titles keep the markup, with \protect in front of fragile commands and
+//! non-ASCII letters possibly wrapped in inputenc's \IeC. Anything else in the file,
+//! such as babel's language switches or \addvspace, is skipped.
+
+use serde::{Deserialize, Serialize};
+use unicode_normalization::UnicodeNormalization;
+
+use crate::latex;
+use crate::prose;
+
+/// Commands classes use for a heading's number inside its title argument
+const NUMBER_COMMANDS: &[&str] = &[
+    "numberline",
+    "chapternumberline",
+    "partnumberline",
+    "tocnumberline",
+];
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TocEntry {
+    /// The kind of heading, e.g. `section`
+    pub level: String,
+    /// How deeply it nests, from 0 for \part and 1 for \chapter down to 6 for
+    /// \subparagraph; None for a kind of entry a class or package adds
+    pub depth: Option<usize>,
+    /// As typeset, e.g. `2.1` or `A`; None for an unnumbered heading
+    pub number: Option<String>,
+    /// The title as plain text
+    pub title: String,
+    /// As typeset, e.g. `12` or `iv`
+    pub page: String,
+    /// The hyperref destination, e.g. `section.2.1`, when the document loads hyperref
+    pub destination: Option<String>,
+}
+
+/// The heading number in a title argument, and the title without it
+fn split_number(title: &str) -> (Option<String>, String) {
+    let found = latex::commands(title).find(|c| NUMBER_COMMANDS.contains(&c.name));
+    let Some(command) = found else {
+        return (None, title.to_string());
+    };
+    let args = latex::read_args(title, command.end, 1);
+    let Some(number) = latex::first_required(&args) else {
+        return (None, title.to_string());
+    };
+    let end = latex::skip_args(title, command.end, 1);
+    let rest = format!("{}{}", &title[..command.start], &title[end..]);
+    let number = number.text.trim();
+    (Some(number.to_string()).filter(|n| !n.is_empty()), rest)
+}
+
+/// Plain text of a title as the .toc has it. Inline math is kept as its source, e.g.
+/// `$O(n^2)$`, as there is no plain text for it.
+fn plain_title(title: &str) -> String {
+    // TeX writes a control word with a space after it, e.g. `\IeC {\'e}` for é
+    let title = title.replace("\\protect ", "").replace("\\IeC ", "");
+    let bytes = title.as_bytes();
+    let mut text = String::new();
+    let mut start = 0;
+    let mut in_math = false;
+    for i in 0..=bytes.len() {
+        let closes_part = i == bytes.len() || (bytes[i] == b'$' && !latex::is_escaped(bytes, i));
+        if !closes_part {
+            continue;
+        }
+        if in_math {
+            text.push_str(&title[start..(i + 1).min(bytes.len())]);
+        } else {
+            text.push_str(&prose::file_prose(&title[start..i]));
+        }
+        start = if in_math { i + 1 } else { i };
+        in_math = !in_math;
+    }
+    let text: String = text.nfc().collect();
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// Every \contentsline of a .toc file, in the order of the document
+pub fn parse(toc: &str) -> Vec<TocEntry> {
+    let mut entries = Vec::new();
+    for command in latex::commands(toc).filter(|c| c.name == "contentsline") {
+        let args = latex::read_args(toc, command.end, 4);
+        let mut required = args
+            .iter()
+            .filter(|arg| arg.kind == latex::ArgKind::Required)
+            .map(|arg| arg.text.as_str());
+        let (Some(level), Some(title), Some(page)) =
+            (required.next(), required.next(), required.next())
+        else {
+            continue;
+        };
+        let destination = required
+            .next()
+            .map(str::trim)
+            .filter(|destination| !destination.is_empty());
+
+        let level = level.trim();
+        let (number, title) = split_number(title);
+        entries.push(TocEntry {
+            level: level.to_string(),
+            depth: latex::SECTIONING_COMMANDS
+                .iter()
+                .position(|command| *command == level),
+            number,
+            title: plain_title(&title),
+            page: page.trim().to_string(),
+            destination: destination.map(str::to_string),
+        });
+    }
+    entries
+}
@@ -1,10 +1,10 @@
 import { useState, useEffect } from "react";
 import { Panel, PanelGroup, PanelResizeHandle } from "react-resizable-panels";
 import { invoke } from "@tauri-apps/api/core";
-import LatexEditor from "./components/LatexEditor";
+import LatexEditor, { CompileResult } from "./components/LatexEditor";
 import PdfViewer from "./components/PdfViewer";
 import FileTree, { FileNode } from "./components/FileTree";
-import WelcomeScreen from "./components/WelcomeScreen";
+import WelcomeScreen, { RecentProject } from "./components/WelcomeScreen";
 import Sidebar from "./components/Sidebar";
 import { ArrowLeft } from "lucide-react";
 import "./App.css";
@@ -19,10 +19,15 @@ type SidebarView =
   | null;
 
 interface GlobalSettings {
-  recent_projects: string[];
+  recent_projects: RecentProject[];
   editor_settings: Record<string, unknown>;
 }
 
+interface FileError {
+  kind: "permission_denied" | "not_found" | "changed_on_disk" | "failed";
+  message: string;
+}
+
 interface ProjectMeta {
   last_opened_file: string | null;
   root_file: string;
@@ -42,7 +47,7 @@ function App() {
   const [compilationError, setCompilationError] = useState<string | null>(null);
 
   // Global settings
-  const [recentProjects, setRecentProjects] = useState<string[]>([]);
+  const [recentProjects, setRecentProjects] = useState<RecentProject[]>([]);
 
   // Sidebar state
   const [activeSidebarView, setActiveSidebarView] = useState<SidebarView>(null);
@@ -109,12 +114,16 @@ function App() {
       } else {
         console.log("No PDF found, auto-compiling...");
         // Auto-compile
-        const pdfBytes = await invoke<number[]>("compile_latex_project", {
+        const result = await invoke<CompileResult>("compile_latex_project", {
           projectPath: projPath,
           filePath: filePath,
           source: latexContent,
         });
-        const pdf = new Uint8Array(pdfBytes);
+        if (!result.succeeded) {
+          console.log("Auto-compile produced no PDF:", result.diagnostics);
+          return;
+        }
+        const pdf = new Uint8Array(result.outputs.pdf);
         setPdfData(pdf);
         setCompilationError(null);
       }
@@ -126,15 +135,10 @@ function App() {
 
   const updateRecentProjects = async (path: string) => {
     try {
-      const settings = await invoke<GlobalSettings>("load_global_settings");
-      const updatedRecent = [
-        path,
-        ...settings.recent_projects.filter((p) => p !== path),
-      ].slice(0, 10); // Keep last 10
-
-      await invoke("save_global_settings", {
-        settings: { ...settings, recent_projects: updatedRecent },
-      });
+      const updatedRecent = await invoke<RecentProject[]>(
+        "add_recent_project",
+        { path },
+      );
       setRecentProjects(updatedRecent);
     } catch (error) {
       console.error("Failed to update recent projects:", error);
@@ -155,7 +159,8 @@ function App() {
       setCompilationError(null);
     } catch (error) {
       console.error("Failed to load file:", error);
-      alert(`Failed to load file: ${error}`);
+      const fileError = error as FileError;
+      alert(fileError.message ?? `Failed to load file: ${error}`);
     }
   };
 
@@ -203,7 +208,8 @@ function App() {
       console.log("File saved successfully");
     } catch (error) {
       console.error("Failed to save file:", error);
-      alert(`Failed to save file: ${error}`);
+      const fileError = error as FileError;
+      alert(fileError.message ?? `Failed to save file: ${error}`);
     }
   };
 
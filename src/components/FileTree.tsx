@@ -5,6 +5,7 @@ export interface FileNode {
   path: string;
   is_dir: boolean;
   children?: FileNode[];
+  error?: string;
 }
 
 interface FileTreeProps {
@@ -59,7 +60,12 @@ const FileTreeNode: React.FC<FileTreeNodeProps> = ({
             📄
           </span>
         )}
-        <span className="overflow-hidden text-ellipsis">{node.name}</span>
+        <span
+          className={`overflow-hidden text-ellipsis ${node.error ? "text-gray-400 italic" : ""}`}
+          title={node.error}
+        >
+          {node.name}
+        </span>
       </div>
       {node.is_dir && isExpanded && node.children && (
         <div>
@@ -16,6 +16,29 @@ import { oneDark } from "@codemirror/theme-one-dark";
 import { latex } from "codemirror-lang-latex";
 import { invoke } from "@tauri-apps/api/core";
 
+export interface Diagnostic {
+  severity: "error" | "warning";
+  message: string;
+  file: string | null;
+  line: number | null;
+}
+
+export interface CompileResult {
+  outputs: Record<string, number[]>;
+  diagnostics: Diagnostic[];
+  error_count: number;
+  warning_count: number;
+  passes: number;
+  succeeded: boolean;
+}
+
+export interface CompileError {
+  kind: "failed" | "bundle_unavailable";
+  message: string;
+  log: string;
+  diagnostics: Diagnostic[];
+}
+
 interface LatexEditorProps {
   initialContent: string;
   onChange: (content: string) => void;
@@ -128,18 +151,33 @@ const LatexEditor: React.FC<LatexEditorProps> = ({
     setIsCompiling(true);
     try {
       // Use project-based compilation
-      const pdfBytes = await invoke<number[]>("compile_latex_project", {
+      const result = await invoke<CompileResult>("compile_latex_project", {
         projectPath,
         filePath,
         source: currentContent,
       });
 
+      if (!result.succeeded) {
+        const firstError = result.diagnostics.find(
+          (d) => d.severity === "error",
+        );
+        onError(firstError?.message ?? "Compilation produced no PDF");
+        return;
+      }
+
       // Convert number array to Uint8Array
-      const pdfData = new Uint8Array(pdfBytes);
+      const pdfData = new Uint8Array(result.outputs.pdf);
       onCompile(pdfData);
     } catch (error) {
       console.error("LaTeX compilation error:", error);
-      onError(String(error));
+      const compileError = error as CompileError;
+      if (compileError.kind === "bundle_unavailable") {
+        onError(
+          "You appear to be offline. Connect to the internet so the LaTeX packages can be downloaded, then compile again.",
+        );
+      } else {
+        onError(compileError.message ?? String(error));
+      }
     } finally {
       setIsCompiling(false);
     }
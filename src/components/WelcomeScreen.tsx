@@ -3,9 +3,15 @@ import { invoke } from "@tauri-apps/api/core";
 import { FileNode } from "./FileTree";
 import { Plus, FolderOpen, File } from "lucide-react";
 
+export interface RecentProject {
+  path: string;
+  pinned: boolean;
+  display_name: string | null;
+}
+
 interface WelcomeScreenProps {
   onProjectOpened: (projectPath: string, fileTree: FileNode) => void;
-  recentProjects: string[];
+  recentProjects: RecentProject[];
 }
 
 const WelcomeScreen: React.FC<WelcomeScreenProps> = ({
@@ -99,7 +105,7 @@ const WelcomeScreen: React.FC<WelcomeScreenProps> = ({
               Recent Projects
             </h2>
             <div className="flex flex-col gap-2">
-              {recentProjects.slice(0, 5).map((path) => (
+              {recentProjects.slice(0, 5).map(({ path, display_name }) => (
                 <button
                   key={path}
                   className="flex items-center gap-3 px-4 py-2.5 bg-white border border-gray-200 text-gray-900 text-left cursor-pointer transition-colors hover:bg-gray-50 hover:border-gray-300 text-sm dark:bg-gray-800 dark:border-gray-700 dark:text-gray-300 dark:hover:bg-gray-750 dark:hover:border-gray-600"
@@ -108,7 +114,7 @@ const WelcomeScreen: React.FC<WelcomeScreenProps> = ({
                 >
                   <File size={16} className="flex-shrink-0" />
                   <span className="overflow-hidden text-ellipsis whitespace-nowrap">
-                    {path}
+                    {display_name ?? path}
                   </span>
                 </button>
               ))}